@@ -3,143 +3,619 @@
 #![warn(missing_docs)]
 #![feature(associated_type_defaults)]
 
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+use nuzon_core::{
+    clock::{Clock, SkewError, SkewPolicy, SystemClock},
+    coordination::{key_id, RevocationList},
+    crypto::{EntropySource, KyberKem},
+};
 use pqcrypto::{
-    kyber::{kyber1024, KyberKeypair},
     dilithium::dilithium5,
-    sign::DetachedSignature
+    falcon::falcon1024,
+    traits::sign::{DetachedSignature as _, PublicKey as _, SecretKey as _},
 };
 use ring::{
     agreement,
+    digest,
     rand::SystemRandom,
-    signature::EcdsaKeyPair,
+    signature::{self, EcdsaKeyPair},
 };
-use serde::{Serialize, Deserialize};
-use tokio::net::TcpStream;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncWrite};
 use zeroize::Zeroize;
 
+pub mod signer;
+pub mod transport;
+
+pub use signer::{HsmSigner, Signer};
+use transport::{FrameError, LengthDelimitedCodec};
+
 const HYBRID_MODE: bool = true; // Enable classical+quantum hybrid
+const MAX_MESSAGE_SIZE: usize = 256 * 1024;
+/// Handshake protocol version bound into the key schedule
+const PROTOCOL_VERSION: u8 = 2;
+const DEFAULT_CONTEXT_LABEL: &[u8] = b"default";
+/// Longest a single handshake message may take to send or arrive
+const DEFAULT_STAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Post-quantum half of the hybrid identity signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PqSignatureScheme {
+    /// CRYSTALS-Dilithium level 5
+    Dilithium5,
+    /// Falcon-1024, smaller signatures for constrained links
+    Falcon1024,
+}
+
+impl PqSignatureScheme {
+    /// Wire identifier bound into every signed transcript
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Dilithium5 => 0x01,
+            Self::Falcon1024 => 0x02,
+        }
+    }
+
+    /// Generate a `(public, secret)` keypair for this scheme
+    pub fn keypair(&self) -> (Vec<u8>, Vec<u8>) {
+        match self {
+            Self::Dilithium5 => {
+                let (pk, sk) = dilithium5::keypair();
+                (pk.as_bytes().to_vec(), sk.as_bytes().to_vec())
+            }
+            Self::Falcon1024 => {
+                let (pk, sk) = falcon1024::keypair();
+                (pk.as_bytes().to_vec(), sk.as_bytes().to_vec())
+            }
+        }
+    }
+
+    fn sign(&self, msg: &[u8], sk: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let invalid = || HandshakeError::CryptoError("Invalid PQ secret key".into());
+        Ok(match self {
+            Self::Dilithium5 => {
+                let sk = dilithium5::SecretKey::from_bytes(sk).map_err(|_| invalid())?;
+                dilithium5::detached_sign(msg, &sk).as_bytes().to_vec()
+            }
+            Self::Falcon1024 => {
+                let sk = falcon1024::SecretKey::from_bytes(sk).map_err(|_| invalid())?;
+                falcon1024::detached_sign(msg, &sk).as_bytes().to_vec()
+            }
+        })
+    }
+
+    fn verify(&self, msg: &[u8], sig: &[u8], pk: &[u8]) -> Result<(), HandshakeError> {
+        let verified = match self {
+            Self::Dilithium5 => {
+                match (dilithium5::PublicKey::from_bytes(pk), dilithium5::DetachedSignature::from_bytes(sig)) {
+                    (Ok(pk), Ok(sig)) => dilithium5::verify_detached_signature(&sig, msg, &pk).is_ok(),
+                    _ => false,
+                }
+            }
+            Self::Falcon1024 => {
+                match (falcon1024::PublicKey::from_bytes(pk), falcon1024::DetachedSignature::from_bytes(sig)) {
+                    (Ok(pk), Ok(sig)) => falcon1024::verify_detached_signature(&sig, msg, &pk).is_ok(),
+                    _ => false,
+                }
+            }
+        };
+
+        if verified {
+            Ok(())
+        } else {
+            Err(HandshakeError::VerificationFailed)
+        }
+    }
+}
+
+/// Algorithms proposed by the initiator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CipherSuite {
+    pub signature: PqSignatureScheme,
+}
+
+impl Default for CipherSuite {
+    fn default() -> Self {
+        Self { signature: PqSignatureScheme::Dilithium5 }
+    }
+}
+
+/// Long-term hybrid signing keys of the local endpoint
+pub struct IdentityKeys {
+    classical: Arc<dyn Signer>,
+    pq_keys: HashMap<PqSignatureScheme, (Vec<u8>, Vec<u8>)>,
+}
+
+impl IdentityKeys {
+    pub fn new(ecdsa: EcdsaKeyPair) -> Self {
+        Self::with_signer(Arc::new(ecdsa))
+    }
+
+    /// Identity whose classical signatures come from `classical`, e.g. an
+    /// `HsmSigner` keeping the private key non-extractable
+    pub fn with_signer(classical: Arc<dyn Signer>) -> Self {
+        Self { classical, pq_keys: HashMap::new() }
+    }
+
+    /// Attach a `(public, secret)` PQ keypair for a scheme
+    pub fn with_pq_key(mut self, scheme: PqSignatureScheme, pk: Vec<u8>, sk: Vec<u8>) -> Self {
+        self.pq_keys.insert(scheme, (pk, sk));
+        self
+    }
+
+    /// Verification keys to distribute to peers
+    pub fn public(&self) -> PeerIdentity {
+        PeerIdentity {
+            ecdsa_pk: self.classical.public_key(),
+            pq_keys: self.pq_keys.iter()
+                .map(|(scheme, (pk, _))| (*scheme, pk.clone()))
+                .collect(),
+        }
+    }
+
+    fn pq_secret(&self, scheme: PqSignatureScheme) -> Result<&[u8], HandshakeError> {
+        self.pq_keys.get(&scheme)
+            .map(|(_, sk)| sk.as_slice())
+            .ok_or(HandshakeError::UnsupportedScheme(scheme))
+    }
+}
+
+impl Drop for IdentityKeys {
+    fn drop(&mut self) {
+        for (_, sk) in self.pq_keys.values_mut() {
+            sk.zeroize();
+        }
+    }
+}
+
+/// Verification keys of the remote endpoint (from its certificate chain)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerIdentity {
+    pub ecdsa_pk: Vec<u8>,
+    pub pq_keys: HashMap<PqSignatureScheme, Vec<u8>>,
+}
+
+impl PeerIdentity {
+    /// Identifier the peer's certificate key is revoked under
+    pub fn key_id(&self) -> String {
+        key_id(&self.ecdsa_pk)
+    }
+
+    /// Identifiers of every key the peer signs with: the certificate key,
+    /// then each PQ key in scheme order
+    pub fn key_ids(&self) -> Vec<String> {
+        let mut pq_keys: Vec<_> = self.pq_keys.iter().collect();
+        pq_keys.sort_by_key(|(scheme, _)| format!("{:?}", scheme));
+        std::iter::once(self.key_id())
+            .chain(pq_keys.into_iter().map(|(_, key)| key_id(key)))
+            .collect()
+    }
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeInit {
+    signature_scheme: PqSignatureScheme,
+    /// Initiator's milliseconds since the Unix epoch, checked against the
+    /// responder's `SkewPolicy`
+    timestamp: u128,
     kyber_pk: Vec<u8>,
     ecdh_pk: Vec<u8>,
-    dilithium_sig: Vec<u8>,
+    hybrid_sig: Vec<u8>,
     cert_chain: Vec<Vec<u8>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeResponse {
+    signature_scheme: PqSignatureScheme,
     kyber_ciphertext: Vec<u8>,
     ecdh_pk: Vec<u8>,
     ephemeral_sig: Vec<u8>,
 }
 
+impl HandshakeInit {
+    /// Bytes covered by the initiator's signature, including the scheme id
+    /// and timestamp
+    fn signed_bytes(&self) -> Vec<u8> {
+        [
+            &[self.signature_scheme.id()][..],
+            &self.timestamp.to_be_bytes(),
+            &self.kyber_pk,
+            &self.ecdh_pk,
+        ].concat()
+    }
+}
+
+impl HandshakeResponse {
+    /// Bytes covered by the responder's signature, bound to the init
+    fn signed_bytes(&self, init: &HandshakeInit) -> Vec<u8> {
+        [
+            &init.signed_bytes()[..],
+            &[self.signature_scheme.id()],
+            &self.kyber_ciphertext,
+            &self.ecdh_pk,
+        ].concat()
+    }
+}
+
+/// Result of a completed handshake
+pub struct HandshakeSession {
+    pub session_key: [u8; 64],
+    /// SHA-384 over the init and response as sent on the wire; identical on
+    /// both peers and usable as a channel-binding value
+    pub transcript_hash: [u8; 48],
+}
+
+impl Drop for HandshakeSession {
+    fn drop(&mut self) {
+        self.session_key.zeroize();
+    }
+}
+
 pub struct PQHandshake {
-    kyber_kp: KyberKeypair,
-    ecdh_priv: agreement::EphemeralPrivateKey,
-    identity_key: EcdsaKeyPair,
+    kyber_pk: Vec<u8>,
+    kyber_sk: Vec<u8>,
+    ecdh_priv: Option<agreement::EphemeralPrivateKey>,
+    ecdh_pk: Vec<u8>,
+    identity: IdentityKeys,
+    suite: CipherSuite,
+    accepted_signatures: Vec<PqSignatureScheme>,
+    context_label: Vec<u8>,
+    stage_timeout: Duration,
+    clock: Arc<dyn Clock>,
+    skew: SkewPolicy,
+    revocations: Option<Arc<RevocationList>>,
     rng: SystemRandom,
+    entropy: Box<dyn EntropySource>,
 }
 
 impl PQHandshake {
-    pub async fn new() -> Result<Self, HandshakeError> {
+    /// Create a handshake signing its classical half with `classical`
+    pub async fn new(classical: Arc<dyn Signer>) -> Result<Self, HandshakeError> {
+        // Load the PQ identity keys to pair with the classical signer
+        Self::with_identity(load_identity_key(classical)?, CipherSuite::default())
+    }
+
+    /// Create a handshake for an explicit identity and proposed cipher suite
+    pub fn with_identity(identity: IdentityKeys, suite: CipherSuite) -> Result<Self, HandshakeError> {
+        Self::with_entropy(identity, suite, Box::new(rand_core::OsRng))
+    }
+
+    /// Create a handshake whose Kyber keygen and encapsulation draw from `entropy`
+    ///
+    /// ECDH randomness still comes from ring's `SystemRandom`, which does
+    /// not accept an external generator, and ECDSA randomness from the
+    /// identity's `Signer`.
+    pub fn with_entropy(
+        identity: IdentityKeys,
+        suite: CipherSuite,
+        mut entropy: Box<dyn EntropySource>,
+    ) -> Result<Self, HandshakeError> {
         let rng = SystemRandom::new();
-        
+
         // Generate post-quantum Kyber1024 keypair
-        let (kyber_pk, kyber_sk) = kyber1024::keypair();
-        
+        let (kyber_pk, kyber_sk) = KyberKem::keypair_with(&mut *entropy);
+
         // Generate classical ECDH P-256 key
         let ecdh_priv = agreement::EphemeralPrivateKey::generate(
-            &agreement::ECDH_P256, 
+            &agreement::ECDH_P256,
             &rng
         )?;
-        
-        // Load identity key (Dilithium5 + ECDSA hybrid)
-        let identity_key = load_identity_key()?;
+        let ecdh_pk = ecdh_priv.compute_public_key()?.as_ref().to_vec();
 
         Ok(Self {
-            kyber_kp: KyberKeypair { pk: kyber_pk, sk: kyber_sk },
-            ecdh_priv,
-            identity_key,
+            kyber_pk,
+            kyber_sk,
+            ecdh_priv: Some(ecdh_priv),
+            ecdh_pk,
+            identity,
+            suite,
+            accepted_signatures: vec![PqSignatureScheme::Dilithium5, PqSignatureScheme::Falcon1024],
+            context_label: DEFAULT_CONTEXT_LABEL.to_vec(),
+            stage_timeout: DEFAULT_STAGE_TIMEOUT,
+            clock: Arc::new(SystemClock),
+            skew: SkewPolicy::default(),
+            revocations: None,
             rng,
+            entropy,
         })
     }
 
-    pub async fn client_handshake(
-        &mut self, 
-        stream: &mut TcpStream
-    ) -> Result<[u8; 64], HandshakeError> {
+    /// Restrict which PQ signature schemes a responder will accept
+    pub fn accept_signatures(mut self, schemes: Vec<PqSignatureScheme>) -> Self {
+        self.accepted_signatures = schemes;
+        self
+    }
+
+    /// Domain-separate derived keys for one application context
+    ///
+    /// Both endpoints must use the same label; a mismatch yields different
+    /// session keys rather than a handshake error.
+    pub fn with_context_label(mut self, label: impl Into<Vec<u8>>) -> Self {
+        self.context_label = label.into();
+        self
+    }
+
+    /// Bound each message exchange; a peer that stalls longer fails the
+    /// handshake with `HandshakeError::Timeout`
+    pub fn with_stage_timeout(mut self, timeout: Duration) -> Self {
+        self.stage_timeout = timeout;
+        self
+    }
+
+    /// Replace the wall clock used to timestamp and check inits
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Refuse inits whose timestamp disagrees with the local clock by more
+    /// than `skew`, with `HandshakeError::ClockSkew`
+    pub fn with_skew_policy(mut self, skew: SkewPolicy) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Refuse peers presenting any revoked key, classical or PQ, with
+    /// `HandshakeError::KeyRevoked`
+    pub fn with_revocations(mut self, revocations: Arc<RevocationList>) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// Run the initiator side over `stream`
+    ///
+    /// Holds no resources beyond the borrowed stream, so the future may be
+    /// dropped at any point. A timed-out or cancelled handshake can leave a
+    /// partial frame on the stream, which must then be closed, not reused.
+    pub async fn client_handshake<S>(
+        &mut self,
+        stream: &mut S,
+        peer: &PeerIdentity,
+    ) -> Result<HandshakeSession, HandshakeError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.check_revocation(peer)?;
+
         // Send initiation
-        let init = self.create_handshake_init()?;
-        send_message(stream, &init).await?;
+        let init = self.create_handshake_init().await?;
+        let init_bytes = within(self.stage_timeout, send_message(stream, &init)).await?;
 
         // Receive response
-        let resp: HandshakeResponse = recv_message(stream).await?;
-        
-        // Process quantum-safe exchange
-        let kyber_ss = kyber1024::decapsulate(
-            &resp.kyber_ciphertext, 
-            &self.kyber_kp.sk
-        );
-        
+        let (resp, resp_bytes): (HandshakeResponse, _) =
+            within(self.stage_timeout, recv_message(stream)).await?;
+
+        // The responder may not substitute a different scheme
+        if resp.signature_scheme != init.signature_scheme {
+            return Err(HandshakeError::VerificationFailed);
+        }
+        verify_hybrid_signature(resp.signature_scheme, &resp.ephemeral_sig, &resp.signed_bytes(&init), peer)?;
+
+        // Process quantum-safe exchange; a malformed ciphertext decapsulates
+        // to an unrelated secret instead of failing here, so it surfaces only
+        // as a session key the peer does not share
+        let kyber_ss = KyberKem::decaps(&resp.kyber_ciphertext, &self.kyber_sk)
+            .map_err(|_| HandshakeError::CryptoError("Invalid Kyber secret key".into()))?;
+
         // Process classical ECDH
-        let peer_pk = agreement::UnparsedPublicKey::new(
-            &agreement::ECDH_P256, 
-            &resp.ecdh_pk
-        );
-        let ecdh_ss = agreement::agree_ephemeral(
-            self.ecdh_priv, 
-            &peer_pk, 
-            |ss| Ok(ss.to_vec())
-        )?;
+        let ecdh_ss = self.agree(&resp.ecdh_pk)?;
 
         // Combine secrets
-        let mut final_ss = [0u8; 64];
-        hkdf_sha384(&kyber_ss, &ecdh_ss, &mut final_ss);
-        
-        // Verify ephemeral signature
-        verify_hybrid_signature(&resp.ephemeral_sig, &final_ss)?;
+        Ok(HandshakeSession {
+            session_key: derive_session_key(&kyber_ss, &ecdh_ss, resp.signature_scheme, &self.context_label),
+            transcript_hash: transcript_hash(&init_bytes, &resp_bytes),
+        })
+    }
 
-        Ok(final_ss)
+    /// Run the responder side over `stream`; cancellation behaves as for
+    /// `client_handshake`
+    pub async fn server_handshake<S>(
+        &mut self,
+        stream: &mut S,
+        peer: &PeerIdentity,
+    ) -> Result<HandshakeSession, HandshakeError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (init, init_bytes): (HandshakeInit, _) = within(self.stage_timeout, recv_message(stream)).await?;
+        self.verify_init(&init, peer)?;
+
+        // Encapsulate to the initiator's Kyber key
+        let (kyber_ct, kyber_ss) = KyberKem::encaps_with(&init.kyber_pk, &mut *self.entropy)
+            .map_err(|_| HandshakeError::CryptoError("Invalid Kyber public key".into()))?;
+        let ecdh_ss = self.agree(&init.ecdh_pk)?;
+
+        let mut resp = HandshakeResponse {
+            signature_scheme: init.signature_scheme,
+            kyber_ciphertext: kyber_ct,
+            ecdh_pk: self.ecdh_pk.clone(),
+            ephemeral_sig: Vec::new(),
+        };
+        resp.ephemeral_sig = sign_hybrid(&self.identity, resp.signature_scheme, &resp.signed_bytes(&init)).await?;
+        let resp_bytes = within(self.stage_timeout, send_message(stream, &resp)).await?;
+
+        Ok(HandshakeSession {
+            session_key: derive_session_key(&kyber_ss, &ecdh_ss, resp.signature_scheme, &self.context_label),
+            transcript_hash: transcript_hash(&init_bytes, &resp_bytes),
+        })
     }
 
-    fn create_handshake_init(&self) -> Result<HandshakeInit, HandshakeError> {
-        // Create quantum-safe signature
-        let msg = [self.kyber_kp.pk.as_ref(), self.ecdh_priv.public_key()?].concat();
-        let sig = sign_hybrid(&self.identity_key, &msg)?;
+    /// Check the peer's key is not revoked, the proposed scheme is
+    /// acceptable, the init signature is valid and its timestamp is within
+    /// tolerance
+    fn verify_init(&self, init: &HandshakeInit, peer: &PeerIdentity) -> Result<(), HandshakeError> {
+        self.check_revocation(peer)?;
+        if !self.accepted_signatures.contains(&init.signature_scheme) {
+            return Err(HandshakeError::UnsupportedScheme(init.signature_scheme));
+        }
+        verify_hybrid_signature(init.signature_scheme, &init.hybrid_sig, &init.signed_bytes(), peer)?;
+        self.skew.check_millis(init.timestamp, self.clock.now_millis())
+            .map_err(HandshakeError::ClockSkew)
+    }
 
-        Ok(HandshakeInit {
-            kyber_pk: self.kyber_kp.pk.to_vec(),
-            ecdh_pk: self.ecdh_priv.public_key()?.as_ref().to_vec(),
-            dilithium_sig: sig,
+    fn check_revocation(&self, peer: &PeerIdentity) -> Result<(), HandshakeError> {
+        match self.revocations.as_ref().and_then(|revocations| revocations.first_revoked(peer.key_ids())) {
+            Some(key_id) => Err(HandshakeError::KeyRevoked(key_id)),
+            None => Ok(()),
+        }
+    }
+
+    async fn create_handshake_init(&self) -> Result<HandshakeInit, HandshakeError> {
+        let mut init = HandshakeInit {
+            signature_scheme: self.suite.signature,
+            timestamp: self.clock.now_millis(),
+            kyber_pk: self.kyber_pk.clone(),
+            ecdh_pk: self.ecdh_pk.clone(),
+            hybrid_sig: Vec::new(),
             cert_chain: load_cert_chain(),
-        })
+        };
+
+        // Create quantum-safe signature over the scheme and key shares
+        init.hybrid_sig = sign_hybrid(&self.identity, init.signature_scheme, &init.signed_bytes()).await?;
+        Ok(init)
+    }
+
+    /// Complete ECDH; the ephemeral key is single-use
+    fn agree(&mut self, peer_pk: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let ecdh_priv = self.ecdh_priv.take()
+            .ok_or_else(|| HandshakeError::CryptoError("Ephemeral key already used".into()))?;
+        let peer_pk = agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, peer_pk);
+
+        agreement::agree_ephemeral(ecdh_priv, &peer_pk, |ss| ss.to_vec())
+            .map_err(|_| HandshakeError::CryptoError("ECDH agreement failed".into()))
     }
 }
 
-// Hybrid signing (Dilithium5 + ECDSA)
-fn sign_hybrid(key: &EcdsaKeyPair, msg: &[u8]) -> Result<Vec<u8>, HandshakeError> {
-    let classical_sig = key.sign(&SystemRandom::new(), msg)?;
-    let quantum_sig = dilithium5::detached_sign(msg, key.private_key());
-    Ok([classical_sig.as_ref(), &quantum_sig].concat())
+// Hybrid signing (PQ + ECDSA), encoded as len(classical) || classical || pq
+async fn sign_hybrid(
+    identity: &IdentityKeys,
+    scheme: PqSignatureScheme,
+    msg: &[u8],
+) -> Result<Vec<u8>, HandshakeError> {
+    let classical_sig = identity.classical.sign(msg).await?;
+    let quantum_sig = scheme.sign(msg, identity.pq_secret(scheme)?)?;
+
+    let classical_len = u16::try_from(classical_sig.len())
+        .map_err(|_| HandshakeError::SerializationError)?;
+    Ok([&classical_len.to_be_bytes()[..], &classical_sig, &quantum_sig].concat())
+}
+
+// Both halves must verify; either failing rejects the signature. Both are
+// always checked, and a malformed encoding or missing peer key is checked as
+// empty, so every rejection is the same `VerificationFailed` after the same
+// work.
+fn verify_hybrid_signature(
+    scheme: PqSignatureScheme,
+    sig: &[u8],
+    msg: &[u8],
+    peer: &PeerIdentity,
+) -> Result<(), HandshakeError> {
+    let (classical_len, body) = match sig.split_first_chunk::<2>() {
+        Some((len, body)) => (u16::from_be_bytes(*len) as usize, body),
+        None => (0, &[][..]),
+    };
+    let well_formed = sig.len() >= 2 && classical_len <= body.len();
+    let (classical_sig, quantum_sig) = body.split_at(classical_len.min(body.len()));
+
+    let classical_ok = signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, &peer.ecdsa_pk)
+        .verify(msg, classical_sig)
+        .is_ok();
+    let pq_pk = peer.pq_keys.get(&scheme);
+    let quantum_ok = scheme.verify(msg, quantum_sig, pq_pk.map_or(&[][..], Vec::as_slice)).is_ok();
+
+    if well_formed & pq_pk.is_some() & classical_ok & quantum_ok {
+        Ok(())
+    } else {
+        Err(HandshakeError::VerificationFailed)
+    }
+}
+
+// Session key bound to the negotiated suite, protocol version and context
+fn derive_session_key(
+    kyber_ss: &[u8],
+    ecdh_ss: &[u8],
+    scheme: PqSignatureScheme,
+    context_label: &[u8],
+) -> [u8; 64] {
+    let mut okm = [0u8; 64];
+    hkdf_sha384(kyber_ss, ecdh_ss, &key_schedule_info(scheme, context_label), &mut okm);
+    okm
+}
+
+// "nuzon_hybrid" || version || scheme id || len(label) || label
+fn key_schedule_info(scheme: PqSignatureScheme, context_label: &[u8]) -> Vec<u8> {
+    let label_len = (context_label.len() as u32).to_be_bytes();
+    [
+        &b"nuzon_hybrid"[..],
+        &[PROTOCOL_VERSION, scheme.id()],
+        &label_len,
+        context_label,
+    ].concat()
+}
+
+// SHA-384 over len(init) || init || len(response) || response
+fn transcript_hash(init: &[u8], response: &[u8]) -> [u8; 48] {
+    let mut ctx = digest::Context::new(&digest::SHA384);
+    for message in [init, response] {
+        ctx.update(&(message.len() as u32).to_be_bytes());
+        ctx.update(message);
+    }
+    let mut hash = [0u8; 48];
+    hash.copy_from_slice(ctx.finish().as_ref());
+    hash
 }
 
 // HKDF with SHA-384
-fn hkdf_sha384(ikm1: &[u8], ikm2: &[u8], okm: &mut [u8]) {
+fn hkdf_sha384(ikm1: &[u8], ikm2: &[u8], info: &[u8], okm: &mut [u8]) {
     use ring::hkdf;
     let salt = hkdf::Salt::new(hkdf::HKDF_SHA384, &[]);
     let prk = salt.extract([ikm1, ikm2].concat().as_ref());
-    prk.expand(&[b"nuzon_hybrid"], hkdf::HKDF_SHA384)
+    prk.expand(&[info], hkdf::HKDF_SHA384)
        .unwrap()
        .fill(okm)
        .unwrap();
 }
 
+/// Fail a stage that does not finish within `timeout`
+async fn within<T>(
+    timeout: Duration,
+    stage: impl Future<Output = Result<T, HandshakeError>>,
+) -> Result<T, HandshakeError> {
+    tokio::time::timeout(timeout, stage)
+        .await
+        .unwrap_or(Err(HandshakeError::Timeout))
+}
+
+/// Send a message, returning its encoding for the transcript
+async fn send_message<S, T>(stream: &mut S, msg: &T) -> Result<Vec<u8>, HandshakeError>
+where
+    S: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let bytes = serde_json::to_vec(msg).map_err(|_| HandshakeError::SerializationError)?;
+    let mut codec = LengthDelimitedCodec::new(MAX_MESSAGE_SIZE);
+    transport::write_frame(stream, &mut codec, bytes.clone().into()).await?;
+    Ok(bytes)
+}
+
+/// Receive a message along with the bytes it was decoded from
+async fn recv_message<S, T>(stream: &mut S) -> Result<(T, Vec<u8>), HandshakeError>
+where
+    S: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let codec = LengthDelimitedCodec::new(MAX_MESSAGE_SIZE);
+    let bytes = transport::read_frame(stream, &codec).await?.to_vec();
+    let msg = serde_json::from_slice(&bytes).map_err(|_| HandshakeError::SerializationError)?;
+    Ok((msg, bytes))
+}
+
 // Zeroize sensitive data
 impl Drop for PQHandshake {
     fn drop(&mut self) {
-        self.kyber_kp.sk.zeroize();
-        self.ecdh_priv.zeroize();
+        self.kyber_sk.zeroize();
+        // ring zeroizes the ephemeral key on drop
+        self.ecdh_priv.take();
     }
 }
 
@@ -148,7 +624,296 @@ pub enum HandshakeError {
     CryptoError(String),
     IoError(std::io::Error),
     SerializationError,
+    /// The peer did not complete a handshake stage in time
+    Timeout,
+    UnsupportedScheme(PqSignatureScheme),
+    VerificationFailed,
+    /// The init's timestamp is outside the responder's skew tolerance
+    ClockSkew(SkewError),
+    /// One of the peer's keys, by `key_id`, is on the revocation list
+    KeyRevoked(String),
     // Additional variants omitted
 }
 
-// Implementation of error conversions omitted
+impl From<FrameError> for HandshakeError {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Io(e) => HandshakeError::IoError(e),
+            FrameError::TooLarge { .. } => HandshakeError::SerializationError,
+        }
+    }
+}
+
+// Remaining error conversions omitted
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> IdentityKeys {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let ecdsa = EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        with_pq_keys(IdentityKeys::new(ecdsa))
+    }
+
+    fn with_pq_keys(mut keys: IdentityKeys) -> IdentityKeys {
+        for scheme in [PqSignatureScheme::Dilithium5, PqSignatureScheme::Falcon1024] {
+            let (pk, sk) = scheme.keypair();
+            keys = keys.with_pq_key(scheme, pk, sk);
+        }
+        keys
+    }
+
+    async fn run_handshake(scheme: PqSignatureScheme) -> (HandshakeSession, HandshakeSession) {
+        run_handshake_between(identity(), identity(), scheme).await
+    }
+
+    async fn run_handshake_between(
+        client_id: IdentityKeys,
+        server_id: IdentityKeys,
+        scheme: PqSignatureScheme,
+    ) -> (HandshakeSession, HandshakeSession) {
+        let (client_pub, server_pub) = (client_id.public(), server_id.public());
+
+        let mut client = PQHandshake::with_identity(client_id, CipherSuite { signature: scheme }).unwrap();
+        let mut server = PQHandshake::with_identity(server_id, CipherSuite::default()).unwrap();
+        let (mut client_io, mut server_io) = tokio::io::duplex(MAX_MESSAGE_SIZE);
+
+        let (client_ss, server_ss) = tokio::join!(
+            client.client_handshake(&mut client_io, &server_pub),
+            server.server_handshake(&mut server_io, &client_pub),
+        );
+        (client_ss.unwrap(), server_ss.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_under_each_scheme() {
+        for scheme in [PqSignatureScheme::Dilithium5, PqSignatureScheme::Falcon1024] {
+            let (client_ss, server_ss) = run_handshake(scheme).await;
+            assert_eq!(client_ss.session_key, server_ss.session_key, "secret mismatch under {:?}", scheme);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcript_hash_binds_both_peers() {
+        let (client, server) = run_handshake(PqSignatureScheme::Dilithium5).await;
+        assert_eq!(client.transcript_hash, server.transcript_hash);
+
+        // Fresh ephemeral keys give every session its own binding value
+        let (other, _) = run_handshake(PqSignatureScheme::Dilithium5).await;
+        assert_ne!(client.transcript_hash, other.transcript_hash);
+
+        let (init, response) = (b"init-bytes".to_vec(), b"response-bytes".to_vec());
+        let reference = transcript_hash(&init, &response);
+        for i in 0..init.len() {
+            let mut altered = init.clone();
+            altered[i] ^= 0x01;
+            assert_ne!(transcript_hash(&altered, &response), reference);
+        }
+        for i in 0..response.len() {
+            let mut altered = response.clone();
+            altered[i] ^= 0x01;
+            assert_ne!(transcript_hash(&init, &altered), reference);
+        }
+        // Moving the message boundary changes the hash too
+        assert_ne!(transcript_hash(b"init-bytesr", b"esponse-bytes"), reference);
+    }
+
+    #[test]
+    fn test_context_label_separates_session_keys() {
+        let (kyber_ss, ecdh_ss) = ([7u8; 32], [9u8; 32]);
+        let scheme = PqSignatureScheme::Dilithium5;
+
+        let billing = derive_session_key(&kyber_ss, &ecdh_ss, scheme, b"billing");
+        let telemetry = derive_session_key(&kyber_ss, &ecdh_ss, scheme, b"telemetry");
+        assert_ne!(billing, telemetry);
+        assert_eq!(billing, derive_session_key(&kyber_ss, &ecdh_ss, scheme, b"billing"));
+
+        // The negotiated scheme is part of the domain too
+        assert_ne!(billing, derive_session_key(&kyber_ss, &ecdh_ss, PqSignatureScheme::Falcon1024, b"billing"));
+    }
+
+    #[tokio::test]
+    async fn test_classical_half_signed_by_softhsm() {
+        let config = hsm_integration::HsmConfig::new(
+            "/usr/lib/softhsm/libsofthsm2.so",
+            "handshake-identity",
+            vec![(0, "1234".to_string())],
+        ).allow_tenant("handshake", b"handshake-secret");
+        let client = Arc::new(hsm_integration::HsmClient::new(config).await.unwrap());
+        let principal = client.authenticate("handshake", b"handshake-secret").unwrap();
+        // Tokens persist between runs; a fresh label avoids pairing stale keys
+        let label = format!("identity-{}", std::time::UNIX_EPOCH.elapsed().unwrap().as_nanos());
+        client.generate_ec_key_pair(&principal, &label).await.unwrap();
+        let signer = Arc::new(HsmSigner::new(client, principal, label).await.unwrap());
+
+        let client_id = with_pq_keys(IdentityKeys::with_signer(signer.clone()));
+        assert_eq!(client_id.public().ecdsa_pk, signer.public_key());
+
+        let (client_ss, server_ss) = run_handshake_between(client_id, identity(), PqSignatureScheme::Dilithium5).await;
+        assert_eq!(client_ss.session_key, server_ss.session_key);
+        assert_eq!(client_ss.transcript_hash, server_ss.transcript_hash);
+    }
+
+    #[tokio::test]
+    async fn test_silent_peer_times_out() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let window = Duration::from_millis(100);
+
+        // Initiator starts a frame and never finishes it
+        let client_pub = identity().public();
+        let mut server = PQHandshake::with_identity(identity(), CipherSuite::default())
+            .unwrap()
+            .with_stage_timeout(window);
+        let (mut peer, mut server_io) = tokio::io::duplex(MAX_MESSAGE_SIZE);
+        peer.write_all(&[0, 0, 0x10, 0x00, b'{']).await.unwrap();
+
+        let started = std::time::Instant::now();
+        let result = server.server_handshake(&mut server_io, &client_pub).await;
+        assert!(matches!(result, Err(HandshakeError::Timeout)));
+        assert!(started.elapsed() < window * 5, "took {:?}", started.elapsed());
+
+        // Responder reads the init and goes silent
+        let server_pub = identity().public();
+        let mut client = PQHandshake::with_identity(identity(), CipherSuite::default())
+            .unwrap()
+            .with_stage_timeout(window);
+        let (mut client_io, mut peer) = tokio::io::duplex(MAX_MESSAGE_SIZE);
+        let silent = tokio::spawn(async move {
+            let mut sink = vec![0u8; MAX_MESSAGE_SIZE];
+            let _ = peer.read(&mut sink).await;
+            peer
+        });
+
+        let started = std::time::Instant::now();
+        let result = client.client_handshake(&mut client_io, &server_pub).await;
+        assert!(matches!(result, Err(HandshakeError::Timeout)));
+        assert!(started.elapsed() < window * 5, "took {:?}", started.elapsed());
+        drop(silent.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_init_timestamp_checked_against_skew_tolerance() {
+        use nuzon_core::clock::MockClock;
+        let skew = SkewPolicy { max_future: Duration::from_secs(5), max_past: Duration::from_secs(30) };
+        let server_clock = Arc::new(MockClock::default());
+        let server = PQHandshake::with_identity(identity(), CipherSuite::default())
+            .unwrap()
+            .with_clock(server_clock.clone())
+            .with_skew_policy(skew);
+
+        let ms = Duration::from_millis(1);
+        let now = server_clock.now();
+        for (client_time, accepted) in [
+            (now + skew.max_future, true),
+            (now + skew.max_future + ms, false),
+            (now - skew.max_past, true),
+            (now - skew.max_past - ms, false),
+        ] {
+            let client_id = identity();
+            let client_pub = client_id.public();
+            let client = PQHandshake::with_identity(client_id, CipherSuite::default())
+                .unwrap()
+                .with_clock(Arc::new(MockClock::new(client_time)));
+            let init = client.create_handshake_init().await.unwrap();
+            match server.verify_init(&init, &client_pub) {
+                Ok(()) => assert!(accepted, "accepted init from {:?}", client_time),
+                Err(HandshakeError::ClockSkew(_)) => assert!(!accepted, "refused init from {:?}", client_time),
+                Err(e) => panic!("unexpected {:?}", e),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revoked_peer_key_refused() {
+        use nuzon_core::coordination::ReplicatedStateMachine;
+        let revocations = Arc::new(RevocationList::attach(Arc::new(ReplicatedStateMachine::new())).await);
+        let (client_id, server_id) = (identity(), identity());
+        let (client_pub, server_pub) = (client_id.public(), server_id.public());
+        let mut client = PQHandshake::with_identity(client_id, CipherSuite::default()).unwrap();
+        let mut server = PQHandshake::with_identity(server_id, CipherSuite::default())
+            .unwrap()
+            .with_revocations(revocations.clone());
+
+        let init = client.create_handshake_init().await.unwrap();
+        assert!(server.verify_init(&init, &client_pub).is_ok());
+
+        revocations.revoke(&client_pub.key_id(), "key compromised", 0).await.unwrap();
+        let (mut client_io, server_io) = tokio::io::duplex(MAX_MESSAGE_SIZE);
+        let (abandoned, refused) = tokio::join!(
+            client.client_handshake(&mut client_io, &server_pub),
+            // The stream closes once the server gives up
+            async {
+                let mut server_io = server_io;
+                server.server_handshake(&mut server_io, &client_pub).await
+            },
+        );
+        assert!(matches!(refused, Err(HandshakeError::KeyRevoked(id)) if id == client_pub.key_id()));
+        assert!(abandoned.is_err());
+
+        // Revoking only a PQ key refuses the peer just the same
+        let other_id = identity();
+        let other_pub = other_id.public();
+        let other = PQHandshake::with_identity(other_id, CipherSuite::default()).unwrap();
+        let init = other.create_handshake_init().await.unwrap();
+        assert!(server.verify_init(&init, &other_pub).is_ok());
+        let pq_key = other_pub.pq_keys.get(&PqSignatureScheme::Dilithium5).unwrap();
+        revocations.revoke(&key_id(pq_key), "PQ key compromised", 0).await.unwrap();
+        assert!(matches!(
+            server.verify_init(&init, &other_pub),
+            Err(HandshakeError::KeyRevoked(id)) if id == key_id(pq_key)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_signature_rejections_are_indistinguishable() {
+        let client_id = identity();
+        let client_pub = client_id.public();
+        let client = PQHandshake::with_identity(client_id, CipherSuite::default()).unwrap();
+        let server = PQHandshake::with_identity(identity(), CipherSuite::default()).unwrap();
+        let init = client.create_handshake_init().await.unwrap();
+        let classical_len = u16::from_be_bytes([init.hybrid_sig[0], init.hybrid_sig[1]]) as usize;
+
+        let tampered: [&dyn Fn(&mut Vec<u8>); 5] = [
+            &|sig| sig[2] ^= 1,
+            &|sig| *sig.last_mut().unwrap() ^= 1,
+            &|sig| sig[0] = 0xff,
+            &|sig| sig.truncate(1),
+            &|sig| sig.truncate(2 + classical_len),
+        ];
+        for tamper in tampered {
+            let mut forged = init.clone();
+            tamper(&mut forged.hybrid_sig);
+            assert!(matches!(
+                server.verify_init(&forged, &client_pub),
+                Err(HandshakeError::VerificationFailed)
+            ));
+        }
+
+        // A peer without a key for the scheme fails the same way
+        let mut keyless = client_pub.clone();
+        keyless.pq_keys.clear();
+        assert!(matches!(server.verify_init(&init, &keyless), Err(HandshakeError::VerificationFailed)));
+    }
+
+    #[tokio::test]
+    async fn test_scheme_downgrade_fails_verification() {
+        let client_id = identity();
+        let client_pub = client_id.public();
+        let client = PQHandshake::with_identity(
+            client_id,
+            CipherSuite { signature: PqSignatureScheme::Falcon1024 },
+        ).unwrap();
+        let server = PQHandshake::with_identity(identity(), CipherSuite::default()).unwrap();
+
+        let mut init = client.create_handshake_init().await.unwrap();
+        assert!(server.verify_init(&init, &client_pub).is_ok());
+
+        init.signature_scheme = PqSignatureScheme::Dilithium5;
+        assert!(matches!(
+            server.verify_init(&init, &client_pub),
+            Err(HandshakeError::VerificationFailed)
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+// signer.rs - Classical Identity Signers for the Hybrid Handshake
+use std::sync::Arc;
+use async_trait::async_trait;
+use hsm_integration::{HsmClient, HsmError, TenantPrincipal};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair},
+};
+
+use super::HandshakeError;
+
+/// Length of one P-256 signature scalar
+const P256_SCALAR_LEN: usize = 32;
+
+/// Classical (ECDSA P-256 / SHA-256) half of the hybrid identity signature
+///
+/// Peers verify with `ECDSA_P256_SHA256_ASN1`, so signatures are ASN.1 DER
+/// and the public key is an uncompressed SEC1 point.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Sign `msg`, returning a DER-encoded signature
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, HandshakeError>;
+
+    /// Key peers verify this signer's signatures with
+    fn public_key(&self) -> Vec<u8>;
+}
+
+/// Software key held in process memory
+#[async_trait]
+impl Signer for EcdsaKeyPair {
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        Ok(EcdsaKeyPair::sign(self, &SystemRandom::new(), msg)?.as_ref().to_vec())
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        KeyPair::public_key(self).as_ref().to_vec()
+    }
+}
+
+/// Tenant P-256 key held in the HSM; the private key never leaves it
+pub struct HsmSigner {
+    client: Arc<HsmClient>,
+    principal: TenantPrincipal,
+    label: String,
+    public_key: Vec<u8>,
+}
+
+impl HsmSigner {
+    /// Signer for the key pair stored under `label` in the principal's
+    /// tenant namespace
+    pub async fn new(
+        client: Arc<HsmClient>,
+        principal: TenantPrincipal,
+        label: impl Into<String>,
+    ) -> Result<Self, HsmError> {
+        let label = label.into();
+        let public_key = client.ec_public_key(&principal, &label).await?;
+        Ok(Self { client, principal, label, public_key })
+    }
+}
+
+#[async_trait]
+impl Signer for HsmSigner {
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let raw = self.client.sign_ecdsa(&self.principal, &self.label, msg)
+            .await
+            .map_err(|e| HandshakeError::CryptoError(format!("HSM signing failed: {}", e)))?;
+        der_signature(&raw)
+            .ok_or_else(|| HandshakeError::CryptoError("Malformed HSM ECDSA signature".into()))
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        self.public_key.clone()
+    }
+}
+
+/// DER `SEQUENCE { INTEGER r, INTEGER s }` from PKCS#11's raw `r || s`
+fn der_signature(raw: &[u8]) -> Option<Vec<u8>> {
+    if raw.len() != 2 * P256_SCALAR_LEN {
+        return None;
+    }
+    let integer = |scalar: &[u8]| {
+        let start = scalar.iter().position(|&b| b != 0).unwrap_or(scalar.len() - 1);
+        let scalar = &scalar[start..];
+        let pad = scalar[0] & 0x80 != 0;
+        let mut encoded = vec![0x02, (scalar.len() + pad as usize) as u8];
+        if pad {
+            encoded.push(0x00);
+        }
+        encoded.extend_from_slice(scalar);
+        encoded
+    };
+    let body = [integer(&raw[..P256_SCALAR_LEN]), integer(&raw[P256_SCALAR_LEN..])].concat();
+    Some([&[0x30, body.len() as u8][..], &body].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_der_signature_minimal_integers() {
+        let mut raw = [0u8; 64];
+        raw[31] = 0x01;
+        raw[32] = 0x80;
+        let der = der_signature(&raw).unwrap();
+        // r = 1 shrinks to one byte; s has its top bit set and gains a zero pad
+        assert_eq!(&der[..5], &[0x30, 3 + 35, 0x02, 0x01, 0x01]);
+        assert_eq!(&der[5..8], &[0x02, 33, 0x00]);
+        assert_eq!(der.len(), 2 + 3 + 35);
+        assert!(der_signature(&raw[..63]).is_none());
+    }
+}
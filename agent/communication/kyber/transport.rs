@@ -0,0 +1,203 @@
+// transport.rs - Length-Delimited Framing for Agent Transport
+use std::{fmt, io};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Width of the big-endian length prefix
+const LENGTH_PREFIX: usize = 4;
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 256 * 1024;
+
+#[derive(Debug)]
+pub enum FrameError {
+    /// Declared or supplied payload length exceeds the codec limit
+    TooLarge { len: usize, max: usize },
+    Io(io::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { len, max } => write!(f, "frame of {} bytes exceeds limit of {}", len, max),
+            Self::Io(e) => write!(f, "transport I/O failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::TooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// `u32` big-endian length prefix followed by the payload
+///
+/// Lengths are checked against `max_frame_size` as soon as the prefix is
+/// read, before any buffer is reserved for the payload.
+#[derive(Debug, Clone, Copy)]
+pub struct LengthDelimitedCodec {
+    max_frame_size: usize,
+}
+
+impl LengthDelimitedCodec {
+    pub fn new(max_frame_size: usize) -> Self {
+        Self { max_frame_size: max_frame_size.min(u32::MAX as usize) }
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    fn check(&self, len: usize) -> Result<(), FrameError> {
+        if len > self.max_frame_size {
+            return Err(FrameError::TooLarge { len, max: self.max_frame_size });
+        }
+        Ok(())
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = BytesMut;
+    type Error = FrameError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, FrameError> {
+        if src.len() < LENGTH_PREFIX {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+        self.check(len)?;
+
+        if src.len() < LENGTH_PREFIX + len {
+            src.reserve(LENGTH_PREFIX + len - src.len());
+            return Ok(None);
+        }
+        src.advance(LENGTH_PREFIX);
+        Ok(Some(src.split_to(len)))
+    }
+}
+
+impl Encoder<Bytes> for LengthDelimitedCodec {
+    type Error = FrameError;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), FrameError> {
+        self.check(item.len())?;
+        dst.reserve(LENGTH_PREFIX + item.len());
+        dst.put_u32(item.len() as u32);
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+/// Write one frame and flush
+pub async fn write_frame<S>(
+    stream: &mut S,
+    codec: &mut LengthDelimitedCodec,
+    payload: Bytes,
+) -> Result<(), FrameError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut buf = BytesMut::new();
+    codec.encode(payload, &mut buf)?;
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read exactly one frame, leaving any bytes after it on the stream
+///
+/// Unlike `Framed`, nothing is read ahead, so the stream can be handed to a
+/// different protocol once the frame has been consumed.
+pub async fn read_frame<S>(
+    stream: &mut S,
+    codec: &LengthDelimitedCodec,
+) -> Result<BytesMut, FrameError>
+where
+    S: AsyncRead + Unpin,
+{
+    let len = stream.read_u32().await? as usize;
+    codec.check(len)?;
+
+    let mut frame = BytesMut::zeroed(len);
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::Framed;
+
+    #[test]
+    fn test_frames_at_and_above_limit() {
+        let mut codec = LengthDelimitedCodec::new(8);
+        let mut buf = BytesMut::new();
+
+        codec.encode(Bytes::from_static(b"12345678"), &mut buf).unwrap();
+        assert_eq!(&buf[..4], &8u32.to_be_bytes());
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), &b"12345678"[..]);
+        assert!(buf.is_empty());
+
+        assert!(matches!(
+            codec.encode(Bytes::from_static(b"123456789"), &mut buf),
+            Err(FrameError::TooLarge { len: 9, max: 8 })
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_oversized_prefix_rejected_before_allocation() {
+        let mut codec = LengthDelimitedCodec::new(1024);
+        let mut buf = BytesMut::from(&u32::MAX.to_be_bytes()[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(FrameError::TooLarge { len, max: 1024 }) if len == u32::MAX as usize
+        ));
+        assert!(buf.capacity() < 1024);
+    }
+
+    #[test]
+    fn test_partial_frame_waits_for_more() {
+        let mut codec = LengthDelimitedCodec::default();
+        let mut buf = BytesMut::new();
+        buf.put_u32(5);
+        buf.extend_from_slice(b"he");
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(b"llo");
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), &b"hello"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_framed_and_direct_reads_interoperate() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut framed = Framed::new(client, LengthDelimitedCodec::new(16));
+
+        framed.send(Bytes::from_static(b"ping")).await.unwrap();
+        assert_eq!(read_frame(&mut server, &LengthDelimitedCodec::new(16)).await.unwrap(), &b"ping"[..]);
+
+        write_frame(&mut server, &mut LengthDelimitedCodec::new(64), Bytes::from(vec![0u8; 17])).await.unwrap();
+        assert!(matches!(
+            framed.next().await,
+            Some(Err(FrameError::TooLarge { len: 17, max: 16 }))
+        ));
+    }
+}
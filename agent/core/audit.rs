@@ -0,0 +1,114 @@
+// audit.rs - Signed Capability Execution Records
+use std::{sync::Arc, time::Duration};
+use anyhow::Result;
+use async_trait::async_trait;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+/// How an audited execution ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionOutcome {
+    Success,
+    /// Rejected by an authorization check before running
+    Denied { reason: String },
+    Failed { error: String },
+}
+
+/// Who called what, with which params and to what effect
+///
+/// Params are kept only as an HMAC under the `AuditPolicy` key, so sensitive
+/// inputs never reach the audit trail and low-entropy ones cannot be
+/// recovered from it by guessing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionRecord {
+    pub caller: String,
+    pub capability_id: String,
+    /// Resolved version; `None` if resolution failed
+    pub version: Option<semver::Version>,
+    pub params_hash: [u8; 32],
+    pub outcome: ExecutionOutcome,
+    pub duration: Duration,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u128,
+}
+
+/// Execution record with an Ed25519 signature over its JSON encoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedExecutionRecord {
+    pub record: ExecutionRecord,
+    pub signature: Vec<u8>,
+}
+
+impl SignedExecutionRecord {
+    fn sign(record: ExecutionRecord, key: &Keypair) -> Result<Self> {
+        let signature = key.sign(&serde_json::to_vec(&record)?).to_bytes().to_vec();
+        Ok(Self { record, signature })
+    }
+
+    /// Check the record was signed by `public` and not altered since
+    pub fn verify(&self, public: &PublicKey) -> bool {
+        let Ok(encoded) = serde_json::to_vec(&self.record) else { return false };
+        Signature::from_bytes(&self.signature)
+            .is_ok_and(|signature| public.verify(&encoded, &signature).is_ok())
+    }
+}
+
+/// Durable destination for execution records
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn write(&self, record: SignedExecutionRecord) -> Result<()>;
+}
+
+/// How params are digested and what a refused record does to its execution
+pub struct AuditPolicy {
+    params_key: hmac::Key,
+    fail_closed: bool,
+}
+
+impl AuditPolicy {
+    /// Digest params with HMAC-SHA256 under `params_secret`
+    ///
+    /// Whoever holds the secret can check which params a record was made
+    /// for; nobody else can.
+    pub fn new(params_secret: &[u8]) -> Self {
+        Self { params_key: hmac::Key::new(hmac::HMAC_SHA256, params_secret), fail_closed: false }
+    }
+
+    /// Fail an execution whose record the sink refuses, rather than logging
+    /// the refusal and returning the result unaudited
+    pub fn fail_closed(mut self) -> Self {
+        self.fail_closed = true;
+        self
+    }
+
+    /// Digest of `params` as it appears in `ExecutionRecord::params_hash`
+    pub fn params_hash(&self, params: &serde_json::Value) -> [u8; 32] {
+        // serde_json maps are key-ordered, so equal params serialize identically
+        let encoded = serde_json::to_vec(params).unwrap_or_default();
+        let mut hash = [0; 32];
+        hash.copy_from_slice(hmac::sign(&self.params_key, &encoded).as_ref());
+        hash
+    }
+}
+
+/// Signs records and hands them to the sink
+pub(crate) struct AuditLog {
+    pub(crate) sink: Arc<dyn AuditSink>,
+    pub(crate) key: Keypair,
+    pub(crate) policy: AuditPolicy,
+}
+
+impl AuditLog {
+    pub(crate) fn params_hash(&self, params: &serde_json::Value) -> [u8; 32] {
+        self.policy.params_hash(params)
+    }
+
+    pub(crate) fn fail_closed(&self) -> bool {
+        self.policy.fail_closed
+    }
+
+    pub(crate) async fn record(&self, record: ExecutionRecord) -> Result<()> {
+        self.sink.write(SignedExecutionRecord::sign(record, &self.key)?).await
+    }
+}
@@ -0,0 +1,98 @@
+// cache.rs - Capability Result Cache
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+/// Entries a registry's result cache holds unless configured otherwise
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// Cache key: capability id, resolved version and a digest of the params
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    capability_id: String,
+    version: semver::Version,
+    params_hash: [u8; 32],
+}
+
+impl CacheKey {
+    pub fn derive(capability_id: &str, version: &semver::Version, params: &serde_json::Value) -> Self {
+        Self {
+            capability_id: capability_id.to_string(),
+            version: version.clone(),
+            params_hash: params_digest(params),
+        }
+    }
+}
+
+/// SHA-256 of the params' JSON encoding
+pub fn params_digest(params: &serde_json::Value) -> [u8; 32] {
+    // serde_json maps are key-ordered, so equal params serialize identically
+    let encoded = serde_json::to_vec(params).unwrap_or_default();
+    Sha256::digest(&encoded).into()
+}
+
+struct CacheEntry {
+    value: serde_json::Value,
+    expires_at: Instant,
+}
+
+/// TTL cache of results from pure capabilities, holding at most
+/// `max_entries` results
+pub struct ResultCache {
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    max_entries: usize,
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl ResultCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self { entries: Mutex::default(), max_entries }
+    }
+
+    /// Cached result, if present and not expired
+    pub async fn get(&self, key: &CacheKey) -> Option<serde_json::Value> {
+        let mut entries = self.entries.lock().await;
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache `value` for `ttl`, first dropping expired entries and, when
+    /// still full, the live entry closest to expiry
+    pub async fn insert(&self, key: CacheKey, value: serde_json::Value, ttl: Duration) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().await;
+        let now = Instant::now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let soonest = entries.iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone());
+            if let Some(soonest) = soonest {
+                entries.remove(&soonest);
+            }
+        }
+        entries.insert(key, CacheEntry { value, expires_at: now + ttl });
+    }
+
+    /// Drop every entry for one capability version
+    pub async fn invalidate(&self, capability_id: &str, version: &semver::Version) {
+        self.entries.lock().await
+            .retain(|key, _| key.capability_id != capability_id || &key.version != version);
+    }
+}
@@ -4,17 +4,64 @@
 #![feature(type_alias_impl_trait)]
 
 use std::{
+    any::Any,
     collections::{BTreeMap, HashMap},
-    sync::Arc,
-    time::Duration,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use futures::{
+    stream::{self, BoxStream, Stream, StreamExt},
+    FutureExt,
+};
+use nuzon_core::{
+    agent::{CallerContext, CapabilityInvoker},
+    EnterpriseError,
+};
+use prometheus::HistogramVec;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, Semaphore};
-use tracing::{debug_span, instrument, Instrument};
+use tokio::sync::{Mutex, Notify, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug_span, error, field, info, info_span, instrument, warn, Instrument, Span};
 use uuid::Uuid;
 
+mod audit;
+mod cache;
+mod circuit_breaker;
+mod idempotency;
+mod merkle_anchor;
+mod middleware;
+mod params;
+mod rate_limit;
+mod scheduler;
+mod warm_pool;
+mod wasm;
+
+use audit::AuditLog;
+use cache::{params_digest, CacheKey, ResultCache};
+use circuit_breaker::CircuitBreakers;
+use idempotency::Idempotency;
+use rate_limit::CallerRateLimiter;
+use scheduler::FairScheduler;
+use warm_pool::{Instance, WarmPool};
+pub use audit::{AuditPolicy, AuditSink, ExecutionOutcome, ExecutionRecord, SignedExecutionRecord};
+pub use circuit_breaker::CircuitBreakerConfig;
+pub use idempotency::{IdempotencyStore, MemoryIdempotencyStore, RecordedResult};
+pub use merkle_anchor::{
+    AnchorPolicy, AnchorPublisher, AnchorStore, MerkleAnchor, MerkleAnchoring, MerkleProof, RecordId,
+};
+pub use middleware::{CapabilityMiddleware, MiddlewareContext, Next};
+pub use params::ParamType;
+pub use rate_limit::CallerRateLimit;
+pub use scheduler::{PoolScheduler, WeightedFairConfig};
+pub use nuzon_core::telemetry::TraceContext;
+pub use wasm::WasmCapability;
+
 /// Enterprise capability metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CapabilityMeta {
@@ -23,6 +70,23 @@ pub struct CapabilityMeta {
     pub required_claims: Vec<String>,
     pub resource_limits: ResourceLimits,
     pub dependencies: Vec<CapabilityRef>,
+    /// Results are a pure function of params and may be reused for this long
+    #[serde(default)]
+    pub cacheable: Option<Duration>,
+    /// Call rate allowed to each `caller_identity`; `None` is unlimited
+    #[serde(default)]
+    pub rate_limit: Option<CallerRateLimit>,
+    /// Object merged under incoming params to fill omitted fields
+    #[serde(default)]
+    pub params_defaults: serde_json::Value,
+    /// Types incoming top-level params are coerced to before execution
+    #[serde(default)]
+    pub params_types: BTreeMap<String, ParamType>,
+    /// Instances initialized by `EnterpriseCapability::warm` at registration
+    /// and reused across calls; `None` serves every call from the
+    /// registered capability
+    #[serde(default)]
+    pub warm_pool_size: Option<usize>,
 }
 
 /// Hardware resource constraints
@@ -31,6 +95,9 @@ pub struct ResourceLimits {
     pub max_memory_mb: u32,
     pub max_cpu_cores: f32,
     pub timeout_secs: u64,
+    /// Order in which callers waiting on the pool are granted permits
+    #[serde(default)]
+    pub scheduler: PoolScheduler,
 }
 
 /// Versioned capability reference
@@ -40,14 +107,56 @@ pub struct CapabilityRef {
     pub version_req: semver::VersionReq,
 }
 
+/// Bound on a single capability's health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest per-call deadline honoured unless the registry is configured otherwise
+const DEFAULT_MAX_DEADLINE: Duration = Duration::from_secs(600);
+
+/// Self-reported capability health
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapabilityHealth {
+    Healthy,
+    /// Serving, but an external dependency is impaired
+    Degraded { message: String },
+    /// Cannot serve requests
+    Unhealthy { message: String },
+}
+
+/// Output of a streaming execution, chunk by chunk; an error ends it
+pub type CapabilityStream = BoxStream<'static, std::result::Result<serde_json::Value, EnterpriseError>>;
+
 /// Runtime capability interface
 #[async_trait]
-pub trait EnterpriseCapability: Send + Sync {
+pub trait EnterpriseCapability: Send + Sync + 'static {
     async fn execute(
         &self,
         params: serde_json::Value,
         context: ExecutionContext,
     ) -> Result<serde_json::Value>;
+
+    /// Produce output progressively, e.g. tokens as a model generates them
+    ///
+    /// The default yields the result of `execute` as the only chunk.
+    fn execute_stream(self: Arc<Self>, params: serde_json::Value, context: ExecutionContext) -> CapabilityStream {
+        Box::pin(stream::once(async move {
+            self.execute(params, context).await.map_err(into_enterprise_error)
+        }))
+    }
+
+    /// Check that external dependencies (model endpoints, databases) are reachable
+    async fn health(&self) -> CapabilityHealth {
+        CapabilityHealth::Healthy
+    }
+
+    /// Pay a costly first-call initialization (loading a model) off the
+    /// critical path, returning the initialized instance
+    ///
+    /// Called `CapabilityMeta::warm_pool_size` times at registration and
+    /// again whenever every warmed instance is busy. The default has
+    /// nothing to warm, and the registered capability serves every call.
+    async fn warm(&self) -> Result<Option<Arc<dyn EnterpriseCapability>>> {
+        Ok(None)
+    }
 }
 
 /// Security context for capability execution
@@ -55,30 +164,146 @@ pub struct ExecutionContext {
     pub caller_identity: String,
     pub auth_claims: Vec<String>,
     pub resource_budget: ResourceBudget,
+    /// Span of the caller; `None` starts a new trace
+    pub trace: Option<TraceContext>,
+    /// Timeout for this call in place of the pool default, clamped to the
+    /// registry's maximum; capabilities see the timeout they run under
+    pub deadline: Option<Duration>,
+    /// When that timeout lapses; set by the registry for the capability, so
+    /// its dependencies get only the time left
+    pub expires_at: Option<Instant>,
+    /// Caller-chosen key under which a retried call returns the first
+    /// call's result instead of running again
+    pub idempotency_key: Option<String>,
 }
 
 /// Runtime resource allocation
 pub struct ResourceBudget {
     semaphore: Arc<Semaphore>,
     cpu_cores: f32,
-    _guard: tokio::sync::OwnedSemaphorePermit,
+    /// Released once every share of the budget is dropped
+    _guard: Arc<tokio::sync::OwnedSemaphorePermit>,
+}
+
+impl ResourceBudget {
+    /// The same allocation, held until this share is dropped too
+    fn share(&self) -> Self {
+        Self { semaphore: self.semaphore.clone(), cpu_cores: self.cpu_cores, _guard: self._guard.clone() }
+    }
+}
+
+/// Registered capability version with its metadata
+#[derive(Clone)]
+struct RegisteredCapability {
+    meta: CapabilityMeta,
+    capability: Arc<dyn EnterpriseCapability>,
+    /// Set when `warm_pool_size` is
+    warm: Option<Arc<WarmPool>>,
+    /// Set by the last health report
+    unhealthy: Arc<AtomicBool>,
 }
 
 /// Central capability registry
 #[derive(Default)]
 pub struct CapabilityRegistry {
-    capabilities: Mutex<HashMap<String, BTreeMap<semver::Version, Arc<dyn EnterpriseCapability>>>>,
-    resource_pools: Mutex<HashMap<String, ResourcePool>>,
+    capabilities: Mutex<HashMap<String, BTreeMap<semver::Version, RegisteredCapability>>>,
+    resource_pools: Mutex<HashMap<String, Arc<ResourcePool>>>,
+    result_cache: ResultCache,
+    idempotency: Idempotency,
+    caller_limits: CallerRateLimiter,
+    breakers: Arc<CircuitBreakers>,
+    shutting_down: AtomicBool,
+    skip_unhealthy: AtomicBool,
+    in_flight: Arc<InFlight>,
+    abort: CancellationToken,
+    audit: Option<Arc<AuditLog>>,
+    middleware: Vec<Arc<dyn CapabilityMiddleware>>,
+    /// Cap on requested deadlines; `None` is `DEFAULT_MAX_DEADLINE`
+    max_deadline: Option<Duration>,
+}
+
+/// Counter of running executions with completion notification
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    drained: Notify,
+}
+
+/// Decrements the in-flight count when an execution finishes or is dropped
+struct InFlightGuard(Arc<InFlight>);
+
+impl InFlightGuard {
+    fn new(in_flight: &Arc<InFlight>) -> Self {
+        in_flight.count.fetch_add(1, Ordering::SeqCst);
+        Self(in_flight.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+    }
 }
 
 impl CapabilityRegistry {
+    /// Write a signed `ExecutionRecord` to `sink` after every execution,
+    /// digesting params and handling refused writes as `policy` says
+    pub fn with_audit(mut self, sink: Arc<dyn AuditSink>, key: ed25519_dalek::Keypair, policy: AuditPolicy) -> Self {
+        self.audit = Some(Arc::new(AuditLog { sink, key, policy }));
+        self
+    }
+
+    /// Wrap executions in `layer`, inside any layers added before it
+    pub fn with_middleware(mut self, layer: Arc<dyn CapabilityMiddleware>) -> Self {
+        self.middleware.push(layer);
+        self
+    }
+
+    /// Record idempotency-keyed results in `store` for `ttl` instead of the
+    /// default in-memory store
+    pub fn with_idempotency_store(mut self, store: Arc<dyn IdempotencyStore>, ttl: Duration) -> Self {
+        self.idempotency = Idempotency::new(store, ttl);
+        self
+    }
+
+    /// Trip each capability version's breaker under `config` instead of
+    /// the default five consecutive failures and 30s cooldown
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.breakers = Arc::new(CircuitBreakers::new(config));
+        self
+    }
+
+    /// Hold at most `max_entries` cached results instead of
+    /// `DEFAULT_MAX_ENTRIES`; zero disables result caching
+    pub fn with_result_cache_capacity(mut self, max_entries: usize) -> Self {
+        self.result_cache = ResultCache::new(max_entries);
+        self
+    }
+
+    /// Clamp the timeouts callers request to `max`; pool defaults are
+    /// configured with the pool and not clamped
+    pub fn with_max_deadline(mut self, max: Duration) -> Self {
+        self.max_deadline = Some(max);
+        self
+    }
+
     /// Register new capability version
+    ///
+    /// A warm pool is filled before the version becomes visible, so its
+    /// first calls already find initialized instances.
     #[instrument(skip_all)]
     pub async fn register(
         &self,
         meta: CapabilityMeta,
         capability: Arc<dyn EnterpriseCapability>,
     ) -> Result<()> {
+        let warm = match meta.warm_pool_size {
+            Some(size) => Some(WarmPool::fill(&*capability, size).await.context("Capability warm-up failed")?),
+            None => None,
+        };
+
         let mut caps = self.capabilities.lock().await;
         let versions = caps.entry(meta.id.to_string()).or_default();
         
@@ -89,17 +314,79 @@ impl CapabilityRegistry {
         // Initialize resource pool
         let mut pools = self.resource_pools.lock().await;
         pools.entry(meta.id.to_string())
-            .or_insert_with(|| ResourcePool::new(
+            .or_insert_with(|| Arc::new(ResourcePool::new(
                 meta.resource_limits.max_memory_mb,
                 meta.resource_limits.max_cpu_cores,
-            ));
+                &meta.resource_limits.scheduler,
+            )));
+
+        versions.insert(meta.version.clone(), RegisteredCapability {
+            meta,
+            capability,
+            warm,
+            unhealthy: Arc::new(AtomicBool::new(false)),
+        });
+        Ok(())
+    }
 
-        versions.insert(meta.version.clone(), capability);
+    /// Remove (yank) a capability version and its cached results
+    #[instrument(skip(self))]
+    pub async fn deregister(&self, capability_id: &str, version: &semver::Version) -> Result<()> {
+        let mut caps = self.capabilities.lock().await;
+        let versions = caps.get_mut(capability_id)
+            .context("Capability not found")?;
+        versions.remove(version)
+            .context("Capability version not registered")?;
+        if versions.is_empty() {
+            caps.remove(capability_id);
+        }
+        drop(caps);
+
+        self.result_cache.invalidate(capability_id, version).await;
         Ok(())
     }
 
+    /// Skip versions reported `Unhealthy` during version selection
+    pub fn set_skip_unhealthy(&self, skip: bool) {
+        self.skip_unhealthy.store(skip, Ordering::SeqCst);
+    }
+
+    /// Query every registered version's health, keyed by `id@version`
+    ///
+    /// Checks run concurrently; one that does not answer within
+    /// `HEALTH_CHECK_TIMEOUT` is reported as unhealthy.
+    #[instrument(skip(self))]
+    pub async fn health_report(&self) -> HashMap<String, CapabilityHealth> {
+        let registered: Vec<_> = self.capabilities.lock().await
+            .iter()
+            .flat_map(|(id, versions)| versions.iter()
+                .map(move |(version, cap)| (format!("{}@{}", id, version), cap.clone())))
+            .collect();
+
+        let checks = registered.into_iter().map(|(key, cap)| async move {
+            let health = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, cap.capability.health())
+                .await
+                .unwrap_or_else(|_| CapabilityHealth::Unhealthy {
+                    message: "health check timed out".into(),
+                });
+            cap.unhealthy.store(
+                matches!(health, CapabilityHealth::Unhealthy { .. }),
+                Ordering::SeqCst,
+            );
+            (key, health)
+        });
+
+        futures::future::join_all(checks).await.into_iter().collect()
+    }
+
     /// Execute capability with security controls
-    #[instrument(skip_all)]
+    #[instrument(skip_all, fields(
+        capability = capability_id,
+        version = %version,
+        caller = %context.caller_identity,
+        otel.status_code = field::Empty,
+        otel.status_message = field::Empty,
+    ))]
     pub async fn execute(
         &self,
         capability_id: &str,
@@ -107,67 +394,868 @@ impl CapabilityRegistry {
         params: serde_json::Value,
         context: ExecutionContext,
     ) -> Result<serde_json::Value> {
+        nuzon_core::enterprise_result_span!(self.execute_idempotent(capability_id, version, params, context).await)
+    }
+
+    /// Run an execution once per idempotency key, if the caller gave one
+    async fn execute_idempotent(
+        &self,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+        context: ExecutionContext,
+    ) -> Result<serde_json::Value> {
+        let trace = child_trace(context.trace.as_ref());
+        let Some(key) = &context.idempotency_key else {
+            return self.execute_traced(
+                capability_id,
+                version,
+                params,
+                context.caller_identity,
+                context.auth_claims,
+                trace,
+                context.deadline,
+            ).await;
+        };
+
+        // Keys are the caller's own, so they are scoped to caller and capability
+        let scoped = format!("{}\0{}\0{}", context.caller_identity, capability_id, key);
+        let params_hash = params_digest(&params);
+        self.idempotency.run(&scoped, params_hash, || self.execute_traced(
+            capability_id,
+            version,
+            params,
+            context.caller_identity.clone(),
+            context.auth_claims.clone(),
+            trace,
+            context.deadline,
+        )).await
+    }
+
+    /// Execute a declared dependency on behalf of a running capability
+    ///
+    /// The dependency runs with the parent's identity and claims, in a span
+    /// nested under the parent's, within whatever remains of the parent's
+    /// timeout.
+    pub async fn execute_dependency(
+        &self,
+        parent: &ExecutionContext,
+        dependency: &CapabilityRef,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.execute_traced(
+            &dependency.name,
+            &dependency.version_req,
+            params,
+            parent.caller_identity.clone(),
+            parent.auth_claims.clone(),
+            child_trace(parent.trace.as_ref()),
+            parent.expires_at
+                .map(|expires_at| expires_at.saturating_duration_since(Instant::now()))
+                .or(parent.deadline),
+        ).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_traced(
+        &self,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+        caller_identity: String,
+        auth_claims: Vec<String>,
+        trace: TraceContext,
+        deadline: Option<Duration>,
+    ) -> Result<serde_json::Value> {
+        let Some(audit) = &self.audit else {
+            return self.run_traced(
+                capability_id, version, params, caller_identity, auth_claims, trace, deadline, &mut None,
+            ).await;
+        };
+
+        let started = Instant::now();
+        let params_hash = audit.params_hash(&params);
+        let caller = caller_identity.clone();
+        let mut resolved = None;
+        let result = self.run_traced(
+            capability_id, version, params, caller_identity, auth_claims, trace, deadline, &mut resolved,
+        ).await;
+
+        let outcome = match &result {
+            Ok(_) => ExecutionOutcome::Success,
+            Err(error) => error_outcome(error),
+        };
+        let record = ExecutionRecord {
+            caller,
+            capability_id: capability_id.to_string(),
+            version: resolved,
+            params_hash,
+            outcome,
+            duration: started.elapsed(),
+            timestamp: now_millis(),
+        };
+        write_audit(audit, record).await?;
+        result
+    }
+
+    /// Execution pipeline; reports the selected version through `resolved`
+    #[allow(clippy::too_many_arguments)]
+    async fn run_traced(
+        &self,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+        caller_identity: String,
+        auth_claims: Vec<String>,
+        trace: TraceContext,
+        deadline: Option<Duration>,
+        resolved: &mut Option<semver::Version>,
+    ) -> Result<serde_json::Value> {
+        let _in_flight = self.enter()?;
+        let selected = self.select(capability_id, version).await?;
+        *resolved = Some(selected.meta.version.clone());
+        check_claims(&selected, &caller_identity, &auth_claims)?;
+
+        // Normalise params first so caching and execution see the same value
+        let params = params::prepare(params, &selected.meta.params_defaults, &selected.meta.params_types)?;
+
+        let ctx = MiddlewareContext {
+            capability_id: capability_id.to_string(),
+            version: selected.meta.version.clone(),
+            caller_identity,
+            auth_claims,
+            params,
+            trace,
+            deadline,
+        };
+        if self.middleware.is_empty() {
+            return self.invoke_selected(&selected, ctx).await;
+        }
+        let selected = &selected;
+        let endpoint: &middleware::Endpoint<'_> = &|ctx| Box::pin(async move {
+            self.invoke_selected(selected, ctx).await.map_err(into_enterprise_error)
+        });
+        // A panicking layer stops here as a panicking capability does
+        Ok(AssertUnwindSafe(Next::new(&self.middleware, endpoint).run(ctx))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|payload| Err(capability_panic(capability_id, &*payload)))?)
+    }
+
+    /// Cache, rate limit, budget and run an already selected capability
+    ///
+    /// Identity, claims, params and trace come from `ctx` as middleware left
+    /// it; the capability and version are always the selected ones.
+    async fn invoke_selected(
+        &self,
+        selected: &RegisteredCapability,
+        ctx: MiddlewareContext,
+    ) -> Result<serde_json::Value> {
+        let MiddlewareContext { caller_identity, auth_claims, params, trace, deadline, .. } = ctx;
+        let capability_id = selected.meta.id.to_string();
+        let capability_id = capability_id.as_str();
+
+        // Unsampled traces still run, just without a span
+        let span = if trace.is_sampled() {
+            info_span!(
+                "capability.execute",
+                capability_id,
+                version = %selected.meta.version,
+                trace_id = %trace.trace_id,
+                span_id = %trace.span_id,
+                parent_span_id = trace.parent_span_id.as_deref(),
+                resource_wait_ms = field::Empty,
+                timeout_ms = field::Empty,
+            )
+        } else {
+            Span::none()
+        };
+
+        // Serve pure capabilities from cache without taking a budget
+        let cache_key = selected.meta.cacheable.map(|ttl| {
+            (CacheKey::derive(capability_id, &selected.meta.version, &params), ttl)
+        });
+        if let Some((key, _)) = &cache_key {
+            if let Some(cached) = self.result_cache.get(key).await {
+                return Ok(cached);
+            }
+        }
+
+        self.admit(selected, &caller_identity)?;
+        let (budget, timeout) = self.acquire(selected, &caller_identity, &auth_claims, deadline, &span).await?;
+        let caller_bound = timeout.caller_bound;
+        let timeout = timeout.duration;
+        let expires_at = Instant::now() + timeout;
+
+        // Warm an instance up if none is idle and execute it, both within the
+        // timeout, aborting if shutdown's grace period lapses. A panic in
+        // either stops at this boundary: the budget moved into the call is
+        // dropped with it and the registry's locks are never held across it.
+        let mut checked_out = None;
+        let execution = tokio::time::timeout(
+            timeout,
+            AssertUnwindSafe(async {
+                let instance = checked_out.insert(self.checkout(selected).await?);
+                instance.execute(params, ExecutionContext {
+                    caller_identity,
+                    auth_claims,
+                    resource_budget: budget,
+                    trace: Some(trace),
+                    deadline: Some(timeout),
+                    expires_at: Some(expires_at),
+                    idempotency_key: None,
+                }).await
+            }).catch_unwind().instrument(span),
+        );
+
+        let (result, timed_out) = tokio::select! {
+            result = execution => match result {
+                Ok(Ok(result)) => (result, false),
+                Ok(Err(payload)) => (Err(capability_panic(capability_id, &*payload).into()), false),
+                Err(elapsed) => (Err(elapsed.into()), true),
+            },
+            _ = self.abort.cancelled() => return Err(EnterpriseError::ResourceLimit(
+                "execution aborted by registry shutdown".into()
+            ).into()),
+        };
+        // An instance whose call failed, panicked or was cut short may be
+        // left inconsistent, so it does not go back to the pool
+        if let (Err(_), Some(instance)) = (&result, checked_out.as_mut()) {
+            instance.discard();
+        }
+        // Panics, infrastructure errors and timeouts the caller did not
+        // shorten count against the breaker; answering with a business error
+        // proves the backend is up
+        match &result {
+            Err(_) if timed_out && caller_bound => {}
+            Err(error) if timed_out || is_backend_failure(error) => {
+                self.breakers.record(capability_id, &selected.meta.version, false);
+            }
+            _ => self.breakers.record(capability_id, &selected.meta.version, true),
+        }
+        let result = result?;
+
+        if let Some((key, ttl)) = cache_key {
+            // A version deregistered while this ran has already had its
+            // results invalidated; caching this one would revive it
+            let caps = self.capabilities.lock().await;
+            let still_registered = caps.get(capability_id)
+                .and_then(|versions| versions.get(&selected.meta.version))
+                .is_some_and(|current| Arc::ptr_eq(&current.capability, &selected.capability));
+            if still_registered {
+                self.result_cache.insert(key, result.clone(), ttl).await;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Execute capability, receiving its output as it is produced
+    ///
+    /// Version selection, claims, params, rate limits, the circuit breaker
+    /// and the resource budget apply as for `execute`, but the budget and
+    /// timeout cover the stream's whole lifetime: the budget is held until
+    /// the stream ends or the caller drops it, and a stream still open when
+    /// the timeout lapses ends with a `ResourceLimit` error. Middleware runs
+    /// its `before_stream` hooks in place of `call`. The audit record is
+    /// written when the stream ends, or when it is dropped unfinished, which
+    /// is recorded as a failure. Result caching and idempotency keys act on
+    /// whole results and do not apply.
+    #[instrument(skip_all, fields(
+        capability = capability_id,
+        version = %version,
+        caller = %context.caller_identity,
+    ))]
+    pub async fn execute_stream(
+        &self,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+        context: ExecutionContext,
+    ) -> Result<CapabilityStream> {
+        let mut audit = self.audit.clone().map(|log| StreamAudit {
+            params_hash: log.params_hash(&params),
+            log: Some(log),
+            caller: context.caller_identity.clone(),
+            capability_id: capability_id.to_string(),
+            version: None,
+            started: Instant::now(),
+        });
+        let result = self.start_stream(capability_id, version, params, context, &mut audit).await;
+        if let (Err(error), Some(audit)) = (&result, audit) {
+            // The call has already failed, so a refused record changes nothing
+            let _ = audit.finish(error_outcome(error)).await;
+        }
+        result
+    }
+
+    /// Streaming pipeline; moves `audit` into the stream once it starts
+    async fn start_stream(
+        &self,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+        context: ExecutionContext,
+        audit: &mut Option<StreamAudit>,
+    ) -> Result<CapabilityStream> {
+        let in_flight = self.enter()?;
+        let selected = self.select(capability_id, version).await?;
+        if let Some(audit) = audit.as_mut() {
+            audit.version = Some(selected.meta.version.clone());
+        }
+        check_claims(&selected, &context.caller_identity, &context.auth_claims)?;
+        let params = params::prepare(params, &selected.meta.params_defaults, &selected.meta.params_types)?;
+
+        let mut ctx = MiddlewareContext {
+            capability_id: capability_id.to_string(),
+            version: selected.meta.version.clone(),
+            caller_identity: context.caller_identity,
+            auth_claims: context.auth_claims,
+            params,
+            trace: child_trace(context.trace.as_ref()),
+            deadline: context.deadline,
+        };
+        // A panicking layer stops here as a panicking capability does
+        ctx = AssertUnwindSafe(async {
+            for layer in &self.middleware {
+                ctx = layer.before_stream(ctx).await?;
+            }
+            Ok::<_, EnterpriseError>(ctx)
+        }).catch_unwind().await.unwrap_or_else(|payload| Err(capability_panic(capability_id, &*payload)))?;
+        let MiddlewareContext { caller_identity, auth_claims, params, trace, deadline, .. } = ctx;
+
+        let span = if trace.is_sampled() {
+            info_span!(
+                "capability.stream",
+                capability_id,
+                version = %selected.meta.version,
+                trace_id = %trace.trace_id,
+                span_id = %trace.span_id,
+                parent_span_id = trace.parent_span_id.as_deref(),
+                resource_wait_ms = field::Empty,
+                timeout_ms = field::Empty,
+                chunks = field::Empty,
+            )
+        } else {
+            Span::none()
+        };
+        self.admit(&selected, &caller_identity)?;
+        let (budget, timeout) = self.acquire(
+            &selected,
+            &caller_identity,
+            &auth_claims,
+            deadline,
+            &span,
+        ).await?;
+        // Warm-up counts towards the stream's timeout
+        let ends = tokio::time::Instant::now() + timeout.duration;
+        let checkout = AssertUnwindSafe(self.checkout(&selected)).catch_unwind();
+        let mut instance = match tokio::time::timeout_at(ends, checkout).await {
+            Ok(Ok(instance)) => instance?,
+            Ok(Err(payload)) => {
+                self.breakers.record(capability_id, &selected.meta.version, false);
+                return Err(capability_panic(capability_id, &*payload).into());
+            }
+            Err(elapsed) => {
+                if !timeout.caller_bound {
+                    self.breakers.record(capability_id, &selected.meta.version, false);
+                }
+                return Err(elapsed.into());
+            }
+        };
+
+        let capability = instance.capability();
+        let context = ExecutionContext {
+            caller_identity,
+            auth_claims,
+            resource_budget: budget.share(),
+            trace: Some(trace),
+            deadline: Some(timeout.duration),
+            expires_at: Some(ends.into_std()),
+            idempotency_key: None,
+        };
+        let chunks = match std::panic::catch_unwind(AssertUnwindSafe(|| capability.execute_stream(params, context))) {
+            Ok(chunks) => chunks,
+            Err(payload) => {
+                instance.discard();
+                self.breakers.record(capability_id, &selected.meta.version, false);
+                return Err(capability_panic(capability_id, &*payload).into());
+            }
+        };
+        let state = BudgetedStream {
+            breakers: self.breakers.clone(),
+            abort: self.abort.clone(),
+            chunks,
+            capability_id: selected.meta.id.to_string(),
+            version: selected.meta.version.clone(),
+            deadline: ends,
+            caller_bound: timeout.caller_bound,
+            produced: 0,
+            span,
+            audit: audit.take(),
+            _budget: budget,
+            instance,
+            _in_flight: in_flight,
+        };
+        Ok(Box::pin(stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            let span = state.span.clone();
+            state.next().instrument(span).await
+        })))
+    }
+
+    /// Count an execution in flight, unless shutdown has begun
+    ///
+    /// The count is raised before the flag is read, and `shutdown` sets the
+    /// flag before reading the count, so either this call sees the flag or
+    /// `shutdown` waits for it. A refused call drops its guard again.
+    fn enter(&self) -> Result<InFlightGuard> {
+        let guard = InFlightGuard::new(&self.in_flight);
+        if self.shutting_down.load(Ordering::SeqCst) {
+            drop(guard);
+            return Err(EnterpriseError::ResourceLimit(
+                "capability registry is shutting down".into()
+            ).into());
+        }
+        Ok(guard)
+    }
+
+    /// Latest registered version matching `version`
+    async fn select(&self, capability_id: &str, version: &semver::VersionReq) -> Result<RegisteredCapability> {
         let caps = self.capabilities.lock().await;
         let versions = caps.get(capability_id)
             .context("Capability not found")?;
 
-        // Select latest compatible version
-        let selected = versions.iter()
+        let skip_unhealthy = self.skip_unhealthy.load(Ordering::SeqCst);
+        versions.iter()
             .rev()
+            .filter(|(_, cap)| !(skip_unhealthy && cap.unhealthy.load(Ordering::SeqCst)))
             .find(|(v, _)| version.matches(v))
-            .context("No compatible version available")?;
+            .map(|(_, cap)| cap.clone())
+            .context("No compatible version available")
+    }
+
+    /// Apply the caller's rate limit and the version's circuit breaker
+    fn admit(&self, selected: &RegisteredCapability, caller_identity: &str) -> Result<()> {
+        let capability_id = selected.meta.id.to_string();
+        if let Some(limit) = &selected.meta.rate_limit {
+            if let Err(retry_after) = self.caller_limits.try_acquire(&capability_id, caller_identity, limit) {
+                return Err(EnterpriseError::ResourceLimit(format!(
+                    "caller {} exceeded rate limit for {}; retry in {}ms",
+                    caller_identity, capability_id, retry_after.as_millis()
+                )).into());
+            }
+        }
 
-        // Acquire resource budget
-        let pools = self.resource_pools.lock().await;
-        let pool = pools.get(capability_id)
+        // Fail fast on a version whose backend keeps failing
+        if let Err(retry_after) = self.breakers.try_admit(&capability_id, &selected.meta.version) {
+            return Err(EnterpriseError::ResourceLimit(format!(
+                "circuit open for {}@{}; retry in {}ms",
+                capability_id, selected.meta.version, retry_after.as_millis()
+            )).into());
+        }
+        Ok(())
+    }
+
+    /// Budget and timeout for one execution of `selected`
+    async fn acquire(
+        &self,
+        selected: &RegisteredCapability,
+        caller_identity: &str,
+        auth_claims: &[String],
+        deadline: Option<Duration>,
+        span: &tracing::Span,
+    ) -> Result<(ResourceBudget, ExecutionTimeout)> {
+        let pool = self.resource_pools.lock().await
+            .get(&selected.meta.id.to_string())
+            .cloned()
             .context("Resource pool missing")?;
 
-        let budget = pool.allocate(
-            context.caller_identity.clone(),
-            context.auth_claims.clone(),
-        ).await?;
+        let wait_start = Instant::now();
+        let budget = pool.allocate(caller_identity.to_string(), auth_claims.to_vec())
+            .instrument(span.clone())
+            .await?;
+        span.record("resource_wait_ms", wait_start.elapsed().as_millis() as u64);
 
-        // Execute with timeout
-        let result = tokio::time::timeout(
-            Duration::from_secs(pool.timeout_secs),
-            selected.1.execute(params, ExecutionContext {
-                resource_budget: budget,
-                ..context
+        let pool_default = Duration::from_secs(pool.timeout_secs);
+        let max = self.max_deadline.unwrap_or(DEFAULT_MAX_DEADLINE);
+        let duration = effective_timeout(pool_default, deadline, max);
+        let timeout = ExecutionTimeout {
+            duration,
+            caller_bound: deadline.is_some_and(|requested| requested < pool_default),
+        };
+        span.record("timeout_ms", duration.as_millis() as u64);
+        let source = match deadline {
+            None => "pool",
+            Some(requested) if requested > max => "clamped",
+            Some(_) => "caller",
+        };
+        timeout_metric().with_label_values(&[source]).observe(duration.as_secs_f64());
+        Ok((budget, timeout))
+    }
+
+    /// Instance to serve one execution of `selected`, warmed up now if the
+    /// capability has a warm pool and none is idle
+    async fn checkout(&self, selected: &RegisteredCapability) -> Result<Instance> {
+        match &selected.warm {
+            Some(pool) => pool.checkout(&selected.capability).await,
+            None => Ok(Instance::unpooled(selected.capability.clone())),
+        }
+    }
+
+    /// Number of executions currently running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.count.load(Ordering::SeqCst)
+    }
+
+    /// Stop accepting executions and drain running ones
+    ///
+    /// Waits up to `grace` for in-flight executions to release their budgets,
+    /// then aborts the remainder. Returns the number of aborted executions.
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self, grace: Duration) -> usize {
+        self.shutting_down.store(true, Ordering::SeqCst);
+
+        let drained = async {
+            loop {
+                let notified = self.in_flight.drained.notified();
+                if self.in_flight() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        };
+
+        if tokio::time::timeout(grace, drained).await.is_ok() {
+            info!("Capability registry drained");
+            return 0;
+        }
+
+        let stragglers = self.in_flight();
+        warn!(stragglers, "Grace period elapsed, aborting in-flight executions");
+        self.abort.cancel();
+        stragglers
+    }
+}
+
+#[async_trait]
+impl CapabilityInvoker for CapabilityRegistry {
+    async fn invoke(
+        &self,
+        caller: &CallerContext,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+    ) -> std::result::Result<serde_json::Value, EnterpriseError> {
+        self.execute_traced(
+            capability_id,
+            version,
+            params,
+            caller.identity.clone(),
+            caller.claims.clone(),
+            child_trace(None),
+            None,
+        )
+        .await
+        .map_err(into_enterprise_error)
+    }
+}
+
+/// A streaming execution and everything it holds until it ends
+struct BudgetedStream {
+    breakers: Arc<CircuitBreakers>,
+    abort: CancellationToken,
+    chunks: CapabilityStream,
+    capability_id: String,
+    version: semver::Version,
+    deadline: tokio::time::Instant,
+    /// The deadline is the caller's, shorter than the pool default
+    caller_bound: bool,
+    produced: u64,
+    span: tracing::Span,
+    /// Taken when the stream ends
+    audit: Option<StreamAudit>,
+    _budget: ResourceBudget,
+    instance: Instance,
+    _in_flight: InFlightGuard,
+}
+
+impl BudgetedStream {
+    /// Next chunk and the state to continue from; `None` state ends the
+    /// stream after this chunk, releasing the budget
+    async fn next(mut self) -> Option<(std::result::Result<serde_json::Value, EnterpriseError>, Option<Self>)> {
+        let (mut panicked, mut aborted, mut timed_out) = (false, false, false);
+        let chunk = tokio::select! {
+            chunk = AssertUnwindSafe(self.chunks.next()).catch_unwind() => chunk.unwrap_or_else(|payload| {
+                panicked = true;
+                Some(Err(capability_panic(&self.capability_id, &*payload)))
             }),
-        ).await??;
+            _ = tokio::time::sleep_until(self.deadline) => {
+                timed_out = true;
+                Some(Err(EnterpriseError::ResourceLimit("capability execution timed out".into())))
+            }
+            _ = self.abort.cancelled() => {
+                aborted = true;
+                Some(Err(EnterpriseError::ResourceLimit("execution aborted by registry shutdown".into())))
+            }
+        };
+        match chunk {
+            Some(Ok(chunk)) => {
+                self.produced += 1;
+                Some((Ok(chunk), Some(self)))
+            }
+            // Counted against the breaker, and the instance kept out of its
+            // pool, as in `invoke_selected`
+            Some(Err(error)) => {
+                self.instance.discard();
+                let counted = if aborted || (timed_out && self.caller_bound) {
+                    None
+                } else {
+                    Some(!(timed_out || panicked || is_backend_error(&error)))
+                };
+                if let Some(success) = counted {
+                    self.breakers.record(&self.capability_id, &self.version, success);
+                }
+                let _ = self.finish(enterprise_outcome(&error)).await;
+                Some((Err(error), None))
+            }
+            None => {
+                self.breakers.record(&self.capability_id, &self.version, true);
+                self.span.record("chunks", self.produced);
+                // Under a fail-closed policy an unrecorded stream ends in error
+                match self.finish(ExecutionOutcome::Success).await {
+                    Ok(()) => None,
+                    Err(_) => Some((Err(EnterpriseError::CriticalFailure), None)),
+                }
+            }
+        }
+    }
 
-        Ok(result)
+    async fn finish(&mut self, outcome: ExecutionOutcome) -> Result<()> {
+        match self.audit.take() {
+            Some(audit) => audit.finish(outcome).await,
+            None => Ok(()),
+        }
     }
 }
 
+/// Audit record of a streaming execution, written when it ends or, if the
+/// caller abandons it, when it is dropped
+struct StreamAudit {
+    /// Taken once the record is written
+    log: Option<Arc<AuditLog>>,
+    caller: String,
+    capability_id: String,
+    version: Option<semver::Version>,
+    params_hash: [u8; 32],
+    started: Instant,
+}
+
+impl StreamAudit {
+    async fn finish(mut self, outcome: ExecutionOutcome) -> Result<()> {
+        match self.log.take() {
+            Some(log) => write_audit(&log, self.record(outcome)).await,
+            None => Ok(()),
+        }
+    }
+
+    fn record(&self, outcome: ExecutionOutcome) -> ExecutionRecord {
+        ExecutionRecord {
+            caller: self.caller.clone(),
+            capability_id: self.capability_id.clone(),
+            version: self.version.clone(),
+            params_hash: self.params_hash,
+            outcome,
+            duration: self.started.elapsed(),
+            timestamp: now_millis(),
+        }
+    }
+}
+
+impl Drop for StreamAudit {
+    fn drop(&mut self) {
+        let Some(log) = self.log.take() else { return };
+        let record = self.record(ExecutionOutcome::Failed { error: "stream dropped before it ended".into() });
+        // Drop cannot wait on the sink, so the write finishes in the background
+        match tokio::runtime::Handle::try_current() {
+            // Nobody is left to fail, so a refusal is only logged
+            Ok(runtime) => drop(runtime.spawn(async move { let _ = write_audit(&log, record).await; })),
+            Err(_) => warn!(capability_id = %record.capability_id, "No runtime to write an abandoned stream's audit record"),
+        }
+    }
+}
+
+/// Sign and write `record`; a refusal is logged, and fails the execution
+/// only under a fail-closed policy
+async fn write_audit(audit: &AuditLog, record: ExecutionRecord) -> Result<()> {
+    let capability_id = record.capability_id.clone();
+    match audit.record(record).await {
+        Ok(()) => Ok(()),
+        Err(error) if audit.fail_closed() => {
+            error!(capability_id, %error, "Failed to write execution audit record; failing the execution");
+            Err(error.context("execution audit record could not be written"))
+        }
+        Err(error) => {
+            warn!(capability_id, %error, "Failed to write execution audit record");
+            Ok(())
+        }
+    }
+}
+
+/// Audit outcome of an execution that failed with `error`
+fn error_outcome(error: &anyhow::Error) -> ExecutionOutcome {
+    match error.downcast_ref::<EnterpriseError>() {
+        Some(error) => enterprise_outcome(error),
+        None => ExecutionOutcome::Failed { error: error.to_string() },
+    }
+}
+
+fn enterprise_outcome(error: &EnterpriseError) -> ExecutionOutcome {
+    match error {
+        EnterpriseError::AccessViolation { reason, .. } => ExecutionOutcome::Denied { reason: reason.clone() },
+        _ => ExecutionOutcome::Failed { error: error.to_string() },
+    }
+}
+
+/// Milliseconds since the Unix epoch
+fn now_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Log a capability's panic, reporting it to the caller as a failure of
+/// the capability alone
+fn capability_panic(capability_id: &str, payload: &(dyn Any + Send)) -> EnterpriseError {
+    let message = payload.downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>");
+    error!(capability_id, panic = message, "Capability panicked");
+    EnterpriseError::CriticalFailure
+}
+
+/// Surface registry failures as the agent-facing error type
+fn into_enterprise_error(error: anyhow::Error) -> EnterpriseError {
+    let error = match error.downcast::<EnterpriseError>() {
+        Ok(e) => return e,
+        Err(error) => error,
+    };
+    if error.is::<tokio::time::error::Elapsed>() {
+        return EnterpriseError::ResourceLimit("capability execution timed out".into());
+    }
+    warn!(%error, "Capability invocation failed");
+    EnterpriseError::CriticalFailure
+}
+
+/// Refuse a caller missing any claim `selected` requires
+fn check_claims(selected: &RegisteredCapability, caller_identity: &str, auth_claims: &[String]) -> Result<()> {
+    if let Some(missing) = selected.meta.required_claims.iter()
+        .find(|claim| !auth_claims.contains(claim))
+    {
+        return Err(EnterpriseError::AccessViolation {
+            module: "capability",
+            reason: format!("caller {} lacks claim {}", caller_identity, missing),
+        }.into());
+    }
+    Ok(())
+}
+
+/// Timeout an execution runs under, and whether the caller shortened it
+#[derive(Debug, Clone, Copy)]
+struct ExecutionTimeout {
+    duration: Duration,
+    /// The caller's deadline is shorter than the call would otherwise get;
+    /// running into it says nothing about the backend
+    caller_bound: bool,
+}
+
+/// Whether a failed execution is the backend's fault
+///
+/// Errors blaming the request or caller (denied access, malformed or
+/// tampered input, a skewed clock) are business errors; anything else,
+/// including errors that are not an `EnterpriseError`, is infrastructure.
+fn is_backend_failure(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<EnterpriseError>().map_or(true, is_backend_error)
+}
+
+fn is_backend_error(error: &EnterpriseError) -> bool {
+    !matches!(
+        error,
+        EnterpriseError::AuthError(_)
+            | EnterpriseError::AccessViolation { .. }
+            | EnterpriseError::ProtocolError
+            | EnterpriseError::IntegrityError
+            | EnterpriseError::ClockSkew(_)
+    )
+}
+
+/// Timeout an execution runs under: the requested deadline, never beyond
+/// `max`, else the pool default
+fn effective_timeout(pool_default: Duration, requested: Option<Duration>, max: Duration) -> Duration {
+    requested.map_or(pool_default, |requested| requested.min(max))
+}
+
+/// Effective timeout of every execution, by whether it is the pool
+/// default, the caller's deadline, or the caller's deadline clamped to the
+/// registry's maximum; registered once per process
+fn timeout_metric() -> &'static HistogramVec {
+    static METRIC: OnceLock<HistogramVec> = OnceLock::new();
+    METRIC.get_or_init(|| prometheus::register_histogram_vec!(
+        "capability_execution_timeout_seconds",
+        "Timeout capability executions run under",
+        &["source"],
+        vec![0.1, 0.5, 1.0, 5.0, 30.0, 120.0, 600.0]
+    ).unwrap())
+}
+
+/// Span for an execution nested under `parent`, else under the task's
+/// current trace (such as the RPC being served), else a new trace's root
+fn child_trace(parent: Option<&TraceContext>) -> TraceContext {
+    parent.cloned()
+        .or_else(TraceContext::current)
+        .map_or_else(TraceContext::root, |parent| parent.child())
+}
+
 /// Resource isolation pool
 struct ResourcePool {
     semaphore: Arc<Semaphore>,
     cpu_cores: f32,
     memory_mb: u32,
     timeout_secs: u64,
+    /// Set for weighted-fair pools; FIFO pools take the semaphore directly
+    fair: Option<FairScheduler>,
 }
 
 impl ResourcePool {
-    fn new(memory_mb: u32, cpu_cores: f32) -> Self {
+    fn new(memory_mb: u32, cpu_cores: f32, scheduler: &PoolScheduler) -> Self {
+        let semaphore = Arc::new(Semaphore::new(cpu_cores as usize));
+        let fair = match scheduler {
+            PoolScheduler::Fifo => None,
+            PoolScheduler::WeightedFair(config) => Some(FairScheduler::new(semaphore.clone(), config.clone())),
+        };
         Self {
-            semaphore: Arc::new(Semaphore::new(cpu_cores as usize)),
+            semaphore,
             cpu_cores,
             memory_mb,
             timeout_secs: 30, // Default timeout
+            fair,
         }
     }
 
     async fn allocate(&self, caller: String, claims: Vec<String>) -> Result<ResourceBudget> {
-        let permit = self.semaphore.clone()
-            .acquire_owned()
-            .await
-            .context("Resource allocation timeout")?;
+        let permit = match &self.fair {
+            Some(fair) => fair.acquire(&caller).await.context("Resource allocation timeout")?,
+            None => self.semaphore.clone()
+                .acquire_owned()
+                .await
+                .context("Resource allocation timeout")?,
+        };
 
         Ok(ResourceBudget {
             semaphore: self.semaphore.clone(),
             cpu_cores: self.cpu_cores,
-            _guard: permit,
+            _guard: Arc::new(permit),
         })
     }
 }
@@ -177,34 +1265,171 @@ mod tests {
     use super::*;
 
     struct TestCapability;
-    
+
+    struct SlowCapability(Duration);
+
     #[async_trait]
-    impl EnterpriseCapability for TestCapability {
+    impl EnterpriseCapability for SlowCapability {
         async fn execute(
             &self,
             _params: serde_json::Value,
             _context: ExecutionContext,
         ) -> Result<serde_json::Value> {
-            Ok(serde_json::json!({"status": "success"}))
+            tokio::time::sleep(self.0).await;
+            Ok(serde_json::json!({"status": "done"}))
         }
     }
 
-    #[tokio::test]
-    async fn test_capability_lifecycle() {
-        let registry = CapabilityRegistry::default();
-        let meta = CapabilityMeta {
+    fn test_meta() -> CapabilityMeta {
+        CapabilityMeta {
             id: Uuid::new_v4(),
             version: semver::Version::parse("1.0.0").unwrap(),
-            required_claims: vec!["admin".into()],
+            required_claims: vec![],
             resource_limits: ResourceLimits {
-                max_memory_mb: 1024,
+                max_memory_mb: 256,
                 max_cpu_cores: 2.0,
                 timeout_secs: 5,
+                scheduler: PoolScheduler::Fifo,
             },
             dependencies: vec![],
-        };
+            cacheable: None,
+            rate_limit: None,
+            params_defaults: serde_json::Value::Null,
+            params_types: BTreeMap::new(),
+            warm_pool_size: None,
+        }
+    }
 
-        registry.register(meta.clone(), Arc::new(TestCapability))
+    #[derive(Default)]
+    struct CountingCapability(AtomicUsize);
+
+    #[async_trait]
+    impl EnterpriseCapability for CountingCapability {
+        async fn execute(
+            &self,
+            params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(serde_json::json!({"calls": calls, "echo": params}))
+        }
+    }
+
+    async fn test_context(caller: &str) -> ExecutionContext {
+        ExecutionContext {
+            caller_identity: caller.into(),
+            auth_claims: vec![],
+            resource_budget: ResourceBudget {
+                semaphore: Arc::new(Semaphore::new(1)),
+                cpu_cores: 1.0,
+                _guard: Arc::new(Arc::new(Semaphore::new(1)).acquire_owned().await.unwrap()),
+            },
+            trace: None,
+            deadline: None,
+            expires_at: None,
+            idempotency_key: None,
+        }
+    }
+
+    /// Reports a failed external dependency
+    struct UnreachableBackend;
+
+    #[async_trait]
+    impl EnterpriseCapability for UnreachableBackend {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            Ok(serde_json::json!({"status": "unreachable"}))
+        }
+
+        async fn health(&self) -> CapabilityHealth {
+            CapabilityHealth::Unhealthy { message: "model endpoint unreachable".into() }
+        }
+    }
+
+    /// Returns the trace context it was executed under
+    struct TraceEcho;
+
+    #[async_trait]
+    impl EnterpriseCapability for TraceEcho {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            Ok(serde_json::to_value(&context.trace)?)
+        }
+    }
+
+    /// Returns the timeout it was executed under, in milliseconds
+    struct DeadlineEcho;
+
+    #[async_trait]
+    impl EnterpriseCapability for DeadlineEcho {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            Ok(serde_json::json!(context.deadline.map(|deadline| deadline.as_millis() as u64)))
+        }
+    }
+
+    /// Calls its dependency after `delay` and reports both trace contexts
+    struct WithDependency {
+        registry: Arc<CapabilityRegistry>,
+        dependency: CapabilityRef,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl EnterpriseCapability for WithDependency {
+        async fn execute(
+            &self,
+            params: serde_json::Value,
+            context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            tokio::time::sleep(self.delay).await;
+            let child = self.registry.execute_dependency(&context, &self.dependency, params).await?;
+            Ok(serde_json::json!({"own": context.trace, "child": child}))
+        }
+    }
+    
+    #[async_trait]
+    impl EnterpriseCapability for TestCapability {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            Ok(serde_json::json!({"status": "success"}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capability_lifecycle() {
+        let registry = CapabilityRegistry::default();
+        let meta = CapabilityMeta {
+            id: Uuid::new_v4(),
+            version: semver::Version::parse("1.0.0").unwrap(),
+            required_claims: vec!["admin".into()],
+            resource_limits: ResourceLimits {
+                max_memory_mb: 1024,
+                max_cpu_cores: 2.0,
+                timeout_secs: 5,
+                scheduler: PoolScheduler::Fifo,
+            },
+            dependencies: vec![],
+            cacheable: None,
+            rate_limit: None,
+            params_defaults: serde_json::Value::Null,
+            params_types: BTreeMap::new(),
+            warm_pool_size: None,
+        };
+
+        registry.register(meta.clone(), Arc::new(TestCapability))
             .await
             .unwrap();
 
@@ -218,11 +1443,1138 @@ mod tests {
                 resource_budget: ResourceBudget {
                     semaphore: Arc::new(Semaphore::new(1)),
                     cpu_cores: 1.0,
-                    _guard: Semaphore::new(1).acquire_owned().await.unwrap(),
+                    _guard: Arc::new(Arc::new(Semaphore::new(1)).acquire_owned().await.unwrap()),
                 },
+                trace: None,
+                deadline: None,
+                expires_at: None,
+                idempotency_key: None,
             },
         ).await.unwrap();
 
         assert_eq!(result, serde_json::json!({"status": "success"}));
     }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_in_flight() {
+        let registry = Arc::new(CapabilityRegistry::default());
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(SlowCapability(Duration::from_millis(200))))
+            .await
+            .unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let running = {
+            let (registry, id, req) = (registry.clone(), id.clone(), req.clone());
+            tokio::spawn(async move {
+                registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(registry.in_flight(), 1);
+
+        let shutdown = {
+            let registry = registry.clone();
+            tokio::spawn(async move { registry.shutdown(Duration::from_secs(2)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let rejected = registry.execute(&id, &req, serde_json::Value::Null, test_context("b").await)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            rejected.downcast_ref::<EnterpriseError>(),
+            Some(EnterpriseError::ResourceLimit(_))
+        ));
+        // The refused call counted itself in flight only momentarily
+        assert_eq!(registry.in_flight(), 1);
+
+        assert_eq!(running.await.unwrap().unwrap(), serde_json::json!({"status": "done"}));
+        assert_eq!(shutdown.await.unwrap(), 0);
+        assert_eq!(registry.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_short_deadline_fires_before_pool_default() {
+        let registry = CapabilityRegistry::default();
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(SlowCapability(Duration::from_secs(5)))).await.unwrap();
+
+        let mut context = test_context("probe").await;
+        context.deadline = Some(Duration::from_millis(50));
+        let started = Instant::now();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let error = registry.execute(&meta.id.to_string(), &req, serde_json::Value::Null, context)
+            .await
+            .unwrap_err();
+        assert!(error.is::<tokio::time::error::Elapsed>(), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_long_deadline_is_clamped_to_max() {
+        let registry = CapabilityRegistry::default().with_max_deadline(Duration::from_secs(2));
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(DeadlineEcho)).await.unwrap();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+
+        let mut batch = test_context("batch").await;
+        batch.deadline = Some(Duration::from_secs(3600));
+        assert_eq!(registry.execute(&id, &req, serde_json::Value::Null, batch).await.unwrap(), 2_000);
+
+        // Without an override the pool default applies; the cap is on callers
+        let result = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap();
+        assert_eq!(result, 30_000);
+    }
+
+    #[tokio::test]
+    async fn test_effective_timeout_is_recorded_by_source() {
+        let registry = CapabilityRegistry::default().with_max_deadline(Duration::from_secs(2));
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(DeadlineEcho)).await.unwrap();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let count = |source| timeout_metric().with_label_values(&[source]).get_sample_count();
+        let before = [count("pool"), count("caller"), count("clamped")];
+
+        registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap();
+        let probe = ExecutionContext { deadline: Some(Duration::from_millis(500)), ..test_context("a").await };
+        registry.execute(&id, &req, serde_json::Value::Null, probe).await.unwrap();
+        let batch = ExecutionContext { deadline: Some(Duration::from_secs(3600)), ..test_context("a").await };
+        registry.execute(&id, &req, serde_json::Value::Null, batch).await.unwrap();
+
+        // Other tests record too, so only growth is checked
+        let after = [count("pool"), count("caller"), count("clamped")];
+        assert!(before.iter().zip(&after).all(|(before, after)| after > before), "{:?} -> {:?}", before, after);
+    }
+
+    #[tokio::test]
+    async fn test_dependency_gets_the_time_its_parent_has_left() {
+        let registry = Arc::new(CapabilityRegistry::default());
+        let leaf = test_meta();
+        registry.register(leaf.clone(), Arc::new(DeadlineEcho)).await.unwrap();
+        let dependency = CapabilityRef {
+            name: leaf.id.to_string(),
+            version_req: semver::VersionReq::parse("^1").unwrap(),
+        };
+        let parent = CapabilityMeta { dependencies: vec![dependency.clone()], ..test_meta() };
+        registry.register(parent.clone(), Arc::new(WithDependency {
+            registry: registry.clone(),
+            dependency,
+            delay: Duration::from_millis(100),
+        })).await.unwrap();
+
+        let context = ExecutionContext { deadline: Some(Duration::from_secs(1)), ..test_context("a").await };
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let result = registry.execute(&parent.id.to_string(), &req, serde_json::Value::Null, context).await.unwrap();
+        let child = result["child"].as_u64().unwrap();
+        assert!((1..=900).contains(&child), "child ran under {}ms", child);
+    }
+
+    #[tokio::test]
+    async fn test_result_cache_hit_and_expiry() {
+        let registry = CapabilityRegistry::default();
+        let capability = Arc::new(CountingCapability::default());
+        let meta = CapabilityMeta {
+            cacheable: Some(Duration::from_millis(50)),
+            ..test_meta()
+        };
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let params = serde_json::json!({"sku": "A-1"});
+
+        let first = registry.execute(&id, &req, params.clone(), test_context("a").await).await.unwrap();
+        let second = registry.execute(&id, &req, params.clone(), test_context("a").await).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(capability.0.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        registry.execute(&id, &req, params.clone(), test_context("a").await).await.unwrap();
+        assert_eq!(capability.0.load(Ordering::SeqCst), 2);
+
+        // Yanking the version drops its cached results
+        registry.deregister(&id, &meta.version).await.unwrap();
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+        registry.execute(&id, &req, params, test_context("a").await).await.unwrap();
+        assert_eq!(capability.0.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_result_cache_bounded_and_not_revived_by_in_flight_calls() {
+        let registry = Arc::new(CapabilityRegistry::default().with_result_cache_capacity(1));
+        let meta = CapabilityMeta { cacheable: Some(Duration::from_secs(60)), ..test_meta() };
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let execute = |sku: &str| {
+            let (registry, id, req) = (registry.clone(), id.clone(), req.clone());
+            let params = serde_json::json!({"sku": sku});
+            async move { registry.execute(&id, &req, params, test_context("a").await).await.unwrap() }
+        };
+
+        // A second result pushes the first out
+        let counting = Arc::new(CountingCapability::default());
+        registry.register(meta.clone(), counting.clone()).await.unwrap();
+        execute("A-1").await;
+        execute("B-2").await;
+        execute("B-2").await;
+        assert_eq!(counting.0.load(Ordering::SeqCst), 2);
+        execute("A-1").await;
+        assert_eq!(counting.0.load(Ordering::SeqCst), 3);
+
+        // A call still running when its version is yanked leaves nothing
+        // behind for the version registered in its place
+        registry.deregister(&id, &meta.version).await.unwrap();
+        let slow = Arc::new(SlowCounter::default());
+        registry.register(meta.clone(), slow.clone()).await.unwrap();
+        let running = tokio::spawn(execute("C-3"));
+        while slow.0.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        registry.deregister(&id, &meta.version).await.unwrap();
+        let replacement = Arc::new(CountingCapability::default());
+        registry.register(meta.clone(), replacement.clone()).await.unwrap();
+        running.await.unwrap();
+
+        execute("C-3").await;
+        assert_eq!(replacement.0.load(Ordering::SeqCst), 1);
+    }
+
+    async fn keyed_context(caller: &str, key: &str) -> ExecutionContext {
+        ExecutionContext { idempotency_key: Some(key.into()), ..test_context(caller).await }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_idempotency_key_returns_recorded_result() {
+        let registry = CapabilityRegistry::default();
+        let capability = Arc::new(CountingCapability::default());
+        let meta = test_meta();
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let params = serde_json::json!({"charge": 100});
+
+        let first = registry.execute(&id, &req, params.clone(), keyed_context("a", "order-7").await).await.unwrap();
+        let retry = registry.execute(&id, &req, params.clone(), keyed_context("a", "order-7").await).await.unwrap();
+        assert_eq!(first, retry);
+        assert_eq!(capability.0.load(Ordering::SeqCst), 1);
+
+        // Another key, or the same key from another caller, runs again
+        registry.execute(&id, &req, params.clone(), keyed_context("a", "order-8").await).await.unwrap();
+        registry.execute(&id, &req, params, keyed_context("b", "order-7").await).await.unwrap();
+        assert_eq!(capability.0.load(Ordering::SeqCst), 3);
+
+        // Reusing a key for different params is refused, not answered
+        let error = registry.execute(&id, &req, serde_json::json!({"charge": 900}), keyed_context("a", "order-7").await)
+            .await
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::ProtocolError)));
+        assert_eq!(capability.0.load(Ordering::SeqCst), 3);
+    }
+
+    /// Counts calls that take a while to complete
+    #[derive(Default)]
+    struct SlowCounter(AtomicUsize);
+
+    #[async_trait]
+    impl EnterpriseCapability for SlowCounter {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(serde_json::json!({"calls": calls}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_with_one_key_execute_once() {
+        let registry = CapabilityRegistry::default();
+        let capability = Arc::new(SlowCounter::default());
+        let meta = test_meta();
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let (first, second) = tokio::join!(
+            registry.execute(&id, &req, serde_json::Value::Null, keyed_context("a", "transfer-1").await),
+            registry.execute(&id, &req, serde_json::Value::Null, keyed_context("a", "transfer-1").await),
+        );
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert_eq!(capability.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_caller_over_rate_is_throttled_independently() {
+        let registry = CapabilityRegistry::default();
+        let capability = Arc::new(CountingCapability::default());
+        let meta = CapabilityMeta {
+            rate_limit: Some(CallerRateLimit::new(0.01, 2).unwrap()),
+            ..test_meta()
+        };
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        for _ in 0..2 {
+            registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap();
+        }
+        let throttled = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            throttled.downcast_ref::<EnterpriseError>(),
+            Some(EnterpriseError::ResourceLimit(_))
+        ));
+
+        registry.execute(&id, &req, serde_json::Value::Null, test_context("b").await).await.unwrap();
+        assert_eq!(capability.0.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_defaults_fill_in_and_numeric_strings_coerce() {
+        let registry = CapabilityRegistry::default();
+        let meta = CapabilityMeta {
+            params_defaults: serde_json::json!({"temperature": 0.7, "sampling": {"top_k": 40}}),
+            params_types: [
+                ("max_tokens".to_string(), ParamType::Integer),
+                ("temperature".to_string(), ParamType::Number),
+            ].into_iter().collect(),
+            ..test_meta()
+        };
+        registry.register(meta.clone(), Arc::new(CountingCapability::default())).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let params = serde_json::json!({"max_tokens": "256", "sampling": {"top_p": 0.9}});
+        let result = registry.execute(&id, &req, params, test_context("a").await).await.unwrap();
+        assert_eq!(result["echo"], serde_json::json!({
+            "max_tokens": 256,
+            "temperature": 0.7,
+            "sampling": {"top_k": 40, "top_p": 0.9},
+        }));
+
+        // Caller-supplied values win over defaults and are coerced too
+        let params = serde_json::json!({"max_tokens": 8, "temperature": "0.2"});
+        let result = registry.execute(&id, &req, params, test_context("a").await).await.unwrap();
+        assert_eq!(result["echo"]["temperature"], serde_json::json!(0.2));
+    }
+
+    #[tokio::test]
+    async fn test_uncoercible_param_is_protocol_error_naming_field() {
+        let registry = CapabilityRegistry::default();
+        let capability = Arc::new(CountingCapability::default());
+        let meta = CapabilityMeta {
+            params_types: [("max_tokens".to_string(), ParamType::Integer)].into_iter().collect(),
+            ..test_meta()
+        };
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let params = serde_json::json!({"max_tokens": "lots"});
+        let error = registry.execute(&meta.id.to_string(), &req, params, test_context("a").await)
+            .await
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::ProtocolError)));
+        assert!(error.to_string().contains("max_tokens"), "{}", error);
+        assert_eq!(capability.0.load(Ordering::SeqCst), 0);
+    }
+
+    #[derive(Default)]
+    struct MemorySink(std::sync::Mutex<Vec<SignedExecutionRecord>>);
+
+    #[async_trait]
+    impl AuditSink for MemorySink {
+        async fn write(&self, record: SignedExecutionRecord) -> Result<()> {
+            self.0.lock().unwrap().push(record);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_success_and_denial_are_audited() {
+        let key = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng);
+        let public = key.public;
+        let sink = Arc::new(MemorySink::default());
+        let policy = AuditPolicy::new(b"audit params secret");
+        let expected_hash = policy.params_hash(&serde_json::json!({"patient": "MRN-0042"}));
+        let registry = CapabilityRegistry::default().with_audit(sink.clone(), key, policy);
+        let meta = CapabilityMeta { required_claims: vec!["HIPAA".into()], ..test_meta() };
+        registry.register(meta.clone(), Arc::new(TestCapability)).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let params = serde_json::json!({"patient": "MRN-0042"});
+        let mut allowed = test_context("clinician").await;
+        allowed.auth_claims = vec!["HIPAA".into()];
+        registry.execute(&id, &req, params.clone(), allowed).await.unwrap();
+        registry.execute(&id, &req, params.clone(), test_context("intruder").await).await.unwrap_err();
+
+        let records = sink.0.lock().unwrap().clone();
+        assert_eq!(records.len(), 2);
+        for signed in &records {
+            assert!(signed.verify(&public));
+            assert_eq!(signed.record.capability_id, id);
+            assert_eq!(signed.record.version, Some(meta.version.clone()));
+            assert_eq!(signed.record.params_hash, expected_hash);
+            // Keyed, so the bare digest of a guessed value does not match
+            assert_ne!(signed.record.params_hash, params_digest(&params));
+            assert!(!serde_json::to_string(signed).unwrap().contains("MRN-0042"));
+        }
+        assert_eq!(records[0].record.caller, "clinician");
+        assert_eq!(records[0].record.outcome, ExecutionOutcome::Success);
+        assert_eq!(records[1].record.caller, "intruder");
+        assert!(matches!(&records[1].record.outcome, ExecutionOutcome::Denied { reason } if reason.contains("HIPAA")));
+
+        // Any alteration breaks the signature
+        let mut tampered = records[1].clone();
+        tampered.record.outcome = ExecutionOutcome::Success;
+        assert!(!tampered.verify(&public));
+    }
+
+    /// Refuses every record
+    struct DownSink;
+
+    #[async_trait]
+    impl AuditSink for DownSink {
+        async fn write(&self, _record: SignedExecutionRecord) -> Result<()> {
+            anyhow::bail!("audit store unreachable")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refused_audit_fails_execution_only_when_fail_closed() {
+        let meta = test_meta();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let registry = |policy: AuditPolicy| {
+            let meta = meta.clone();
+            async move {
+                let key = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng);
+                let registry = CapabilityRegistry::default().with_audit(Arc::new(DownSink), key, policy);
+                registry.register(meta, Arc::new(TestCapability)).await.unwrap();
+                registry
+            }
+        };
+
+        let open = registry(AuditPolicy::new(b"secret")).await;
+        open.execute(&id, &req, serde_json::json!({}), test_context("a").await).await.unwrap();
+
+        let closed = registry(AuditPolicy::new(b"secret").fail_closed()).await;
+        let error = closed.execute(&id, &req, serde_json::json!({}), test_context("a").await).await.unwrap_err();
+        assert!(error.to_string().contains("audit"), "{}", error);
+    }
+
+    /// Returns its params as the result
+    struct ParamsEcho;
+
+    #[async_trait]
+    impl EnterpriseCapability for ParamsEcho {
+        async fn execute(
+            &self,
+            params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            Ok(params)
+        }
+    }
+
+    /// Redacts the patient from streamed params and refuses one caller
+    struct StreamGuard(&'static str);
+
+    #[async_trait]
+    impl CapabilityMiddleware for StreamGuard {
+        async fn call(
+            &self,
+            ctx: MiddlewareContext,
+            next: Next<'_>,
+        ) -> std::result::Result<serde_json::Value, EnterpriseError> {
+            next.run(ctx).await
+        }
+
+        async fn before_stream(
+            &self,
+            mut ctx: MiddlewareContext,
+        ) -> std::result::Result<MiddlewareContext, EnterpriseError> {
+            if ctx.caller_identity == self.0 {
+                return Err(EnterpriseError::AccessViolation {
+                    module: "stream_guard",
+                    reason: format!("{} may not stream", self.0),
+                });
+            }
+            ctx.params["patient"] = "redacted".into();
+            Ok(ctx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streams_are_audited_and_pass_through_middleware() {
+        let key = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng);
+        let sink = Arc::new(MemorySink::default());
+        let registry = CapabilityRegistry::default()
+            .with_audit(sink.clone(), key, AuditPolicy::new(b"audit params secret"))
+            .with_middleware(Arc::new(StreamGuard("intruder")));
+        let meta = CapabilityMeta { required_claims: vec!["HIPAA".into()], ..test_meta() };
+        registry.register(meta.clone(), Arc::new(ParamsEcho)).await.unwrap();
+        let tokens = test_meta();
+        registry.register(tokens.clone(), Arc::new(TokenStream(&["a", "b"]))).await.unwrap();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let params = serde_json::json!({"patient": "MRN-0042"});
+        let with_claim = |caller: &'static str| async move {
+            ExecutionContext { auth_claims: vec!["HIPAA".into()], ..test_context(caller).await }
+        };
+
+        // The stream owns what it holds, so it can outlive the call that started it
+        let stream = registry.execute_stream(&id, &req, params.clone(), with_claim("clinician").await).await.unwrap();
+        let chunks = tokio::spawn(stream.collect::<Vec<_>>()).await.unwrap();
+        assert_eq!(chunks[0].as_ref().unwrap()["patient"], "redacted");
+
+        registry.execute_stream(&id, &req, params.clone(), test_context("clinician").await).await.err().unwrap();
+        registry.execute_stream(&id, &req, params.clone(), with_claim("intruder").await).await.err().unwrap();
+        let mut abandoned = registry.execute_stream(&tokens.id.to_string(), &req, params.clone(), test_context("a").await)
+            .await
+            .unwrap();
+        abandoned.next().await.unwrap().unwrap();
+        drop(abandoned);
+        while sink.0.lock().unwrap().len() < 4 {
+            tokio::task::yield_now().await;
+        }
+
+        let records: Vec<_> = sink.0.lock().unwrap().iter().map(|signed| signed.record.clone()).collect();
+        assert_eq!(records[0].outcome, ExecutionOutcome::Success);
+        assert_eq!(records[0].params_hash, AuditPolicy::new(b"audit params secret").params_hash(&params));
+        assert!(matches!(&records[1].outcome, ExecutionOutcome::Denied { reason } if reason.contains("HIPAA")));
+        assert!(matches!(&records[2].outcome, ExecutionOutcome::Denied { reason } if reason.contains("may not stream")));
+        assert_eq!(records[2].version, Some(meta.version.clone()));
+        assert_eq!(records[3].capability_id, tokens.id.to_string());
+        assert!(matches!(&records[3].outcome, ExecutionOutcome::Failed { error } if error.contains("dropped")));
+    }
+
+    #[tokio::test]
+    async fn test_dependency_span_nests_under_parent() {
+        let registry = Arc::new(CapabilityRegistry::default());
+        let leaf = test_meta();
+        registry.register(leaf.clone(), Arc::new(TraceEcho)).await.unwrap();
+
+        let dependency = CapabilityRef {
+            name: leaf.id.to_string(),
+            version_req: semver::VersionReq::parse("^1").unwrap(),
+        };
+        let parent = CapabilityMeta { dependencies: vec![dependency.clone()], ..test_meta() };
+        registry.register(parent.clone(), Arc::new(WithDependency {
+            registry: registry.clone(),
+            dependency,
+            delay: Duration::ZERO,
+        })).await.unwrap();
+
+        let caller = TraceContext::root();
+        let mut context = test_context("a").await;
+        context.trace = Some(caller.clone());
+
+        let result = registry.execute(
+            &parent.id.to_string(),
+            &semver::VersionReq::parse("^1").unwrap(),
+            serde_json::Value::Null,
+            context,
+        ).await.unwrap();
+
+        let own: TraceContext = serde_json::from_value(result["own"].clone()).unwrap();
+        let child: TraceContext = serde_json::from_value(result["child"].clone()).unwrap();
+        assert_eq!(own.trace_id, caller.trace_id);
+        assert_eq!(own.parent_span_id.as_deref(), Some(caller.span_id.as_str()));
+        assert_eq!(child.trace_id, caller.trace_id);
+        assert_eq!(child.parent_span_id.as_deref(), Some(own.span_id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_execution_continues_the_task_trace() {
+        let registry = CapabilityRegistry::default();
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(TraceEcho)).await.unwrap();
+        let req = semver::VersionReq::parse("^1").unwrap();
+
+        // As when a coordinator RPC handler runs a capability without its own trace
+        let rpc = TraceContext::root();
+        let context = test_context("a").await;
+        let result = rpc.clone()
+            .scope(registry.execute(&meta.id.to_string(), &req, serde_json::Value::Null, context))
+            .await
+            .unwrap();
+
+        let own: TraceContext = serde_json::from_value(result).unwrap();
+        assert_eq!(own.trace_id, rpc.trace_id);
+        assert_eq!(own.parent_span_id.as_deref(), Some(rpc.span_id.as_str()));
+    }
+
+    /// Answers from a fixed value without running the capability
+    struct CachedAnswer(serde_json::Value);
+
+    #[async_trait]
+    impl CapabilityMiddleware for CachedAnswer {
+        async fn call(
+            &self,
+            _ctx: MiddlewareContext,
+            _next: Next<'_>,
+        ) -> std::result::Result<serde_json::Value, EnterpriseError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// Logs entry and exit around the rest of the chain
+    struct Recorder(&'static str, Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl CapabilityMiddleware for Recorder {
+        async fn call(
+            &self,
+            ctx: MiddlewareContext,
+            next: Next<'_>,
+        ) -> std::result::Result<serde_json::Value, EnterpriseError> {
+            self.1.lock().unwrap().push(format!("{} before", self.0));
+            let result = next.run(ctx).await;
+            self.1.lock().unwrap().push(format!("{} after", self.0));
+            result
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_short_circuits_and_runs_in_order() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let capability = Arc::new(CountingCapability::default());
+        let registry = CapabilityRegistry::default()
+            .with_middleware(Arc::new(Recorder("outer", log.clone())))
+            .with_middleware(Arc::new(Recorder("inner", log.clone())));
+        let meta = test_meta();
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+        let req = semver::VersionReq::parse("^1").unwrap();
+
+        let result = registry.execute(&meta.id.to_string(), &req, serde_json::json!({}), test_context("alice").await)
+            .await
+            .unwrap();
+        assert_eq!(result["calls"], 1);
+        assert_eq!(*log.lock().unwrap(), ["outer before", "inner before", "inner after", "outer after"]);
+
+        let cached = serde_json::json!({"cached": true});
+        let registry = CapabilityRegistry::default().with_middleware(Arc::new(CachedAnswer(cached.clone())));
+        registry.register(meta.clone(), capability.clone()).await.unwrap();
+        let result = registry.execute(&meta.id.to_string(), &req, serde_json::json!({}), test_context("alice").await)
+            .await
+            .unwrap();
+        assert_eq!(result, cached);
+        assert_eq!(capability.0.load(Ordering::SeqCst), 1);
+    }
+
+    /// Model loader whose instances each cost one initialization
+    #[derive(Default)]
+    struct ModelLoader {
+        inits: Arc<AtomicUsize>,
+    }
+
+    /// Initialized instance holding each call for `params` milliseconds
+    struct LoadedModel(usize);
+
+    #[async_trait]
+    impl EnterpriseCapability for ModelLoader {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            anyhow::bail!("calls are served by warmed instances")
+        }
+
+        async fn warm(&self) -> Result<Option<Arc<dyn EnterpriseCapability>>> {
+            let instance = self.inits.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(Arc::new(LoadedModel(instance))))
+        }
+    }
+
+    #[async_trait]
+    impl EnterpriseCapability for LoadedModel {
+        async fn execute(
+            &self,
+            params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            tokio::time::sleep(Duration::from_millis(params.as_u64().unwrap_or(0))).await;
+            Ok(serde_json::json!(self.0))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_pool_reuses_instances_and_inits_on_demand_when_exhausted() {
+        let registry = CapabilityRegistry::default();
+        let loader = ModelLoader::default();
+        let inits = loader.inits.clone();
+        let mut meta = CapabilityMeta { warm_pool_size: Some(2), ..test_meta() };
+        meta.resource_limits.max_cpu_cores = 4.0;
+        registry.register(meta.clone(), Arc::new(loader)).await.unwrap();
+        assert_eq!(inits.load(Ordering::SeqCst), 2);
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        for _ in 0..5 {
+            let instance = registry.execute(&id, &req, serde_json::json!(0), test_context("a").await).await.unwrap();
+            assert!(instance.as_u64().unwrap() < 2);
+        }
+        assert_eq!(inits.load(Ordering::SeqCst), 2);
+
+        // Three overlapping calls outnumber the two warmed instances
+        let (a, b, c) = tokio::join!(
+            registry.execute(&id, &req, serde_json::json!(50), test_context("a").await),
+            registry.execute(&id, &req, serde_json::json!(50), test_context("b").await),
+            registry.execute(&id, &req, serde_json::json!(50), test_context("c").await),
+        );
+        let mut served: Vec<_> = [a, b, c].into_iter().map(|r| r.unwrap().as_u64().unwrap()).collect();
+        served.sort();
+        assert_eq!(served, vec![0, 1, 2]);
+        assert_eq!(inits.load(Ordering::SeqCst), 3);
+
+        // The pool keeps its size; later calls still need no initialization
+        registry.execute(&id, &req, serde_json::json!(0), test_context("a").await).await.unwrap();
+        assert_eq!(inits.load(Ordering::SeqCst), 3);
+    }
+
+    /// Model loader that takes 200ms over every instance after the first
+    #[derive(Default)]
+    struct SlowLoader(AtomicUsize);
+
+    #[async_trait]
+    impl EnterpriseCapability for SlowLoader {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            anyhow::bail!("calls are served by warmed instances")
+        }
+
+        async fn warm(&self) -> Result<Option<Arc<dyn EnterpriseCapability>>> {
+            let instance = self.0.fetch_add(1, Ordering::SeqCst);
+            if instance > 0 {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            Ok(Some(Arc::new(LoadedModel(instance))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_runs_within_the_timeout_and_timed_out_instances_are_discarded() {
+        let registry = CapabilityRegistry::default();
+        let meta = CapabilityMeta { warm_pool_size: Some(1), ..test_meta() };
+        registry.register(meta.clone(), Arc::new(SlowLoader::default())).await.unwrap();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let hurried = || async {
+            ExecutionContext { deadline: Some(Duration::from_millis(20)), ..test_context("a").await }
+        };
+
+        // The warmed instance runs out of time and is dropped from the pool
+        let error = registry.execute(&id, &req, serde_json::json!(100), hurried().await).await.unwrap_err();
+        assert!(error.is::<tokio::time::error::Elapsed>(), "{}", error);
+
+        // So the next call warms one up on demand, which takes longer than it has
+        let started = Instant::now();
+        let error = registry.execute(&id, &req, serde_json::json!(0), hurried().await).await.unwrap_err();
+        assert!(error.is::<tokio::time::error::Elapsed>(), "{}", error);
+        assert!(started.elapsed() < Duration::from_millis(150), "{:?}", started.elapsed());
+
+        // A call with time to spare is served by a fresh instance
+        let served = registry.execute(&id, &req, serde_json::json!(0), test_context("a").await).await.unwrap();
+        assert_eq!(served, serde_json::json!(2));
+    }
+
+    /// Fails every call until its backend comes back
+    #[derive(Default)]
+    struct FlakyBackend {
+        up: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EnterpriseCapability for FlakyBackend {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if !self.up.load(Ordering::SeqCst) {
+                anyhow::bail!("backend connection refused");
+            }
+            Ok(serde_json::json!({"status": "recovered"}))
+        }
+    }
+
+    fn short_breaker() -> CircuitBreakerConfig {
+        CircuitBreakerConfig { failure_threshold: 3, open_cooldown: Duration::from_millis(50) }
+    }
+
+    fn is_resource_limit(error: &anyhow::Error) -> bool {
+        matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::ResourceLimit(_)))
+    }
+
+    #[tokio::test]
+    async fn test_breaker_trips_short_circuits_and_recovers_after_probe() {
+        let registry = CapabilityRegistry::default().with_circuit_breaker(short_breaker());
+        let backend = Arc::new(FlakyBackend::default());
+        let meta = test_meta();
+        registry.register(meta.clone(), backend.clone()).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        for _ in 0..3 {
+            let error = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap_err();
+            assert!(!is_resource_limit(&error), "{}", error);
+        }
+
+        // Open: refused without reaching the backend
+        let refused = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap_err();
+        assert!(is_resource_limit(&refused), "{}", refused);
+        assert!(refused.to_string().contains("circuit open"), "{}", refused);
+        assert_eq!(backend.calls.load(Ordering::SeqCst), 3);
+
+        // A failed probe re-opens the breaker at once
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(!is_resource_limit(&registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap_err()));
+        assert!(is_resource_limit(&registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap_err()));
+        assert_eq!(backend.calls.load(Ordering::SeqCst), 4);
+
+        // A successful probe closes it
+        backend.up.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        for _ in 0..3 {
+            let result = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap();
+            assert_eq!(result, serde_json::json!({"status": "recovered"}));
+        }
+        assert_eq!(backend.calls.load(Ordering::SeqCst), 7);
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_the_caller_did_not_shorten_trip_the_breaker() {
+        let registry = CapabilityRegistry::default()
+            .with_circuit_breaker(short_breaker())
+            .with_max_deadline(Duration::from_millis(10));
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(SlowCapability(Duration::from_millis(200)))).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        // The deadline asked for is longer than the pool default; it is the
+        // registry's cap that cuts the calls short
+        let patient = || async {
+            ExecutionContext { deadline: Some(Duration::from_secs(3600)), ..test_context("a").await }
+        };
+        for _ in 0..3 {
+            let error = registry.execute(&id, &req, serde_json::Value::Null, patient().await).await.unwrap_err();
+            assert!(error.is::<tokio::time::error::Elapsed>(), "{}", error);
+        }
+        let refused = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap_err();
+        assert!(is_resource_limit(&refused), "{}", refused);
+    }
+
+    #[tokio::test]
+    async fn test_caller_deadline_timeouts_do_not_trip_the_breaker() {
+        let registry = CapabilityRegistry::default().with_circuit_breaker(short_breaker());
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(SlowCapability(Duration::from_millis(200)))).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let hurried = || async {
+            ExecutionContext { deadline: Some(Duration::from_millis(10)), ..test_context("a").await }
+        };
+        // Every call still reaches the capability and runs out of its own time
+        for _ in 0..5 {
+            let error = registry.execute(&id, &req, serde_json::Value::Null, hurried().await).await.unwrap_err();
+            assert!(error.is::<tokio::time::error::Elapsed>(), "{}", error);
+        }
+    }
+
+    /// Rejects every request as malformed
+    struct Rejecting(AtomicUsize);
+
+    #[async_trait]
+    impl EnterpriseCapability for Rejecting {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(EnterpriseError::ProtocolError.into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_business_errors_do_not_trip_the_breaker() {
+        let registry = CapabilityRegistry::default().with_circuit_breaker(short_breaker());
+        let backend = Arc::new(Rejecting(AtomicUsize::new(0)));
+        let meta = test_meta();
+        registry.register(meta.clone(), backend.clone()).await.unwrap();
+
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        for _ in 0..5 {
+            let error = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap_err();
+            assert!(matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::ProtocolError)), "{}", error);
+        }
+        assert_eq!(backend.0.load(Ordering::SeqCst), 5);
+    }
+
+    /// Panics on every call
+    struct PanickingCapability;
+
+    #[async_trait]
+    impl EnterpriseCapability for PanickingCapability {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            panic!("model weights corrupted");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_capability_is_isolated_and_trips_breaker() {
+        let registry = CapabilityRegistry::default().with_circuit_breaker(short_breaker());
+        let faulty = test_meta();
+        let healthy = test_meta();
+        registry.register(faulty.clone(), Arc::new(PanickingCapability)).await.unwrap();
+        registry.register(healthy.clone(), Arc::new(TestCapability)).await.unwrap();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let pool = registry.resource_pools.lock().await[&faulty.id.to_string()].clone();
+
+        for _ in 0..3 {
+            let error = registry.execute(&faulty.id.to_string(), &req, serde_json::Value::Null, test_context("a").await)
+                .await
+                .unwrap_err();
+            assert!(matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::CriticalFailure)), "{}", error);
+            // The budget went back and nothing is left running
+            assert_eq!(pool.semaphore.available_permits(), 2);
+            assert_eq!(registry.in_flight(), 0);
+        }
+
+        // Repeated panics open the breaker
+        let refused = registry.execute(&faulty.id.to_string(), &req, serde_json::Value::Null, test_context("a").await)
+            .await
+            .unwrap_err();
+        assert!(is_resource_limit(&refused), "{}", refused);
+
+        // Everything else carries on
+        let result = registry.execute(&healthy.id.to_string(), &req, serde_json::Value::Null, test_context("a").await)
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!({"status": "success"}));
+        assert_eq!(registry.health_report().await.len(), 2);
+    }
+
+    /// Loader whose first instance initializes and every later one panics
+    #[derive(Default)]
+    struct CrashingLoader(AtomicUsize);
+
+    #[async_trait]
+    impl EnterpriseCapability for CrashingLoader {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            anyhow::bail!("calls are served by warmed instances")
+        }
+
+        async fn warm(&self) -> Result<Option<Arc<dyn EnterpriseCapability>>> {
+            match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => Ok(Some(Arc::new(LoadedModel(0)))),
+                _ => panic!("out of accelerator memory"),
+            }
+        }
+    }
+
+    /// Panics instead of passing the call on
+    struct PanickingLayer;
+
+    #[async_trait]
+    impl CapabilityMiddleware for PanickingLayer {
+        async fn call(
+            &self,
+            _ctx: MiddlewareContext,
+            _next: Next<'_>,
+        ) -> std::result::Result<serde_json::Value, EnterpriseError> {
+            panic!("layer misconfigured");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panics_in_warm_up_and_middleware_are_isolated() {
+        let registry = CapabilityRegistry::default();
+        let mut meta = CapabilityMeta { warm_pool_size: Some(1), ..test_meta() };
+        meta.resource_limits.max_cpu_cores = 4.0;
+        registry.register(meta.clone(), Arc::new(CrashingLoader::default())).await.unwrap();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+
+        // The second of two overlapping calls warms an instance up on demand
+        let (held, crashed) = tokio::join!(
+            registry.execute(&id, &req, serde_json::json!(50), test_context("a").await),
+            registry.execute(&id, &req, serde_json::json!(0), test_context("b").await),
+        );
+        assert_eq!(held.unwrap(), serde_json::json!(0));
+        let crashed = crashed.unwrap_err();
+        assert!(matches!(crashed.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::CriticalFailure)), "{}", crashed);
+        assert_eq!(registry.in_flight(), 0);
+        assert_eq!(
+            registry.execute(&id, &req, serde_json::json!(0), test_context("a").await).await.unwrap(),
+            serde_json::json!(0)
+        );
+
+        let registry = CapabilityRegistry::default().with_middleware(Arc::new(PanickingLayer));
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(TestCapability)).await.unwrap();
+        let error = registry.execute(&meta.id.to_string(), &req, serde_json::Value::Null, test_context("a").await)
+            .await
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::CriticalFailure)), "{}", error);
+        assert_eq!(registry.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_health_report_and_unhealthy_skip() {
+        let registry = CapabilityRegistry::default();
+        let stable = test_meta();
+        let broken = CapabilityMeta {
+            version: semver::Version::parse("1.1.0").unwrap(),
+            ..stable.clone()
+        };
+        registry.register(stable.clone(), Arc::new(TestCapability)).await.unwrap();
+        registry.register(broken.clone(), Arc::new(UnreachableBackend)).await.unwrap();
+
+        let report = registry.health_report().await;
+        assert_eq!(report[&format!("{}@1.0.0", stable.id)], CapabilityHealth::Healthy);
+        assert_eq!(
+            report[&format!("{}@1.1.0", stable.id)],
+            CapabilityHealth::Unhealthy { message: "model endpoint unreachable".into() }
+        );
+
+        let id = stable.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let latest = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap();
+        assert_eq!(latest, serde_json::json!({"status": "unreachable"}));
+
+        registry.set_skip_unhealthy(true);
+        let healthy = registry.execute(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap();
+        assert_eq!(healthy, serde_json::json!({"status": "success"}));
+    }
+
+    /// Streams a fixed completion token by token, dropping its context
+    /// before the first token
+    struct TokenStream(&'static [&'static str]);
+
+    #[async_trait]
+    impl EnterpriseCapability for TokenStream {
+        async fn execute(
+            &self,
+            _params: serde_json::Value,
+            _context: ExecutionContext,
+        ) -> Result<serde_json::Value> {
+            Ok(serde_json::json!(self.0.concat()))
+        }
+
+        fn execute_stream(self: Arc<Self>, _params: serde_json::Value, _context: ExecutionContext) -> CapabilityStream {
+            Box::pin(stream::iter(self.0.iter().map(|token| Ok(serde_json::json!(token)))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_holds_budget_until_it_ends() {
+        let registry = CapabilityRegistry::default();
+        let mut meta = test_meta();
+        meta.resource_limits.max_cpu_cores = 1.0;
+        registry.register(meta.clone(), Arc::new(TokenStream(&["The", " quick", " fox"]))).await.unwrap();
+        let id = meta.id.to_string();
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let pool = registry.resource_pools.lock().await[&id].clone();
+
+        let mut chunks = Box::pin(
+            registry.execute_stream(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap()
+        );
+        for expected in ["The", " quick", " fox"] {
+            assert_eq!(chunks.next().await.unwrap().unwrap(), expected);
+            assert_eq!(pool.semaphore.available_permits(), 0);
+            assert_eq!(registry.in_flight(), 1);
+        }
+        assert!(chunks.next().await.is_none());
+        assert_eq!(pool.semaphore.available_permits(), 1);
+        assert_eq!(registry.in_flight(), 0);
+
+        // A caller abandoning a stream releases the budget too
+        let mut abandoned = Box::pin(
+            registry.execute_stream(&id, &req, serde_json::Value::Null, test_context("a").await).await.unwrap()
+        );
+        abandoned.next().await.unwrap().unwrap();
+        assert_eq!(pool.semaphore.available_permits(), 0);
+        drop(abandoned);
+        assert_eq!(pool.semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_default_wraps_execute_and_times_out_as_a_whole() {
+        let registry = CapabilityRegistry::default();
+        let meta = test_meta();
+        registry.register(meta.clone(), Arc::new(TestCapability)).await.unwrap();
+        let slow = test_meta();
+        registry.register(slow.clone(), Arc::new(SlowCapability(Duration::from_secs(5)))).await.unwrap();
+        let req = semver::VersionReq::parse("^1").unwrap();
+
+        let chunks: Vec<_> = registry.execute_stream(&meta.id.to_string(), &req, serde_json::Value::Null, test_context("a").await)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].as_ref().unwrap(), &serde_json::json!({"status": "success"}));
+
+        let context = ExecutionContext { deadline: Some(Duration::from_millis(20)), ..test_context("a").await };
+        let chunks: Vec<_> = registry.execute_stream(&slow.id.to_string(), &req, serde_json::Value::Null, context)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(chunks.as_slice(), [Err(EnterpriseError::ResourceLimit(_))]), "{:?}", chunks);
+    }
+
+    #[tokio::test]
+    async fn test_agent_invokes_registered_capability() {
+        let registry = Arc::new(CapabilityRegistry::default());
+        let open = test_meta();
+        let restricted = CapabilityMeta { required_claims: vec!["HIPAA".into()], ..test_meta() };
+        registry.register(open.clone(), Arc::new(TestCapability)).await.unwrap();
+        registry.register(restricted.clone(), Arc::new(TestCapability)).await.unwrap();
+
+        let agent = nuzon_core::agent::EnterpriseAgent::new(nuzon_core::agent::AgentConfig {
+            compliance_rules: vec!["GDPR".into()],
+            ..nuzon_core::agent::AgentConfig::new(1024, 0.5, 1_000_000)
+        }).unwrap().with_capabilities(registry);
+
+        let req = semver::VersionReq::parse("^1").unwrap();
+        let result = agent.invoke_capability(&open.id.to_string(), &req, serde_json::Value::Null)
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!({"status": "success"}));
+
+        // The agent's compliance rules do not cover the restricted capability
+        assert!(matches!(
+            agent.invoke_capability(&restricted.id.to_string(), &req, serde_json::Value::Null).await,
+            Err(EnterpriseError::AccessViolation { module: "capability", .. })
+        ));
+    }
 }
@@ -0,0 +1,130 @@
+// circuit_breaker.rs - Per-Version Capability Circuit Breakers
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+
+/// When a capability version's breaker opens and how long it stays open
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive infrastructure failures, panics or pool-default timeouts
+    /// that open the breaker
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Time an open breaker refuses calls before admitting a probe
+    #[serde(default = "default_open_cooldown")]
+    pub open_cooldown: Duration,
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_open_cooldown() -> Duration {
+    Duration::from_secs(30)
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            open_cooldown: default_open_cooldown(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitState {
+    Closed,
+    Open,
+    /// Cooldown elapsed; one call at a time probes recovery
+    HalfOpen { probe_started: Instant },
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    since: Instant,
+}
+
+impl Breaker {
+    fn closed() -> Self {
+        Self { state: CircuitState::Closed, consecutive_failures: 0, since: Instant::now() }
+    }
+
+    fn transition(&mut self, state: CircuitState) {
+        self.state = state;
+        self.since = Instant::now();
+    }
+}
+
+/// Breakers for every capability version that has failed
+///
+/// A breaker opens after `failure_threshold` consecutive failures and
+/// refuses calls for `open_cooldown`. It then admits a single probe: success
+/// closes it, failure re-opens it. A probe that never reports back, because
+/// its caller gave up, is replaced after another cooldown.
+#[derive(Default)]
+pub(crate) struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    breakers: Mutex<HashMap<(String, semver::Version), Breaker>>,
+}
+
+impl CircuitBreakers {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self { config, breakers: Mutex::default() }
+    }
+
+    /// Admit a call, or return how long until the breaker takes a probe
+    pub(crate) fn try_admit(&self, capability_id: &str, version: &semver::Version) -> Result<(), Duration> {
+        let mut breakers = self.lock();
+        let Some(breaker) = breakers.get_mut(&(capability_id.to_string(), version.clone())) else {
+            return Ok(());
+        };
+        let cooldown = self.config.open_cooldown;
+        match breaker.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open if breaker.since.elapsed() < cooldown => {
+                Err(cooldown.saturating_sub(breaker.since.elapsed()))
+            }
+            CircuitState::HalfOpen { probe_started } if probe_started.elapsed() < cooldown => {
+                Err(cooldown.saturating_sub(probe_started.elapsed()))
+            }
+            CircuitState::Open => {
+                breaker.transition(CircuitState::HalfOpen { probe_started: Instant::now() });
+                Ok(())
+            }
+            // The last probe was abandoned
+            CircuitState::HalfOpen { .. } => {
+                breaker.state = CircuitState::HalfOpen { probe_started: Instant::now() };
+                Ok(())
+            }
+        }
+    }
+
+    /// Record an admitted call's outcome
+    pub(crate) fn record(&self, capability_id: &str, version: &semver::Version, success: bool) {
+        let mut breakers = self.lock();
+        let key = (capability_id.to_string(), version.clone());
+        if success {
+            breakers.remove(&key);
+            return;
+        }
+
+        let breaker = breakers.entry(key).or_insert_with(Breaker::closed);
+        breaker.consecutive_failures += 1;
+        // A failed probe re-opens immediately
+        if matches!(breaker.state, CircuitState::HalfOpen { .. })
+            || breaker.consecutive_failures >= self.config.failure_threshold
+        {
+            breaker.transition(CircuitState::Open);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(String, semver::Version), Breaker>> {
+        self.breakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
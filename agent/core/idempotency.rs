@@ -0,0 +1,207 @@
+// idempotency.rs - Idempotency Keys for Side-Effecting Capabilities
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use nuzon_core::EnterpriseError;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// Results kept by `MemoryIdempotencyStore` unless configured otherwise
+const DEFAULT_CAPACITY: usize = 10_000;
+/// How long a recorded result answers retries of the same key
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Result recorded under an idempotency key, with the params it answered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResult {
+    /// SHA-256 of the params' JSON encoding; a retry must carry the same
+    pub params_hash: [u8; 32],
+    pub value: serde_json::Value,
+}
+
+/// Where results recorded under idempotency keys are kept
+///
+/// Only successful results are recorded, so a retry after a failure runs
+/// the capability again.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Result recorded under `key`, if it has not expired
+    async fn get(&self, key: &str) -> Option<RecordedResult>;
+
+    async fn put(&self, key: &str, result: RecordedResult, ttl: Duration);
+}
+
+struct StoredResult {
+    result: RecordedResult,
+    expires_at: Instant,
+}
+
+/// Bounded in-process store evicting the oldest key once full
+pub struct MemoryIdempotencyStore {
+    capacity: usize,
+    entries: Mutex<(HashMap<String, StoredResult>, VecDeque<String>)>,
+}
+
+impl MemoryIdempotencyStore {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), entries: Mutex::default() }
+    }
+}
+
+impl Default for MemoryIdempotencyStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn get(&self, key: &str) -> Option<RecordedResult> {
+        let (results, _) = &*self.entries.lock().await;
+        results.get(key)
+            .filter(|stored| stored.expires_at > Instant::now())
+            .map(|stored| stored.result.clone())
+    }
+
+    async fn put(&self, key: &str, result: RecordedResult, ttl: Duration) {
+        let (results, order) = &mut *self.entries.lock().await;
+        let now = Instant::now();
+        results.retain(|_, stored| stored.expires_at > now);
+        order.retain(|key| results.contains_key(key));
+
+        if results.insert(key.to_string(), StoredResult { result, expires_at: now + ttl }).is_none() {
+            order.push_back(key.to_string());
+        }
+        while results.len() > self.capacity {
+            let Some(oldest) = order.pop_front() else { break };
+            results.remove(&oldest);
+        }
+    }
+}
+
+/// Runs each idempotency key at most once per TTL
+///
+/// Calls arriving while a key is executing wait for it and then take its
+/// recorded result rather than starting a second execution. A key reused
+/// with different params is refused rather than answered with a result
+/// computed for other inputs.
+pub(crate) struct Idempotency {
+    store: Arc<dyn IdempotencyStore>,
+    ttl: Duration,
+    in_flight: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl Default for Idempotency {
+    fn default() -> Self {
+        Self::new(Arc::new(MemoryIdempotencyStore::default()), DEFAULT_TTL)
+    }
+}
+
+impl Idempotency {
+    pub(crate) fn new(store: Arc<dyn IdempotencyStore>, ttl: Duration) -> Self {
+        Self { store, ttl, in_flight: std::sync::Mutex::default() }
+    }
+
+    /// Recorded result for `key`, or `execute`'s once no earlier call with
+    /// the key is still running
+    pub(crate) async fn run<F, Fut>(&self, key: &str, params_hash: [u8; 32], execute: F) -> Result<serde_json::Value>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<serde_json::Value>>,
+    {
+        if let Some(recorded) = self.store.get(key).await {
+            return replay(recorded, &params_hash);
+        }
+
+        let turn = Turn::take(self, key);
+        let _turn = turn.handle.lock().await;
+        // The call ahead of us may have recorded a result meanwhile
+        if let Some(recorded) = self.store.get(key).await {
+            return replay(recorded, &params_hash);
+        }
+        let value = execute().await?;
+        self.store.put(key, RecordedResult { params_hash, value: value.clone() }, self.ttl).await;
+        Ok(value)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Mutex<()>>>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A recorded result, if it answered the same params
+fn replay(recorded: RecordedResult, params_hash: &[u8; 32]) -> Result<serde_json::Value> {
+    if recorded.params_hash != *params_hash {
+        return Err(anyhow::Error::new(EnterpriseError::ProtocolError)
+            .context("idempotency key was already used with different params"));
+    }
+    Ok(recorded.value)
+}
+
+/// A call's place in line for one key
+///
+/// Dropping it, whether the call finished or was cancelled, removes the key
+/// from the in-flight map once no other call holds it.
+struct Turn<'a> {
+    idempotency: &'a Idempotency,
+    key: &'a str,
+    handle: Arc<Mutex<()>>,
+}
+
+impl<'a> Turn<'a> {
+    fn take(idempotency: &'a Idempotency, key: &'a str) -> Self {
+        let handle = idempotency.lock().entry(key.to_string()).or_default().clone();
+        Self { idempotency, key, handle }
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        // Last one out removes the key; only the map holds another handle
+        let mut in_flight = self.idempotency.lock();
+        if Arc::strong_count(&self.handle) == 2 {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recorded(value: serde_json::Value) -> RecordedResult {
+        RecordedResult { params_hash: [0; 32], value }
+    }
+
+    #[tokio::test]
+    async fn test_store_is_bounded_and_expires() {
+        let store = MemoryIdempotencyStore::new(2);
+        for key in ["a", "b", "c"] {
+            store.put(key, recorded(json!(key)), Duration::from_secs(60)).await;
+        }
+        assert_eq!(store.get("a").await, None);
+        assert_eq!(store.get("c").await, Some(recorded(json!("c"))));
+
+        store.put("short", recorded(json!(1)), Duration::from_millis(10)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(store.get("short").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_call_releases_its_key() {
+        let idempotency = Idempotency::default();
+        let stalled = idempotency.run("k", [1; 32], || std::future::pending());
+        assert!(tokio::time::timeout(Duration::from_millis(10), stalled).await.is_err());
+        assert!(idempotency.lock().is_empty());
+
+        // The key is still usable after the cancelled leader
+        let value = idempotency.run("k", [1; 32], || async { Ok(json!(1)) }).await.unwrap();
+        assert_eq!(value, json!(1));
+        assert!(idempotency.lock().is_empty());
+    }
+}
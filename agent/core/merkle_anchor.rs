@@ -0,0 +1,673 @@
+// merkle_anchor.rs - Externally Verifiable Audit Log Anchors
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::audit::{AuditSink, SignedExecutionRecord};
+
+/// Position of a record in the anchored log, in the order written
+pub type RecordId = u64;
+
+/// Domain separation for leaves and interior nodes, as in RFC 6962
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+const ANCHOR_CONTEXT: &[u8] = b"capability-audit-anchor-v1";
+
+/// When anchors are produced
+#[derive(Debug, Clone)]
+pub struct AnchorPolicy {
+    /// Records written between anchors
+    pub every_records: u64,
+    /// Longest a written record may go unanchored, checked on each write
+    /// and by the timer `spawn_interval` starts
+    pub every: Option<Duration>,
+}
+
+impl Default for AnchorPolicy {
+    fn default() -> Self {
+        Self { every_records: 1024, every: Some(Duration::from_secs(300)) }
+    }
+}
+
+/// Signed Merkle root over the first `count` records of the log
+///
+/// Published to a third party, it commits the log to those records as of
+/// `timestamp`: any of them can later be shown to be included with a
+/// `MerkleProof`, without handing over the rest of the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleAnchor {
+    pub root: [u8; 32],
+    pub count: u64,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u128,
+    /// Ed25519 signature over the root, count and timestamp
+    pub sig: Vec<u8>,
+}
+
+impl MerkleAnchor {
+    fn signed_bytes(root: &[u8; 32], count: u64, timestamp: u128) -> Vec<u8> {
+        [ANCHOR_CONTEXT, root, &count.to_be_bytes(), &timestamp.to_be_bytes()].concat()
+    }
+
+    fn sign(root: [u8; 32], count: u64, timestamp: u128, key: &Keypair) -> Self {
+        let sig = key.sign(&Self::signed_bytes(&root, count, timestamp)).to_bytes().to_vec();
+        Self { root, count, timestamp, sig }
+    }
+
+    /// Check the anchor was signed by `public` and not altered since
+    pub fn verify(&self, public: &PublicKey) -> bool {
+        let signed = Self::signed_bytes(&self.root, self.count, self.timestamp);
+        Signature::from_bytes(&self.sig).is_ok_and(|sig| public.verify(&signed, &sig).is_ok())
+    }
+}
+
+/// Audit path from one record to the root of an anchored tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub record_id: RecordId,
+    /// Size of the tree the path leads through, that of the anchor it
+    /// verifies against
+    pub count: u64,
+    /// Sibling hashes from the leaf upwards
+    pub path: Vec<[u8; 32]>,
+}
+
+impl MerkleProof {
+    /// Whether `record` is the `record_id`-th record under `anchor`, and
+    /// `anchor` was signed by `public`
+    pub fn verify(&self, record: &SignedExecutionRecord, anchor: &MerkleAnchor, public: &PublicKey) -> bool {
+        if self.count != anchor.count || self.record_id >= self.count || !anchor.verify(public) {
+            return false;
+        }
+        let Some(leaf) = record_leaf(record) else { return false };
+
+        // RFC 9162 section 2.1.3.2
+        let (mut index, mut last) = (self.record_id, self.count - 1);
+        let mut hash = leaf;
+        for sibling in &self.path {
+            if last == 0 {
+                return false;
+            }
+            if index & 1 == 1 || index == last {
+                hash = node_hash(sibling, &hash);
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, sibling);
+            }
+            index >>= 1;
+            last >>= 1;
+        }
+        last == 0 && hash == anchor.root
+    }
+}
+
+/// Destination anchors are published to, such as a transparency log or a
+/// counterparty's endpoint
+#[async_trait]
+pub trait AnchorPublisher: Send + Sync {
+    async fn publish(&self, anchor: MerkleAnchor) -> Result<()>;
+}
+
+/// Durable copy of the tree's leaves and the latest published anchor, so a
+/// restarted process extends the tree it had rather than starting over
+#[async_trait]
+pub trait AnchorStore: Send + Sync {
+    /// Every leaf stored so far, in record id order, and the latest anchor
+    async fn load(&self) -> Result<(Vec<[u8; 32]>, Option<MerkleAnchor>)>;
+    /// Store `leaf` under `record_id`, replacing any leaf already stored
+    /// under it and dropping those after it
+    async fn append_leaf(&self, record_id: RecordId, leaf: [u8; 32]) -> Result<()>;
+    /// Called once `anchor` has been published
+    async fn save_anchor(&self, anchor: &MerkleAnchor) -> Result<()>;
+}
+
+#[derive(Default)]
+struct AnchorState {
+    leaves: Vec<[u8; 32]>,
+    latest: Option<MerkleAnchor>,
+    /// When the oldest record not yet anchored was written
+    unanchored_since: Option<Instant>,
+}
+
+/// Audit sink accumulating what it forwards into a Merkle tree, signing
+/// and publishing its root under an `AnchorPolicy`
+///
+/// Records are appended once the inner sink has accepted them, one write
+/// at a time so the tree's order is the log's, and an anchor only ever
+/// covers records that were written. A persisted leaf is stored before its
+/// record is written, and replaced by the next record's if the write fails. An anchor becomes the latest only
+/// once it has been published. Inclusion proofs are produced against any
+/// anchor's record count; records written since the latest are covered by
+/// the next one.
+///
+/// Without an `AnchorStore` the tree lives in memory and a restarted
+/// process starts again from record 0.
+pub struct MerkleAnchoring {
+    inner: Arc<dyn AuditSink>,
+    key: Keypair,
+    publisher: Arc<dyn AnchorPublisher>,
+    store: Option<Arc<dyn AnchorStore>>,
+    policy: AnchorPolicy,
+    state: Mutex<AnchorState>,
+    /// Serializes appends, keeping leaves in the order records were written
+    appending: tokio::sync::Mutex<()>,
+    /// Serializes publishing, so anchors go out in order of their count
+    publishing: tokio::sync::Mutex<()>,
+}
+
+impl MerkleAnchoring {
+    pub fn new(inner: Arc<dyn AuditSink>, key: Keypair, publisher: Arc<dyn AnchorPublisher>) -> Self {
+        Self {
+            inner,
+            key,
+            publisher,
+            store: None,
+            policy: AnchorPolicy::default(),
+            state: Mutex::default(),
+            appending: tokio::sync::Mutex::new(()),
+            publishing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Anchoring that persists its tree in `store`, continuing from
+    /// whatever it already holds
+    pub async fn open(
+        inner: Arc<dyn AuditSink>,
+        key: Keypair,
+        publisher: Arc<dyn AnchorPublisher>,
+        store: Arc<dyn AnchorStore>,
+    ) -> Result<Self> {
+        let (leaves, latest) = store.load().await.context("failed to load audit anchor state")?;
+        if latest.as_ref().is_some_and(|latest| latest.count > leaves.len() as u64) {
+            anyhow::bail!("stored audit anchor covers more records than the stored tree");
+        }
+        let anchored = latest.as_ref().map_or(0, |latest| latest.count);
+        let unanchored_since = (leaves.len() as u64 > anchored).then(Instant::now);
+        let anchoring = Self { store: Some(store), ..Self::new(inner, key, publisher) };
+        *anchoring.lock() = AnchorState { leaves, latest, unanchored_since };
+        Ok(anchoring)
+    }
+
+    pub fn with_policy(mut self, policy: AnchorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sign and publish an anchor over every record written so far, as at
+    /// shutdown
+    ///
+    /// Returns `None` when the latest anchor already covers them all. A
+    /// failed publish leaves the latest anchor as it was.
+    pub async fn anchor_now(&self) -> Result<Option<MerkleAnchor>> {
+        let _publishing = self.publishing.lock().await;
+        let anchor = {
+            let state = self.lock();
+            let count = state.leaves.len() as u64;
+            if count == 0 || state.latest.as_ref().is_some_and(|latest| latest.count == count) {
+                return Ok(None);
+            }
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            MerkleAnchor::sign(tree_root(&state.leaves), count, timestamp, &self.key)
+        };
+        info!(count = anchor.count, "Publishing audit log anchor");
+        self.publisher.publish(anchor.clone()).await?;
+        if let Some(store) = &self.store {
+            // Published regardless; a stale stored anchor only means the
+            // restarted process publishes this count again
+            if let Err(error) = store.save_anchor(&anchor).await {
+                warn!(%error, count = anchor.count, "Failed to store audit log anchor");
+            }
+        }
+
+        let mut state = self.lock();
+        // Records written while publishing are still waiting for an anchor
+        if state.leaves.len() as u64 == anchor.count {
+            state.unanchored_since = None;
+        }
+        state.latest = Some(anchor.clone());
+        Ok(Some(anchor))
+    }
+
+    /// Anchor on a timer, so the tail of a log that has gone quiet is
+    /// anchored within `AnchorPolicy::every` of being written
+    ///
+    /// Returns `None` when the policy has no interval. The task ends once
+    /// the anchoring is dropped.
+    pub fn spawn_interval(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let every = self.policy.every?;
+        let anchoring = Arc::downgrade(self);
+        Some(tokio::spawn(anchor_on_interval(anchoring, every)))
+    }
+
+    pub fn latest_anchor(&self) -> Option<MerkleAnchor> {
+        self.lock().latest.clone()
+    }
+
+    /// Id the first record matching `record` was written under
+    pub fn record_id(&self, record: &SignedExecutionRecord) -> Option<RecordId> {
+        let leaf = record_leaf(record)?;
+        self.lock().leaves.iter().position(|candidate| *candidate == leaf).map(|id| id as RecordId)
+    }
+
+    /// Inclusion proof for `record_id` against the anchor over the first
+    /// `anchor_count` records, such as the one a verifier already holds
+    ///
+    /// `None` when that anchor does not cover the record, or covers more
+    /// records than have been written.
+    pub fn inclusion_proof(&self, record_id: RecordId, anchor_count: u64) -> Option<MerkleProof> {
+        let state = self.lock();
+        if record_id >= anchor_count || anchor_count > state.leaves.len() as u64 {
+            return None;
+        }
+        let leaves = &state.leaves[..anchor_count as usize];
+        Some(MerkleProof { record_id, count: anchor_count, path: audit_path(record_id as usize, leaves) })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, AnchorState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl AuditSink for MerkleAnchoring {
+    async fn write(&self, record: SignedExecutionRecord) -> Result<()> {
+        let leaf = record_leaf(&record).ok_or_else(|| anyhow::anyhow!("audit record does not encode"))?;
+        let appending = self.appending.lock().await;
+        // A written record missing from the stored tree would shift every
+        // later id, so the leaf goes first
+        if let Some(store) = &self.store {
+            let record_id = self.lock().leaves.len() as RecordId;
+            store.append_leaf(record_id, leaf).await.context("failed to store the record's anchor leaf")?;
+        }
+        self.inner.write(record).await?;
+
+        let due = {
+            let mut state = self.lock();
+            state.leaves.push(leaf);
+            let since = *state.unanchored_since.get_or_insert_with(Instant::now);
+            let anchored = state.latest.as_ref().map_or(0, |latest| latest.count);
+            state.leaves.len() as u64 - anchored >= self.policy.every_records.max(1)
+                || self.policy.every.is_some_and(|every| since.elapsed() >= every)
+        };
+        drop(appending);
+        // The record is durable either way; a failed publish is retried
+        // with the next anchor
+        if due {
+            if let Err(error) = self.anchor_now().await {
+                warn!(%error, "Failed to publish audit log anchor");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Wake when the oldest unanchored record is due, anchoring it and
+/// everything written after it
+async fn anchor_on_interval(anchoring: Weak<MerkleAnchoring>, every: Duration) {
+    let mut wait = every;
+    loop {
+        tokio::time::sleep(wait).await;
+        let Some(anchoring) = anchoring.upgrade() else {
+            return;
+        };
+        let waited = anchoring.lock().unanchored_since.map(|since| since.elapsed());
+        wait = match waited {
+            Some(waited) if waited >= every => {
+                if let Err(error) = anchoring.anchor_now().await {
+                    warn!(%error, "Failed to publish audit log anchor");
+                }
+                every
+            }
+            Some(waited) => every - waited,
+            None => every,
+        };
+    }
+}
+
+/// Leaf hash of a record: its JSON encoding under the leaf prefix
+fn record_leaf(record: &SignedExecutionRecord) -> Option<[u8; 32]> {
+    let encoded = serde_json::to_vec(record).ok()?;
+    Some(Sha256::new().chain_update([LEAF_PREFIX]).chain_update(encoded).finalize().into())
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new().chain_update([NODE_PREFIX]).chain_update(left).chain_update(right).finalize().into()
+}
+
+/// Largest power of two below `n`, where the tree over `n` leaves splits
+fn split_point(n: usize) -> usize {
+    n.next_power_of_two() / 2
+}
+
+fn tree_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves {
+        [] => Sha256::digest(b"").into(),
+        [leaf] => *leaf,
+        _ => {
+            let (left, right) = leaves.split_at(split_point(leaves.len()));
+            node_hash(&tree_root(left), &tree_root(right))
+        }
+    }
+}
+
+fn audit_path(index: usize, leaves: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    let (left, right) = leaves.split_at(split_point(leaves.len()));
+    if index < left.len() {
+        let mut path = audit_path(index, left);
+        path.push(tree_root(right));
+        path
+    } else {
+        let mut path = audit_path(index - left.len(), right);
+        path.push(tree_root(left));
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecutionOutcome, ExecutionRecord};
+
+    #[derive(Default)]
+    struct MemorySink(Mutex<Vec<SignedExecutionRecord>>);
+
+    #[async_trait]
+    impl AuditSink for MemorySink {
+        async fn write(&self, record: SignedExecutionRecord) -> Result<()> {
+            self.0.lock().unwrap().push(record);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Published(Mutex<Vec<MerkleAnchor>>);
+
+    #[async_trait]
+    impl AnchorPublisher for Published {
+        async fn publish(&self, anchor: MerkleAnchor) -> Result<()> {
+            self.0.lock().unwrap().push(anchor);
+            Ok(())
+        }
+    }
+
+    fn record(n: u64) -> SignedExecutionRecord {
+        let record = ExecutionRecord {
+            caller: format!("caller-{}", n),
+            capability_id: "summarize".into(),
+            version: Some(semver::Version::new(1, 0, 0)),
+            params_hash: [n as u8; 32],
+            outcome: ExecutionOutcome::Success,
+            duration: Duration::from_millis(n),
+            timestamp: 1_700_000_000_000 + u128::from(n),
+        };
+        SignedExecutionRecord { record, signature: vec![0; 64] }
+    }
+
+    #[test]
+    fn test_split_point_is_largest_power_of_two_below() {
+        for (n, k) in [(2, 1), (3, 2), (4, 2), (5, 4), (7, 4), (8, 4), (9, 8), (1000, 512)] {
+            assert_eq!(split_point(n), k, "n = {}", n);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inclusion_proof_verifies_against_published_anchor() {
+        let key = Keypair::generate(&mut rand::rngs::OsRng);
+        let public = key.public;
+        let sink = Arc::new(MemorySink::default());
+        let published = Arc::new(Published::default());
+        let anchoring = MerkleAnchoring::new(sink.clone(), key, published.clone())
+            .with_policy(AnchorPolicy { every_records: 7, every: None });
+
+        for n in 0..10 {
+            anchoring.write(record(n)).await.unwrap();
+        }
+        assert_eq!(sink.0.lock().unwrap().len(), 10);
+
+        // Anchored after seven records; the last three wait for the next
+        let anchor = published.0.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(anchor.count, 7);
+        assert!(anchor.verify(&public));
+        assert!(anchoring.inclusion_proof(8, anchor.count).is_none());
+
+        // A third party holding only the anchor, one record and its proof
+        for n in 0..7 {
+            let id = anchoring.record_id(&record(n)).unwrap();
+            let proof = anchoring.inclusion_proof(id, anchor.count).unwrap();
+            assert!(proof.verify(&record(n), &anchor, &public), "record {}", n);
+        }
+        let proof = anchoring.inclusion_proof(4, anchor.count).unwrap();
+        assert!(!proof.verify(&record(5), &anchor, &public));
+        assert!(!proof.verify(&record(11), &anchor, &public));
+
+        // Any alteration of the anchor or the path is detected
+        let mut forged = anchor.clone();
+        forged.root[0] ^= 1;
+        assert!(!proof.verify(&record(4), &forged, &public));
+        let mut tampered = proof.clone();
+        tampered.path[0][0] ^= 1;
+        assert!(!tampered.verify(&record(4), &anchor, &public));
+        let other = Keypair::generate(&mut rand::rngs::OsRng);
+        assert!(!proof.verify(&record(4), &anchor, &other.public));
+
+        // Anchoring on demand covers the rest
+        let first = anchor;
+        let anchor = anchoring.anchor_now().await.unwrap().unwrap();
+        assert_eq!(anchor.count, 10);
+        assert!(anchoring.anchor_now().await.unwrap().is_none());
+        let proof = anchoring.inclusion_proof(9, anchor.count).unwrap();
+        assert!(proof.verify(&record(9), &anchor, &public));
+
+        // A verifier still holding the first anchor gets a proof against it
+        let proof = anchoring.inclusion_proof(3, first.count).unwrap();
+        assert!(proof.verify(&record(3), &first, &public));
+        assert!(anchoring.inclusion_proof(3, 11).is_none());
+    }
+
+    /// Refuses anchors until it is brought back up
+    #[derive(Default)]
+    struct Unreachable {
+        up: std::sync::atomic::AtomicBool,
+        published: Published,
+    }
+
+    #[async_trait]
+    impl AnchorPublisher for Unreachable {
+        async fn publish(&self, anchor: MerkleAnchor) -> Result<()> {
+            if !self.up.load(std::sync::atomic::Ordering::SeqCst) {
+                anyhow::bail!("transparency log unreachable");
+            }
+            self.published.publish(anchor).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_publish_leaves_latest_anchor_unchanged() {
+        let publisher = Arc::new(Unreachable::default());
+        let anchoring = MerkleAnchoring::new(
+            Arc::new(MemorySink::default()),
+            Keypair::generate(&mut rand::rngs::OsRng),
+            publisher.clone(),
+        ).with_policy(AnchorPolicy { every_records: 2, every: None });
+
+        for n in 0..4 {
+            anchoring.write(record(n)).await.unwrap();
+        }
+        assert!(anchoring.latest_anchor().is_none());
+        assert!(anchoring.anchor_now().await.is_err());
+        assert!(anchoring.latest_anchor().is_none());
+
+        publisher.up.store(true, std::sync::atomic::Ordering::SeqCst);
+        let anchor = anchoring.anchor_now().await.unwrap().unwrap();
+        assert_eq!(anchor.count, 4);
+        assert_eq!(anchoring.latest_anchor(), Some(anchor.clone()));
+        assert_eq!(*publisher.published.0.lock().unwrap(), vec![anchor]);
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<(Vec<[u8; 32]>, Option<MerkleAnchor>)>);
+
+    #[async_trait]
+    impl AnchorStore for MemoryStore {
+        async fn load(&self) -> Result<(Vec<[u8; 32]>, Option<MerkleAnchor>)> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        async fn append_leaf(&self, record_id: RecordId, leaf: [u8; 32]) -> Result<()> {
+            let mut stored = self.0.lock().unwrap();
+            assert!(stored.0.len() as u64 >= record_id);
+            stored.0.truncate(record_id as usize);
+            stored.0.push(leaf);
+            Ok(())
+        }
+
+        async fn save_anchor(&self, anchor: &MerkleAnchor) -> Result<()> {
+            self.0.lock().unwrap().1 = Some(anchor.clone());
+            Ok(())
+        }
+    }
+
+    /// Sink and store that fail their next call once `fail_next` is set
+    #[derive(Default)]
+    struct Flaky<T> {
+        inner: T,
+        fail_next: std::sync::atomic::AtomicBool,
+    }
+
+    impl<T> Flaky<T> {
+        fn fail_next(&self) {
+            self.fail_next.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        fn check(&self) -> Result<()> {
+            if self.fail_next.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                anyhow::bail!("unavailable");
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl AuditSink for Flaky<MemorySink> {
+        async fn write(&self, record: SignedExecutionRecord) -> Result<()> {
+            self.check()?;
+            self.inner.write(record).await
+        }
+    }
+
+    #[async_trait]
+    impl AnchorStore for Flaky<MemoryStore> {
+        async fn load(&self) -> Result<(Vec<[u8; 32]>, Option<MerkleAnchor>)> {
+            self.inner.load().await
+        }
+
+        async fn append_leaf(&self, record_id: RecordId, leaf: [u8; 32]) -> Result<()> {
+            self.check()?;
+            self.inner.append_leaf(record_id, leaf).await
+        }
+
+        async fn save_anchor(&self, anchor: &MerkleAnchor) -> Result<()> {
+            self.inner.save_anchor(anchor).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_leaf_append_or_write_keeps_log_and_tree_aligned() {
+        let sink = Arc::new(Flaky::<MemorySink>::default());
+        let store = Arc::new(Flaky::<MemoryStore>::default());
+        let anchoring = MerkleAnchoring::open(
+            sink.clone(),
+            Keypair::generate(&mut rand::rngs::OsRng),
+            Arc::new(Published::default()),
+            store.clone(),
+        ).await.unwrap();
+
+        // Without its leaf stored the record is not written
+        store.fail_next();
+        assert!(anchoring.write(record(0)).await.is_err());
+        assert!(sink.inner.0.lock().unwrap().is_empty());
+        assert_eq!(anchoring.record_id(&record(0)), None);
+
+        // A record the log refused leaves a stored leaf the next one replaces
+        sink.fail_next();
+        assert!(anchoring.write(record(1)).await.is_err());
+        anchoring.write(record(2)).await.unwrap();
+
+        assert_eq!(sink.inner.0.lock().unwrap().len(), 1);
+        assert_eq!(store.inner.0.lock().unwrap().0, vec![record_leaf(&record(2)).unwrap()]);
+        assert_eq!(anchoring.record_id(&record(1)), None);
+        assert_eq!(anchoring.record_id(&record(2)), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_restarted_anchoring_extends_the_stored_tree() {
+        let key = Keypair::generate(&mut rand::rngs::OsRng);
+        let (public, key_bytes) = (key.public, key.to_bytes());
+        let store = Arc::new(MemoryStore::default());
+        let published = Arc::new(Published::default());
+        let sink = Arc::new(MemorySink::default());
+        let policy = AnchorPolicy { every_records: 3, every: None };
+
+        let anchoring = MerkleAnchoring::open(sink.clone(), key, published.clone(), store.clone())
+            .await
+            .unwrap()
+            .with_policy(policy.clone());
+        for n in 0..4 {
+            anchoring.write(record(n)).await.unwrap();
+        }
+        drop(anchoring);
+
+        let key = Keypair::from_bytes(&key_bytes).unwrap();
+        let restarted = MerkleAnchoring::open(sink, key, published.clone(), store)
+            .await
+            .unwrap()
+            .with_policy(policy);
+        assert_eq!(restarted.latest_anchor().map(|anchor| anchor.count), Some(3));
+        assert_eq!(restarted.record_id(&record(3)), Some(3));
+        restarted.write(record(4)).await.unwrap();
+
+        let anchor = restarted.anchor_now().await.unwrap().unwrap();
+        assert_eq!(anchor.count, 5);
+        for n in 0..5 {
+            let proof = restarted.inclusion_proof(n, anchor.count).unwrap();
+            assert!(proof.verify(&record(n), &anchor, &public), "record {}", n);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quiet_log_tail_is_anchored_by_the_interval() {
+        let published = Arc::new(Published::default());
+        let anchoring = Arc::new(MerkleAnchoring::new(
+            Arc::new(MemorySink::default()),
+            Keypair::generate(&mut rand::rngs::OsRng),
+            published.clone(),
+        ).with_policy(AnchorPolicy { every_records: 100, every: Some(Duration::from_millis(50)) }));
+        let timer = anchoring.spawn_interval().unwrap();
+
+        anchoring.write(record(0)).await.unwrap();
+        anchoring.write(record(1)).await.unwrap();
+        assert!(published.0.lock().unwrap().is_empty());
+
+        // Nothing else is written, yet the two records are anchored
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(published.0.lock().unwrap().iter().map(|anchor| anchor.count).collect::<Vec<_>>(), vec![2]);
+
+        // The timer stops with the anchoring
+        drop(anchoring);
+        tokio::time::timeout(Duration::from_millis(200), timer).await.unwrap().unwrap();
+    }
+}
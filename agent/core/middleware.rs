@@ -0,0 +1,67 @@
+// middleware.rs - Composable Layers Around Capability Execution
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use nuzon_core::EnterpriseError;
+
+use super::TraceContext;
+
+/// One execution as seen by middleware
+///
+/// The capability and version are already resolved and the caller's claims
+/// checked. Layers may rewrite `params`, e.g. to redact fields, before
+/// passing the context on.
+#[derive(Debug, Clone)]
+pub struct MiddlewareContext {
+    pub capability_id: String,
+    pub version: semver::Version,
+    pub caller_identity: String,
+    pub auth_claims: Vec<String>,
+    pub params: serde_json::Value,
+    pub trace: TraceContext,
+    /// Per-call timeout override, before clamping
+    pub deadline: Option<std::time::Duration>,
+}
+
+/// Innermost step of the chain: the registry's own execution
+pub(crate) type Endpoint<'a> =
+    dyn Fn(MiddlewareContext) -> BoxFuture<'a, Result<serde_json::Value, EnterpriseError>> + Send + Sync + 'a;
+
+/// The layers after the current one, ending in the capability itself
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn CapabilityMiddleware>],
+    endpoint: &'a Endpoint<'a>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(layers: &'a [Arc<dyn CapabilityMiddleware>], endpoint: &'a Endpoint<'a>) -> Self {
+        Self { layers, endpoint }
+    }
+
+    /// Hand the execution to the next layer
+    pub async fn run(self, ctx: MiddlewareContext) -> Result<serde_json::Value, EnterpriseError> {
+        match self.layers.split_first() {
+            Some((layer, rest)) => layer.call(ctx, Next { layers: rest, endpoint: self.endpoint }).await,
+            None => (self.endpoint)(ctx).await,
+        }
+    }
+}
+
+/// Cross-cutting behaviour wrapped around every capability execution
+///
+/// Layers run in registration order, the first registered outermost. A
+/// layer may return without calling `next`, skipping the capability.
+#[async_trait]
+pub trait CapabilityMiddleware: Send + Sync {
+    async fn call(&self, ctx: MiddlewareContext, next: Next<'_>) -> Result<serde_json::Value, EnterpriseError>;
+
+    /// Rewrite or refuse a streaming execution before it starts
+    ///
+    /// A stream has no single result for `call` to wrap, so streaming
+    /// executions run only this hook, in the same order. The default passes
+    /// `ctx` on unchanged.
+    async fn before_stream(&self, ctx: MiddlewareContext) -> Result<MiddlewareContext, EnterpriseError> {
+        Ok(ctx)
+    }
+}
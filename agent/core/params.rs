@@ -0,0 +1,114 @@
+// params.rs - Parameter Defaults and Type Coercion
+use std::collections::BTreeMap;
+use anyhow::{Context, Result};
+use nuzon_core::EnterpriseError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+/// Type a capability declares for one top-level parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+}
+
+/// Fill in defaults and coerce declared parameter types
+///
+/// `defaults` is merged under `params`, recursing into nested objects, so
+/// anything the caller sent wins. Declared types are then applied to the
+/// merged object: numeric and boolean strings become numbers and booleans,
+/// and scalars become strings. Explicit nulls are left alone.
+pub(crate) fn prepare(
+    params: Value,
+    defaults: &Value,
+    types: &BTreeMap<String, ParamType>,
+) -> Result<Value> {
+    let has_defaults = defaults.as_object().is_some_and(|d| !d.is_empty());
+    if !has_defaults && types.is_empty() {
+        return Ok(params);
+    }
+
+    let mut params = match params {
+        Value::Object(params) => params,
+        Value::Null => Map::new(),
+        other => return Err(invalid("params", &other, "object")),
+    };
+    if let Some(defaults) = defaults.as_object() {
+        merge_defaults(&mut params, defaults);
+    }
+    for (field, ty) in types {
+        if let Some(value) = params.get_mut(field) {
+            coerce(value, *ty).with_context(|| format!("parameter `{}`", field))?;
+        }
+    }
+    Ok(Value::Object(params))
+}
+
+fn merge_defaults(params: &mut Map<String, Value>, defaults: &Map<String, Value>) {
+    for (key, default) in defaults {
+        match (params.get_mut(key), default) {
+            (None, _) => {
+                params.insert(key.clone(), default.clone());
+            }
+            (Some(Value::Object(given)), Value::Object(default)) => merge_defaults(given, default),
+            (Some(_), _) => {}
+        }
+    }
+}
+
+fn coerce(value: &mut Value, ty: ParamType) -> Result<()> {
+    let coerced = match (ty, &*value) {
+        (_, Value::Null) => return Ok(()),
+        (ParamType::String, Value::String(_))
+        | (ParamType::Number, Value::Number(_))
+        | (ParamType::Boolean, Value::Bool(_)) => return Ok(()),
+        (ParamType::Integer, Value::Number(n)) if n.is_i64() || n.is_u64() => return Ok(()),
+
+        (ParamType::String, Value::Number(n)) => Some(Value::String(n.to_string())),
+        (ParamType::String, Value::Bool(b)) => Some(Value::String(b.to_string())),
+        (ParamType::Number, Value::String(s)) => s.trim().parse::<f64>().ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        (ParamType::Integer, Value::String(s)) => {
+            let s = s.trim();
+            s.parse::<i64>().map(Value::from).ok()
+                .or_else(|| s.parse::<u64>().map(Value::from).ok())
+        }
+        (ParamType::Boolean, Value::String(s)) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+    let expected = match ty {
+        ParamType::String => "string",
+        ParamType::Number => "number",
+        ParamType::Integer => "integer",
+        ParamType::Boolean => "boolean",
+    };
+    match coerced {
+        Some(coerced) => {
+            *value = coerced;
+            Ok(())
+        }
+        None => Err(invalid("value", value, expected)),
+    }
+}
+
+/// `ProtocolError` carrying what was wrong with the offending value
+fn invalid(what: &str, value: &Value, expected: &str) -> anyhow::Error {
+    let found = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    };
+    anyhow::Error::new(EnterpriseError::ProtocolError)
+        .context(format!("{} must be {}, got {} {}", what, expected, found, value))
+}
@@ -0,0 +1,166 @@
+// rate_limit.rs - Per-Caller Capability Rate Limiting
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// How often idle buckets are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Token bucket applied to each caller of one capability
+///
+/// Built with `new` or deserialized, both of which refuse a rate or burst
+/// that could never admit a call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawCallerRateLimit")]
+pub struct CallerRateLimit {
+    calls_per_second: f64,
+    burst: u32,
+}
+
+#[derive(Deserialize)]
+struct RawCallerRateLimit {
+    calls_per_second: f64,
+    burst: u32,
+}
+
+impl TryFrom<RawCallerRateLimit> for CallerRateLimit {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawCallerRateLimit) -> Result<Self> {
+        Self::new(raw.calls_per_second, raw.burst)
+    }
+}
+
+impl CallerRateLimit {
+    /// Allow each caller `calls_per_second` sustained, and up to `burst` at once
+    pub fn new(calls_per_second: f64, burst: u32) -> Result<Self> {
+        anyhow::ensure!(
+            calls_per_second.is_finite() && calls_per_second > 0.0,
+            "rate limit must allow a positive, finite number of calls per second, got {}",
+            calls_per_second
+        );
+        anyhow::ensure!(burst > 0, "rate limit burst must allow at least one call");
+        Ok(Self { calls_per_second, burst })
+    }
+
+    pub fn calls_per_second(&self) -> f64 {
+        self.calls_per_second
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Time for an empty bucket to refill completely
+    fn refill_time(&self) -> Duration {
+        Duration::from_secs_f64(self.burst as f64 / self.calls_per_second)
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    refill_time: Duration,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    /// Keyed by `(capability id, caller identity)`
+    buckets: HashMap<(String, String), TokenBucket>,
+    last_sweep: Option<Instant>,
+}
+
+/// Per-caller token buckets for every rate-limited capability
+///
+/// A bucket left idle long enough to refill is indistinguishable from a new
+/// one, so such buckets are dropped periodically to bound memory.
+#[derive(Default)]
+pub struct CallerRateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+impl CallerRateLimiter {
+    /// Take one call token, or return how long until one becomes available
+    pub fn try_acquire(
+        &self,
+        capability_id: &str,
+        caller: &str,
+        limit: &CallerRateLimit,
+    ) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.last_sweep.map_or(true, |at| now.duration_since(at) >= SWEEP_INTERVAL) {
+            state.sweep(now);
+        }
+
+        let bucket = state.buckets
+            .entry((capability_id.to_string(), caller.to_string()))
+            .or_insert_with(|| TokenBucket {
+                tokens: limit.burst as f64,
+                last_refill: now,
+                refill_time: limit.refill_time(),
+            });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.calls_per_second).min(limit.burst as f64);
+        bucket.last_refill = now;
+        bucket.refill_time = limit.refill_time();
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / limit.calls_per_second))
+        }
+    }
+
+    /// Callers currently holding a bucket
+    pub fn tracked_callers(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).buckets.len()
+    }
+}
+
+impl LimiterState {
+    fn sweep(&mut self, now: Instant) {
+        self.buckets.retain(|_, bucket| now.duration_since(bucket.last_refill) < bucket.refill_time);
+        self.last_sweep = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refilled_buckets_are_swept() {
+        let limiter = CallerRateLimiter::default();
+        let limit = CallerRateLimit::new(10.0, 2).unwrap();
+        limiter.try_acquire("cap", "a", &limit).unwrap();
+        limiter.try_acquire("cap", "b", &limit).unwrap();
+        assert_eq!(limiter.tracked_callers(), 2);
+
+        let mut state = limiter.state.lock().unwrap();
+        state.sweep(Instant::now() + Duration::from_millis(100));
+        assert_eq!(state.buckets.len(), 2);
+        state.sweep(Instant::now() + limit.refill_time());
+        assert!(state.buckets.is_empty());
+    }
+
+    #[test]
+    fn test_limit_that_never_admits_is_refused() {
+        assert!(CallerRateLimit::new(0.0, 2).is_err());
+        assert!(CallerRateLimit::new(-1.0, 2).is_err());
+        assert!(CallerRateLimit::new(f64::NAN, 2).is_err());
+        assert!(CallerRateLimit::new(10.0, 0).is_err());
+
+        let parsed: Result<CallerRateLimit, _> = serde_json::from_str(r#"{"calls_per_second": 0, "burst": 2}"#);
+        assert!(parsed.is_err());
+        let parsed: CallerRateLimit = serde_json::from_str(r#"{"calls_per_second": 5, "burst": 2}"#).unwrap();
+        assert_eq!((parsed.calls_per_second(), parsed.burst()), (5.0, 2));
+    }
+}
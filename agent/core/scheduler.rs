@@ -0,0 +1,208 @@
+// scheduler.rs - Weighted-Fair Permit Scheduling for Resource Pools
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{oneshot, Notify, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_window_ms() -> u64 {
+    10_000
+}
+
+/// How a resource pool orders callers waiting for a permit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PoolScheduler {
+    /// First come, first served
+    #[default]
+    Fifo,
+    /// Permits shared between waiting callers in proportion to their weights
+    WeightedFair(WeightedFairConfig),
+}
+
+/// Weighted-fair scheduling settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedFairConfig {
+    /// Weight per `caller_identity`; unlisted callers get `default_weight`
+    #[serde(default)]
+    pub weights: HashMap<String, u32>,
+    #[serde(default = "default_weight")]
+    pub default_weight: u32,
+    /// Span over which grants count against a caller's share
+    #[serde(default = "default_window_ms")]
+    pub window_ms: u64,
+}
+
+/// Hands out a pool's semaphore permits to the caller furthest below its
+/// weighted share of recent grants
+///
+/// A permit is only taken while someone is waiting, and any waiting caller
+/// may have it, so capacity never sits idle for fairness' sake.
+pub(crate) struct FairScheduler {
+    shared: Arc<Shared>,
+    dispatcher: JoinHandle<()>,
+}
+
+struct Shared {
+    config: WeightedFairConfig,
+    state: Mutex<State>,
+    waiting: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    queues: HashMap<String, VecDeque<oneshot::Sender<OwnedSemaphorePermit>>>,
+    /// Grants inside the window, oldest first
+    grants: VecDeque<(Instant, String)>,
+    granted: HashMap<String, u64>,
+}
+
+impl FairScheduler {
+    pub(crate) fn new(semaphore: Arc<Semaphore>, config: WeightedFairConfig) -> Self {
+        let shared = Arc::new(Shared { config, state: Mutex::default(), waiting: Notify::new() });
+        let dispatcher = tokio::spawn(dispatch(shared.clone(), semaphore));
+        Self { shared, dispatcher }
+    }
+
+    /// Wait for a permit on behalf of `caller`
+    pub(crate) async fn acquire(&self, caller: &str) -> Result<OwnedSemaphorePermit, oneshot::error::RecvError> {
+        let (grant, granted) = oneshot::channel();
+        self.shared.lock().queues.entry(caller.to_string()).or_default().push_back(grant);
+        self.shared.waiting.notify_one();
+        granted.await
+    }
+}
+
+impl Drop for FairScheduler {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn weight(&self, caller: &str) -> f64 {
+        self.config.weights.get(caller).copied().unwrap_or(self.config.default_weight).max(1) as f64
+    }
+
+    /// Oldest waiter of the caller with the fewest recent grants per weight
+    fn next_waiter(&self, now: Instant) -> Option<(String, oneshot::Sender<OwnedSemaphorePermit>)> {
+        let mut state = self.lock();
+        let window = Duration::from_millis(self.config.window_ms);
+        while state.grants.front().is_some_and(|(at, _)| now.duration_since(*at) >= window) {
+            let (_, caller) = state.grants.pop_front().expect("checked front");
+            if let Some(count) = state.granted.get_mut(&caller) {
+                *count -= 1;
+            }
+        }
+
+        let share = |caller: &str| state.granted.get(caller).copied().unwrap_or(0) as f64 / self.weight(caller);
+        let caller = state.queues.keys()
+            .min_by(|a, b| share(a).total_cmp(&share(b)).then_with(|| a.cmp(b)))?
+            .clone();
+        let queue = state.queues.get_mut(&caller).expect("caller chosen from queues");
+        let waiter = queue.pop_front().expect("queues are never left empty");
+        if queue.is_empty() {
+            state.queues.remove(&caller);
+        }
+        Some((caller, waiter))
+    }
+
+    fn record_grant(&self, caller: String, at: Instant) {
+        let mut state = self.lock();
+        *state.granted.entry(caller.clone()).or_default() += 1;
+        state.grants.push_back((at, caller));
+    }
+}
+
+async fn dispatch(shared: Arc<Shared>, semaphore: Arc<Semaphore>) {
+    loop {
+        while shared.lock().queues.is_empty() {
+            shared.waiting.notified().await;
+        }
+        let Ok(mut permit) = semaphore.clone().acquire_owned().await else {
+            return;
+        };
+
+        // The fairest caller may have given up waiting; offer the next one
+        let now = Instant::now();
+        while let Some((caller, waiter)) = shared.next_waiter(now) {
+            match waiter.send(permit) {
+                Ok(()) => {
+                    shared.record_grant(caller, now);
+                    break;
+                }
+                Err(returned) => permit = returned,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(weights: &[(&str, u32)]) -> WeightedFairConfig {
+        WeightedFairConfig {
+            weights: weights.iter().map(|(caller, weight)| (caller.to_string(), *weight)).collect(),
+            default_weight: 1,
+            window_ms: 60_000,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_grants_under_contention_follow_weights() {
+        let scheduler = Arc::new(FairScheduler::new(
+            Arc::new(Semaphore::new(1)),
+            config(&[("interactive", 3), ("batch", 1)]),
+        ));
+        let grants = Arc::new(Mutex::new(Vec::new()));
+
+        // Both callers keep several requests queued at all times
+        let workers: Vec<_> = ["interactive", "batch"].into_iter()
+            .flat_map(|caller| std::iter::repeat(caller).take(4))
+            .map(|caller| {
+                let (scheduler, grants) = (scheduler.clone(), grants.clone());
+                tokio::spawn(async move {
+                    for _ in 0..50 {
+                        let permit = scheduler.acquire(caller).await.unwrap();
+                        grants.lock().unwrap().push(caller);
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                        drop(permit);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.await.unwrap();
+        }
+
+        // Interactive has 200 requests to batch's 200, so both contend for
+        // at least the first 160 grants
+        let grants = grants.lock().unwrap();
+        let interactive = grants[..160].iter().filter(|caller| **caller == "interactive").count();
+        let share = interactive as f64 / 160.0;
+        assert!((0.65..=0.85).contains(&share), "interactive got {} of 160 grants", interactive);
+    }
+
+    #[tokio::test]
+    async fn test_lone_caller_uses_whole_pool() {
+        let scheduler = FairScheduler::new(Arc::new(Semaphore::new(2)), config(&[("batch", 1), ("interactive", 9)]));
+        let first = scheduler.acquire("batch").await.unwrap();
+        let second = tokio::time::timeout(Duration::from_secs(1), scheduler.acquire("batch")).await;
+        assert!(second.is_ok(), "idle capacity withheld from the only caller");
+        drop(first);
+    }
+}
@@ -0,0 +1,97 @@
+// warm_pool.rs - Pre-Initialized Capability Instances
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use tracing::debug;
+
+use super::EnterpriseCapability;
+
+/// Instances initialized ahead of demand by `EnterpriseCapability::warm`
+///
+/// Calls take an idle instance when one is available and otherwise
+/// initialize one on demand. Instances go back to the pool after use until
+/// it holds `size` again; extras are dropped, as are instances whose call
+/// failed or was cut short.
+pub(crate) struct WarmPool {
+    size: usize,
+    idle: Mutex<Vec<Arc<dyn EnterpriseCapability>>>,
+}
+
+/// Instance serving one call, returned to its pool when dropped
+pub(crate) struct Instance {
+    capability: Arc<dyn EnterpriseCapability>,
+    pool: Option<Arc<WarmPool>>,
+}
+
+impl WarmPool {
+    /// Initialize `size` instances from `factory`
+    ///
+    /// A capability with nothing to warm yields an empty pool, and every
+    /// call is then served by the registered capability itself.
+    pub(crate) async fn fill(factory: &dyn EnterpriseCapability, size: usize) -> Result<Arc<Self>> {
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            match factory.warm().await? {
+                Some(instance) => idle.push(instance),
+                None => break,
+            }
+        }
+        Ok(Arc::new(Self { size, idle: Mutex::new(idle) }))
+    }
+
+    /// Idle instance, or one initialized now if all are checked out
+    pub(crate) async fn checkout(self: &Arc<Self>, factory: &Arc<dyn EnterpriseCapability>) -> Result<Instance> {
+        let idle = self.lock().pop();
+        let capability = match idle {
+            Some(instance) => instance,
+            None => match factory.warm().await? {
+                Some(instance) => {
+                    debug!(size = self.size, "Warm pool exhausted, initializing capability on demand");
+                    instance
+                }
+                None => return Ok(Instance::unpooled(factory.clone())),
+            },
+        };
+        Ok(Instance { capability, pool: Some(self.clone()) })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Arc<dyn EnterpriseCapability>>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Instance {
+    /// The registered capability serving calls directly
+    pub(crate) fn unpooled(capability: Arc<dyn EnterpriseCapability>) -> Self {
+        Self { capability, pool: None }
+    }
+
+    /// Keep this instance out of the pool once dropped, as after its call
+    /// failed, panicked or timed out and may have left it inconsistent
+    pub(crate) fn discard(&mut self) {
+        self.pool = None;
+    }
+
+    /// The instance itself, for calls that outlive a borrow of `self`
+    pub(crate) fn capability(&self) -> Arc<dyn EnterpriseCapability> {
+        self.capability.clone()
+    }
+}
+
+impl std::ops::Deref for Instance {
+    type Target = dyn EnterpriseCapability;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.capability
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            let mut idle = pool.lock();
+            if idle.len() < pool.size {
+                idle.push(self.capability.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+// wasm.rs - Sandboxed WASM Capabilities
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use nuzon_core::EnterpriseError;
+use wasmtime::{Config, Engine, Instance, Module, ResourceLimiter, Store, Trap};
+
+use super::{EnterpriseCapability, ExecutionContext, ResourceLimits};
+
+/// Fuel granted per core-second of budget; one unit is roughly one instruction
+const FUEL_PER_CORE_SECOND: f64 = 500_000_000.0;
+/// Granularity of the epoch clock that enforces `timeout_secs`
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+const MAX_TABLE_ELEMENTS: usize = 10_000;
+
+/// Caps linear memory growth and remembers whether the cap was hit
+struct SandboxLimiter {
+    max_memory: usize,
+    memory_exceeded: bool,
+}
+
+impl ResourceLimiter for SandboxLimiter {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> Result<bool> {
+        if desired > self.max_memory {
+            self.memory_exceeded = true;
+            anyhow::bail!("memory growth to {} bytes exceeds sandbox limit", desired);
+        }
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> Result<bool> {
+        Ok(desired <= MAX_TABLE_ELEMENTS)
+    }
+}
+
+/// Advances the engine epoch until the owning capability is dropped
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn start(engine: Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        std::thread::spawn(move || {
+            while !flag.load(Ordering::Relaxed) {
+                std::thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        Self { stop }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Compiled module shared by concurrent executions
+struct Sandbox {
+    engine: Engine,
+    module: Module,
+    limits: ResourceLimits,
+    _ticker: EpochTicker,
+}
+
+/// Capability backed by an untrusted WASM module
+///
+/// The module is instantiated with no imports, so it has no host access
+/// beyond its own linear memory. It must export `memory`, `alloc(len) -> ptr`
+/// and `run(ptr, len) -> i64`: params are written as JSON at the allocated
+/// `ptr`, and `run` returns the JSON result location packed as
+/// `(ptr << 32) | len`. Each execution gets a fresh instance.
+pub struct WasmCapability {
+    sandbox: Arc<Sandbox>,
+    fuel: u64,
+}
+
+impl WasmCapability {
+    /// Compile a module (binary or text format) under the given limits
+    pub fn new(wasm: &[u8], limits: ResourceLimits) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wasm).context("Invalid WASM module")?;
+
+        let core_seconds = limits.max_cpu_cores.max(0.0) as f64 * limits.timeout_secs as f64;
+        Ok(Self {
+            fuel: (core_seconds * FUEL_PER_CORE_SECOND) as u64,
+            sandbox: Arc::new(Sandbox {
+                _ticker: EpochTicker::start(engine.clone()),
+                engine,
+                module,
+                limits,
+            }),
+        })
+    }
+
+    /// Override the fuel budget derived from `ResourceLimits`
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+}
+
+impl Sandbox {
+    fn invoke(&self, input: &[u8], fuel: u64) -> Result<Vec<u8>> {
+        let mut store = Store::new(&self.engine, SandboxLimiter {
+            max_memory: self.limits.max_memory_mb as usize * 1024 * 1024,
+            memory_exceeded: false,
+        });
+        store.limiter(|limiter| limiter);
+        store.set_fuel(fuel)?;
+        let timeout = Duration::from_secs(self.limits.timeout_secs);
+        store.set_epoch_deadline((timeout.as_millis() / EPOCH_TICK.as_millis()).max(1) as u64);
+
+        call_module(&mut store, &self.module, input)
+            .map_err(|error| sandbox_error(error, store.data()))
+    }
+}
+
+fn call_module(store: &mut Store<SandboxLimiter>, module: &Module, input: &[u8]) -> Result<Vec<u8>> {
+    let instance = Instance::new(&mut *store, module, &[])?;
+    let memory = instance.get_memory(&mut *store, "memory")
+        .context("WASM module exports no memory")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+    let run = instance.get_typed_func::<(i32, i32), i64>(&mut *store, "run")?;
+
+    let len = i32::try_from(input.len()).context("Params too large for WASM memory")?;
+    let ptr = alloc.call(&mut *store, len)?;
+    memory.write(&mut *store, ptr as u32 as usize, input)?;
+
+    let packed = run.call(&mut *store, (ptr, len))? as u64;
+    let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+    if out_ptr.checked_add(out_len).map_or(true, |end| end > memory.data_size(&*store)) {
+        anyhow::bail!("WASM result lies outside module memory");
+    }
+    Ok(memory.data(&*store)[out_ptr..out_ptr + out_len].to_vec())
+}
+
+/// Report limiter and runtime traps as resource limits
+fn sandbox_error(error: anyhow::Error, limiter: &SandboxLimiter) -> anyhow::Error {
+    if limiter.memory_exceeded {
+        return EnterpriseError::ResourceLimit("WASM memory limit exceeded".into()).into();
+    }
+    match error.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => EnterpriseError::ResourceLimit("WASM fuel exhausted".into()).into(),
+        Some(Trap::Interrupt) => EnterpriseError::ResourceLimit("WASM execution timed out".into()).into(),
+        _ => error,
+    }
+}
+
+#[async_trait]
+impl EnterpriseCapability for WasmCapability {
+    async fn execute(
+        &self,
+        params: serde_json::Value,
+        _context: ExecutionContext,
+    ) -> Result<serde_json::Value> {
+        let input = serde_json::to_vec(&params)?;
+        let (sandbox, fuel) = (self.sandbox.clone(), self.fuel);
+        let output = tokio::task::spawn_blocking(move || sandbox.invoke(&input, fuel)).await??;
+        Ok(serde_json::from_slice(&output)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolScheduler, ResourceBudget};
+    use tokio::sync::Semaphore;
+
+    const ECHO: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "run") (param $ptr i32) (param $len i32) (result i64)
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+              (i64.extend_i32_u (local.get $len)))))
+    "#;
+
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "run") (param i32 i32) (result i64)
+            (loop $spin (br $spin))
+            (i64.const 0)))
+    "#;
+
+    const GROW: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "run") (param i32 i32) (result i64)
+            (drop (memory.grow (i32.const 64)))
+            (i64.const 0)))
+    "#;
+
+    fn limits() -> ResourceLimits {
+        ResourceLimits { max_memory_mb: 1, max_cpu_cores: 1.0, timeout_secs: 5, scheduler: PoolScheduler::Fifo }
+    }
+
+    async fn context() -> ExecutionContext {
+        ExecutionContext {
+            caller_identity: "sandbox-test".into(),
+            auth_claims: vec![],
+            resource_budget: ResourceBudget {
+                semaphore: Arc::new(Semaphore::new(1)),
+                cpu_cores: 1.0,
+                _guard: Arc::new(Arc::new(Semaphore::new(1)).acquire_owned().await.unwrap()),
+            },
+            trace: None,
+            deadline: None,
+            expires_at: None,
+            idempotency_key: None,
+        }
+    }
+
+    fn assert_resource_limit(error: anyhow::Error) {
+        assert!(
+            matches!(error.downcast_ref::<EnterpriseError>(), Some(EnterpriseError::ResourceLimit(_))),
+            "unexpected error: {:?}", error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_echo_module_round_trips_params() {
+        let capability = WasmCapability::new(ECHO.as_bytes(), limits()).unwrap();
+        let params = serde_json::json!({"sku": "A-1", "qty": [1, 2, 3]});
+        let result = capability.execute(params.clone(), context().await).await.unwrap();
+        assert_eq!(result, params);
+    }
+
+    #[tokio::test]
+    async fn test_runaway_module_exhausts_fuel() {
+        let capability = WasmCapability::new(SPIN.as_bytes(), limits()).unwrap().with_fuel(1_000_000);
+        let error = capability.execute(serde_json::Value::Null, context().await).await.unwrap_err();
+        assert_resource_limit(error);
+    }
+
+    #[tokio::test]
+    async fn test_memory_growth_beyond_limit_fails() {
+        let capability = WasmCapability::new(GROW.as_bytes(), limits()).unwrap();
+        let error = capability.execute(serde_json::Value::Null, context().await).await.unwrap_err();
+        assert_resource_limit(error);
+    }
+}
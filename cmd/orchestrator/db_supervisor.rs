@@ -0,0 +1,335 @@
+// db_supervisor.rs - Background reconnection for the database pool
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use async_trait::async_trait;
+use cirium_core::db::PgPool;
+use prometheus::{IntCounter, IntGauge, Registry};
+use serde::Deserialize;
+use tokio::{sync::watch, time::{sleep, timeout}};
+use tonic::{Request, Status};
+use tracing::{error, info, warn};
+
+use crate::error::CoordinationError;
+
+/// Backoff applied while the pool has no healthy connections
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReconnectPolicy {
+    /// Between health checks of an established pool
+    #[serde(default = "default_check_interval_ms")]
+    pub check_interval_ms: u64,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Ceiling the backoff doubles up to; a failed attempt made after
+    /// backing off this long is reported to callers
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Longest a probe query may take before it counts as failed
+    #[serde(default = "default_probe_timeout_ms")]
+    pub probe_timeout_ms: u64,
+}
+
+fn default_check_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    250
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_probe_timeout_ms() -> u64 {
+    5_000
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            check_interval_ms: default_check_interval_ms(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            probe_timeout_ms: default_probe_timeout_ms(),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Policy from `DB_RECONNECT_*` environment variables, such as
+    /// `DB_RECONNECT_MAX_BACKOFF_MS`, with defaults for any left unset
+    ///
+    /// Read here rather than from `cirium_core::config::Config`, which has
+    /// no place for the orchestrator's own settings.
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        config::Config::builder()
+            .add_source(config::Environment::with_prefix("DB_RECONNECT").try_parsing(true))
+            .build()?
+            .try_deserialize()
+    }
+}
+
+/// A pool the supervisor can probe
+#[async_trait]
+pub trait SupervisedPool: Send + Sync + 'static {
+    /// Run a query on one of the pool's connections, proving the database
+    /// answers rather than merely that sockets are open
+    async fn probe(&self) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl SupervisedPool for PgPool {
+    async fn probe(&self) -> Result<(), sqlx::Error> {
+        let mut connection = self.acquire().await?;
+        sqlx::query("SELECT 1").execute(&mut *connection).await.map(drop)
+    }
+}
+
+/// Pool health as seen by callers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolHealth {
+    Healthy,
+    /// Reconnecting and still below the backoff ceiling
+    Reconnecting,
+    /// Reconnecting past the backoff ceiling
+    Unavailable,
+}
+
+#[derive(Debug, Clone)]
+struct SupervisorMetrics {
+    reconnect_attempts: IntCounter,
+    healthy: IntGauge,
+}
+
+impl SupervisorMetrics {
+    fn register(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let metrics = Self {
+            reconnect_attempts: IntCounter::new(
+                "db_reconnect_attempts_total",
+                "Attempts to reconnect a database pool that lost every connection"
+            )?,
+            healthy: IntGauge::new("db_pool_healthy", "1 while the database pool has a healthy connection")?,
+        };
+        registry.register(Box::new(metrics.reconnect_attempts.clone()))?;
+        registry.register(Box::new(metrics.healthy.clone()))?;
+        Ok(metrics)
+    }
+}
+
+/// Watches an established pool and reconnects it after total connection loss
+///
+/// Every `check_interval_ms` the pool is probed with a query; once one fails
+/// the supervisor retries with exponential backoff until a probe succeeds.
+/// Callers gated by `check` or `interceptor` are let through while the
+/// backoff is below its ceiling, their queries waiting on the pool, and see
+/// `CoordinationError::DbConnection` once it has reached the ceiling without
+/// a connection. The background task stops when the supervisor is dropped.
+pub struct PoolSupervisor<P> {
+    pool: Arc<P>,
+    policy: ReconnectPolicy,
+    health: watch::Sender<PoolHealth>,
+    metrics: SupervisorMetrics,
+}
+
+impl<P: SupervisedPool> PoolSupervisor<P> {
+    /// Start supervising `pool`, registering its metrics with `registry`
+    pub fn spawn(pool: Arc<P>, policy: ReconnectPolicy, registry: &Registry) -> Result<Arc<Self>, prometheus::Error> {
+        let metrics = SupervisorMetrics::register(registry)?;
+        metrics.healthy.set(1);
+        let supervisor = Arc::new(Self { pool, policy, health: watch::Sender::new(PoolHealth::Healthy), metrics });
+        let interval = Duration::from_millis(supervisor.policy.check_interval_ms);
+        tokio::spawn(supervise(Arc::downgrade(&supervisor), interval));
+        Ok(supervisor)
+    }
+
+    pub fn health(&self) -> PoolHealth {
+        *self.health.borrow()
+    }
+
+    /// Fail once reconnection has backed off to its ceiling
+    pub fn check(&self) -> Result<(), CoordinationError> {
+        match self.health() {
+            PoolHealth::Unavailable => Err(CoordinationError::DbConnection(sqlx::Error::PoolTimedOut)),
+            PoolHealth::Healthy | PoolHealth::Reconnecting => Ok(()),
+        }
+    }
+
+    /// Interceptor refusing RPCs as `UNAVAILABLE` while `check` fails
+    pub fn interceptor(self: &Arc<Self>) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+        let supervisor = self.clone();
+        move |req: Request<()>| match supervisor.check() {
+            Ok(()) => Ok(req),
+            Err(e) => Err(Status::unavailable(e.to_string())),
+        }
+    }
+
+    /// Probe the pool, failing a probe that outlasts `probe_timeout_ms`
+    async fn probe(&self) -> Result<(), sqlx::Error> {
+        let limit = Duration::from_millis(self.policy.probe_timeout_ms);
+        timeout(limit, self.pool.probe()).await.unwrap_or(Err(sqlx::Error::PoolTimedOut))
+    }
+
+    fn set_health(&self, health: PoolHealth) {
+        self.metrics.healthy.set(i64::from(health == PoolHealth::Healthy));
+        self.health.send_replace(health);
+    }
+
+    /// Retry until the pool has a connection again
+    async fn recover(&self) {
+        warn!("Database pool has no healthy connections, reconnecting");
+        self.set_health(PoolHealth::Reconnecting);
+        let max_backoff = Duration::from_millis(self.policy.max_backoff_ms);
+        let mut backoff = Duration::from_millis(self.policy.initial_backoff_ms).min(max_backoff);
+        let mut attempts = 0u64;
+        loop {
+            attempts += 1;
+            self.metrics.reconnect_attempts.inc();
+            match self.probe().await {
+                Ok(()) => {
+                    info!(attempts, "Database pool reconnected");
+                    self.set_health(PoolHealth::Healthy);
+                    return;
+                }
+                Err(e) if backoff >= max_backoff => {
+                    if self.health() != PoolHealth::Unavailable {
+                        error!(attempts, error = %e, "Database unreachable at the reconnect backoff ceiling");
+                        self.set_health(PoolHealth::Unavailable);
+                    }
+                }
+                Err(e) => warn!(attempts, error = %e, backoff_ms = backoff.as_millis() as u64, "Database reconnect failed"),
+            }
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+}
+
+async fn supervise<P: SupervisedPool>(supervisor: Weak<PoolSupervisor<P>>, interval: Duration) {
+    loop {
+        sleep(interval).await;
+        let Some(supervisor) = supervisor.upgrade() else {
+            return;
+        };
+        if let Err(e) = supervisor.probe().await {
+            warn!(error = %e, "Database pool health probe failed");
+            supervisor.recover().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io,
+        sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+    };
+
+    /// Pool whose connections can be cut, refusing a set number of
+    /// probes before the database comes back
+    #[derive(Default)]
+    struct FlakyPool {
+        connections: AtomicUsize,
+        refusals: AtomicU32,
+    }
+
+    #[async_trait]
+    impl SupervisedPool for FlakyPool {
+        async fn probe(&self) -> Result<(), sqlx::Error> {
+            if self.connections.load(Ordering::SeqCst) > 0 {
+                return Ok(());
+            }
+            if self.refusals.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+                return Err(sqlx::Error::Io(io::Error::from(io::ErrorKind::ConnectionRefused)));
+            }
+            self.connections.store(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Pool whose open sockets never answer a query
+    struct HungPool;
+
+    #[async_trait]
+    impl SupervisedPool for HungPool {
+        async fn probe(&self) -> Result<(), sqlx::Error> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool_recovers_from_total_connection_loss() {
+        let pool = Arc::new(FlakyPool { connections: AtomicUsize::new(4), ..FlakyPool::default() });
+        let policy = ReconnectPolicy {
+            check_interval_ms: 100,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 400,
+            ..ReconnectPolicy::default()
+        };
+        let registry = Registry::new();
+        let supervisor = PoolSupervisor::spawn(pool.clone(), policy, &registry).unwrap();
+        let metrics = supervisor.metrics.clone();
+        assert!(supervisor.check().is_ok());
+
+        // The probe noticing the loss is refused, then, backing off 100,
+        // 200, then 400ms, the attempt at the ceiling fails and two more
+        // follow it before the database is back
+        pool.refusals.store(6, Ordering::SeqCst);
+        pool.connections.store(0, Ordering::SeqCst);
+        let mut health = supervisor.health.subscribe();
+        health.wait_for(|health| *health == PoolHealth::Unavailable).await.unwrap();
+        assert_eq!(metrics.healthy.get(), 0);
+        assert_eq!(metrics.reconnect_attempts.get(), 3);
+        assert!(matches!(supervisor.check(), Err(CoordinationError::DbConnection(_))));
+        let refused = (supervisor.interceptor())(Request::new(())).unwrap_err();
+        assert_eq!(refused.code(), tonic::Code::Unavailable);
+
+        health.wait_for(|health| *health == PoolHealth::Healthy).await.unwrap();
+        assert!(supervisor.check().is_ok());
+        assert!((supervisor.interceptor())(Request::new(())).is_ok());
+        assert_eq!(pool.connections.load(Ordering::SeqCst), 1);
+        assert_eq!(metrics.reconnect_attempts.get(), 6);
+        assert_eq!(metrics.healthy.get(), 1);
+
+        // The metrics are exported under the names dashboards expect
+        let names: Vec<_> = registry.gather().iter().map(|family| family.get_name().to_string()).collect();
+        assert!(names.contains(&"db_reconnect_attempts_total".to_string()));
+        assert!(names.contains(&"db_pool_healthy".to_string()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_callers_wait_out_backoff_below_the_ceiling() {
+        let pool = Arc::new(FlakyPool { refusals: AtomicU32::new(2), ..FlakyPool::default() });
+        let policy = ReconnectPolicy {
+            check_interval_ms: 100,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 10_000,
+            ..ReconnectPolicy::default()
+        };
+        let supervisor = PoolSupervisor::spawn(pool.clone(), policy, &Registry::new()).unwrap();
+
+        let mut health = supervisor.health.subscribe();
+        health.wait_for(|health| *health == PoolHealth::Reconnecting).await.unwrap();
+        assert!(supervisor.check().is_ok());
+        health.wait_for(|health| *health == PoolHealth::Healthy).await.unwrap();
+        assert_eq!(supervisor.metrics.reconnect_attempts.get(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_open_connections_that_never_answer_are_unhealthy() {
+        let policy = ReconnectPolicy {
+            check_interval_ms: 100,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 100,
+            probe_timeout_ms: 50,
+        };
+        let supervisor = PoolSupervisor::spawn(Arc::new(HungPool), policy, &Registry::new()).unwrap();
+
+        let mut health = supervisor.health.subscribe();
+        health.wait_for(|health| *health == PoolHealth::Unavailable).await.unwrap();
+        assert!(supervisor.check().is_err());
+        assert_eq!(supervisor.metrics.healthy.get(), 0);
+    }
+}
@@ -3,7 +3,7 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{future::Future, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 use cirium_core::{
     config::{load_config, Config},
     coordinator::QuantumCoordinator,
@@ -16,11 +16,39 @@ use cirium_core::{
     },
     telemetry::{init_tracing, shutdown_tracing},
 };
-use tokio::{signal, sync::mpsc};
-use tonic::transport::Server;
-use tracing::{info, error};
+use nuzon_core::crypto::quantum_safe::kyber_tls;
+use tokio::{net::TcpListener, signal, sync::mpsc};
+use tokio_rustls::TlsAcceptor;
+use tonic::{service::interceptor::InterceptedService, transport::Server};
+use tracing::{info, error, warn};
 
+mod db_supervisor;
 mod error;
+mod rate_limit;
+mod shutdown;
+mod trace_propagation;
+mod wire;
+
+use db_supervisor::{PoolSupervisor, ReconnectPolicy};
+use error::CoordinationError;
+use rate_limit::{RpcRateLimitConfig, RpcRateLimiter};
+use shutdown::{ConnectionTracker, Drain, ShutdownReport};
+use trace_propagation::TraceScope;
+use wire::{Dispatcher, WireSettings};
+
+/// Attempts for each startup step before giving up on a transient failure
+const STARTUP_ATTEMPTS: u32 = 10;
+const STARTUP_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const STARTUP_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Longest the metrics exporter may take to flush before it is aborted
+const METRICS_FLUSH_DEADLINE: Duration = Duration::from_secs(10);
+/// Longest the coordinator may take to stop before it is abandoned
+const COORDINATOR_STOP_DEADLINE: Duration = Duration::from_secs(10);
+/// Longest closing the database pool may wait for connections in use
+const POOL_CLOSE_DEADLINE: Duration = Duration::from_secs(10);
+/// File receiving the last shutdown's report, if set
+const SHUTDOWN_REPORT_PATH_VAR: &str = "SHUTDOWN_REPORT_PATH";
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -36,12 +64,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let server_kp = kyber_provider.generate_keypair().await?;
     
     // Initialize database pool
-    let db_pool = PgPool::connect_with_retry(
-        &config.database.url,
-        config.database.max_connections,
-        Duration::from_secs(5),
-        10,
-    ).await?;
+    let db_pool = retry_startup("database", || async {
+        PgPool::connect(&config.database.url, config.database.max_connections)
+            .await
+            .map_err(CoordinationError::from)
+    }).await?;
+
+    // Reconnect the pool in the background should it ever lose every connection
+    let db_supervisor = PoolSupervisor::spawn(
+        Arc::new(db_pool.clone()),
+        ReconnectPolicy::from_env()?,
+        prometheus::default_registry(),
+    )?;
     
     // Create metrics registry
     let metrics = MetricsRegistry::new(
@@ -63,8 +97,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .register_encoded_file_descriptor_set(nuzon_core::pb::FILE_DESCRIPTOR_SET)
         .build()?;
 
-    let svc = CoordinatorServiceServer::new(coordinator);
-    let health = HealthServer::new(coordinator.clone());
+    // Throttle RPC traffic per authenticated client
+    let rpc_limiter = RpcRateLimiter::new(RpcRateLimitConfig::from_env()?);
+
+    // Count served RPCs so shutdown can report what it drained
+    let connections = ConnectionTracker::default();
+    let drain = Drain::new(connections.clone());
+
+    // Refuse RPCs once the database has been unreachable past the backoff ceiling
+    let mut limit = rpc_limiter.interceptor();
+    let mut db_available = db_supervisor.interceptor();
+    let svc = InterceptedService::new(
+        TraceScope::new(connections.layer(CoordinatorServiceServer::new(coordinator)))
+            .with_sampling(trace_propagation::sampling_from_env()?),
+        move |req| trace_propagation::extract(db_available(limit(req)?)?),
+    );
+    let health = connections.layer(HealthServer::new(coordinator.clone()));
     
     // Start metrics exporter
     let metrics_handle = metrics.start_exporter().await?;
@@ -74,25 +122,84 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .server_tls_config(server_kp)
         .await?;
 
+    // Serve low-overhead agent traffic over the binary protocol, on its own
+    // listener behind the same Kyber TLS
+    let wire_settings = WireSettings::from_env()?;
+    let wire_listener = match wire_settings.addr {
+        Some(wire_addr) => {
+            let dispatcher = Dispatcher::default()
+                .with_max_frame_size(wire_settings.max_frame_size)
+                .with_max_in_flight(wire_settings.max_in_flight)
+                .register(wire::PING_MSG_TYPE, wire::Ping);
+            let acceptor = TlsAcceptor::from(Arc::new(
+                kyber_tls::configure_server().map_err(|e| CoordinationError::Crypto(e.to_string()))?,
+            ));
+            info!("Serving wire protocol on {}", wire_addr);
+            Some(dispatcher.spawn_listener(TcpListener::bind(wire_addr).await?, acceptor))
+        }
+        None => None,
+    };
+
     let addr: SocketAddr = config.server.addr.parse()?;
     let server = Server::builder()
         .tls_config(tls_config)?
         .add_service(svc)
         .add_service(health)
         .add_service(reflection)
-        .with_graceful_shutdown(shutdown_signal(shutdown_tx.clone()));
+        .with_graceful_shutdown(drain.on_signal(shutdown_signal(shutdown_tx.clone())));
 
     // Start coordination engine
     info!("Starting coordination engine on {}", addr);
     server.serve(addr).await?;
     
     // Cleanup resources
-    shutdown_tx.send(()).await?;
-    metrics_handle.await??;
+    let mut report = ShutdownReport::default();
+    drain.finish(&mut report);
+    if let Some(wire_listener) = wire_listener {
+        shutdown::abort_task(wire_listener, &mut report);
+    }
+    shutdown::stop_coordinator(shutdown_tx, COORDINATOR_STOP_DEADLINE, &mut report).await;
+    let flushed = shutdown::flush_metrics(metrics_handle, METRICS_FLUSH_DEADLINE, &mut report).await;
+    shutdown::close_pool(&db_pool, POOL_CLOSE_DEADLINE, &mut report).await;
+
+    report.log();
+    if let Some(path) = std::env::var_os(SHUTDOWN_REPORT_PATH_VAR).map(PathBuf::from) {
+        if let Err(e) = report.write_to(&path) {
+            warn!(path = %path.display(), error = %e, "Failed to write shutdown report");
+        }
+    }
     shutdown_tracing(telemetry_guard);
+    if let Some(flushed) = flushed {
+        flushed??;
+    }
     Ok(())
 }
 
+/// Run a startup step, retrying only failures classified as transient
+async fn retry_startup<T, F, Fut>(step: &str, mut op: F) -> Result<T, CoordinationError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, CoordinationError>>,
+{
+    let mut backoff = STARTUP_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if e.is_retryable() && attempt < STARTUP_ATTEMPTS => {
+                warn!(step, attempt, error = %e, "Transient startup failure, retrying");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(STARTUP_MAX_BACKOFF);
+                attempt += 1;
+            }
+            Err(e) => {
+                error!(step, attempt, category = ?e.category(), error = %e, "Startup step failed");
+                return Err(e);
+            }
+        }
+    }
+}
+
 async fn shutdown_signal(shutdown_tx: mpsc::Sender<()>) {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -141,4 +248,61 @@ mod error {
         #[error("I/O operation failed: {0}")]
         Io(#[from] std::io::Error),
     }
+
+    /// Whether retrying the failed operation can succeed
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorCategory {
+        /// Outage or contention expected to clear; retry with backoff
+        Transient,
+        /// Wrong input, configuration or peer behaviour; fail fast
+        Permanent,
+    }
+
+    impl CoordinationError {
+        pub fn category(&self) -> ErrorCategory {
+            match self {
+                Self::DbConnection(_)
+                | Self::ResourceExhausted(_)
+                | Self::Transport(_)
+                | Self::Io(_) => ErrorCategory::Transient,
+                Self::Config(_)
+                | Self::Crypto(_)
+                | Self::ProtocolViolation(_) => ErrorCategory::Permanent,
+            }
+        }
+
+        pub fn is_retryable(&self) -> bool {
+            self.category() == ErrorCategory::Transient
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_error_classification() {
+            // tonic::transport::Error has no public constructor; it is transient
+            // alongside the other connectivity failures below
+            let cases = [
+                (CoordinationError::DbConnection(sqlx::Error::PoolTimedOut), ErrorCategory::Transient),
+                (CoordinationError::ResourceExhausted("worker slots".into()), ErrorCategory::Transient),
+                (
+                    CoordinationError::Io(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset")),
+                    ErrorCategory::Transient,
+                ),
+                (
+                    CoordinationError::Config(config::ConfigError::Message("missing server.addr".into())),
+                    ErrorCategory::Permanent,
+                ),
+                (CoordinationError::Crypto("bad key".into()), ErrorCategory::Permanent),
+                (CoordinationError::ProtocolViolation("unexpected epoch".into()), ErrorCategory::Permanent),
+            ];
+
+            for (error, expected) in cases {
+                assert_eq!(error.category(), expected, "{}", error);
+                assert_eq!(error.is_retryable(), expected == ErrorCategory::Transient, "{}", error);
+            }
+        }
+    }
 }
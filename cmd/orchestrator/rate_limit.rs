@@ -0,0 +1,197 @@
+// rate_limit.rs - Per-client gRPC rate limiting
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use dashmap::DashMap;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tonic::{metadata::MetadataValue, Request, Status};
+
+/// How often idle buckets are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Token bucket parameters applied to each client identity
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcRateLimitConfig {
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_requests_per_second() -> f64 {
+    500.0
+}
+
+fn default_burst() -> u32 {
+    1000
+}
+
+impl Default for RpcRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: default_requests_per_second(),
+            burst: default_burst(),
+        }
+    }
+}
+
+impl RpcRateLimitConfig {
+    /// Limits from `RPC_RATE_LIMIT_*` environment variables, such as
+    /// `RPC_RATE_LIMIT_BURST`, with defaults for any left unset
+    ///
+    /// Read here rather than from `cirium_core::config::Config`, which has
+    /// no place for the orchestrator's own settings.
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        config::Config::builder()
+            .add_source(config::Environment::with_prefix("RPC_RATE_LIMIT").try_parsing(true))
+            .build()?
+            .try_deserialize()
+    }
+
+    /// Time for an empty bucket to refill completely
+    fn refill_time(&self) -> Duration {
+        Duration::try_from_secs_f64(self.burst as f64 / self.requests_per_second).unwrap_or(Duration::MAX)
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Shared limiter state backing the tonic interceptor
+///
+/// A bucket left idle long enough to refill is indistinguishable from a new
+/// one, so such buckets are dropped periodically to bound memory.
+#[derive(Clone)]
+pub struct RpcRateLimiter {
+    config: RpcRateLimitConfig,
+    buckets: Arc<DashMap<String, Mutex<TokenBucket>>>,
+    last_sweep: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RpcRateLimiter {
+    pub fn new(config: RpcRateLimitConfig) -> Self {
+        Self { config, buckets: Arc::new(DashMap::new()), last_sweep: Arc::default() }
+    }
+
+    /// Interceptor suitable for `*Server::with_interceptor`
+    pub fn interceptor(&self) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+        let limiter = self.clone();
+        move |req: Request<()>| limiter.check(req)
+    }
+
+    fn check(&self, req: Request<()>) -> Result<Request<()>, Status> {
+        let identity = client_identity(&req);
+        match self.try_acquire(&identity) {
+            Ok(()) => Ok(req),
+            Err(retry_after) => {
+                let mut status = Status::resource_exhausted(
+                    format!("rate limit exceeded for client {}", identity)
+                );
+                let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+                if let Ok(value) = MetadataValue::try_from(secs.to_string()) {
+                    status.metadata_mut().insert("retry-after", value);
+                }
+                Err(status)
+            }
+        }
+    }
+
+    /// Take one token, or return how long until one becomes available
+    fn try_acquire(&self, identity: &str) -> Result<(), Duration> {
+        let due = {
+            let mut last_sweep = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let due = last_sweep.map_or(true, |at| now.duration_since(at) >= SWEEP_INTERVAL);
+            if due {
+                *last_sweep = Some(now);
+            }
+            due
+        };
+        if due {
+            self.sweep(Instant::now());
+        }
+
+        let entry = self.buckets.entry(identity.to_string())
+            .or_insert_with(|| Mutex::new(TokenBucket {
+                tokens: self.config.burst as f64,
+                last_refill: Instant::now(),
+            }));
+        let mut bucket = entry.lock().unwrap_or_else(|e| e.into_inner());
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.config.requests_per_second)
+            .min(self.config.burst as f64);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / self.config.requests_per_second.max(f64::EPSILON)))
+        }
+    }
+
+    fn sweep(&self, now: Instant) {
+        let refill_time = self.config.refill_time();
+        self.buckets.retain(|_, bucket| {
+            let bucket = bucket.get_mut().unwrap_or_else(|e| e.into_inner());
+            now.duration_since(bucket.last_refill) < refill_time
+        });
+    }
+}
+
+/// Client identity from the mTLS leaf certificate, falling back to the peer address
+fn client_identity<T>(req: &Request<T>) -> String {
+    if let Some(leaf) = req.peer_certs().and_then(|certs| certs.first().cloned()) {
+        return hex::encode(Sha256::digest(leaf.as_ref()));
+    }
+
+    req.remote_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "anonymous".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excess_calls_are_resource_exhausted() {
+        let limiter = RpcRateLimiter::new(RpcRateLimitConfig {
+            requests_per_second: 1.0,
+            burst: 5,
+        });
+        let mut intercept = limiter.interceptor();
+
+        let results: Vec<_> = (0..20)
+            .map(|_| intercept(Request::new(())))
+            .collect();
+
+        let rejected: Vec<_> = results.iter()
+            .filter_map(|r| r.as_ref().err())
+            .collect();
+        assert_eq!(results.len() - rejected.len(), 5);
+        assert!(rejected.iter().all(|s| s.code() == tonic::Code::ResourceExhausted));
+        assert!(rejected[0].metadata().get("retry-after").is_some());
+    }
+
+    #[test]
+    fn test_refilled_buckets_are_swept() {
+        let config = RpcRateLimitConfig { requests_per_second: 10.0, burst: 2 };
+        let limiter = RpcRateLimiter::new(config.clone());
+        limiter.try_acquire("a").unwrap();
+        limiter.try_acquire("b").unwrap();
+        assert_eq!(limiter.buckets.len(), 2);
+
+        limiter.sweep(Instant::now() + Duration::from_millis(100));
+        assert_eq!(limiter.buckets.len(), 2);
+        limiter.sweep(Instant::now() + config.refill_time());
+        assert!(limiter.buckets.is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+// shutdown.rs - Record of what the graceful shutdown actually did
+use std::{
+    future::Future,
+    io,
+    path::Path,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use async_trait::async_trait;
+use cirium_core::db::PgPool;
+use serde::Serialize;
+use tokio::{
+    sync::mpsc,
+    task::{JoinError, JoinHandle},
+};
+use tonic::server::NamedService;
+use tower::Service;
+use tracing::{info, warn};
+
+/// Numbers contributed by each component as the orchestrator shuts down
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ShutdownReport {
+    /// RPCs in flight when the signal arrived, each let run to completion
+    pub connections_drained: u64,
+    /// Background tasks aborted, either because they run until stopped or
+    /// for missing the shutdown deadline
+    pub tasks_aborted: u64,
+    pub metrics_flush_ms: u64,
+    /// From the signal until the server stopped serving
+    pub drain_ms: u64,
+    /// From the server stopping until the coordinator did
+    pub coordinator_stop_ms: u64,
+    /// Database connections open when the pool was closed
+    pub pool_connections_closed: u64,
+    pub pool_close_ms: u64,
+}
+
+impl ShutdownReport {
+    pub fn log(&self) {
+        info!(
+            connections_drained = self.connections_drained,
+            tasks_aborted = self.tasks_aborted,
+            metrics_flush_ms = self.metrics_flush_ms,
+            drain_ms = self.drain_ms,
+            coordinator_stop_ms = self.coordinator_stop_ms,
+            pool_connections_closed = self.pool_connections_closed,
+            pool_close_ms = self.pool_close_ms,
+            "Shutdown complete"
+        );
+    }
+
+    /// Replace the last-shutdown record at `path` with this report
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+/// Counts RPCs currently being served
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTracker {
+    active: Arc<AtomicU64>,
+}
+
+/// One tracked RPC, counted until dropped
+#[derive(Debug)]
+pub struct Tracked {
+    active: Arc<AtomicU64>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConnectionTracker {
+    pub fn active(&self) -> u64 {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn track(&self) -> Tracked {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        Tracked { active: self.active.clone() }
+    }
+
+    pub fn layer<S>(&self, inner: S) -> TrackConnections<S> {
+        TrackConnections { inner, tracker: self.clone() }
+    }
+}
+
+/// Keeps each request counted by a `ConnectionTracker` while it is served
+#[derive(Debug, Clone)]
+pub struct TrackConnections<S> {
+    inner: S,
+    tracker: ConnectionTracker,
+}
+
+impl<S, B> Service<http::Request<B>> for TrackConnections<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let tracked = self.tracker.track();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let _tracked = tracked;
+            future.await
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for TrackConnections<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+/// Times the drain from the shutdown signal to the server's exit
+#[derive(Debug, Clone)]
+pub struct Drain {
+    tracker: ConnectionTracker,
+    /// When the signal fired and how many RPCs were then in flight
+    started: Arc<Mutex<Option<(Instant, u64)>>>,
+}
+
+impl Drain {
+    pub fn new(tracker: ConnectionTracker) -> Self {
+        Self { tracker, started: Arc::default() }
+    }
+
+    /// Wrap the server's shutdown signal so the drain is measured from it
+    pub fn on_signal<F: Future<Output = ()>>(&self, signal: F) -> impl Future<Output = ()> {
+        let (tracker, started) = (self.tracker.clone(), self.started.clone());
+        async move {
+            signal.await;
+            *started.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), tracker.active()));
+        }
+    }
+
+    /// Add the drain to `report` once the server has stopped; a server
+    /// that stopped without the signal drained nothing
+    pub fn finish(&self, report: &mut ShutdownReport) {
+        if let Some((at, in_flight)) = *self.started.lock().unwrap_or_else(|e| e.into_inner()) {
+            report.connections_drained = in_flight;
+            report.drain_ms = millis(at.elapsed());
+        }
+    }
+}
+
+/// Wait up to `deadline` for the metrics exporter's final flush, aborting
+/// it past that
+///
+/// Returns the exporter's outcome, or `None` if it was aborted.
+pub async fn flush_metrics<T>(
+    mut exporter: JoinHandle<T>,
+    deadline: Duration,
+    report: &mut ShutdownReport,
+) -> Option<Result<T, JoinError>> {
+    let started = Instant::now();
+    let flushed = match tokio::time::timeout(deadline, &mut exporter).await {
+        Ok(flushed) => Some(flushed),
+        Err(_) => {
+            warn!(deadline_ms = millis(deadline), "Metrics exporter missed the shutdown deadline, aborting");
+            exporter.abort();
+            report.tasks_aborted += 1;
+            None
+        }
+    };
+    report.metrics_flush_ms = millis(started.elapsed());
+    flushed
+}
+
+/// Abort a task that runs until stopped, such as a listener
+pub fn abort_task<T>(task: JoinHandle<T>, report: &mut ShutdownReport) {
+    if !task.is_finished() {
+        task.abort();
+        report.tasks_aborted += 1;
+    }
+}
+
+/// Signal the coordinator to stop and wait up to `deadline` for it to drop
+/// its shutdown receiver, which it does once stopped
+///
+/// A coordinator still running past the deadline is abandoned and counted
+/// as aborted.
+pub async fn stop_coordinator(shutdown_tx: mpsc::Sender<()>, deadline: Duration, report: &mut ShutdownReport) {
+    let started = Instant::now();
+    // Fails only if a signal is already pending or the coordinator stopped
+    let _ = shutdown_tx.try_send(());
+    if tokio::time::timeout(deadline, shutdown_tx.closed()).await.is_err() {
+        warn!(deadline_ms = millis(deadline), "Coordinator missed the shutdown deadline, abandoning it");
+        report.tasks_aborted += 1;
+    }
+    report.coordinator_stop_ms = millis(started.elapsed());
+}
+
+/// Connection pool closed once nothing is left to use it
+#[async_trait]
+pub trait ClosablePool: Send + Sync {
+    /// Connections currently open, idle or in use
+    fn open_connections(&self) -> u64;
+    /// Close every connection, waiting for those in use to be returned
+    async fn close(&self);
+}
+
+#[async_trait]
+impl ClosablePool for PgPool {
+    fn open_connections(&self) -> u64 {
+        u64::from(self.size())
+    }
+
+    async fn close(&self) {
+        PgPool::close(self).await
+    }
+}
+
+/// Close `pool`, waiting up to `deadline` for connections in use to be
+/// returned
+pub async fn close_pool(pool: &dyn ClosablePool, deadline: Duration, report: &mut ShutdownReport) {
+    let started = Instant::now();
+    report.pool_connections_closed = pool.open_connections();
+    if tokio::time::timeout(deadline, pool.close()).await.is_err() {
+        warn!(deadline_ms = millis(deadline), "Database pool missed the shutdown deadline");
+        report.tasks_aborted += 1;
+    }
+    report.pool_close_ms = millis(started.elapsed());
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_report_counts_drained_connections_and_aborted_tasks() {
+        let tracker = ConnectionTracker::default();
+        let drain = Drain::new(tracker.clone());
+
+        // Three RPCs block until released; one more finishes before the signal
+        let (release, released) = watch::channel(false);
+        let svc = tracker.layer(tower::service_fn(move |_: http::Request<()>| {
+            let mut released = released.clone();
+            async move {
+                let _ = released.wait_for(|released| *released).await;
+                Ok::<_, Infallible>(())
+            }
+        }));
+        let blocked: Vec<_> = (0..3)
+            .map(|_| tokio::spawn(svc.clone().oneshot(http::Request::new(()))))
+            .collect();
+        drop(tracker.track());
+        while tracker.active() < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        drain.on_signal(std::future::ready(())).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        release.send(true).unwrap();
+        for call in blocked {
+            call.await.unwrap().unwrap();
+        }
+        assert_eq!(tracker.active(), 0);
+
+        let mut report = ShutdownReport::default();
+        drain.finish(&mut report);
+        assert_eq!(report.connections_drained, 3);
+        assert!(report.drain_ms >= 20);
+
+        let flushed = flush_metrics(tokio::spawn(async { 7 }), Duration::from_secs(1), &mut report).await;
+        assert_eq!(flushed.unwrap().unwrap(), 7);
+        assert_eq!(report.tasks_aborted, 0);
+
+        let stuck = tokio::spawn(std::future::pending::<()>());
+        assert!(flush_metrics(stuck, Duration::from_millis(10), &mut report).await.is_none());
+        assert_eq!(report.tasks_aborted, 1);
+        assert!(report.metrics_flush_ms >= 10);
+    }
+
+    /// Pool whose connections are returned `in_use` after closing begins
+    struct SlowPool {
+        open: u64,
+        in_use: Duration,
+    }
+
+    #[async_trait]
+    impl ClosablePool for SlowPool {
+        fn open_connections(&self) -> u64 {
+            self.open
+        }
+
+        async fn close(&self) {
+            tokio::time::sleep(self.in_use).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coordinator_listener_and_pool_contribute_to_the_report() {
+        let mut report = ShutdownReport::default();
+
+        let listener = tokio::spawn(std::future::pending::<()>());
+        abort_task(listener, &mut report);
+        let finished = tokio::spawn(async {});
+        while !finished.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        abort_task(finished, &mut report);
+        assert_eq!(report.tasks_aborted, 1);
+
+        // Stops 20ms after the signal by dropping its receiver
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+        let coordinator = tokio::spawn(async move {
+            shutdown_rx.recv().await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        });
+        stop_coordinator(shutdown_tx, Duration::from_secs(1), &mut report).await;
+        assert!(report.coordinator_stop_ms >= 20);
+        assert_eq!(report.tasks_aborted, 1);
+        coordinator.await.unwrap();
+
+        // Never stops
+        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        stop_coordinator(shutdown_tx, Duration::from_millis(10), &mut report).await;
+        assert_eq!(report.tasks_aborted, 2);
+        drop(shutdown_rx);
+
+        close_pool(&SlowPool { open: 4, in_use: Duration::from_millis(20) }, Duration::from_secs(1), &mut report).await;
+        assert_eq!(report.pool_connections_closed, 4);
+        assert!(report.pool_close_ms >= 20);
+        assert_eq!(report.tasks_aborted, 2);
+
+        close_pool(&SlowPool { open: 2, in_use: Duration::from_secs(60) }, Duration::from_millis(10), &mut report).await;
+        assert_eq!(report.tasks_aborted, 3);
+    }
+}
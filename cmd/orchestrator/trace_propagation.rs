@@ -0,0 +1,212 @@
+// trace_propagation.rs - W3C trace context across the gRPC boundary
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use nuzon_core::telemetry::{Sampler, SamplingPolicy, TraceContext, TraceIdRatioSampler};
+use tonic::{metadata::MetadataValue, server::NamedService, Request, Status};
+use tower::Service;
+use tracing::{info_span, Instrument, Span};
+
+const TRACEPARENT: &str = "traceparent";
+
+/// Server interceptor resolving the request's trace context
+///
+/// A valid `traceparent` makes this RPC a child of the caller's span;
+/// anything else starts a new trace. The context is left in the request
+/// extensions for `TraceScope` to install.
+pub fn extract(mut req: Request<()>) -> Result<Request<()>, Status> {
+    let trace = req.metadata()
+        .get(TRACEPARENT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::parse_traceparent)
+        .map_or_else(TraceContext::root, |parent| parent.child());
+    req.extensions_mut().insert(trace);
+    Ok(req)
+}
+
+/// Client interceptor forwarding the current trace context to the callee
+pub fn inject(mut req: Request<()>) -> Result<Request<()>, Status> {
+    if let Some(context) = TraceContext::current() {
+        if let Ok(value) = MetadataValue::try_from(context.to_traceparent()) {
+            req.metadata_mut().insert(TRACEPARENT, value);
+        }
+    }
+    Ok(req)
+}
+
+/// Sampling from `TRACE_SAMPLING_*` environment variables: `_RATE`, and
+/// `_ALWAYS_ON` listing comma-separated RPC paths traced regardless of it
+///
+/// Read here rather than from `cirium_core::config::Config`, which has no
+/// place for the orchestrator's own settings.
+pub fn sampling_from_env() -> Result<SamplingPolicy, config::ConfigError> {
+    config::Config::builder()
+        .add_source(
+            config::Environment::with_prefix("TRACE_SAMPLING")
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("always_on"),
+        )
+        .build()?
+        .try_deserialize()
+}
+
+/// Runs each request inside its trace: a root `rpc` span plus the
+/// task-local context read by `TraceContext::current`, which capability
+/// executions started by the handler continue
+///
+/// The sampling decision is made here, keyed by the RPC path, and written
+/// to the context's sampled flag; an unsampled request gets no span, and
+/// the cleared flag travels with the context to everything it calls.
+///
+/// Must sit inside the interceptor that runs `extract`, i.e.
+/// `InterceptedService::new(TraceScope::new(server), interceptor)`.
+#[derive(Clone)]
+pub struct TraceScope<S> {
+    inner: S,
+    policy: Arc<SamplingPolicy>,
+    sampler: Arc<dyn Sampler>,
+}
+
+impl<S> TraceScope<S> {
+    /// Scope sampling every request
+    pub fn new(inner: S) -> Self {
+        Self { inner, policy: Arc::new(SamplingPolicy::default()), sampler: Arc::new(TraceIdRatioSampler) }
+    }
+
+    pub fn with_sampling(mut self, policy: SamplingPolicy) -> Self {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: Arc<dyn Sampler>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for TraceScope<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let mut trace = req.extensions()
+            .get::<TraceContext>()
+            .cloned()
+            .unwrap_or_else(TraceContext::root);
+        let span = if self.policy.decide(&*self.sampler, req.uri().path(), &mut trace) {
+            info_span!(
+                parent: None,
+                "rpc",
+                path = %req.uri().path(),
+                trace_id = %trace.trace_id,
+                span_id = %trace.span_id,
+                parent_span_id = trace.parent_span_id.as_deref(),
+            )
+        } else {
+            Span::none()
+        };
+        let future = self.inner.call(req);
+        Box::pin(trace.scope(future.instrument(span)))
+    }
+}
+
+impl<S: NamedService> NamedService for TraceScope<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::Infallible, sync::{Arc, Mutex}};
+    use tonic::{body::BoxBody, service::interceptor::InterceptedService};
+    use tower::ServiceExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const CALLER_SPAN: &str = "00f067aa0ba902b7";
+
+    #[tokio::test]
+    async fn test_traceparent_flows_through_rpc_and_outbound_calls() {
+        let seen = Arc::new(Mutex::new(None));
+        let handler = {
+            let seen = seen.clone();
+            tower::service_fn(move |_req: http::Request<BoxBody>| {
+                let seen = seen.clone();
+                async move {
+                    let served = TraceContext::current().expect("handler runs inside the trace");
+                    // Downstream async work still sees the context
+                    let downstream = async { TraceContext::current().unwrap().child() }.await;
+                    let outbound = inject(Request::new(())).unwrap();
+                    let forwarded = outbound.metadata().get(TRACEPARENT).unwrap().to_str().unwrap().to_string();
+                    *seen.lock().unwrap() = Some((served, downstream, forwarded));
+                    Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+                }
+            })
+        };
+
+        let service = InterceptedService::new(TraceScope::new(handler), extract);
+        let request = http::Request::builder()
+            .uri("/nuzon.coordinator.v1.CoordinatorService/Commit")
+            .header(TRACEPARENT, format!("00-{}-{}-01", TRACE_ID, CALLER_SPAN))
+            .body(tonic::body::empty_body())
+            .unwrap();
+        service.oneshot(request).await.unwrap();
+
+        let (served, downstream, forwarded) = seen.lock().unwrap().take().unwrap();
+        assert_eq!(served.trace_id, TRACE_ID);
+        assert_eq!(served.parent_span_id.as_deref(), Some(CALLER_SPAN));
+        assert_eq!(downstream.trace_id, TRACE_ID);
+        assert_ne!(downstream.span_id, served.span_id);
+        assert_eq!(forwarded, served.to_traceparent());
+
+        // No context leaks outside the served request
+        assert!(TraceContext::current().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sampling_decided_at_ingress_and_carried_downstream() {
+        const COMMIT: &str = "/nuzon.coordinator.v1.CoordinatorService/Commit";
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let seen = seen.clone();
+            tower::service_fn(move |_req: http::Request<BoxBody>| {
+                let seen = seen.clone();
+                async move {
+                    let served = TraceContext::current().unwrap();
+                    let outbound = inject(Request::new(())).unwrap();
+                    let forwarded = outbound.metadata().get(TRACEPARENT).unwrap().to_str().unwrap().to_string();
+                    seen.lock().unwrap().push((served.is_sampled(), forwarded));
+                    Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+                }
+            })
+        };
+        let scope = TraceScope::new(handler).with_sampling(SamplingPolicy::new(0.0).always_sample(COMMIT));
+        let service = InterceptedService::new(scope, extract);
+
+        for path in [COMMIT, "/nuzon.coordinator.v1.CoordinatorService/ListAgents"] {
+            // The caller's sampled flag is not taken on trust
+            let request = http::Request::builder()
+                .uri(path)
+                .header(TRACEPARENT, format!("00-{}-{}-01", TRACE_ID, CALLER_SPAN))
+                .body(tonic::body::empty_body())
+                .unwrap();
+            service.clone().oneshot(request).await.unwrap();
+        }
+
+        let seen = seen.lock().unwrap();
+        assert!(seen[0].0 && seen[0].1.ends_with("-01"));
+        assert!(!seen[1].0 && seen[1].1.ends_with("-00"));
+    }
+}
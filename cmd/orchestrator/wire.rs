@@ -0,0 +1,502 @@
+// wire.rs - Compact binary protocol for internal agent traffic
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+    sync::{mpsc, oneshot, Semaphore},
+    task::JoinHandle,
+};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, warn};
+
+use crate::error::{CoordinationError, ErrorCategory};
+
+/// Message type of error frames; handlers cannot register it
+pub const ERROR_MSG_TYPE: u16 = u16::MAX;
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+/// Requests a dispatcher runs at once, across all its connections
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 256;
+/// `msg_type` plus `request_id`, following the length prefix
+const HEADER_LEN: usize = 2 + 8;
+/// Frames queued for the writer before senders wait
+const OUTBOUND_CAPACITY: usize = 64;
+
+/// Where and how the binary listener serves agent traffic
+#[derive(Debug, Clone, Deserialize)]
+pub struct WireSettings {
+    /// Listener address; the wire protocol is off when unset
+    #[serde(default)]
+    pub addr: Option<SocketAddr>,
+    #[serde(default = "default_max_frame_size")]
+    pub max_frame_size: usize,
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+fn default_max_frame_size() -> usize {
+    DEFAULT_MAX_FRAME_SIZE
+}
+
+fn default_max_in_flight() -> usize {
+    DEFAULT_MAX_IN_FLIGHT
+}
+
+impl WireSettings {
+    /// Settings from `WIRE_*` environment variables, such as `WIRE_ADDR`,
+    /// with defaults for any left unset
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        config::Config::builder()
+            .add_source(config::Environment::with_prefix("WIRE").try_parsing(true))
+            .build()?
+            .try_deserialize()
+    }
+}
+
+/// One message on the wire
+///
+/// Encoded as a big-endian `u32` length of everything after it, then
+/// `msg_type`, `request_id` and the payload. A response carries the
+/// `msg_type` and `request_id` of its request, or `ERROR_MSG_TYPE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub msg_type: u16,
+    pub request_id: u64,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn encode(&self) -> Vec<u8> {
+        let len = (HEADER_LEN + self.payload.len()) as u32;
+        let mut buf = Vec::with_capacity(4 + len as usize);
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(&self.msg_type.to_be_bytes());
+        buf.extend_from_slice(&self.request_id.to_be_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+
+    /// Read one frame, or `None` at a clean end of stream
+    ///
+    /// The length is checked against `max_frame_size` before the payload
+    /// buffer is allocated.
+    pub async fn read<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        max_frame_size: usize,
+    ) -> Result<Option<Self>, CoordinationError> {
+        let len = match reader.read_u32().await {
+            Ok(len) => len as usize,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if !(HEADER_LEN..=max_frame_size).contains(&len) {
+            return Err(CoordinationError::ProtocolViolation(format!(
+                "frame length {} outside {}..={}", len, HEADER_LEN, max_frame_size
+            )));
+        }
+        let msg_type = reader.read_u16().await?;
+        let request_id = reader.read_u64().await?;
+        let mut payload = vec![0; len - HEADER_LEN];
+        reader.read_exact(&mut payload).await?;
+        Ok(Some(Self { msg_type, request_id, payload }))
+    }
+
+    fn error(request_id: u64, error: &WireError) -> Self {
+        Self { msg_type: ERROR_MSG_TYPE, request_id, payload: error.encode() }
+    }
+}
+
+/// Failure of one request, carried back to the caller in an error frame
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WireError {
+    #[error("no handler for message type {0}")]
+    UnknownType(u16),
+    /// The handler failed; the reason says only whether retrying may help,
+    /// and the error itself stays in the server's log
+    #[error("handler failed: {0}")]
+    Handler(String),
+    /// Error frame whose payload could not be decoded
+    #[error("malformed error frame")]
+    Malformed,
+    /// The connection ended before the response arrived
+    #[error("connection closed")]
+    Closed,
+}
+
+impl WireError {
+    const UNKNOWN_TYPE: u8 = 1;
+    const HANDLER: u8 = 2;
+
+    /// Reasons a handler failure is reported with
+    pub const UNAVAILABLE: &'static str = "temporarily unavailable";
+    pub const REJECTED: &'static str = "request rejected";
+
+    fn handler(error: &CoordinationError) -> Self {
+        Self::Handler(match error.category() {
+            ErrorCategory::Transient => Self::UNAVAILABLE,
+            ErrorCategory::Permanent => Self::REJECTED,
+        }.into())
+    }
+
+    /// Error frame payload: a code byte and its detail
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::UnknownType(msg_type) => [&[Self::UNKNOWN_TYPE][..], &msg_type.to_be_bytes()].concat(),
+            Self::Handler(reason) => [&[Self::HANDLER][..], reason.as_bytes()].concat(),
+            // Only ever produced locally
+            Self::Malformed | Self::Closed => unreachable!("{} is not sent on the wire", self),
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn decode(payload: &[u8]) -> Self {
+        match payload.split_first() {
+            Some((&Self::UNKNOWN_TYPE, &[hi, lo])) => Self::UnknownType(u16::from_be_bytes([hi, lo])),
+            Some((&Self::HANDLER, reason)) => {
+                String::from_utf8(reason.to_vec()).map_or(Self::Malformed, Self::Handler)
+            }
+            _ => Self::Malformed,
+        }
+    }
+}
+
+/// Serves the payloads of one message type
+#[async_trait]
+pub trait FrameHandler: Send + Sync {
+    async fn handle(&self, payload: Vec<u8>) -> Result<Vec<u8>, CoordinationError>;
+}
+
+/// Message type echoing its payload, for agents checking the connection
+pub const PING_MSG_TYPE: u16 = 0;
+
+pub struct Ping;
+
+#[async_trait]
+impl FrameHandler for Ping {
+    async fn handle(&self, payload: Vec<u8>) -> Result<Vec<u8>, CoordinationError> {
+        Ok(payload)
+    }
+}
+
+/// Routes inbound frames to handlers by `msg_type`
+///
+/// Each request runs on its own task and its response is written as soon
+/// as it is ready, so a slow request does not hold up the ones behind it
+/// on the same connection. At most `max_in_flight` requests run at once
+/// across every connection served; past that, connections are not read
+/// until one finishes.
+#[derive(Clone)]
+pub struct Dispatcher {
+    handlers: Arc<HashMap<u16, Arc<dyn FrameHandler>>>,
+    max_frame_size: usize,
+    in_flight: Arc<Semaphore>,
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self {
+            handlers: Arc::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            in_flight: Arc::new(Semaphore::new(DEFAULT_MAX_IN_FLIGHT)),
+        }
+    }
+}
+
+impl Dispatcher {
+    /// Route `msg_type` to `handler`, replacing any earlier registration
+    pub fn register(mut self, msg_type: u16, handler: impl FrameHandler + 'static) -> Self {
+        assert_ne!(msg_type, ERROR_MSG_TYPE, "message type {} is reserved for errors", ERROR_MSG_TYPE);
+        Arc::make_mut(&mut self.handlers).insert(msg_type, Arc::new(handler));
+        self
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.in_flight = Arc::new(Semaphore::new(max_in_flight));
+        self
+    }
+
+    /// Accept TLS connections on `listener` and serve each until its peer
+    /// closes it
+    pub fn spawn_listener(self, listener: TcpListener, acceptor: TlsAcceptor) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to accept wire connection");
+                        continue;
+                    }
+                };
+                let (dispatcher, acceptor) = (self.clone(), acceptor.clone());
+                tokio::spawn(async move {
+                    let served = match acceptor.accept(stream).await {
+                        Ok(stream) => dispatcher.serve(stream).await,
+                        Err(e) => Err(e.into()),
+                    };
+                    if let Err(e) = served {
+                        debug!(%peer, error = %e, "Wire connection ended");
+                    }
+                });
+            }
+        })
+    }
+
+    /// Serve requests from `stream` until the peer closes it
+    ///
+    /// A request for an unregistered type, or one whose handler fails,
+    /// gets an error frame; only a framing violation ends the connection.
+    pub async fn serve<S>(&self, stream: S) -> Result<(), CoordinationError>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut reader, writer) = tokio::io::split(stream);
+        let (outbound, writing) = spawn_writer(writer);
+
+        while let Some(request) = Frame::read(&mut reader, self.max_frame_size).await? {
+            // The semaphore is never closed
+            let Ok(permit) = self.in_flight.clone().acquire_owned().await else { break };
+            let Some(handler) = self.handlers.get(&request.msg_type).cloned() else {
+                debug!(msg_type = request.msg_type, "No handler for wire message type");
+                let error = Frame::error(request.request_id, &WireError::UnknownType(request.msg_type));
+                if outbound.send(error).await.is_err() {
+                    break;
+                }
+                continue;
+            };
+            let outbound = outbound.clone();
+            tokio::spawn(async move {
+                let response = match handler.handle(request.payload).await {
+                    Ok(payload) => Frame { payload, ..request },
+                    Err(e) => {
+                        warn!(msg_type = request.msg_type, error = %e, "Wire handler failed");
+                        Frame::error(request.request_id, &WireError::handler(&e))
+                    }
+                };
+                drop(permit);
+                let _ = outbound.send(response).await;
+            });
+        }
+
+        // Let in-flight requests finish before the writer closes
+        drop(outbound);
+        writing.await.map_err(|e| CoordinationError::Io(std::io::Error::other(e)))?
+    }
+}
+
+/// Caller side of a connection served by a `Dispatcher`
+///
+/// Any number of calls may be outstanding at once; responses are matched
+/// to them by `request_id` in whatever order they arrive.
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct WireClient {
+    next_id: AtomicU64,
+    /// `None` once the connection has ended
+    pending: Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Frame>>>>>,
+    outbound: mpsc::Sender<Frame>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl WireClient {
+    pub fn new<S>(stream: S, max_frame_size: usize) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut reader, writer) = tokio::io::split(stream);
+        let (outbound, _) = spawn_writer(writer);
+        let pending = Arc::new(Mutex::new(Some(HashMap::<u64, oneshot::Sender<Frame>>::new())));
+
+        let routing = pending.clone();
+        tokio::spawn(async move {
+            loop {
+                match Frame::read(&mut reader, max_frame_size).await {
+                    Ok(Some(response)) => {
+                        let waiter = routing.lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .as_mut()
+                            .and_then(|pending| pending.remove(&response.request_id));
+                        match waiter {
+                            Some(waiter) => drop(waiter.send(response)),
+                            None => debug!(request_id = response.request_id, "Response for no pending call"),
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!(error = %e, "Wire connection failed");
+                        break;
+                    }
+                }
+            }
+            // Dropping the waiters fails every outstanding call with `Closed`
+            routing.lock().unwrap_or_else(|e| e.into_inner()).take();
+        });
+
+        Self { next_id: AtomicU64::new(1), pending, outbound }
+    }
+
+    /// Send a request and wait for its response
+    pub async fn call(&self, msg_type: u16, payload: Vec<u8>) -> Result<Vec<u8>, WireError> {
+        let request_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (waiter, response) = oneshot::channel();
+        self.lock()
+            .as_mut()
+            .ok_or(WireError::Closed)?
+            .insert(request_id, waiter);
+
+        if self.outbound.send(Frame { msg_type, request_id, payload }).await.is_err() {
+            if let Some(pending) = self.lock().as_mut() {
+                pending.remove(&request_id);
+            }
+            return Err(WireError::Closed);
+        }
+        let response = response.await.map_err(|_| WireError::Closed)?;
+        match response.msg_type {
+            ERROR_MSG_TYPE => Err(WireError::decode(&response.payload)),
+            _ => Ok(response.payload),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<HashMap<u64, oneshot::Sender<Frame>>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Single task owning the write half, so frames are never interleaved
+fn spawn_writer<W>(mut writer: W) -> (mpsc::Sender<Frame>, tokio::task::JoinHandle<Result<(), CoordinationError>>)
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (outbound, mut frames) = mpsc::channel::<Frame>(OUTBOUND_CAPACITY);
+    let writing = tokio::spawn(async move {
+        while let Some(frame) = frames.recv().await {
+            writer.write_all(&frame.encode()).await?;
+        }
+        writer.shutdown().await?;
+        Ok(())
+    });
+    (outbound, writing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const ECHO: u16 = 1;
+    const FAIL: u16 = 2;
+
+    /// Echoes the payload after a delay of its first byte in milliseconds,
+    /// so later requests can overtake earlier ones
+    struct DelayedEcho;
+
+    #[async_trait]
+    impl FrameHandler for DelayedEcho {
+        async fn handle(&self, payload: Vec<u8>) -> Result<Vec<u8>, CoordinationError> {
+            tokio::time::sleep(Duration::from_millis(payload[0] as u64)).await;
+            Ok(payload)
+        }
+    }
+
+    /// Records how many calls it is serving at once
+    #[derive(Default)]
+    struct Concurrency {
+        current: AtomicU64,
+        peak: AtomicU64,
+    }
+
+    #[async_trait]
+    impl FrameHandler for Arc<Concurrency> {
+        async fn handle(&self, payload: Vec<u8>) -> Result<Vec<u8>, CoordinationError> {
+            let now = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.current.fetch_sub(1, Ordering::SeqCst);
+            Ok(payload)
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl FrameHandler for Failing {
+        async fn handle(&self, _: Vec<u8>) -> Result<Vec<u8>, CoordinationError> {
+            Err(CoordinationError::ResourceExhausted("worker slots".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_round_trip_and_length_limit() {
+        let frame = Frame { msg_type: 7, request_id: u64::MAX - 1, payload: b"hello".to_vec() };
+        let encoded = frame.encode();
+        assert_eq!(Frame::read(&mut &encoded[..], 64).await.unwrap(), Some(frame));
+        assert_eq!(Frame::read(&mut &[][..], 64).await.unwrap(), None);
+        assert!(matches!(
+            Frame::read(&mut &encoded[..], HEADER_LEN + 4).await,
+            Err(CoordinationError::ProtocolViolation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_multiplexed_over_one_connection() {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let dispatcher = Dispatcher::default().register(ECHO, DelayedEcho).register(FAIL, Failing);
+        let server = tokio::spawn(async move { dispatcher.serve(server_io).await });
+        let client = Arc::new(WireClient::new(client_io, DEFAULT_MAX_FRAME_SIZE));
+
+        // Issued slowest first, so responses come back in reverse order
+        let calls: Vec<_> = (0..16u8).map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let payload = vec![(16 - i) * 3, i];
+                (payload.clone(), client.call(ECHO, payload).await)
+            })
+        }).collect();
+        for call in calls {
+            let (sent, received) = call.await.unwrap();
+            assert_eq!(received.unwrap(), sent);
+        }
+
+        // Failures arrive as typed errors and leave the connection usable
+        assert_eq!(client.call(99, vec![]).await, Err(WireError::UnknownType(99)));
+        // The handler's own error is not disclosed to the peer
+        assert_eq!(client.call(FAIL, vec![]).await, Err(WireError::Handler(WireError::UNAVAILABLE.into())));
+        assert_eq!(client.call(ECHO, vec![0, 42]).await.unwrap(), vec![0, 42]);
+
+        drop(client);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requests_in_flight_are_capped() {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let concurrency = Arc::new(Concurrency::default());
+        let dispatcher = Dispatcher::default().register(ECHO, concurrency.clone()).with_max_in_flight(2);
+        let server = tokio::spawn(async move { dispatcher.serve(server_io).await });
+        let client = Arc::new(WireClient::new(client_io, DEFAULT_MAX_FRAME_SIZE));
+
+        let calls: Vec<_> = (0..8u8).map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move { client.call(ECHO, vec![i]).await })
+        }).collect();
+        for (i, call) in calls.into_iter().enumerate() {
+            assert_eq!(call.await.unwrap().unwrap(), vec![i as u8]);
+        }
+        assert_eq!(concurrency.peak.load(Ordering::SeqCst), 2);
+
+        drop(client);
+        server.await.unwrap().unwrap();
+    }
+}
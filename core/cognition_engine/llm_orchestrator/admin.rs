@@ -0,0 +1,150 @@
+// admin.rs - Router Admin gRPC Service
+use std::sync::Arc;
+pub use nuzon_core::admin::AdminAuth;
+use tonic::{Request, Response, Status};
+
+use crate::circuit_breaker::{CircuitBreakers, CircuitState};
+
+pub mod pb {
+    tonic::include_proto!("nuzon.router.admin.v1");
+}
+
+use pb::{
+    router_admin_server::{RouterAdmin, RouterAdminServer},
+    BreakerState, BreakerStatesRequest, BreakerStatesResponse, BreakerStatus,
+    ResetBreakerRequest, ResetBreakerResponse,
+};
+
+/// Exposes circuit-breaker state to operators
+pub struct RouterAdminService {
+    breakers: Arc<CircuitBreakers>,
+    auth: AdminAuth,
+}
+
+impl RouterAdminService {
+    pub fn new(breakers: Arc<CircuitBreakers>, auth: AdminAuth) -> Self {
+        Self { breakers, auth }
+    }
+
+    pub fn into_server(self) -> RouterAdminServer<Self> {
+        RouterAdminServer::new(self)
+    }
+}
+
+impl From<CircuitState> for BreakerState {
+    fn from(state: CircuitState) -> Self {
+        match state {
+            CircuitState::Closed => BreakerState::Closed,
+            CircuitState::Open => BreakerState::Open,
+            CircuitState::HalfOpen => BreakerState::HalfOpen,
+        }
+    }
+}
+
+/// Refuse `request` unless it carries a token `auth` accepts
+fn authorize<T>(auth: &AdminAuth, request: &Request<T>) -> Result<(), Status> {
+    let authorization = request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+    auth.check(authorization).map_err(|e| Status::unauthenticated(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl RouterAdmin for RouterAdminService {
+    async fn breaker_states(
+        &self,
+        request: Request<BreakerStatesRequest>,
+    ) -> Result<Response<BreakerStatesResponse>, Status> {
+        authorize(&self.auth, &request)?;
+        let breakers = self.breakers.snapshot()
+            .into_iter()
+            .map(|b| BreakerStatus {
+                endpoint: b.endpoint,
+                state: BreakerState::from(b.state) as i32,
+                consecutive_failures: b.consecutive_failures,
+                seconds_in_state: b.time_in_state.as_secs_f64(),
+            })
+            .collect();
+        Ok(Response::new(BreakerStatesResponse { breakers }))
+    }
+
+    async fn reset_breaker(
+        &self,
+        request: Request<ResetBreakerRequest>,
+    ) -> Result<Response<ResetBreakerResponse>, Status> {
+        authorize(&self.auth, &request)?;
+        let endpoint = request.into_inner().endpoint;
+        let reset = self.breakers.reset(&endpoint);
+        let status = if reset {
+            format!("breaker for {} closed", endpoint)
+        } else {
+            format!("no breaker recorded for {}; nothing to reset", endpoint)
+        };
+        Ok(Response::new(ResetBreakerResponse { reset, status }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "operator-token";
+
+    fn authorized<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", format!("Bearer {}", TOKEN).parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_trip_read_and_reset_breaker() {
+        let breakers = Arc::new(CircuitBreakers::default());
+        let admin = RouterAdminService::new(breakers.clone(), AdminAuth::default().allow_token(TOKEN));
+        for _ in 0..5 {
+            breakers.record_failure("llm-a:443");
+        }
+        assert!(!breakers.allows("llm-a:443"));
+
+        let states = admin.breaker_states(authorized(BreakerStatesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .breakers;
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].endpoint, "llm-a:443");
+        assert_eq!(states[0].state, BreakerState::Open as i32);
+        assert_eq!(states[0].consecutive_failures, 5);
+
+        let reset = admin.reset_breaker(authorized(ResetBreakerRequest { endpoint: "llm-a:443".into() }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(reset.reset);
+        assert!(breakers.allows("llm-a:443"));
+
+        let unknown = admin.reset_breaker(authorized(ResetBreakerRequest { endpoint: "llm-z:443".into() }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!unknown.reset);
+        assert!(unknown.status.contains("llm-z:443"));
+    }
+
+    #[tokio::test]
+    async fn test_calls_without_an_accepted_token_are_refused() {
+        let breakers = Arc::new(CircuitBreakers::default());
+        for _ in 0..5 {
+            breakers.record_failure("llm-a:443");
+        }
+        let admin = RouterAdminService::new(breakers.clone(), AdminAuth::default().allow_token(TOKEN));
+
+        let anonymous = admin.reset_breaker(Request::new(ResetBreakerRequest { endpoint: "llm-a:443".into() })).await;
+        assert_eq!(anonymous.unwrap_err().code(), tonic::Code::Unauthenticated);
+        let mut forged = Request::new(BreakerStatesRequest {});
+        forged.metadata_mut().insert("authorization", "Bearer guessed".parse().unwrap());
+        assert_eq!(admin.breaker_states(forged).await.unwrap_err().code(), tonic::Code::Unauthenticated);
+        assert!(!breakers.allows("llm-a:443"));
+
+        // A service allowing no tokens refuses even a well-formed call
+        let closed = RouterAdminService::new(breakers, AdminAuth::default());
+        assert!(closed.breaker_states(authorized(BreakerStatesRequest {})).await.is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// circuit_breaker.rs - Per-Endpoint Circuit Breakers
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+
+use crate::qos::HIGH_PRIORITY;
+
+/// Consecutive failures that open a breaker
+const FAILURE_THRESHOLD: u32 = 5;
+/// Time an open breaker waits before admitting a probe
+const OPEN_COOLDOWN: Duration = Duration::from_secs(30);
+/// Time a half-open breaker waits for lower-priority traffic to probe it
+/// before high-priority requests may
+const PROBE_RESERVE: Duration = Duration::from_secs(5);
+/// Time after which a probe that never reported back is presumed lost and
+/// another may be sent
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Breaker position for one backend endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// Cooldown elapsed; one request at a time is let through as a probe
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    since: Instant,
+    /// When the half-open breaker's outstanding probe was let through
+    probe_sent: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn closed() -> Self {
+        Self { state: CircuitState::Closed, consecutive_failures: 0, since: Instant::now(), probe_sent: None }
+    }
+
+    fn transition(&mut self, state: CircuitState) {
+        if self.state != state {
+            self.state = state;
+            self.since = Instant::now();
+            self.probe_sent = None;
+        }
+    }
+
+    /// Send the half-open breaker's probe, unless one is still outstanding
+    fn try_probe(&mut self) -> bool {
+        if self.probe_sent.is_some_and(|sent| sent.elapsed() < PROBE_TIMEOUT) {
+            return false;
+        }
+        self.probe_sent = Some(Instant::now());
+        true
+    }
+
+    /// Promote an open breaker to half-open once its cooldown has elapsed
+    fn refresh(&mut self) {
+        if self.state == CircuitState::Open && self.since.elapsed() >= OPEN_COOLDOWN {
+            self.transition(CircuitState::HalfOpen);
+        }
+    }
+}
+
+/// Point-in-time view of one breaker
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakerSnapshot {
+    pub endpoint: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub time_in_state: Duration,
+}
+
+/// Breakers for every endpoint the router has talked to
+#[derive(Debug, Default)]
+pub struct CircuitBreakers {
+    breakers: DashMap<String, CircuitBreaker>,
+}
+
+impl CircuitBreakers {
+    /// Whether a request may currently be sent to the endpoint
+    ///
+    /// A half-open breaker lets one through as its probe and refuses the
+    /// rest until that probe is recorded as a success or failure.
+    pub fn allows(&self, endpoint: &str) -> bool {
+        self.breakers.get_mut(endpoint).map_or(true, |mut breaker| {
+            breaker.refresh();
+            match breaker.state {
+                CircuitState::Closed => true,
+                CircuitState::Open => false,
+                CircuitState::HalfOpen => breaker.try_probe(),
+            }
+        })
+    }
+
+    /// Like `allows`, but keeps latency-critical traffic off half-open probes
+    ///
+    /// A probe is likely to fail, so it is drawn from connections below
+    /// `HIGH_PRIORITY`; high-priority ones only probe once the breaker has
+    /// sat half-open for `PROBE_RESERVE` without other traffic.
+    pub fn allows_priority(&self, endpoint: &str, priority: u8) -> bool {
+        self.breakers.get_mut(endpoint).map_or(true, |mut breaker| {
+            breaker.refresh();
+            match breaker.state {
+                CircuitState::Closed => true,
+                CircuitState::Open => false,
+                CircuitState::HalfOpen => {
+                    (priority < HIGH_PRIORITY || breaker.since.elapsed() >= PROBE_RESERVE) && breaker.try_probe()
+                }
+            }
+        })
+    }
+
+    pub fn record_success(&self, endpoint: &str) {
+        if let Some(mut breaker) = self.breakers.get_mut(endpoint) {
+            breaker.consecutive_failures = 0;
+            breaker.transition(CircuitState::Closed);
+        }
+    }
+
+    pub fn record_failure(&self, endpoint: &str) {
+        let mut breaker = self.breakers.entry(endpoint.to_string())
+            .or_insert_with(CircuitBreaker::closed);
+        breaker.refresh();
+        breaker.consecutive_failures += 1;
+        // A failed half-open probe re-opens immediately
+        if breaker.state == CircuitState::HalfOpen || breaker.consecutive_failures >= FAILURE_THRESHOLD {
+            breaker.transition(CircuitState::Open);
+        }
+    }
+
+    /// Manually close a breaker; returns false if the endpoint is unknown
+    pub fn reset(&self, endpoint: &str) -> bool {
+        match self.breakers.get_mut(endpoint) {
+            Some(mut breaker) => {
+                breaker.consecutive_failures = 0;
+                breaker.transition(CircuitState::Closed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<BreakerSnapshot> {
+        let mut snapshot: Vec<_> = self.breakers.iter_mut()
+            .map(|mut entry| {
+                entry.refresh();
+                BreakerSnapshot {
+                    endpoint: entry.key().clone(),
+                    state: entry.state,
+                    consecutive_failures: entry.consecutive_failures,
+                    time_in_state: entry.since.elapsed(),
+                }
+            })
+            .collect();
+        snapshot.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn half_open(breakers: &CircuitBreakers, endpoint: &str) {
+        for _ in 0..FAILURE_THRESHOLD {
+            breakers.record_failure(endpoint);
+        }
+        breakers.breakers.get_mut(endpoint).unwrap().transition(CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn test_half_open_breaker_sends_one_probe_at_a_time() {
+        let breakers = CircuitBreakers::default();
+        half_open(&breakers, "a");
+        assert!(breakers.allows("a"));
+        assert!(!breakers.allows("a"));
+        assert!(!breakers.allows_priority("a", 0));
+
+        // A failed probe re-opens the breaker
+        breakers.record_failure("a");
+        assert!(!breakers.allows("a"));
+
+        // A successful one closes it to all traffic
+        half_open(&breakers, "a");
+        assert!(breakers.allows_priority("a", 0));
+        breakers.record_success("a");
+        assert!(breakers.allows("a") && breakers.allows("a"));
+    }
+
+    #[test]
+    fn test_lost_probe_is_replaced_after_timeout() {
+        let breakers = CircuitBreakers::default();
+        half_open(&breakers, "a");
+        assert!(breakers.allows("a"));
+        assert!(!breakers.allows("a"));
+
+        breakers.breakers.get_mut("a").unwrap().probe_sent = Instant::now().checked_sub(PROBE_TIMEOUT);
+        assert!(breakers.allows("a"));
+        assert!(!breakers.allows("a"));
+    }
+}
@@ -0,0 +1,286 @@
+// qos.rs - Priority-Ordered Admission with Aging
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// Queueing time worth one priority level
+const DEFAULT_AGING_STEP: Duration = Duration::from_millis(50);
+/// Longest a connection queues for admission before it is refused
+const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(5);
+
+/// Lowest priority treated as latency-critical
+pub const HIGH_PRIORITY: u8 = 192;
+/// Lowest priority treated as interactive rather than bulk
+pub const NORMAL_PRIORITY: u8 = 64;
+
+/// Coarse priority class used as a metrics label
+pub fn priority_class(priority: u8) -> &'static str {
+    match priority {
+        p if p >= HIGH_PRIORITY => "high",
+        p if p >= NORMAL_PRIORITY => "normal",
+        _ => "low",
+    }
+}
+
+struct Waiter {
+    id: u64,
+    priority: u8,
+    enqueued: Instant,
+    grant: oneshot::Sender<()>,
+}
+
+struct BucketState {
+    tokens: f64,
+    refilled: Instant,
+    next_id: u64,
+    waiters: Vec<Waiter>,
+}
+
+impl BucketState {
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.refilled = now;
+    }
+
+    /// Hand every whole token to the most deserving waiters
+    fn dispatch(&mut self, now: Instant, aging_step: Duration) {
+        let score = |w: &Waiter| {
+            w.priority as u128 + now.saturating_duration_since(w.enqueued).as_nanos() / aging_step.as_nanos().max(1)
+        };
+        while self.tokens >= 1.0 {
+            let best = self.waiters.iter()
+                .enumerate()
+                // Equal scores go to whoever queued first
+                .max_by(|(_, a), (_, b)| score(a).cmp(&score(b)).then(b.id.cmp(&a.id)))
+                .map(|(index, _)| index);
+            let Some(index) = best else { return };
+            // A waiter whose receiver is already gone cannot take the token
+            if self.waiters.swap_remove(index).grant.send(()).is_ok() {
+                self.tokens -= 1.0;
+            }
+        }
+    }
+}
+
+/// Token bucket that admits contended connections highest priority first
+///
+/// Tokens accrue at `rate` per second, up to a burst of one second's worth.
+/// Connections take one each; while any are queued, every new token goes to
+/// the queued connection with the highest effective priority, so nothing
+/// waits behind a lower-priority connection. Every `aging_step` spent queued
+/// raises a connection's effective priority by one level, so bulk traffic
+/// still gets through under a sustained stream of higher-priority arrivals.
+/// A rate of zero admits everything at once.
+pub struct PriorityRateLimiter {
+    state: Mutex<BucketState>,
+    rate: f64,
+    burst: f64,
+    aging_step: Duration,
+    max_wait: Duration,
+}
+
+impl PriorityRateLimiter {
+    pub fn new(rate: f64) -> Self {
+        let burst = rate.ceil().max(1.0);
+        Self {
+            state: Mutex::new(BucketState { tokens: burst, refilled: Instant::now(), next_id: 0, waiters: Vec::new() }),
+            rate,
+            burst,
+            aging_step: DEFAULT_AGING_STEP,
+            max_wait: DEFAULT_MAX_WAIT,
+        }
+    }
+
+    /// Override how quickly queued connections gain priority
+    pub fn with_aging_step(mut self, aging_step: Duration) -> Self {
+        self.aging_step = aging_step;
+        self
+    }
+
+    /// Override how long a connection may queue before it is refused
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Take a token, queueing behind higher effective priorities
+    ///
+    /// Fails once the connection has queued for `max_wait`.
+    pub async fn acquire(&self, priority: u8) -> anyhow::Result<()> {
+        if self.rate == 0.0 {
+            return Ok(());
+        }
+        let queued_at = Instant::now();
+        let (id, mut granted) = {
+            let mut state = self.lock();
+            state.refill(queued_at, self.rate, self.burst);
+            if state.waiters.is_empty() && state.tokens >= 1.0 {
+                state.tokens -= 1.0;
+                return Ok(());
+            }
+            let (grant, granted) = oneshot::channel();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push(Waiter { id, priority, enqueued: queued_at, grant });
+            (id, granted)
+        };
+
+        // Whichever waiter wakes after a token accrues hands it out, so no
+        // task is needed to drive the queue
+        let mut pending = PendingWaiter { limiter: self, id, granted: false };
+        let deadline = queued_at + self.max_wait;
+        loop {
+            let next_token = {
+                let mut state = self.lock();
+                let now = Instant::now();
+                state.refill(now, self.rate, self.burst);
+                state.dispatch(now, self.aging_step);
+                Duration::from_secs_f64((1.0 - state.tokens).max(0.0) / self.rate)
+            };
+            let wake = (Instant::now() + next_token).min(deadline);
+            tokio::select! {
+                biased;
+                _ = &mut granted => {
+                    pending.granted = true;
+                    return Ok(());
+                }
+                _ = tokio::time::sleep_until(wake.into()) => {}
+            }
+            if Instant::now() >= deadline {
+                if granted.try_recv().is_ok() {
+                    pending.granted = true;
+                    return Ok(());
+                }
+                anyhow::bail!("queued for admission longer than {:?}", self.max_wait);
+            }
+        }
+    }
+
+    /// Number of connections currently queued
+    pub fn queued(&self) -> usize {
+        self.lock().waiters.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BucketState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Withdraws a waiter that gave up or was cancelled, returning any token it
+/// was granted in the meantime
+struct PendingWaiter<'a> {
+    limiter: &'a PriorityRateLimiter,
+    id: u64,
+    granted: bool,
+}
+
+impl Drop for PendingWaiter<'_> {
+    fn drop(&mut self) {
+        if self.granted {
+            return;
+        }
+        let mut state = self.limiter.lock();
+        match state.waiters.iter().position(|w| w.id == self.id) {
+            Some(index) => drop(state.waiters.swap_remove(index)),
+            None => {
+                state.tokens += 1.0;
+                state.dispatch(Instant::now(), self.limiter.aging_step);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Spend the initial burst so the next acquire has to queue
+    async fn drain(limiter: &PriorityRateLimiter) {
+        for _ in 0..limiter.burst as usize {
+            limiter.acquire(0).await.unwrap();
+        }
+    }
+
+    async fn wait_for_queue(limiter: &PriorityRateLimiter, len: usize) {
+        while limiter.queued() < len {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_admitted_before_queued_low_priority() {
+        // One token every 100ms once the burst is spent
+        let limiter = Arc::new(PriorityRateLimiter::new(10.0).with_aging_step(Duration::from_secs(3600)));
+        drain(&limiter).await;
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let spawn = |priority: u8| {
+            let (limiter, order_tx) = (limiter.clone(), order_tx.clone());
+            tokio::spawn(async move {
+                limiter.acquire(priority).await.unwrap();
+                order_tx.send(priority).unwrap();
+            })
+        };
+        let low = spawn(0);
+        wait_for_queue(&limiter, 1).await;
+        let high = spawn(HIGH_PRIORITY);
+        wait_for_queue(&limiter, 2).await;
+
+        low.await.unwrap();
+        high.await.unwrap();
+        assert_eq!(order_rx.recv().await, Some(HIGH_PRIORITY));
+        assert_eq!(order_rx.recv().await, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_aged_waiter_overtakes_fresh_high_priority() {
+        // One token every 500ms
+        let limiter = Arc::new(PriorityRateLimiter::new(2.0).with_aging_step(Duration::from_millis(1)));
+        drain(&limiter).await;
+
+        let low = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire(0).await.unwrap(); Instant::now() })
+        };
+        wait_for_queue(&limiter, 1).await;
+        // 300ms of queueing outweighs the 255 priority levels of a newcomer
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let high = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire(u8::MAX).await.unwrap(); Instant::now() })
+        };
+        wait_for_queue(&limiter, 2).await;
+
+        assert!(low.await.unwrap() <= high.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_does_not_leak_token() {
+        let limiter = PriorityRateLimiter::new(10.0);
+        drain(&limiter).await;
+        assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire(0)).await.is_err());
+        assert_eq!(limiter.queued(), 0);
+
+        tokio::time::timeout(Duration::from_millis(200), limiter.acquire(0)).await
+            .expect("token leaked by cancelled waiter")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_waiting_past_max_wait_is_refused() {
+        let limiter = PriorityRateLimiter::new(1.0).with_max_wait(Duration::from_millis(20));
+        drain(&limiter).await;
+        assert!(limiter.acquire(u8::MAX).await.is_err());
+        assert_eq!(limiter.queued(), 0);
+
+        // An unlimited limiter never queues
+        let unlimited = PriorityRateLimiter::new(0.0);
+        for _ in 0..100 {
+            unlimited.acquire(0).await.unwrap();
+        }
+    }
+}
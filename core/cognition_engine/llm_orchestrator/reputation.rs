@@ -0,0 +1,115 @@
+// reputation.rs - Trust-Weighted Endpoint Selection
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+use async_trait::async_trait;
+use eigen_trust::ReputationEngine;
+
+/// Selection weight given to a trust score of 1.0
+const WEIGHT_SCALE: f64 = 1_000_000.0;
+
+/// Global trust lookup backing `RoutingStrategy::ReputationWeighted`
+#[async_trait]
+pub trait TrustSource: Send + Sync {
+    /// Node's global trust and when it was last computed
+    async fn global_trust(&self, node_id: &str) -> Option<(f64, SystemTime)>;
+}
+
+#[async_trait]
+impl TrustSource for ReputationEngine {
+    async fn global_trust(&self, node_id: &str) -> Option<(f64, SystemTime)> {
+        ReputationEngine::global_trust(self, node_id).await
+    }
+}
+
+/// How trust scores become selection weights
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TrustPolicy {
+    pub min_trust: f64,
+    /// Assumed for nodes with no score or one older than `max_age`
+    pub default_trust: f64,
+    pub max_age: Duration,
+}
+
+/// Selection weight per endpoint, proportional to its node's trust
+///
+/// `nodes` maps each candidate endpoint to the node id it reports trust
+/// under. Endpoints whose effective trust is below `min_trust` get no
+/// weight and are left out.
+pub(crate) async fn trust_weights(
+    source: Option<&dyn TrustSource>,
+    nodes: &HashMap<String, String>,
+    policy: TrustPolicy,
+    now: SystemTime,
+) -> HashMap<String, u32> {
+    let mut weights = HashMap::with_capacity(nodes.len());
+    for (endpoint, node_id) in nodes {
+        let observed = match source {
+            Some(source) => source.global_trust(node_id).await,
+            None => None,
+        };
+        // A score stamped in the future (clock skew) still counts as fresh
+        let trust = observed
+            .filter(|(_, updated)| now.duration_since(*updated).map_or(true, |age| age <= policy.max_age))
+            .map_or(policy.default_trust, |(trust, _)| trust);
+
+        if trust.is_finite() && trust >= policy.min_trust {
+            let weight = (trust * WEIGHT_SCALE).round().clamp(1.0, u32::MAX as f64) as u32;
+            weights.insert(endpoint.clone(), weight);
+        }
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SmoothWeightedRoundRobin;
+
+    struct FixedTrust(HashMap<&'static str, (f64, SystemTime)>);
+
+    #[async_trait]
+    impl TrustSource for FixedTrust {
+        async fn global_trust(&self, node_id: &str) -> Option<(f64, SystemTime)> {
+            self.0.get(node_id).copied()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traffic_prefers_trusted_and_skips_untrusted() {
+        let now = SystemTime::now();
+        let stale = now - Duration::from_secs(3600);
+        let source = FixedTrust([
+            ("node-low", (0.01, now)),
+            ("node-mid", (0.2, now)),
+            ("node-high", (0.6, now)),
+            ("node-stale", (0.9, stale)),
+        ].into_iter().collect());
+        let nodes: HashMap<String, String> = [
+            ("low:443", "node-low"),
+            ("mid:443", "node-mid"),
+            ("high:443", "node-high"),
+            ("stale:443", "node-stale"),
+            ("unscored:443", "node-new"),
+        ].into_iter().map(|(e, n)| (e.to_string(), n.to_string())).collect();
+        let policy = TrustPolicy {
+            min_trust: 0.05,
+            default_trust: 0.1,
+            max_age: Duration::from_secs(60),
+        };
+
+        let weights = trust_weights(Some(&source), &nodes, policy, now).await;
+        assert!(!weights.contains_key("low:443"));
+        // Stale and missing scores fall back to the default instead of exclusion
+        assert_eq!(weights.get("stale:443"), weights.get("unscored:443"));
+
+        let mut selector = SmoothWeightedRoundRobin::default();
+        let picks: Vec<_> = (0..1000).map(|_| selector.next(&weights).unwrap()).collect();
+        let count = |endpoint: &str| picks.iter().filter(|p| *p == endpoint).count();
+        assert_eq!(count("low:443"), 0);
+        assert!(count("high:443") > count("mid:443"));
+        assert!(count("mid:443") > count("unscored:443"));
+        assert!(count("unscored:443") > 0);
+    }
+}
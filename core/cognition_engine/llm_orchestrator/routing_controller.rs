@@ -4,29 +4,81 @@
 #![feature(type_alias_impl_trait)]
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     net::SocketAddr,
-    sync::atomic::{AtomicU64, Ordering},
-    time::{Duration, Instant},
+    sync::Arc,
+    task::{Context as TaskContext, Poll, Waker},
+    time::{Duration, Instant, SystemTime},
 };
-use anyhow::Context;
+use async_trait::async_trait;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
     net::TcpStream,
-    sync::{mpsc, Semaphore},
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
 };
-use tracing::{debug, error, info_span, Instrument};
-use prometheus::{HistogramVec, IntCounterVec, register};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use prometheus::{Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, register};
 use rustls::{ClientConfig, ServerConfig};
+use tokio_rustls::{server::TlsStream as ServerTlsStream, TlsAcceptor};
+use nuzon_core::telemetry::RateLimitedLog;
 use crate::crypto::quantum_safe::kyber_tls;
 
+mod admin;
+mod circuit_breaker;
+mod qos;
+mod reputation;
+mod session_tickets;
+mod tenants;
+
+pub use admin::{AdminAuth, RouterAdminService};
+pub use circuit_breaker::{BreakerSnapshot, CircuitBreakers, CircuitState};
+pub use qos::{priority_class, PriorityRateLimiter, HIGH_PRIORITY, NORMAL_PRIORITY};
+pub use reputation::TrustSource;
+pub use session_tickets::SessionTicketer;
+pub use tenants::{Tenant, TenantDirectory};
+
+type TlsStream = ServerTlsStream<TcpStream>;
+
+/// ALPN identifiers offered by the router, in server preference order
+const ALPN_H2: &[u8] = b"h2";
+const ALPN_HTTP11: &[u8] = b"http/1.1";
+
+/// Idle age after which an unprobeable pooled connection is discarded
+const DEFAULT_MAX_IDLE: Duration = Duration::from_secs(90);
+
+/// Bytes read from one side of a tunnel before writing them to the other
+const RELAY_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Latency samples kept per endpoint when hybrid scoring's fallback sets
+/// no window of its own
+const DEFAULT_LATENCY_WINDOW: usize = 32;
+
+fn default_cold_start_samples() -> usize {
+    32
+}
+
+fn default_trust_max_age_secs() -> u64 {
+    300
+}
+
+fn default_ticket_rotation_secs() -> u64 {
+    3600
+}
+
 /// Core routing engine metrics
 #[derive(Clone)]
 pub struct RoutingMetrics {
     pub routing_latency: HistogramVec,
     pub routing_errors: IntCounterVec,
     pub throughput: IntCounterVec,
+    /// Tunnels by how they ended
+    pub tunnel_outcomes: IntCounterVec,
+    /// Time spent queued for a rate-limiter permit, by priority class
+    pub admission_wait: HistogramVec,
+    pub pool: PoolMetrics,
 }
 
 impl RoutingMetrics {
@@ -47,16 +99,117 @@ impl RoutingMetrics {
                 "Network throughput metrics",
                 &["direction"]
             )?,
+            tunnel_outcomes: register_int_counter_vec!(
+                "nuzon_routing_tunnel_outcomes_total",
+                "Forwarded tunnels by which side closed first",
+                &["outcome"]
+            )?,
+            admission_wait: register_histogram_vec!(
+                "nuzon_routing_admission_wait_seconds",
+                "Time connections wait for rate-limiter admission",
+                &["priority"]
+            )?,
+            pool: {
+                let pool = PoolMetrics::detached();
+                pool.register()?;
+                pool
+            },
         })
     }
 }
 
+/// Backend connection pool utilisation
+#[derive(Clone)]
+pub struct PoolMetrics {
+    /// Connections handed out and not yet returned
+    pub in_use: IntGauge,
+    pub idle: IntGauge,
+    /// Time blocked on route and global connection permits
+    pub acquire_wait: Histogram,
+    /// Pooled connections discarded as dead or stale
+    pub evictions: IntCounter,
+}
+
+impl PoolMetrics {
+    /// Metrics not yet exposed through any registry
+    fn detached() -> Self {
+        Self {
+            in_use: IntGauge::new("nuzon_routing_pool_connections_in_use", "Backend connections currently in use")
+                .expect("valid metric options"),
+            idle: IntGauge::new("nuzon_routing_pool_connections_idle", "Idle backend connections held for reuse")
+                .expect("valid metric options"),
+            acquire_wait: Histogram::with_opts(HistogramOpts::new(
+                "nuzon_routing_pool_acquire_wait_seconds",
+                "Time spent waiting for a backend connection permit",
+            )).expect("valid metric options"),
+            evictions: IntCounter::new("nuzon_routing_pool_evictions_total", "Pooled connections discarded as unhealthy")
+                .expect("valid metric options"),
+        }
+    }
+
+    /// Expose the metrics through the default registry
+    fn register(&self) -> prometheus::Result<()> {
+        register(Box::new(self.in_use.clone()))?;
+        register(Box::new(self.idle.clone()))?;
+        register(Box::new(self.acquire_wait.clone()))?;
+        register(Box::new(self.evictions.clone()))
+    }
+}
+
+/// Why a connection could not be routed
+///
+/// Each variant is counted under its own `routing_errors` label when
+/// `handle_connection` returns it.
+#[derive(Debug, thiserror::Error)]
+pub enum RoutingError {
+    #[error("rate limit rejected the connection")]
+    RateLimited(#[source] anyhow::Error),
+    #[error("circuit open for {endpoint}")]
+    CircuitOpen { endpoint: String },
+    #[error("no route: {0}")]
+    NoRoute(String),
+    #[error("TLS handshake failed")]
+    TlsHandshake(#[source] std::io::Error),
+    #[error("unrecognized application protocol")]
+    ProtocolUnrecognized(#[source] anyhow::Error),
+    #[error("backend {endpoint} unavailable")]
+    BackendUnavailable {
+        endpoint: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("invalid router configuration: {0}")]
+    InvalidConfig(String),
+}
+
+impl RoutingError {
+    /// `routing_errors` label the error is counted under
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::RateLimited(_) => "rate_limited",
+            Self::CircuitOpen { .. } => "circuit_open",
+            Self::NoRoute(_) => "no_route",
+            Self::TlsHandshake(_) => "tls_handshake",
+            Self::ProtocolUnrecognized(_) => "protocol_unrecognized",
+            Self::BackendUnavailable { .. } => "backend_unavailable",
+            Self::InvalidConfig(_) => "invalid_config",
+        }
+    }
+}
+
 /// Adaptive routing strategy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RoutingStrategy {
     LatencyOptimized {
         historical_samples: usize,
         outlier_threshold: f32,
+        /// Candidate backends
+        #[serde(default)]
+        endpoints: Vec<String>,
+        /// Samples required across all endpoints before latency is trusted;
+        /// until then selection round-robins to gather data
+        #[serde(default = "default_cold_start_samples")]
+        cold_start_samples: usize,
     },
     CostAware {
         cost_weights: HashMap<String, f32>,
@@ -67,6 +220,261 @@ pub enum RoutingStrategy {
         cost_weight: f32,
         fallback: Box<RoutingStrategy>,
     },
+    /// Static administrative weights; zero drains an endpoint
+    WeightedRoundRobin {
+        weights: HashMap<String, u32>,
+    },
+    /// Weight endpoints by their node's global reputation
+    ReputationWeighted {
+        /// Endpoints whose node scores below this are never selected
+        min_trust: f64,
+        /// Candidate endpoints mapped to the node id they report trust under
+        #[serde(default)]
+        nodes: HashMap<String, String>,
+        /// Trust assumed while a node has no score or a stale one; defaults
+        /// to `min_trust`, keeping such endpoints in rotation at low weight
+        #[serde(default)]
+        default_trust: Option<f64>,
+        /// Age after which a trust score is considered stale
+        #[serde(default = "default_trust_max_age_secs")]
+        max_age_secs: u64,
+    },
+}
+
+/// Router construction parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouterConfig {
+    pub strategy: RoutingStrategy,
+    /// Global ceiling on backend connections in use, handshakes included
+    pub pool_size: usize,
+    /// Admission rate; a `requests_per_second` of zero admits without limit
+    pub rate_limits: RateLimitConfig,
+    /// Per-route shares of `pool_size`
+    #[serde(default)]
+    pub route_limits: RouteConnectionLimits,
+    /// How often TLS session ticket keys are replaced
+    #[serde(default = "default_ticket_rotation_secs")]
+    pub ticket_rotation_secs: u64,
+}
+
+/// Quotas on each route's connections in use, beneath the pool's global cap
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteConnectionLimits {
+    /// Limit for routes without an explicit entry; `None` leaves them
+    /// bounded only by the global cap
+    #[serde(default)]
+    pub default: Option<usize>,
+    /// Limits keyed by endpoint
+    #[serde(default)]
+    pub per_route: HashMap<String, usize>,
+}
+
+impl RouterConfig {
+    /// Reject settings that would leave the router unable to route
+    pub fn validate(&self) -> Result<(), RoutingError> {
+        if self.pool_size == 0 {
+            return Err(invalid_config("pool_size must be greater than zero"));
+        }
+        let rate = self.rate_limits.requests_per_second;
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(invalid_config(format!("rate limit of {} requests per second is not a non-negative number", rate)));
+        }
+        if let Some(endpoint) = self.route_limits.per_route.iter().find(|(_, limit)| **limit == 0).map(|(e, _)| e) {
+            return Err(invalid_config(format!("connection limit for {} must be greater than zero", endpoint)));
+        }
+        if self.route_limits.default == Some(0) {
+            return Err(invalid_config("default route connection limit must be greater than zero"));
+        }
+        if self.ticket_rotation_secs == 0 {
+            return Err(invalid_config("ticket rotation interval must be greater than zero"));
+        }
+        self.strategy.validate()
+    }
+}
+
+impl RoutingStrategy {
+    fn validate(&self) -> Result<(), RoutingError> {
+        let non_negative = |value: f64| value.is_finite() && value >= 0.0;
+        let unit = |value: f64| non_negative(value) && value <= 1.0;
+        match self {
+            Self::LatencyOptimized { historical_samples, outlier_threshold, .. } => {
+                if *historical_samples == 0 {
+                    return Err(invalid_config("latency strategy needs at least one historical sample"));
+                }
+                if !non_negative(*outlier_threshold as f64) {
+                    return Err(invalid_config(format!("outlier threshold {} is not a non-negative number", outlier_threshold)));
+                }
+            }
+            Self::CostAware { cost_weights, max_cost } => {
+                if cost_weights.is_empty() {
+                    return Err(invalid_config("cost-aware strategy has no cost weights"));
+                }
+                if let Some((endpoint, weight)) = cost_weights.iter().find(|(_, w)| !non_negative(**w as f64)) {
+                    return Err(invalid_config(format!("cost weight {} for {} is not a non-negative number", weight, endpoint)));
+                }
+                if !non_negative(*max_cost) {
+                    return Err(invalid_config(format!("max cost {} is not a non-negative number", max_cost)));
+                }
+            }
+            Self::Hybrid { latency_weight, cost_weight, fallback } => {
+                let (latency, cost) = (*latency_weight as f64, *cost_weight as f64);
+                if !unit(latency) || !unit(cost) || (latency + cost - 1.0).abs() > 1e-6 {
+                    return Err(invalid_config(format!(
+                        "hybrid weights must lie in [0, 1] and sum to 1, got {} and {}",
+                        latency_weight, cost_weight
+                    )));
+                }
+                fallback.validate()?;
+            }
+            Self::WeightedRoundRobin { weights } => {
+                if weights.values().all(|w| *w == 0) {
+                    return Err(invalid_config("weighted round-robin has no endpoint with a positive weight"));
+                }
+            }
+            Self::ReputationWeighted { min_trust, default_trust, max_age_secs, .. } => {
+                if !unit(*min_trust) || default_trust.is_some_and(|t| !unit(t)) {
+                    return Err(invalid_config("trust thresholds must lie in [0, 1]"));
+                }
+                if *max_age_secs == 0 {
+                    return Err(invalid_config("trust max age must be greater than zero"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_config(reason: impl Into<String>) -> RoutingError {
+    RoutingError::InvalidConfig(reason.into())
+}
+
+impl RouteConnectionLimits {
+    fn limit_for(&self, endpoint: &str) -> Option<usize> {
+        self.per_route.get(endpoint).copied().or(self.default)
+    }
+}
+
+/// Resolved routing target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub endpoint: String,
+}
+
+/// Smooth weighted round-robin (nginx) selector state
+///
+/// Each pick adds every endpoint's weight to its running score, selects the
+/// highest score and subtracts the total weight from it, interleaving
+/// endpoints instead of sending runs to the heaviest one.
+#[derive(Debug, Default)]
+struct SmoothWeightedRoundRobin {
+    current: HashMap<String, i64>,
+}
+
+impl SmoothWeightedRoundRobin {
+    fn next(&mut self, weights: &HashMap<String, u32>) -> Option<String> {
+        self.current.retain(|endpoint, _| weights.get(endpoint).is_some_and(|w| *w > 0));
+
+        let mut candidates: Vec<_> = weights.iter().filter(|(_, w)| **w > 0).collect();
+        candidates.sort_by(|a, b| a.0.cmp(b.0));
+
+        let total: i64 = candidates.iter().map(|(_, w)| **w as i64).sum();
+        let mut best: Option<(&String, i64)> = None;
+
+        for (endpoint, weight) in candidates {
+            let score = self.current.entry(endpoint.clone()).or_insert(0);
+            *score += *weight as i64;
+            if best.map_or(true, |(_, s)| *score > s) {
+                best = Some((endpoint, *score));
+            }
+        }
+
+        let (endpoint, _) = best?;
+        *self.current.get_mut(endpoint)? -= total;
+        Some(endpoint.clone())
+    }
+}
+
+/// Latency-optimized selector with a round-robin cold start
+///
+/// Keeps a bounded window of recent latencies per endpoint. While fewer than
+/// `cold_start_samples` have been observed in total the choice has no basis,
+/// so endpoints are cycled to collect data; afterwards the lowest mean wins.
+#[derive(Debug, Default)]
+struct LatencySelector {
+    samples: HashMap<String, VecDeque<f64>>,
+    cursor: usize,
+}
+
+impl LatencySelector {
+    /// Record one observation, keeping at most `window` per endpoint
+    fn record(&mut self, endpoint: &str, latency_secs: f64, window: usize) {
+        let history = self.samples.entry(endpoint.to_string()).or_default();
+        history.push_back(latency_secs);
+        while history.len() > window.max(1) {
+            history.pop_front();
+        }
+    }
+
+    /// Mean of the endpoint's recorded latencies
+    fn mean(&self, endpoint: &str) -> Option<f64> {
+        let history = self.samples.get(endpoint).filter(|h| !h.is_empty())?;
+        Some(history.iter().sum::<f64>() / history.len() as f64)
+    }
+
+    fn next(&mut self, endpoints: &[String], cold_start_samples: usize) -> Option<String> {
+        if endpoints.is_empty() {
+            return None;
+        }
+
+        let total: usize = endpoints.iter()
+            .filter_map(|e| self.samples.get(e))
+            .map(VecDeque::len)
+            .sum();
+        if total < cold_start_samples {
+            let endpoint = endpoints[self.cursor % endpoints.len()].clone();
+            self.cursor = self.cursor.wrapping_add(1);
+            return Some(endpoint);
+        }
+
+        // An endpoint that joined after warm-up is probed once before ranking
+        if let Some(unsampled) = endpoints.iter().find(|e| !self.samples.contains_key(*e)) {
+            return Some(unsampled.clone());
+        }
+
+        endpoints.iter()
+            .filter_map(|e| Some((e, self.mean(e)?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(e, _)| e.clone())
+    }
+}
+
+/// Application protocol carried over the routed connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolType {
+    Http2,
+    Http1,
+    Grpc,
+    Raw,
+}
+
+impl ProtocolType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Http2 => "h2",
+            Self::Http1 => "http/1.1",
+            Self::Grpc => "grpc",
+            Self::Raw => "raw",
+        }
+    }
+
+    /// Map a negotiated ALPN identifier to a protocol
+    pub fn from_alpn(alpn: &[u8]) -> Option<Self> {
+        match alpn {
+            ALPN_H2 => Some(Self::Http2),
+            ALPN_HTTP11 => Some(Self::Http1),
+            _ => None,
+        }
+    }
 }
 
 /// Connection metadata for routing decisions
@@ -77,52 +485,243 @@ pub struct ConnectionContext {
     pub tls_version: Option<String>,
     pub priority: u8,
     pub qos_tags: HashMap<String, String>,
+    /// SNI hostname presented by the client, filled in during the handshake
+    pub server_name: Option<String>,
+    /// Protocol selected via ALPN, filled in during the handshake
+    pub alpn_protocol: Option<ProtocolType>,
+    /// Tenant owning the SNI hostname, filled in during the handshake
+    pub tenant: Option<String>,
 }
 
 /// Main routing controller structure
 pub struct RoutingController {
     strategy: RoutingStrategy,
     metrics: RoutingMetrics,
-    circuit_breakers: DashMap<String, CircuitState>,
+    circuit_breakers: Arc<CircuitBreakers>,
     connection_pool: ConnectionPool,
-    rate_limiter: RateLimiter,
-    tls_config: Arc<ServerConfig>,
+    /// Admits connections at the configured rate, highest priority first
+    admission: PriorityRateLimiter,
+    tls: Arc<ServerTls>,
+    /// Rotates ticket keys and reloads `tls` on SIGHUP; unset in tests
+    tls_maintenance: Option<tokio::task::JoinHandle<()>>,
+    tenants: Option<Arc<TenantDirectory>>,
+    trust: Option<Arc<dyn TrustSource>>,
+    backend: Arc<dyn BackendConnector>,
+    weighted_rr: std::sync::Mutex<SmoothWeightedRoundRobin>,
+    reputation_rr: std::sync::Mutex<SmoothWeightedRoundRobin>,
+    latency_selector: std::sync::Mutex<LatencySelector>,
+    /// Summarizes repeated routing failures instead of logging each one
+    error_log: Arc<RateLimitedLog>,
+    /// Reports what `error_log` held back once a flood stops; unset in tests
+    error_log_flush: Option<tokio::task::JoinHandle<()>>,
+}
+
+/// Interval over which identical routing failures share one log line
+const ERROR_LOG_WINDOW: Duration = Duration::from_secs(10);
+
+impl Drop for RoutingController {
+    fn drop(&mut self) {
+        if let Some(maintenance) = &self.tls_maintenance {
+            maintenance.abort();
+        }
+        if let Some(flush) = &self.error_log_flush {
+            flush.abort();
+        }
+    }
+}
+
+/// Server TLS configuration, rebuilt on SIGHUP so renewed certificates are
+/// served without a restart
+struct ServerTls {
+    current: std::sync::RwLock<Arc<ServerConfig>>,
+    tickets: Arc<SessionTicketer>,
+    /// Installed as the certificate resolver of every rebuilt configuration
+    tenants: std::sync::RwLock<Option<Arc<TenantDirectory>>>,
+}
+
+impl ServerTls {
+    fn new(tickets: Arc<SessionTicketer>) -> anyhow::Result<Self> {
+        let config = Self::build(&tickets, None)?;
+        Ok(Self {
+            current: std::sync::RwLock::new(Arc::new(config)),
+            tickets,
+            tenants: std::sync::RwLock::new(None),
+        })
+    }
+
+    fn build(tickets: &Arc<SessionTicketer>, tenants: Option<&Arc<TenantDirectory>>) -> anyhow::Result<ServerConfig> {
+        let mut config = with_alpn(kyber_tls::configure_server()?);
+        // Returning clients resume from a ticket, skipping the certificate
+        // and its signature; PSK-DHE still runs a fresh key exchange, Kyber
+        // encapsulation included, so resumption does not save the KEM
+        config.ticketer = tickets.clone();
+        if let Some(tenants) = tenants {
+            config.cert_resolver = tenants.clone();
+        }
+        Ok(config)
+    }
+
+    /// Configuration for the next handshake
+    fn current(&self) -> Arc<ServerConfig> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_tenants(&self, tenants: Arc<TenantDirectory>) {
+        let mut config = (*self.current()).clone();
+        config.cert_resolver = tenants.clone();
+        *self.tenants.write().unwrap_or_else(|e| e.into_inner()) = Some(tenants);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+    }
+
+    /// Rebuild from the certificates on disk, keeping the current
+    /// configuration if that fails
+    fn reload(&self) {
+        let tenants = self.tenants.read().unwrap_or_else(|e| e.into_inner()).clone();
+        match Self::build(&self.tickets, tenants.as_ref()) {
+            Ok(config) => {
+                *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+                info!("Reloaded router TLS configuration");
+            }
+            Err(e) => error!(error = %e, "Router TLS reload failed; keeping current configuration"),
+        }
+    }
 }
 
 impl RoutingController {
+    /// Controller serving `kyber_tls` certificates with session resumption
+    ///
+    /// Until the controller is dropped, a background task rotates session
+    /// ticket keys every `ticket_rotation_secs`; SIGHUP reloads the TLS
+    /// certificates and rotates the ticket key at once.
     pub async fn new(config: RouterConfig) -> anyhow::Result<Self> {
-        let tls_config = Arc::new(kyber_tls::configure_server()?);
+        config.validate()?;
+        let tickets = Arc::new(SessionTicketer::new(Duration::from_secs(config.ticket_rotation_secs))?);
+        let tls = Arc::new(ServerTls::new(tickets.clone())?);
+        let tls_maintenance = tickets.spawn_rotation({
+            let tls = Arc::downgrade(&tls);
+            move || {
+                if let Some(tls) = tls.upgrade() {
+                    tls.reload();
+                }
+            }
+        });
         let metrics = RoutingMetrics::new()?;
+        let error_log = Arc::new(RateLimitedLog::new(ERROR_LOG_WINDOW));
+        let error_log_flush = error_log.spawn_flush(|error_type, occurrences| {
+            warn!(error_type, occurrences, "Connection routing failures since their last log line");
+        });
         
         Ok(Self {
             strategy: config.strategy,
+            circuit_breakers: Arc::new(CircuitBreakers::default()),
+            connection_pool: ConnectionPool::new(
+                config.pool_size,
+                metrics.routing_latency.clone(),
+            ).with_route_limits(config.route_limits)
+                .with_metrics(metrics.pool.clone()),
             metrics,
-            circuit_breakers: DashMap::new(),
-            connection_pool: ConnectionPool::new(config.pool_size),
-            rate_limiter: RateLimiter::new(config.rate_limits),
-            tls_config,
+            admission: PriorityRateLimiter::new(config.rate_limits.requests_per_second),
+            tls,
+            tls_maintenance: Some(tls_maintenance),
+            tenants: None,
+            trust: None,
+            backend: Arc::new(FallbackConnector),
+            weighted_rr: std::sync::Mutex::new(SmoothWeightedRoundRobin::default()),
+            reputation_rr: std::sync::Mutex::new(SmoothWeightedRoundRobin::default()),
+            latency_selector: std::sync::Mutex::new(LatencySelector::default()),
+            error_log,
+            error_log_flush: Some(error_log_flush),
         })
     }
 
+    /// Trust scores consulted by `RoutingStrategy::ReputationWeighted`,
+    /// typically the reputation engine
+    pub fn with_trust_source(mut self, trust: Arc<dyn TrustSource>) -> Self {
+        self.trust = Some(trust);
+        self
+    }
+
+    /// Open backend connections through `backend` rather than
+    /// `connect_with_fallback`
+    pub fn with_backend_connector(mut self, backend: Arc<dyn BackendConnector>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Serve several tenants, each with its own certificate and endpoints
+    ///
+    /// The tenant is picked by SNI during the handshake and unknown hostnames
+    /// are refused; routing then only considers the tenant's endpoints.
+    pub fn with_tenants(mut self, tenants: TenantDirectory) -> Self {
+        let tenants = Arc::new(tenants);
+        self.tls.set_tenants(tenants.clone());
+        self.tenants = Some(tenants);
+        self
+    }
+
     /// Core routing decision pipeline
-    #[tracing::instrument(skip(self, stream))]
+    ///
+    /// Tripping `cancel` closes an established tunnel in both directions and
+    /// returns its backend connection to the pool, so draining the router
+    /// does not wait on long-lived connections.
+    #[tracing::instrument(skip_all, fields(
+        source = %context.source,
+        protocol = ?context.protocol,
+        priority = context.priority,
+        otel.status_code = tracing::field::Empty,
+        otel.status_message = tracing::field::Empty,
+    ))]
     pub async fn handle_connection(
         &self,
-        mut stream: TcpStream,
+        stream: TcpStream,
         context: ConnectionContext,
-    ) -> anyhow::Result<()> {
-        let _permit = self.rate_limiter.acquire(&context).await?;
+        cancel: CancellationToken,
+    ) -> Result<(), RoutingError> {
+        nuzon_core::enterprise_result_span!(self.route_connection(stream, context, cancel)
+            .await
+            .map_err(|e| self.count_error(e)))
+    }
+
+    /// Record a failure under its `routing_errors` label
+    ///
+    /// Failures are logged once per `ERROR_LOG_WINDOW` and label, with the
+    /// number of occurrences the line stands for.
+    fn count_error(&self, error: RoutingError) -> RoutingError {
+        let label = error.label();
+        self.metrics.routing_errors.with_label_values(&[label]).inc();
+        self.error_log.log(label, |occurrences| {
+            warn!(error_type = label, occurrences, error = %error, "Connection routing failed");
+        });
+        error
+    }
+
+    async fn route_connection(
+        &self,
+        stream: TcpStream,
+        mut context: ConnectionContext,
+        cancel: CancellationToken,
+    ) -> Result<(), RoutingError> {
+        // Each token goes to the highest-priority queued connection (after
+        // aging), so a waiting bulk connection never holds up a critical one
+        let queued_at = Instant::now();
+        self.admission.acquire(context.priority).await.map_err(RoutingError::RateLimited)?;
+        self.metrics.admission_wait
+            .with_label_values(&[priority_class(context.priority)])
+            .observe(queued_at.elapsed().as_secs_f64());
         let start_time = Instant::now();
 
         // Quantum-safe TLS handshake
-        let tls_stream = self.perform_tls_handshake(stream).await?;
+        let tls_stream = self.perform_tls_handshake(stream, &mut context).await?;
         
         // Protocol detection & routing
-        let protocol = detect_protocol(&tls_stream).await?;
+        let protocol = detect_protocol(&tls_stream)
+            .await
+            .map_err(RoutingError::ProtocolUnrecognized)?;
         let route = self.select_route(&protocol, &context).await?;
+        self.check_circuit(&route.endpoint, context.priority)?;
         
         // Connection pooling & forwarding
-        self.forward_traffic(tls_stream, route).await?;
+        self.forward_traffic(tls_stream, route, &cancel).await?;
 
         // Update metrics
         let latency = start_time.elapsed().as_secs_f64();
@@ -133,98 +732,1446 @@ impl RoutingController {
         Ok(())
     }
 
+    fn check_circuit(&self, endpoint: &str, priority: u8) -> Result<(), RoutingError> {
+        if self.circuit_breakers.allows_priority(endpoint, priority) {
+            Ok(())
+        } else {
+            Err(RoutingError::CircuitOpen { endpoint: endpoint.to_string() })
+        }
+    }
+
     /// Adaptive route selection logic
     async fn select_route(
         &self,
         protocol: &ProtocolType,
         context: &ConnectionContext,
-    ) -> anyhow::Result<Route> {
-        match &self.strategy {
-            RoutingStrategy::LatencyOptimized { historical_samples, .. } => {
-                self.latency_based_routing(protocol, *historical_samples).await
+    ) -> Result<Route, RoutingError> {
+        let tenant_endpoints = self.tenant_endpoints(context)?;
+        self.select_with(&self.strategy, protocol, tenant_endpoints).await
+    }
+
+    /// Pick an endpoint under `strategy`, confined to `tenant_endpoints` when
+    /// serving tenants
+    async fn select_with(
+        &self,
+        strategy: &RoutingStrategy,
+        protocol: &ProtocolType,
+        tenant_endpoints: Option<&[String]>,
+    ) -> Result<Route, RoutingError> {
+        let permitted = |endpoint: &String| tenant_endpoints.map_or(true, |allowed| allowed.contains(endpoint));
+        match strategy {
+            RoutingStrategy::LatencyOptimized { endpoints, cold_start_samples, .. } => {
+                self.latency_based_routing(tenant_endpoints.unwrap_or(endpoints), *cold_start_samples)
             }
-            RoutingStrategy::CostAware { .. } => {
-                self.cost_optimized_routing(context).await
+            RoutingStrategy::CostAware { cost_weights, max_cost } => {
+                cost_weights.iter()
+                    .filter(|(endpoint, cost)| permitted(endpoint) && f64::from(**cost) <= *max_cost)
+                    .min_by(|a, b| a.1.total_cmp(b.1).then_with(|| a.0.cmp(b.0)))
+                    .map(|(endpoint, _)| Route { endpoint: endpoint.clone() })
+                    .ok_or_else(|| RoutingError::NoRoute(format!("no endpoint within the cost ceiling of {}", max_cost)))
             }
-            RoutingStrategy::Hybrid { .. } => {
-                self.hybrid_routing_strategy(protocol, context).await
+            RoutingStrategy::Hybrid { latency_weight, cost_weight, fallback } => {
+                match self.hybrid_routing(*latency_weight, *cost_weight, fallback, permitted) {
+                    Some(route) => Ok(route),
+                    None => Box::pin(self.select_with(fallback, protocol, tenant_endpoints)).await,
+                }
             }
+            RoutingStrategy::WeightedRoundRobin { weights } => {
+                let scoped: HashMap<String, u32>;
+                let weights = match tenant_endpoints {
+                    Some(_) => {
+                        scoped = weights.iter()
+                            .filter(|(endpoint, _)| permitted(endpoint))
+                            .map(|(endpoint, weight)| (endpoint.clone(), *weight))
+                            .collect();
+                        &scoped
+                    }
+                    None => weights,
+                };
+                let endpoint = self.weighted_rr.lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .next(weights)
+                    .ok_or_else(|| RoutingError::NoRoute("no weighted endpoints available".into()))?;
+                Ok(Route { endpoint })
+            }
+            RoutingStrategy::ReputationWeighted { min_trust, nodes, default_trust, max_age_secs } => {
+                let policy = reputation::TrustPolicy {
+                    min_trust: *min_trust,
+                    default_trust: default_trust.unwrap_or(*min_trust),
+                    max_age: Duration::from_secs(*max_age_secs),
+                };
+                let mut weights = reputation::trust_weights(
+                    self.trust.as_deref(),
+                    nodes,
+                    policy,
+                    SystemTime::now(),
+                ).await;
+                weights.retain(|endpoint, _| permitted(endpoint));
+                let endpoint = self.reputation_rr.lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .next(&weights)
+                    .ok_or_else(|| RoutingError::NoRoute(format!("no endpoint meets the minimum trust of {}", min_trust)))?;
+                Ok(Route { endpoint })
+            }
+        }
+    }
+
+    /// Endpoints of the connection's tenant, when serving tenants
+    ///
+    /// A connection whose tenant is unknown is refused rather than routed
+    /// over every tenant's backends.
+    fn tenant_endpoints(&self, context: &ConnectionContext) -> Result<Option<&[String]>, RoutingError> {
+        let Some(tenants) = &self.tenants else { return Ok(None) };
+        let tenant = context.tenant.as_deref()
+            .and_then(|id| tenants.get(id))
+            .ok_or_else(|| RoutingError::NoRoute("connection has no known tenant".into()))?;
+        Ok(Some(&tenant.endpoints))
+    }
+
+    /// Endpoint minimising the weighted sum of its mean latency and cost,
+    /// each relative to the largest among the candidates
+    ///
+    /// Candidates are the permitted endpoints `fallback` chooses among, with
+    /// costs taken from it when it is cost-aware. A candidate without latency
+    /// samples is probed before any are scored, since nothing else would
+    /// route to it and gather them. `None` when there are no candidates,
+    /// leaving the choice to `fallback`.
+    fn hybrid_routing(
+        &self,
+        latency_weight: f32,
+        cost_weight: f32,
+        fallback: &RoutingStrategy,
+        permitted: impl Fn(&String) -> bool,
+    ) -> Option<Route> {
+        let costs = match fallback {
+            RoutingStrategy::CostAware { cost_weights, .. } => Some(cost_weights),
+            _ => None,
+        };
+        let selector = self.latency_selector.lock().unwrap_or_else(|e| e.into_inner());
+        let candidates: Vec<&String> = strategy_endpoints(fallback).into_iter()
+            .filter(|endpoint| permitted(endpoint))
+            .collect();
+        if let Some(unsampled) = candidates.iter().find(|endpoint| selector.mean(endpoint).is_none()) {
+            return Some(Route { endpoint: (*unsampled).clone() });
+        }
+        let scored: Vec<(&String, f64, f64)> = candidates.into_iter()
+            .filter_map(|endpoint| {
+                let cost = costs.and_then(|costs| costs.get(endpoint)).map_or(0.0, |cost| f64::from(*cost));
+                Some((endpoint, selector.mean(endpoint)?, cost))
+            })
+            .collect();
+        let max_latency = scored.iter().map(|s| s.1).fold(f64::EPSILON, f64::max);
+        let max_cost = scored.iter().map(|s| s.2).fold(f64::EPSILON, f64::max);
+        scored.into_iter()
+            .map(|(endpoint, latency, cost)| {
+                let score = f64::from(latency_weight) * latency / max_latency + f64::from(cost_weight) * cost / max_cost;
+                (endpoint, score)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)))
+            .map(|(endpoint, _)| Route { endpoint: endpoint.clone() })
+    }
+
+    fn latency_based_routing(&self, endpoints: &[String], cold_start_samples: usize) -> Result<Route, RoutingError> {
+        let endpoint = self.latency_selector.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .next(endpoints, cold_start_samples)
+            .ok_or_else(|| RoutingError::NoRoute("no latency-routed endpoints configured".into()))?;
+        Ok(Route { endpoint })
+    }
+
+    /// Feed an observed connection latency to the selector latency-optimized
+    /// and hybrid routing rank by
+    fn record_latency(&self, endpoint: &str, latency_secs: f64) {
+        if let Some(window) = latency_window(&self.strategy) {
+            self.latency_selector.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .record(endpoint, latency_secs, window);
         }
     }
 
     /// Connection pooling management
+    ///
+    /// The time taken to open a new backend connection, handshake included,
+    /// is what latency-based selection ranks the endpoint by.
     async fn forward_traffic(
         &self,
         mut src_stream: TlsStream,
         route: Route,
-    ) -> anyhow::Result<()> {
-        let mut dest_stream = self.connection_pool
-            .acquire(&route)
+        cancel: &CancellationToken,
+    ) -> Result<(), RoutingError> {
+        let outcome = self.connection_pool
+            .forward(&route, &mut src_stream, cancel, || async {
+                let started = Instant::now();
+                let backend = self.backend.connect(&route).await?;
+                self.record_latency(&route.endpoint, started.elapsed().as_secs_f64());
+                Ok(backend)
+            })
             .await
-            .or_else(|| connect_with_fallback(&route))
-            .ok_or_else(|| anyhow!("No available endpoints"))?;
-
-        let (mut src_rd, mut src_wr) = src_stream.split();
-        let (mut dest_rd, mut dest_wr) = dest_stream.split();
-
-        let client_to_server = tokio::io::copy(&mut src_rd, &mut dest_wr);
-        let server_to_client = tokio::io::copy(&mut dest_rd, &mut src_wr);
-
-        tokio::try_join!(client_to_server, server_to_client)?;
-        self.connection_pool.release(dest_stream).await;
-        Ok(())
+            .map_err(|source| RoutingError::BackendUnavailable {
+                endpoint: route.endpoint.clone(),
+                source,
+            })?;
+        self.metrics.tunnel_outcomes.with_label_values(&[outcome.label()]).inc();
+        match outcome {
+            TunnelOutcome::Error(e) => Err(RoutingError::BackendUnavailable {
+                endpoint: route.endpoint,
+                source: e.into(),
+            }),
+            _ => Ok(()),
+        }
     }
 
     /// TLS 1.3 with post-quantum Kyber integration
+    ///
+    /// Records the client's SNI hostname, its tenant and the negotiated ALPN
+    /// protocol on the connection context.
     async fn perform_tls_handshake(
         &self,
         stream: TcpStream,
-    ) -> anyhow::Result<TlsStream> {
-        let tls_connector = TlsConnector::new(self.tls_config.clone());
-        let domain = rustls::ServerName::try_from("nuzon.ai")?;
-        
-        let tls_stream = tls_connector
-            .connect(domain, stream)
+        context: &mut ConnectionContext,
+    ) -> Result<TlsStream, RoutingError> {
+        let tls_stream = TlsAcceptor::from(self.tls.current())
+            .accept(stream)
             .await
-            .context("TLS handshake failed")?;
+            .map_err(RoutingError::TlsHandshake)?;
+
+        let (_, session) = tls_stream.get_ref();
+        context.server_name = session.server_name().map(str::to_string);
+        context.tenant = self.tenants.as_ref()
+            .zip(context.server_name.as_deref())
+            .and_then(|(tenants, name)| tenants.for_hostname(name))
+            .map(|tenant| tenant.id.clone());
+        context.alpn_protocol = negotiated_protocol(&tls_stream);
 
         Ok(tls_stream)
     }
 
+    /// Admin gRPC service sharing this controller's circuit breakers, open
+    /// to the operators `auth` accepts
+    pub fn admin_service(&self, auth: AdminAuth) -> RouterAdminService {
+        RouterAdminService::new(self.circuit_breakers.clone(), auth)
+    }
+
     // Additional optimization methods
-    async fn hybrid_routing_strategy(&self) -> anyhow::Result<Route> { /* ... */ }
     async fn update_circuit_breakers(&self, endpoint: &str) { /* ... */ }
     async fn calculate_cost_weights(&self) -> HashMap<String, f32> { /* ... */ }
 }
 
-/// Connection pool with LRU eviction
-struct ConnectionPool {
+/// Latency samples kept per endpoint, if `strategy` ranks by latency
+fn latency_window(strategy: &RoutingStrategy) -> Option<usize> {
+    match strategy {
+        RoutingStrategy::LatencyOptimized { historical_samples, .. } => Some(*historical_samples),
+        RoutingStrategy::Hybrid { fallback, .. } => Some(latency_window(fallback).unwrap_or(DEFAULT_LATENCY_WINDOW)),
+        _ => None,
+    }
+}
+
+/// Endpoints `strategy` can select from
+fn strategy_endpoints(strategy: &RoutingStrategy) -> Vec<&String> {
+    match strategy {
+        RoutingStrategy::LatencyOptimized { endpoints, .. } => endpoints.iter().collect(),
+        RoutingStrategy::CostAware { cost_weights, max_cost } => cost_weights.iter()
+            .filter(|(_, cost)| f64::from(**cost) <= *max_cost)
+            .map(|(endpoint, _)| endpoint)
+            .collect(),
+        RoutingStrategy::Hybrid { fallback, .. } => strategy_endpoints(fallback),
+        RoutingStrategy::WeightedRoundRobin { weights } => weights.iter()
+            .filter(|(_, weight)| **weight > 0)
+            .map(|(endpoint, _)| endpoint)
+            .collect(),
+        RoutingStrategy::ReputationWeighted { nodes, .. } => nodes.keys().collect(),
+    }
+}
+
+/// Advertise the router's ALPN protocols on a server config
+fn with_alpn(mut config: ServerConfig) -> ServerConfig {
+    config.alpn_protocols = vec![ALPN_H2.to_vec(), ALPN_HTTP11.to_vec()];
+    config
+}
+
+/// Protocol agreed via ALPN, if the client offered one we support
+fn negotiated_protocol(tls_stream: &TlsStream) -> Option<ProtocolType> {
+    let (_, session) = tls_stream.get_ref();
+    session.alpn_protocol().and_then(ProtocolType::from_alpn)
+}
+
+/// Identify the application protocol, preferring ALPN over content sniffing
+async fn detect_protocol(tls_stream: &TlsStream) -> anyhow::Result<ProtocolType> {
+    if let Some(protocol) = negotiated_protocol(tls_stream) {
+        return Ok(protocol);
+    }
+    sniff_protocol(tls_stream).await
+}
+
+/// Opens the backend connections routes are tunnelled over
+#[async_trait]
+pub trait BackendConnector: Send + Sync {
+    /// New connection to the route's endpoint
+    async fn connect(&self, route: &Route) -> anyhow::Result<TlsStream>;
+}
+
+/// Connects through `connect_with_fallback`
+struct FallbackConnector;
+
+#[async_trait]
+impl BackendConnector for FallbackConnector {
+    async fn connect(&self, route: &Route) -> anyhow::Result<TlsStream> {
+        connect_with_fallback(route).ok_or_else(|| anyhow::anyhow!("No available endpoints"))
+    }
+}
+
+/// Backend connection that can be health-checked before reuse
+trait PoolConnection: Send {
+    /// Underlying socket, if the transport exposes one for probing
+    fn socket(&self) -> Option<&TcpStream>;
+}
+
+impl PoolConnection for TcpStream {
+    fn socket(&self) -> Option<&TcpStream> {
+        Some(self)
+    }
+}
+
+impl PoolConnection for TlsStream {
+    fn socket(&self) -> Option<&TcpStream> {
+        Some(self.get_ref().0)
+    }
+}
+
+/// Non-blocking liveness probe of an idle socket
+///
+/// An idle pooled connection should have nothing to read: pending means the
+/// peer is still there, EOF means it half-closed, and unsolicited bytes
+/// (typically a TLS alert) mean the session is not safe to reuse.
+fn socket_is_alive(socket: &TcpStream) -> bool {
+    let mut byte = [0u8; 1];
+    let mut buf = ReadBuf::new(&mut byte);
+    let mut cx = TaskContext::from_waker(Waker::noop());
+    matches!(socket.poll_peek(&mut cx, &mut buf), Poll::Pending)
+}
+
+/// How a tunnel ended, counted under `nuzon_routing_tunnel_outcomes_total`
+#[derive(Debug)]
+pub enum TunnelOutcome {
+    /// The client finished sending or stopped reading first
+    ClientClosed,
+    /// The backend finished sending or stopped reading first
+    BackendClosed,
+    Cancelled,
+    /// A peer failed other than by hanging up
+    Error(std::io::Error),
+}
+
+impl TunnelOutcome {
+    /// `tunnel_outcomes` label the outcome is counted under
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ClientClosed => "client_closed",
+            Self::BackendClosed => "backend_closed",
+            Self::Cancelled => "cancelled",
+            Self::Error(_) => "error",
+        }
+    }
+
+    /// Judge from the client-to-backend and backend-to-client relays
+    fn classify(upstream: Relay, downstream: Relay) -> Self {
+        // The direction that ended first was ended by its source hanging
+        // up or by its sink no longer accepting data
+        let upstream_first = upstream.finished <= downstream.finished;
+        let first = if upstream_first { &upstream } else { &downstream };
+        let client_closed = matches!(first.end, RelayEnd::SinkFailed(_)) != upstream_first;
+
+        for relay in [upstream, downstream] {
+            if let RelayEnd::SourceFailed(e) | RelayEnd::SinkFailed(e) = relay.end {
+                if !is_hang_up(&e) {
+                    return Self::Error(e);
+                }
+            }
+        }
+        if client_closed {
+            Self::ClientClosed
+        } else {
+            Self::BackendClosed
+        }
+    }
+}
+
+/// One direction of a tunnel, as it ended
+struct Relay {
+    end: RelayEnd,
+    finished: Instant,
+}
+
+enum RelayEnd {
+    /// The source sent EOF and the sink was flushed and half-closed
+    SourceEof,
+    SourceFailed(std::io::Error),
+    SinkFailed(std::io::Error),
+}
+
+/// Copy `from` into `to` until `from` ends, then flush and half-close `to`
+async fn relay<R, W>(from: &mut R, to: &mut W) -> Relay
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; RELAY_BUFFER_SIZE];
+    let end = loop {
+        let n = match from.read(&mut buf).await {
+            Ok(0) => break RelayEnd::SourceEof,
+            Ok(n) => n,
+            Err(e) => break RelayEnd::SourceFailed(e),
+        };
+        if let Err(e) = to.write_all(&buf[..n]).await {
+            break RelayEnd::SinkFailed(e);
+        }
+    };
+    // Whatever was relayed still reaches the sink when the source failed
+    let end = match end {
+        RelayEnd::SinkFailed(_) => end,
+        _ => match to.shutdown().await {
+            Ok(()) => end,
+            Err(e) => RelayEnd::SinkFailed(e),
+        },
+    };
+    Relay { end, finished: Instant::now() }
+}
+
+/// Errors meaning the peer went away rather than misbehaved
+fn is_hang_up(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(error.kind(), BrokenPipe | ConnectionReset | ConnectionAborted | UnexpectedEof)
+}
+
+/// Connection pool with health validation on reuse
+struct ConnectionPool<C = TlsStream> {
+    /// Bounds backend connections in use across all routes; idle ones hold
+    /// no permit
     semaphore: Arc<Semaphore>,
-    entries: DashMap<String, PoolEntry>,
+    route_limits: RouteConnectionLimits,
+    /// Created lazily for routes that have a limit
+    route_semaphores: DashMap<String, Arc<Semaphore>>,
+    entries: DashMap<String, Vec<PoolEntry<C>>>,
+    max_idle: Duration,
+    latency: HistogramVec,
+    metrics: PoolMetrics,
+}
+
+struct PoolEntry<C> {
+    stream: C,
+    last_used: Instant,
+}
+
+/// Backend connection checked out of the pool
+///
+/// Holds the route and global permits it was opened or reused under until
+/// it is released back to the pool or dropped.
+struct PooledConnection<C> {
+    stream: C,
+    _in_use: InUse,
+    _route_permit: Option<OwnedSemaphorePermit>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<C> std::ops::Deref for PooledConnection<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.stream
+    }
+}
+
+impl<C> std::ops::DerefMut for PooledConnection<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.stream
+    }
+}
+
+/// Counts a connection in `in_use` for as long as it lives
+struct InUse(IntGauge);
+
+impl InUse {
+    fn new(gauge: &IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge.clone())
+    }
 }
 
-struct PoolEntry {
-    stream: TlsStream,
-    last_used: AtomicU64,
+impl Drop for InUse {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
 }
 
-impl ConnectionPool {
-    pub fn new(max_connections: usize) -> Self {
+impl<C: PoolConnection> ConnectionPool<C> {
+    pub fn new(max_connections: usize, latency: HistogramVec) -> Self {
         Self {
             semaphore: Arc::new(Semaphore::new(max_connections)),
+            route_limits: RouteConnectionLimits::default(),
+            route_semaphores: DashMap::new(),
             entries: DashMap::new(),
+            max_idle: DEFAULT_MAX_IDLE,
+            latency,
+            metrics: PoolMetrics::detached(),
+        }
+    }
+
+    /// Report utilisation through `metrics` instead of a private set
+    pub fn with_metrics(mut self, metrics: PoolMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Cap each route's share of the global limit
+    pub fn with_route_limits(mut self, route_limits: RouteConnectionLimits) -> Self {
+        self.route_limits = route_limits;
+        self.route_semaphores.clear();
+        self
+    }
+
+    /// Override the idle-age cutoff used for connections that cannot be probed
+    pub fn with_max_idle(mut self, max_idle: Duration) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Take a healthy pooled connection for the route, or open a new one
+    ///
+    /// Waits for the route's quota and a global permit first; both stay held
+    /// until the connection is released or dropped. Dead connections found
+    /// along the way are dropped, and the cost of any handshake is recorded
+    /// under `routing_latency`, as a reconnect if it replaces one.
+    pub async fn acquire<F, Fut>(&self, route: &Route, connect: F) -> anyhow::Result<PooledConnection<C>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<C>>,
+    {
+        // Route quota first, so a route waiting on its own share never
+        // holds a global slot another route could use
+        let waiting = Instant::now();
+        let route_permit = match self.route_semaphore(route) {
+            Some(semaphore) => Some(semaphore.acquire_owned().await?),
+            None => None,
+        };
+        let permit = self.semaphore.clone().acquire_owned().await?;
+        self.metrics.acquire_wait.observe(waiting.elapsed().as_secs_f64());
+        let checked_out = |stream| PooledConnection {
+            stream,
+            _in_use: InUse::new(&self.metrics.in_use),
+            _route_permit: route_permit,
+            _permit: permit,
+        };
+
+        let mut replacing = false;
+        while let Some(entry) = self.take_idle(route) {
+            if self.is_healthy(&entry) {
+                return Ok(checked_out(entry.stream));
+            }
+            replacing = true;
+            self.metrics.evictions.inc();
+            debug!(endpoint = %route.endpoint, "Discarding dead pooled connection");
+        }
+
+        let start = Instant::now();
+        let stream = connect().await?;
+        self.latency
+            .with_label_values(&["backend", if replacing { "reconnect" } else { "connect" }])
+            .observe(start.elapsed().as_secs_f64());
+        Ok(checked_out(stream))
+    }
+
+    /// Tunnel `client` to a backend for the route until both sides finish
+    /// or `cancel` trips
+    ///
+    /// Each direction runs to its own end: when one peer stops, whatever
+    /// the other has already sent is still flushed before its direction is
+    /// half-closed. On cancellation both directions are half-closed at
+    /// once. Either way the backend ends half-closed and can carry no
+    /// further request, so the connection is dropped rather than pooled.
+    pub async fn forward<S, F, Fut>(
+        &self,
+        route: &Route,
+        client: &mut S,
+        cancel: &CancellationToken,
+        connect: F,
+    ) -> anyhow::Result<TunnelOutcome>
+    where
+        C: AsyncRead + AsyncWrite + Unpin,
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<C>>,
+    {
+        let mut backend = self.acquire(route, connect).await?;
+
+        let outcome = tokio::select! {
+            (upstream, downstream) = async {
+                let (mut client_rx, mut client_tx) = tokio::io::split(&mut *client);
+                let (mut backend_rx, mut backend_tx) = tokio::io::split(&mut *backend);
+                tokio::join!(
+                    relay(&mut client_rx, &mut backend_tx),
+                    relay(&mut backend_rx, &mut client_tx),
+                )
+            } => TunnelOutcome::classify(upstream, downstream),
+            _ = cancel.cancelled() => {
+                debug!(endpoint = %route.endpoint, "Tunnel cancelled, closing both directions");
+                let (to_client, to_backend) = tokio::join!(client.shutdown(), backend.shutdown());
+                if let Err(e) = to_client.and(to_backend) {
+                    debug!(endpoint = %route.endpoint, error = %e, "Half-close on cancelled tunnel failed");
+                }
+                TunnelOutcome::Cancelled
+            }
+        };
+
+        if let TunnelOutcome::Error(e) = &outcome {
+            debug!(endpoint = %route.endpoint, error = %e, "Tunnel failed, dropping backend connection");
+        }
+        drop(backend);
+        Ok(outcome)
+    }
+
+    /// Return a connection to the pool for later reuse
+    ///
+    /// Its permits are freed once it is back among the idle connections, so
+    /// whoever they wake can reuse it.
+    pub fn release(&self, route: &Route, connection: PooledConnection<C>) {
+        let PooledConnection { stream, .. } = connection;
+        self.metrics.idle.inc();
+        self.entries
+            .entry(route.endpoint.clone())
+            .or_default()
+            .push(PoolEntry { stream, last_used: Instant::now() });
+    }
+
+    fn route_semaphore(&self, route: &Route) -> Option<Arc<Semaphore>> {
+        let limit = self.route_limits.limit_for(&route.endpoint)?;
+        Some(self.route_semaphores
+            .entry(route.endpoint.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone())
+    }
+
+    /// Most recently used idle connection for the route
+    fn take_idle(&self, route: &Route) -> Option<PoolEntry<C>> {
+        let entry = self.entries.get_mut(&route.endpoint)?.pop()?;
+        self.metrics.idle.dec();
+        Some(entry)
+    }
+
+    fn is_healthy(&self, entry: &PoolEntry<C>) -> bool {
+        match entry.stream.socket() {
+            Some(socket) => socket_is_alive(socket),
+            None => entry.last_used.elapsed() < self.max_idle,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsConnector;
+
+    /// Certificate `test_controller` serves, shared so clients can trust it
+    fn router_cert() -> (rustls::Certificate, rustls::PrivateKey) {
+        static CERT: std::sync::OnceLock<(rustls::Certificate, rustls::PrivateKey)> = std::sync::OnceLock::new();
+        CERT.get_or_init(|| self_signed("router.nuzon.ai")).clone()
+    }
+
+    fn self_signed(host: &str) -> (rustls::Certificate, rustls::PrivateKey) {
+        let cert = rcgen::generate_simple_self_signed(vec![host.to_string()]).unwrap();
+        (
+            rustls::Certificate(cert.serialize_der().unwrap()),
+            rustls::PrivateKey(cert.serialize_private_key_der()),
+        )
+    }
+
+    /// Server config presenting `router_cert`
+    fn router_config() -> ServerConfig {
+        let (cert, key) = router_cert();
+        with_alpn(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert], key)
+                .unwrap(),
+        )
+    }
+
+    /// Client config trusting `router_cert` and offering h2
+    fn client_config() -> Arc<ClientConfig> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&router_cert().0).unwrap();
+        let mut client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec()];
+        Arc::new(client_config)
+    }
+
+    fn test_metrics(prefix: &str) -> RoutingMetrics {
+        let histogram = |name: &str, labels: &[&str]| {
+            HistogramVec::new(HistogramOpts::new(format!("{}_{}", prefix, name), "test"), labels).unwrap()
+        };
+        RoutingMetrics {
+            routing_latency: histogram("latency", &["protocol", "strategy"]),
+            routing_errors: IntCounterVec::new(
+                prometheus::Opts::new(format!("{}_errors", prefix), "test"),
+                &["error_type"],
+            ).unwrap(),
+            throughput: IntCounterVec::new(
+                prometheus::Opts::new(format!("{}_throughput", prefix), "test"),
+                &["direction"],
+            ).unwrap(),
+            tunnel_outcomes: IntCounterVec::new(
+                prometheus::Opts::new(format!("{}_tunnel_outcomes", prefix), "test"),
+                &["outcome"],
+            ).unwrap(),
+            admission_wait: histogram("admission_wait", &["priority"]),
+            pool: PoolMetrics::detached(),
+        }
+    }
+
+    /// Controller over an unregistered metric set, serving a self-signed cert
+    fn test_controller(strategy: RoutingStrategy) -> RoutingController {
+        let metrics = test_metrics("test_routing_error");
+        RoutingController {
+            strategy,
+            circuit_breakers: Arc::new(CircuitBreakers::default()),
+            connection_pool: ConnectionPool::new(4, metrics.routing_latency.clone()),
+            metrics,
+            admission: PriorityRateLimiter::new(RateLimitConfig::default().requests_per_second),
+            tls: Arc::new(ServerTls {
+                current: std::sync::RwLock::new(Arc::new(router_config())),
+                tickets: Arc::new(SessionTicketer::new(Duration::from_secs(3600)).unwrap()),
+                tenants: std::sync::RwLock::new(None),
+            }),
+            tls_maintenance: None,
+            tenants: None,
+            trust: None,
+            backend: Arc::new(FallbackConnector),
+            weighted_rr: std::sync::Mutex::new(SmoothWeightedRoundRobin::default()),
+            reputation_rr: std::sync::Mutex::new(SmoothWeightedRoundRobin::default()),
+            latency_selector: std::sync::Mutex::new(LatencySelector::default()),
+            error_log: Arc::new(RateLimitedLog::new(ERROR_LOG_WINDOW)),
+            error_log_flush: None,
+        }
+    }
+
+    fn test_context() -> ConnectionContext {
+        ConnectionContext {
+            source: "127.0.0.1:9".parse().unwrap(),
+            protocol: ProtocolType::Raw,
+            tls_version: None,
+            priority: NORMAL_PRIORITY,
+            qos_tags: HashMap::new(),
+            server_name: None,
+            alpn_protocol: None,
+            tenant: None,
+        }
+    }
+
+    /// Run one client connection through `handle_connection`
+    ///
+    /// With `tls` the client completes a handshake offering h2 and holds
+    /// the session open until the router drops it; without, it sends
+    /// plaintext HTTP.
+    async fn serve_one(controller: &RoutingController, tls: bool) -> Result<(), RoutingError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            if !tls {
+                let _ = stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await;
+                return;
+            }
+            let domain = rustls::ServerName::try_from("router.nuzon.ai").unwrap();
+            if let Ok(mut tls_stream) = TlsConnector::from(client_config()).connect(domain, stream).await {
+                let _ = tls_stream.read_to_end(&mut Vec::new()).await;
+            }
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let result = controller.handle_connection(stream, test_context(), CancellationToken::new()).await;
+        client.await.unwrap();
+        result
+    }
+
+    /// Backends that take a set time to connect to, each a loopback TLS
+    /// peer that sends nothing, hangs up after `hold` and closes once the
+    /// client does
+    struct LoopbackBackends {
+        delays: HashMap<String, Duration>,
+        hold: Duration,
+        connected: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl BackendConnector for LoopbackBackends {
+        async fn connect(&self, route: &Route) -> anyhow::Result<TlsStream> {
+            tokio::time::sleep(self.delays[&route.endpoint]).await;
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            let hold = self.hold;
+            tokio::spawn(async move {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let domain = rustls::ServerName::try_from("router.nuzon.ai").unwrap();
+                let mut peer = TlsConnector::from(client_config()).connect(domain, stream).await.unwrap();
+                tokio::time::sleep(hold).await;
+                let _ = peer.shutdown().await;
+                let _ = peer.read_to_end(&mut Vec::new()).await;
+            });
+            let (stream, _) = listener.accept().await?;
+            let backend = TlsAcceptor::from(Arc::new(router_config())).accept(stream).await?;
+            self.connected.lock().unwrap().push(route.endpoint.clone());
+            Ok(backend)
+        }
+    }
+
+    fn error_count(controller: &RoutingController, label: &str) -> u64 {
+        controller.metrics.routing_errors.with_label_values(&[label]).get()
+    }
+
+    #[tokio::test]
+    async fn test_failed_connections_count_under_their_label() {
+        // Refused at admission, before any handshake
+        let mut controller = test_controller(weighted(&[("a", 1)]));
+        controller.admission = PriorityRateLimiter::new(1.0).with_max_wait(Duration::from_millis(20));
+        controller.admission.acquire(0).await.unwrap();
+        let error = serve_one(&controller, true).await.unwrap_err();
+        assert_eq!(error.label(), "rate_limited");
+        assert_eq!(error_count(&controller, "rate_limited"), 1);
+
+        let controller = test_controller(weighted(&[("a", 1)]));
+        let error = serve_one(&controller, false).await.unwrap_err();
+        assert!(matches!(error, RoutingError::TlsHandshake(_)), "{:?}", error);
+        assert_eq!(error_count(&controller, "tls_handshake"), 1);
+
+        let controller = test_controller(weighted(&[]));
+        let error = serve_one(&controller, true).await.unwrap_err();
+        assert!(matches!(error, RoutingError::NoRoute(_)), "{:?}", error);
+        assert_eq!(error_count(&controller, "no_route"), 1);
+
+        let controller = test_controller(weighted(&[("flaky", 1)]));
+        for _ in 0..5 {
+            controller.circuit_breakers.record_failure("flaky");
+        }
+        for _ in 0..2 {
+            let error = serve_one(&controller, true).await.unwrap_err();
+            assert!(matches!(error, RoutingError::CircuitOpen { ref endpoint } if endpoint == "flaky"));
+        }
+        assert_eq!(error_count(&controller, "circuit_open"), 2);
+        // Each failure is counted only under its own label
+        assert_eq!(error_count(&controller, "no_route") + error_count(&controller, "tls_handshake"), 0);
+    }
+
+    fn config(strategy: RoutingStrategy) -> RouterConfig {
+        RouterConfig {
+            strategy,
+            pool_size: 16,
+            rate_limits: RateLimitConfig::default(),
+            route_limits: RouteConnectionLimits::default(),
+            ticket_rotation_secs: 3600,
+        }
+    }
+
+    fn weighted(weights: &[(&str, u32)]) -> RoutingStrategy {
+        RoutingStrategy::WeightedRoundRobin {
+            weights: weights.iter().map(|(e, w)| (e.to_string(), *w)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid_router_config_accepted() {
+        let hybrid = RoutingStrategy::Hybrid {
+            latency_weight: 0.7,
+            cost_weight: 0.3,
+            fallback: Box::new(weighted(&[("a", 1), ("b", 0)])),
+        };
+        assert!(config(hybrid).validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_router_configs_rejected() {
+        let mut cases: Vec<(&str, RouterConfig)> = Vec::new();
+        cases.push(("pool_size", RouterConfig { pool_size: 0, ..config(weighted(&[("a", 1)])) }));
+
+        let mut negative_rate = config(weighted(&[("a", 1)]));
+        negative_rate.rate_limits.requests_per_second = -1.0;
+        cases.push(("rate limit", negative_rate));
+
+        let mut zero_route = config(weighted(&[("a", 1)]));
+        zero_route.route_limits.per_route.insert("a".into(), 0);
+        cases.push(("connection limit for a", zero_route));
+        cases.push(("ticket rotation", RouterConfig { ticket_rotation_secs: 0, ..config(weighted(&[("a", 1)])) }));
+
+        cases.push(("hybrid weights", config(RoutingStrategy::Hybrid {
+            latency_weight: 1.0,
+            cost_weight: 1.0,
+            fallback: Box::new(weighted(&[("a", 1)])),
+        })));
+        cases.push(("positive weight", config(RoutingStrategy::Hybrid {
+            latency_weight: 0.5,
+            cost_weight: 0.5,
+            fallback: Box::new(weighted(&[("a", 0)])),
+        })));
+        cases.push(("no cost weights", config(RoutingStrategy::CostAware {
+            cost_weights: HashMap::new(),
+            max_cost: 1.0,
+        })));
+        cases.push(("cost weight", config(RoutingStrategy::CostAware {
+            cost_weights: [("a".to_string(), -0.5)].into_iter().collect(),
+            max_cost: 1.0,
+        })));
+        cases.push(("historical sample", config(RoutingStrategy::LatencyOptimized {
+            historical_samples: 0,
+            outlier_threshold: 2.0,
+            endpoints: vec!["a".into()],
+            cold_start_samples: 32,
+        })));
+        cases.push(("trust thresholds", config(RoutingStrategy::ReputationWeighted {
+            min_trust: 1.5,
+            nodes: HashMap::new(),
+            default_trust: None,
+            max_age_secs: 300,
+        })));
+
+        for (expected, config) in cases {
+            match config.validate() {
+                Err(RoutingError::InvalidConfig(reason)) => assert!(reason.contains(expected), "{}", reason),
+                other => panic!("{} accepted: {:?}", expected, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_strategy_is_no_route() {
+        let weights = [("drained".to_string(), 0)].into_iter().collect();
+        let controller = test_controller(RoutingStrategy::WeightedRoundRobin { weights });
+        let error = controller.select_route(&ProtocolType::Raw, &test_context()).await.unwrap_err();
+        assert!(matches!(error, RoutingError::NoRoute(_)), "{:?}", error);
+
+        let controller = test_controller(RoutingStrategy::ReputationWeighted {
+            min_trust: 0.5,
+            nodes: HashMap::new(),
+            default_trust: None,
+            max_age_secs: 300,
+        });
+        let error = controller.select_route(&ProtocolType::Raw, &test_context()).await.unwrap_err();
+        assert!(matches!(error, RoutingError::NoRoute(_)), "{:?}", error);
+    }
+
+    fn tenant(id: &str, endpoints: &[&str]) -> Tenant {
+        let (cert, key) = self_signed(&format!("{}.nuzon.ai", id));
+        Tenant {
+            id: id.into(),
+            endpoints: endpoints.iter().map(|e| e.to_string()).collect(),
+            cert: Arc::new(rustls::sign::CertifiedKey::new(vec![cert], rustls::sign::any_supported_type(&key).unwrap())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_strategy_routes_within_the_tenant() {
+        let costs: HashMap<String, f32> =
+            [("shared-cheap", 0.1), ("acme-a", 0.5), ("acme-b", 0.9)].into_iter().map(|(e, c)| (e.to_string(), c)).collect();
+        let cost_aware = || RoutingStrategy::CostAware { cost_weights: costs.clone(), max_cost: 1.0 };
+        let strategies = [
+            cost_aware(),
+            RoutingStrategy::Hybrid { latency_weight: 0.5, cost_weight: 0.5, fallback: Box::new(cost_aware()) },
+            RoutingStrategy::WeightedRoundRobin { weights: costs.keys().map(|e| (e.clone(), 1)).collect() },
+        ];
+        let acme = ConnectionContext { tenant: Some("acme".into()), ..test_context() };
+
+        for strategy in strategies {
+            let mut directory = TenantDirectory::new();
+            directory.insert(tenant("acme", &["acme-a", "acme-b"]), ["acme.nuzon.ai"]);
+            let controller = test_controller(strategy.clone()).with_tenants(directory);
+
+            for _ in 0..4 {
+                let route = controller.select_route(&ProtocolType::Raw, &acme).await.unwrap();
+                assert!(route.endpoint.starts_with("acme-"), "{:?} routed acme to {}", strategy, route.endpoint);
+            }
+            // Without a tenant the connection is refused, not routed globally
+            let error = controller.select_route(&ProtocolType::Raw, &test_context()).await.unwrap_err();
+            assert!(matches!(error, RoutingError::NoRoute(_)), "{:?}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_routing_learns_latency_from_served_connections() {
+        let costs = [("cheap".to_string(), 0.1), ("fast".to_string(), 0.9)].into_iter().collect();
+        let backends = Arc::new(LoopbackBackends {
+            delays: [("cheap", 80), ("fast", 5)].into_iter()
+                .map(|(endpoint, millis)| (endpoint.to_string(), Duration::from_millis(millis)))
+                .collect(),
+            hold: Duration::ZERO,
+            connected: std::sync::Mutex::new(Vec::new()),
+        });
+        let controller = test_controller(RoutingStrategy::Hybrid {
+            latency_weight: 0.9,
+            cost_weight: 0.1,
+            fallback: Box::new(RoutingStrategy::CostAware { cost_weights: costs, max_cost: 1.0 }),
+        }).with_backend_connector(backends.clone());
+
+        for _ in 0..4 {
+            serve_one(&controller, true).await.unwrap();
+        }
+
+        // Each endpoint is probed once, then the slow one loses despite its cost
+        let connected = backends.connected.lock().unwrap().clone();
+        let mut probed = connected[..2].to_vec();
+        probed.sort();
+        assert_eq!(probed, ["cheap", "fast"]);
+        assert_eq!(connected[2..], ["fast", "fast"]);
+        let selector = controller.latency_selector.lock().unwrap();
+        assert!(selector.mean("cheap").unwrap() > selector.mean("fast").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_latency_sample_is_backend_connect_time() {
+        let backends = Arc::new(LoopbackBackends {
+            delays: [("a".to_string(), Duration::from_millis(5))].into_iter().collect(),
+            hold: Duration::from_millis(300),
+            connected: std::sync::Mutex::new(Vec::new()),
+        });
+        let controller = test_controller(RoutingStrategy::LatencyOptimized {
+            historical_samples: 8,
+            outlier_threshold: 2.0,
+            endpoints: vec!["a".into()],
+            cold_start_samples: 1,
+        }).with_backend_connector(backends);
+
+        serve_one(&controller, true).await.unwrap();
+
+        // The tunnel stayed open for 300ms; only reaching the backend counts
+        let mean = controller.latency_selector.lock().unwrap().mean("a").unwrap();
+        assert!((0.005..0.3).contains(&mean), "{}", mean);
+    }
+
+    #[test]
+    fn test_tripped_breaker_is_circuit_open() {
+        let controller = test_controller(RoutingStrategy::WeightedRoundRobin { weights: HashMap::new() });
+        assert!(controller.check_circuit("flaky", NORMAL_PRIORITY).is_ok());
+        for _ in 0..5 {
+            controller.circuit_breakers.record_failure("flaky");
+        }
+        let error = controller.check_circuit("flaky", NORMAL_PRIORITY).unwrap_err();
+        assert!(matches!(error, RoutingError::CircuitOpen { ref endpoint } if endpoint == "flaky"));
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_client_is_tls_handshake_error() {
+        let controller = test_controller(RoutingStrategy::WeightedRoundRobin { weights: HashMap::new() });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let _ = stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await;
+            stream
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let error = controller.perform_tls_handshake(stream, &mut test_context()).await.unwrap_err();
+        assert!(matches!(error, RoutingError::TlsHandshake(_)), "{:?}", error);
+        drop(client.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_alpn_negotiation_surfaces_protocol() {
+        let (cert, key) = self_signed("tenant.nuzon.ai");
+        let server_config = with_alpn(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert.clone()], key)
+                .unwrap(),
+        );
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&cert).unwrap();
+        let mut client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let domain = rustls::ServerName::try_from("tenant.nuzon.ai").unwrap();
+            TlsConnector::from(Arc::new(client_config))
+                .connect(domain, stream)
+                .await
+                .unwrap()
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let tls_stream = TlsAcceptor::from(Arc::new(server_config))
+            .accept(stream)
+            .await
+            .unwrap();
+
+        assert_eq!(negotiated_protocol(&tls_stream), Some(ProtocolType::Http2));
+        assert_eq!(detect_protocol(&tls_stream).await.unwrap(), ProtocolType::Http2);
+        assert_eq!(tls_stream.get_ref().1.server_name(), Some("tenant.nuzon.ai"));
+        client.await.unwrap();
+    }
+
+    #[test]
+    fn test_smooth_weighted_round_robin_distribution() {
+        let weights: HashMap<String, u32> = [("a", 5), ("b", 3), ("c", 2), ("drained", 0)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        let mut selector = SmoothWeightedRoundRobin::default();
+
+        let picks: Vec<_> = (0..1000).map(|_| selector.next(&weights).unwrap()).collect();
+        let count = |ep: &str| picks.iter().filter(|p| *p == ep).count() as f64;
+
+        assert!((count("a") / 1000.0 - 0.5).abs() < 0.01);
+        assert!((count("b") / 1000.0 - 0.3).abs() < 0.01);
+        assert!((count("c") / 1000.0 - 0.2).abs() < 0.01);
+        assert_eq!(count("drained"), 0.0);
+
+        // Smoothness: the heaviest endpoint is never picked more than twice in a row
+        let longest_run = picks.windows(3).filter(|w| w.iter().all(|p| p == "a")).count();
+        assert_eq!(longest_run, 0);
+    }
+
+    #[test]
+    fn test_latency_selector_cold_start_then_converges() {
+        let endpoints: Vec<String> = ["fast", "medium", "slow"].iter().map(|e| e.to_string()).collect();
+        let latency = |ep: &str| match ep {
+            "fast" => 0.010,
+            "medium" => 0.050,
+            _ => 0.200,
+        };
+        let mut selector = LatencySelector::default();
+
+        // Cold: no history, so every endpoint gets an equal share
+        let cold: Vec<_> = (0..9).map(|_| {
+            let pick = selector.next(&endpoints, 9).unwrap();
+            selector.record(&pick, latency(&pick), 16);
+            pick
+        }).collect();
+        for ep in &endpoints {
+            assert_eq!(cold.iter().filter(|p| *p == ep).count(), 3);
+        }
+
+        // Warm: enough samples, so selection settles on the fastest endpoint
+        for _ in 0..20 {
+            let pick = selector.next(&endpoints, 9).unwrap();
+            assert_eq!(pick, "fast");
+            selector.record(&pick, latency(&pick), 16);
+        }
+        assert_eq!(selector.samples["fast"].len(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_pool_replaces_dead_connection() {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_routing_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: ConnectionPool<TcpStream> = ConnectionPool::new(4, latency.clone());
+        let route = Route { endpoint: "backend-a".into() };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connects = AtomicUsize::new(0);
+        let connect = || async {
+            connects.fetch_add(1, Ordering::SeqCst);
+            Ok(TcpStream::connect(addr).await?)
+        };
+
+        // Healthy pooled connection is reused without reconnecting
+        let first = pool.acquire(&route, connect).await.unwrap();
+        let (backend, _) = listener.accept().await.unwrap();
+        let first_port = first.local_addr().unwrap().port();
+        pool.release(&route, first);
+        let reused = pool.acquire(&route, connect).await.unwrap();
+        assert_eq!(reused.local_addr().unwrap().port(), first_port);
+        assert_eq!(connects.load(Ordering::SeqCst), 1);
+
+        // Backend closes out-of-band while the connection sits idle
+        pool.release(&route, reused);
+        drop(backend);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let replaced = pool.acquire(&route, connect).await.unwrap();
+        assert_ne!(replaced.local_addr().unwrap().port(), first_port);
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+        // Only the handshake that replaced the dead connection is a reconnect
+        assert_eq!(latency.with_label_values(&["backend", "connect"]).get_sample_count(), 1);
+        assert_eq!(latency.with_label_values(&["backend", "reconnect"]).get_sample_count(), 1);
+        assert_eq!(pool.metrics.evictions.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_metrics_reflect_contention() {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_pool_metrics_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: Arc<ConnectionPool<TcpStream>> = Arc::new(ConnectionPool::new(1, latency));
+        let route = Route { endpoint: "backend".into() };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The only permit is held by a handshake that stalls until released
+        let stalled = Arc::new(tokio::sync::Notify::new());
+        let first = {
+            let (pool, route, stalled) = (pool.clone(), route.clone(), stalled.clone());
+            tokio::spawn(async move {
+                pool.acquire(&route, || async move {
+                    stalled.notified().await;
+                    Ok(TcpStream::connect(addr).await?)
+                }).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let second = {
+            let (pool, route) = (pool.clone(), route.clone());
+            tokio::spawn(async move {
+                pool.acquire(&route, || async { Ok(TcpStream::connect(addr).await?) }).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(pool.metrics.acquire_wait.get_sample_count(), 1);
+        stalled.notify_one();
+
+        // The finished handshake keeps the permit while its connection is in use
+        let first = first.await.unwrap().unwrap();
+        let metrics = &pool.metrics;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!second.is_finished());
+        assert_eq!(metrics.in_use.get(), 1);
+
+        // Releasing it hands both the permit and the connection to the waiter
+        pool.release(&route, first);
+        let second = second.await.unwrap().unwrap();
+        assert_eq!((metrics.in_use.get(), metrics.idle.get()), (1, 0));
+        assert_eq!(metrics.acquire_wait.get_sample_count(), 2);
+        assert!(metrics.acquire_wait.get_sample_sum() >= 0.1, "{}", metrics.acquire_wait.get_sample_sum());
+
+        drop(second);
+        assert_eq!((metrics.in_use.get(), pool.semaphore.available_permits()), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn test_failed_connect_releases_permits_and_gauge() {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_pool_failed_connect_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: ConnectionPool<TcpStream> = ConnectionPool::new(1, latency)
+            .with_route_limits(RouteConnectionLimits { default: Some(1), per_route: HashMap::new() });
+        let route = Route { endpoint: "backend".into() };
+
+        for _ in 0..2 {
+            pool.acquire(&route, || async { anyhow::bail!("connection refused") }).await.err().unwrap();
+        }
+        assert_eq!(pool.metrics.in_use.get(), 0);
+        assert_eq!(pool.semaphore.available_permits(), 1);
+        assert_eq!(pool.route_semaphore(&route).unwrap().available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_tears_down_tunnel_and_drops_backend() {
+        use tokio::io::AsyncReadExt;
+
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_tunnel_cancel_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: Arc<ConnectionPool<TcpStream>> = Arc::new(ConnectionPool::new(4, latency));
+        let backend_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend_listener.local_addr().unwrap();
+        let front_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(front_listener.local_addr().unwrap()).await.unwrap();
+        let (mut accepted, _) = front_listener.accept().await.unwrap();
+
+        let cancel = CancellationToken::new();
+        let route = Route { endpoint: "backend".into() };
+        let tunnel = {
+            let (pool, route, cancel) = (pool.clone(), route.clone(), cancel.clone());
+            tokio::spawn(async move {
+                pool.forward(&route, &mut accepted, &cancel, || async move {
+                    Ok(TcpStream::connect(backend_addr).await?)
+                }).await
+            })
+        };
+        let (mut backend, _) = backend_listener.accept().await.unwrap();
+
+        // Traffic flows while the tunnel is up
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        backend.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_secs(1), tunnel)
+            .await
+            .expect("tunnel ignored cancellation")
+            .unwrap()
+            .unwrap();
+
+        // Both ends see the half-close and the backend is not pooled
+        assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(backend.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(pool.entries.get("backend").map_or(0, |e| e.len()), 0);
+        assert_eq!(pool.metrics.in_use.get(), 0);
+    }
+
+    /// Client that has stopped reading: whatever is sent to it fails as on
+    /// a socket whose peer shut down its read half
+    struct StoppedReading(tokio::io::DuplexStream);
+
+    impl AsyncRead for StoppedReading {
+        fn poll_read(self: std::pin::Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+            std::pin::Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
         }
     }
 
-    pub async fn acquire(&self, route: &Route) -> Option<TlsStream> {
-        let permit = self.semaphore.acquire().await.ok()?;
-        let entry = self.entries.get_mut(&route.endpoint)?;
-        entry.last_used.store(now(), Ordering::Relaxed);
-        Some(entry.stream.clone())
+    impl AsyncWrite for StoppedReading {
+        fn poll_write(self: std::pin::Pin<&mut Self>, _: &mut TaskContext<'_>, _: &[u8]) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(self: std::pin::Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: std::pin::Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_hang_up_still_flushes_request_to_backend() {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_tunnel_half_close_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: Arc<ConnectionPool<TcpStream>> = Arc::new(ConnectionPool::new(4, latency));
+        let backend_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend_listener.local_addr().unwrap();
+        let (mut request, client_side) = tokio::io::duplex(64);
+
+        let route = Route { endpoint: "backend".into() };
+        let tunnel = {
+            let (pool, route) = (pool.clone(), route.clone());
+            tokio::spawn(async move {
+                let mut client = StoppedReading(client_side);
+                pool.forward(&route, &mut client, &CancellationToken::new(), || async move {
+                    Ok(TcpStream::connect(backend_addr).await?)
+                }).await
+            })
+        };
+        let (mut backend, _) = backend_listener.accept().await.unwrap();
+
+        // The backend answers early; relaying the answer fails mid-request
+        request.write_all(b"POST /v1/infer HTTP/1.1\r\n").await.unwrap();
+        backend.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        request.write_all(b"content-length: 2\r\n\r\n{}").await.unwrap();
+        drop(request);
+
+        let mut received = Vec::new();
+        tokio::time::timeout(Duration::from_secs(1), backend.read_to_end(&mut received))
+            .await
+            .expect("backend never saw the request end")
+            .unwrap();
+        assert_eq!(received, b"POST /v1/infer HTTP/1.1\r\ncontent-length: 2\r\n\r\n{}");
+
+        let outcome = tunnel.await.unwrap().unwrap();
+        assert!(matches!(outcome, TunnelOutcome::ClientClosed), "{:?}", outcome);
+
+        // The backend was half-closed, so it is dropped instead of pooled
+        assert_eq!(pool.entries.get("backend").map_or(0, |e| e.len()), 0);
+        assert_eq!((pool.metrics.in_use.get(), pool.metrics.idle.get()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_hot_route_cannot_starve_cold_route() {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_route_quota_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: Arc<ConnectionPool<TcpStream>> = Arc::new(
+            ConnectionPool::new(4, latency).with_route_limits(RouteConnectionLimits {
+                default: Some(3),
+                per_route: [("hot".to_string(), 2)].into_iter().collect(),
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Saturate the hot route with handshakes that never complete
+        let stalled = Arc::new(tokio::sync::Notify::new());
+        let hot = Route { endpoint: "hot".into() };
+        let hot_attempts: Vec<_> = (0..3).map(|_| {
+            let (pool, hot, stalled) = (pool.clone(), hot.clone(), stalled.clone());
+            tokio::spawn(async move {
+                pool.acquire(&hot, || async move {
+                    stalled.notified().await;
+                    Ok(TcpStream::connect(addr).await?)
+                }).await
+            })
+        }).collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Two hold the hot quota; the third waits on it without taking a global slot
+        assert_eq!(pool.route_semaphore(&hot).unwrap().available_permits(), 0);
+        assert_eq!(pool.semaphore.available_permits(), 2);
+
+        let cold = Route { endpoint: "cold".into() };
+        let stream = tokio::time::timeout(
+            Duration::from_secs(1),
+            pool.acquire(&cold, || async { Ok(TcpStream::connect(addr).await?) }),
+        ).await.expect("cold route starved by hot route").unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+
+        for attempt in hot_attempts {
+            attempt.abort();
+        }
     }
 
-    pub async fn release(&self, stream: TlsStream) {
-        // Update connection state and return to pool
+    #[tokio::test]
+    async fn test_route_quota_covers_connections_in_use() {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("test_route_quota_in_use_latency", "test"),
+            &["protocol", "strategy"],
+        ).unwrap();
+        let pool: Arc<ConnectionPool<TcpStream>> = Arc::new(
+            ConnectionPool::new(4, latency).with_route_limits(RouteConnectionLimits {
+                default: None,
+                per_route: [("hot".to_string(), 1)].into_iter().collect(),
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hot = Route { endpoint: "hot".into() };
+
+        // A finished handshake still counts against the quota while in use
+        let open = pool.acquire(&hot, || async { Ok(TcpStream::connect(addr).await?) }).await.unwrap();
+        let waiting = {
+            let (pool, hot) = (pool.clone(), hot.clone());
+            tokio::spawn(async move {
+                pool.acquire(&hot, || async { Ok(TcpStream::connect(addr).await?) }).await.map(drop)
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(open);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("quota not returned when the connection closed")
+            .unwrap()
+            .unwrap();
     }
 }
 
@@ -242,4 +2189,5 @@ tracing-subscriber = { version = "0.3", features = ["env-filter"] }
 anyhow = "1.0"
 serde = { version = "1.0", features = ["derive"] }
 async-trait = "0.1"
+thiserror = "1.0"
 */
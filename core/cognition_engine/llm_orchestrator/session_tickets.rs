@@ -0,0 +1,277 @@
+// session_tickets.rs - Rotating TLS Session Ticket Keys
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+use rustls::{server::ProducesTickets, Ticketer};
+use tokio::{signal::unix::{signal, SignalKind}, task::JoinHandle};
+use tracing::{error, info};
+
+/// Issues and accepts TLS 1.3 session tickets under rotating keys
+///
+/// Resumed handshakes authenticate with the ticket instead of the
+/// certificate, saving its transmission and signature. They use the PSK-DHE
+/// mode, the only one rustls offers, so each still runs a fresh key
+/// exchange, KEM included: resumption keeps sessions forward secret but
+/// does not skip the post-quantum key share. Tickets issued under the
+/// previous key stay valid for one more rotation; older ones force a full
+/// handshake.
+pub struct SessionTicketer {
+    keys: RwLock<TicketKeys>,
+    lifetime: Duration,
+    resumptions: AtomicU64,
+}
+
+struct TicketKeys {
+    current: Arc<dyn ProducesTickets>,
+    previous: Option<Arc<dyn ProducesTickets>>,
+}
+
+impl SessionTicketer {
+    /// Ticketer whose keys are meant to be rotated every `rotation`
+    pub fn new(rotation: Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            keys: RwLock::new(TicketKeys { current: fresh_key()?, previous: None }),
+            lifetime: rotation,
+            resumptions: AtomicU64::new(0),
+        })
+    }
+
+    /// Start issuing under a new key, retiring the one before the current
+    pub fn rotate(&self) {
+        match fresh_key() {
+            Ok(key) => {
+                let mut keys = self.keys.write().unwrap_or_else(|e| e.into_inner());
+                keys.previous = Some(std::mem::replace(&mut keys.current, key));
+                info!("Rotated TLS session ticket key");
+            }
+            Err(e) => error!(error = %e, "TLS session ticket key rotation failed; keeping current key"),
+        }
+    }
+
+    /// Tickets accepted for resumption so far
+    pub fn resumptions(&self) -> u64 {
+        self.resumptions.load(Ordering::Relaxed)
+    }
+
+    /// Rotate every rotation interval and whenever the process receives
+    /// SIGHUP, first calling `on_hangup` to reload the certificates
+    pub fn spawn_rotation(self: &Arc<Self>, on_hangup: impl Fn() + Send + 'static) -> JoinHandle<()> {
+        let ticketer = self.clone();
+        tokio::spawn(async move {
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => Some(hangup),
+                Err(e) => {
+                    error!(error = %e, "SIGHUP handler unavailable; rotating ticket keys on schedule only");
+                    None
+                }
+            };
+            let mut interval = tokio::time::interval(ticketer.lifetime);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    Some(()) = async { hangup.as_mut()?.recv().await } => on_hangup(),
+                }
+                ticketer.rotate();
+            }
+        })
+    }
+
+    fn keys(&self) -> std::sync::RwLockReadGuard<'_, TicketKeys> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn fresh_key() -> anyhow::Result<Arc<dyn ProducesTickets>> {
+    Ticketer::new().map_err(|e| anyhow::anyhow!("ticket key generation failed: {:?}", e))
+}
+
+impl ProducesTickets for SessionTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.lifetime.as_secs().try_into().unwrap_or(u32::MAX)
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        self.keys().current.encrypt(plain)
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        let keys = self.keys();
+        let plain = keys.current.decrypt(cipher)
+            .or_else(|| keys.previous.as_ref()?.decrypt(cipher))?;
+        self.resumptions.fetch_add(1, Ordering::Relaxed);
+        Some(plain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        pin::Pin,
+        sync::Mutex,
+        task::{Context, Poll},
+        time::SystemTime,
+    };
+    use rustls::{
+        client::{ServerCertVerified, ServerCertVerifier},
+        Certificate, ClientConfig, ServerConfig, ServerName,
+    };
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+    use crate::crypto::quantum_safe::kyber_tls;
+
+    const PRE_SHARED_KEY: u16 = 41;
+    const KEY_SHARE: u16 = 51;
+
+    /// Trusts whatever certificate the router's own configuration serves
+    struct AcceptAnyCertificate;
+
+    impl ServerCertVerifier for AcceptAnyCertificate {
+        fn verify_server_cert(
+            &self,
+            _: &Certificate,
+            _: &[Certificate],
+            _: &ServerName,
+            _: &mut dyn Iterator<Item = &[u8]>,
+            _: &[u8],
+            _: SystemTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+    }
+
+    /// Server side of a connection, keeping a copy of everything it sends
+    struct Recorded {
+        stream: TcpStream,
+        sent: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl AsyncRead for Recorded {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Recorded {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            let this = self.get_mut();
+            let written = Pin::new(&mut this.stream).poll_write(cx, buf);
+            if let Poll::Ready(Ok(n)) = written {
+                this.sent.lock().unwrap().extend_from_slice(&buf[..n]);
+            }
+            written
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+        }
+    }
+
+    /// Extension types of the ServerHello opening the server's first flight
+    fn server_hello_extensions(flight: &[u8]) -> Vec<u16> {
+        let u16_at = |at: usize| u16::from_be_bytes([flight[at], flight[at + 1]]);
+        // Record and handshake headers, legacy version and random
+        let mut at = 5 + 4 + 2 + 32;
+        // Session id, then cipher suite and compression method
+        at += 1 + flight[at] as usize + 3;
+        let end = at + 2 + u16_at(at) as usize;
+        at += 2;
+        let mut types = Vec::new();
+        while at < end {
+            types.push(u16_at(at));
+            at += 4 + u16_at(at + 2) as usize;
+        }
+        types
+    }
+
+    /// Complete a handshake and read one byte, which also delivers the
+    /// server's tickets to the client's session cache; returns the
+    /// extensions of the server's hello
+    async fn connect(server: Arc<ServerConfig>, client: Arc<ClientConfig>) -> Vec<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let server_side = tokio::spawn({
+            let sent = sent.clone();
+            async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut tls = TlsAcceptor::from(server).accept(Recorded { stream, sent }).await.unwrap();
+                tls.write_all(b"k").await.unwrap();
+                tls.flush().await.unwrap();
+                tls
+            }
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut tls = TlsConnector::from(client)
+            .connect(ServerName::try_from("router.nuzon.ai").unwrap(), stream)
+            .await
+            .unwrap();
+        let mut byte = [0; 1];
+        tls.read_exact(&mut byte).await.unwrap();
+        drop(server_side.await.unwrap());
+        let sent = sent.lock().unwrap();
+        server_hello_extensions(&sent)
+    }
+
+    #[tokio::test]
+    async fn test_resumption_skips_the_certificate_but_not_the_kem() {
+        let ticketer = Arc::new(SessionTicketer::new(Duration::from_secs(3600)).unwrap());
+        let mut server = kyber_tls::configure_server().unwrap();
+        server.ticketer = ticketer.clone();
+        // Only tickets may resume, not the server-side session cache
+        server.session_storage = Arc::new(rustls::server::NoServerSessionStorage {});
+        let server = Arc::new(server);
+        let client = Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+                .with_no_client_auth(),
+        );
+
+        let mut kem_operations = 0;
+        let mut handshake = |hello: Vec<u16>| {
+            kem_operations += usize::from(hello.contains(&KEY_SHARE));
+            hello.contains(&PRE_SHARED_KEY)
+        };
+
+        assert!(!handshake(connect(server.clone(), client.clone()).await));
+        assert_eq!(ticketer.resumptions(), 0);
+
+        // The returning client presents a ticket in place of the certificate
+        // exchange, yet the server still answers with a fresh key share
+        assert!(handshake(connect(server.clone(), client.clone()).await));
+        assert_eq!(ticketer.resumptions(), 1);
+
+        // A ticket from the previous key still resumes after one rotation
+        ticketer.rotate();
+        assert!(handshake(connect(server.clone(), client.clone()).await));
+        assert_eq!(ticketer.resumptions(), 2);
+
+        // Two rotations retire every ticket the client holds
+        ticketer.rotate();
+        ticketer.rotate();
+        assert!(!handshake(connect(server, client).await));
+        assert_eq!(ticketer.resumptions(), 2);
+
+        // Every handshake, resumed or not, paid for its own key exchange
+        assert_eq!(kem_operations, 4);
+    }
+}
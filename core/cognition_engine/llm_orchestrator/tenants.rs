@@ -0,0 +1,155 @@
+// tenants.rs - SNI-Based Tenant Certificates and Endpoints
+use std::{collections::HashMap, sync::Arc};
+use rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use tracing::warn;
+
+/// One tenant served by a multi-tenant gateway
+pub struct Tenant {
+    pub id: String,
+    /// Backends this tenant's connections may be routed to
+    pub endpoints: Vec<String>,
+    /// Certificate presented to clients that reach the tenant by SNI
+    pub cert: Arc<CertifiedKey>,
+}
+
+/// Tenants indexed by SNI hostname and by id
+///
+/// Installed as the TLS certificate resolver, it presents each tenant's own
+/// certificate and aborts handshakes whose SNI names no known tenant.
+#[derive(Default)]
+pub struct TenantDirectory {
+    by_hostname: HashMap<String, Arc<Tenant>>,
+    by_id: HashMap<String, Arc<Tenant>>,
+}
+
+impl TenantDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `tenant` for each of `hostnames`, replacing earlier claims on them
+    pub fn insert<'a>(&mut self, tenant: Tenant, hostnames: impl IntoIterator<Item = &'a str>) {
+        let tenant = Arc::new(tenant);
+        for hostname in hostnames {
+            self.by_hostname.insert(hostname.to_ascii_lowercase(), tenant.clone());
+        }
+        self.by_id.insert(tenant.id.clone(), tenant);
+    }
+
+    /// Tenant reached through an SNI hostname
+    pub fn for_hostname(&self, hostname: &str) -> Option<&Arc<Tenant>> {
+        self.by_hostname.get(&hostname.to_ascii_lowercase())
+    }
+
+    pub fn get(&self, tenant_id: &str) -> Option<&Arc<Tenant>> {
+        self.by_id.get(tenant_id)
+    }
+}
+
+impl ResolvesServerCert for TenantDirectory {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let Some(sni) = client_hello.server_name() else {
+            warn!("Rejecting TLS handshake without SNI");
+            return None;
+        };
+        match self.for_hostname(sni) {
+            Some(tenant) => Some(tenant.cert.clone()),
+            None => {
+                warn!(sni, "Rejecting TLS handshake for unknown SNI");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+    fn self_signed(host: &str) -> (Certificate, PrivateKey) {
+        let cert = rcgen::generate_simple_self_signed(vec![host.to_string()]).unwrap();
+        (Certificate(cert.serialize_der().unwrap()), PrivateKey(cert.serialize_private_key_der()))
+    }
+
+    fn tenant(id: &str, host: &str, endpoints: &[&str]) -> (Tenant, Certificate) {
+        let (cert, key) = self_signed(host);
+        let signing_key = rustls::sign::any_supported_type(&key).unwrap();
+        let tenant = Tenant {
+            id: id.into(),
+            endpoints: endpoints.iter().map(|e| e.to_string()).collect(),
+            cert: Arc::new(CertifiedKey::new(vec![cert.clone()], signing_key)),
+        };
+        (tenant, cert)
+    }
+
+    /// Handshake as `sni`; returns the server's certificate and the tenant id
+    /// the server side resolved, or `None` if the handshake was refused
+    async fn connect(
+        server: Arc<ServerConfig>,
+        directory: Arc<TenantDirectory>,
+        roots: RootCertStore,
+        sni: &'static str,
+    ) -> Option<(Certificate, String)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_side = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let tls = TlsAcceptor::from(server).accept(stream).await.ok()?;
+            let server_name = tls.get_ref().1.server_name()?.to_string();
+            directory.for_hostname(&server_name).map(|t| t.id.clone())
+        });
+
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let tls = TlsConnector::from(Arc::new(client_config))
+            .connect(ServerName::try_from(sni).unwrap(), stream)
+            .await
+            .ok();
+        let tenant_id = server_side.await.unwrap();
+        let cert = tls?.get_ref().1.peer_certificates()?.first()?.clone();
+        Some((cert, tenant_id?))
+    }
+
+    #[tokio::test]
+    async fn test_each_sni_gets_its_tenants_cert_and_endpoints() {
+        let (acme, acme_cert) = tenant("acme", "acme.nuzon.ai", &["acme-llm:443"]);
+        let (globex, globex_cert) = tenant("globex", "globex.nuzon.ai", &["globex-a:443", "globex-b:443"]);
+
+        let mut directory = TenantDirectory::new();
+        directory.insert(acme, ["acme.nuzon.ai"]);
+        directory.insert(globex, ["globex.nuzon.ai", "GLOBEX.example.com"]);
+        let directory = Arc::new(directory);
+        let server = Arc::new(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_cert_resolver(directory.clone()),
+        );
+
+        let mut roots = RootCertStore::empty();
+        for cert in [&acme_cert, &globex_cert] {
+            roots.add(cert).unwrap();
+        }
+
+        let (cert, tenant_id) = connect(server.clone(), directory.clone(), roots.clone(), "acme.nuzon.ai").await.unwrap();
+        assert_eq!((cert, tenant_id.as_str()), (acme_cert, "acme"));
+        assert_eq!(directory.get("acme").unwrap().endpoints, ["acme-llm:443"]);
+
+        let (cert, tenant_id) = connect(server.clone(), directory.clone(), roots.clone(), "globex.nuzon.ai").await.unwrap();
+        assert_eq!((cert, tenant_id.as_str()), (globex_cert, "globex"));
+        assert_eq!(directory.get("globex").unwrap().endpoints, ["globex-a:443", "globex-b:443"]);
+        assert_eq!(directory.for_hostname("globex.example.com").unwrap().id, "globex");
+
+        // A hostname no tenant claims never completes the handshake
+        assert!(connect(server, directory, roots, "unknown.nuzon.ai").await.is_none());
+    }
+}
@@ -3,13 +3,17 @@
 #![warn(missing_docs)]
 #![feature(specialization)]
 
-use rand::{Rng, thread_rng};
+use rand::{rngs::OsRng, Rng};
 use anyhow::{Result, Context};
+use nuzon_core::crypto::EntropySource;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 
 const ENTANGLED_PAIRS: usize = 1024;
-const BELL_THRESHOLD: f64 = 2.0f64.sqrt();
+/// Largest |S| reachable by any local hidden-variable model
+const CLASSICAL_BOUND: f64 = 2.0;
+/// Tsirelson's bound: largest |S| quantum mechanics allows, 2√2
+const TSIRELSON_BOUND: f64 = 2.0 * std::f64::consts::SQRT_2;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum Basis {
@@ -18,6 +22,107 @@ enum Basis {
     Circular,
 }
 
+impl Basis {
+    /// Alice's analyzer orientation in degrees
+    fn alice_angle(self) -> f64 {
+        match self {
+            Basis::Rectilinear => 0.0,
+            Basis::Diagonal => 45.0,
+            Basis::Circular => 90.0,
+        }
+    }
+
+    /// Bob's analyzers sit 45° from Alice's, as in the original E91 layout
+    fn bob_angle(self) -> f64 {
+        self.alice_angle() + 45.0
+    }
+}
+
+/// The two settings per party entering the CHSH sum
+#[derive(Debug, Clone, Copy)]
+struct ChshSettings {
+    a: Basis,
+    a_prime: Basis,
+    b: Basis,
+    b_prime: Basis,
+}
+
+impl Default for ChshSettings {
+    /// a = 0°, a' = 90°, b = 45°, b' = 135°: maximal violation for a singlet
+    fn default() -> Self {
+        Self {
+            a: Basis::Rectilinear,
+            a_prime: Basis::Circular,
+            b: Basis::Rectilinear,
+            b_prime: Basis::Circular,
+        }
+    }
+}
+
+/// CHSH value with its four correlators
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChshEstimate {
+    /// S = E(a,b) − E(a,b') + E(a',b) + E(a',b')
+    s: f64,
+    e_ab: f64,
+    e_ab_prime: f64,
+    e_a_prime_b: f64,
+    e_a_prime_b_prime: f64,
+    /// Pairs behind each correlator, in the order above
+    samples: [usize; 4],
+}
+
+impl ChshEstimate {
+    /// Bin `(alice basis, bob basis, alice result, bob result)` measurements
+    /// into the four setting combinations; other combinations are ignored
+    fn from_measurements(
+        settings: ChshSettings,
+        measurements: impl IntoIterator<Item = (Basis, Basis, i8, i8)>,
+    ) -> Result<Self> {
+        let mut sums = [0i64; 4];
+        let mut samples = [0usize; 4];
+
+        for (a_basis, b_basis, a_result, b_result) in measurements {
+            let alice = if a_basis == settings.a {
+                0
+            } else if a_basis == settings.a_prime {
+                2
+            } else {
+                continue;
+            };
+            let bob = if b_basis == settings.b {
+                0
+            } else if b_basis == settings.b_prime {
+                1
+            } else {
+                continue;
+            };
+            sums[alice + bob] += (a_result * b_result) as i64;
+            samples[alice + bob] += 1;
+        }
+
+        if let Some(empty) = samples.iter().position(|n| *n == 0) {
+            anyhow::bail!("No measurements for CHSH correlator {}", empty);
+        }
+        let e = |i: usize| sums[i] as f64 / samples[i] as f64;
+        let (e_ab, e_ab_prime, e_a_prime_b, e_a_prime_b_prime) = (e(0), e(1), e(2), e(3));
+
+        Ok(Self {
+            s: e_ab - e_ab_prime + e_a_prime_b + e_a_prime_b_prime,
+            e_ab,
+            e_ab_prime,
+            e_a_prime_b,
+            e_a_prime_b_prime,
+            samples,
+        })
+    }
+
+    /// Three standard errors of S, bounding each correlator's variance by 1/n
+    fn tolerance(&self) -> f64 {
+        3.0 * self.samples.iter().map(|n| 1.0 / *n as f64).sum::<f64>().sqrt()
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 struct PhotonPair {
     alice_angle: f64,
@@ -26,15 +131,31 @@ struct PhotonPair {
     bob_result: i8,
 }
 
-struct E91Channel {
+struct E91Channel<R = OsRng> {
     entangled_pairs: Vec<PhotonPair>,
     basis_choices: HashMap<usize, (Basis, Basis)>,
+    rng: R,
 }
 
 impl E91Channel {
+    fn new() -> Self {
+        Self::with_entropy(OsRng)
+    }
+}
+
+impl<R: EntropySource> E91Channel<R> {
+    /// Channel whose pair generation and measurements draw from `rng`
+    fn with_entropy(rng: R) -> Self {
+        Self {
+            entangled_pairs: Vec::new(),
+            basis_choices: HashMap::new(),
+            rng,
+        }
+    }
+
     fn generate_entangled_pairs(&mut self) -> Result<()> {
-        let mut rng = thread_rng();
-        
+        let rng = &mut self.rng;
+
         self.entangled_pairs = (0..ENTANGLED_PAIRS)
             .map(|_| {
                 let base_angle: f64 = rng.gen_range(0.0..360.0);
@@ -51,17 +172,12 @@ impl E91Channel {
     }
 
     fn measure_pair(&mut self, index: usize, alice_basis: Basis, bob_basis: Basis) -> Result<()> {
-        let pair = self.entangled_pairs
-            .get_mut(index)
-            .context("Invalid photon pair index")?;
-
-        let (a_result, b_result) = self.quantum_measurement(
-            pair.alice_angle, 
-            pair.bob_angle,
-            alice_basis,
-            bob_basis,
-        )?;
+        if index >= self.entangled_pairs.len() {
+            anyhow::bail!("Invalid photon pair index");
+        }
+        let (a_result, b_result) = self.quantum_measurement(alice_basis, bob_basis)?;
 
+        let pair = &mut self.entangled_pairs[index];
         pair.alice_result = a_result;
         pair.bob_result = b_result;
         self.basis_choices.insert(index, (alice_basis, bob_basis));
@@ -69,63 +185,42 @@ impl E91Channel {
         Ok(())
     }
 
-    fn quantum_measurement(
-        &self,
-        a_angle: f64,
-        b_angle: f64,
-        a_basis: Basis,
-        b_basis: Basis,
-    ) -> Result<(i8, i8)> {
-        let angle_diff = |angle1: f64, angle2: f64| -> f64 {
-            let diff = (angle1 - angle2).abs();
-            diff.min(360.0 - diff)
-        };
-
-        let (a, b) = match (a_basis, b_basis) {
-            (Basis::Rectilinear, Basis::Diagonal) => {
-                let theta = angle_diff(a_angle, 0.0);
-                let phi = angle_diff(b_angle, 45.0);
-                quantum_probability(theta, phi)
-            }
-            (Basis::Diagonal, Basis::Circular) => {
-                let theta = angle_diff(a_angle, 45.0);
-                let phi = angle_diff(b_angle, 90.0);
-                quantum_probability(theta, phi)
-            }
-            (Basis::Circular, Basis::Rectilinear) => {
-                let theta = angle_diff(a_angle, 90.0);
-                let phi = angle_diff(b_angle, 0.0);
-                quantum_probability(theta, phi)
-            }
-            _ => return Ok((0, 0)), // Basis mismatch
-        };
+    /// Singlet statistics: outcomes anticorrelate with probability
+    /// cos²(Δ/2) for analyzer separation Δ, so E(a,b) = −cos(a − b)
+    fn quantum_measurement(&mut self, a_basis: Basis, b_basis: Basis) -> Result<(i8, i8)> {
+        let separation = (a_basis.alice_angle() - b_basis.bob_angle()).to_radians();
+        let anticorrelated = (separation / 2.0).cos().powi(2);
 
+        let a: i8 = if self.rng.gen_bool(0.5) { 1 } else { -1 };
+        let b = if self.rng.gen_bool(anticorrelated.clamp(0.0, 1.0)) { -a } else { a };
         Ok((a, b))
     }
 
-    fn verify_bell_inequality(&self) -> Result<f64> {
-        let mut chsh = 0.0f64;
-        let mut valid_pairs = 0;
-
-        for (index, (a_basis, b_basis)) in &self.basis_choices {
-            let pair = self.entangled_pairs
-                .get(*index)
-                .context("Missing measurement data")?;
-
-            if *a_basis != Basis::Rectilinear || *b_basis != Basis::Diagonal {
-                continue;
-            }
+    /// Estimate S from the recorded measurements and check it witnesses
+    /// entanglement
+    ///
+    /// |S| at or below the classical bound means the correlations could be
+    /// local (eavesdropping or decoherence). |S| beyond Tsirelson's bound by
+    /// more than sampling error cannot come from a quantum source at all.
+    fn verify_bell_inequality(&self) -> Result<ChshEstimate> {
+        let measurements = self.basis_choices.iter()
+            .map(|(index, (a_basis, b_basis))| {
+                let pair = self.entangled_pairs
+                    .get(*index)
+                    .context("Missing measurement data")?;
+                Ok((*a_basis, *b_basis, pair.alice_result, pair.bob_result))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-            chsh += (pair.alice_result * pair.bob_result) as f64;
-            valid_pairs += 1;
+        let estimate = ChshEstimate::from_measurements(ChshSettings::default(), measurements)?;
+        let magnitude = estimate.s.abs();
+        if magnitude <= CLASSICAL_BOUND {
+            return Err(anyhow::anyhow!("Quantum entanglement violation: S = {}", estimate.s));
         }
-
-        let bell_value = chsh / (valid_pairs as f64);
-        if bell_value.abs() > BELL_THRESHOLD {
-            Ok(bell_value)
-        } else {
-            Err(anyhow::anyhow!("Quantum entanglement violation: {}", bell_value))
+        if magnitude > TSIRELSON_BOUND + estimate.tolerance() {
+            return Err(anyhow::anyhow!("CHSH value exceeds Tsirelson bound: S = {}", estimate.s));
         }
+        Ok(estimate)
     }
 
     fn generate_key(&self) -> Result<Vec<u8>> {
@@ -137,7 +232,8 @@ impl E91Channel {
             let bases = self.basis_choices.get(&index)
                 .context("Missing basis choice")?;
 
-            if bases.0 == Basis::Rectilinear && bases.1 == Basis::Rectilinear {
+            // Aligned analyzers give perfectly anticorrelated, usable bits
+            if bases.0.alice_angle() == bases.1.bob_angle() {
                 buffer = (buffer << 1) | (pair.alice_result as u8 & 1);
                 bit_count += 1;
 
@@ -153,54 +249,111 @@ impl E91Channel {
     }
 }
 
-fn quantum_probability(theta: f64, phi: f64) -> (i8, i8) {
-    let angle = (theta - phi).to_radians();
-    let prob = (angle / 2.0).cos().powi(2);
-    
-    let mut rng = thread_rng();
-    let result = if rng.gen_bool(prob.into()) { 1 } else { -1 };
-    
-    (result, -result)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn random_basis(rng: &mut impl Rng) -> Basis {
+        match rng.gen_range(0..3) {
+            0 => Basis::Rectilinear,
+            1 => Basis::Diagonal,
+            _ => Basis::Circular,
+        }
+    }
+
     #[test]
     fn full_protocol_cycle() -> Result<()> {
-        let mut channel = E91Channel {
-            entangled_pairs: Vec::new(),
-            basis_choices: HashMap::new(),
-        };
+        let mut channel = E91Channel::new();
         
         channel.generate_entangled_pairs()?;
         
         // Simulate measurements
-        let mut rng = thread_rng();
+        let mut rng = rand::thread_rng();
         for i in 0..ENTANGLED_PAIRS {
-            let a_basis = match rng.gen_range(0..3) {
-                0 => Basis::Rectilinear,
-                1 => Basis::Diagonal,
-                _ => Basis::Circular,
-            };
-            
-            let b_basis = match rng.gen_range(0..3) {
-                0 => Basis::Rectilinear,
-                1 => Basis::Diagonal,
-                _ => Basis::Circular,
-            };
-            
+            let (a_basis, b_basis) = (random_basis(&mut rng), random_basis(&mut rng));
             channel.measure_pair(i, a_basis, b_basis)?;
         }
         
-        let bell_value = channel.verify_bell_inequality()?;
-        assert!(bell_value.abs() > BELL_THRESHOLD, 
-            "Bell inequality violation failed: {}", bell_value);
+        let estimate = channel.verify_bell_inequality()?;
+        assert!(estimate.s.abs() > CLASSICAL_BOUND, 
+            "Bell inequality violation failed: {}", estimate.s);
             
         let key = channel.generate_key()?;
         assert!(!key.is_empty(), "Key generation failed");
         
         Ok(())
     }
+
+    /// `n` pairs per setting combination whose products average to exactly
+    /// `correlation(alice, bob)`, up to rounding
+    fn synthetic(n: usize, correlation: impl Fn(Basis, Basis) -> f64) -> Vec<(Basis, Basis, i8, i8)> {
+        let settings = ChshSettings::default();
+        let mut measurements = Vec::new();
+        for a in [settings.a, settings.a_prime] {
+            for b in [settings.b, settings.b_prime] {
+                let same = ((1.0 + correlation(a, b)) / 2.0 * n as f64).round() as usize;
+                measurements.extend((0..n).map(|i| (a, b, 1, if i < same { 1 } else { -1 })));
+            }
+        }
+        measurements
+    }
+
+    #[test]
+    fn idealized_singlet_reaches_tsirelson_bound() -> Result<()> {
+        let singlet = |a: Basis, b: Basis| -(a.alice_angle() - b.bob_angle()).to_radians().cos();
+        let estimate = ChshEstimate::from_measurements(ChshSettings::default(), synthetic(100_000, singlet))?;
+
+        assert!((estimate.e_ab + std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-4);
+        assert!((estimate.e_ab_prime - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-4);
+        assert!((estimate.s.abs() - TSIRELSON_BOUND).abs() < 1e-3, "S = {}", estimate.s);
+        assert_eq!(estimate.samples, [100_000; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn classical_correlations_respect_chsh_bound() -> Result<()> {
+        // Independent outcomes
+        let uncorrelated = ChshEstimate::from_measurements(ChshSettings::default(), synthetic(10_000, |_, _| 0.0))?;
+        assert!(uncorrelated.s.abs() <= CLASSICAL_BOUND);
+        assert!(uncorrelated.s.abs() < 1e-9);
+
+        // Best deterministic local strategy saturates, but never exceeds, the bound
+        let local = ChshEstimate::from_measurements(ChshSettings::default(), synthetic(10_000, |_, _| 1.0))?;
+        assert!((local.s - CLASSICAL_BOUND).abs() < 1e-9);
+
+        let mut channel = E91Channel::new();
+        channel.entangled_pairs = (0..4).map(|_| PhotonPair {
+            alice_angle: 0.0, bob_angle: 45.0, alice_result: 1, bob_result: 1,
+        }).collect();
+        let settings = ChshSettings::default();
+        for (i, bases) in [
+            (settings.a, settings.b), (settings.a, settings.b_prime),
+            (settings.a_prime, settings.b), (settings.a_prime, settings.b_prime),
+        ].into_iter().enumerate() {
+            channel.basis_choices.insert(i, bases);
+        }
+        assert!(channel.verify_bell_inequality().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn injected_entropy_reproduces_key() -> Result<()> {
+        let run = |seed| -> Result<Vec<u8>> {
+            let mut channel = E91Channel::with_entropy(StdRng::seed_from_u64(seed));
+            channel.generate_entangled_pairs()?;
+            let mut bases = StdRng::seed_from_u64(seed ^ 0xe91);
+            for i in 0..ENTANGLED_PAIRS {
+                channel.measure_pair(i, random_basis(&mut bases), random_basis(&mut bases))?;
+            }
+            channel.generate_key()
+        };
+
+        let key = run(42)?;
+        assert!(!key.is_empty());
+        assert_eq!(key, run(42)?);
+        assert_ne!(key, run(43)?);
+        Ok(())
+    }
 }
@@ -0,0 +1,57 @@
+// admin.rs - Operator Authentication for Admin Services
+use sha2::{Digest, Sha256};
+
+use crate::EnterpriseError;
+
+/// Operators allowed to call an admin service
+///
+/// Callers present `authorization: Bearer <token>`; only the SHA-256 of each
+/// accepted token is held. With no tokens allowed every call is refused.
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuth {
+    token_digests: Vec<[u8; 32]>,
+}
+
+impl AdminAuth {
+    /// Accept calls presenting `token`
+    pub fn allow_token(mut self, token: impl AsRef<[u8]>) -> Self {
+        self.token_digests.push(Sha256::digest(token.as_ref()).into());
+        self
+    }
+
+    /// Accept or refuse a call by the value of its `authorization` header
+    ///
+    /// A refusal is an `AuthError` whose reason may be returned to the caller.
+    pub fn check(&self, authorization: Option<&str>) -> Result<(), EnterpriseError> {
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| EnterpriseError::AuthError("admin bearer token required".into()))?;
+        let presented: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        // Compare every digest in full so timing does not reveal a near match
+        let accepted = self.token_digests.iter().fold(false, |accepted, digest| {
+            accepted | (digest.iter().zip(&presented).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0)
+        });
+        if accepted {
+            Ok(())
+        } else {
+            Err(EnterpriseError::AuthError("admin token not accepted".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_allowed_bearer_tokens_are_accepted() {
+        let auth = AdminAuth::default().allow_token("first").allow_token("second");
+        assert!(auth.check(Some("Bearer first")).is_ok());
+        assert!(auth.check(Some("Bearer second")).is_ok());
+
+        for refused in [None, Some("first"), Some("Bearer firs"), Some("Basic second")] {
+            assert!(matches!(auth.check(refused), Err(EnterpriseError::AuthError(_))), "{:?}", refused);
+        }
+        assert!(AdminAuth::default().check(Some("Bearer first")).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+// admission.rs - Single-Point Quota Admission for Agent Messages
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{AgentConfig, ConcurrencyMode};
+use crate::EnterpriseError;
+
+/// Width of the message- and byte-rate window
+const RATE_WINDOW_MILLIS: u128 = 1_000;
+
+/// Limit an admission decision can be refused on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quota {
+    Concurrency,
+    MessageRate,
+    InFlightBytes,
+    NetworkRate,
+}
+
+impl Quota {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Concurrency => "concurrency",
+            Self::MessageRate => "message_rate",
+            Self::InFlightBytes => "in_flight_bytes",
+            Self::NetworkRate => "network_rate",
+        }
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Limits checked before a message is processed; `None` leaves one unbounded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionQuotas {
+    /// Bytes of in-flight messages held at once
+    pub max_in_flight_bytes: Option<u64>,
+    /// Inbound message bytes admitted per second
+    pub network_bytes_per_sec: Option<u64>,
+    pub max_concurrent: Option<usize>,
+    pub max_messages_per_sec: Option<u32>,
+}
+
+impl From<&AgentConfig> for AdmissionQuotas {
+    /// In `ConcurrencyMode::Wait` excess messages queue at the concurrency
+    /// gate rather than being refused, so concurrency is not a quota here
+    fn from(config: &AgentConfig) -> Self {
+        Self {
+            max_in_flight_bytes: config.max_in_flight_bytes,
+            network_bytes_per_sec: config.network_bytes_per_sec,
+            max_concurrent: (config.concurrency_mode == ConcurrencyMode::Reject)
+                .then_some(config.max_concurrent_messages),
+            max_messages_per_sec: config.max_messages_per_sec,
+        }
+    }
+}
+
+/// Resource usage at the moment of an admission decision
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageSnapshot {
+    /// Bytes held by messages still being processed
+    pub in_flight_bytes: u64,
+    pub in_flight: usize,
+    /// Messages admitted within the last second
+    pub recent_messages: u32,
+    /// Bytes of the messages admitted within the last second
+    pub recent_bytes: u64,
+}
+
+/// Admission history a migrated agent takes with it
+///
+/// Reservations of in-flight messages stay with the host still processing
+/// them, so only the rate window moves.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CarriedUsage {
+    /// Admissions still inside the rate window: milliseconds since the Unix
+    /// epoch and message size
+    pub admitted: Vec<(u128, u64)>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    in_flight_bytes: u64,
+    in_flight: usize,
+    /// Admission times, in milliseconds since the Unix epoch, and sizes
+    admitted: VecDeque<(u128, u64)>,
+    /// Sum of the sizes in `admitted`
+    recent_bytes: u64,
+}
+
+impl Usage {
+    fn snapshot(&mut self, now_millis: u128) -> UsageSnapshot {
+        while let Some(&(at, size)) = self.admitted.front() {
+            if now_millis.saturating_sub(at) < RATE_WINDOW_MILLIS {
+                break;
+            }
+            self.admitted.pop_front();
+            self.recent_bytes -= size;
+        }
+        UsageSnapshot {
+            in_flight_bytes: self.in_flight_bytes,
+            in_flight: self.in_flight,
+            recent_messages: self.admitted.len() as u32,
+            recent_bytes: self.recent_bytes,
+        }
+    }
+}
+
+/// Admits or refuses messages against every agent quota at once
+///
+/// The check and the reservation happen under one lock, so two concurrent
+/// messages cannot both squeeze under the same remaining headroom.
+#[derive(Debug)]
+pub struct AdmissionController {
+    quotas: AdmissionQuotas,
+    usage: Arc<Mutex<Usage>>,
+}
+
+/// Reservation for one admitted message, released when dropped
+#[derive(Debug)]
+pub struct Admission {
+    usage: Arc<Mutex<Usage>>,
+    size: u64,
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.in_flight_bytes = usage.in_flight_bytes.saturating_sub(self.size);
+        usage.in_flight = usage.in_flight.saturating_sub(1);
+    }
+}
+
+impl AdmissionController {
+    pub fn new(quotas: AdmissionQuotas) -> Self {
+        Self { quotas, usage: Arc::default() }
+    }
+
+    /// Whether a `request_size`-byte message fits within every quota given
+    /// `usage`
+    ///
+    /// Quotas are checked in `Quota` declaration order and the first one
+    /// breached is named in the `EnterpriseError::ResourceLimit` message.
+    pub fn check(&self, usage: &UsageSnapshot, request_size: u64) -> Result<(), EnterpriseError> {
+        let quotas = &self.quotas;
+        if let Some(max) = quotas.max_concurrent.filter(|max| usage.in_flight >= *max) {
+            return Err(breach(Quota::Concurrency, usage.in_flight as u64 + 1, max as u64));
+        }
+        if let Some(max) = quotas.max_messages_per_sec.filter(|max| usage.recent_messages >= *max) {
+            return Err(breach(Quota::MessageRate, usage.recent_messages as u64 + 1, max as u64));
+        }
+        let in_flight_bytes = usage.in_flight_bytes.saturating_add(request_size);
+        if let Some(max) = quotas.max_in_flight_bytes.filter(|max| in_flight_bytes > *max) {
+            return Err(breach(Quota::InFlightBytes, in_flight_bytes, max));
+        }
+        let recent_bytes = usage.recent_bytes.saturating_add(request_size);
+        if let Some(max) = quotas.network_bytes_per_sec.filter(|max| recent_bytes > *max) {
+            return Err(breach(Quota::NetworkRate, recent_bytes, max));
+        }
+        Ok(())
+    }
+
+    /// Check a message against current usage and, if it fits, reserve its
+    /// share of every quota
+    pub fn admit(&self, request_size: u64, now_millis: u128) -> Result<Admission, EnterpriseError> {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        self.check(&usage.snapshot(now_millis), request_size)?;
+        usage.in_flight_bytes += request_size;
+        usage.in_flight += 1;
+        usage.admitted.push_back((now_millis, request_size));
+        usage.recent_bytes += request_size;
+        Ok(Admission { usage: self.usage.clone(), size: request_size })
+    }
+
+    /// Current usage, with the rate window evaluated at `now_millis`
+    pub fn usage(&self, now_millis: u128) -> UsageSnapshot {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).snapshot(now_millis)
+    }
+
+    /// Usage for `with_usage` on another host, with the rate window
+    /// evaluated at `now_millis`
+    pub fn carried_usage(&self, now_millis: u128) -> CarriedUsage {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.snapshot(now_millis);
+        CarriedUsage { admitted: usage.admitted.iter().copied().collect() }
+    }
+
+    /// Resume from usage exported by `carried_usage`
+    pub fn with_usage(self, carried: CarriedUsage) -> Self {
+        {
+            let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+            usage.recent_bytes = carried.admitted.iter().map(|(_, size)| size).sum();
+            usage.admitted = carried.admitted.into();
+        }
+        self
+    }
+}
+
+fn breach(quota: Quota, requested: u64, limit: u64) -> EnterpriseError {
+    EnterpriseError::ResourceLimit(format!("{} quota: {} would exceed limit {}", quota, requested, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> AdmissionController {
+        AdmissionController::new(AdmissionQuotas {
+            max_in_flight_bytes: Some(1_000),
+            network_bytes_per_sec: Some(1_500),
+            max_concurrent: Some(4),
+            max_messages_per_sec: Some(10),
+        })
+    }
+
+    fn assert_breaches(result: Result<(), EnterpriseError>, quota: Quota) {
+        match result {
+            Err(EnterpriseError::ResourceLimit(reason)) => {
+                assert!(reason.starts_with(&format!("{} quota", quota)), "{}", reason)
+            }
+            other => panic!("expected {} breach, got {:?}", quota, other),
+        }
+    }
+
+    #[test]
+    fn test_each_quota_can_bind() {
+        let controller = controller();
+        let idle = UsageSnapshot::default();
+        assert!(controller.check(&idle, 100).is_ok());
+
+        assert_breaches(controller.check(&UsageSnapshot { in_flight: 4, ..idle }, 100), Quota::Concurrency);
+        assert_breaches(controller.check(&UsageSnapshot { recent_messages: 10, ..idle }, 100), Quota::MessageRate);
+        assert_breaches(controller.check(&UsageSnapshot { in_flight_bytes: 950, ..idle }, 100), Quota::InFlightBytes);
+        assert_breaches(controller.check(&UsageSnapshot { recent_bytes: 1_450, ..idle }, 100), Quota::NetworkRate);
+    }
+
+    #[test]
+    fn test_admissions_reserve_and_release_usage() {
+        let controller = controller();
+        let first = controller.admit(600, 0).unwrap();
+        assert_breaches(controller.admit(600, 1).map(drop), Quota::InFlightBytes);
+
+        drop(first);
+        let usage = controller.usage(2);
+        assert_eq!((usage.in_flight_bytes, usage.recent_bytes, usage.in_flight), (0, 600, 0));
+        assert!(controller.admit(600, 3).is_ok());
+    }
+
+    #[test]
+    fn test_network_rate_recovers_as_the_window_slides() {
+        let controller = controller();
+        drop(controller.admit(900, 0).unwrap());
+        assert_breaches(controller.admit(900, 500).map(drop), Quota::NetworkRate);
+
+        // A rate, not a lifetime budget: earlier traffic stops counting
+        for second in 1..10 {
+            drop(controller.admit(900, second * 1_000).unwrap());
+        }
+        assert_eq!(controller.usage(9_500).recent_bytes, 900);
+    }
+
+    #[test]
+    fn test_message_rate_window_slides() {
+        let controller = AdmissionController::new(AdmissionQuotas {
+            max_in_flight_bytes: None,
+            network_bytes_per_sec: None,
+            max_concurrent: None,
+            max_messages_per_sec: Some(2),
+        });
+        controller.admit(1, 0).unwrap();
+        controller.admit(1, 500).unwrap();
+        assert_breaches(controller.admit(1, 999).map(drop), Quota::MessageRate);
+        assert!(controller.admit(1, 1_000).is_ok());
+    }
+}
@@ -0,0 +1,247 @@
+// compliance.rs - Agent Compliance Rule Evaluation
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+
+use serde::{de::IgnoredAny, Deserialize};
+use tracing::warn;
+
+use crate::{codec::{Compression, Envelope, MessageCodec}, crypto::SecureContainer, EnterpriseError};
+
+/// Message fields compliance rules inspect; absent fields take their
+/// defaults, so a message only needs to carry what applies to it
+///
+/// Collecting the names of the remaining fields needs a self-describing
+/// codec, JSON or CBOR.
+#[derive(Debug, Default, Deserialize)]
+pub struct ComplianceHeaders {
+    /// The body carries personal data
+    #[serde(default)]
+    pub contains_pii: bool,
+    /// The data subject consented to this processing
+    #[serde(default)]
+    pub pii_consent: bool,
+    /// Encrypted payload, when the body is sealed
+    #[serde(default)]
+    pub sealed: Option<SecureContainer>,
+    /// Every other field the message carries in the clear, by name
+    #[serde(flatten)]
+    pub fields: HashMap<String, IgnoredAny>,
+}
+
+/// One check a compliance rule identifier stands for
+pub trait ComplianceRule: Send + Sync {
+    /// Identifier listed in `AgentConfig::compliance_rules`
+    fn id(&self) -> &str;
+
+    /// Why `headers` violate the rule, if they do
+    fn check(&self, headers: &ComplianceHeaders) -> Result<(), String>;
+}
+
+/// GDPR: personal data is only processed with consent
+pub struct GdprRule;
+
+impl ComplianceRule for GdprRule {
+    fn id(&self) -> &str {
+        "GDPR"
+    }
+
+    fn check(&self, headers: &ComplianceHeaders) -> Result<(), String> {
+        if headers.contains_pii && !headers.pii_consent {
+            return Err("personal data without recorded consent".into());
+        }
+        Ok(())
+    }
+}
+
+/// HIPAA: health information travels only inside an encryption envelope
+///
+/// The payload must be sealed, and nothing but the compliance headers and
+/// the fields allowed by `with_plaintext_fields` may travel beside it. Any
+/// other field could hold health information, so it is refused rather than
+/// guessed at.
+#[derive(Debug, Clone, Default)]
+pub struct HipaaRule {
+    plaintext_fields: HashSet<String>,
+}
+
+impl HipaaRule {
+    /// Allow fields known to carry no health information, such as routing
+    /// metadata, outside the secure container
+    pub fn with_plaintext_fields<I: Into<String>>(mut self, fields: impl IntoIterator<Item = I>) -> Self {
+        self.plaintext_fields.extend(fields.into_iter().map(Into::into));
+        self
+    }
+}
+
+impl ComplianceRule for HipaaRule {
+    fn id(&self) -> &str {
+        "HIPAA"
+    }
+
+    fn check(&self, headers: &ComplianceHeaders) -> Result<(), String> {
+        if headers.sealed.is_none() {
+            return Err("payload not sealed in a secure container".into());
+        }
+        let mut exposed: Vec<_> = headers.fields.keys()
+            .filter(|field| !self.plaintext_fields.contains(*field))
+            .map(String::as_str)
+            .collect();
+        if !exposed.is_empty() {
+            exposed.sort_unstable();
+            return Err(format!("fields outside the secure container: {}", exposed.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+/// Registry mapping compliance rule identifiers to their checks
+#[derive(Clone)]
+pub struct ComplianceEngine {
+    rules: HashMap<String, Arc<dyn ComplianceRule>>,
+}
+
+impl Default for ComplianceEngine {
+    fn default() -> Self {
+        Self::new().register(GdprRule).register(HipaaRule::default())
+    }
+}
+
+impl ComplianceEngine {
+    /// Engine with no rules; `default` registers the built-in ones
+    pub fn new() -> Self {
+        Self { rules: HashMap::new() }
+    }
+
+    /// Add a rule, replacing any registered under the same identifier
+    pub fn register(mut self, rule: impl ComplianceRule + 'static) -> Self {
+        self.rules.insert(rule.id().to_string(), Arc::new(rule));
+        self
+    }
+
+    /// Run every rule in `required` against an inbound envelope
+    ///
+    /// A violated rule, or one with no registered check, is an
+    /// `AccessViolation` naming it; a message whose fields do not decode is
+    /// a `ProtocolError`.
+    pub fn evaluate(
+        &self,
+        required: &[String],
+        msg: &[u8],
+        accepted: &[MessageCodec],
+        accepted_compression: &[Compression],
+    ) -> Result<(), EnterpriseError> {
+        if required.is_empty() {
+            return Ok(());
+        }
+        let (_, headers): (_, ComplianceHeaders) = Envelope::open(msg, accepted, accepted_compression)?;
+
+        for id in required {
+            let rule = self.rules.get(id).ok_or_else(|| EnterpriseError::AccessViolation {
+                module: "compliance",
+                reason: format!("{}: no check registered", id),
+            })?;
+            if let Err(reason) = rule.check(&headers) {
+                warn!(rule = %id, %reason, "Message violates compliance rule");
+                return Err(EnterpriseError::AccessViolation {
+                    module: "compliance",
+                    reason: format!("{}: {}", id, reason),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KyberKem;
+    use serde_json::json;
+
+    const JSON: &[MessageCodec] = &[MessageCodec::Json];
+
+    fn rules(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn message(body: serde_json::Value) -> Vec<u8> {
+        Envelope::seal(MessageCodec::Json, &body).unwrap()
+    }
+
+    /// Deployment-specific rule refusing personal data outright
+    struct RegionTagged;
+
+    impl ComplianceRule for RegionTagged {
+        fn id(&self) -> &str {
+            "EU-RESIDENCY"
+        }
+
+        fn check(&self, headers: &ComplianceHeaders) -> Result<(), String> {
+            if headers.contains_pii {
+                Err("personal data routed outside the EU".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_violated_rule_is_access_violation_naming_it() {
+        let engine = ComplianceEngine::default();
+        let unconsented = message(json!({ "contains_pii": true, "text": "jane@example.com" }));
+        match engine.evaluate(&rules(&["GDPR"]), &unconsented, JSON, &Compression::ALL) {
+            Err(EnterpriseError::AccessViolation { module, reason }) => {
+                assert_eq!(module, "compliance");
+                assert!(reason.starts_with("GDPR:"), "{}", reason);
+            }
+            other => panic!("expected a GDPR violation, got {:?}", other),
+        }
+
+        let custom = engine.register(RegionTagged);
+        assert!(matches!(
+            custom.evaluate(&rules(&["EU-RESIDENCY"]), &unconsented, JSON, &Compression::ALL),
+            Err(EnterpriseError::AccessViolation { reason, .. }) if reason.starts_with("EU-RESIDENCY:")
+        ));
+        assert!(matches!(
+            custom.evaluate(&rules(&["SOX"]), &unconsented, JSON, &Compression::ALL),
+            Err(EnterpriseError::AccessViolation { reason, .. }) if reason.starts_with("SOX:")
+        ));
+    }
+
+    #[test]
+    fn test_message_satisfying_every_rule_passes() {
+        let (pk, _) = KyberKem::keypair();
+        let sealed = SecureContainer::seal(&pk, b"lab results").unwrap();
+        let msg = message(json!({
+            "contains_pii": true,
+            "pii_consent": true,
+            "sealed": sealed,
+        }));
+        let engine = ComplianceEngine::default().register(RegionTagged);
+        assert!(engine.evaluate(&rules(&["GDPR", "HIPAA"]), &msg, JSON, &Compression::ALL).is_ok());
+
+        // No rules, nothing to decode
+        assert!(engine.evaluate(&[], b"opaque", JSON, &Compression::ALL).is_ok());
+    }
+
+    #[test]
+    fn test_hipaa_refuses_fields_beside_the_sealed_payload() {
+        let (pk, _) = KyberKem::keypair();
+        let sealed = SecureContainer::seal(&pk, b"lab results").unwrap();
+        let leaky = message(json!({ "sealed": sealed, "route": "lab", "diagnosis": "type 2 diabetes" }));
+        match ComplianceEngine::default().evaluate(&rules(&["HIPAA"]), &leaky, JSON, &Compression::ALL) {
+            Err(EnterpriseError::AccessViolation { reason, .. }) => {
+                assert_eq!(reason, "HIPAA: fields outside the secure container: diagnosis, route");
+            }
+            other => panic!("expected a HIPAA violation, got {:?}", other),
+        }
+
+        // Routing metadata may be allowed, but the diagnosis still may not
+        let engine = ComplianceEngine::default().register(HipaaRule::default().with_plaintext_fields(["route"]));
+        assert!(matches!(
+            engine.evaluate(&rules(&["HIPAA"]), &leaky, JSON, &Compression::ALL),
+            Err(EnterpriseError::AccessViolation { reason, .. }) if reason.ends_with(": diagnosis")
+        ));
+        let routed = message(json!({ "sealed": sealed, "route": "lab" }));
+        assert!(engine.evaluate(&rules(&["HIPAA"]), &routed, JSON, &Compression::ALL).is_ok());
+    }
+}
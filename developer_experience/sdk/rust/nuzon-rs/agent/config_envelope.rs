@@ -0,0 +1,161 @@
+// config_envelope.rs - Versioned Agent Configuration Storage
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{AgentConfig, AgentIdentity};
+
+/// Schema version written by this build
+///
+/// 1. Identity timestamps as JSON numbers
+/// 2. Identity timestamps as decimal strings
+pub const CONFIG_SCHEMA_VERSION: u32 = 2;
+
+/// Why a stored envelope could not be read
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("config envelope has no schema_version")]
+    MissingVersion,
+    #[error("config schema version {found} is not supported; this build reads versions 1 to {supported}")]
+    UnsupportedVersion { found: u64, supported: u32 },
+    #[error("config envelope version {version} is malformed: {reason}")]
+    Malformed { version: u32, reason: String },
+}
+
+/// Agent configuration and identity tagged with the schema they were
+/// written under
+///
+/// Reading an envelope upgrades older schemas with `migrate` before the
+/// fields are decoded, and refuses newer ones outright: a newer writer may
+/// rely on fields this build would otherwise drop without notice.
+#[derive(Debug, Serialize)]
+pub struct ConfigEnvelope {
+    pub schema_version: u32,
+    pub config: AgentConfig,
+    pub identity: Option<AgentIdentity>,
+}
+
+impl ConfigEnvelope {
+    /// Envelope at the current schema version
+    pub fn new(config: AgentConfig, identity: Option<AgentIdentity>) -> Self {
+        Self { schema_version: CONFIG_SCHEMA_VERSION, config, identity }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigEnvelope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Current {
+            config: AgentConfig,
+            #[serde(default)]
+            identity: Option<AgentIdentity>,
+        }
+
+        let raw = migrate(Value::deserialize(deserializer)?).map_err(D::Error::custom)?;
+        let Current { config, identity } = serde_json::from_value(raw).map_err(D::Error::custom)?;
+        Ok(Self { schema_version: CONFIG_SCHEMA_VERSION, config, identity })
+    }
+}
+
+/// Upgrade a raw envelope of any supported version to the current schema
+pub fn migrate(mut envelope: Value) -> Result<Value, ConfigError> {
+    let found = envelope.get("schema_version")
+        .ok_or(ConfigError::MissingVersion)?
+        .as_u64()
+        .ok_or(ConfigError::MissingVersion)?;
+    let mut version = match u32::try_from(found) {
+        Ok(version @ 1..=CONFIG_SCHEMA_VERSION) => version,
+        _ => return Err(ConfigError::UnsupportedVersion { found, supported: CONFIG_SCHEMA_VERSION }),
+    };
+
+    while version < CONFIG_SCHEMA_VERSION {
+        match version {
+            1 => timestamps_to_strings(&mut envelope)
+                .map_err(|reason| ConfigError::Malformed { version, reason })?,
+            _ => unreachable!("no migration from schema version {}", version),
+        }
+        version += 1;
+    }
+    envelope["schema_version"] = CONFIG_SCHEMA_VERSION.into();
+    Ok(envelope)
+}
+
+/// 1 to 2: identity timestamps become strings, as JSON numbers are not
+/// portable past 2^53
+fn timestamps_to_strings(envelope: &mut Value) -> Result<(), String> {
+    let Some(identity) = envelope.get_mut("identity").filter(|identity| !identity.is_null()) else {
+        return Ok(());
+    };
+    for field in ["valid_from", "valid_to"] {
+        let Some(value) = identity.get_mut(field) else {
+            continue;
+        };
+        let millis = value.as_u64().ok_or_else(|| format!("identity.{} is not an integer", field))?;
+        *value = millis.to_string().into();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::MessageCodec;
+    use serde_json::json;
+
+    fn v1_envelope() -> Value {
+        json!({
+            "schema_version": 1,
+            "config": {
+                "max_memory": 1024,
+                "cpu_quota": 0.5,
+                "network_budget": 1000000,
+                "compliance_rules": ["GDPR"],
+                "max_concurrent_messages": 8,
+            },
+            "identity": {
+                "id": "6f1d7a3e-2c4b-4c8e-9a51-0b8f2d6e4c11",
+                "generation": 3,
+                "valid_from": 1700000000000u64,
+                "valid_to": 1700003600000u64,
+                "attestation": [1, 2, 3],
+            },
+        })
+    }
+
+    #[test]
+    fn test_v1_envelope_reads_into_current_structs() {
+        let envelope: ConfigEnvelope = serde_json::from_value(v1_envelope()).unwrap();
+        assert_eq!(envelope.schema_version, CONFIG_SCHEMA_VERSION);
+        assert_eq!(envelope.config.compliance_rules, vec!["GDPR".to_string()]);
+        // Fields v1 did not have take their defaults
+        assert_eq!(envelope.config.mailbox_capacity, 256);
+        assert_eq!(envelope.config.codec, MessageCodec::Json);
+
+        let identity = envelope.identity.as_ref().unwrap();
+        assert_eq!((identity.valid_from, identity.valid_to), (1_700_000_000_000, 1_700_003_600_000));
+
+        // Written back at the current version, timestamps as strings
+        let written = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(written["schema_version"], json!(CONFIG_SCHEMA_VERSION));
+        assert_eq!(written["identity"]["valid_from"], json!("1700000000000"));
+        let reread: ConfigEnvelope = serde_json::from_value(written).unwrap();
+        assert_eq!(reread.identity.unwrap().valid_to, u128::from(1_700_003_600_000u64));
+    }
+
+    #[test]
+    fn test_future_and_unversioned_envelopes_rejected() {
+        let mut future = v1_envelope();
+        future["schema_version"] = json!(CONFIG_SCHEMA_VERSION + 1);
+        let error = serde_json::from_value::<ConfigEnvelope>(future.clone()).unwrap_err();
+        assert!(error.to_string().contains("schema version 3 is not supported"), "{}", error);
+        assert!(matches!(migrate(future), Err(ConfigError::UnsupportedVersion { found: 3, .. })));
+
+        let mut unversioned = v1_envelope();
+        unversioned.as_object_mut().unwrap().remove("schema_version");
+        assert!(matches!(migrate(unversioned), Err(ConfigError::MissingVersion)));
+
+        let mut malformed = v1_envelope();
+        malformed["identity"]["valid_to"] = json!("soon");
+        assert!(matches!(migrate(malformed), Err(ConfigError::Malformed { version: 1, .. })));
+    }
+}
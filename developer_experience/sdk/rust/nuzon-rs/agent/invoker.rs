@@ -0,0 +1,59 @@
+// invoker.rs - Agent-to-Capability Invocation Bridge
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::EnterpriseAgent;
+use crate::EnterpriseError;
+
+/// Identity and claims an agent presents when invoking a capability
+#[derive(Debug, Clone)]
+pub struct CallerContext {
+    pub identity: String,
+    pub claims: Vec<String>,
+}
+
+/// Capability execution backend an agent can call into
+///
+/// Implemented by the capability registry, which depends on this crate, so
+/// the agent holds it behind this trait rather than by concrete type.
+#[async_trait]
+pub trait CapabilityInvoker: Send + Sync {
+    async fn invoke(
+        &self,
+        caller: &CallerContext,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, EnterpriseError>;
+}
+
+impl EnterpriseAgent {
+    /// Attach the registry used by `invoke_capability`
+    pub fn with_capabilities(mut self, capabilities: Arc<dyn CapabilityInvoker>) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Invoke a registered capability as this agent
+    ///
+    /// The agent's compliance rules are presented as its claims, so only
+    /// capabilities whose required claims the agent satisfies will run.
+    pub async fn invoke_capability(
+        &self,
+        capability_id: &str,
+        version: &semver::VersionReq,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, EnterpriseError> {
+        let capabilities = self.capabilities.as_ref().ok_or_else(|| EnterpriseError::AccessViolation {
+            module: "agent",
+            reason: "no capability registry attached".into(),
+        })?;
+
+        let caller = CallerContext {
+            identity: self.identity.id.to_string(),
+            claims: self.config.compliance_rules.clone(),
+        };
+        capabilities.invoke(&caller, capability_id, version, params).await
+    }
+}
@@ -0,0 +1,190 @@
+// mailbox.rs - Bounded Back-Pressured Agent Mailbox
+use async_trait::async_trait;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+use super::EnterpriseAgent;
+use crate::EnterpriseError;
+
+/// Owner of the state a mailbox worker processes messages against
+#[async_trait]
+pub trait MailboxHandler: Send + 'static {
+    async fn handle(&mut self, msg: Vec<u8>) -> Result<Vec<u8>, EnterpriseError>;
+}
+
+#[async_trait]
+impl MailboxHandler for EnterpriseAgent {
+    async fn handle(&mut self, msg: Vec<u8>) -> Result<Vec<u8>, EnterpriseError> {
+        self.process_message(msg).await
+    }
+}
+
+struct Envelope {
+    msg: Vec<u8>,
+    reply: oneshot::Sender<Result<Vec<u8>, EnterpriseError>>,
+}
+
+/// Sending half of a bounded queue drained by a single worker task
+///
+/// The worker owns the handler and processes messages one at a time in
+/// the order they were accepted. Once `capacity` messages are queued,
+/// `send` refuses further messages instead of buffering them.
+#[derive(Clone)]
+pub struct Mailbox {
+    tx: mpsc::Sender<Envelope>,
+}
+
+/// Outcome of one message, delivered once the worker has processed it
+pub struct Reply {
+    rx: oneshot::Receiver<Result<Vec<u8>, EnterpriseError>>,
+}
+
+impl Mailbox {
+    /// Start a worker owning `handler` behind a queue of `capacity` messages
+    ///
+    /// The worker exits and hands the handler back once every `Mailbox`
+    /// clone has been dropped and the queue is drained. A mailbox must hold
+    /// at least one message.
+    pub fn spawn<H: MailboxHandler>(mut handler: H, capacity: usize) -> Result<(Self, JoinHandle<H>), EnterpriseError> {
+        if capacity == 0 {
+            return Err(EnterpriseError::ResourceLimit("agent mailbox capacity must be at least 1".into()));
+        }
+        let (tx, mut rx) = mpsc::channel::<Envelope>(capacity);
+        let worker = tokio::spawn(async move {
+            while let Some(envelope) = rx.recv().await {
+                let result = handler.handle(envelope.msg).await;
+                if envelope.reply.send(result).is_err() {
+                    debug!("Mailbox reply dropped by sender");
+                }
+            }
+            handler
+        });
+        Ok((Self { tx }, worker))
+    }
+
+    /// Queue a message without waiting for room
+    ///
+    /// A full mailbox is a `ResourceLimit`, so callers see back-pressure
+    /// immediately; a stopped worker is a `CriticalFailure`.
+    pub fn send(&self, msg: Vec<u8>) -> Result<Reply, EnterpriseError> {
+        let (reply, rx) = oneshot::channel();
+        self.tx.try_send(Envelope { msg, reply }).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => {
+                warn!(capacity = self.tx.max_capacity(), "Agent mailbox full");
+                EnterpriseError::ResourceLimit("agent mailbox full".into())
+            }
+            mpsc::error::TrySendError::Closed(_) => EnterpriseError::CriticalFailure,
+        })?;
+        Ok(Reply { rx })
+    }
+
+    /// Free queue slots
+    pub fn available(&self) -> usize {
+        self.tx.capacity()
+    }
+}
+
+impl Reply {
+    /// Wait for the worker to process the message
+    pub async fn wait(self) -> Result<Vec<u8>, EnterpriseError> {
+        self.rx.await.map_err(|_| EnterpriseError::CriticalFailure)?
+    }
+}
+
+impl EnterpriseAgent {
+    /// Move the agent onto a worker task fed by a mailbox of
+    /// `AgentConfig::mailbox_capacity` messages
+    pub fn spawn_mailbox(self) -> Result<(Mailbox, JoinHandle<Self>), EnterpriseError> {
+        let capacity = self.config.mailbox_capacity;
+        Mailbox::spawn(self, capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Notify;
+
+    /// Records messages in processing order, holding the first until released
+    struct Recorder {
+        seen: Arc<Mutex<Vec<u8>>>,
+        release: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl MailboxHandler for Recorder {
+        async fn handle(&mut self, msg: Vec<u8>) -> Result<Vec<u8>, EnterpriseError> {
+            if self.seen.lock().unwrap().is_empty() {
+                self.release.notified().await;
+            }
+            self.seen.lock().unwrap().push(msg[0]);
+            Ok(msg)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flooded_mailbox_rejects_and_preserves_order() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let release = Arc::new(Notify::new());
+        let (mailbox, worker) = Mailbox::spawn(Recorder { seen: seen.clone(), release: release.clone() }, 4).unwrap();
+
+        // The worker takes the first message and blocks on it
+        let mut replies = vec![mailbox.send(vec![0]).unwrap()];
+        while mailbox.available() < 4 {
+            tokio::task::yield_now().await;
+        }
+
+        for i in 1..=4 {
+            replies.push(mailbox.send(vec![i]).unwrap());
+        }
+        for i in 5..10 {
+            assert!(matches!(mailbox.send(vec![i]), Err(EnterpriseError::ResourceLimit(_))));
+        }
+
+        release.notify_one();
+        for (i, reply) in replies.into_iter().enumerate() {
+            assert_eq!(reply.wait().await.unwrap(), vec![i as u8]);
+        }
+        assert_eq!(*seen.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+
+        // Room again once the queue drains
+        let late = mailbox.send(vec![9]).unwrap();
+        assert_eq!(late.wait().await.unwrap(), vec![9]);
+
+        drop(mailbox);
+        let recorder = worker.await.unwrap();
+        assert_eq!(recorder.seen.lock().unwrap().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_agent_mailbox_sized_from_config() {
+        use crate::agent::{AgentConfig, AgentIdentity};
+
+        // An expired identity gives every message a known outcome
+        let identity = AgentIdentity {
+            id: uuid::Uuid::new_v4(),
+            generation: 1,
+            valid_from: 0,
+            valid_to: 1,
+            attestation: Vec::new(),
+        };
+        let agent = |mailbox_capacity| {
+            let config = AgentConfig { mailbox_capacity, ..AgentConfig::new(1024, 0.8, 1_000_000) };
+            EnterpriseAgent::with_identity(config, identity.clone()).unwrap()
+        };
+        assert!(matches!(agent(0).spawn_mailbox(), Err(EnterpriseError::ResourceLimit(_))));
+
+        let (mailbox, worker) = agent(3).spawn_mailbox().unwrap();
+        assert_eq!(mailbox.available(), 3);
+        let reply = mailbox.send(vec![0; 8]).unwrap();
+        assert!(matches!(reply.wait().await, Err(EnterpriseError::AuthError(_))));
+
+        drop(mailbox);
+        let agent = worker.await.unwrap();
+        assert_eq!(agent.config.mailbox_capacity, 3);
+    }
+}
@@ -0,0 +1,357 @@
+// snapshot.rs - Agent Export and Restore for Live Migration
+use std::sync::Arc;
+
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    AdmissionController, AdmissionQuotas, AgentConfig, AgentIdentity, CapabilityInvoker, CarriedUsage,
+    ComplianceEngine, ConcurrencyMode, ConfigEnvelope, EnterpriseAgent,
+};
+use crate::{
+    clock::{Clock, SkewPolicy, SystemClock},
+    coordination::{ReplicatedStateMachine, RevocationList, StateSnapshot, ValidatorSet},
+    crypto, EnterpriseError,
+};
+
+/// Snapshot format written by `export_state`
+///
+/// Version 2 prefixes the payload with its HMAC; unauthenticated version 1
+/// snapshots are no longer restored. Version 3 carries the sizes of
+/// admissions in the rate window in place of a lifetime byte count.
+pub const AGENT_SNAPSHOT_VERSION: u32 = 3;
+const TAG_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct AgentSnapshot {
+    snapshot_version: u32,
+    /// Identity and configuration, read back through the envelope's schema
+    /// migrations
+    agent: ConfigEnvelope,
+    usage: CarriedUsage,
+    state: StateSnapshot,
+}
+
+/// HMAC-SHA256 key authenticating snapshots between the hosts an agent
+/// migrates across
+#[derive(Clone)]
+pub struct SnapshotKey(hmac::Key);
+
+impl SnapshotKey {
+    pub fn new(secret: &[u8]) -> Self {
+        Self(hmac::Key::new(hmac::HMAC_SHA256, secret))
+    }
+
+    fn tag(&self, payload: &[u8]) -> hmac::Tag {
+        hmac::sign(&self.0, payload)
+    }
+
+    fn verify(&self, payload: &[u8], tag: &[u8]) -> bool {
+        hmac::verify(&self.0, payload, tag).is_ok()
+    }
+}
+
+impl std::fmt::Debug for SnapshotKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SnapshotKey(..)")
+    }
+}
+
+/// Decides whether an identity's attestation comes from a host this
+/// deployment trusts
+pub trait AttestationVerifier: Send + Sync {
+    /// Refuse the identity, normally with `AuthError`, unless its
+    /// attestation checks out
+    fn verify(&self, identity: &AgentIdentity) -> Result<(), EnterpriseError>;
+}
+
+/// What a restored agent is wired to on its new host
+///
+/// None of it travels in a snapshot: the collaborators are process-local
+/// and the policies, resource limits included, belong to the destination
+/// deployment.
+pub struct AgentDeps {
+    /// Resource limits on this host, replacing those in the snapshot
+    pub quotas: AdmissionQuotas,
+    /// Key the snapshot must be authenticated with
+    pub snapshot_key: SnapshotKey,
+    pub attestation: Arc<dyn AttestationVerifier>,
+    pub crypto: crypto::KyberKem,
+    pub capabilities: Option<Arc<dyn CapabilityInvoker>>,
+    pub compliance: ComplianceEngine,
+    /// Membership governing certified batches for the agent's state machine
+    pub validators: ValidatorSet,
+    pub revocations: Option<Arc<RevocationList>>,
+    pub clock: Arc<dyn Clock>,
+    pub skew: SkewPolicy,
+}
+
+impl AgentDeps {
+    /// Dependencies with the destination's limits, snapshot key and
+    /// attestation policy, and every other collaborator at its default
+    pub fn new(quotas: AdmissionQuotas, snapshot_key: SnapshotKey, attestation: Arc<dyn AttestationVerifier>) -> Self {
+        Self {
+            quotas,
+            snapshot_key,
+            attestation,
+            crypto: crypto::KyberKem,
+            capabilities: None,
+            compliance: ComplianceEngine::default(),
+            validators: ValidatorSet::default(),
+            revocations: None,
+            clock: Arc::new(SystemClock),
+            skew: SkewPolicy::default(),
+        }
+    }
+}
+
+impl EnterpriseAgent {
+    /// Serialize everything an agent needs to resume on another host
+    ///
+    /// Captures the identity, configuration, admission history (the rate
+    /// window) and a snapshot of the state machine,
+    /// which holds the `NonceStore` replay window, authenticated with the
+    /// agent's `SnapshotKey`; without one the export is refused with
+    /// `AuthError`. Messages still in flight are not captured; drain the
+    /// agent first so none are lost.
+    pub async fn export_state(&self) -> Result<Vec<u8>, EnterpriseError> {
+        let key = self.snapshot_key.as_ref()
+            .ok_or_else(|| EnterpriseError::AuthError("no snapshot key to authenticate the export".into()))?;
+        let snapshot = AgentSnapshot {
+            snapshot_version: AGENT_SNAPSHOT_VERSION,
+            agent: ConfigEnvelope::new(self.config.clone(), Some(self.identity.clone())),
+            usage: self.admission.carried_usage(self.clock.now_millis()),
+            state: self.state_machine.export_snapshot().await,
+        };
+        let payload = serde_json::to_vec(&snapshot).map_err(|_| EnterpriseError::ProtocolError)?;
+        Ok([key.tag(&payload).as_ref(), &payload].concat())
+    }
+
+    /// Rebuild an agent from `export_state` output, wired to `deps`
+    ///
+    /// A snapshot not authenticated with `deps.snapshot_key` is an
+    /// `IntegrityError`, and nothing in it is read. An identity
+    /// `deps.attestation` refuses, that has expired, or whose id or
+    /// attestation is on `deps.revocations`, is an `AuthError`; one not yet
+    /// valid, allowing for `deps.skew`, a `ClockSkew`. A malformed or
+    /// other-version snapshot is a `ProtocolError`. The agent runs under
+    /// `deps.quotas`, whatever limits the snapshot's configuration carried.
+    pub fn restore(bytes: &[u8], deps: AgentDeps) -> Result<EnterpriseAgent, EnterpriseError> {
+        let (tag, payload) = bytes.split_at_checked(TAG_LEN).ok_or(EnterpriseError::ProtocolError)?;
+        if !deps.snapshot_key.verify(payload, tag) {
+            warn!("Refusing agent snapshot that fails authentication");
+            return Err(EnterpriseError::IntegrityError);
+        }
+        let snapshot: AgentSnapshot = serde_json::from_slice(payload).map_err(|e| {
+            warn!(error = %e, "Unreadable agent snapshot");
+            EnterpriseError::ProtocolError
+        })?;
+        if snapshot.snapshot_version != AGENT_SNAPSHOT_VERSION {
+            warn!(version = snapshot.snapshot_version, "Unsupported agent snapshot version");
+            return Err(EnterpriseError::ProtocolError);
+        }
+        let ConfigEnvelope { config, identity, .. } = snapshot.agent;
+        let identity = identity.ok_or(EnterpriseError::ProtocolError)?;
+
+        deps.attestation.verify(&identity)?;
+        identity.check_at(deps.clock.now_millis(), &deps.skew)?;
+        identity.check_revocation(deps.revocations.as_deref())?;
+
+        info!(identity = %identity.id, sequence = snapshot.state.sequence, "Restoring agent from snapshot");
+        let config = with_quotas(config, &deps.quotas);
+        let admission = AdmissionController::new(deps.quotas).with_usage(snapshot.usage);
+        let mut agent = EnterpriseAgent::with_identity(config, identity)?
+            .with_clock(deps.clock)
+            .with_skew_policy(deps.skew)
+            .with_compliance(deps.compliance)
+            .with_snapshot_key(deps.snapshot_key);
+        agent.admission = admission;
+        agent.state_machine = Arc::new(ReplicatedStateMachine::from_snapshot(deps.validators, snapshot.state));
+        agent.crypto = deps.crypto;
+        agent.capabilities = deps.capabilities;
+        agent.revocations = deps.revocations;
+        Ok(agent)
+    }
+}
+
+/// `config` with the destination's limits in place of its own, so the
+/// concurrency gate and any later export agree with the admission quotas
+fn with_quotas(mut config: AgentConfig, quotas: &AdmissionQuotas) -> AgentConfig {
+    config.max_in_flight_bytes = quotas.max_in_flight_bytes;
+    config.network_bytes_per_sec = quotas.network_bytes_per_sec;
+    config.max_messages_per_sec = quotas.max_messages_per_sec;
+    match quotas.max_concurrent {
+        Some(max_concurrent) => {
+            config.max_concurrent_messages = max_concurrent;
+            config.concurrency_mode = ConcurrencyMode::Reject;
+        }
+        None => config.concurrency_mode = ConcurrencyMode::Wait,
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::{AgentConfig, AgentIdentity},
+        clock::MockClock,
+        coordination::{key_id, NonceStore},
+    };
+    use std::time::{Duration, SystemTime};
+    use tokio::runtime::Runtime;
+    use uuid::Uuid;
+
+    fn agent(clock: Arc<MockClock>) -> EnterpriseAgent {
+        let now = clock.now_millis();
+        let identity = AgentIdentity {
+            id: Uuid::new_v4(),
+            generation: 2,
+            valid_from: now,
+            valid_to: now + 3_600_000,
+            attestation: vec![7; 4],
+        };
+        let config = AgentConfig { max_messages_per_sec: Some(5), ..AgentConfig::new(1024, 0.5, 1_000) };
+        EnterpriseAgent::with_identity(config, identity).unwrap().with_clock(clock).with_snapshot_key(key())
+    }
+
+    fn key() -> SnapshotKey {
+        SnapshotKey::new(b"shared migration secret")
+    }
+
+    /// Trusts identities attested by one host key
+    struct TrustedHost(Vec<u8>);
+
+    impl AttestationVerifier for TrustedHost {
+        fn verify(&self, identity: &AgentIdentity) -> Result<(), EnterpriseError> {
+            match identity.attestation == self.0 {
+                true => Ok(()),
+                false => Err(EnterpriseError::AuthError(format!("{} attested by an untrusted host", identity.id))),
+            }
+        }
+    }
+
+    fn quotas() -> AdmissionQuotas {
+        AdmissionQuotas {
+            max_in_flight_bytes: Some(1024),
+            network_bytes_per_sec: Some(1_000),
+            max_concurrent: Some(8),
+            max_messages_per_sec: Some(5),
+        }
+    }
+
+    fn deps(clock: Arc<MockClock>) -> AgentDeps {
+        AgentDeps { clock, ..AgentDeps::new(quotas(), key(), Arc::new(TrustedHost(vec![7; 4]))) }
+    }
+
+    #[test]
+    fn test_restored_agent_keeps_replay_window_and_state() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+            let source = agent(clock.clone());
+
+            // Mid-session: a nonce seen, three messages admitted this second
+            let nonces = NonceStore::new(source.state_machine()).with_clock(clock.clone());
+            assert!(nonces.check_and_record(b"request-1", Duration::from_secs(60)).await.unwrap());
+            for _ in 0..3 {
+                drop(source.admission.admit(100, clock.now_millis()).unwrap());
+            }
+            let exported = source.export_state().await.unwrap();
+
+            let restored = EnterpriseAgent::restore(&exported, deps(clock.clone())).unwrap();
+            assert_eq!(restored.identity.id, source.identity.id);
+            assert_eq!(restored.config.max_messages_per_sec, Some(5));
+            let (moved, original) = (restored.state_machine().export_snapshot().await, source.state_machine().export_snapshot().await);
+            assert_eq!((moved.sequence, moved.state), (original.sequence, original.state));
+
+            // The replayed nonce is still refused on the new host
+            let nonces = NonceStore::new(restored.state_machine()).with_clock(clock.clone());
+            assert!(!nonces.check_and_record(b"request-1", Duration::from_secs(60)).await.unwrap());
+
+            let usage = restored.admission.usage(clock.now_millis());
+            assert_eq!((usage.recent_bytes, usage.recent_messages, usage.in_flight), (300, 3, 0));
+            let later = restored.admission.usage(clock.now_millis() + 1_000);
+            assert_eq!((later.recent_bytes, later.recent_messages), (0, 0));
+        });
+    }
+
+    #[test]
+    fn test_restore_refuses_revoked_or_expired_identity() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+            let source = agent(clock.clone());
+            let exported = source.export_state().await.unwrap();
+
+            let revocations = Arc::new(RevocationList::attach(Arc::new(ReplicatedStateMachine::new())).await);
+            revocations.revoke(&source.identity.id.to_string(), "host compromised", 0).await.unwrap();
+            let deps = AgentDeps { revocations: Some(revocations), ..deps(clock.clone()) };
+            assert!(matches!(EnterpriseAgent::restore(&exported, deps), Err(EnterpriseError::AuthError(_))));
+
+            // Revoking the attesting host key refuses the identity too
+            let revocations = Arc::new(RevocationList::attach(Arc::new(ReplicatedStateMachine::new())).await);
+            revocations.revoke(&key_id(&source.identity.attestation), "host compromised", 0).await.unwrap();
+            let deps = AgentDeps { revocations: Some(revocations), ..deps(clock.clone()) };
+            assert!(matches!(EnterpriseAgent::restore(&exported, deps), Err(EnterpriseError::AuthError(_))));
+
+            // So is one attested by a host this deployment does not trust
+            let deps = AgentDeps { attestation: Arc::new(TrustedHost(vec![8; 4])), ..deps(clock.clone()) };
+            assert!(matches!(EnterpriseAgent::restore(&exported, deps), Err(EnterpriseError::AuthError(_))));
+
+            clock.advance(Duration::from_secs(7_200));
+            assert!(matches!(EnterpriseAgent::restore(&exported, deps(clock.clone())), Err(EnterpriseError::AuthError(_))));
+
+            assert!(matches!(
+                EnterpriseAgent::restore(b"{}", deps(clock.clone())),
+                Err(EnterpriseError::ProtocolError)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_restore_refuses_unauthenticated_snapshot() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+            let source = agent(clock.clone());
+            let exported = source.export_state().await.unwrap();
+
+            // Raising its own budget in transit breaks the tag
+            let text = String::from_utf8(exported[32..].to_vec()).unwrap();
+            let forged = [&exported[..32], text.replace("\"network_budget\":1000", "\"network_budget\":9999").as_bytes()].concat();
+            assert_ne!(forged, exported);
+            assert!(matches!(EnterpriseAgent::restore(&forged, deps(clock.clone())), Err(EnterpriseError::IntegrityError)));
+
+            let deps = AgentDeps { snapshot_key: SnapshotKey::new(b"another deployment"), ..deps(clock.clone()) };
+            assert!(matches!(EnterpriseAgent::restore(&exported, deps), Err(EnterpriseError::IntegrityError)));
+
+            // An agent without a key cannot export at all
+            let unkeyed = EnterpriseAgent::with_identity(AgentConfig::new(1024, 0.5, 1_000), source.identity.clone()).unwrap();
+            assert!(matches!(unkeyed.export_state().await, Err(EnterpriseError::AuthError(_))));
+        });
+    }
+
+    #[test]
+    fn test_restored_agent_runs_under_destination_quotas() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)));
+            let exported = agent(clock.clone()).export_state().await.unwrap();
+
+            let tight = AdmissionQuotas {
+                max_in_flight_bytes: Some(64),
+                network_bytes_per_sec: Some(100),
+                max_concurrent: Some(1),
+                max_messages_per_sec: None,
+            };
+            let restored = EnterpriseAgent::restore(&exported, AgentDeps { quotas: tight, ..deps(clock.clone()) }).unwrap();
+            assert_eq!((restored.config.max_in_flight_bytes, restored.config.network_bytes_per_sec), (Some(64), Some(100)));
+            assert_eq!(restored.config.max_messages_per_sec, None);
+            assert_eq!(restored.config.max_concurrent_messages, 1);
+            assert!(restored.admission.admit(65, clock.now_millis()).is_err());
+            drop(restored.admission.admit(64, clock.now_millis()).unwrap());
+        });
+    }
+}
@@ -0,0 +1,160 @@
+// clock.rs - Pluggable Wall Clock
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Source of wall-clock time for validity, decay and replay checks
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    /// Milliseconds since the Unix epoch, the unit of `AgentIdentity` bounds
+    fn now_millis(&self) -> u128 {
+        self.now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+    }
+}
+
+/// The operating system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Manually advanced clock for deterministic tests
+///
+/// Clones share the same instant, so a test can keep one handle and
+/// advance time seen by the component holding the other.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self { now: Arc::new(Mutex::new(start)) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    pub fn set(&self, to: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = to;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// How far a peer's clock may disagree with ours before its timestamps
+/// are refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkewPolicy {
+    /// Largest amount a timestamp may lie ahead of the local clock
+    pub max_future: Duration,
+    /// Largest amount a timestamp may lie behind the local clock
+    pub max_past: Duration,
+}
+
+impl Default for SkewPolicy {
+    fn default() -> Self {
+        Self { max_future: Duration::from_secs(30), max_past: Duration::from_secs(300) }
+    }
+}
+
+/// A timestamp outside a `SkewPolicy`'s tolerance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum SkewError {
+    #[error("timestamp {by:?} ahead of the local clock")]
+    Ahead { by: Duration },
+    #[error("timestamp {by:?} behind the local clock")]
+    Behind { by: Duration },
+}
+
+impl SkewPolicy {
+    /// Accept `timestamp` if it is within tolerance of `now`
+    pub fn check(&self, timestamp: SystemTime, now: SystemTime) -> Result<(), SkewError> {
+        match timestamp.duration_since(now) {
+            Ok(ahead) if ahead > self.max_future => Err(SkewError::Ahead { by: ahead }),
+            Ok(_) => Ok(()),
+            Err(e) if e.duration() > self.max_past => Err(SkewError::Behind { by: e.duration() }),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// `check` for milliseconds since the Unix epoch
+    pub fn check_millis(&self, timestamp: u128, now: u128) -> Result<(), SkewError> {
+        self.check(from_millis(timestamp), from_millis(now))
+    }
+
+    /// Accept a validity window starting at `valid_from` as begun at `now`,
+    /// allowing for the issuer's clock running up to `max_future` ahead
+    ///
+    /// A window starting too far ahead is `Ahead` by how much it still has
+    /// to go. Only the start is widened: the end of a window is the
+    /// issuer's decision, and a window past it has expired whatever the
+    /// clocks say.
+    pub fn check_started(&self, valid_from: u128, now: u128) -> Result<(), SkewError> {
+        match from_millis(valid_from).duration_since(from_millis(now)) {
+            Ok(ahead) if ahead > self.max_future => Err(SkewError::Ahead { by: ahead }),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn from_millis(millis: u128) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.try_into().unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn policy() -> SkewPolicy {
+        SkewPolicy { max_future: Duration::from_secs(10), max_past: Duration::from_secs(60) }
+    }
+
+    #[test]
+    fn test_timestamps_at_the_tolerance_edges() {
+        let now = MockClock::default().now();
+        let (future, past) = (policy().max_future, policy().max_past);
+
+        assert!(policy().check(now + future, now).is_ok());
+        assert_eq!(policy().check(now + future + MS, now), Err(SkewError::Ahead { by: future + MS }));
+        assert!(policy().check(now - past, now).is_ok());
+        assert_eq!(policy().check(now - past - MS, now), Err(SkewError::Behind { by: past + MS }));
+    }
+
+    #[test]
+    fn test_validity_start_widened_by_future_tolerance() {
+        let now = MockClock::default().now_millis();
+        let future = 10_000;
+
+        // Not yet valid by less than the future tolerance
+        assert!(policy().check_started(now + future, now).is_ok());
+        assert_eq!(
+            policy().check_started(now + future + 1, now),
+            Err(SkewError::Ahead { by: policy().max_future + MS })
+        );
+        assert!(policy().check_started(now - 100_000, now).is_ok());
+    }
+}
@@ -0,0 +1,452 @@
+// codec.rs - Agent Message Wire Codecs
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::EnterpriseError;
+
+/// Header without a compression field, written whenever the body is sent
+/// uncompressed so peers predating compression can still read it
+const ENVELOPE_VERSION: u8 = 1;
+/// Header carrying the compression tag, with the original length following
+/// it
+const COMPRESSED_ENVELOPE_VERSION: u8 = 2;
+const HEADER_LEN: usize = 2;
+const ORIGINAL_LEN_LEN: usize = 4;
+
+/// Largest body a compressed envelope may inflate to; anything claiming
+/// more is refused before it is decompressed
+pub const MAX_INFLATED_SIZE: usize = 16 * 1024 * 1024;
+
+/// Serialization format of an agent message body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MessageCodec {
+    #[default]
+    Json,
+    Cbor,
+    Bincode,
+}
+
+impl MessageCodec {
+    /// Tag byte carried in the envelope header
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::Json => 0x01,
+            Self::Cbor => 0x02,
+            Self::Bincode => 0x03,
+        }
+    }
+
+    /// Resolve an envelope tag byte
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0x01 => Some(Self::Json),
+            0x02 => Some(Self::Cbor),
+            0x03 => Some(Self::Bincode),
+            _ => None,
+        }
+    }
+
+    /// Serialize a value without an envelope
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, EnterpriseError> {
+        match self {
+            Self::Json => serde_json::to_vec(value).map_err(|_| EnterpriseError::ProtocolError),
+            Self::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(value, &mut buf).map_err(|_| EnterpriseError::ProtocolError)?;
+                Ok(buf)
+            }
+            Self::Bincode => bincode::serialize(value).map_err(|_| EnterpriseError::ProtocolError),
+        }
+    }
+
+    /// Deserialize a value without an envelope
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, EnterpriseError> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes).map_err(|_| EnterpriseError::ProtocolError),
+            Self::Cbor => ciborium::from_reader(bytes).map_err(|_| EnterpriseError::ProtocolError),
+            Self::Bincode => bincode::deserialize(bytes).map_err(|_| EnterpriseError::ProtocolError),
+        }
+    }
+}
+
+/// Compression applied to an envelope body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// Tag byte carried in the envelope header
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::None => 0x00,
+            Self::Zstd => 0x01,
+            Self::Lz4 => 0x02,
+        }
+    }
+
+    /// Every compression this build can inflate
+    pub const ALL: [Compression; 3] = [Compression::None, Compression::Zstd, Compression::Lz4];
+
+    /// Resolve an envelope tag byte
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0x00 => Some(Self::None),
+            0x01 => Some(Self::Zstd),
+            0x02 => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    fn compress(&self, body: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+        match self {
+            Self::None => Ok(body.to_vec()),
+            Self::Zstd => zstd::bulk::compress(body, 0).map_err(|_| EnterpriseError::ProtocolError),
+            Self::Lz4 => Ok(lz4_flex::block::compress(body)),
+        }
+    }
+
+    /// Inflate `body` to exactly `original_len` bytes, never allocating more
+    fn decompress(&self, body: &[u8], original_len: usize) -> Result<Vec<u8>, EnterpriseError> {
+        let inflated = match self {
+            Self::None => return Err(EnterpriseError::ProtocolError),
+            Self::Zstd => zstd::bulk::decompress(body, original_len).map_err(|_| EnterpriseError::ProtocolError)?,
+            Self::Lz4 => lz4_flex::block::decompress(body, original_len).map_err(|_| EnterpriseError::ProtocolError)?,
+        };
+        if inflated.len() != original_len {
+            return Err(EnterpriseError::ProtocolError);
+        }
+        Ok(inflated)
+    }
+}
+
+/// When outbound bodies are compressed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionPolicy {
+    #[serde(default)]
+    pub algorithm: Compression,
+    /// Bodies of at most this many bytes are sent as they are, as
+    /// compressing them costs more CPU than it saves bandwidth
+    #[serde(default = "default_compression_threshold")]
+    pub threshold: usize,
+}
+
+fn default_compression_threshold() -> usize {
+    4096
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self { algorithm: Compression::None, threshold: default_compression_threshold() }
+    }
+}
+
+impl CompressionPolicy {
+    /// This policy for a peer accepting only `accepted`: bodies go out
+    /// uncompressed when the peer cannot inflate the configured algorithm
+    pub fn negotiate(&self, accepted: &[Compression]) -> Self {
+        match accepted.contains(&self.algorithm) {
+            true => self.clone(),
+            false => Self { algorithm: Compression::None, ..self.clone() },
+        }
+    }
+}
+
+/// Envelope header (`version`, `codec tag`) followed by the body
+///
+/// Version 2 adds a compression tag after the codec tag and, when the body
+/// is compressed, its original length as a big-endian `u32`. Only
+/// compressed bodies are sent as version 2.
+pub struct Envelope;
+
+impl Envelope {
+    /// Encode a value with the given codec and prepend the header
+    pub fn seal<T: Serialize>(codec: MessageCodec, value: &T) -> Result<Vec<u8>, EnterpriseError> {
+        let body = codec.encode(value)?;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+        bytes.push(ENVELOPE_VERSION);
+        bytes.push(codec.tag());
+        bytes.extend(body);
+        Ok(bytes)
+    }
+
+    /// Encode a value, compressing the body under `policy`
+    ///
+    /// The body is compressed only when it exceeds the threshold and the
+    /// result is actually smaller; otherwise the envelope is the version 1
+    /// one `seal` writes. Use `CompressionPolicy::negotiate` to stay within
+    /// what the receiving peer accepts.
+    pub fn seal_with<T: Serialize>(
+        codec: MessageCodec,
+        policy: &CompressionPolicy,
+        value: &T,
+    ) -> Result<Vec<u8>, EnterpriseError> {
+        let body = codec.encode(value)?;
+        let compressed = match policy.algorithm {
+            Compression::None => None,
+            _ if body.len() <= policy.threshold || body.len() > MAX_INFLATED_SIZE => None,
+            algorithm => Some((algorithm, algorithm.compress(&body)?)).filter(|(_, c)| c.len() < body.len()),
+        };
+
+        let Some((algorithm, compressed)) = compressed else {
+            let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+            bytes.extend([ENVELOPE_VERSION, codec.tag()]);
+            bytes.extend(body);
+            return Ok(bytes);
+        };
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 1 + ORIGINAL_LEN_LEN + compressed.len());
+        bytes.extend([COMPRESSED_ENVELOPE_VERSION, codec.tag(), algorithm.tag()]);
+        bytes.extend((body.len() as u32).to_be_bytes());
+        bytes.extend(compressed);
+        Ok(bytes)
+    }
+
+    /// Decode an envelope, selecting the decoder from its tag
+    ///
+    /// Unknown versions, codec tags outside `accepted`, compression tags
+    /// outside `accepted_compression`, and bodies that do not decode under
+    /// the tagged codec are all rejected as `ProtocolError`, as is a
+    /// compressed body that claims to inflate past `MAX_INFLATED_SIZE` or to
+    /// a length other than the one it does.
+    pub fn open<T: DeserializeOwned>(
+        bytes: &[u8],
+        accepted: &[MessageCodec],
+        accepted_compression: &[Compression],
+    ) -> Result<(MessageCodec, T), EnterpriseError> {
+        let (header, body) = bytes.split_at_checked(HEADER_LEN)
+            .ok_or(EnterpriseError::ProtocolError)?;
+        let codec = MessageCodec::from_tag(header[1])
+            .filter(|c| accepted.contains(c))
+            .ok_or(EnterpriseError::ProtocolError)?;
+        match header[0] {
+            ENVELOPE_VERSION => Ok((codec, codec.decode(body)?)),
+            COMPRESSED_ENVELOPE_VERSION => Ok((codec, codec.decode(&inflate(body, accepted_compression)?)?)),
+            _ => Err(EnterpriseError::ProtocolError),
+        }
+    }
+}
+
+/// Body of a version 2 envelope, starting at its compression tag
+fn inflate<'a>(
+    body: &'a [u8],
+    accepted: &[Compression],
+) -> Result<std::borrow::Cow<'a, [u8]>, EnterpriseError> {
+    let (&tag, body) = body.split_first().ok_or(EnterpriseError::ProtocolError)?;
+    let algorithm = Compression::from_tag(tag)
+        .filter(|c| *c == Compression::None || accepted.contains(c))
+        .ok_or(EnterpriseError::ProtocolError)?;
+    match algorithm {
+        Compression::None => Ok(body.into()),
+        algorithm => {
+            let (original_len, body) = body.split_first_chunk::<ORIGINAL_LEN_LEN>()
+                .ok_or(EnterpriseError::ProtocolError)?;
+            let original_len = u32::from_be_bytes(*original_len) as usize;
+            if original_len > MAX_INFLATED_SIZE {
+                return Err(EnterpriseError::ProtocolError);
+            }
+            Ok(algorithm.decompress(body, original_len)?.into())
+        }
+    }
+}
+
+/// `u128` as a decimal string in human-readable formats, which cannot all
+/// represent integers that large, and natively in binary ones
+///
+/// Human-readable input may also give the value as a bare number, as
+/// documents written before the string encoding do.
+///
+/// For use as `#[serde(with = "codec::u128_string")]`.
+pub mod u128_string {
+    use std::fmt;
+
+    use serde::{
+        de::{Error, Visitor},
+        Deserialize, Deserializer, Serializer,
+    };
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(value)
+        } else {
+            serializer.serialize_u128(*value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(StringOrNumber)
+        } else {
+            u128::deserialize(deserializer)
+        }
+    }
+
+    struct StringOrNumber;
+
+    impl<'de> Visitor<'de> for StringOrNumber {
+        type Value = u128;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("an unsigned integer or a string holding one")
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<u128, E> {
+            value.parse().map_err(E::custom)
+        }
+
+        fn visit_u64<E: Error>(self, value: u64) -> Result<u128, E> {
+            Ok(value.into())
+        }
+
+        fn visit_u128<E: Error>(self, value: u128) -> Result<u128, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: Error>(self, value: i64) -> Result<u128, E> {
+            u128::try_from(value).map_err(E::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [MessageCodec; 3] = [MessageCodec::Json, MessageCodec::Cbor, MessageCodec::Bincode];
+    const COMPRESSED: [Compression; 2] = [Compression::Zstd, Compression::Lz4];
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        id: u64,
+        name: String,
+        payload: Vec<u8>,
+    }
+
+    fn sample() -> Sample {
+        Sample { id: 42, name: "agent-7".into(), payload: vec![0, 1, 2, 255] }
+    }
+
+    #[test]
+    fn test_round_trip_each_codec() {
+        for codec in ALL {
+            let bytes = Envelope::seal(codec, &sample()).unwrap();
+            let (decoded_codec, decoded): (_, Sample) = Envelope::open(&bytes, &ALL, &Compression::ALL).unwrap();
+            assert_eq!(decoded_codec, codec);
+            assert_eq!(decoded, sample());
+        }
+    }
+
+    #[test]
+    fn test_mismatched_codec_tag_rejected() {
+        let mut bytes = Envelope::seal(MessageCodec::Json, &sample()).unwrap();
+        bytes[1] = MessageCodec::Bincode.tag();
+        assert!(matches!(
+            Envelope::open::<Sample>(&bytes, &ALL, &Compression::ALL),
+            Err(EnterpriseError::ProtocolError)
+        ));
+
+        let cbor = Envelope::seal(MessageCodec::Cbor, &sample()).unwrap();
+        assert!(matches!(
+            Envelope::open::<Sample>(&cbor, &[MessageCodec::Json], &Compression::ALL),
+            Err(EnterpriseError::ProtocolError)
+        ));
+    }
+
+    fn policy(algorithm: Compression) -> CompressionPolicy {
+        CompressionPolicy { algorithm, ..CompressionPolicy::default() }
+    }
+
+    fn large_sample() -> Sample {
+        Sample { id: 7, name: "bulk".into(), payload: b"telemetry frame ".repeat(4096) }
+    }
+
+    #[test]
+    fn test_large_payload_round_trips_under_each_compression() {
+        for algorithm in COMPRESSED {
+            for codec in ALL {
+                let plain = Envelope::seal(codec, &large_sample()).unwrap();
+                let bytes = Envelope::seal_with(codec, &policy(algorithm), &large_sample()).unwrap();
+                assert_eq!((bytes[0], bytes[2]), (COMPRESSED_ENVELOPE_VERSION, algorithm.tag()));
+                assert!(bytes.len() < plain.len() / 4, "{:?} {:?}: {} bytes", algorithm, codec, bytes.len());
+
+                let (decoded_codec, decoded): (_, Sample) = Envelope::open(&bytes, &ALL, &Compression::ALL).unwrap();
+                assert_eq!(decoded_codec, codec);
+                assert_eq!(decoded, large_sample());
+            }
+        }
+    }
+
+    #[test]
+    fn test_small_payload_sent_uncompressed() {
+        for algorithm in COMPRESSED {
+            // Written as version 1, which peers predating compression read
+            let bytes = Envelope::seal_with(MessageCodec::Json, &policy(algorithm), &sample()).unwrap();
+            assert_eq!(bytes, Envelope::seal(MessageCodec::Json, &sample()).unwrap());
+            assert_eq!(bytes[0], ENVELOPE_VERSION);
+            let (_, decoded): (_, Sample) = Envelope::open(&bytes, &ALL, &Compression::ALL).unwrap();
+            assert_eq!(decoded, sample());
+        }
+
+        // Disabled compression leaves even large bodies alone
+        let bytes = Envelope::seal_with(MessageCodec::Json, &CompressionPolicy::default(), &large_sample()).unwrap();
+        assert_eq!(bytes, Envelope::seal(MessageCodec::Json, &large_sample()).unwrap());
+    }
+
+    #[test]
+    fn test_compression_negotiated_with_the_peer() {
+        // A peer that cannot inflate zstd is sent the body as it is
+        let negotiated = policy(Compression::Zstd).negotiate(&[Compression::None, Compression::Lz4]);
+        assert_eq!(negotiated.algorithm, Compression::None);
+        let bytes = Envelope::seal_with(MessageCodec::Json, &negotiated, &large_sample()).unwrap();
+        assert_eq!(bytes[0], ENVELOPE_VERSION);
+        assert_eq!(policy(Compression::Lz4).negotiate(&Compression::ALL), policy(Compression::Lz4));
+
+        // And refuses a body compressed with it regardless
+        let zstd = Envelope::seal_with(MessageCodec::Json, &policy(Compression::Zstd), &large_sample()).unwrap();
+        assert!(matches!(
+            Envelope::open::<Sample>(&zstd, &ALL, &[Compression::None, Compression::Lz4]),
+            Err(EnterpriseError::ProtocolError)
+        ));
+        let (_, decoded): (_, Sample) = Envelope::open(&zstd, &ALL, &[Compression::Zstd]).unwrap();
+        assert_eq!(decoded, large_sample());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Stamped {
+        #[serde(with = "u128_string")]
+        at: u128,
+    }
+
+    #[test]
+    fn test_u128_reads_strings_and_bare_numbers() {
+        let large = Stamped { at: u128::MAX };
+        let json = serde_json::to_string(&large).unwrap();
+        assert_eq!(json, format!("{{\"at\":\"{}\"}}", u128::MAX));
+        assert_eq!(serde_json::from_str::<Stamped>(&json).unwrap(), large);
+
+        assert_eq!(serde_json::from_str::<Stamped>(r#"{"at":1700000000000}"#).unwrap(), Stamped { at: 1_700_000_000_000 });
+        assert!(serde_json::from_str::<Stamped>(r#"{"at":-1}"#).is_err());
+        assert!(serde_json::from_str::<Stamped>(r#"{"at":"soon"}"#).is_err());
+
+        let binary = bincode::serialize(&large).unwrap();
+        assert_eq!(bincode::deserialize::<Stamped>(&binary).unwrap(), large);
+    }
+
+    #[test]
+    fn test_decompression_bomb_refused() {
+        for algorithm in COMPRESSED {
+            let mut bytes = Envelope::seal_with(MessageCodec::Json, &policy(algorithm), &large_sample()).unwrap();
+
+            // A header claiming more than the cap is refused outright
+            let mut bomb = bytes.clone();
+            bomb[3..7].copy_from_slice(&(MAX_INFLATED_SIZE as u32 + 1).to_be_bytes());
+            assert!(matches!(Envelope::open::<Sample>(&bomb, &ALL, &Compression::ALL), Err(EnterpriseError::ProtocolError)));
+
+            // So is a body inflating past the length it declares
+            let declared = u32::from_be_bytes(bytes[3..7].try_into().unwrap());
+            bytes[3..7].copy_from_slice(&(declared / 2).to_be_bytes());
+            assert!(matches!(Envelope::open::<Sample>(&bytes, &ALL, &Compression::ALL), Err(EnterpriseError::ProtocolError)));
+        }
+    }
+}
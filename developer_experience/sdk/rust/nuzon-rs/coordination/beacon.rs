@@ -0,0 +1,314 @@
+// beacon.rs - Verifiable Epoch Randomness from Hash-Chain Reveals
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::ValidatorSet;
+use crate::EnterpriseError;
+
+const CHAIN_DOMAIN: &[u8] = b"nuzon-beacon-chain-v1";
+const OUTPUT_DOMAIN: &[u8] = b"nuzon-beacon-output-v1";
+/// Most epochs a reveal may lie past its validator's anchor, bounding the
+/// hashing a single verification can be made to do
+const MAX_CHAIN_GAP: u64 = 1 << 20;
+
+fn chain_step(value: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(CHAIN_DOMAIN);
+    hasher.update(value);
+    hasher.finalize().into()
+}
+
+/// A validator's secret hash chain, revealed backwards one link per epoch
+///
+/// Only the final link is published up front, as the commitment. Each
+/// epoch's reveal hashes to the previous one, so it is fixed in advance
+/// yet cannot be predicted before the validator discloses it.
+#[derive(Clone)]
+pub struct HashChain {
+    seed: [u8; 32],
+    length: u64,
+    anchor_epoch: u64,
+}
+
+impl HashChain {
+    /// Chain covering the `length` epochs after `anchor_epoch`
+    ///
+    /// A chain longer than `MAX_CHAIN_GAP` could not have its later reveals
+    /// verified and is refused with `ResourceLimit`.
+    pub fn new(seed: [u8; 32], length: u64, anchor_epoch: u64) -> Result<Self, EnterpriseError> {
+        if length > MAX_CHAIN_GAP {
+            return Err(EnterpriseError::ResourceLimit(format!(
+                "hash chain of {} epochs exceeds the {} verifiers accept", length, MAX_CHAIN_GAP
+            )));
+        }
+        Ok(Self { seed, length, anchor_epoch })
+    }
+
+    /// Value registered with `RandomBeacon::commit`
+    pub fn commitment(&self) -> [u8; 32] {
+        self.link(self.length)
+    }
+
+    /// This validator's contribution to `epoch`, if the chain covers it
+    pub fn reveal(&self, epoch: u64) -> Option<[u8; 32]> {
+        let offset = epoch.checked_sub(self.anchor_epoch).filter(|offset| (1..=self.length).contains(offset))?;
+        Some(self.link(self.length - offset))
+    }
+
+    fn link(&self, steps: u64) -> [u8; 32] {
+        (0..steps).fold(self.seed, |link, _| chain_step(&link))
+    }
+}
+
+/// One validator's disclosed chain link for an epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconReveal {
+    pub validator_id: String,
+    pub reveal: [u8; 32],
+}
+
+/// Epoch randomness with the reveals it was derived from, recorded in
+/// `ConsensusHeader::beacon`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconOutput {
+    pub value: [u8; 32],
+    /// Value of the preceding epoch, chained into this one
+    pub previous: [u8; 32],
+    /// Contributions in validator id order
+    pub reveals: Vec<BeaconReveal>,
+}
+
+impl BeaconOutput {
+    /// Seed for any `rand::SeedableRng`
+    pub fn seed(&self) -> [u8; 32] {
+        self.value
+    }
+
+    /// Candidate chosen by this epoch's randomness, independent of the
+    /// order `candidates` are listed in
+    pub fn select<'a>(&self, candidates: &'a [String]) -> Option<&'a String> {
+        let mut sorted: Vec<_> = candidates.iter().collect();
+        sorted.sort();
+        sorted.dedup();
+        if sorted.is_empty() {
+            return None;
+        }
+        let draw = u64::from_be_bytes(self.value[..8].try_into().expect("8 bytes"));
+        Some(sorted[(draw % sorted.len() as u64) as usize])
+    }
+
+    fn derive(epoch: u64, previous: &[u8; 32], reveals: &[BeaconReveal]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(OUTPUT_DOMAIN);
+        hasher.update(epoch.to_be_bytes());
+        hasher.update(previous);
+        for contribution in reveals {
+            hasher.update((contribution.validator_id.len() as u64).to_be_bytes());
+            hasher.update(contribution.validator_id.as_bytes());
+            hasher.update(contribution.reveal);
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Latest verified link of a validator's chain
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    link: [u8; 32],
+    epoch: u64,
+}
+
+/// Derives each epoch's randomness from every contributor's hash-chain reveal
+///
+/// A reveal is valid when hashing it once per epoch since its validator's
+/// anchor gives the anchor, so anyone holding the commitments can check a
+/// recorded `BeaconOutput` after the fact. The contributors to an epoch are
+/// all members of its validator set with a registered chain, and the output
+/// needs a reveal from each of them, so whoever assembles it has no subset
+/// of reveals to choose between. A validator can still withhold its own
+/// reveal after seeing the others; that stalls the beacon until the
+/// validator is removed from the set at an epoch boundary, but cannot steer
+/// the value to one of several outcomes.
+#[derive(Debug, Clone)]
+pub struct RandomBeacon {
+    anchors: HashMap<String, Anchor>,
+    previous: [u8; 32],
+    epoch: u64,
+}
+
+impl RandomBeacon {
+    /// Beacon whose first output chains from `genesis`
+    pub fn new(genesis: [u8; 32]) -> Self {
+        Self { anchors: HashMap::new(), previous: genesis, epoch: 0 }
+    }
+
+    /// Register `validator_id`'s chain, whose first reveal is for the epoch
+    /// after `epoch`
+    pub fn commit(&mut self, validator_id: impl Into<String>, commitment: [u8; 32], epoch: u64) {
+        self.anchors.insert(validator_id.into(), Anchor { link: commitment, epoch });
+    }
+
+    /// Epoch of the last accepted output
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Derive `epoch`'s output from the contributions collected for it
+    ///
+    /// Reveals from validators outside the epoch's membership, duplicates
+    /// and ones that do not match their chain are left out. A missing
+    /// reveal from any contributor, or fewer contributors than a quorum, is
+    /// an `IntegrityError`.
+    pub fn produce(
+        &self,
+        epoch: u64,
+        validators: &ValidatorSet,
+        reveals: impl IntoIterator<Item = BeaconReveal>,
+    ) -> Result<BeaconOutput, EnterpriseError> {
+        if epoch <= self.epoch {
+            return Err(EnterpriseError::ProtocolError);
+        }
+        let members = validators.members_at(epoch).ok_or(EnterpriseError::ProtocolError)?;
+
+        let valid: BTreeMap<_, _> = reveals.into_iter()
+            .filter(|contribution| members.contains_key(&contribution.validator_id))
+            .filter(|contribution| self.reveal_valid(epoch, contribution))
+            .map(|contribution| (contribution.validator_id.clone(), contribution))
+            .collect();
+        let contributors = members.keys().filter(|id| self.anchors.contains_key(*id)).count();
+        if valid.len() < contributors || valid.len() < validators.quorum_size(epoch) {
+            return Err(EnterpriseError::IntegrityError);
+        }
+
+        let reveals: Vec<_> = valid.into_values().collect();
+        Ok(BeaconOutput {
+            value: BeaconOutput::derive(epoch, &self.previous, &reveals),
+            previous: self.previous,
+            reveals,
+        })
+    }
+
+    /// Check a recorded output for `epoch` against the commitments
+    ///
+    /// Every listed reveal must be valid and every contributor must be
+    /// listed, so an output can neither carry contributions the value was
+    /// not derived from nor leave out one it should have been.
+    pub fn verify(&self, epoch: u64, validators: &ValidatorSet, output: &BeaconOutput) -> Result<(), EnterpriseError> {
+        let expected = self.produce(epoch, validators, output.reveals.iter().cloned())?;
+        if expected != *output {
+            return Err(EnterpriseError::IntegrityError);
+        }
+        Ok(())
+    }
+
+    /// Verify `output` and make it the one the next epoch chains from
+    pub fn accept(&mut self, epoch: u64, validators: &ValidatorSet, output: &BeaconOutput) -> Result<(), EnterpriseError> {
+        self.verify(epoch, validators, output)?;
+        for contribution in &output.reveals {
+            self.anchors.insert(contribution.validator_id.clone(), Anchor { link: contribution.reveal, epoch });
+        }
+        self.previous = output.value;
+        self.epoch = epoch;
+        Ok(())
+    }
+
+    fn reveal_valid(&self, epoch: u64, contribution: &BeaconReveal) -> bool {
+        let Some(anchor) = self.anchors.get(&contribution.validator_id) else {
+            return false;
+        };
+        match epoch.checked_sub(anchor.epoch) {
+            Some(gap @ 1..=MAX_CHAIN_GAP) => {
+                (0..gap).fold(contribution.reveal, |link, _| chain_step(&link)) == anchor.link
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Keypair;
+
+    fn fixture() -> (ValidatorSet, RandomBeacon, Vec<(String, HashChain)>) {
+        let chains: Vec<_> = (0..4u8)
+            .map(|i| (format!("v{}", i), HashChain::new([i; 32], 16, 0).unwrap()))
+            .collect();
+        let validators = ValidatorSet::new(chains.iter()
+            .map(|(id, _)| (id.clone(), Keypair::generate(&mut rand::rngs::OsRng).public)));
+        let mut beacon = RandomBeacon::new([0; 32]);
+        for (id, chain) in &chains {
+            beacon.commit(id.clone(), chain.commitment(), 0);
+        }
+        (validators, beacon, chains)
+    }
+
+    fn reveals(chains: &[(String, HashChain)], epoch: u64) -> Vec<BeaconReveal> {
+        chains.iter()
+            .map(|(id, chain)| BeaconReveal { validator_id: id.clone(), reveal: chain.reveal(epoch).unwrap() })
+            .collect()
+    }
+
+    #[test]
+    fn test_beacon_output_verifies_against_recorded_reveals() {
+        let (mut validators, mut beacon, chains) = fixture();
+        let verifier = beacon.clone();
+        validators.advance_epoch();
+
+        // Leaving out one validator's reveal, even with a quorum left, is refused
+        assert!(matches!(
+            beacon.produce(1, &validators, reveals(&chains, 1)[1..].to_vec()),
+            Err(EnterpriseError::IntegrityError)
+        ));
+        let output = beacon.produce(1, &validators, reveals(&chains, 1)).unwrap();
+        assert_eq!(output.reveals.len(), 4);
+        assert!(verifier.verify(1, &validators, &output).is_ok());
+        beacon.accept(1, &validators, &output).unwrap();
+
+        // So is a recorded output whose value was derived from a subset
+        let mut subset = output.clone();
+        subset.reveals.remove(0);
+        subset.value = BeaconOutput::derive(1, &subset.previous, &subset.reveals);
+        assert!(matches!(verifier.verify(1, &validators, &subset), Err(EnterpriseError::IntegrityError)));
+
+        // Tampering with the value or any reveal is detected
+        let mut forged = output.clone();
+        forged.value[0] ^= 1;
+        assert!(matches!(verifier.verify(1, &validators, &forged), Err(EnterpriseError::IntegrityError)));
+        let mut forged = output.clone();
+        forged.reveals[0].reveal = [9; 32];
+        assert!(verifier.verify(1, &validators, &forged).is_err());
+
+        // A validator that stops revealing is only dropped by leaving the
+        // set; the next epoch chains from this one without it
+        validators.remove("v0");
+        validators.advance_epoch();
+        let next = beacon.produce(2, &validators, reveals(&chains, 2)[1..].to_vec()).unwrap();
+        assert_eq!((next.previous, next.reveals.len()), (output.value, 3));
+        assert_ne!(next.value, output.value);
+        beacon.accept(2, &validators, &next).unwrap();
+
+        // Once it rejoins, its chain still verifies across the gap
+        validators.add("v0", Keypair::generate(&mut rand::rngs::OsRng).public);
+        validators.advance_epoch();
+        let rejoined = beacon.produce(3, &validators, reveals(&chains, 3)).unwrap();
+        assert_eq!(rejoined.reveals.len(), 4);
+        beacon.accept(3, &validators, &rejoined).unwrap();
+
+        // Reusing an earlier reveal is not a contribution to a later epoch
+        validators.advance_epoch();
+        assert!(matches!(
+            beacon.produce(4, &validators, reveals(&chains, 3)),
+            Err(EnterpriseError::IntegrityError)
+        ));
+        assert!(matches!(HashChain::new([0; 32], MAX_CHAIN_GAP + 1, 0), Err(EnterpriseError::ResourceLimit(_))));
+
+        let candidates: Vec<String> = chains.iter().map(|(id, _)| id.clone()).collect();
+        let mut reversed = candidates.clone();
+        reversed.reverse();
+        assert_eq!(next.select(&candidates), next.select(&reversed));
+        assert!(next.select(&[]).is_none());
+    }
+}
@@ -0,0 +1,20 @@
+// certifier.rs - Ordering and Certification of Submitted Batches
+use async_trait::async_trait;
+
+use super::{CommittedBatch, StateOperation};
+use crate::EnterpriseError;
+
+/// Consensus round a replicated state machine submits its batches through
+///
+/// `certify` orders `ops` after every batch certified before it and returns
+/// the batch once the validator quorum of its epoch has signed it. Batches
+/// other nodes submitted are fetched with `batches_since`, so the submitter
+/// can apply them first and judge its own batch against the same state every
+/// other replica will.
+#[async_trait]
+pub trait BatchCertifier: std::fmt::Debug + Send + Sync {
+    async fn certify(&self, ops: Vec<StateOperation>) -> Result<CommittedBatch, EnterpriseError>;
+
+    /// Certified batches of `from_epoch` and later, in order
+    async fn batches_since(&self, from_epoch: u64) -> Result<Vec<CommittedBatch>, EnterpriseError>;
+}
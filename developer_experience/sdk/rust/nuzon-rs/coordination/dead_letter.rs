@@ -0,0 +1,113 @@
+// dead_letter.rs - Dead-Letter Store for Rejected State Operations
+use std::{collections::VecDeque, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::StateOperation;
+
+/// Rejected operations retained before the oldest are discarded
+pub const DEAD_LETTER_CAPACITY: usize = 10_000;
+
+/// Operation removed from a batch because it could not be applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub op: StateOperation,
+    pub reason: String,
+}
+
+/// Bounded queue of operations rejected during `submit_batch`
+///
+/// Lets callers find out what was dropped from a batch, and retry or report
+/// it, without the rejection holding up the rest of the batch.
+#[derive(Debug)]
+pub struct DeadLetterStore {
+    entries: Mutex<VecDeque<DeadLetter>>,
+    capacity: usize,
+}
+
+impl Default for DeadLetterStore {
+    fn default() -> Self {
+        Self::with_capacity(DEAD_LETTER_CAPACITY)
+    }
+}
+
+impl DeadLetterStore {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { entries: Mutex::new(VecDeque::new()), capacity: capacity.max(1) }
+    }
+
+    pub(super) fn record(&self, letter: DeadLetter) {
+        warn!(key = %letter.op.key, reason = %letter.reason, "State operation dead-lettered");
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(letter);
+    }
+
+    /// Copy of the retained entries, oldest first
+    pub fn entries(&self) -> Vec<DeadLetter> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    /// Remove and return every retained entry, oldest first
+    pub fn drain(&self) -> Vec<DeadLetter> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordination::{ReplicatedStateMachine, StateAction};
+    use tokio::runtime::Runtime;
+
+    fn put(key: &str, value: u8) -> StateOperation {
+        StateOperation { key: key.into(), action: StateAction::Put(vec![value]) }
+    }
+
+    #[test]
+    fn test_invalid_cas_is_dead_lettered_and_rest_commits() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let sm = ReplicatedStateMachine::new();
+            sm.submit_batch(vec![put("lock", 1)]).await.unwrap();
+
+            let stale_cas = StateOperation {
+                key: "lock".into(),
+                action: StateAction::CompareAndSwap { expected: Some(vec![0]), new: vec![2] },
+            };
+            let committed = sm.submit_batch(vec![put("a", 1), stale_cas.clone(), put("b", 2)]).await.unwrap();
+
+            assert_eq!(committed, 2);
+            assert_eq!(sm.get("a").await, Some(vec![1]));
+            assert_eq!(sm.get("b").await, Some(vec![2]));
+            assert_eq!(sm.get("lock").await, Some(vec![1]));
+
+            let letters = sm.dead_letters().drain();
+            assert_eq!(letters.len(), 1);
+            assert_eq!(letters[0].op, stale_cas);
+            assert!(letters[0].reason.contains("lock"), "{}", letters[0].reason);
+            assert!(sm.dead_letters().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_store_keeps_newest_at_capacity() {
+        let store = DeadLetterStore::with_capacity(2);
+        for i in 0..3 {
+            store.record(DeadLetter { op: put("k", i), reason: format!("r{}", i) });
+        }
+        let reasons: Vec<_> = store.entries().into_iter().map(|l| l.reason).collect();
+        assert_eq!(reasons, ["r1", "r2"]);
+    }
+}
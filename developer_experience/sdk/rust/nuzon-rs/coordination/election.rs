@@ -0,0 +1,290 @@
+// election.rs - Lease-Based Leader Election over ReplicatedStateMachine
+use std::{sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{info, warn};
+
+use super::{ReplicatedStateMachine, StateAction, StateOperation};
+use crate::{
+    clock::{Clock, SystemClock},
+    EnterpriseError,
+};
+
+/// State key holding the current lease
+pub const LEADER_LEASE_KEY: &str = "coordination/leader_lease";
+
+/// Lease record stored under `LEADER_LEASE_KEY`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Lease {
+    holder: String,
+    /// Incremented on every change of holder; fences stale leaders
+    epoch: u64,
+    /// Milliseconds since the Unix epoch
+    expires_at: u128,
+}
+
+/// This node's view of leadership
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Leadership {
+    Leader { epoch: u64, expires_at: u128 },
+    Follower { leader: Option<String>, epoch: u64 },
+}
+
+/// Where a state operation should be committed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitRoute {
+    /// Applied locally under the given leader epoch
+    Local { epoch: u64 },
+    /// Send to the current leader instead
+    Forward { leader: Option<String> },
+}
+
+/// Elects a single leader per epoch by compare-and-swap on a shared lease
+///
+/// The leader renews the lease on every `tick`; if it stalls past
+/// `lease_duration` any other node's next `tick` takes over with a higher
+/// epoch. Lease changes go through `submit_batch`, so once the state machine
+/// has a certifier, two nodes with stale views of the lease cannot both win
+/// it: the later swap is judged against the earlier one on every replica.
+pub struct LeaderElection {
+    node_id: String,
+    state: Arc<ReplicatedStateMachine>,
+    clock: Arc<dyn Clock>,
+    lease_duration: Duration,
+    leadership: watch::Sender<Leadership>,
+}
+
+impl LeaderElection {
+    pub fn new(node_id: impl Into<String>, state: Arc<ReplicatedStateMachine>, lease_duration: Duration) -> Self {
+        Self {
+            node_id: node_id.into(),
+            state,
+            clock: Arc::new(SystemClock),
+            lease_duration,
+            leadership: watch::channel(Leadership::Follower { leader: None, epoch: 0 }).0,
+        }
+    }
+
+    /// Replace the wall clock used for lease expiry
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Whether this node holds an unexpired lease
+    pub fn is_leader(&self) -> bool {
+        match *self.leadership.borrow() {
+            Leadership::Leader { expires_at, .. } => self.clock.now_millis() < expires_at,
+            Leadership::Follower { .. } => false,
+        }
+    }
+
+    /// Receiver notified whenever this node gains or loses leadership
+    pub fn watch(&self) -> watch::Receiver<Leadership> {
+        self.leadership.subscribe()
+    }
+
+    /// Acquire, renew or observe the lease once
+    pub async fn tick(&self) -> Result<Leadership, EnterpriseError> {
+        let now = self.clock.now_millis();
+        let (current_raw, current) = self.read_lease().await?;
+
+        let candidate = match &current {
+            Some(lease) if lease.holder == self.node_id => Some(lease.epoch),
+            Some(lease) if lease.expires_at > now => None,
+            Some(lease) => Some(lease.epoch + 1),
+            None => Some(1),
+        };
+
+        let leadership = match candidate {
+            Some(epoch) => {
+                let lease = Lease {
+                    holder: self.node_id.clone(),
+                    epoch,
+                    expires_at: now + self.lease_duration.as_millis(),
+                };
+                match self.swap_lease(current_raw, &lease).await {
+                    Ok(()) => Leadership::Leader { epoch, expires_at: lease.expires_at },
+                    // Another node won the race; report whoever holds it now
+                    Err(EnterpriseError::IntegrityError) => self.follower().await?,
+                    Err(e) => return Err(e),
+                }
+            }
+            None => {
+                let lease = current.expect("only a live foreign lease yields no candidate");
+                Leadership::Follower { leader: Some(lease.holder), epoch: lease.epoch }
+            }
+        };
+
+        self.publish(leadership.clone());
+        Ok(leadership)
+    }
+
+    /// Apply `op` if this node leads; otherwise name the node to forward to
+    pub async fn route(&self, op: StateOperation) -> Result<CommitRoute, EnterpriseError> {
+        let leadership = self.leadership.borrow().clone();
+        match leadership {
+            Leadership::Leader { epoch, .. } if self.is_leader() => {
+                self.state.apply_operation(op).await?;
+                Ok(CommitRoute::Local { epoch })
+            }
+            Leadership::Leader { .. } => Ok(CommitRoute::Forward { leader: None }),
+            Leadership::Follower { leader, .. } => Ok(CommitRoute::Forward { leader }),
+        }
+    }
+
+    /// Tick every third of the lease duration until the handle is aborted
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let election = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(election.lease_duration / 3);
+            loop {
+                interval.tick().await;
+                if let Err(e) = election.tick().await {
+                    warn!(node = %election.node_id, error = %e, "Leader election tick failed");
+                }
+            }
+        })
+    }
+
+    async fn read_lease(&self) -> Result<(Option<Vec<u8>>, Option<Lease>), EnterpriseError> {
+        let raw = self.state.get(LEADER_LEASE_KEY).await;
+        let lease = raw.as_deref()
+            .map(serde_json::from_slice)
+            .transpose()
+            .map_err(|_| EnterpriseError::IntegrityError)?;
+        Ok((raw, lease))
+    }
+
+    async fn swap_lease(&self, expected: Option<Vec<u8>>, lease: &Lease) -> Result<(), EnterpriseError> {
+        let new = serde_json::to_vec(lease).map_err(|_| EnterpriseError::CriticalFailure)?;
+        let committed = self.state.submit_batch(vec![StateOperation {
+            key: LEADER_LEASE_KEY.into(),
+            action: StateAction::CompareAndSwap { expected, new },
+        }]).await?;
+        // Unreplicated state machines dead-letter the losing swap instead
+        match committed {
+            0 => Err(EnterpriseError::IntegrityError),
+            _ => Ok(()),
+        }
+    }
+
+    async fn follower(&self) -> Result<Leadership, EnterpriseError> {
+        let (_, lease) = self.read_lease().await?;
+        Ok(match lease {
+            Some(lease) => Leadership::Follower { leader: Some(lease.holder), epoch: lease.epoch },
+            None => Leadership::Follower { leader: None, epoch: 0 },
+        })
+    }
+
+    fn publish(&self, leadership: Leadership) {
+        self.leadership.send_if_modified(|current| {
+            let was_leader = matches!(current, Leadership::Leader { .. });
+            let is_leader = matches!(leadership, Leadership::Leader { .. });
+            if was_leader != is_leader {
+                info!(node = %self.node_id, ?leadership, "Leadership changed");
+            }
+            // Renewals only move the expiry; don't wake watchers for them
+            let changed = was_leader != is_leader || match (&*current, &leadership) {
+                (Leadership::Follower { leader: a, .. }, Leadership::Follower { leader: b, .. }) => a != b,
+                _ => false,
+            };
+            *current = leadership;
+            changed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        coordination::{testing::LocalQuorum, BatchCertifier},
+    };
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_single_leader_and_failover_on_lease_expiry() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let state = Arc::new(ReplicatedStateMachine::new());
+            let clock = MockClock::default();
+            let lease = Duration::from_secs(10);
+            let a = LeaderElection::new("a", state.clone(), lease).with_clock(Arc::new(clock.clone()));
+            let b = LeaderElection::new("b", state.clone(), lease).with_clock(Arc::new(clock.clone()));
+            let mut b_watch = b.watch();
+
+            assert!(matches!(a.tick().await.unwrap(), Leadership::Leader { epoch: 1, .. }));
+            assert_eq!(
+                b.tick().await.unwrap(),
+                Leadership::Follower { leader: Some("a".into()), epoch: 1 }
+            );
+            assert!(a.is_leader() && !b.is_leader());
+
+            let op = StateOperation { key: "k".into(), action: StateAction::Put(vec![1]) };
+            assert_eq!(a.route(op.clone()).await.unwrap(), CommitRoute::Local { epoch: 1 });
+            assert_eq!(b.route(op.clone()).await.unwrap(), CommitRoute::Forward { leader: Some("a".into()) });
+
+            // Renewal within the lease keeps the same leader and epoch
+            clock.advance(Duration::from_secs(6));
+            assert!(matches!(a.tick().await.unwrap(), Leadership::Leader { epoch: 1, .. }));
+            clock.advance(Duration::from_secs(6));
+            assert_eq!(
+                b.tick().await.unwrap(),
+                Leadership::Follower { leader: Some("a".into()), epoch: 1 }
+            );
+            b_watch.borrow_and_update();
+
+            // Leader stops renewing; its lease lapses and the follower takes over
+            clock.advance(Duration::from_secs(11));
+            assert!(!a.is_leader());
+            assert!(matches!(b.tick().await.unwrap(), Leadership::Leader { epoch: 2, .. }));
+            assert!(b_watch.has_changed().unwrap());
+            assert!(matches!(*b_watch.borrow_and_update(), Leadership::Leader { epoch: 2, .. }));
+
+            // The stale leader learns it was replaced
+            assert_eq!(
+                a.tick().await.unwrap(),
+                Leadership::Follower { leader: Some("b".into()), epoch: 2 }
+            );
+            assert!(b.is_leader() && !a.is_leader());
+        });
+    }
+
+    #[test]
+    fn test_replicas_with_stale_views_elect_one_leader() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let quorum = Arc::new(LocalQuorum::new(4));
+            let (replica_a, replica_b) = (Arc::new(quorum.replica()), Arc::new(quorum.replica()));
+            let clock = MockClock::default();
+            let lease = Duration::from_secs(10);
+            let a = LeaderElection::new("a", replica_a.clone(), lease).with_clock(Arc::new(clock.clone()));
+            let b = LeaderElection::new("b", replica_b.clone(), lease).with_clock(Arc::new(clock.clone()));
+
+            // b has not seen a's lease yet, but its swap is certified after a's
+            assert!(matches!(a.tick().await.unwrap(), Leadership::Leader { epoch: 1, .. }));
+            assert_eq!(replica_b.get(LEADER_LEASE_KEY).await, None);
+            assert_eq!(
+                b.tick().await.unwrap(),
+                Leadership::Follower { leader: Some("a".into()), epoch: 1 }
+            );
+            assert!(a.is_leader() && !b.is_leader());
+
+            // After failover the old leader's stale renewal loses the same way
+            clock.advance(Duration::from_secs(11));
+            assert!(matches!(b.tick().await.unwrap(), Leadership::Leader { epoch: 2, .. }));
+            assert_eq!(
+                a.tick().await.unwrap(),
+                Leadership::Follower { leader: Some("b".into()), epoch: 2 }
+            );
+
+            let epoch = replica_b.applied_epoch().await;
+            replica_b.catch_up(epoch, quorum.batches_since(epoch).await.unwrap()).await.unwrap();
+            assert_eq!(replica_a.applied_sequence().await, replica_b.applied_sequence().await);
+            assert_eq!(replica_a.committed_state().await, replica_b.committed_state().await);
+        });
+    }
+}
@@ -0,0 +1,282 @@
+// heartbeat.rs - Signed Agent Liveness Reporting
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    EnterpriseError,
+};
+
+/// Liveness report an agent signs and sends every `HeartbeatConfig::interval`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub agent_id: String,
+    /// Incremented each time the agent restarts
+    pub generation: u64,
+    /// Position within the generation; the first heartbeat is 1
+    pub seq: u64,
+    /// Sender's milliseconds since the Unix epoch
+    pub timestamp: u128,
+    pub sig: Vec<u8>,
+}
+
+impl Heartbeat {
+    pub fn signed(agent_id: impl Into<String>, generation: u64, seq: u64, timestamp: u128, key: &Keypair) -> Self {
+        let mut heartbeat = Self { agent_id: agent_id.into(), generation, seq, timestamp, sig: Vec::new() };
+        heartbeat.sig = key.sign(&heartbeat.signing_bytes()).to_bytes().to_vec();
+        heartbeat
+    }
+
+    /// Everything but the signature, domain-separated from other signed payloads
+    fn signing_bytes(&self) -> Vec<u8> {
+        [
+            b"nuzon-heartbeat:".as_slice(),
+            self.agent_id.as_bytes(),
+            &self.generation.to_be_bytes(),
+            &self.seq.to_be_bytes(),
+            &self.timestamp.to_be_bytes(),
+        ].concat()
+    }
+}
+
+/// Coordinator's view of one agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    Alive,
+    /// Missed `suspect_after` heartbeats
+    Suspect,
+    /// Missed `dead_after` heartbeats
+    Dead,
+}
+
+/// Heartbeat cadence and how many missed beats mark an agent down
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+    pub suspect_after: u32,
+    pub dead_after: u32,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self { interval: Duration::from_secs(5), suspect_after: 3, dead_after: 12 }
+    }
+}
+
+#[derive(Debug)]
+struct AgentRecord {
+    key: PublicKey,
+    generation: u64,
+    seq: u64,
+    /// Coordinator time of the last accepted heartbeat
+    last_seen: u128,
+    liveness: Liveness,
+}
+
+/// Verifies agent heartbeats and tracks which agents are still alive
+///
+/// Liveness is judged by when heartbeats arrive on the coordinator's clock,
+/// so agent clock skew cannot keep a silent agent alive.
+pub struct HeartbeatMonitor {
+    config: HeartbeatConfig,
+    clock: Arc<dyn Clock>,
+    agents: Mutex<HashMap<String, AgentRecord>>,
+    liveness: watch::Sender<HashMap<String, Liveness>>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(config: HeartbeatConfig) -> Self {
+        Self {
+            config,
+            clock: Arc::new(SystemClock),
+            agents: Mutex::new(HashMap::new()),
+            liveness: watch::channel(HashMap::new()).0,
+        }
+    }
+
+    /// Replace the wall clock used to time missed heartbeats
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Expect heartbeats from `agent_id` signed by `key`, starting now
+    pub fn register(&self, agent_id: impl Into<String>, key: PublicKey) {
+        let record = AgentRecord {
+            key,
+            generation: 0,
+            seq: 0,
+            last_seen: self.clock.now_millis(),
+            liveness: Liveness::Alive,
+        };
+        self.lock().insert(agent_id.into(), record);
+        self.publish();
+    }
+
+    /// Receiver updated whenever any agent's liveness changes
+    pub fn watch(&self) -> watch::Receiver<HashMap<String, Liveness>> {
+        self.liveness.subscribe()
+    }
+
+    /// Accept a heartbeat and mark its agent alive
+    ///
+    /// An unregistered agent is an `AuthError`, a bad signature an
+    /// `IntegrityError`, and a heartbeat from an older generation or not
+    /// after the last one accepted a `ProtocolError`.
+    pub fn receive(&self, heartbeat: &Heartbeat) -> Result<(), EnterpriseError> {
+        let mut agents = self.lock();
+        let agent = agents.get_mut(&heartbeat.agent_id)
+            .ok_or_else(|| EnterpriseError::AuthError(format!("unknown agent {}", heartbeat.agent_id)))?;
+
+        let verified = Signature::from_bytes(&heartbeat.sig)
+            .and_then(|sig| agent.key.verify(&heartbeat.signing_bytes(), &sig));
+        if verified.is_err() {
+            warn!(agent = %heartbeat.agent_id, "Rejected heartbeat with a bad signature");
+            return Err(EnterpriseError::IntegrityError);
+        }
+        if (heartbeat.generation, heartbeat.seq) <= (agent.generation, agent.seq) {
+            warn!(
+                agent = %heartbeat.agent_id,
+                generation = heartbeat.generation,
+                seq = heartbeat.seq,
+                "Rejected stale heartbeat"
+            );
+            return Err(EnterpriseError::ProtocolError);
+        }
+
+        agent.generation = heartbeat.generation;
+        agent.seq = heartbeat.seq;
+        agent.last_seen = self.clock.now_millis();
+        let revived = agent.liveness != Liveness::Alive;
+        agent.liveness = Liveness::Alive;
+        drop(agents);
+
+        if revived {
+            info!(agent = %heartbeat.agent_id, generation = heartbeat.generation, "Agent heartbeats resumed");
+            self.publish();
+        }
+        Ok(())
+    }
+
+    /// Re-evaluate every agent against the missed-heartbeat thresholds
+    pub fn tick(&self) {
+        let now = self.clock.now_millis();
+        let interval = self.config.interval.as_millis().max(1);
+        let mut changed = false;
+        for (agent_id, agent) in self.lock().iter_mut() {
+            let missed = now.saturating_sub(agent.last_seen) / interval;
+            let liveness = if missed >= self.config.dead_after as u128 {
+                Liveness::Dead
+            } else if missed >= self.config.suspect_after as u128 {
+                Liveness::Suspect
+            } else {
+                Liveness::Alive
+            };
+            if liveness != agent.liveness {
+                warn!(agent = %agent_id, missed, ?liveness, "Agent heartbeats missed");
+                agent.liveness = liveness;
+                changed = true;
+            }
+        }
+        if changed {
+            self.publish();
+        }
+    }
+
+    /// Tick every heartbeat interval until the handle is aborted
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(monitor.config.interval);
+            loop {
+                interval.tick().await;
+                monitor.tick();
+            }
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, AgentRecord>> {
+        self.agents.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn publish(&self) {
+        let liveness = self.lock().iter().map(|(id, agent)| (id.clone(), agent.liveness)).collect();
+        self.liveness.send_replace(liveness);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn keypair() -> Keypair {
+        Keypair::generate(&mut rand::rngs::OsRng)
+    }
+
+    fn monitor(clock: &Arc<MockClock>) -> HeartbeatMonitor {
+        HeartbeatMonitor::new(HeartbeatConfig { interval: Duration::from_secs(1), suspect_after: 3, dead_after: 6 })
+            .with_clock(clock.clone())
+    }
+
+    #[test]
+    fn test_silent_agent_becomes_suspect_then_dead() {
+        let clock = Arc::new(MockClock::default());
+        let monitor = monitor(&clock);
+        let (steady, silent) = (keypair(), keypair());
+        monitor.register("steady", steady.public);
+        monitor.register("silent", silent.public);
+        let liveness = monitor.watch();
+
+        let mut states = Vec::new();
+        for seq in 1..=7 {
+            clock.advance(Duration::from_secs(1));
+            monitor.receive(&Heartbeat::signed("steady", 1, seq, clock.now_millis(), &steady)).unwrap();
+            monitor.tick();
+            let current = liveness.borrow().clone();
+            assert_eq!(current["steady"], Liveness::Alive);
+            states.push(current["silent"]);
+        }
+        use Liveness::*;
+        assert_eq!(states, [Alive, Alive, Suspect, Suspect, Suspect, Dead, Dead]);
+
+        // A restarted agent comes back under a new generation
+        monitor.receive(&Heartbeat::signed("silent", 2, 1, clock.now_millis(), &silent)).unwrap();
+        assert_eq!(liveness.borrow()["silent"], Alive);
+    }
+
+    #[test]
+    fn test_stale_and_forged_heartbeats_rejected() {
+        let clock = Arc::new(MockClock::default());
+        let monitor = monitor(&clock);
+        let agent = keypair();
+        monitor.register("agent", agent.public);
+        let now = clock.now_millis();
+
+        monitor.receive(&Heartbeat::signed("agent", 2, 5, now, &agent)).unwrap();
+        for (generation, seq) in [(2, 5), (2, 4), (1, 9)] {
+            assert!(matches!(
+                monitor.receive(&Heartbeat::signed("agent", generation, seq, now, &agent)),
+                Err(EnterpriseError::ProtocolError)
+            ));
+        }
+
+        let forged = Heartbeat::signed("agent", 3, 1, now, &keypair());
+        assert!(matches!(monitor.receive(&forged), Err(EnterpriseError::IntegrityError)));
+        let mut altered = Heartbeat::signed("agent", 3, 1, now, &agent);
+        altered.seq = 2;
+        assert!(matches!(monitor.receive(&altered), Err(EnterpriseError::IntegrityError)));
+        assert!(matches!(
+            monitor.receive(&Heartbeat::signed("stranger", 1, 1, now, &agent)),
+            Err(EnterpriseError::AuthError(_))
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+// nonce.rs - Replay-Protected Nonce Store over ReplicatedStateMachine
+use std::{
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tracing::warn;
+
+use super::{ReplicatedStateMachine, StateAction, StateOperation};
+use crate::{
+    clock::{Clock, SystemClock},
+    EnterpriseError,
+};
+
+/// State key prefix for recorded nonces
+pub const NONCE_KEY_PREFIX: &str = "coordination/nonce/";
+/// Records between automatic sweeps of expired nonces
+const PURGE_EVERY: u64 = 1024;
+
+/// Shared seen-nonce set with per-entry expiry
+///
+/// Each nonce is stored under `NONCE_KEY_PREFIX` with its expiry in Unix
+/// milliseconds. Recording is a compare-and-swap submitted through
+/// `submit_batch`, so of several callers presenting the same nonce exactly
+/// one succeeds, on one node or across replicas sharing a certifier.
+pub struct NonceStore {
+    state: Arc<ReplicatedStateMachine>,
+    clock: Arc<dyn Clock>,
+    records: AtomicU64,
+}
+
+impl NonceStore {
+    pub fn new(state: Arc<ReplicatedStateMachine>) -> Self {
+        Self { state, clock: Arc::new(SystemClock), records: AtomicU64::new(0) }
+    }
+
+    /// Replace the wall clock used for expiry
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record `nonce` for `ttl` if unseen; `false` means it is a replay
+    pub async fn check_and_record(&self, nonce: &[u8], ttl: Duration) -> Result<bool, EnterpriseError> {
+        if self.records.fetch_add(1, Ordering::Relaxed) % PURGE_EVERY == PURGE_EVERY - 1 {
+            if let Err(e) = self.purge_expired().await {
+                warn!(error = %e, "Nonce sweep failed");
+            }
+        }
+
+        let key = nonce_key(nonce);
+        let now = self.clock.now_millis();
+        let expected = match self.state.get(&key).await {
+            Some(raw) if expiry(&raw).map_or(true, |expires_at| expires_at > now) => return Ok(false),
+            // Expired entries may be reused
+            current => current,
+        };
+
+        let new = (now + ttl.as_millis()).to_be_bytes().to_vec();
+        let op = StateOperation { key, action: StateAction::CompareAndSwap { expected, new } };
+        match self.state.submit_batch(vec![op]).await {
+            Ok(1) => Ok(true),
+            // A concurrent caller recorded it first
+            Ok(_) | Err(EnterpriseError::IntegrityError) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Drop every expired nonce, returning how many were removed
+    ///
+    /// Each delete only applies while the entry still holds the expiry it
+    /// was swept for, so a nonce re-recorded in the meantime survives. When
+    /// replicated, one such race rejects the whole sweep and the next one
+    /// retries it.
+    pub async fn purge_expired(&self) -> Result<usize, EnterpriseError> {
+        let now = self.clock.now_millis();
+        let expired: Vec<_> = self.state.state.read().await.iter()
+            .filter(|(key, raw)| {
+                key.starts_with(NONCE_KEY_PREFIX) && expiry(raw).map_or(true, |expires_at| expires_at <= now)
+            })
+            .map(|(key, raw)| StateOperation {
+                key: key.clone(),
+                action: StateAction::CompareAndDelete { expected: raw.clone() },
+            })
+            .collect();
+        if expired.is_empty() {
+            return Ok(0);
+        }
+
+        match self.state.submit_batch(expired).await {
+            Err(EnterpriseError::IntegrityError) => Ok(0),
+            purged => purged,
+        }
+    }
+}
+
+fn nonce_key(nonce: &[u8]) -> String {
+    let mut key = String::with_capacity(NONCE_KEY_PREFIX.len() + nonce.len() * 2);
+    key.push_str(NONCE_KEY_PREFIX);
+    for byte in nonce {
+        let _ = write!(key, "{:02x}", byte);
+    }
+    key
+}
+
+fn expiry(raw: &[u8]) -> Option<u128> {
+    raw.try_into().ok().map(u128::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        coordination::{testing::LocalQuorum, BatchCertifier},
+    };
+    use std::collections::HashMap;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_duplicate_rejected_until_expiry() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = MockClock::default();
+            let state = Arc::new(ReplicatedStateMachine::new());
+            let store = NonceStore::new(state).with_clock(Arc::new(clock.clone()));
+            let ttl = Duration::from_secs(30);
+
+            assert!(store.check_and_record(b"n-1", ttl).await.unwrap());
+            assert!(!store.check_and_record(b"n-1", ttl).await.unwrap());
+            assert!(store.check_and_record(b"n-2", ttl).await.unwrap());
+
+            clock.advance(Duration::from_secs(29));
+            assert!(!store.check_and_record(b"n-1", ttl).await.unwrap());
+
+            clock.advance(Duration::from_secs(2));
+            assert!(store.check_and_record(b"n-1", ttl).await.unwrap());
+            assert!(!store.check_and_record(b"n-1", ttl).await.unwrap());
+
+            // n-2 has lapsed and is swept; the re-recorded n-1 is kept
+            assert_eq!(store.purge_expired().await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn test_replay_window_and_sweeps_reach_replicas() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let quorum = Arc::new(LocalQuorum::new(4));
+            let (replica_a, replica_b) = (Arc::new(quorum.replica()), Arc::new(quorum.replica()));
+            let clock = MockClock::default();
+            let a = NonceStore::new(replica_a.clone()).with_clock(Arc::new(clock.clone()));
+            let b = NonceStore::new(replica_b.clone()).with_clock(Arc::new(clock.clone()));
+            let ttl = Duration::from_secs(30);
+
+            // Recorded through a, so replaying it at b is refused
+            assert!(a.check_and_record(b"n-1", ttl).await.unwrap());
+            assert!(a.check_and_record(b"n-2", ttl).await.unwrap());
+            assert!(!b.check_and_record(b"n-1", ttl).await.unwrap());
+
+            // A sweep at a removes the lapsed entries from b's state too
+            clock.advance(Duration::from_secs(31));
+            assert_eq!(a.purge_expired().await.unwrap(), 2);
+            let epoch = replica_b.applied_epoch().await;
+            replica_b.catch_up(epoch, quorum.batches_since(epoch).await.unwrap()).await.unwrap();
+            assert_eq!(replica_b.committed_state().await, HashMap::new());
+        });
+    }
+
+    #[test]
+    fn test_concurrent_callers_record_once() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Arc::new(NonceStore::new(Arc::new(ReplicatedStateMachine::new())));
+            let attempts = (0..16).map(|_| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    store.check_and_record(b"shared", Duration::from_secs(60)).await.unwrap()
+                })
+            });
+            let mut recorded = 0;
+            for attempt in attempts.collect::<Vec<_>>() {
+                recorded += attempt.await.unwrap() as usize;
+            }
+            assert_eq!(recorded, 1);
+        });
+    }
+}
@@ -0,0 +1,298 @@
+// repair.rs - Snapshot Restore and Read-Repair for ReplicatedStateMachine
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::info;
+
+use super::{CommittedBatch, ReplicatedStateMachine, ValidatorSet};
+use crate::EnterpriseError;
+
+/// Certified batches retained for serving lagging peers
+pub const BATCH_LOG_CAPACITY: usize = 1024;
+
+/// Ring buffer of recently committed certified batches, served by epoch
+#[derive(Debug, Default)]
+pub struct BatchLog {
+    last_sequence: u64,
+    last_epoch: u64,
+    /// Newest epoch with batches no longer retained
+    truncated_epoch: Option<u64>,
+    recent: VecDeque<CommittedBatch>,
+}
+
+impl BatchLog {
+    /// Sequence of the last batch reflected in the committed state
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Epoch of the last batch reflected in the committed state
+    pub fn last_epoch(&self) -> u64 {
+        self.last_epoch
+    }
+
+    pub(super) fn record(&mut self, batch: CommittedBatch) {
+        self.last_sequence = batch.header.sequence;
+        self.last_epoch = batch.header.epoch;
+        self.recent.push_back(batch);
+        if self.recent.len() > BATCH_LOG_CAPACITY {
+            self.truncated_epoch = self.recent.pop_front().map(|evicted| evicted.header.epoch);
+        }
+    }
+
+    /// Restart the log at a snapshot boundary
+    fn reset(&mut self, sequence: u64, epoch: u64) {
+        self.last_sequence = sequence;
+        self.last_epoch = epoch;
+        self.truncated_epoch = (sequence > 0).then_some(epoch);
+        self.recent.clear();
+    }
+
+    /// Retained batches of `from_epoch` and later, or `None` if a later
+    /// epoch has lost batches
+    ///
+    /// Batches at the start of `from_epoch` itself may have been evicted;
+    /// `catch_up` refuses the delta if the requester needed them.
+    pub fn since(&self, from_epoch: u64) -> Option<Vec<CommittedBatch>> {
+        if self.truncated_epoch.is_some_and(|truncated| truncated > from_epoch) {
+            return None;
+        }
+        Some(self.recent.iter()
+            .filter(|b| b.header.epoch >= from_epoch)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Committed state as of a log position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub sequence: u64,
+    /// Epoch of the batch at `sequence`
+    #[serde(default)]
+    pub epoch: u64,
+    pub state: HashMap<String, Vec<u8>>,
+}
+
+/// Peer able to supply certified batches a lagging node is missing
+pub trait BatchSource {
+    /// Certified batches of `from_epoch` and later, in order
+    fn batches_since(
+        &self,
+        from_epoch: u64,
+    ) -> impl Future<Output = Result<Vec<CommittedBatch>, EnterpriseError>> + Send;
+}
+
+impl BatchSource for ReplicatedStateMachine {
+    fn batches_since(
+        &self,
+        from_epoch: u64,
+    ) -> impl Future<Output = Result<Vec<CommittedBatch>, EnterpriseError>> + Send {
+        async move {
+            self.log.read().await
+                .since(from_epoch)
+                .ok_or_else(|| EnterpriseError::ResourceLimit(
+                    "batch log truncated; full snapshot transfer required".into()
+                ))
+        }
+    }
+}
+
+impl ReplicatedStateMachine {
+    /// State machine starting where `snapshot` was taken, as
+    /// `import_snapshot` would leave a fresh one
+    pub fn from_snapshot(validators: ValidatorSet, snapshot: StateSnapshot) -> Self {
+        let mut log = BatchLog::default();
+        log.reset(snapshot.sequence, snapshot.epoch);
+        Self {
+            state: Arc::new(RwLock::new(snapshot.state)),
+            log: Arc::new(RwLock::new(log)),
+            ..Self::with_validators(validators)
+        }
+    }
+
+    /// Sequence of the last certified batch applied locally
+    pub async fn applied_sequence(&self) -> u64 {
+        self.log.read().await.last_sequence()
+    }
+
+    /// Epoch of the last certified batch applied locally
+    pub async fn applied_epoch(&self) -> u64 {
+        self.log.read().await.last_epoch()
+    }
+
+    /// Consistent copy of the committed state and its log position
+    pub async fn export_snapshot(&self) -> StateSnapshot {
+        let log = self.log.read().await;
+        let state = self.state.read().await;
+        StateSnapshot { sequence: log.last_sequence(), epoch: log.last_epoch(), state: state.clone() }
+    }
+
+    /// Replace local state with a snapshot
+    ///
+    /// The snapshot may be stale; follow with `catch_up` or `repair_from`
+    /// before serving reads.
+    pub async fn import_snapshot(&self, snapshot: StateSnapshot) {
+        let mut log = self.log.write().await;
+        let mut state = self.state.write().await;
+        let replaced = std::mem::replace(&mut *state, snapshot.state);
+        log.reset(snapshot.sequence, snapshot.epoch);
+
+        if self.has_observers() {
+            let changes: Vec<_> = replaced.into_keys()
+                .filter(|key| !state.contains_key(key))
+                .map(|key| (key, None))
+                .chain(state.iter().map(|(key, value)| (key.clone(), Some(value.clone()))))
+                .collect();
+            drop(state);
+            drop(log);
+            self.notify(&changes);
+        }
+    }
+
+    /// Apply the certified batches of `from_epoch` and later, in order
+    ///
+    /// The whole delta is verified before any of it is applied, and it is
+    /// applied as one commit. Batches already applied are skipped, and so
+    /// are batches every replica rejects as invalid. A delta starting in a
+    /// later epoch than the local position, reaching back before
+    /// `from_epoch`, or with a hole in its sequence, is rejected as
+    /// `ProtocolError` and leaves the state untouched.
+    pub async fn catch_up(
+        &self,
+        from_epoch: u64,
+        batches: Vec<CommittedBatch>,
+    ) -> Result<(), EnterpriseError> {
+        if from_epoch > self.applied_epoch().await || batches.iter().any(|b| b.header.epoch < from_epoch) {
+            return Err(EnterpriseError::ProtocolError);
+        }
+        self.commit_certified_run(batches).await.map(|_| ())
+    }
+
+    /// Fetch and apply everything the source has beyond the local position
+    pub async fn repair_from(&self, source: &impl BatchSource) -> Result<(), EnterpriseError> {
+        let (applied, epoch) = (self.applied_sequence().await, self.applied_epoch().await);
+        let delta = source.batches_since(epoch).await?;
+        let missing = delta.iter().filter(|b| b.header.sequence > applied).count();
+        if missing > 0 {
+            info!(applied, epoch, missing, "Catching up from peer");
+        }
+        self.catch_up(epoch, delta).await
+    }
+
+    /// Commit a certified batch, first repairing from `source` if it reveals
+    /// that this node is behind
+    pub async fn commit_or_repair(
+        &self,
+        batch: CommittedBatch,
+        source: &impl BatchSource,
+    ) -> Result<(), EnterpriseError> {
+        if batch.header.sequence > self.applied_sequence().await + 1 {
+            self.repair_from(source).await?;
+        }
+        self.commit_certified(batch).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordination::{ConsensusHeader, QuorumVote, StateAction, StateOperation, ValidatorSet};
+    use ed25519_dalek::{Keypair, Signer};
+    use tokio::runtime::Runtime;
+
+    struct Quorum(Vec<(String, Keypair)>);
+
+    impl Quorum {
+        fn new() -> Self {
+            Self((0..3)
+                .map(|i| (format!("v{}", i), Keypair::generate(&mut rand::rngs::OsRng)))
+                .collect())
+        }
+
+        fn validators(&self) -> ValidatorSet {
+            ValidatorSet::new(self.0.iter().map(|(id, kp)| (id.clone(), kp.public)))
+        }
+
+        fn batch(&self, sequence: u64, key: &str, value: u8) -> CommittedBatch {
+            let mut batch = CommittedBatch {
+                header: ConsensusHeader {
+                    epoch: 0,
+                    view_number: 0,
+                    quorum_signature: Vec::new(),
+                    timestamp: sequence as u128,
+                    sequence,
+                    beacon: None,
+                },
+                ops: vec![StateOperation { key: key.into(), action: StateAction::Put(vec![value]) }],
+            };
+            let digest = batch.digest();
+            let votes: Vec<_> = self.0.iter()
+                .map(|(id, kp)| QuorumVote {
+                    validator_id: id.clone(),
+                    signature: kp.sign(&digest).to_bytes().to_vec(),
+                })
+                .collect();
+            batch.header.quorum_signature = QuorumVote::encode(&votes);
+            batch
+        }
+    }
+
+    #[test]
+    fn test_stale_snapshot_converges_after_catch_up() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let quorum = Quorum::new();
+            let batches: Vec<_> = (1..=6)
+                .map(|seq| quorum.batch(seq, &format!("k{}", seq % 3), seq as u8))
+                .collect();
+
+            let leader = ReplicatedStateMachine::with_validators(quorum.validators());
+            for batch in &batches[..2] {
+                leader.commit_certified(batch.clone()).await.unwrap();
+            }
+            let stale = leader.export_snapshot().await;
+            let stale_state = stale.state.clone();
+            for batch in &batches[2..5] {
+                leader.commit_certified(batch.clone()).await.unwrap();
+            }
+
+            // Restarted node restores the old snapshot and is behind the leader
+            let node = ReplicatedStateMachine::with_validators(quorum.validators());
+            node.import_snapshot(stale).await;
+            assert_eq!(node.applied_sequence().await, 2);
+
+            // A delta with a hole is rejected before any of it applies
+            let holed = vec![batches[2].clone(), batches[4].clone()];
+            assert!(matches!(node.catch_up(0, holed).await, Err(EnterpriseError::ProtocolError)));
+            assert_eq!(node.applied_sequence().await, 2);
+            assert_eq!(node.export_snapshot().await.state, stale_state);
+
+            // So is one starting in an epoch the node has not reached
+            let delta = leader.batches_since(node.applied_epoch().await).await.unwrap();
+            assert!(matches!(node.catch_up(1, delta.clone()).await, Err(EnterpriseError::ProtocolError)));
+
+            // The epoch's batches already applied are skipped
+            assert_eq!(delta.len(), 5);
+            node.catch_up(0, delta).await.unwrap();
+            assert_eq!(node.applied_sequence().await, 5);
+            assert_eq!(node.export_snapshot().await.state, leader.export_snapshot().await.state);
+
+            // A batch beyond the local log triggers repair before committing
+            let lagging = ReplicatedStateMachine::with_validators(quorum.validators());
+            assert!(matches!(
+                lagging.commit_certified(batches[5].clone()).await,
+                Err(EnterpriseError::ProtocolError)
+            ));
+            leader.commit_certified(batches[5].clone()).await.unwrap();
+            lagging.commit_or_repair(batches[5].clone(), &leader).await.unwrap();
+            assert_eq!(lagging.applied_sequence().await, 6);
+            assert_eq!(lagging.export_snapshot().await.state, leader.export_snapshot().await.state);
+        });
+    }
+}
@@ -0,0 +1,169 @@
+// revocation.rs - Replicated Key Revocation List
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use super::{ReplicatedStateMachine, StateAction, StateObserver, StateOperation};
+use crate::{
+    clock::{Clock, SystemClock},
+    EnterpriseError,
+};
+
+/// State key prefix for revoked keys
+pub const REVOCATION_KEY_PREFIX: &str = "coordination/revocation/";
+
+/// Identifier a public key is revoked under: its SHA-256 in lowercase hex
+pub fn key_id(public_key: &[u8]) -> String {
+    let mut id = String::with_capacity(64);
+    for byte in Sha256::digest(public_key) {
+        let _ = write!(id, "{:02x}", byte);
+    }
+    id
+}
+
+/// A key that must no longer be accepted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revocation {
+    pub key_id: String,
+    pub reason: String,
+    /// Milliseconds since the Unix epoch from which the key is refused
+    pub effective: u128,
+}
+
+/// Revoked keys as last committed, kept current by the state machine
+#[derive(Debug, Default)]
+struct RevokedKeys(RwLock<HashMap<String, Revocation>>);
+
+impl StateObserver for RevokedKeys {
+    fn committed(&self, key: &str, value: Option<&[u8]>) {
+        let Some(key_id) = key.strip_prefix(REVOCATION_KEY_PREFIX) else {
+            return;
+        };
+        let mut revoked = self.0.write().unwrap_or_else(|e| e.into_inner());
+        match value.map(serde_json::from_slice::<Revocation>) {
+            Some(Ok(revocation)) => {
+                revoked.insert(key_id.to_string(), revocation);
+            }
+            Some(Err(e)) => warn!(key_id, error = %e, "Ignoring malformed revocation entry"),
+            None => {
+                revoked.remove(key_id);
+            }
+        }
+    }
+}
+
+/// Compromised agent and node keys, shared by every subsystem that checks
+/// a peer's key
+///
+/// Each revocation is submitted under `REVOCATION_KEY_PREFIX` through
+/// `submit_batch`, so it reaches replicas with the certified batch carrying
+/// it, and every list observing a state machine picks it up as soon as that
+/// batch commits. Lookups are answered from memory, which keeps
+/// `is_revoked` cheap enough for the handshake and interaction paths. Node
+/// and handshake keys are identified by `key_id`; agents by their identity
+/// id.
+#[derive(Debug)]
+pub struct RevocationList {
+    state: Arc<ReplicatedStateMachine>,
+    revoked: Arc<RevokedKeys>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RevocationList {
+    /// List tracking the revocations committed to `state`
+    pub async fn attach(state: Arc<ReplicatedStateMachine>) -> Self {
+        let revoked = Arc::new(RevokedKeys::default());
+        state.observe(REVOCATION_KEY_PREFIX, revoked.clone()).await;
+        Self { state, revoked, clock: Arc::new(SystemClock) }
+    }
+
+    /// Replace the wall clock revocations take effect by
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Refuse `key_id` from `effective`, in milliseconds since the Unix epoch
+    ///
+    /// A compromise discovered late may be backdated; a planned retirement
+    /// may take effect in the future. Revoking an already revoked key
+    /// replaces its reason and effective time.
+    pub async fn revoke(&self, key_id: &str, reason: &str, effective: u128) -> Result<(), EnterpriseError> {
+        let revocation = Revocation { key_id: key_id.to_string(), reason: reason.to_string(), effective };
+        let value = serde_json::to_vec(&revocation).map_err(|_| EnterpriseError::ProtocolError)?;
+        let key = format!("{}{}", REVOCATION_KEY_PREFIX, key_id);
+        warn!(key_id, reason, effective = effective as u64, "Revoking key");
+        self.state.submit_batch(vec![StateOperation { key, action: StateAction::Put(value) }]).await.map(|_| ())
+    }
+
+    /// Whether `key_id` is revoked as of now
+    pub fn is_revoked(&self, key_id: &str) -> bool {
+        self.revocation(key_id)
+            .is_some_and(|revocation| revocation.effective <= self.clock.now_millis())
+    }
+
+    /// The first of `key_ids` revoked as of now, for a party presenting
+    /// several keys that must all still be trusted
+    pub fn first_revoked<S: AsRef<str>>(&self, key_ids: impl IntoIterator<Item = S>) -> Option<S> {
+        key_ids.into_iter().find(|key_id| self.is_revoked(key_id.as_ref()))
+    }
+
+    /// The revocation recorded for `key_id`, in effect or not
+    pub fn revocation(&self, key_id: &str) -> Option<Revocation> {
+        self.revoked.0.read().unwrap_or_else(|e| e.into_inner()).get(key_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        coordination::{testing::LocalQuorum, BatchCertifier},
+    };
+    use std::time::{Duration, SystemTime};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_revocations_reach_replicas_and_take_effect_on_time() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000)));
+            let quorum = Arc::new(LocalQuorum::new(4));
+            let leader = Arc::new(quorum.replica());
+            let replica = Arc::new(quorum.replica());
+            let local = RevocationList::attach(leader.clone()).await.with_clock(clock.clone());
+            let remote = RevocationList::attach(replica.clone()).await.with_clock(clock.clone());
+
+            let compromised = key_id(b"node-7 public key");
+            let retiring = key_id(b"node-8 public key");
+            let now = clock.now_millis();
+            local.revoke(&compromised, "key material leaked", now - 60_000).await.unwrap();
+            local.revoke(&retiring, "scheduled rotation", now + 60_000).await.unwrap();
+            assert!(local.is_revoked(&compromised));
+            assert!(!local.is_revoked(&retiring));
+            assert!(!remote.is_revoked(&compromised));
+
+            // The replica learns of them from the certified log alone
+            replica.catch_up(0, quorum.batches_since(0).await.unwrap()).await.unwrap();
+            assert!(remote.is_revoked(&compromised));
+            assert_eq!(remote.revocation(&compromised).unwrap().reason, "key material leaked");
+
+            clock.advance(Duration::from_secs(60));
+            assert!(remote.is_revoked(&retiring));
+            assert!(!remote.is_revoked(&key_id(b"node-9 public key")));
+            let presented = [key_id(b"node-9 public key"), retiring.clone()];
+            assert_eq!(remote.first_revoked(&presented), Some(&retiring));
+
+            // A list attached later sees what was already committed
+            let late = RevocationList::attach(replica).await.with_clock(clock);
+            assert!(late.is_revoked(&compromised));
+        });
+    }
+}
@@ -0,0 +1,227 @@
+// testing.rs - Deterministic Commit Harness for ReplicatedStateMachine
+//
+// Compiled for unit tests and behind the `testing` feature so downstream
+// crates can drive commit timing explicitly instead of racing the
+// automatic batch trigger.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+
+use super::{
+    BatchCertifier, CommitStage, CommittedBatch, ConsensusHeader, QuorumVote, ReplicatedStateMachine,
+    StateOperation, ValidatorSet,
+};
+use crate::EnterpriseError;
+
+impl ReplicatedStateMachine {
+    /// Disable the automatic batch commit; batches only commit on `force_commit`
+    pub fn with_manual_commit(mut self) -> Self {
+        self.auto_commit = false;
+        self
+    }
+
+    /// Commit every pending operation now, as the automatic trigger would
+    ///
+    /// On failure the batch is returned to the pending queue unchanged so the
+    /// test can retry it.
+    pub async fn force_commit(&self) -> Result<(), EnterpriseError> {
+        let mut pending = self.pending_ops.lock().await;
+        let batch = std::mem::take(&mut *pending);
+
+        if let Err(e) = self.submit_batch(batch.clone()).await {
+            *pending = batch;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Fail the next commit when it reaches `stage`
+    pub fn inject_failure(&self, stage: CommitStage) {
+        *self.injected_fault.lock().unwrap_or_else(|e| e.into_inner()) = Some(stage);
+    }
+
+    /// Number of operations not yet committed
+    pub async fn pending_len(&self) -> usize {
+        self.pending_ops.lock().await.len()
+    }
+
+    /// Copy of the committed state
+    pub async fn committed_state(&self) -> HashMap<String, Vec<u8>> {
+        self.state.read().await.clone()
+    }
+
+    /// Enqueue operations without triggering a commit
+    pub async fn enqueue_all(&self, ops: impl IntoIterator<Item = StateOperation>) {
+        self.pending_ops.lock().await.extend(ops);
+    }
+}
+
+/// In-process certifier signing every batch with a fixed validator quorum
+///
+/// Stands in for the consensus round, so state machines in one test can
+/// replicate through it while each keeps its own state. Keys are derived
+/// from the validator index, making runs reproducible.
+#[derive(Debug)]
+pub struct LocalQuorum {
+    validators: Vec<(String, Keypair)>,
+    log: Mutex<Vec<CommittedBatch>>,
+}
+
+impl LocalQuorum {
+    pub fn new(size: u8) -> Self {
+        let validators = (0..size)
+            .map(|i| {
+                let secret = SecretKey::from_bytes(&[i + 1; 32]).expect("seed is 32 bytes");
+                let public = PublicKey::from(&secret);
+                (format!("validator-{}", i), Keypair { secret, public })
+            })
+            .collect();
+        Self { validators, log: Mutex::new(Vec::new()) }
+    }
+
+    /// Genesis membership of the quorum
+    pub fn validators(&self) -> ValidatorSet {
+        ValidatorSet::new(self.validators.iter().map(|(id, kp)| (id.clone(), kp.public)))
+    }
+
+    /// A fresh replica submitting its batches through this quorum
+    pub fn replica(self: &Arc<Self>) -> ReplicatedStateMachine {
+        ReplicatedStateMachine::with_validators(self.validators()).with_certifier(self.clone())
+    }
+}
+
+#[async_trait]
+impl BatchCertifier for LocalQuorum {
+    async fn certify(&self, ops: Vec<StateOperation>) -> Result<CommittedBatch, EnterpriseError> {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        let sequence = log.len() as u64 + 1;
+        let mut batch = CommittedBatch {
+            header: ConsensusHeader {
+                epoch: 0,
+                view_number: 0,
+                quorum_signature: Vec::new(),
+                timestamp: sequence as u128,
+                sequence,
+                beacon: None,
+            },
+            ops,
+        };
+        let digest = batch.digest();
+        let votes: Vec<_> = self.validators.iter()
+            .map(|(id, kp)| QuorumVote { validator_id: id.clone(), signature: kp.sign(&digest).to_bytes().to_vec() })
+            .collect();
+        batch.header.quorum_signature = QuorumVote::encode(&votes);
+        log.push(batch.clone());
+        Ok(batch)
+    }
+
+    async fn batches_since(&self, from_epoch: u64) -> Result<Vec<CommittedBatch>, EnterpriseError> {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(log.iter().filter(|b| b.header.epoch >= from_epoch).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordination::StateAction;
+    use proptest::prelude::*;
+    use tokio::runtime::Runtime;
+
+    fn put(key: u8, value: u8) -> StateOperation {
+        StateOperation {
+            key: format!("k{}", key),
+            action: StateAction::Put(vec![value]),
+        }
+    }
+
+    #[test]
+    fn test_manual_commit_and_injected_failure() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let sm = ReplicatedStateMachine::new().with_manual_commit();
+            for i in 0..150 {
+                sm.apply_operation(put(i, i)).await.unwrap();
+            }
+            assert_eq!(sm.pending_len().await, 150);
+            assert!(sm.committed_state().await.is_empty());
+
+            sm.inject_failure(CommitStage::Apply);
+            assert!(matches!(sm.force_commit().await, Err(EnterpriseError::CriticalFailure)));
+            assert_eq!(sm.pending_len().await, 150);
+            assert!(sm.committed_state().await.is_empty());
+
+            sm.force_commit().await.unwrap();
+            assert_eq!(sm.pending_len().await, 0);
+            assert_eq!(sm.committed_state().await.len(), 150);
+        });
+    }
+
+    proptest! {
+        #[test]
+        fn prop_commit_boundaries_do_not_change_state(
+            values in proptest::collection::btree_map(any::<u8>(), any::<u8>(), 0..64),
+            chunk in 1usize..16,
+        ) {
+            let ops: Vec<_> = values.iter().map(|(k, v)| put(*k, *v)).collect();
+            let rt = Runtime::new().unwrap();
+
+            let (single, chunked) = rt.block_on(async {
+                let single = ReplicatedStateMachine::new().with_manual_commit();
+                single.enqueue_all(ops.clone()).await;
+                single.force_commit().await.unwrap();
+
+                let chunked = ReplicatedStateMachine::new().with_manual_commit();
+                for batch in ops.chunks(chunk) {
+                    chunked.enqueue_all(batch.iter().cloned()).await;
+                    chunked.force_commit().await.unwrap();
+                }
+
+                (single.committed_state().await, chunked.committed_state().await)
+            });
+
+            prop_assert_eq!(single, chunked);
+        }
+
+        #[test]
+        fn prop_enqueue_order_does_not_change_state(
+            ops in proptest::collection::vec((0u8..4, any::<u8>(), 0u8..3), 1..48)
+                .prop_map(|ops| ops.into_iter().map(|(key, value, kind)| match kind {
+                    0 => put(key, value),
+                    1 => StateOperation { key: format!("k{}", key), action: StateAction::Delete },
+                    _ => StateOperation {
+                        key: format!("k{}", key),
+                        action: StateAction::CompareAndSwap { expected: None, new: vec![value] },
+                    },
+                }).collect::<Vec<_>>()),
+            seeds in proptest::collection::vec(any::<u64>(), 4),
+        ) {
+            use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+            let rt = Runtime::new().unwrap();
+
+            // Colliding keys make the outcome depend on apply order
+            let runs = rt.block_on(async {
+                let mut runs = Vec::new();
+                for seed in seeds {
+                    let mut shuffled = ops.clone();
+                    shuffled.shuffle(&mut StdRng::seed_from_u64(seed));
+                    let sm = ReplicatedStateMachine::new().with_manual_commit();
+                    sm.enqueue_all(shuffled).await;
+                    sm.force_commit().await.unwrap();
+
+                    let state: std::collections::BTreeMap<_, _> = sm.committed_state().await.into_iter().collect();
+                    runs.push(serde_json::to_vec(&state).unwrap());
+                }
+                runs
+            });
+
+            for run in &runs[1..] {
+                prop_assert_eq!(run, &runs[0]);
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// validators.rs - Epoch-scoped Consensus Validator Membership
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use serde::{Deserialize, Serialize};
+
+use super::ConsensusHeader;
+use crate::EnterpriseError;
+
+/// Single validator signature over a batch digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuorumVote {
+    pub validator_id: String,
+    pub signature: Vec<u8>,
+}
+
+impl QuorumVote {
+    /// Pack votes into a `ConsensusHeader::quorum_signature` payload
+    pub fn encode(votes: &[QuorumVote]) -> Vec<u8> {
+        serde_json::to_vec(votes).unwrap_or_default()
+    }
+
+    /// Unpack votes from a `ConsensusHeader::quorum_signature` payload
+    pub fn decode(bytes: &[u8]) -> Result<Vec<QuorumVote>, EnterpriseError> {
+        serde_json::from_slice(bytes).map_err(|_| EnterpriseError::ProtocolError)
+    }
+}
+
+/// Change to membership staged until the next epoch boundary
+#[derive(Debug, Clone)]
+enum MembershipChange {
+    Add(PublicKey),
+    Remove,
+}
+
+/// Validator membership history keyed by the epoch it became active
+///
+/// `add` and `remove` are staged and only take effect when `advance_epoch`
+/// crosses the boundary, so the set seen by any past epoch is immutable.
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorSet {
+    epoch: u64,
+    history: BTreeMap<u64, HashMap<String, PublicKey>>,
+    staged: BTreeMap<String, MembershipChange>,
+}
+
+impl ValidatorSet {
+    /// Create a set whose genesis membership is active from epoch 0
+    pub fn new(genesis: impl IntoIterator<Item = (String, PublicKey)>) -> Self {
+        let mut history = BTreeMap::new();
+        history.insert(0, genesis.into_iter().collect());
+        Self { epoch: 0, history, staged: BTreeMap::new() }
+    }
+
+    /// Current epoch
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Stage a validator to join at the next epoch boundary
+    pub fn add(&mut self, validator_id: impl Into<String>, key: PublicKey) {
+        self.staged.insert(validator_id.into(), MembershipChange::Add(key));
+    }
+
+    /// Stage a validator to leave at the next epoch boundary
+    pub fn remove(&mut self, validator_id: impl Into<String>) {
+        self.staged.insert(validator_id.into(), MembershipChange::Remove);
+    }
+
+    /// Close the current epoch and activate staged membership changes
+    pub fn advance_epoch(&mut self) -> u64 {
+        let mut members = self.members_at(self.epoch).cloned().unwrap_or_default();
+        for (id, change) in std::mem::take(&mut self.staged) {
+            match change {
+                MembershipChange::Add(key) => {
+                    members.insert(id, key);
+                }
+                MembershipChange::Remove => {
+                    members.remove(&id);
+                }
+            }
+        }
+
+        self.epoch += 1;
+        self.history.insert(self.epoch, members);
+        self.epoch
+    }
+
+    /// Membership active during `epoch`
+    pub fn members_at(&self, epoch: u64) -> Option<&HashMap<String, PublicKey>> {
+        self.history.range(..=epoch).next_back().map(|(_, members)| members)
+    }
+
+    /// Signatures required for a BFT quorum (2f + 1 of 3f + 1)
+    pub fn quorum_size(&self, epoch: u64) -> usize {
+        let n = self.members_at(epoch).map_or(0, HashMap::len);
+        n * 2 / 3 + 1
+    }
+
+    /// Verify the header's votes reach quorum under the epoch's membership
+    pub fn verify_quorum(&self, header: &ConsensusHeader, digest: &[u8]) -> Result<(), EnterpriseError> {
+        if header.epoch > self.epoch {
+            return Err(EnterpriseError::ProtocolError);
+        }
+
+        let members = self.members_at(header.epoch)
+            .ok_or(EnterpriseError::ProtocolError)?;
+        let votes = QuorumVote::decode(&header.quorum_signature)?;
+
+        let mut signers = HashSet::new();
+        for vote in &votes {
+            let Some(key) = members.get(&vote.validator_id) else {
+                continue;
+            };
+            let Ok(signature) = Signature::from_bytes(&vote.signature) else {
+                continue;
+            };
+            if key.verify(digest, &signature).is_ok() {
+                signers.insert(vote.validator_id.as_str());
+            }
+        }
+
+        if signers.len() >= self.quorum_size(header.epoch) {
+            Ok(())
+        } else {
+            Err(EnterpriseError::IntegrityError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Keypair, Signer};
+
+    fn keypair() -> Keypair {
+        Keypair::generate(&mut rand::rngs::OsRng)
+    }
+
+    fn header(epoch: u64, digest: &[u8], signers: &[(&str, &Keypair)]) -> ConsensusHeader {
+        let votes: Vec<_> = signers.iter()
+            .map(|(id, kp)| QuorumVote {
+                validator_id: id.to_string(),
+                signature: kp.sign(digest).to_bytes().to_vec(),
+            })
+            .collect();
+
+        ConsensusHeader {
+            epoch,
+            view_number: 0,
+            quorum_signature: QuorumVote::encode(&votes),
+            timestamp: 0,
+            sequence: 0,
+            beacon: None,
+        }
+    }
+
+    #[test]
+    fn test_validator_joins_at_epoch_boundary() {
+        let (a, b, c, d) = (keypair(), keypair(), keypair(), keypair());
+        let mut set = ValidatorSet::new([
+            ("a".to_string(), a.public),
+            ("b".to_string(), b.public),
+            ("c".to_string(), c.public),
+        ]);
+        let digest = [7u8; 32];
+
+        let committed = header(0, &digest, &[("a", &a), ("b", &b), ("c", &c)]);
+        assert!(set.verify_quorum(&committed, &digest).is_ok());
+
+        // Staged join does not count until the boundary
+        set.add("d", d.public);
+        let early = header(0, &digest, &[("a", &a), ("b", &b), ("d", &d)]);
+        assert!(set.verify_quorum(&early, &digest).is_err());
+
+        assert_eq!(set.advance_epoch(), 1);
+        let joined = header(1, &digest, &[("a", &a), ("b", &b), ("d", &d)]);
+        assert!(set.verify_quorum(&joined, &digest).is_ok());
+
+        // Already-committed epoch 0 batch is unaffected by the change
+        assert!(set.verify_quorum(&committed, &digest).is_ok());
+    }
+
+    #[test]
+    fn test_removed_validator_signature_not_counted() {
+        let (a, b, c, d) = (keypair(), keypair(), keypair(), keypair());
+        let mut set = ValidatorSet::new([
+            ("a".to_string(), a.public),
+            ("b".to_string(), b.public),
+            ("c".to_string(), c.public),
+            ("d".to_string(), d.public),
+        ]);
+        let digest = [9u8; 32];
+
+        set.remove("d");
+        set.advance_epoch();
+
+        let votes = header(1, &digest, &[("a", &a), ("b", &b), ("d", &d)]);
+        assert!(matches!(
+            set.verify_quorum(&votes, &digest),
+            Err(EnterpriseError::IntegrityError)
+        ));
+
+        let quorum = header(1, &digest, &[("a", &a), ("b", &b), ("c", &c)]);
+        assert!(set.verify_quorum(&quorum, &digest).is_ok());
+    }
+}
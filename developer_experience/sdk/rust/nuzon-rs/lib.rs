@@ -12,7 +12,7 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, RwLock, Semaphore};
 use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 
@@ -34,46 +34,493 @@ pub enum EnterpriseError {
     ProtocolError,
     #[error("Internal system failure")]
     CriticalFailure,
+    #[error("Clock skew beyond tolerance: {0}")]
+    ClockSkew(clock::SkewError),
 }
 
+pub mod admin;
+pub mod clock;
+pub mod codec;
+
 /// Quantum-safe cryptographic operations
 pub mod crypto {
     use pqcrypto::prelude::*;
-    use rand_core::OsRng;
+    use rand_core::{CryptoRng, OsRng, RngCore};
+    use ring::{aead, hkdf, hmac};
     use serde::{Deserialize, Serialize};
+    use x25519_dalek::{PublicKey as X25519Public, StaticSecret as X25519Secret};
+
+    use super::EnterpriseError;
+
+    /// Length of an X25519 public or secret key
+    const X25519_LEN: usize = 32;
+
+    /// HKDF info binding derived keys to the container format
+    const CONTAINER_KEY_INFO: &[u8] = b"nuzon secure container v1";
+
+    /// HKDF info binding a hybrid shared secret to its combiner
+    const HYBRID_KDF_INFO: &[u8] = b"nuzon hybrid kem kyber1024+x25519 v1";
+
+    /// Containers written since the header became authenticated; earlier
+    /// ones carry no `version` and are read as 0
+    const CONTAINER_VERSION: u32 = 1;
+
+    /// Output length requested from HKDF
+    struct OkmLen(usize);
+
+    impl hkdf::KeyType for OkmLen {
+        fn len(&self) -> usize {
+            self.0
+        }
+    }
+
+    /// Randomness for key generation and encapsulation
+    ///
+    /// FIPS deployments implement this over an approved DRBG; everything
+    /// else uses `DefaultEntropy`.
+    pub trait EntropySource: RngCore + CryptoRng + Send {}
+
+    impl<T: RngCore + CryptoRng + Send + ?Sized> EntropySource for T {}
+
+    /// Entropy used wherever no source is injected
+    pub type DefaultEntropy = OsRng;
+
+    // Fails to compile if the default ever stops being the OS generator
+    const _: fn(DefaultEntropy) -> OsRng = |rng| rng;
     
+    /// Key encapsulation mechanism a `SecureContainer` can be sealed under
+    pub trait Kem: Send + Sync {
+        /// Identifier recorded in the container header
+        fn algorithm_id(&self) -> &'static str;
+
+        /// Fresh `(public, secret)` key pair
+        fn keypair(&self) -> (Vec<u8>, Vec<u8>) {
+            self.keypair_with(&mut DefaultEntropy::default())
+        }
+
+        /// `keypair` drawing randomness from `rng`
+        fn keypair_with(&self, rng: &mut dyn EntropySource) -> (Vec<u8>, Vec<u8>);
+
+        /// `(ciphertext, shared secret)` for the holder of `pk`'s secret key
+        fn encaps(&self, pk: &[u8]) -> Result<(Vec<u8>, Vec<u8>), EnterpriseError> {
+            self.encaps_with(pk, &mut DefaultEntropy::default())
+        }
+
+        /// `encaps` drawing randomness from `rng`
+        fn encaps_with(&self, pk: &[u8], rng: &mut dyn EntropySource) -> Result<(Vec<u8>, Vec<u8>), EnterpriseError>;
+
+        /// Shared secret for `ct`; must not reveal through failure whether
+        /// `ct` was genuine, see `KyberKem::decaps`. Only a malformed `sk`
+        /// is an error.
+        fn decaps(&self, ct: &[u8], sk: &[u8]) -> Result<Vec<u8>, EnterpriseError>;
+    }
+
+    /// Built-in KEM recorded under `algorithm_id`
+    pub fn kem_for(algorithm_id: &str) -> Option<&'static dyn Kem> {
+        match algorithm_id {
+            KyberKem::ALGORITHM => Some(&KyberKem),
+            HybridKem::ALGORITHM => Some(&HybridKem),
+            _ => None,
+        }
+    }
+
+    fn default_algorithm() -> String {
+        KyberKem::ALGORITHM.to_string()
+    }
+
+    /// AEAD encrypting a `SecureContainer` body
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum SymmetricCipher {
+        #[default]
+        Aes256Gcm,
+        /// Outperforms AES on CPUs without AES instructions
+        ChaCha20Poly1305,
+    }
+
+    impl SymmetricCipher {
+        fn algorithm(&self) -> &'static aead::Algorithm {
+            match self {
+                Self::Aes256Gcm => &aead::AES_256_GCM,
+                Self::ChaCha20Poly1305 => &aead::CHACHA20_POLY1305,
+            }
+        }
+
+        /// Length of the nonce the cipher takes
+        pub fn nonce_len(&self) -> usize {
+            self.algorithm().nonce_len()
+        }
+    }
+
     /// Hybrid encryption container
+    ///
+    /// Containers written before the KEM was recorded carry no `algorithm`
+    /// and are read as Kyber; ones written before the cipher was recorded
+    /// carry no `cipher` and are read as AES-256-GCM. From
+    /// `CONTAINER_VERSION` 1 the HMAC covers the version, KEM and cipher
+    /// too, so none of them can be relabelled, nor the version stripped to
+    /// pass as a legacy container.
     #[derive(Debug, Serialize, Deserialize)]
     pub struct SecureContainer {
-        kyber_ciphertext: Vec<u8>,
-        aes_nonce: [u8; 12],
+        #[serde(default)]
+        version: u32,
+        #[serde(default = "default_algorithm")]
+        algorithm: String,
+        #[serde(default)]
+        cipher: SymmetricCipher,
+        #[serde(alias = "kyber_ciphertext")]
+        kem_ciphertext: Vec<u8>,
+        #[serde(alias = "aes_nonce")]
+        nonce: Vec<u8>,
         encrypted_data: Vec<u8>,
         hmac_tag: [u8; 32],
     }
 
+    /// Body and HMAC-SHA256 keys derived from a KEM shared secret
+    struct ContainerKeys {
+        body: [u8; 32],
+        mac: hmac::Key,
+    }
+
+    impl ContainerKeys {
+        fn derive(shared_secret: &[u8]) -> Self {
+            let mut okm = [0u8; 64];
+            hkdf::Salt::new(hkdf::HKDF_SHA256, &[])
+                .extract(shared_secret)
+                .expand(&[CONTAINER_KEY_INFO], OkmLen(okm.len()))
+                .and_then(|okm_ref| okm_ref.fill(&mut okm))
+                .expect("64 bytes is within the HKDF-SHA256 output limit");
+            let mut body = [0u8; 32];
+            body.copy_from_slice(&okm[..32]);
+            Self { body, mac: hmac::Key::new(hmac::HMAC_SHA256, &okm[32..]) }
+        }
+
+        fn cipher(&self, cipher: SymmetricCipher) -> aead::LessSafeKey {
+            aead::LessSafeKey::new(
+                aead::UnboundKey::new(cipher.algorithm(), &self.body).expect("both ciphers take 32-byte keys"),
+            )
+        }
+    }
+
+    impl SecureContainer {
+        /// Encrypt `plaintext` to the holder of the Kyber secret key for `pk`
+        pub fn seal(pk: &[u8], plaintext: &[u8]) -> Result<Self, EnterpriseError> {
+            Self::seal_with(&KyberKem, pk, plaintext)
+        }
+
+        /// Encrypt `plaintext` to the holder of `kem` secret key for `pk`
+        pub fn seal_with(kem: &dyn Kem, pk: &[u8], plaintext: &[u8]) -> Result<Self, EnterpriseError> {
+            Self::seal_with_cipher(kem, SymmetricCipher::default(), pk, plaintext)
+        }
+
+        /// Encrypt `plaintext` under `cipher` to the holder of `kem` secret
+        /// key for `pk`
+        pub fn seal_with_cipher(
+            kem: &dyn Kem,
+            cipher: SymmetricCipher,
+            pk: &[u8],
+            plaintext: &[u8],
+        ) -> Result<Self, EnterpriseError> {
+            Self::seal_with_entropy(kem, cipher, pk, plaintext, &mut DefaultEntropy::default())
+        }
+
+        /// `seal_with_cipher` drawing the encapsulation and nonce randomness
+        /// from `rng`
+        pub fn seal_with_entropy(
+            kem: &dyn Kem,
+            cipher: SymmetricCipher,
+            pk: &[u8],
+            plaintext: &[u8],
+            rng: &mut dyn EntropySource,
+        ) -> Result<Self, EnterpriseError> {
+            let (kem_ciphertext, shared_secret) = kem.encaps_with(pk, &mut *rng)?;
+            let keys = ContainerKeys::derive(&shared_secret);
+
+            let mut nonce = vec![0u8; cipher.nonce_len()];
+            rng.fill_bytes(&mut nonce);
+            let mut encrypted_data = plaintext.to_vec();
+            keys.cipher(cipher)
+                .seal_in_place_append_tag(
+                    aead::Nonce::try_assume_unique_for_key(&nonce).map_err(|_| EnterpriseError::CriticalFailure)?,
+                    aead::Aad::empty(),
+                    &mut encrypted_data,
+                )
+                .map_err(|_| EnterpriseError::CriticalFailure)?;
+
+            let mut container = Self {
+                version: CONTAINER_VERSION,
+                algorithm: kem.algorithm_id().to_string(),
+                cipher,
+                kem_ciphertext,
+                nonce,
+                encrypted_data,
+                hmac_tag: [0; 32],
+            };
+            container.hmac_tag.copy_from_slice(hmac::sign(&keys.mac, &container.authenticated_bytes()).as_ref());
+            Ok(container)
+        }
+
+        /// KEM the container was sealed under
+        pub fn algorithm(&self) -> &str {
+            &self.algorithm
+        }
+
+        /// AEAD the body was encrypted with
+        pub fn cipher(&self) -> SymmetricCipher {
+            self.cipher
+        }
+
+        /// Decrypt with the secret key of the built-in KEM the container
+        /// records
+        ///
+        /// An unknown algorithm fails like any other tampering, see
+        /// `open_with`.
+        pub fn open(&self, sk: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+            // Any built-in KEM refuses a header naming an unknown one
+            self.open_with(kem_for(&self.algorithm).unwrap_or(&KyberKem), sk)
+        }
+
+        /// Decrypt with a `kem` secret key
+        ///
+        /// Decapsulation never fails on a bad ciphertext (see
+        /// `KyberKem::decaps`), so a tampered KEM ciphertext yields unrelated
+        /// keys; a malformed `sk` is a `ProtocolError`. A header naming another KEM
+        /// or cipher, a malformed nonce, a bad body and a bad tag are all
+        /// likewise reported as the one `IntegrityError`, and the HMAC and
+        /// AEAD checks both run whatever the outcome of the other, so neither
+        /// the error nor the time taken tells which step refused the
+        /// container.
+        pub fn open_with(&self, kem: &dyn Kem, sk: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+            self.open_with_entropy(kem, sk, &mut DefaultEntropy::default())
+        }
+
+        /// `open_with` drawing the stand-in secret for a refused header from
+        /// `rng`
+        pub fn open_with_entropy(
+            &self,
+            kem: &dyn Kem,
+            sk: &[u8],
+            rng: &mut dyn EntropySource,
+        ) -> Result<Vec<u8>, EnterpriseError> {
+            if kem.algorithm_id() != self.algorithm {
+                return self.decrypt(&rejected_secret(rng));
+            }
+            self.decrypt(&kem.decaps(&self.kem_ciphertext, sk)?)
+        }
+
+        fn decrypt(&self, shared_secret: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+            let keys = ContainerKeys::derive(shared_secret);
+            let mac_ok = hmac::verify(&keys.mac, &self.authenticated_bytes(), &self.hmac_tag).is_ok();
+
+            // A nonce of the wrong length is swapped for zeros so the AEAD
+            // still runs; its tag check then fails
+            let nonce = aead::Nonce::try_assume_unique_for_key(&self.nonce)
+                .unwrap_or_else(|_| aead::Nonce::assume_unique_for_key([0; aead::NONCE_LEN]));
+            let nonce_ok = self.nonce.len() == self.cipher.nonce_len();
+            let mut data = self.encrypted_data.clone();
+            let plaintext_len = keys.cipher(self.cipher)
+                .open_in_place(nonce, aead::Aad::empty(), &mut data)
+                .map(|plaintext| plaintext.len());
+
+            match plaintext_len {
+                Ok(len) if mac_ok & nonce_ok => {
+                    data.truncate(len);
+                    Ok(data)
+                }
+                _ => Err(EnterpriseError::IntegrityError),
+            }
+        }
+
+        fn authenticated_bytes(&self) -> Vec<u8> {
+            if self.version == 0 {
+                return [&self.kem_ciphertext[..], &self.nonce, &self.encrypted_data].concat();
+            }
+            let mut bytes = self.version.to_be_bytes().to_vec();
+            let cipher = [self.cipher as u8];
+            for field in [self.algorithm.as_bytes(), &cipher, &self.kem_ciphertext, &self.nonce, &self.encrypted_data] {
+                bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+                bytes.extend_from_slice(field);
+            }
+            bytes
+        }
+    }
+
+    /// Stand-in secret for a container that cannot be decapsulated, which
+    /// derives keys no tag will match
+    fn rejected_secret(rng: &mut dyn EntropySource) -> [u8; 32] {
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+        secret
+    }
+
     /// NIST PQC Standard Implementation
     pub struct KyberKem;
     impl KyberKem {
+        pub const ALGORITHM: &'static str = "kyber1024";
+
         pub fn keypair() -> (Vec<u8>, Vec<u8>) {
-            let (pk, sk) = pqcrypto_kyber::kyber1024::keypair();
+            Self::keypair_with(&mut OsRng)
+        }
+
+        /// Generate a keypair drawing randomness from `rng`
+        pub fn keypair_with<R: EntropySource + ?Sized>(rng: &mut R) -> (Vec<u8>, Vec<u8>) {
+            let (pk, sk) = pqcrypto_kyber::kyber1024::keypair_from_rng(rng);
             (pk.as_bytes().to_vec(), sk.as_bytes().to_vec())
         }
 
-        pub fn encaps(pk: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        pub fn encaps(pk: &[u8]) -> Result<(Vec<u8>, Vec<u8>), EnterpriseError> {
+            Self::encaps_with(pk, &mut OsRng)
+        }
+
+        /// Encapsulate to `pk` drawing randomness from `rng`; a malformed
+        /// `pk` is a `ProtocolError`
+        pub fn encaps_with<R: EntropySource + ?Sized>(
+            pk: &[u8],
+            rng: &mut R,
+        ) -> Result<(Vec<u8>, Vec<u8>), EnterpriseError> {
             let pk = pqcrypto_kyber::kyber1024::PublicKey::from_bytes(pk)
-                .expect("Invalid public key");
-            let (ct, ss) = pqcrypto_kyber::kyber1024::encaps(&pk, &mut OsRng);
-            (ct.as_bytes().to_vec(), ss.as_bytes().to_vec())
+                .map_err(|_| EnterpriseError::ProtocolError)?;
+            let (ct, ss) = pqcrypto_kyber::kyber1024::encaps(&pk, rng);
+            Ok((ct.as_bytes().to_vec(), ss.as_bytes().to_vec()))
+        }
+
+        /// Recover the shared secret, with implicit rejection
+        ///
+        /// Never fails on a bad ciphertext: Kyber's FO transform already maps a
+        /// well-sized but invalid ciphertext to a pseudo-random secret derived
+        /// from the key's rejection seed `z`, and a ciphertext of the wrong
+        /// length is given the same treatment here via HMAC(z, ct). Callers
+        /// must therefore decide authenticity with a MAC over the derived
+        /// keys, never by inspecting the decapsulation result. Only a
+        /// malformed `sk`, which is the caller's own key, is an error:
+        /// `ProtocolError`.
+        pub fn decaps(ct: &[u8], sk: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+            let secret = pqcrypto_kyber::kyber1024::SecretKey::from_bytes(sk)
+                .map_err(|_| EnterpriseError::ProtocolError)?;
+            Ok(match pqcrypto_kyber::kyber1024::Ciphertext::from_bytes(ct) {
+                Ok(ct) => pqcrypto_kyber::kyber1024::decaps(&ct, &secret)
+                    .as_bytes()
+                    .to_vec(),
+                Err(_) => {
+                    // The secret key ends with the 32-byte rejection seed
+                    let z = &sk[sk.len() - 32..];
+                    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, z), ct).as_ref().to_vec()
+                }
+            })
+        }
+    }
+
+    impl Kem for KyberKem {
+        fn algorithm_id(&self) -> &'static str {
+            Self::ALGORITHM
+        }
+
+        fn keypair_with(&self, rng: &mut dyn EntropySource) -> (Vec<u8>, Vec<u8>) {
+            Self::keypair_with(rng)
+        }
+
+        fn encaps_with(&self, pk: &[u8], rng: &mut dyn EntropySource) -> Result<(Vec<u8>, Vec<u8>), EnterpriseError> {
+            Self::encaps_with(pk, rng)
         }
 
-        pub fn decaps(ct: &[u8], sk: &[u8]) -> Vec<u8> {
-            let sk = pqcrypto_kyber::kyber1024::SecretKey::from_bytes(sk)
-                .expect("Invalid secret key");
-            let ct = pqcrypto_kyber::kyber1024::Ciphertext::from_bytes(ct)
-                .expect("Invalid ciphertext");
-            pqcrypto_kyber::kyber1024::decaps(&ct, &sk)
-                .as_bytes()
-                .to_vec()
+        fn decaps(&self, ct: &[u8], sk: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+            Self::decaps(ct, sk)
+        }
+    }
+
+    /// Kyber1024 combined with X25519
+    ///
+    /// Keys and ciphertexts are the Kyber value followed by the 32-byte
+    /// X25519 one. The shared secret is HKDF-SHA256 over both component
+    /// secrets, the whole ciphertext and the recipient's public key, so the
+    /// container stays confidential unless both schemes are broken and the
+    /// secret is bound to the exchange it came from.
+    pub struct HybridKem;
+
+    impl HybridKem {
+        pub const ALGORITHM: &'static str = "kyber1024+x25519";
+
+        /// Kyber and X25519 halves of a hybrid key with the given Kyber length
+        fn split_key(key: &[u8], kyber_len: usize) -> Result<(&[u8], [u8; X25519_LEN]), EnterpriseError> {
+            if key.len() != kyber_len + X25519_LEN {
+                return Err(EnterpriseError::ProtocolError);
+            }
+            let (kyber, x25519) = key.split_at(kyber_len);
+            Ok((kyber, x25519.try_into().map_err(|_| EnterpriseError::ProtocolError)?))
+        }
+
+        /// Recipient's hybrid public key, recovered from its secret key
+        ///
+        /// A Kyber secret key embeds its public key ahead of the 32-byte
+        /// public key hash and rejection seed.
+        fn public_key(kyber_sk: &[u8], x25519_sk: &X25519Secret) -> Vec<u8> {
+            let pk_len = pqcrypto_kyber::kyber1024::public_key_bytes();
+            let offset = kyber_sk.len() - pk_len - 64;
+            [&kyber_sk[offset..offset + pk_len], X25519Public::from(x25519_sk).as_bytes()].concat()
+        }
+
+        fn combine(kyber_ss: &[u8], x25519_ss: &[u8], ct: &[u8], pk: &[u8]) -> Vec<u8> {
+            let mut secret = vec![0u8; 32];
+            hkdf::Salt::new(hkdf::HKDF_SHA256, &[])
+                .extract(&[kyber_ss, x25519_ss, ct, pk].concat())
+                .expand(&[HYBRID_KDF_INFO], OkmLen(secret.len()))
+                .and_then(|okm| okm.fill(&mut secret))
+                .expect("32 bytes is within the HKDF-SHA256 output limit");
+            secret
+        }
+    }
+
+    impl Kem for HybridKem {
+        fn algorithm_id(&self) -> &'static str {
+            Self::ALGORITHM
+        }
+
+        fn keypair_with(&self, rng: &mut dyn EntropySource) -> (Vec<u8>, Vec<u8>) {
+            let (kyber_pk, kyber_sk) = KyberKem::keypair_with(&mut *rng);
+            let secret = X25519Secret::random_from_rng(rng);
+            let public = X25519Public::from(&secret);
+            ([kyber_pk, public.as_bytes().to_vec()].concat(), [kyber_sk, secret.to_bytes().to_vec()].concat())
+        }
+
+        /// A malformed key, or an X25519 key of low order, is a `ProtocolError`
+        fn encaps_with(&self, pk: &[u8], rng: &mut dyn EntropySource) -> Result<(Vec<u8>, Vec<u8>), EnterpriseError> {
+            let (kyber_pk, peer) = Self::split_key(pk, pqcrypto_kyber::kyber1024::public_key_bytes())?;
+            let (kyber_ct, kyber_ss) = KyberKem::encaps_with(kyber_pk, &mut *rng)?;
+
+            let ephemeral = X25519Secret::random_from_rng(rng);
+            let x25519_ss = ephemeral.diffie_hellman(&X25519Public::from(peer));
+            if !x25519_ss.was_contributory() {
+                return Err(EnterpriseError::ProtocolError);
+            }
+            let ct = [kyber_ct, X25519Public::from(&ephemeral).as_bytes().to_vec()].concat();
+            let ss = Self::combine(&kyber_ss, x25519_ss.as_bytes(), &ct, pk);
+            Ok((ct, ss))
+        }
+
+        /// Fails only on a malformed `sk`
+        ///
+        /// Like Kyber, the X25519 half rejects implicitly: a ciphertext of
+        /// the wrong length, or carrying a low-order X25519 key, takes
+        /// HMAC(x25519 secret, ct) as that half's secret, so it too yields an
+        /// unrelated shared secret.
+        fn decaps(&self, ct: &[u8], sk: &[u8]) -> Result<Vec<u8>, EnterpriseError> {
+            let (kyber_sk, x25519_sk) = Self::split_key(sk, pqcrypto_kyber::kyber1024::secret_key_bytes())?;
+            let secret = X25519Secret::from(x25519_sk);
+            let pk = Self::public_key(kyber_sk, &secret);
+
+            let expected_len = pqcrypto_kyber::kyber1024::ciphertext_bytes() + X25519_LEN;
+            let (kyber_ct, peer) = match Self::split_key(ct, expected_len - X25519_LEN) {
+                Ok((kyber_ct, peer)) => (kyber_ct, Some(peer)),
+                Err(_) => (ct, None),
+            };
+            let kyber_ss = KyberKem::decaps(kyber_ct, kyber_sk)?;
+            let x25519_ss = peer
+                .map(|peer| secret.diffie_hellman(&X25519Public::from(peer)))
+                .filter(|shared| shared.was_contributory())
+                .map(|shared| shared.as_bytes().to_vec())
+                .unwrap_or_else(|| {
+                    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &x25519_sk), ct).as_ref().to_vec()
+                });
+            Ok(Self::combine(&kyber_ss, &x25519_ss, ct, &pk))
         }
     }
 }
@@ -81,6 +528,31 @@ pub mod crypto {
 /// Distributed agent coordination
 pub mod coordination {
     use super::*;
+    use sha2::{Digest, Sha256};
+
+    pub mod beacon;
+    pub mod certifier;
+    pub mod dead_letter;
+    pub mod election;
+    pub mod heartbeat;
+    pub mod nonce;
+    pub mod repair;
+    pub mod revocation;
+    pub mod validators;
+    #[cfg(any(test, feature = "testing"))]
+    pub mod testing;
+
+    pub use beacon::{BeaconOutput, BeaconReveal, HashChain, RandomBeacon};
+    pub use certifier::BatchCertifier;
+    pub use dead_letter::{DeadLetter, DeadLetterStore};
+    pub use election::{CommitRoute, LeaderElection, Leadership};
+    pub use heartbeat::{Heartbeat, HeartbeatConfig, HeartbeatMonitor, Liveness};
+    pub use nonce::NonceStore;
+    pub use repair::{BatchLog, BatchSource, StateSnapshot};
+    pub use revocation::{key_id, Revocation, RevocationList, REVOCATION_KEY_PREFIX};
+    pub use validators::{QuorumVote, ValidatorSet};
+
+    const AUTO_COMMIT_THRESHOLD: usize = 100;
     
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ConsensusHeader {
@@ -88,6 +560,94 @@ pub mod coordination {
         pub view_number: u32,
         pub quorum_signature: Vec<u8>,
         pub timestamp: u128,
+        /// Position in the certified commit log; the first batch is 1
+        #[serde(default)]
+        pub sequence: u64,
+        /// Randomness for `epoch`, set on the batch opening the epoch
+        #[serde(default)]
+        pub beacon: Option<BeaconOutput>,
+    }
+
+    /// Mutation applied to the replicated key-value state
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct StateOperation {
+        pub key: String,
+        pub action: StateAction,
+    }
+
+    impl StateOperation {
+        /// SHA-256 of the serialized operation
+        pub fn digest(&self) -> [u8; 32] {
+            Sha256::digest(serde_json::to_vec(self).unwrap_or_default()).into()
+        }
+    }
+
+    /// Put `ops` in the order every replica applies them
+    ///
+    /// Operations sort by key, then by `StateOperation::digest`, so the order
+    /// depends only on what a batch contains and never on when each operation
+    /// was enqueued. Operations that tie are byte-identical, and swapping
+    /// them cannot change the result. Operations on one key that rely on each
+    /// other's effect, such as chained compare-and-swaps, belong in separate
+    /// batches.
+    pub fn canonical_order(ops: &mut [StateOperation]) {
+        ops.sort_by_cached_key(|op| (op.key.clone(), op.digest()));
+    }
+
+    /// State mutation kinds
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum StateAction {
+        #[default]
+        Delete,
+        Put(Vec<u8>),
+        CompareAndSwap {
+            expected: Option<Vec<u8>>,
+            new: Vec<u8>,
+        },
+        /// Delete only while the key still holds `expected`
+        CompareAndDelete {
+            expected: Vec<u8>,
+        },
+    }
+
+    /// Batch of operations certified by a validator quorum
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct CommittedBatch {
+        pub header: ConsensusHeader,
+        pub ops: Vec<StateOperation>,
+    }
+
+    impl CommittedBatch {
+        /// Digest signed by validators; covers everything but the signatures
+        pub fn digest(&self) -> [u8; 32] {
+            let mut hasher = Sha256::new();
+            hasher.update(self.header.epoch.to_be_bytes());
+            hasher.update(self.header.view_number.to_be_bytes());
+            hasher.update(self.header.timestamp.to_be_bytes());
+            hasher.update(self.header.sequence.to_be_bytes());
+            if let Some(beacon) = &self.header.beacon {
+                hasher.update(beacon.value);
+            }
+            for op in &self.ops {
+                hasher.update(serde_json::to_vec(op).unwrap_or_default());
+            }
+            hasher.finalize().into()
+        }
+    }
+
+    /// Checkpoints within a commit where a fault can be injected
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CommitStage {
+        /// Operations validated against a staged copy of the state
+        Staging,
+        /// Staged state about to replace the committed state
+        Apply,
+    }
+
+    /// Told about committed changes to keys under the prefix it observes
+    pub trait StateObserver: std::fmt::Debug + Send + Sync {
+        /// `value` is `None` when the key was deleted
+        fn committed(&self, key: &str, value: Option<&[u8]>);
     }
 
     /// Byzantine Fault Tolerant State Machine
@@ -95,86 +655,661 @@ pub mod coordination {
     pub struct ReplicatedStateMachine {
         state: Arc<RwLock<HashMap<String, Vec<u8>>>>,
         pending_ops: Arc<Mutex<Vec<StateOperation>>>,
+        validators: Arc<RwLock<ValidatorSet>>,
+        log: Arc<RwLock<BatchLog>>,
+        dead_letters: Arc<DeadLetterStore>,
+        observers: Arc<std::sync::RwLock<Vec<(String, Arc<dyn StateObserver>)>>>,
+        certifier: Option<Arc<dyn BatchCertifier>>,
+        auto_commit: bool,
+        injected_fault: Arc<std::sync::Mutex<Option<CommitStage>>>,
     }
 
     impl ReplicatedStateMachine {
         pub fn new() -> Self {
+            Self::with_validators(ValidatorSet::default())
+        }
+
+        /// Create a state machine governed by the given validator set
+        pub fn with_validators(validators: ValidatorSet) -> Self {
             Self {
                 state: Arc::new(RwLock::new(HashMap::new())),
                 pending_ops: Arc::new(Mutex::new(Vec::new())),
+                validators: Arc::new(RwLock::new(validators)),
+                log: Arc::new(RwLock::new(BatchLog::default())),
+                dead_letters: Arc::new(DeadLetterStore::default()),
+                observers: Arc::new(std::sync::RwLock::new(Vec::new())),
+                certifier: None,
+                auto_commit: true,
+                injected_fault: Arc::new(std::sync::Mutex::new(None)),
             }
         }
 
+        /// Replicate submitted batches by certifying them through `certifier`
+        ///
+        /// Without one, `submit_batch` commits to this node only, which is
+        /// only safe while it is the sole replica.
+        pub fn with_certifier(mut self, certifier: Arc<dyn BatchCertifier>) -> Self {
+            self.certifier = Some(certifier);
+            self
+        }
+
+        /// Shared handle to the validator membership
+        pub fn validators(&self) -> Arc<RwLock<ValidatorSet>> {
+            self.validators.clone()
+        }
+
+        /// Operations rejected from submitted batches
+        pub fn dead_letters(&self) -> Arc<DeadLetterStore> {
+            self.dead_letters.clone()
+        }
+
+        /// Report every committed change to a key starting with `prefix`
+        ///
+        /// The observer is first told about the keys already committed under
+        /// `prefix`, then about each later change, whether committed locally,
+        /// from a certified batch or by importing a snapshot.
+        pub async fn observe(&self, prefix: impl Into<String>, observer: Arc<dyn StateObserver>) {
+            let prefix = prefix.into();
+            let state = self.state.read().await;
+            for (key, value) in state.iter().filter(|(key, _)| key.starts_with(&prefix)) {
+                observer.committed(key, Some(value));
+            }
+            self.observers.write().unwrap_or_else(|e| e.into_inner()).push((prefix, observer));
+        }
+
+        /// Tell observers about keys whose committed value changed
+        fn notify(&self, changes: &[(String, Option<Vec<u8>>)]) {
+            let observers = self.observers.read().unwrap_or_else(|e| e.into_inner());
+            for (prefix, observer) in observers.iter() {
+                for (key, value) in changes.iter().filter(|(key, _)| key.starts_with(prefix.as_str())) {
+                    observer.committed(key, value.as_deref());
+                }
+            }
+        }
+
+        fn has_observers(&self) -> bool {
+            !self.observers.read().unwrap_or_else(|e| e.into_inner()).is_empty()
+        }
+
         #[instrument(skip_all)]
         pub async fn apply_operation(&self, op: StateOperation) -> Result<(), EnterpriseError> {
             let mut guard = self.pending_ops.lock().await;
             guard.push(op);
             
-            if guard.len() >= 100 {
-                self.commit_batch().await?;
+            if self.auto_commit && guard.len() >= AUTO_COMMIT_THRESHOLD {
+                let batch = std::mem::take(&mut *guard);
+                drop(guard);
+                self.submit_batch(batch).await?;
             }
             
             Ok(())
         }
 
-        async fn commit_batch(&self) -> Result<(), EnterpriseError> {
-            // Implementation of consensus protocol
-            unimplemented!("BFT batch commit logic")
+        /// Apply a batch certified by the quorum of the batch's epoch
+        ///
+        /// Membership is resolved at `header.epoch`, so later validator changes
+        /// never affect batches that were already committed. Batches must arrive
+        /// in sequence: replays are ignored, and a batch beyond the next
+        /// sequence is rejected with `ProtocolError` until the node catches up.
+        /// A batch that fails validation is rejected with `IntegrityError` but
+        /// still takes its place in the log, since every replica rejects it alike.
+        #[instrument(skip_all, fields(epoch = batch.header.epoch, sequence = batch.header.sequence))]
+        pub async fn commit_certified(&self, batch: CommittedBatch) -> Result<(), EnterpriseError> {
+            let sequence = batch.header.sequence;
+            if self.commit_certified_run(vec![batch]).await?.contains(&sequence) {
+                return Err(EnterpriseError::IntegrityError);
+            }
+            Ok(())
+        }
+
+        /// Verify every batch of a run, then apply it as one commit
+        ///
+        /// Batches already applied are dropped; the rest must continue the
+        /// local log without a hole and never go back an epoch, or nothing is
+        /// applied. Each batch still applies all-or-nothing on its own.
+        /// Returns the sequences of batches rejected as invalid.
+        pub(super) async fn commit_certified_run(&self, batches: Vec<CommittedBatch>) -> Result<Vec<u64>, EnterpriseError> {
+            {
+                let validators = self.validators.read().await;
+                for batch in &batches {
+                    validators.verify_quorum(&batch.header, &batch.digest())?;
+                }
+            }
+
+            let mut log = self.log.write().await;
+            let applied = log.last_sequence();
+            let batches: Vec<_> = batches.into_iter().filter(|b| b.header.sequence > applied).collect();
+            let mut epoch = log.last_epoch();
+            for (expected, batch) in (applied + 1..).zip(&batches) {
+                if batch.header.sequence != expected || batch.header.epoch < epoch {
+                    warn!(applied, sequence = batch.header.sequence, "Certified batch beyond local log");
+                    return Err(EnterpriseError::ProtocolError);
+                }
+                epoch = batch.header.epoch;
+            }
+            if batches.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let observed = self.has_observers();
+            let mut state = self.state.write().await;
+            let mut staged: Option<HashMap<_, _>> = None;
+            let mut changed = Vec::new();
+            let mut rejected = Vec::new();
+            for batch in &batches {
+                let mut next = staged.as_ref().unwrap_or(&*state).clone();
+                match stage_ops(&mut next, batch.ops.clone(), false, observed) {
+                    Ok(batch) => {
+                        staged = Some(next);
+                        changed.extend(batch.changed);
+                    }
+                    Err(_) => rejected.push(batch.header.sequence),
+                }
+            }
+            self.check_fault(CommitStage::Staging)?;
+
+            self.check_fault(CommitStage::Apply)?;
+            if let Some(staged) = staged {
+                *state = staged;
+            }
+            let changes = changed_values(&state, changed);
+            drop(state);
+            for batch in batches {
+                log.record(batch);
+            }
+            drop(log);
+
+            self.notify(&changes);
+            Ok(rejected)
+        }
+
+        /// Read the committed value for a key
+        pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+            self.state.read().await.get(key).cloned()
+        }
+
+        /// Fail the commit if a test harness armed a fault for this stage
+        fn check_fault(&self, stage: CommitStage) -> Result<(), EnterpriseError> {
+            let mut fault = self.injected_fault.lock().unwrap_or_else(|e| e.into_inner());
+            if *fault == Some(stage) {
+                *fault = None;
+                return Err(EnterpriseError::CriticalFailure);
+            }
+            Ok(())
+        }
+
+        /// Commit a batch on every replica, or on this node if unreplicated
+        ///
+        /// With a certifier the batch is certified, the batches certified
+        /// ahead of it are applied, and it commits like any certified batch:
+        /// all-or-nothing, so an operation that fails validation (such as a
+        /// stale compare-and-swap) rejects it with `IntegrityError` on every
+        /// replica. Without one, such an operation is moved to `dead_letters`
+        /// with its reason and the remainder commits. Returns the number of
+        /// operations committed. Certified or not, operations apply in
+        /// `canonical_order`.
+        pub async fn submit_batch(&self, ops: Vec<StateOperation>) -> Result<usize, EnterpriseError> {
+            let Some(certifier) = &self.certifier else {
+                return self.commit_staged(ops, true).await;
+            };
+
+            let count = ops.len();
+            let batch = certifier.certify(ops).await?;
+            if batch.header.sequence > self.applied_sequence().await + 1 {
+                let epoch = self.applied_epoch().await;
+                let ahead: Vec<_> = certifier.batches_since(epoch).await?
+                    .into_iter()
+                    .take_while(|b| b.header.sequence < batch.header.sequence)
+                    .collect();
+                self.catch_up(epoch, ahead).await?;
+            }
+            self.commit_certified(batch).await?;
+            Ok(count)
+        }
+
+        async fn commit_staged(&self, ops: Vec<StateOperation>, dead_letter: bool) -> Result<usize, EnterpriseError> {
+            let observed = self.has_observers();
+            let mut state = self.state.write().await;
+            let mut staged = state.clone();
+            let batch = stage_ops(&mut staged, ops, dead_letter, observed)?;
+            self.check_fault(CommitStage::Staging)?;
+
+            self.check_fault(CommitStage::Apply)?;
+            *state = staged;
+            let changes = changed_values(&state, batch.changed);
+            drop(state);
+
+            self.notify(&changes);
+            for letter in batch.rejected {
+                self.dead_letters.record(letter);
+            }
+            Ok(batch.committed)
         }
     }
+
+    /// Outcome of staging one batch
+    struct StagedBatch {
+        committed: usize,
+        /// Keys written, collected only when someone is listening
+        changed: Vec<String>,
+        rejected: Vec<DeadLetter>,
+    }
+
+    /// Apply `ops` to the staged state in `canonical_order`
+    ///
+    /// An operation that fails validation is dead-lettered, or fails the
+    /// whole batch with `IntegrityError` when `dead_letter` is false.
+    fn stage_ops(
+        staged: &mut HashMap<String, Vec<u8>>,
+        mut ops: Vec<StateOperation>,
+        dead_letter: bool,
+        observed: bool,
+    ) -> Result<StagedBatch, EnterpriseError> {
+        canonical_order(&mut ops);
+        let mut batch = StagedBatch { committed: 0, changed: Vec::new(), rejected: Vec::new() };
+        for op in ops {
+            let key = observed.then(|| op.key.clone());
+            match stage_operation(staged, op) {
+                Ok(()) => {
+                    batch.committed += 1;
+                    batch.changed.extend(key);
+                }
+                Err(letter) if dead_letter => batch.rejected.push(letter),
+                Err(_) => return Err(EnterpriseError::IntegrityError),
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Pair each changed key with its committed value
+    fn changed_values(state: &HashMap<String, Vec<u8>>, keys: Vec<String>) -> Vec<(String, Option<Vec<u8>>)> {
+        keys.into_iter()
+            .map(|key| {
+                let value = state.get(&key).cloned();
+                (key, value)
+            })
+            .collect()
+    }
+
+    /// Apply one operation to the staged state, or explain why it cannot be
+    fn stage_operation(staged: &mut HashMap<String, Vec<u8>>, op: StateOperation) -> Result<(), DeadLetter> {
+        match op.action {
+            StateAction::Put(value) => {
+                staged.insert(op.key, value);
+            }
+            StateAction::Delete => {
+                staged.remove(&op.key);
+            }
+            StateAction::CompareAndSwap { expected, new } => {
+                if staged.get(&op.key) != expected.as_ref() {
+                    let reason = format!("compare-and-swap on '{}' found a different current value", op.key);
+                    let op = StateOperation { key: op.key, action: StateAction::CompareAndSwap { expected, new } };
+                    return Err(DeadLetter { op, reason });
+                }
+                staged.insert(op.key, new);
+            }
+            StateAction::CompareAndDelete { expected } => {
+                if staged.get(&op.key) != Some(&expected) {
+                    let reason = format!("compare-and-delete on '{}' found a different current value", op.key);
+                    let op = StateOperation { key: op.key, action: StateAction::CompareAndDelete { expected } };
+                    return Err(DeadLetter { op, reason });
+                }
+                staged.remove(&op.key);
+            }
+        }
+        Ok(())
+    }
 }
 
+pub use coordination::StateOperation;
+
 /// Enterprise Agent Core
 pub mod agent {
     use super::*;
+
+    pub mod admission;
+    pub mod compliance;
+    pub mod config_envelope;
+    pub mod invoker;
+    pub mod mailbox;
+    pub mod snapshot;
+
+    pub use admission::{Admission, AdmissionController, AdmissionQuotas, CarriedUsage, Quota, UsageSnapshot};
+    pub use compliance::{ComplianceEngine, ComplianceHeaders, ComplianceRule, GdprRule, HipaaRule};
+    pub use config_envelope::{migrate, ConfigEnvelope, ConfigError, CONFIG_SCHEMA_VERSION};
+    pub use invoker::{CallerContext, CapabilityInvoker};
+    pub use mailbox::{Mailbox, MailboxHandler, Reply};
+    pub use snapshot::{AgentDeps, AttestationVerifier, SnapshotKey, AGENT_SNAPSHOT_VERSION};
+    use crate::clock::{Clock, SkewPolicy, SystemClock};
     
-    #[derive(Debug, Serialize, Deserialize)]
+    /// Agent identity; timestamps serialize as decimal strings in
+    /// human-readable formats
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct AgentIdentity {
         pub id: Uuid,
+        #[serde(default)]
         pub generation: u32,
+        /// Start of validity, milliseconds since the Unix epoch
+        #[serde(with = "codec::u128_string")]
         pub valid_from: u128,
+        /// End of validity (exclusive), milliseconds since the Unix epoch
+        #[serde(with = "codec::u128_string")]
         pub valid_to: u128,
+        #[serde(default)]
         pub attestation: Vec<u8>,
     }
 
+    impl AgentIdentity {
+        /// Whether the identity is within its validity window at `now_millis`
+        pub fn is_valid_at(&self, now_millis: u128) -> bool {
+            self.valid_from <= now_millis && now_millis < self.valid_to
+        }
+
+        /// Accept the identity at `now_millis` if it is within its validity
+        /// window, its start widened by `skew`
+        ///
+        /// One that has not started yet, beyond the tolerance, is
+        /// `ClockSkew`; one past `valid_to` has expired and is `AuthError`.
+        pub fn check_at(&self, now_millis: u128, skew: &SkewPolicy) -> Result<(), EnterpriseError> {
+            skew.check_started(self.valid_from, now_millis).map_err(EnterpriseError::ClockSkew)?;
+            if now_millis >= self.valid_to {
+                return Err(EnterpriseError::AuthError("agent identity outside validity window".into()));
+            }
+            Ok(())
+        }
+
+        /// Ids a revocation of this identity may be recorded under: the
+        /// identity id, then the `key_id` of its attestation, which covers
+        /// revoking the host key that attested it
+        pub fn revocation_ids(&self) -> Vec<String> {
+            let attestation = (!self.attestation.is_empty()).then(|| coordination::key_id(&self.attestation));
+            std::iter::once(self.id.to_string()).chain(attestation).collect()
+        }
+
+        /// Refuse the identity with `AuthError` if any of its
+        /// `revocation_ids` is revoked
+        pub fn check_revocation(&self, revocations: Option<&coordination::RevocationList>) -> Result<(), EnterpriseError> {
+            match revocations.and_then(|revocations| revocations.first_revoked(self.revocation_ids())) {
+                Some(revoked) => {
+                    warn!(identity = %self.id, revoked = %revoked, "Refusing revoked agent identity");
+                    Err(EnterpriseError::AuthError(format!("agent identity {} is revoked", self.id)))
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
     /// Runtime configuration with resource limits
-    #[derive(Debug, Serialize, Deserialize)]
+    ///
+    /// Stored inside a `ConfigEnvelope`. Only the resource limits are
+    /// required; every other field, and any added later, needs a serde
+    /// default so envelopes written before it existed still read.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct AgentConfig {
         pub max_memory: u64,
         pub cpu_quota: f32,
         pub network_budget: u64,
+        #[serde(default)]
         pub compliance_rules: Vec<String>,
+        #[serde(default = "default_max_concurrent_messages")]
+        pub max_concurrent_messages: usize,
+        #[serde(default)]
+        pub concurrency_mode: ConcurrencyMode,
+        /// Messages admitted per second; unbounded when absent
+        #[serde(default)]
+        pub max_messages_per_sec: Option<u32>,
+        /// Bytes of messages being processed at once; unbounded when absent
+        #[serde(default)]
+        pub max_in_flight_bytes: Option<u64>,
+        /// Inbound message bytes admitted per second; unbounded when absent
+        #[serde(default)]
+        pub network_bytes_per_sec: Option<u64>,
+        /// Codec used for outbound messages; inbound may use any accepted codec
+        #[serde(default)]
+        pub codec: codec::MessageCodec,
+        #[serde(default = "default_accepted_codecs")]
+        pub accepted_codecs: Vec<codec::MessageCodec>,
+        /// Compression of outbound bodies; inbound bodies are inflated
+        /// according to their envelope
+        #[serde(default)]
+        pub compression: codec::CompressionPolicy,
+        /// Compression inbound envelopes may use; anything else is refused
+        /// before it is inflated
+        #[serde(default = "default_accepted_compression")]
+        pub accepted_compression: Vec<codec::Compression>,
+        /// Messages queued for the mailbox worker before `send` is refused
+        #[serde(default = "default_mailbox_capacity")]
+        pub mailbox_capacity: usize,
+    }
+
+    fn default_accepted_codecs() -> Vec<codec::MessageCodec> {
+        vec![codec::MessageCodec::Json, codec::MessageCodec::Cbor, codec::MessageCodec::Bincode]
+    }
+
+    fn default_accepted_compression() -> Vec<codec::Compression> {
+        codec::Compression::ALL.to_vec()
+    }
+
+    fn default_max_concurrent_messages() -> usize {
+        64
+    }
+
+    fn default_mailbox_capacity() -> usize {
+        256
+    }
+
+    impl AgentConfig {
+        /// Configuration with these resource limits and every other field
+        /// at its default
+        pub fn new(max_memory: u64, cpu_quota: f32, network_budget: u64) -> Self {
+            Self {
+                max_memory,
+                cpu_quota,
+                network_budget,
+                compliance_rules: Vec::new(),
+                max_concurrent_messages: default_max_concurrent_messages(),
+                concurrency_mode: ConcurrencyMode::default(),
+                max_messages_per_sec: None,
+                max_in_flight_bytes: None,
+                network_bytes_per_sec: None,
+                codec: codec::MessageCodec::default(),
+                accepted_codecs: default_accepted_codecs(),
+                compression: codec::CompressionPolicy::default(),
+                accepted_compression: default_accepted_compression(),
+                mailbox_capacity: default_mailbox_capacity(),
+            }
+        }
+    }
+
+    /// Behaviour when the in-flight message limit is reached
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum ConcurrencyMode {
+        /// Fail fast with `EnterpriseError::ResourceLimit`
+        #[default]
+        Reject,
+        /// Wait for an in-flight message to complete
+        Wait,
+    }
+
+    /// Bounds the number of messages processed concurrently
+    #[derive(Debug, Clone)]
+    pub struct ConcurrencyGate {
+        semaphore: Arc<Semaphore>,
+        mode: ConcurrencyMode,
+    }
+
+    impl ConcurrencyGate {
+        /// Gate admitting `max_concurrent` messages at once; zero would
+        /// refuse or stall every message, so it is a `ResourceLimit`
+        pub fn new(max_concurrent: usize, mode: ConcurrencyMode) -> Result<Self, EnterpriseError> {
+            if max_concurrent == 0 {
+                return Err(EnterpriseError::ResourceLimit("max concurrent messages must be at least 1".into()));
+            }
+            Ok(Self { semaphore: Arc::new(Semaphore::new(max_concurrent)), mode })
+        }
+
+        /// Reserve a processing slot, released when the permit is dropped
+        pub async fn admit(&self) -> Result<OwnedSemaphorePermit, EnterpriseError> {
+            match self.mode {
+                ConcurrencyMode::Reject => self.semaphore.clone().try_acquire_owned()
+                    .map_err(|_| EnterpriseError::ResourceLimit("concurrent message limit".into())),
+                ConcurrencyMode::Wait => self.semaphore.clone().acquire_owned().await
+                    .map_err(|_| EnterpriseError::CriticalFailure),
+            }
+        }
+
+        /// Currently free processing slots
+        pub fn available(&self) -> usize {
+            self.semaphore.available_permits()
+        }
     }
 
     /// Stateful agent instance
     pub struct EnterpriseAgent {
         identity: AgentIdentity,
         config: AgentConfig,
-        state_machine: coordination::ReplicatedStateMachine,
+        state_machine: Arc<coordination::ReplicatedStateMachine>,
         crypto: crypto::KyberKem,
+        message_gate: ConcurrencyGate,
+        admission: AdmissionController,
+        compliance: ComplianceEngine,
+        capabilities: Option<Arc<dyn CapabilityInvoker>>,
+        revocations: Option<Arc<coordination::RevocationList>>,
+        clock: Arc<dyn Clock>,
+        skew: SkewPolicy,
+        snapshot_key: Option<SnapshotKey>,
     }
 
     impl EnterpriseAgent {
         pub fn new(config: AgentConfig) -> Result<Self, EnterpriseError> {
+            Self::with_identity(config, Self::generate_identity()?)
+        }
+
+        /// Agent running under a previously provisioned identity
+        pub fn with_identity(config: AgentConfig, identity: AgentIdentity) -> Result<Self, EnterpriseError> {
             Ok(Self {
-                identity: Self::generate_identity()?,
+                identity,
+                message_gate: ConcurrencyGate::new(config.max_concurrent_messages, config.concurrency_mode)?,
+                admission: AdmissionController::new(AdmissionQuotas::from(&config)),
+                compliance: ComplianceEngine::default(),
                 config,
-                state_machine: coordination::ReplicatedStateMachine::new(),
+                state_machine: Arc::new(coordination::ReplicatedStateMachine::new()),
                 crypto: crypto::KyberKem,
+                capabilities: None,
+                revocations: None,
+                clock: Arc::new(SystemClock),
+                skew: SkewPolicy::default(),
+                snapshot_key: None,
             })
         }
 
+        /// Replace the wall clock used for identity validity checks
+        pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+            self.clock = clock;
+            self
+        }
+
+        /// Evaluate `AgentConfig::compliance_rules` with this engine, which
+        /// may carry deployment-specific rules
+        pub fn with_compliance(mut self, compliance: ComplianceEngine) -> Self {
+            self.compliance = compliance;
+            self
+        }
+
+        /// Tolerate this much disagreement between the local clock and the
+        /// one that set the identity's validity window
+        pub fn with_skew_policy(mut self, skew: SkewPolicy) -> Self {
+            self.skew = skew;
+            self
+        }
+
+        /// Authenticate `export_state` output with `key`, which the host
+        /// restoring it must hold too
+        pub fn with_snapshot_key(mut self, key: SnapshotKey) -> Self {
+            self.snapshot_key = Some(key);
+            self
+        }
+
+        /// Stop admitting messages once this agent's identity id or
+        /// attestation is revoked
+        pub fn with_revocations(mut self, revocations: Arc<coordination::RevocationList>) -> Self {
+            self.revocations = Some(revocations);
+            self
+        }
+
+        /// Agent-local replicated state, such as the `NonceStore` guarding
+        /// against replayed requests
+        pub fn state_machine(&self) -> Arc<coordination::ReplicatedStateMachine> {
+            self.state_machine.clone()
+        }
+
+        /// Whether the agent identity is currently within its validity
+        /// window, allowing for clock skew
+        pub fn identity_valid(&self) -> bool {
+            self.identity.check_at(self.clock.now_millis(), &self.skew).is_ok()
+        }
+
+        /// Wrap a message in an envelope using the configured codec and
+        /// compression
+        pub fn encode_message<T: Serialize>(&self, msg: &T) -> Result<Vec<u8>, EnterpriseError> {
+            codec::Envelope::seal_with(self.config.codec, &self.config.compression, msg)
+        }
+
+        /// Wrap a message for a peer that accepts only `peer_compression`,
+        /// sending it uncompressed if the configured algorithm is not among
+        /// them
+        pub fn encode_message_for<T: Serialize>(
+            &self,
+            msg: &T,
+            peer_compression: &[codec::Compression],
+        ) -> Result<Vec<u8>, EnterpriseError> {
+            codec::Envelope::seal_with(self.config.codec, &self.config.compression.negotiate(peer_compression), msg)
+        }
+
+        /// Decode an inbound envelope with whichever accepted codec and
+        /// compression it is tagged with
+        pub fn decode_message<T: serde::de::DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, EnterpriseError> {
+            codec::Envelope::open(bytes, &self.config.accepted_codecs, &self.config.accepted_compression)
+                .map(|(_, msg)| msg)
+        }
+
         fn generate_identity() -> Result<AgentIdentity, EnterpriseError> {
             // Hardware-backed identity generation
             unimplemented!("TPM-based identity creation")
         }
 
-        #[instrument(skip(self))]
-        pub async fn process_message(&mut self, msg: Vec<u8>) -> Result<Vec<u8>, EnterpriseError> {
+        #[instrument(skip_all, fields(
+            agent = %self.identity.id,
+            size = msg.len(),
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+        ))]
+        pub async fn process_message(&self, msg: Vec<u8>) -> Result<Vec<u8>, EnterpriseError> {
+            crate::enterprise_result_span!(self.run_pipeline(msg).await)
+        }
+
+        async fn run_pipeline(&self, msg: Vec<u8>) -> Result<Vec<u8>, EnterpriseError> {
+            // Every quota is checked before any work begins; the admission
+            // and the slot are held for the whole pipeline and dropped on
+            // every return path
+            self.identity.check_revocation(self.revocations.as_deref())?;
+            let _admission = self.admission.admit(msg.len() as u64, self.clock.now_millis())?;
+            let _slot = self.message_gate.admit().await?;
+
+            self.identity.check_at(self.clock.now_millis(), &self.skew).inspect_err(|e| {
+                warn!(error = %e, "Agent identity outside its validity window");
+            })?;
+
+            self.compliance.evaluate(
+                &self.config.compliance_rules,
+                &msg,
+                &self.config.accepted_codecs,
+                &self.config.accepted_compression,
+            )?;
+
             // Secure message processing pipeline
             self.validate_protocol(msg)?;
             self.check_authorization()?;
-            self.enforce_quotas()?;
             
             let response = self.execute_logic().await?;
             self.audit_operation()?;
@@ -184,9 +1319,139 @@ pub mod agent {
     }
 }
 
+/// Endpoint access policies
+pub mod policy {
+    use super::*;
+
+    pub mod expr;
+
+    pub use expr::{Policy, PolicyParseError};
+
+    tokio::task_local! {
+        static CURRENT: SecurityContext;
+    }
+
+    /// Decides whether a caller satisfies a policy
+    pub trait PolicyEngine: Send + Sync {
+        fn evaluate(&self, policy: &Policy, caller: &SecurityContext) -> bool;
+    }
+
+    /// Evaluates role and claim checks against the caller's own lists
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ClaimsPolicyEngine;
+
+    impl PolicyEngine for ClaimsPolicyEngine {
+        fn evaluate(&self, policy: &Policy, caller: &SecurityContext) -> bool {
+            match policy {
+                Policy::Role(role) => caller.roles.iter().any(|r| r == role),
+                Policy::Claim(claim) => caller.claims.iter().any(|c| c == claim),
+                Policy::All(policies) => policies.iter().all(|p| self.evaluate(p, caller)),
+                Policy::Any(policies) => policies.iter().any(|p| self.evaluate(p, caller)),
+            }
+        }
+    }
+
+    /// Caller on whose behalf an `api_endpoint` runs
+    #[derive(Clone)]
+    pub struct SecurityContext {
+        pub roles: Vec<String>,
+        pub claims: Vec<String>,
+        engine: Arc<dyn PolicyEngine>,
+    }
+
+    impl SecurityContext {
+        pub fn new(roles: Vec<String>, claims: Vec<String>) -> Self {
+            Self { roles, claims, engine: Arc::new(ClaimsPolicyEngine) }
+        }
+
+        /// Replace the engine policies are evaluated with
+        pub fn with_engine(mut self, engine: Arc<dyn PolicyEngine>) -> Self {
+            self.engine = engine;
+            self
+        }
+
+        /// Context of the scope this runs in, or a caller with no roles or
+        /// claims outside any
+        pub fn acquire() -> Self {
+            CURRENT.try_with(Self::clone)
+                .unwrap_or_else(|_| Self::new(Vec::new(), Vec::new()))
+        }
+
+        /// Run `future` with this as the context endpoints it reaches acquire
+        ///
+        /// The context belongs to the task, not the thread: it follows the
+        /// future across worker threads and is never seen by other tasks
+        /// sharing one. Tasks spawned inside start with no context.
+        pub async fn scope<F: std::future::Future>(self, future: F) -> F::Output {
+            CURRENT.scope(self, future).await
+        }
+
+        /// Run `f` with this as the context endpoints it reaches acquire
+        pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+            CURRENT.sync_scope(self, f)
+        }
+
+        /// Whether the caller satisfies a policy expression; one that does
+        /// not parse is never satisfied
+        pub fn verify_policy(&self, policy: &str) -> bool {
+            match Policy::parse(policy) {
+                Ok(policy) => self.verify(&policy),
+                Err(e) => {
+                    warn!(error = %e, "Denying access under a malformed policy");
+                    false
+                }
+            }
+        }
+
+        /// Whether the caller satisfies an already parsed policy
+        pub fn verify(&self, policy: &Policy) -> bool {
+            self.engine.evaluate(policy, self)
+        }
+    }
+}
+
+pub use policy::SecurityContext;
+
+/// Evaluate a method's result, recording a failure on the current span
+///
+/// An `Err` sets `otel.status_code = "error"` and the error's `Display` as
+/// `otel.status_message`. Tracing drops values for fields a span did not
+/// declare, so the span must list both, usually as `tracing::field::Empty`
+/// in `#[instrument(fields(..))]`.
+#[macro_export]
+macro_rules! enterprise_result_span {
+    ($result:expr) => {{
+        let result = $result;
+        if let Err(e) = &result {
+            $crate::telemetry::record_error(&tracing::Span::current(), e);
+        }
+        result
+    }};
+}
+
 /// Real-time monitoring hooks
 pub mod telemetry {
     use super::*;
+
+    /// Mark `span` failed, with `error` as its OpenTelemetry status message
+    pub fn record_error(span: &tracing::Span, error: &dyn std::fmt::Display) {
+        span.record("otel.status_code", "error");
+        span.record("otel.status_message", tracing::field::display(error));
+    }
+
+    pub mod buffer;
+    pub mod exporter;
+    pub mod otlp_http;
+    pub mod pipeline;
+    pub mod rate_limited_log;
+    pub mod sampler;
+
+    pub use buffer::{bounded, OverflowPolicy, TelemetryBufferConfig, TelemetryReceiver, TelemetrySender};
+    pub use exporter::{MetricBatch, OtlpExporter, OtlpExporterConfig, OtlpTransport};
+    pub use otlp_http::OtlpHttpTransport;
+    pub use pipeline::{collect_batch, TelemetryPipeline, TelemetryPipelineConfig};
+    pub use rate_limited_log::RateLimitedLog;
+    pub use sampler::{Sampler, SamplingPolicy, TraceIdRatioSampler, SAMPLED_FLAG};
     
     #[derive(Debug, Default, Serialize, Deserialize)]
     pub struct PerformanceMetrics {
@@ -196,13 +1461,104 @@ pub mod telemetry {
         pub latency: Duration,
     }
 
+    tokio::task_local! {
+        static CURRENT_TRACE: TraceContext;
+    }
+
     /// Distributed tracing context
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
     pub struct TraceContext {
         pub trace_id: String,
         pub span_id: String,
+        /// Span this one is nested under, if it was started in this process
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub parent_span_id: Option<String>,
         pub flags: u8,
     }
+
+    impl TraceContext {
+        /// Start a new sampled trace
+        pub fn root() -> Self {
+            Self {
+                trace_id: Uuid::new_v4().simple().to_string(),
+                span_id: new_span_id(),
+                parent_span_id: None,
+                flags: SAMPLED_FLAG,
+            }
+        }
+
+        /// Span nested under this one in the same trace
+        pub fn child(&self) -> Self {
+            Self {
+                trace_id: self.trace_id.clone(),
+                span_id: new_span_id(),
+                parent_span_id: Some(self.span_id.clone()),
+                flags: self.flags,
+            }
+        }
+
+        /// Context installed by the innermost enclosing `scope` on this task
+        pub fn current() -> Option<Self> {
+            CURRENT_TRACE.try_with(Clone::clone).ok()
+        }
+
+        /// Run `future` with this as the task's current context
+        pub fn scope<F: std::future::Future>(self, future: F) -> tokio::task::futures::TaskLocalFuture<Self, F> {
+            CURRENT_TRACE.scope(self, future)
+        }
+
+        pub fn is_sampled(&self) -> bool {
+            self.flags & SAMPLED_FLAG != 0
+        }
+
+        /// Set or clear the sampled bit, leaving other flags untouched
+        pub fn set_sampled(&mut self, sampled: bool) {
+            if sampled {
+                self.flags |= SAMPLED_FLAG;
+            } else {
+                self.flags &= !SAMPLED_FLAG;
+            }
+        }
+
+        /// Parse a W3C `traceparent` header value
+        ///
+        /// Only version `00` fields are read; all-zero ids are invalid per the
+        /// spec and rejected.
+        pub fn parse_traceparent(header: &str) -> Option<Self> {
+            let mut parts = header.trim().split('-');
+            let (version, trace_id, span_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+            let is_hex = |s: &str, len: usize| {
+                s.len() == len && s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+            };
+            if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+                return None;
+            }
+            if !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+                return None;
+            }
+            if trace_id.bytes().all(|b| b == b'0') || span_id.bytes().all(|b| b == b'0') {
+                return None;
+            }
+            Some(Self {
+                trace_id: trace_id.to_string(),
+                span_id: span_id.to_string(),
+                parent_span_id: None,
+                flags: u8::from_str_radix(flags, 16).ok()?,
+            })
+        }
+
+        /// Encode as a W3C `traceparent` header value
+        pub fn to_traceparent(&self) -> String {
+            format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.flags)
+        }
+    }
+
+    /// 64-bit span id, hex encoded
+    fn new_span_id() -> String {
+        let mut id = Uuid::new_v4().simple().to_string();
+        id.truncate(16);
+        id
+    }
 }
 
 // FFI Interface for cross-language support
@@ -235,19 +1591,426 @@ mod tests {
         assert!(sk.len() > 2048);
     }
 
+    #[test]
+    fn test_injected_entropy_is_reproducible() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (pk, sk) = crypto::KyberKem::keypair_with(&mut rng);
+            let (ct, ss) = crypto::KyberKem::encaps_with(&pk, &mut rng).unwrap();
+            assert_eq!(crypto::KyberKem::decaps(&ct, &sk).unwrap(), ss);
+            (pk, ct, ss)
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7).0, run(8).0);
+    }
+
+    #[test]
+    fn test_injected_entropy_reaches_hybrid_kem_and_container() {
+        use crypto::Kem;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (pk, sk) = crypto::HybridKem.keypair_with(&mut rng);
+            let sealed = crypto::SecureContainer::seal_with_entropy(
+                &crypto::HybridKem,
+                crypto::SymmetricCipher::ChaCha20Poly1305,
+                &pk,
+                b"ledger",
+                &mut rng,
+            ).unwrap();
+            assert_eq!(sealed.open_with_entropy(&crypto::HybridKem, &sk, &mut rng).unwrap(), b"ledger");
+            assert!(sealed.open_with_entropy(&crypto::KyberKem, &sk, &mut rng).is_err());
+            serde_json::to_value(&sealed).unwrap()
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn test_corrupted_kyber_ciphertext_fails_at_hmac() {
+        let (pk, sk) = crypto::KyberKem::keypair();
+        let container = crypto::SecureContainer::seal(&pk, b"quarterly ledger").unwrap();
+        assert_eq!(container.open(&sk).unwrap(), b"quarterly ledger");
+
+        let mut encoded = serde_json::to_value(&container).unwrap();
+        let tamper = |mutate: &dyn Fn(&mut Vec<serde_json::Value>)| {
+            let mut tampered = encoded.clone();
+            mutate(tampered["kem_ciphertext"].as_array_mut().unwrap());
+            serde_json::from_value::<crypto::SecureContainer>(tampered).unwrap()
+        };
+
+        // Decapsulation of a flipped or truncated ciphertext still yields a
+        // secret of the usual size instead of an error
+        let flipped = tamper(&|ct| ct[0] = (ct[0].as_u64().unwrap() ^ 1).into());
+        let truncated = tamper(&|ct| ct.truncate(100));
+        let (ct, ss) = crypto::KyberKem::encaps(&pk).unwrap();
+        let mut bad_ct = ct.clone();
+        bad_ct[0] ^= 1;
+        assert_eq!(crypto::KyberKem::decaps(&bad_ct, &sk).unwrap().len(), ss.len());
+        assert_eq!(crypto::KyberKem::decaps(&ct[..100], &sk).unwrap().len(), ss.len());
+        assert_eq!(crypto::KyberKem::decaps(&ct[..100], &sk).unwrap(), crypto::KyberKem::decaps(&ct[..100], &sk).unwrap());
+
+        // Malformed keys are errors rather than panics
+        assert!(matches!(crypto::KyberKem::decaps(&ct, &sk[..10]), Err(EnterpriseError::ProtocolError)));
+        assert!(matches!(crypto::KyberKem::encaps(&pk[..10]), Err(EnterpriseError::ProtocolError)));
+        assert!(matches!(container.open(&sk[..10]), Err(EnterpriseError::ProtocolError)));
+        assert!(matches!(crypto::SecureContainer::seal(&pk[..10], b"x"), Err(EnterpriseError::ProtocolError)));
+
+        // ...and the container rejects them exactly like a forged tag
+        encoded["hmac_tag"][0] = (encoded["hmac_tag"][0].as_u64().unwrap() ^ 1).into();
+        let forged = serde_json::from_value::<crypto::SecureContainer>(encoded).unwrap();
+        for container in [flipped, truncated, forged] {
+            assert!(matches!(container.open(&sk), Err(EnterpriseError::IntegrityError)));
+        }
+    }
+
+    #[test]
+    fn test_containers_record_and_open_under_their_kem() {
+        use crypto::Kem;
+
+        for kem in [&crypto::KyberKem as &dyn Kem, &crypto::HybridKem] {
+            let (pk, sk) = kem.keypair();
+            let container = crypto::SecureContainer::seal_with(kem, &pk, b"board minutes").unwrap();
+            assert_eq!(container.algorithm(), kem.algorithm_id());
+
+            let decoded: crypto::SecureContainer =
+                serde_json::from_value(serde_json::to_value(&container).unwrap()).unwrap();
+            assert_eq!(decoded.algorithm(), kem.algorithm_id());
+            assert_eq!(decoded.open(&sk).unwrap(), b"board minutes");
+        }
+
+        // The recorded algorithm picks the KEM; a different or unknown one
+        // is refused like tampering
+        let (pk, sk) = crypto::HybridKem.keypair();
+        let hybrid = crypto::SecureContainer::seal_with(&crypto::HybridKem, &pk, b"x").unwrap();
+        assert!(matches!(hybrid.open_with(&crypto::KyberKem, &sk), Err(EnterpriseError::IntegrityError)));
+        let mut unknown = serde_json::to_value(&hybrid).unwrap();
+        unknown["algorithm"] = "frodo640".into();
+        let unknown: crypto::SecureContainer = serde_json::from_value(unknown).unwrap();
+        assert!(matches!(unknown.open(&sk), Err(EnterpriseError::IntegrityError)));
+
+        // Containers from before the header default to Kyber
+        let (pk, sk) = crypto::KyberKem::keypair();
+        let mut legacy = serde_json::to_value(crypto::SecureContainer::seal(&pk, b"y").unwrap()).unwrap();
+        let object = legacy.as_object_mut().unwrap();
+        object.remove("algorithm");
+        let ciphertext = object.remove("kem_ciphertext").unwrap();
+        object.insert("kyber_ciphertext".into(), ciphertext);
+        let legacy: crypto::SecureContainer = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.open(&sk).unwrap(), b"y");
+    }
+
+    #[test]
+    fn test_hybrid_kem_binds_the_exchange_and_rejects_bad_keys() {
+        use crypto::Kem;
+
+        let (pk, sk) = crypto::HybridKem.keypair();
+        let (ct, ss) = crypto::HybridKem.encaps(&pk).unwrap();
+        assert_eq!(ss.len(), 32);
+        assert_eq!(crypto::HybridKem.decaps(&ct, &sk).unwrap(), ss);
+
+        // Malformed keys and a low-order X25519 key are errors, not panics
+        assert!(matches!(crypto::HybridKem.decaps(&ct, &sk[..16]), Err(EnterpriseError::ProtocolError)));
+        assert!(matches!(crypto::HybridKem.encaps(&pk[..16]), Err(EnterpriseError::ProtocolError)));
+        let mut low_order = pk.clone();
+        low_order[pk.len() - 32..].fill(0);
+        assert!(matches!(crypto::HybridKem.encaps(&low_order), Err(EnterpriseError::ProtocolError)));
+
+        // A zeroed or cut X25519 share rejects implicitly and deterministically
+        let split = ct.len() - 32;
+        let mut zeroed = ct.clone();
+        zeroed[split..].fill(0);
+        let rejected = crypto::HybridKem.decaps(&zeroed, &sk).unwrap();
+        assert_ne!(rejected, ss);
+        assert_eq!(rejected, crypto::HybridKem.decaps(&zeroed, &sk).unwrap());
+        assert_ne!(crypto::HybridKem.decaps(&ct[..ct.len() - 1], &sk).unwrap(), ss);
+
+        // Swapping in another exchange's X25519 share changes the secret
+        let (other_ct, _) = crypto::HybridKem.encaps(&pk).unwrap();
+        let mut swapped = ct.clone();
+        swapped[split..].copy_from_slice(&other_ct[split..]);
+        assert_ne!(crypto::HybridKem.decaps(&swapped, &sk).unwrap(), ss);
+    }
+
+    #[test]
+    fn test_container_header_is_authenticated() {
+        use crypto::Kem;
+
+        let (pk, sk) = crypto::HybridKem.keypair();
+        let sealed = serde_json::to_value(crypto::SecureContainer::seal_with(&crypto::HybridKem, &pk, b"x").unwrap()).unwrap();
+        assert_eq!(sealed["version"], 1);
+
+        let mut stripped = sealed.clone();
+        stripped.as_object_mut().unwrap().remove("version");
+        let stripped: crypto::SecureContainer = serde_json::from_value(stripped).unwrap();
+        assert!(matches!(stripped.open(&sk), Err(EnterpriseError::IntegrityError)));
+
+        let mut relabelled = sealed.clone();
+        relabelled["cipher"] = "ChaCha20Poly1305".into();
+        let relabelled: crypto::SecureContainer = serde_json::from_value(relabelled).unwrap();
+        assert!(matches!(relabelled.open(&sk), Err(EnterpriseError::IntegrityError)));
+
+        let genuine: crypto::SecureContainer = serde_json::from_value(sealed).unwrap();
+        assert_eq!(genuine.open(&sk).unwrap(), b"x");
+        assert_eq!(crypto::HybridKem.algorithm_id(), genuine.algorithm());
+    }
+
+    #[test]
+    fn test_open_failures_are_indistinguishable() {
+        let (pk, sk) = crypto::KyberKem::keypair();
+        let encoded = serde_json::to_value(crypto::SecureContainer::seal(&pk, b"salary bands").unwrap()).unwrap();
+        let corrupt = |field: &str, edit: &dyn Fn(&mut Vec<serde_json::Value>)| {
+            let mut tampered = encoded.clone();
+            edit(tampered[field].as_array_mut().unwrap());
+            serde_json::from_value::<crypto::SecureContainer>(tampered).unwrap().open(&sk).unwrap_err()
+        };
+        let flip = |bytes: &mut Vec<serde_json::Value>| bytes[0] = (bytes[0].as_u64().unwrap() ^ 1).into();
+
+        let failures = [
+            corrupt("encrypted_data", &flip),
+            corrupt("kem_ciphertext", &flip),
+            corrupt("nonce", &flip),
+            corrupt("nonce", &|nonce| nonce.truncate(8)),
+            corrupt("hmac_tag", &flip),
+        ];
+        for error in &failures {
+            assert!(matches!(error, EnterpriseError::IntegrityError), "{:?}", error);
+            assert_eq!(error.to_string(), failures[0].to_string());
+        }
+    }
+
+    #[test]
+    fn test_round_trip_under_each_cipher() {
+        let (pk, sk) = crypto::KyberKem::keypair();
+        for cipher in [crypto::SymmetricCipher::Aes256Gcm, crypto::SymmetricCipher::ChaCha20Poly1305] {
+            let container = crypto::SecureContainer::seal_with_cipher(&crypto::KyberKem, cipher, &pk, b"payroll").unwrap();
+            assert_eq!(container.cipher(), cipher);
+            assert_eq!(container.open(&sk).unwrap(), b"payroll", "{:?}", cipher);
+
+            let encoded = serde_json::to_value(&container).unwrap();
+            assert_eq!(encoded["nonce"].as_array().unwrap().len(), cipher.nonce_len());
+            let decoded: crypto::SecureContainer = serde_json::from_value(encoded).unwrap();
+            assert_eq!(decoded.open(&sk).unwrap(), b"payroll", "{:?}", cipher);
+        }
+    }
+
+    #[test]
+    fn test_container_opens_under_the_cipher_it_records() {
+        let (pk, sk) = crypto::KyberKem::keypair();
+        assert_eq!(crypto::SecureContainer::seal(&pk, b"z").unwrap().cipher(), crypto::SymmetricCipher::Aes256Gcm);
+
+        let sealed = crypto::SecureContainer::seal_with_cipher(
+            &crypto::KyberKem,
+            crypto::SymmetricCipher::ChaCha20Poly1305,
+            &pk,
+            b"audit trail",
+        ).unwrap();
+        let mut encoded = serde_json::to_value(&sealed).unwrap();
+        assert_eq!(encoded["cipher"], "ChaCha20Poly1305");
+
+        // Relabelled as AES-GCM, the body no longer authenticates
+        encoded["cipher"] = "Aes256Gcm".into();
+        let relabelled: crypto::SecureContainer = serde_json::from_value(encoded.clone()).unwrap();
+        assert!(matches!(relabelled.open(&sk), Err(EnterpriseError::IntegrityError)));
+
+        // Containers from before the header are AES-GCM with an `aes_nonce`
+        let legacy = crypto::SecureContainer::seal(&pk, b"w").unwrap();
+        let mut legacy = serde_json::to_value(legacy).unwrap();
+        let object = legacy.as_object_mut().unwrap();
+        object.remove("cipher");
+        let nonce = object.remove("nonce").unwrap();
+        object.insert("aes_nonce".into(), nonce);
+        let legacy: crypto::SecureContainer = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.cipher(), crypto::SymmetricCipher::Aes256Gcm);
+        assert_eq!(legacy.open(&sk).unwrap(), b"w");
+    }
+
+    #[test]
+    fn test_traceparent_round_trips() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = telemetry::TraceContext::parse_traceparent(header).unwrap();
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.flags, 1);
+        assert_eq!(context.to_traceparent(), header);
+
+        for bad in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(telemetry::TraceContext::parse_traceparent(bad).is_none(), "accepted {:?}", bad);
+        }
+    }
+
     #[test]
     fn test_agent_creation() {
         let config = agent::AgentConfig {
-            max_memory: 1024,
-            cpu_quota: 0.8,
-            network_budget: 1_000_000,
             compliance_rules: vec!["GDPR".into()],
+            ..agent::AgentConfig::new(1024, 0.8, 1_000_000)
         };
         
         let agent = agent::EnterpriseAgent::new(config).unwrap();
         assert!(agent.identity.id.get_version_num() >= 4);
     }
 
+    #[test]
+    fn test_identity_expires_with_clock() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let clock = Arc::new(clock::MockClock::default());
+            let now = clock::Clock::now_millis(clock.as_ref());
+            let identity = agent::AgentIdentity {
+                id: Uuid::new_v4(),
+                generation: 1,
+                valid_from: now,
+                valid_to: now + 60_000,
+                attestation: Vec::new(),
+            };
+            let config = agent::AgentConfig::new(1024, 0.8, 1_000_000);
+            let agent = agent::EnterpriseAgent::with_identity(config, identity).unwrap().with_clock(clock.clone());
+            assert!(agent.identity_valid());
+
+            clock.advance(Duration::from_secs(59));
+            assert!(agent.identity_valid());
+
+            clock.advance(Duration::from_secs(1));
+            assert!(!agent.identity_valid());
+            assert!(matches!(agent.process_message(Vec::new()).await, Err(EnterpriseError::AuthError(_))));
+        });
+    }
+
+    /// Fields recorded on every span, as strings, by span name
+    #[derive(Clone, Default)]
+    struct SpanCapture(Arc<std::sync::Mutex<HashMap<u64, (&'static str, HashMap<String, String>)>>>);
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanCapture {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().insert(id.into_u64(), (attrs.metadata().name(), fields));
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if let Some((_, fields)) = self.0.lock().unwrap().get_mut(&id.into_u64()) {
+                values.record(&mut FieldVisitor(fields));
+            }
+        }
+    }
+
+    impl SpanCapture {
+        fn span(&self, name: &str) -> HashMap<String, String> {
+            self.0.lock().unwrap().values()
+                .find(|(span, _)| *span == name)
+                .map(|(_, fields)| fields.clone())
+                .unwrap_or_else(|| panic!("no {} span recorded", name))
+        }
+    }
+
+    #[test]
+    fn test_failed_message_records_error_status_on_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = SpanCapture::default();
+        let _default = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let identity = agent::AgentIdentity {
+                id: Uuid::new_v4(),
+                generation: 1,
+                valid_from: 0,
+                valid_to: u128::MAX,
+                attestation: Vec::new(),
+            };
+            let config = agent::AgentConfig::new(16, 0.8, 1_000_000);
+            let agent = agent::EnterpriseAgent::with_identity(config, identity).unwrap();
+            let error = agent.process_message(vec![0; 32]).await.unwrap_err();
+
+            let span = capture.span("process_message");
+            assert_eq!(span["otel.status_code"], "error");
+            assert_eq!(span["otel.status_message"], error.to_string());
+            assert_eq!(span["size"], "32");
+        });
+    }
+
+    #[test]
+    fn test_message_with_health_data_in_the_clear_is_refused() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let identity = agent::AgentIdentity {
+                id: Uuid::new_v4(),
+                generation: 1,
+                valid_from: 0,
+                valid_to: u128::MAX,
+                attestation: Vec::new(),
+            };
+            let config = agent::AgentConfig {
+                compliance_rules: vec!["HIPAA".into()],
+                ..agent::AgentConfig::new(1 << 20, 0.8, 1_000_000)
+            };
+            let agent = agent::EnterpriseAgent::with_identity(config, identity).unwrap();
+
+            let (pk, _) = crypto::KyberKem::keypair();
+            let sealed = crypto::SecureContainer::seal(&pk, b"lab results").unwrap();
+            let body = serde_json::json!({ "sealed": sealed, "text": "patient reports chest pain" });
+            let msg = codec::Envelope::seal(codec::MessageCodec::Json, &body).unwrap();
+            match agent.process_message(msg).await {
+                Err(EnterpriseError::AccessViolation { module, reason }) => {
+                    assert_eq!(module, "compliance");
+                    assert_eq!(reason, "HIPAA: fields outside the secure container: text");
+                }
+                other => panic!("expected a HIPAA violation, got {:?}", other.map(|_| ())),
+            }
+        });
+    }
+
+    #[test]
+    fn test_admission_runs_before_any_other_check() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            // An expired identity would otherwise fail with AuthError
+            let identity = agent::AgentIdentity {
+                id: Uuid::new_v4(),
+                generation: 1,
+                valid_from: 0,
+                valid_to: 1,
+                attestation: Vec::new(),
+            };
+            let config = agent::AgentConfig { max_in_flight_bytes: Some(16), ..agent::AgentConfig::new(1024, 0.8, 1_000_000) };
+            let agent = agent::EnterpriseAgent::with_identity(config, identity).unwrap();
+            match agent.process_message(vec![0; 32]).await {
+                Err(EnterpriseError::ResourceLimit(reason)) => assert!(reason.starts_with("in_flight_bytes quota"), "{}", reason),
+                other => panic!("expected in-flight bytes quota breach, got {:?}", other.map(|_| ())),
+            }
+        });
+    }
+
     #[test]
     fn test_consensus_mechanism() {
         let rt = Runtime::new().unwrap();
@@ -256,4 +2019,89 @@ mod tests {
             sm.apply_operation(StateOperation::default()).await.unwrap();
         });
     }
+
+    #[test]
+    fn test_message_concurrency_limit() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let gate = agent::ConcurrencyGate::new(2, agent::ConcurrencyMode::Reject).unwrap();
+            let first = gate.admit().await.unwrap();
+            let _second = gate.admit().await.unwrap();
+
+            assert!(matches!(gate.admit().await, Err(EnterpriseError::ResourceLimit(_))));
+
+            // A failing handler still releases its slot
+            drop(first);
+            let failed: Result<(), EnterpriseError> = async {
+                let _slot = gate.admit().await?;
+                Err(EnterpriseError::ProtocolError)
+            }.await;
+            assert!(failed.is_err());
+            assert_eq!(gate.available(), 1);
+        });
+    }
+
+    #[test]
+    fn test_zero_message_concurrency_rejected() {
+        for mode in [agent::ConcurrencyMode::Reject, agent::ConcurrencyMode::Wait] {
+            assert!(matches!(agent::ConcurrencyGate::new(0, mode), Err(EnterpriseError::ResourceLimit(_))));
+        }
+
+        let identity = agent::AgentIdentity {
+            id: Uuid::new_v4(),
+            generation: 1,
+            valid_from: 0,
+            valid_to: u128::MAX,
+            attestation: Vec::new(),
+        };
+        let config = agent::AgentConfig { max_concurrent_messages: 0, ..agent::AgentConfig::new(1024, 0.8, 1_000_000) };
+        assert!(matches!(
+            agent::EnterpriseAgent::with_identity(config, identity),
+            Err(EnterpriseError::ResourceLimit(_))
+        ));
+    }
+
+    #[test]
+    fn test_policy_satisfied_by_entered_context() {
+        let auditor = SecurityContext::new(vec!["auditor".into()], vec!["GDPR".into()]);
+        auditor.sync_scope(|| {
+            let context = SecurityContext::acquire();
+            assert!(context.verify_policy("role:admin || (role:auditor && claim:GDPR)"));
+            assert!(context.verify_policy("claim:GDPR"));
+        });
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_context_follows_its_task_and_no_other() {
+        let auditor = SecurityContext::new(vec!["auditor".into()], vec![]);
+        let bystander = tokio::spawn(async {
+            for _ in 0..100 {
+                assert!(!SecurityContext::acquire().verify_policy("role:auditor"));
+                tokio::task::yield_now().await;
+            }
+        });
+        auditor.scope(async {
+            for _ in 0..100 {
+                tokio::task::yield_now().await;
+                assert!(SecurityContext::acquire().verify_policy("role:auditor"));
+            }
+            let spawned = tokio::spawn(async { SecurityContext::acquire().verify_policy("role:auditor") });
+            assert!(!spawned.await.unwrap());
+        }).await;
+        bystander.await.unwrap();
+        assert!(!SecurityContext::acquire().verify_policy("role:auditor"));
+    }
+
+    #[test]
+    fn test_policy_denied_without_required_claims() {
+        let context = SecurityContext::new(vec!["auditor".into()], vec!["GDPR".into()]);
+        assert!(!context.verify_policy("role:admin"));
+        assert!(!context.verify_policy("role:auditor && claim:HIPAA"));
+        assert!(!context.verify_policy("role:auditor &&"));
+
+        // Outside any scope the caller is anonymous
+        assert!(!SecurityContext::acquire().verify_policy("role:auditor"));
+        context.sync_scope(|| assert!(SecurityContext::acquire().verify_policy("role:auditor")));
+        assert!(!SecurityContext::acquire().verify_policy("role:auditor"));
+    }
 }
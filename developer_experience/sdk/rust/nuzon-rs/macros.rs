@@ -4,24 +4,42 @@
 #![feature(proc_macro_diagnostic)]
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, quote_spanned};
 use syn::{
     parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields,
-    GenericParam, Generics, Ident, Lit, Meta, NestedMeta, Type
+    GenericParam, Generics, Ident, Lit, LitStr, Meta, NestedMeta, Type
 };
 
+#[path = "policy/expr.rs"]
+mod policy_expr;
+
+use policy_expr::Policy;
+
 /// Enterprise-grade API endpoint generation
+///
+/// The attribute is a policy expression checked against the caller's
+/// `SecurityContext`. It is parsed here, so a malformed policy fails the
+/// build rather than denying every call, with the parse error pointing at
+/// the attribute; `tests/ui/malformed_policy.rs` holds the expected
+/// diagnostics.
 #[proc_macro_attribute]
 pub fn api_endpoint(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as LitStr);
     let input = parse_macro_input!(item as syn::ItemFn);
     
     let func_vis = &input.vis;
     let func_sig = &input.sig;
     let func_block = &input.block;
 
+    let policy = match Policy::parse(&attr.value()) {
+        Ok(policy) => policy_tokens(&policy),
+        Err(e) => return Error::new(attr.span(), e).to_compile_error().into(),
+    };
+
     let security_check = quote! {
         let __ent_ctx = nuzon_core::SecurityContext::acquire();
-        if !__ent_ctx.verify_policy(#attr) {
+        if !__ent_ctx.verify(&#policy) {
             return Err(nuzon_core::EnterpriseError::AccessViolation {
                 module: module_path!(),
                 reason: "Policy violation".into()
@@ -49,6 +67,22 @@ pub fn api_endpoint(attr: TokenStream, item: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
+/// Expression rebuilding a parsed policy at run time
+fn policy_tokens(policy: &Policy) -> TokenStream2 {
+    match policy {
+        Policy::Role(role) => quote! { nuzon_core::policy::Policy::Role(#role.to_string()) },
+        Policy::Claim(claim) => quote! { nuzon_core::policy::Policy::Claim(#claim.to_string()) },
+        Policy::All(policies) => {
+            let policies = policies.iter().map(policy_tokens);
+            quote! { nuzon_core::policy::Policy::All(vec![#(#policies),*]) }
+        }
+        Policy::Any(policies) => {
+            let policies = policies.iter().map(policy_tokens);
+            quote! { nuzon_core::policy::Policy::Any(vec![#(#policies),*]) }
+        }
+    }
+}
+
 /// Quantum-safe serialization framework
 #[proc_macro_derive(QuantumSerialize, attributes(qs_field))]
 pub fn quantum_serialize(input: TokenStream) -> TokenStream {
@@ -184,3 +218,11 @@ fn parse_field_attrs(attrs: &[Attribute]) -> FieldAttributes {
     
     result
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_malformed_policies_fail_the_build() {
+        trybuild::TestCases::new().compile_fail("tests/ui/malformed_policy.rs");
+    }
+}
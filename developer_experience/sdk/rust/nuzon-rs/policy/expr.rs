@@ -0,0 +1,174 @@
+// expr.rs - Endpoint Policy Expressions
+//
+// Shared with the macros crate so `api_endpoint` can reject a malformed
+// policy at compile time; keep this file free of dependencies beyond std.
+use std::fmt;
+
+/// Parsed access policy
+///
+/// Written as checks joined by `&&` and `||`, with `&&` binding tighter
+/// and parentheses for grouping:
+///
+/// ```text
+/// role:admin || (claim:GDPR && role:auditor)
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Policy {
+    /// Caller holds the named role
+    Role(String),
+    /// Caller presents the named claim
+    Claim(String),
+    /// Every sub-policy holds
+    All(Vec<Policy>),
+    /// At least one sub-policy holds
+    Any(Vec<Policy>),
+}
+
+/// Why a policy expression could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyParseError {
+    /// Byte offset of the offending input
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for PolicyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid policy at byte {}: {}", self.offset, self.reason)
+    }
+}
+
+impl std::error::Error for PolicyParseError {}
+
+impl Policy {
+    pub fn parse(input: &str) -> Result<Self, PolicyParseError> {
+        let mut parser = Parser { input, pos: 0 };
+        let policy = parser.any()?;
+        parser.skip_whitespace();
+        if parser.pos < input.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(policy)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn any(&mut self) -> Result<Policy, PolicyParseError> {
+        let mut terms = vec![self.all()?];
+        while self.eat("||") {
+            terms.push(self.all()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Policy::Any(terms) })
+    }
+
+    fn all(&mut self) -> Result<Policy, PolicyParseError> {
+        let mut terms = vec![self.atom()?];
+        while self.eat("&&") {
+            terms.push(self.atom()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Policy::All(terms) })
+    }
+
+    fn atom(&mut self) -> Result<Policy, PolicyParseError> {
+        if self.eat("(") {
+            let inner = self.any()?;
+            if !self.eat(")") {
+                return Err(self.error("expected `)`"));
+            }
+            return Ok(inner);
+        }
+
+        self.skip_whitespace();
+        let start = self.pos;
+        let kind = self.name();
+        let check: fn(String) -> Policy = match kind {
+            "role" => Policy::Role,
+            "claim" => Policy::Claim,
+            "" => return Err(self.error("expected `role:`, `claim:` or `(`")),
+            other => {
+                let reason = format!("unknown check `{}`", other);
+                self.pos = start;
+                return Err(self.error(reason));
+            }
+        };
+        if !self.input[self.pos..].starts_with(':') {
+            return Err(self.error("expected `:`"));
+        }
+        self.pos += 1;
+        match self.name() {
+            "" => Err(self.error(format!("expected a {} name", kind))),
+            name => Ok(check(name.to_string())),
+        }
+    }
+
+    /// Letters, digits and `_ - .`, possibly empty
+    fn name(&mut self) -> &'a str {
+        let input = self.input;
+        let rest = &input[self.pos..];
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || "_-.".contains(c))).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matched = self.input[self.pos..].starts_with(token);
+        if matched {
+            self.pos += token.len();
+        }
+        matched
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, reason: impl Into<String>) -> PolicyParseError {
+        PolicyParseError { offset: self.pos, reason: reason.into() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        use Policy::*;
+        assert_eq!(
+            Policy::parse("role:admin || claim:GDPR && role:auditor").unwrap(),
+            Any(vec![
+                Role("admin".into()),
+                All(vec![Claim("GDPR".into()), Role("auditor".into())]),
+            ])
+        );
+        assert_eq!(
+            Policy::parse(" (role:admin || claim:GDPR) && role:auditor ").unwrap(),
+            All(vec![
+                Any(vec![Role("admin".into()), Claim("GDPR".into())]),
+                Role("auditor".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_malformed_policies_rejected() {
+        for (input, offset) in [
+            ("", 0),
+            ("role:", 5),
+            ("group:ops", 0),
+            ("role:admin &&", 13),
+            ("(role:admin", 11),
+            ("role:admin claim:GDPR", 11),
+        ] {
+            let error = Policy::parse(input).unwrap_err();
+            assert_eq!(error.offset, offset, "{:?}: {}", input, error);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// buffer.rs - Bounded Buffering Between Metric Producers and the Exporter
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use prometheus::{IntCounter, Opts};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::EnterpriseError;
+
+fn default_capacity() -> usize {
+    4096
+}
+
+/// What a full buffer does with a new sample
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Evict the oldest buffered sample and count it in `telemetry_dropped_total`
+    #[default]
+    DropOldest,
+    /// Make the producer wait until the exporter catches up
+    BlockProducer,
+}
+
+/// Telemetry buffer settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryBufferConfig {
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for TelemetryBufferConfig {
+    fn default() -> Self {
+        Self { capacity: default_capacity(), overflow: OverflowPolicy::default() }
+    }
+}
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    senders: AtomicUsize,
+    /// Set once the receiver is dropped
+    closed: AtomicBool,
+    not_empty: Notify,
+    not_full: Notify,
+    dropped: IntCounter,
+}
+
+impl<T> Shared<T> {
+    fn queue(&self) -> std::sync::MutexGuard<'_, VecDeque<T>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Create a bounded buffer; memory is capped at `capacity` samples
+///
+/// `name` labels the buffer's drop counter, so several buffers can be
+/// registered with one registry.
+pub fn bounded<T>(name: &str, config: &TelemetryBufferConfig) -> (TelemetrySender<T>, TelemetryReceiver<T>) {
+    let capacity = config.capacity.max(1);
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+        overflow: config.overflow,
+        senders: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+        dropped: IntCounter::with_opts(
+            Opts::new("telemetry_dropped_total", "Telemetry samples evicted from a full buffer")
+                .const_label("buffer", name)
+        ).expect("metric options are valid"),
+    });
+    (TelemetrySender { shared: shared.clone() }, TelemetryReceiver { shared })
+}
+
+/// Producer half; clone for each metric source
+pub struct TelemetrySender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> TelemetrySender<T> {
+    /// Buffer a sample, applying the overflow policy when full
+    ///
+    /// Once the receiver is gone the sample is discarded and the send fails
+    /// with `CriticalFailure`, including a send blocked on a full buffer.
+    pub async fn send(&self, sample: T) -> Result<(), EnterpriseError> {
+        let mut sample = Some(sample);
+        loop {
+            let notified = self.shared.not_full.notified();
+            if self.shared.closed.load(Ordering::SeqCst) {
+                return Err(EnterpriseError::CriticalFailure);
+            }
+            {
+                let mut queue = self.shared.queue();
+                if queue.len() >= self.shared.capacity {
+                    match self.shared.overflow {
+                        OverflowPolicy::DropOldest => {
+                            queue.pop_front();
+                            self.shared.dropped.inc();
+                        }
+                        OverflowPolicy::BlockProducer => {
+                            drop(queue);
+                            notified.await;
+                            continue;
+                        }
+                    }
+                }
+                queue.push_back(sample.take().expect("sample buffered once"));
+            }
+            self.shared.not_empty.notify_one();
+            return Ok(());
+        }
+    }
+
+    /// Counter to register with the metrics exporter
+    pub fn dropped_counter(&self) -> &IntCounter {
+        &self.shared.dropped
+    }
+}
+
+impl<T> Clone for TelemetrySender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::SeqCst);
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for TelemetrySender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.not_empty.notify_one();
+        }
+    }
+}
+
+/// Exporter half
+pub struct TelemetryReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> TelemetryReceiver<T> {
+    /// Next sample, or `None` once every sender is gone and the buffer is drained
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let notified = self.shared.not_empty.notified();
+            if let Some(sample) = self.shared.queue().pop_front() {
+                self.shared.not_full.notify_one();
+                return Some(sample);
+            }
+            if self.shared.senders.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            notified.await;
+        }
+    }
+
+    /// Samples currently buffered
+    pub fn len(&self) -> usize {
+        self.shared.queue().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for TelemetryReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.not_full.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_stalled_consumer_drops_oldest() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config = TelemetryBufferConfig { capacity: 4, overflow: OverflowPolicy::DropOldest };
+            let (tx, mut rx) = bounded("test", &config);
+
+            // Nothing consumes while the producer runs
+            for sample in 0..10u32 {
+                tx.send(sample).await.unwrap();
+            }
+            assert_eq!(tx.dropped_counter().get(), 6);
+            assert_eq!(rx.len(), 4);
+
+            drop(tx);
+            let mut drained = Vec::new();
+            while let Some(sample) = rx.recv().await {
+                drained.push(sample);
+            }
+            assert_eq!(drained, vec![6, 7, 8, 9]);
+        });
+    }
+
+    #[test]
+    fn test_block_policy_waits_for_consumer() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config = TelemetryBufferConfig { capacity: 1, overflow: OverflowPolicy::BlockProducer };
+            let (tx, mut rx) = bounded("test", &config);
+            tx.send(1u32).await.unwrap();
+
+            assert!(tokio::time::timeout(Duration::from_millis(50), tx.send(2)).await.is_err());
+            assert_eq!(rx.recv().await, Some(1));
+            tokio::time::timeout(Duration::from_millis(50), tx.send(3)).await.unwrap().unwrap();
+            assert_eq!(rx.recv().await, Some(3));
+            assert_eq!(tx.dropped_counter().get(), 0);
+        });
+    }
+
+    #[test]
+    fn test_dropped_receiver_releases_blocked_producer() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config = TelemetryBufferConfig { capacity: 1, overflow: OverflowPolicy::BlockProducer };
+            let (tx, rx) = bounded("test", &config);
+            tx.send(1u32).await.unwrap();
+
+            let blocked = tokio::spawn(async move { tx.send(2).await });
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(!blocked.is_finished());
+            drop(rx);
+            let result = tokio::time::timeout(Duration::from_millis(200), blocked).await
+                .expect("producer still blocked after the receiver was dropped")
+                .unwrap();
+            assert!(matches!(result, Err(EnterpriseError::CriticalFailure)));
+        });
+    }
+
+    #[test]
+    fn test_buffers_register_side_by_side() {
+        let registry = prometheus::Registry::new();
+        let config = TelemetryBufferConfig::default();
+        let (metrics, _metrics_rx) = bounded::<u32>("metrics", &config);
+        let (spans, _spans_rx) = bounded::<u32>("spans", &config);
+        registry.register(Box::new(metrics.dropped_counter().clone())).unwrap();
+        registry.register(Box::new(spans.dropped_counter().clone())).unwrap();
+        assert_eq!(registry.gather()[0].get_metric().len(), 2);
+    }
+}
@@ -0,0 +1,310 @@
+// exporter.rs - OTLP Metric Export with Replay Across Collector Outages
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use prometheus::{IntCounter, IntGauge};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use super::buffer::TelemetryReceiver;
+use crate::EnterpriseError;
+
+fn default_replay_intervals() -> usize {
+    60
+}
+
+fn default_retry_interval_ms() -> u64 {
+    5_000
+}
+
+/// One collection interval's worth of metric points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricBatch {
+    /// When the interval was collected; kept as-is on replay
+    pub collected_at: SystemTime,
+    /// Metric name and value pairs
+    pub points: Vec<(String, f64)>,
+}
+
+/// Connection to an OTLP collector
+#[async_trait]
+pub trait OtlpTransport: Send + Sync {
+    /// Deliver one batch; an error means the collector is unreachable
+    async fn export(&self, batch: &MetricBatch) -> Result<(), EnterpriseError>;
+}
+
+/// Exporter settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OtlpExporterConfig {
+    /// Most recent intervals retained while the collector is down
+    #[serde(default = "default_replay_intervals")]
+    pub replay_intervals: usize,
+    /// How often to retry delivery while intervals are pending
+    #[serde(default = "default_retry_interval_ms")]
+    pub retry_interval_ms: u64,
+}
+
+impl Default for OtlpExporterConfig {
+    fn default() -> Self {
+        Self {
+            replay_intervals: default_replay_intervals(),
+            retry_interval_ms: default_retry_interval_ms(),
+        }
+    }
+}
+
+/// Exports metric intervals, holding back the most recent ones while the
+/// collector is unreachable and replaying them in order on reconnect
+///
+/// Memory is capped at `replay_intervals` batches; the oldest pending batch
+/// is evicted first and counted in `telemetry_export_dropped_total`. No
+/// lock is held while a batch is in flight, so queueing never waits on the
+/// collector; one delivery loop runs at a time and the others leave their
+/// batches to it.
+pub struct OtlpExporter<X> {
+    transport: X,
+    /// Pending batches, each tagged with its sequence number
+    pending: Mutex<Pending>,
+    flushing: AtomicBool,
+    capacity: usize,
+    retry_interval: Duration,
+    depth: IntGauge,
+    dropped: IntCounter,
+}
+
+#[derive(Default)]
+struct Pending {
+    batches: VecDeque<(u64, MetricBatch)>,
+    next_seq: u64,
+}
+
+/// Clears the flushing flag however the delivery loop ends
+struct FlushGuard<'a>(&'a AtomicBool);
+
+impl Drop for FlushGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl<X: OtlpTransport> OtlpExporter<X> {
+    /// Exporter delivering through `transport`
+    pub fn new(transport: X, config: &OtlpExporterConfig) -> Self {
+        Self {
+            transport,
+            pending: Mutex::default(),
+            flushing: AtomicBool::new(false),
+            capacity: config.replay_intervals.max(1),
+            retry_interval: Duration::from_millis(config.retry_interval_ms),
+            depth: IntGauge::new(
+                "telemetry_export_buffer_depth",
+                "Metric intervals awaiting delivery to the OTLP collector"
+            ).expect("static metric options are valid"),
+            dropped: IntCounter::new(
+                "telemetry_export_dropped_total",
+                "Metric intervals evicted while the OTLP collector was unreachable"
+            ).expect("static metric options are valid"),
+        }
+    }
+
+    /// Queue `batch` behind any pending intervals and deliver as many as the
+    /// collector accepts
+    pub async fn export(&self, batch: MetricBatch) {
+        {
+            let mut pending = self.pending();
+            if pending.batches.len() >= self.capacity {
+                pending.batches.pop_front();
+                self.dropped.inc();
+            }
+            let seq = pending.next_seq;
+            pending.next_seq += 1;
+            pending.batches.push_back((seq, batch));
+            self.depth.set(pending.batches.len() as i64);
+        }
+        self.flush().await;
+    }
+
+    /// Retry delivery of pending intervals; returns how many remain
+    ///
+    /// Returns at once, leaving the batches to it, while another call is
+    /// already delivering.
+    pub async fn flush(&self) -> usize {
+        if self.flushing.swap(true, Ordering::SeqCst) {
+            return self.pending().batches.len();
+        }
+        let _flushing = FlushGuard(&self.flushing);
+
+        let backlog = self.pending().batches.len();
+        let mut delivered = 0;
+        loop {
+            let Some((seq, batch)) = self.pending().batches.front().cloned() else { break };
+            if let Err(e) = self.transport.export(&batch).await {
+                debug!(error = %e, pending = self.pending().batches.len(), "OTLP collector unreachable, buffering");
+                break;
+            }
+            delivered += 1;
+            // Evicted while in flight if the window moved on meanwhile
+            let mut pending = self.pending();
+            if pending.batches.front().is_some_and(|(front, _)| *front == seq) {
+                pending.batches.pop_front();
+            }
+            self.depth.set(pending.batches.len() as i64);
+        }
+
+        let remaining = self.pending().batches.len();
+        if backlog > 1 && delivered > 1 && remaining == 0 {
+            info!(replayed = delivered, "OTLP collector reachable again, buffered intervals delivered");
+        }
+        remaining
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Export every interval from `rx`, retrying pending ones on the retry
+    /// interval, until all senders are gone
+    ///
+    /// Whatever is still pending at shutdown gets one last delivery attempt.
+    pub async fn run(&self, mut rx: TelemetryReceiver<MetricBatch>) {
+        loop {
+            let backlog = self.pending().batches.len();
+            let next = if backlog == 0 {
+                rx.recv().await
+            } else {
+                tokio::select! {
+                    batch = rx.recv() => batch,
+                    _ = tokio::time::sleep(self.retry_interval) => {
+                        self.flush().await;
+                        continue;
+                    }
+                }
+            };
+            match next {
+                Some(batch) => self.export(batch).await,
+                None => break,
+            }
+        }
+        let remaining = self.flush().await;
+        if remaining > 0 {
+            warn!(remaining, "Exporter stopped with undelivered metric intervals");
+        }
+    }
+
+    /// Gauge to register with the metrics exporter
+    pub fn depth_gauge(&self) -> &IntGauge {
+        &self.depth
+    }
+
+    /// Counter to register with the metrics exporter
+    pub fn dropped_counter(&self) -> &IntCounter {
+        &self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::{runtime::Runtime, sync::Semaphore};
+
+    #[derive(Clone, Default)]
+    struct MockCollector {
+        down: Arc<AtomicBool>,
+        received: Arc<Mutex<Vec<MetricBatch>>>,
+    }
+
+    #[async_trait]
+    impl OtlpTransport for MockCollector {
+        async fn export(&self, batch: &MetricBatch) -> Result<(), EnterpriseError> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(EnterpriseError::ResourceLimit("collector unavailable".into()));
+            }
+            self.received.lock().unwrap().push(batch.clone());
+            Ok(())
+        }
+    }
+
+    fn interval(secs: u64) -> MetricBatch {
+        MetricBatch {
+            collected_at: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+            points: vec![("requests_total".into(), secs as f64)],
+        }
+    }
+
+    #[test]
+    fn test_buffered_intervals_replay_after_collector_restart() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let collector = MockCollector::default();
+            let config = OtlpExporterConfig { replay_intervals: 3, ..Default::default() };
+            let exporter = OtlpExporter::new(collector.clone(), &config);
+
+            exporter.export(interval(1)).await;
+            collector.down.store(true, Ordering::SeqCst);
+            for secs in 2..=5 {
+                exporter.export(interval(secs)).await;
+            }
+            assert_eq!(exporter.depth_gauge().get(), 3);
+            assert_eq!(exporter.dropped_counter().get(), 1);
+            assert_eq!(collector.received.lock().unwrap().len(), 1);
+
+            // Collector back: the window is replayed, oldest first, with the
+            // timestamps it was collected at
+            collector.down.store(false, Ordering::SeqCst);
+            assert_eq!(exporter.flush().await, 0);
+            assert_eq!(exporter.depth_gauge().get(), 0);
+            let received = collector.received.lock().unwrap().clone();
+            assert_eq!(received, vec![interval(1), interval(3), interval(4), interval(5)]);
+        });
+    }
+    /// Delivers one batch per permit added to `admit`
+    #[derive(Clone, Default)]
+    struct StalledCollector {
+        admit: Arc<Semaphore>,
+        received: Arc<Mutex<Vec<MetricBatch>>>,
+    }
+
+    #[async_trait]
+    impl OtlpTransport for StalledCollector {
+        async fn export(&self, batch: &MetricBatch) -> Result<(), EnterpriseError> {
+            self.admit.acquire().await.unwrap().forget();
+            self.received.lock().unwrap().push(batch.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_queueing_does_not_wait_on_a_slow_collector() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let collector = StalledCollector::default();
+            let exporter = Arc::new(OtlpExporter::new(collector.clone(), &OtlpExporterConfig::default()));
+
+            let first = tokio::spawn({
+                let exporter = exporter.clone();
+                async move { exporter.export(interval(1)).await }
+            });
+            while !exporter.flushing.load(Ordering::SeqCst) {
+                tokio::task::yield_now().await;
+            }
+            // The first delivery is stuck in the collector; the second batch
+            // is still queued straight away
+            tokio::time::timeout(Duration::from_millis(50), exporter.export(interval(2))).await
+                .expect("export waited on an in-flight delivery");
+            assert_eq!(exporter.depth_gauge().get(), 2);
+
+            collector.admit.add_permits(2);
+            first.await.unwrap();
+            assert_eq!(exporter.depth_gauge().get(), 0);
+            assert_eq!(*collector.received.lock().unwrap(), vec![interval(1), interval(2)]);
+        });
+    }
+}
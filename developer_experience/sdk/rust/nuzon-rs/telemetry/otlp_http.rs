@@ -0,0 +1,194 @@
+// otlp_http.rs - OTLP/HTTP JSON Delivery to a Collector
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::exporter::{MetricBatch, OtlpTransport};
+use crate::EnterpriseError;
+
+/// Path collectors accept metric exports on
+const METRICS_PATH: &str = "/v1/metrics";
+/// Most of a collector's response read; only the status line matters
+const MAX_RESPONSE_LEN: u64 = 64 * 1024;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exports batches as OTLP/HTTP JSON to a plaintext collector endpoint,
+/// such as a local collector sidecar on `http://localhost:4318`
+///
+/// Each point becomes a gauge data point stamped with the batch's
+/// collection time; the `name{label="value",...}` form `collect_batch`
+/// produces is split back into the metric name and its attributes.
+#[derive(Debug, Clone)]
+pub struct OtlpHttpTransport {
+    /// `host:port` to connect to and send as `Host`
+    authority: String,
+    service_name: String,
+    timeout: Duration,
+}
+
+impl OtlpHttpTransport {
+    /// Transport for the collector at `endpoint`, an `http://host:port` URL
+    pub fn new(endpoint: &str, service_name: impl Into<String>) -> Result<Self, EnterpriseError> {
+        let authority = endpoint.strip_prefix("http://")
+            .map(|rest| rest.trim_end_matches('/'))
+            .filter(|authority| !authority.is_empty() && !authority.contains('/'))
+            .ok_or(EnterpriseError::ProtocolError)?;
+        let authority = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+        Ok(Self { authority, service_name: service_name.into(), timeout: DEFAULT_TIMEOUT })
+    }
+
+    /// Give up on a delivery after `timeout` instead of 10s
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `ExportMetricsServiceRequest` carrying `batch`
+    pub fn encode(&self, batch: &MetricBatch) -> Value {
+        let at = batch.collected_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string();
+        let metrics: Vec<_> = batch.points.iter()
+            .map(|(key, value)| {
+                let (name, attributes) = split_labels(key);
+                let attributes: Vec<_> = attributes.into_iter()
+                    .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                    .collect();
+                json!({
+                    "name": name,
+                    "gauge": {"dataPoints": [{"timeUnixNano": at, "asDouble": value, "attributes": attributes}]},
+                })
+            })
+            .collect();
+        json!({
+            "resourceMetrics": [{
+                "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": self.service_name}}]},
+                "scopeMetrics": [{"scope": {"name": "nuzon"}, "metrics": metrics}],
+            }]
+        })
+    }
+
+    async fn post(&self, body: &[u8]) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(&self.authority).await?;
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            METRICS_PATH, self.authority, body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+        stream.flush().await?;
+
+        let mut response = Vec::new();
+        stream.take(MAX_RESPONSE_LEN).read_to_end(&mut response).await?;
+        let response = String::from_utf8_lossy(&response);
+        Ok(response.lines().next().unwrap_or_default().to_string())
+    }
+}
+
+#[async_trait]
+impl OtlpTransport for OtlpHttpTransport {
+    async fn export(&self, batch: &MetricBatch) -> Result<(), EnterpriseError> {
+        let body = serde_json::to_vec(&self.encode(batch)).map_err(|_| EnterpriseError::ProtocolError)?;
+        let status_line = tokio::time::timeout(self.timeout, self.post(&body)).await
+            .map_err(|_| EnterpriseError::ResourceLimit("OTLP collector timed out".into()))?
+            .map_err(|e| EnterpriseError::ResourceLimit(format!("OTLP collector unreachable: {}", e)))?;
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(EnterpriseError::ResourceLimit(format!("OTLP collector refused export: {}", status_line))),
+        }
+    }
+}
+
+/// Metric name and label pairs of a `name{label="value",...}` key
+fn split_labels(key: &str) -> (&str, Vec<(&str, &str)>) {
+    let Some((name, rest)) = key.split_once('{') else { return (key, Vec::new()) };
+    let mut labels = Vec::new();
+    let mut rest = rest.strip_suffix('}').unwrap_or(rest);
+    while let Some((label, after)) = rest.split_once("=\"") {
+        let Some((value, after)) = after.split_once('"') else { break };
+        labels.push((label, value));
+        rest = after.strip_prefix(',').unwrap_or(after);
+    }
+    (name, labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use tokio::{io::AsyncBufReadExt, net::TcpListener};
+
+    /// One-shot collector answering with `status`, returning the JSON body
+    async fn collector(status: &'static str) -> (String, tokio::task::JoinHandle<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let served = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = tokio::io::BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).await.unwrap();
+            reader.get_mut().write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes()).await.unwrap();
+            serde_json::from_slice(&body).unwrap()
+        });
+        (endpoint, served)
+    }
+
+    fn batch() -> MetricBatch {
+        MetricBatch {
+            collected_at: UNIX_EPOCH + Duration::from_secs(5),
+            points: vec![
+                ("requests_total".into(), 7.0),
+                ("telemetry_dropped_total{buffer=\"otlp_export\",zone=\"a\"}".into(), 1.0),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_posted_as_otlp_json() {
+        let (endpoint, served) = collector("200 OK").await;
+        let transport = OtlpHttpTransport::new(&endpoint, "coordinator").unwrap();
+        transport.export(&batch()).await.unwrap();
+
+        let request = served.await.unwrap();
+        let resource = &request["resourceMetrics"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["value"]["stringValue"], "coordinator");
+        let metrics = &resource["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics[0]["name"], "requests_total");
+        let point = &metrics[0]["gauge"]["dataPoints"][0];
+        assert_eq!(point["asDouble"], 7.0);
+        assert_eq!(point["timeUnixNano"], "5000000000");
+        assert_eq!(metrics[1]["name"], "telemetry_dropped_total");
+        let attributes = &metrics[1]["gauge"]["dataPoints"][0]["attributes"];
+        assert_eq!(attributes[0]["key"], "buffer");
+        assert_eq!(attributes[1]["value"]["stringValue"], "a");
+    }
+
+    #[tokio::test]
+    async fn test_refused_or_unreachable_collector_is_an_error() {
+        let (endpoint, served) = collector("503 Service Unavailable").await;
+        let transport = OtlpHttpTransport::new(&endpoint, "coordinator").unwrap();
+        assert!(matches!(transport.export(&batch()).await, Err(EnterpriseError::ResourceLimit(_))));
+        served.await.unwrap();
+
+        // Nothing listens there any more
+        let unreachable = OtlpHttpTransport::new(&endpoint, "coordinator").unwrap();
+        assert!(unreachable.export(&MetricBatch { collected_at: SystemTime::now(), points: vec![] }).await.is_err());
+
+        assert!(OtlpHttpTransport::new("https://collector:4318", "coordinator").is_err());
+        assert!(OtlpHttpTransport::new("http://collector:4318/v1/metrics", "coordinator").is_err());
+    }
+}
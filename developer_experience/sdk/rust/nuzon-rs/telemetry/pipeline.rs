@@ -0,0 +1,158 @@
+// pipeline.rs - Registry Sampling Through the Buffer to the Exporter
+use std::{sync::Arc, time::{Duration, SystemTime}};
+
+use prometheus::{proto::MetricType, Registry};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::{
+    buffer::{bounded, TelemetryBufferConfig},
+    exporter::{MetricBatch, OtlpExporter, OtlpExporterConfig, OtlpTransport},
+};
+
+fn default_interval_ms() -> u64 {
+    10_000
+}
+
+/// Settings for sampling a registry and exporting what it holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryPipelineConfig {
+    /// How often the registry is sampled into a batch
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    #[serde(default)]
+    pub buffer: TelemetryBufferConfig,
+    #[serde(default)]
+    pub exporter: OtlpExporterConfig,
+}
+
+impl Default for TelemetryPipelineConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: default_interval_ms(),
+            buffer: TelemetryBufferConfig::default(),
+            exporter: OtlpExporterConfig::default(),
+        }
+    }
+}
+
+/// Current value of every counter and gauge in `registry`, plus the sum
+/// and count of every histogram, keyed by name and labels
+pub fn collect_batch(registry: &Registry) -> MetricBatch {
+    let mut points = Vec::new();
+    for family in registry.gather() {
+        for metric in family.get_metric() {
+            let labels: Vec<_> = metric.get_label().iter()
+                .map(|label| format!("{}=\"{}\"", label.get_name(), label.get_value()))
+                .collect();
+            let key = |suffix: &str| if labels.is_empty() {
+                format!("{}{}", family.get_name(), suffix)
+            } else {
+                format!("{}{}{{{}}}", family.get_name(), suffix, labels.join(","))
+            };
+            match family.get_field_type() {
+                MetricType::COUNTER => points.push((key(""), metric.get_counter().get_value())),
+                MetricType::GAUGE => points.push((key(""), metric.get_gauge().get_value())),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    points.push((key("_sum"), histogram.get_sample_sum()));
+                    points.push((key("_count"), histogram.get_sample_count() as f64));
+                }
+                _ => {}
+            }
+        }
+    }
+    MetricBatch { collected_at: SystemTime::now(), points }
+}
+
+/// Samples a registry on an interval and streams the batches through a
+/// bounded buffer to an `OtlpExporter`
+///
+/// The buffer's drop counter and the exporter's depth gauge and drop
+/// counter are registered with the sampled registry, so they are exported
+/// alongside everything else.
+pub struct TelemetryPipeline {
+    sampler: JoinHandle<()>,
+    exporter: JoinHandle<()>,
+}
+
+impl TelemetryPipeline {
+    pub fn spawn<X: OtlpTransport + 'static>(
+        registry: Registry,
+        transport: X,
+        config: &TelemetryPipelineConfig,
+    ) -> prometheus::Result<Self> {
+        let (tx, rx) = bounded("otlp_export", &config.buffer);
+        let exporter = Arc::new(OtlpExporter::new(transport, &config.exporter));
+        registry.register(Box::new(tx.dropped_counter().clone()))?;
+        registry.register(Box::new(exporter.depth_gauge().clone()))?;
+        registry.register(Box::new(exporter.dropped_counter().clone()))?;
+
+        let exporter = tokio::spawn(async move { exporter.run(rx).await });
+        let interval = Duration::from_millis(config.interval_ms.max(1));
+        let sampler = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if tx.send(collect_batch(&registry)).await.is_err() {
+                    warn!("Telemetry exporter stopped, no longer sampling metrics");
+                    return;
+                }
+            }
+        });
+        Ok(Self { sampler, exporter })
+    }
+
+    /// Stop sampling and wait for the exporter to drain the buffer and make
+    /// its last delivery attempt
+    pub async fn shutdown(self) {
+        self.sampler.abort();
+        let _ = self.sampler.await;
+        if let Err(e) = self.exporter.await {
+            warn!(error = %e, "Telemetry exporter task failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    use crate::EnterpriseError;
+
+    #[derive(Clone, Default)]
+    struct MockCollector(Arc<Mutex<Vec<MetricBatch>>>);
+
+    #[async_trait]
+    impl OtlpTransport for MockCollector {
+        async fn export(&self, batch: &MetricBatch) -> Result<(), EnterpriseError> {
+            self.0.lock().unwrap().push(batch.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_samples_reach_the_collector() {
+        let registry = Registry::new();
+        let requests = prometheus::IntCounter::new("requests_total", "Requests served").unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        requests.inc_by(7);
+
+        let collector = MockCollector::default();
+        let config = TelemetryPipelineConfig { interval_ms: 10, ..Default::default() };
+        let pipeline = TelemetryPipeline::spawn(registry, collector.clone(), &config).unwrap();
+        while collector.0.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        pipeline.shutdown().await;
+
+        let received = collector.0.lock().unwrap();
+        let first = &received[0].points;
+        assert!(first.contains(&("requests_total".to_string(), 7.0)));
+        assert!(first.contains(&("telemetry_dropped_total{buffer=\"otlp_export\"}".to_string(), 0.0)));
+        assert!(first.iter().any(|(name, _)| name == "telemetry_export_buffer_depth"));
+    }
+}
@@ -0,0 +1,186 @@
+// rate_limited_log.rs - Deduplicated Logging of Repeated Events
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::task::JoinHandle;
+
+use crate::clock::{Clock, SystemClock};
+
+/// Keys tracked before entries with nothing pending are swept
+const SWEEP_THRESHOLD: usize = 1024;
+
+#[derive(Debug)]
+struct Window {
+    started: SystemTime,
+    /// Occurrences since the last emitted line
+    suppressed: u64,
+}
+
+/// Collapses identical events into one log line per window
+///
+/// Events are keyed by a caller-supplied discriminator such as an error
+/// label. The first occurrence of a key is emitted at once; further ones
+/// within `window` are only counted, and the next occurrence after the
+/// window closes is emitted carrying the total since the previous line. A
+/// flood therefore shows up as one summary per window instead of one line
+/// per event. Occurrences counted in a window that closes with no further
+/// event are only reported by `flush`, which `spawn_flush` runs on a timer.
+#[derive(Debug)]
+pub struct RateLimitedLog {
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    events: Mutex<HashMap<String, Window>>,
+}
+
+impl RateLimitedLog {
+    pub fn new(window: Duration) -> Self {
+        Self { window, clock: Arc::new(SystemClock), events: Mutex::default() }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record one occurrence of `key`, calling `emit` with the number of
+    /// occurrences the line stands for when one is due
+    ///
+    /// ```ignore
+    /// errors.log(label, |occurrences| warn!(label, occurrences, "Request failed"));
+    /// ```
+    pub fn log(&self, key: &str, emit: impl FnOnce(u64)) {
+        let now = self.clock.now();
+        let occurrences = {
+            let mut events = self.lock();
+            if events.len() >= SWEEP_THRESHOLD {
+                let window = self.window;
+                events.retain(|_, entry| entry.suppressed > 0 || !expired(entry, now, window));
+            }
+            match events.get_mut(key) {
+                Some(entry) if !expired(entry, now, self.window) => {
+                    entry.suppressed += 1;
+                    None
+                }
+                Some(entry) => {
+                    let occurrences = entry.suppressed + 1;
+                    *entry = Window { started: now, suppressed: 0 };
+                    Some(occurrences)
+                }
+                None => {
+                    events.insert(key.to_string(), Window { started: now, suppressed: 0 });
+                    Some(1)
+                }
+            }
+        };
+        // Emitted outside the lock so a slow subscriber does not serialize callers
+        if let Some(occurrences) = occurrences {
+            emit(occurrences);
+        }
+    }
+
+    /// Emit the occurrences still held back for every key, as on shutdown
+    pub fn flush(&self, mut emit: impl FnMut(&str, u64)) {
+        let pending: Vec<_> = self.lock()
+            .iter_mut()
+            .filter(|(_, entry)| entry.suppressed > 0)
+            .map(|(key, entry)| (key.clone(), std::mem::take(&mut entry.suppressed)))
+            .collect();
+        for (key, occurrences) in pending {
+            emit(&key, occurrences);
+        }
+    }
+
+    /// Flush the windows that have closed every `window`, until the log is
+    /// dropped, so the tail of a flood that has stopped is still reported
+    pub fn spawn_flush(self: &Arc<Self>, emit: impl Fn(&str, u64) + Send + 'static) -> JoinHandle<()> {
+        let log = Arc::downgrade(self);
+        let window = self.window;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(window);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(log) = log.upgrade() else { break };
+                log.flush_expired(&emit);
+            }
+        })
+    }
+
+    /// Emit the occurrences held back for keys whose window has closed;
+    /// ones in an open window are reported by the key's next event
+    fn flush_expired(&self, mut emit: impl FnMut(&str, u64)) {
+        let now = self.clock.now();
+        let pending: Vec<_> = self.lock()
+            .iter_mut()
+            .filter(|(_, entry)| entry.suppressed > 0 && expired(entry, now, self.window))
+            .map(|(key, entry)| (key.clone(), std::mem::take(&mut entry.suppressed)))
+            .collect();
+        for (key, occurrences) in pending {
+            emit(&key, occurrences);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Window>> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A clock stepping backwards keeps the current window open
+fn expired(entry: &Window, now: SystemTime, window: Duration) -> bool {
+    now.duration_since(entry.started).map_or(false, |elapsed| elapsed >= window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_flood_of_identical_errors_collapses_into_summaries() {
+        let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH));
+        let log = RateLimitedLog::new(Duration::from_secs(1)).with_clock(clock.clone());
+
+        // 1000 identical errors, 10ms apart, span ten windows
+        let mut records = Vec::new();
+        for _ in 0..1000 {
+            log.log("timeout", |occurrences| records.push(occurrences));
+            clock.advance(Duration::from_millis(10));
+        }
+        log.log("unavailable", |occurrences| records.push(occurrences));
+        let mut flushed = Vec::new();
+        log.flush(|key, occurrences| flushed.push((key.to_string(), occurrences)));
+
+        // A distinct key is reported on its own
+        assert_eq!(records.pop(), Some(1));
+        assert!((2..=11).contains(&records.len()), "{} records", records.len());
+        assert_eq!(records[0], 1);
+        assert!(records[1..].iter().all(|&occurrences| occurrences == 100), "{:?}", records);
+
+        let reported: u64 = records.iter().sum::<u64>() + flushed.iter().map(|(_, n)| n).sum::<u64>();
+        assert_eq!(reported, 1000);
+        assert!(flushed.iter().all(|(key, _)| key == "timeout"));
+
+        // Nothing is held back twice
+        log.flush(|_, _| panic!("already flushed"));
+    }
+
+    #[test]
+    fn test_flood_that_stops_is_flushed_once_its_window_closes() {
+        let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH));
+        let log = RateLimitedLog::new(Duration::from_secs(1)).with_clock(clock.clone());
+        for _ in 0..5 {
+            log.log("timeout", |_| {});
+        }
+
+        log.flush_expired(|_, _| panic!("window still open"));
+        clock.advance(Duration::from_secs(1));
+        let mut flushed = Vec::new();
+        log.flush_expired(|key, occurrences| flushed.push((key.to_string(), occurrences)));
+        assert_eq!(flushed, vec![("timeout".to_string(), 4)]);
+
+        log.flush_expired(|_, _| panic!("already flushed"));
+    }
+}
@@ -0,0 +1,120 @@
+// sampler.rs - Deterministic Trace Sampling Decisions
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::TraceContext;
+
+/// W3C `traceparent` flag bit marking a trace as sampled
+pub const SAMPLED_FLAG: u8 = 0x01;
+
+fn default_rate() -> f64 {
+    1.0
+}
+
+/// Decides whether a trace is recorded
+pub trait Sampler: Send + Sync {
+    /// Whether to sample `trace_id` when a `rate` fraction of traces is kept
+    fn should_sample(&self, trace_id: &str, rate: f64) -> bool;
+}
+
+/// Samples by a SHA-256 hash of the trace id
+///
+/// Every service hashing the same id reaches the same decision, so a trace
+/// is kept or dropped as a whole without the flag having to be trusted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceIdRatioSampler;
+
+impl Sampler for TraceIdRatioSampler {
+    fn should_sample(&self, trace_id: &str, rate: f64) -> bool {
+        if rate.is_nan() || rate <= 0.0 {
+            return false;
+        }
+        if rate >= 1.0 {
+            return true;
+        }
+        let digest = Sha256::digest(trace_id.as_bytes());
+        let bucket = u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 is 32 bytes"));
+        bucket < (rate * u64::MAX as f64) as u64
+    }
+}
+
+/// Sample rate plus operations that are always traced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingPolicy {
+    #[serde(default = "default_rate")]
+    pub rate: f64,
+    /// Critical paths traced regardless of `rate`
+    #[serde(default)]
+    pub always_on: HashSet<String>,
+}
+
+impl Default for SamplingPolicy {
+    fn default() -> Self {
+        Self { rate: default_rate(), always_on: HashSet::new() }
+    }
+}
+
+impl SamplingPolicy {
+    pub fn new(rate: f64) -> Self {
+        Self { rate, always_on: HashSet::new() }
+    }
+
+    pub fn always_sample(mut self, operation: impl Into<String>) -> Self {
+        self.always_on.insert(operation.into());
+        self
+    }
+
+    /// Decide for a trace started by `operation` and record the decision
+    /// in its sampled flag, which children then inherit
+    pub fn decide(&self, sampler: &dyn Sampler, operation: &str, trace: &mut TraceContext) -> bool {
+        let sampled = self.always_on.contains(operation) || sampler.should_sample(&trace.trace_id, self.rate);
+        trace.set_sampled(sampled);
+        sampled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_is_stable_per_trace_id() {
+        let policy = SamplingPolicy::new(0.5);
+        for _ in 0..100 {
+            let trace = TraceContext::root();
+            let first = TraceIdRatioSampler.should_sample(&trace.trace_id, 0.5);
+
+            // A different service parsing the same traceparent agrees
+            let mut downstream = TraceContext::parse_traceparent(&trace.to_traceparent()).unwrap();
+            assert_eq!(policy.decide(&TraceIdRatioSampler, "ingest", &mut downstream), first);
+            assert_eq!(downstream.is_sampled(), first);
+            assert_eq!(downstream.child().is_sampled(), first);
+        }
+    }
+
+    #[test]
+    fn test_observed_rate_approximates_configured_rate() {
+        for rate in [0.0, 0.1, 0.25, 0.9, 1.0] {
+            let sampled = (0..20_000)
+                .filter(|_| TraceIdRatioSampler.should_sample(&TraceContext::root().trace_id, rate))
+                .count();
+            let observed = sampled as f64 / 20_000.0;
+            assert!((observed - rate).abs() < 0.02, "rate {}: observed {}", rate, observed);
+        }
+    }
+
+    #[test]
+    fn test_always_on_operations_override_rate() {
+        let policy = SamplingPolicy::new(0.0).always_sample("settle_payment");
+        let mut critical = TraceContext::root();
+        let mut routine = TraceContext { flags: 0x03, ..TraceContext::root() };
+
+        assert!(policy.decide(&TraceIdRatioSampler, "settle_payment", &mut critical));
+        assert!(!policy.decide(&TraceIdRatioSampler, "list_agents", &mut routine));
+        assert!(critical.is_sampled());
+        // Only the sampled bit is cleared
+        assert_eq!(routine.flags, 0x02);
+    }
+}
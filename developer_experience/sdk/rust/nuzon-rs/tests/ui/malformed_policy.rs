@@ -0,0 +1,11 @@
+#[nuzon_macros::api_endpoint("role:admin &&")]
+fn purge_tenant() -> Result<(), nuzon_core::EnterpriseError> {
+    Ok(())
+}
+
+#[nuzon_macros::api_endpoint("claim:GDPR || rol:admin")]
+fn export_records() -> Result<(), nuzon_core::EnterpriseError> {
+    Ok(())
+}
+
+fn main() {}
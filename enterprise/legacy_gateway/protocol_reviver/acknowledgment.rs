@@ -0,0 +1,321 @@
+// acknowledgment.rs - EDIFACT CONTRL Functional Acknowledgment Generation
+use ring::digest;
+use serde::{Serialize, Deserialize};
+
+use super::{
+    split::{DIGEST_LEN, MAX_CONTROL_REFERENCE_LEN},
+    EdifactElement, EdifactInterchange, EdifactMessage, EdifactSegment,
+    UnbSegment, UnhSegment, UntSegment, UnzSegment,
+};
+
+/// UN/EDIFACT syntax error codes (data element 0085)
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SyntaxErrorCode {
+    /// 12 - Invalid value
+    InvalidValue,
+    /// 13 - Missing mandatory data
+    Missing,
+    /// 14 - Value not supported in this position
+    ValueNotSupported,
+    /// 15 - Not supported in this position
+    NotSupportedInPosition,
+    /// 16 - Too many constituents
+    TooManyConstituents,
+    /// 18 - Unspecified error
+    Unspecified,
+    /// 35 - Too many segment repetitions
+    TooManyRepetitions,
+}
+
+impl SyntaxErrorCode {
+    /// Numeric code as transmitted in UCM/UCS/UCD segments
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidValue => "12",
+            Self::Missing => "13",
+            Self::ValueNotSupported => "14",
+            Self::NotSupportedInPosition => "15",
+            Self::TooManyConstituents => "16",
+            Self::Unspecified => "18",
+            Self::TooManyRepetitions => "35",
+        }
+    }
+}
+
+/// A single segment rejected during validation of a received message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentRejection {
+    pub message_reference: String,
+    pub segment_position: u32,
+    pub segment_tag: String,
+    pub error_code: SyntaxErrorCode,
+    pub element_position: Option<u32>,
+    /// GS06 of the X12 functional group holding the transaction set, whose
+    /// ST02 is only unique within it; unset for EDIFACT messages
+    #[serde(default)]
+    pub group_control_number: Option<String>,
+}
+
+/// Validation result of a received interchange, used to drive acknowledgments
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationOutcome {
+    pub rejections: Vec<SegmentRejection>,
+    /// Error in the interchange envelope itself (UNB/UNZ), rejecting it
+    /// with every message inside
+    #[serde(default)]
+    pub interchange_error: Option<SyntaxErrorCode>,
+}
+
+impl ValidationOutcome {
+    /// Outcome accepting every message
+    pub fn accepted() -> Self {
+        Self::default()
+    }
+
+    /// Record a rejected segment
+    pub fn reject(&mut self, rejection: SegmentRejection) -> &mut Self {
+        self.rejections.push(rejection);
+        self
+    }
+
+    /// Reject the interchange as a whole, as for a bad UNB or UNZ
+    pub fn reject_interchange(&mut self, error_code: SyntaxErrorCode) -> &mut Self {
+        self.interchange_error = Some(error_code);
+        self
+    }
+
+    /// Whether the interchange passed validation
+    pub fn is_accepted(&self) -> bool {
+        self.rejections.is_empty() && self.interchange_error.is_none()
+    }
+
+    /// Rejections belonging to a single message
+    pub fn rejections_for<'a>(&'a self, message_reference: &'a str) -> impl Iterator<Item = &'a SegmentRejection> {
+        self.rejections.iter()
+            .filter(move |r| r.message_reference == message_reference)
+    }
+
+    /// Rejections belonging to one X12 transaction set, identified by its
+    /// group's GS06 and its own ST02
+    pub fn rejections_for_transaction<'a>(
+        &'a self,
+        group_control_number: &'a str,
+        transaction_control_number: &'a str,
+    ) -> impl Iterator<Item = &'a SegmentRejection> {
+        self.rejections.iter().filter(move |r| {
+            r.group_control_number.as_deref() == Some(group_control_number)
+                && r.message_reference == transaction_control_number
+        })
+    }
+}
+
+// Action codes (data element 0083)
+const ACTION_REJECTED: &str = "4";
+const ACTION_ACKNOWLEDGED: &str = "7";
+
+impl EdifactInterchange {
+    /// Generate a CONTRL message acknowledging this interchange
+    ///
+    /// The acknowledgment travels in the reverse direction and references the
+    /// original interchange and message control numbers in UCI/UCM. The UCI
+    /// rejects the interchange only for an interchange-level error, in which
+    /// case no UCM follows; otherwise it acknowledges the interchange and
+    /// each message carrying rejected segments is rejected in its own UCM.
+    pub fn generate_contrl(&self, validation: &ValidationOutcome) -> EdifactInterchange {
+        let control_reference = ack_control_reference(&self.unb.control_reference);
+
+        let mut uci = vec![
+            EdifactElement::simple(&self.unb.control_reference),
+            self.unb.sender_identification.to_element(),
+            self.unb.recipient_identification.to_element(),
+        ];
+        match validation.interchange_error {
+            Some(error_code) => uci.extend([
+                EdifactElement::simple(ACTION_REJECTED),
+                EdifactElement::simple(error_code.code()),
+            ]),
+            None => uci.push(EdifactElement::simple(ACTION_ACKNOWLEDGED)),
+        }
+        let mut segments = vec![EdifactSegment { tag: "UCI".into(), elements: uci }];
+
+        let messages = match validation.interchange_error {
+            Some(_) => &[][..],
+            None => &self.messages[..],
+        };
+        for message in messages {
+            let reference = &message.unh.message_reference_number;
+            let rejections: Vec<_> = validation.rejections_for(reference).collect();
+            let message_identifier = EdifactElement::composite(&[
+                &message.unh.message_identifier,
+                &message.unh.message_version,
+                &message.unh.message_release,
+                &message.unh.controlling_agency,
+            ]);
+
+            if rejections.is_empty() {
+                segments.push(EdifactSegment {
+                    tag: "UCM".into(),
+                    elements: vec![
+                        EdifactElement::simple(reference),
+                        message_identifier,
+                        EdifactElement::simple(ACTION_ACKNOWLEDGED),
+                    ],
+                });
+                continue;
+            }
+
+            segments.push(EdifactSegment {
+                tag: "UCM".into(),
+                elements: vec![
+                    EdifactElement::simple(reference),
+                    message_identifier,
+                    EdifactElement::simple(ACTION_REJECTED),
+                ],
+            });
+
+            for rejection in rejections {
+                segments.push(EdifactSegment::simple("UCS", &[
+                    &rejection.segment_position.to_string(),
+                    rejection.error_code.code(),
+                ]));
+
+                if let Some(element) = rejection.element_position {
+                    segments.push(EdifactSegment::simple("UCD", &[
+                        rejection.error_code.code(),
+                        &element.to_string(),
+                    ]));
+                }
+            }
+        }
+
+        let message_reference = "1".to_string();
+        let contrl = EdifactMessage {
+            unh: UnhSegment {
+                message_reference_number: message_reference.clone(),
+                message_identifier: "CONTRL".into(),
+                message_version: "D".into(),
+                message_release: "3".into(),
+                controlling_agency: "UN".into(),
+            },
+            // UNH and UNT are included in the count
+            unt: UntSegment {
+                segment_count: segments.len() as u32 + 2,
+                message_reference_number: message_reference,
+            },
+            segments,
+        };
+
+        EdifactInterchange {
+            unb: UnbSegment {
+                syntax_identifier: self.unb.syntax_identifier.clone(),
+                syntax_version: self.unb.syntax_version.clone(),
+                sender_identification: self.unb.recipient_identification.clone(),
+                recipient_identification: self.unb.sender_identification.clone(),
+                preparation_time: self.unb.preparation_time.clone(),
+                control_reference: control_reference.clone(),
+                application_reference: "CONTRL".into(),
+            },
+            messages: vec![contrl],
+            unz: UnzSegment {
+                interchange_control_count: 1,
+                interchange_control_reference: control_reference,
+            },
+        }
+    }
+}
+
+/// Derive the acknowledgment's own control reference (an..14) from the original
+///
+/// An original too long to prefix has its tail replaced with hex digits of
+/// its digest, as `split` does, so distinct originals keep distinct
+/// acknowledgments.
+fn ack_control_reference(original: &str) -> String {
+    let reference = format!("A{}", original);
+    if reference.chars().count() <= MAX_CONTROL_REFERENCE_LEN {
+        return reference;
+    }
+    let prefix: String = reference.chars().take(MAX_CONTROL_REFERENCE_LEN - DIGEST_LEN).collect();
+    let digest = hex::encode_upper(digest::digest(&digest::SHA256, original.as_bytes()));
+    format!("{}{}", prefix, &digest[..DIGEST_LEN])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn message(reference: &str) -> EdifactMessage {
+        fixtures::message(reference, "ORDERS", vec![
+            EdifactSegment::simple("BGM", &["220", "PO-1", "9"]),
+            EdifactSegment::simple("DTM", &["137:20230516:102"]),
+        ])
+    }
+
+    #[test]
+    fn test_contrl_reports_rejected_segment() {
+        let interchange = fixtures::interchange(vec![message("1"), message("2")]);
+
+        let mut validation = ValidationOutcome::accepted();
+        validation.reject(SegmentRejection {
+            message_reference: "2".into(),
+            segment_position: 3,
+            segment_tag: "DTM".into(),
+            error_code: SyntaxErrorCode::InvalidValue,
+            element_position: Some(1),
+            group_control_number: None,
+        });
+
+        let ack = interchange.generate_contrl(&validation);
+        assert_eq!(ack.unb.sender_identification.identification, "RecipientID");
+        assert_eq!(ack.unb.recipient_identification.identification, "SenderID");
+
+        let contrl = &ack.messages[0];
+        assert_eq!(contrl.unh.message_identifier, "CONTRL");
+        assert_eq!(contrl.unt.segment_count as usize, contrl.segments.len() + 2);
+
+        // Only the second message is rejected, so the interchange itself is
+        // acknowledged
+        let uci = &contrl.segments[0];
+        assert_eq!(uci.elements[0].components[0], "123456");
+        assert_eq!(uci.elements[3].components[0], ACTION_ACKNOWLEDGED);
+        assert_eq!(uci.elements.len(), 4);
+
+        let ucm: Vec<_> = contrl.segments.iter().filter(|s| s.tag == "UCM").collect();
+        assert_eq!(ucm[0].elements[2].components[0], ACTION_ACKNOWLEDGED);
+        assert_eq!(ucm[1].elements[0].components[0], "2");
+        assert_eq!(ucm[1].elements[2].components[0], ACTION_REJECTED);
+
+        let ucs = contrl.segments.iter().find(|s| s.tag == "UCS").unwrap();
+        assert_eq!(ucs.elements[0].components[0], "3");
+        assert_eq!(ucs.elements[1].components[0], "12");
+    }
+
+    #[test]
+    fn test_contrl_rejects_interchange_level_error() {
+        let mut interchange = fixtures::interchange(vec![message("1")]);
+        interchange.unz.interchange_control_reference = "654321".into();
+
+        let mut validation = ValidationOutcome::accepted();
+        validation.reject_interchange(SyntaxErrorCode::InvalidValue);
+        assert!(!validation.is_accepted());
+
+        let contrl = &interchange.generate_contrl(&validation).messages[0];
+        let uci = &contrl.segments[0];
+        assert_eq!(uci.elements[3].components[0], ACTION_REJECTED);
+        assert_eq!(uci.elements[4].components[0], "12");
+        assert!(contrl.segments.iter().all(|s| s.tag != "UCM"));
+    }
+
+    #[test]
+    fn test_long_control_references_keep_distinct_acknowledgments() {
+        assert_eq!(ack_control_reference("123456"), "A123456");
+
+        let first = ack_control_reference("REF2023051600001");
+        let second = ack_control_reference("REF2023051600002");
+        assert_ne!(first, second);
+        for reference in [&first, &second] {
+            assert_eq!(reference.len(), MAX_CONTROL_REFERENCE_LEN);
+            assert!(reference.starts_with("AREF2023"), "{}", reference);
+        }
+    }
+}
@@ -0,0 +1,422 @@
+// autack.rs - AUTACK Interchange Signature Verification
+use std::collections::{HashMap, HashSet};
+
+use ring::{digest, signature};
+
+use super::{EdiDelimiters, EdiError, EdifactInterchange, EdifactMessage, EdifactSegment};
+
+/// Message identifier of the secure authentication and acknowledgement message
+pub const AUTACK: &str = "AUTACK";
+
+/// Security segments permitted inside an AUTACK message
+pub(crate) const SECURITY_SEGMENT_TAGS: &[&str] = &["USH", "USA", "USC", "USB", "USX", "USY", "UST", "USR"];
+
+/// Signature algorithm named in USA (`USA+1:16:ED25519'`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    Ed25519,
+    /// DER-encoded ECDSA over P-256 with SHA-256
+    EcdsaP256Sha256,
+}
+
+impl SignatureAlgorithm {
+    fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "ED25519" => Some(Self::Ed25519),
+            "ECDSA-P256-SHA256" => Some(Self::EcdsaP256Sha256),
+            _ => None,
+        }
+    }
+
+    fn verification(&self) -> &'static dyn signature::VerificationAlgorithm {
+        match self {
+            Self::Ed25519 => &signature::ED25519,
+            Self::EcdsaP256Sha256 => &signature::ECDSA_P256_SHA256_ASN1,
+        }
+    }
+}
+
+/// USA segment (security algorithm)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsaSegment {
+    pub use_of_algorithm: String,
+    pub algorithm: SignatureAlgorithm,
+}
+
+/// USC segment (certificate) identifying the signing key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UscSegment {
+    pub certificate_reference: String,
+    /// Party the certificate was issued to
+    pub owner: String,
+}
+
+/// USB segment (secured data identification)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbSegment {
+    pub response_type: String,
+    pub timestamp: String,
+    pub sender: String,
+    pub recipient: String,
+}
+
+/// USX/USY pair: one secured message and its SHA-256 digest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecuredReference {
+    pub interchange_reference: String,
+    pub message_reference: String,
+    pub digest: Vec<u8>,
+}
+
+/// Security content of one AUTACK message
+///
+/// The USR signature covers every segment before UST, rendered with the
+/// default delimiters, so it vouches for the USY digests and through them
+/// for the referenced messages.
+#[derive(Debug, Clone)]
+pub struct Autack {
+    pub message_reference: String,
+    pub usa: UsaSegment,
+    pub usc: UscSegment,
+    pub usb: UsbSegment,
+    pub references: Vec<SecuredReference>,
+    pub signature: Vec<u8>,
+    signed_data: Vec<u8>,
+}
+
+impl Autack {
+    /// Read the security segments of an AUTACK message
+    pub fn from_message(message: &EdifactMessage) -> Result<Self, EdiError> {
+        let reference = &message.unh.message_reference_number;
+        let invalid = |details: String| EdiError::ValidationError(format!("AUTACK {}: {}", reference, details));
+        let field = |segment: &EdifactSegment, element: usize, component: usize| {
+            segment.elements.get(element)
+                .and_then(|e| e.components.get(component))
+                .filter(|c| !c.is_empty())
+                .cloned()
+                .ok_or_else(|| invalid(format!("{} element {} missing", segment.tag, element)))
+        };
+        let hex_field = |segment: &EdifactSegment, element: usize| {
+            hex::decode(field(segment, element, 0)?)
+                .map_err(|_| invalid(format!("{} element {} is not hex", segment.tag, element)))
+        };
+
+        let (mut usa, mut usc, mut usb, mut signature) = (None, None, None, None);
+        let mut references: Vec<SecuredReference> = Vec::new();
+        let mut signed_end = message.segments.len();
+        for (index, segment) in message.segments.iter().enumerate() {
+            match segment.tag.as_str() {
+                "USA" => {
+                    let code = field(segment, 0, 2)?;
+                    usa = Some(UsaSegment {
+                        use_of_algorithm: field(segment, 0, 0)?,
+                        algorithm: SignatureAlgorithm::from_code(&code)
+                            .ok_or_else(|| invalid(format!("unsupported algorithm {}", code)))?,
+                    });
+                }
+                "USC" => usc = Some(UscSegment {
+                    certificate_reference: field(segment, 0, 0)?,
+                    owner: field(segment, 1, 0)?,
+                }),
+                "USB" => usb = Some(UsbSegment {
+                    response_type: field(segment, 0, 0)?,
+                    timestamp: field(segment, 1, 0)?,
+                    sender: field(segment, 2, 0)?,
+                    recipient: field(segment, 3, 0)?,
+                }),
+                "USX" => references.push(SecuredReference {
+                    interchange_reference: field(segment, 0, 0)?,
+                    message_reference: field(segment, 1, 0)?,
+                    digest: Vec::new(),
+                }),
+                "USY" => {
+                    let secured = references.last_mut()
+                        .filter(|r| r.digest.is_empty())
+                        .ok_or_else(|| invalid("USY without a preceding USX".into()))?;
+                    secured.digest = hex_field(segment, 1)?;
+                }
+                "UST" => signed_end = signed_end.min(index),
+                "USR" => signature = Some(hex_field(segment, 0)?),
+                _ => {}
+            }
+        }
+
+        let missing = |tag: &str| invalid(format!("missing {} segment", tag));
+        if references.is_empty() {
+            return Err(missing("USX"));
+        }
+        if let Some(unhashed) = references.iter().find(|r| r.digest.is_empty()) {
+            return Err(invalid(format!("no USY digest for message {}", unhashed.message_reference)));
+        }
+        Ok(Self {
+            message_reference: reference.clone(),
+            usa: usa.ok_or_else(|| missing("USA"))?,
+            usc: usc.ok_or_else(|| missing("USC"))?,
+            usb: usb.ok_or_else(|| missing("USB"))?,
+            references,
+            signature: signature.ok_or_else(|| missing("USR"))?,
+            signed_data: render_segments(&message.segments[..signed_end]),
+        })
+    }
+
+    /// Check the signature, then each secured message's digest
+    fn verify(&self, interchange: &EdifactInterchange, trust: &TrustAnchors) -> Result<(), EdiError> {
+        let invalid = |details: String| {
+            EdiError::ValidationError(format!("AUTACK {}: {}", self.message_reference, details))
+        };
+        let sender = &interchange.unb.sender_identification.identification;
+        if &self.usc.owner != sender {
+            return Err(invalid(format!("signed by {} on behalf of sender {}", self.usc.owner, sender)));
+        }
+        let key = trust.certificate(&self.usc.owner, &self.usc.certificate_reference)
+            .ok_or_else(|| invalid(format!("no trusted certificate {}", self.usc.certificate_reference)))?;
+        signature::UnparsedPublicKey::new(self.usa.algorithm.verification(), key)
+            .verify(&self.signed_data, &self.signature)
+            .map_err(|_| invalid("signature verification failed".into()))?;
+
+        for secured in &self.references {
+            if secured.interchange_reference != interchange.unb.control_reference {
+                return Err(invalid(format!("references foreign interchange {}", secured.interchange_reference)));
+            }
+            let message = interchange.messages.iter()
+                .find(|m| m.unh.message_reference_number == secured.message_reference)
+                .ok_or_else(|| invalid(format!("secured message {} not in interchange", secured.message_reference)))?;
+            if message_digest(message) != secured.digest {
+                return Err(invalid(format!("digest mismatch for message {}", secured.message_reference)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Certificates trusted to sign AUTACKs, and the senders that must send one
+#[derive(Debug, Clone, Default)]
+pub struct TrustAnchors {
+    /// Subject public key keyed by owner and certificate reference
+    certificates: HashMap<(String, String), Vec<u8>>,
+    required: HashSet<String>,
+}
+
+impl TrustAnchors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust `public_key` as certificate `certificate_reference` of `owner`
+    pub fn insert(
+        &mut self,
+        owner: impl Into<String>,
+        certificate_reference: impl Into<String>,
+        public_key: Vec<u8>,
+    ) -> &mut Self {
+        self.certificates.insert((owner.into(), certificate_reference.into()), public_key);
+        self
+    }
+
+    /// Refuse interchanges from `sender` unless every message is secured by
+    /// an AUTACK
+    pub fn require_autack(&mut self, sender: impl Into<String>) -> &mut Self {
+        self.required.insert(sender.into());
+        self
+    }
+
+    fn certificate(&self, owner: &str, certificate_reference: &str) -> Option<&[u8]> {
+        self.certificates.get(&(owner.to_string(), certificate_reference.to_string())).map(Vec::as_slice)
+    }
+}
+
+impl EdifactInterchange {
+    /// Verify every AUTACK message in the interchange
+    ///
+    /// Each AUTACK's signature is checked against the sender's trusted
+    /// certificate and each message it references against its digest. For
+    /// senders marked with `TrustAnchors::require_autack`, a missing AUTACK
+    /// or a message no AUTACK covers is rejected as well. Message references
+    /// must be unique once an AUTACK is present, since a digest secures the
+    /// one message its reference names. All failures are
+    /// `EdiError::ValidationError`s prefixed with `AUTACK`.
+    pub fn verify_autack(&self, trust: &TrustAnchors) -> Result<(), EdiError> {
+        let sender = &self.unb.sender_identification.identification;
+        let required = trust.required.contains(sender);
+        let autacks: Vec<Autack> = self.messages.iter()
+            .filter(|m| m.unh.message_identifier == AUTACK)
+            .map(Autack::from_message)
+            .try_collect()?;
+        if autacks.is_empty() {
+            if required {
+                return Err(EdiError::ValidationError(format!("AUTACK required from {} but not present", sender)));
+            }
+            return Ok(());
+        }
+
+        let mut references = HashSet::new();
+        if let Some(duplicate) = self.messages.iter()
+            .map(|m| m.unh.message_reference_number.as_str())
+            .find(|reference| !references.insert(*reference))
+        {
+            return Err(EdiError::ValidationError(format!(
+                "AUTACK cannot secure interchange with duplicate message reference {}",
+                duplicate
+            )));
+        }
+
+        let mut secured = HashSet::new();
+        for autack in &autacks {
+            autack.verify(self, trust)?;
+            secured.extend(autack.references.iter().map(|r| r.message_reference.as_str()));
+        }
+        if required {
+            if let Some(unsecured) = self.messages.iter().find(|m| {
+                m.unh.message_identifier != AUTACK && !secured.contains(m.unh.message_reference_number.as_str())
+            }) {
+                return Err(EdiError::ValidationError(format!(
+                    "AUTACK required from {} but message {} is not secured",
+                    sender, unsecured.unh.message_reference_number
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// SHA-256 over the message from UNH to UNT, as carried in USY
+pub fn message_digest(message: &EdifactMessage) -> Vec<u8> {
+    let unh = &message.unh;
+    let header = EdifactSegment {
+        tag: "UNH".into(),
+        elements: vec![
+            super::EdifactElement::simple(&unh.message_reference_number),
+            super::EdifactElement::composite(&[
+                &unh.message_identifier,
+                &unh.message_version,
+                &unh.message_release,
+                &unh.controlling_agency,
+            ]),
+        ],
+    };
+    let trailer = EdifactSegment::simple(
+        "UNT",
+        &[&message.unt.segment_count.to_string(), &message.unt.message_reference_number],
+    );
+    let mut rendered = render_segments(std::slice::from_ref(&header));
+    rendered.extend(render_segments(&message.segments));
+    rendered.extend(render_segments(std::slice::from_ref(&trailer)));
+    digest::digest(&digest::SHA256, &rendered).as_ref().to_vec()
+}
+
+/// Segments with the default delimiters, releasing any that occur in values
+fn render_segments(segments: &[EdifactSegment]) -> Vec<u8> {
+    let d = EdiDelimiters::default();
+    let special = [d.component_separator, d.data_separator, d.escape_character, d.segment_terminator];
+    let mut out = String::new();
+    for segment in segments {
+        out.push_str(&segment.tag);
+        for element in &segment.elements {
+            out.push(d.data_separator);
+            for (i, component) in element.components.iter().enumerate() {
+                if i > 0 {
+                    out.push(d.component_separator);
+                }
+                for c in component.chars() {
+                    if special.contains(&c) {
+                        out.push(d.escape_character);
+                    }
+                    out.push(c);
+                }
+            }
+        }
+        out.push(d.segment_terminator);
+    }
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures::{self, message}, EdifactElement, UnbSegment};
+    use ring::{rand::SystemRandom, signature::{Ed25519KeyPair, KeyPair}};
+
+    fn signed_interchange(key: &Ed25519KeyPair) -> EdifactInterchange {
+        let orders = message("1", "ORDERS", vec![
+            EdifactSegment::simple("BGM", &["220", "PO-1"]),
+            EdifactSegment::simple("QTY", &["21:100"]),
+        ]);
+        let mut security = vec![
+            EdifactSegment::simple("USH", &["7", "1"]),
+            EdifactSegment { tag: "USA".into(), elements: vec![EdifactElement::composite(&["1", "16", "ED25519"])] },
+            EdifactSegment::simple("USC", &["CERT-7", "SENDER"]),
+            EdifactSegment::simple("USB", &["1", "20230516134500", "SENDER", "RECIPIENT"]),
+            EdifactSegment::simple("USX", &["123456", "1"]),
+            EdifactSegment::simple("USY", &["1", &hex::encode(message_digest(&orders))]),
+        ];
+        let signature = key.sign(&render_segments(&security));
+        security.push(EdifactSegment::simple("UST", &["1", "8"]));
+        security.push(EdifactSegment::simple("USR", &[&hex::encode(signature.as_ref())]));
+
+        let mut interchange = fixtures::interchange(vec![orders, message("2", AUTACK, security)]);
+        interchange.unb = UnbSegment {
+            syntax_version: "4".into(),
+            sender_identification: "SENDER".into(),
+            recipient_identification: "RECIPIENT".into(),
+            ..fixtures::unb()
+        };
+        interchange
+    }
+
+    fn anchors(key: &Ed25519KeyPair) -> TrustAnchors {
+        let mut trust = TrustAnchors::new();
+        trust.insert("SENDER", "CERT-7", key.public_key().as_ref().to_vec()).require_autack("SENDER");
+        trust
+    }
+
+    fn assert_rejected(result: Result<(), EdiError>, expected: &str) {
+        match result {
+            Err(EdiError::ValidationError(reason)) => {
+                assert!(reason.starts_with("AUTACK") && reason.contains(expected), "{}", reason)
+            }
+            other => panic!("expected AUTACK rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_signed_interchange_verifies_and_tampering_is_rejected() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let trust = anchors(&key);
+
+        let interchange = signed_interchange(&key);
+        assert_eq!(interchange.verify_autack(&trust), Ok(()));
+
+        let mut tampered = interchange.clone();
+        tampered.messages[0].segments[1] = EdifactSegment::simple("QTY", &["21:900"]);
+        assert_rejected(tampered.verify_autack(&trust), "digest mismatch for message 1");
+
+        let mut forged = interchange.clone();
+        forged.messages[1].segments[3] = EdifactSegment::simple("USB", &["1", "20990101000000", "SENDER", "RECIPIENT"]);
+        assert_rejected(forged.verify_autack(&trust), "signature verification failed");
+    }
+
+    #[test]
+    fn test_message_reusing_a_secured_reference_is_rejected() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let mut injected = signed_interchange(&key);
+        injected.messages.push(message("1", "ORDERS", vec![
+            EdifactSegment::simple("BGM", &["220", "PO-2"]),
+            EdifactSegment::simple("QTY", &["21:900"]),
+        ]));
+
+        assert_rejected(injected.verify_autack(&anchors(&key)), "duplicate message reference 1");
+    }
+
+    #[test]
+    fn test_missing_required_autack_is_rejected() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let mut unsigned = signed_interchange(&key);
+        unsigned.messages.truncate(1);
+
+        assert_rejected(unsigned.verify_autack(&anchors(&key)), "not present");
+        assert_eq!(unsigned.verify_autack(&TrustAnchors::new()), Ok(()));
+    }
+}
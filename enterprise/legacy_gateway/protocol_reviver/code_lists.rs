@@ -0,0 +1,81 @@
+// code_lists.rs - Controlled Code-List Validation for EDIFACT Elements
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{EdiError, EdiParser, EdifactMessage, SourcePath};
+
+/// Values permitted at one component position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeList {
+    /// Message identifier (e.g. `ORDERS`) the list applies to; `None`
+    /// applies it to every message type
+    #[serde(default)]
+    pub message_type: Option<String>,
+    /// Position checked, e.g. `CUX/0/1` for the currency code
+    pub path: SourcePath,
+    pub codes: BTreeSet<String>,
+}
+
+/// Code lists keyed by segment, element and component position
+///
+/// Typically deserialized from operator configuration, e.g.
+/// `{"lists": [{"path": "CUX/0/1", "codes": ["EUR", "USD"]}]}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodeListRegistry {
+    #[serde(default)]
+    pub lists: Vec<CodeList>,
+}
+
+impl CodeListRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict the values allowed at `path` in every message type
+    pub fn insert<'a>(&mut self, path: SourcePath, codes: impl IntoIterator<Item = &'a str>) {
+        self.lists.push(CodeList {
+            message_type: None,
+            path,
+            codes: codes.into_iter().map(str::to_string).collect(),
+        });
+    }
+
+    fn lists_for<'a>(&'a self, message_type: &'a str) -> impl Iterator<Item = &'a CodeList> {
+        self.lists.iter()
+            .filter(move |list| list.message_type.as_deref().map_or(true, |m| m == message_type))
+    }
+}
+
+impl EdiParser<'_> {
+    /// Check every coded value in `msg` against `registry`
+    ///
+    /// Returns one `EdiError::InvalidCode` per offending value, with the
+    /// segment's position in the message counting UNH as 1. Empty or absent
+    /// components are left to mandatory-element validation.
+    pub fn validate_codes(&self, msg: &EdifactMessage, registry: &CodeListRegistry) -> Vec<EdiError> {
+        let mut violations = Vec::new();
+        for list in registry.lists_for(&msg.unh.message_identifier) {
+            for (index, segment) in msg.segments.iter().enumerate() {
+                if !list.path.matches(segment) {
+                    continue;
+                }
+                let value = segment.elements.get(list.path.element)
+                    .and_then(|e| e.components.get(list.path.component))
+                    .filter(|c| !c.is_empty());
+                if let Some(value) = value.filter(|v| !list.codes.contains(*v)) {
+                    violations.push(EdiError::InvalidCode {
+                        path: list.path.to_string(),
+                        segment_position: index + 2,
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        violations.sort_by_key(|e| match e {
+            EdiError::InvalidCode { segment_position, .. } => *segment_position,
+            _ => 0,
+        });
+        violations
+    }
+}
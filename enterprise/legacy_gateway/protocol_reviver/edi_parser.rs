@@ -3,12 +3,29 @@
 #![warn(missing_docs)]
 #![feature(iterator_try_collect)]
 
-use std::{collections::HashMap, str::Chars, iter::Peekable};
+use std::{collections::{HashMap, HashSet}, str::Chars, iter::Peekable};
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
-use regex::Regex;
+use rust_decimal::Decimal;
 use tracing::{info_span, instrument};
 
+pub mod acknowledgment;
+pub mod autack;
+pub mod code_lists;
+pub mod mapping;
+pub mod split;
+pub mod stream;
+pub mod x12;
+#[cfg(test)]
+mod fixtures;
+
+pub use acknowledgment::{SegmentRejection, SyntaxErrorCode, ValidationOutcome};
+pub use autack::{Autack, SignatureAlgorithm, TrustAnchors, UsaSegment, UsbSegment, UscSegment, AUTACK};
+pub use code_lists::{CodeList, CodeListRegistry};
+pub use mapping::{transform, FieldMapping, GroupMapping, MappingSpec, SourcePath};
+pub use stream::{MessageStream, ParserCheckpoint};
+pub use x12::X12Interchange;
+
 /// EDIFACT parse error hierarchy
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum EdiError {
@@ -25,6 +42,14 @@ pub enum EdiError {
     MandatoryElementMissing,
     #[error("Validation rule violation: {0}")]
     ValidationError(String),
+    #[error("Parser limit exceeded: {0}")]
+    ResourceLimit(String),
+    #[error("Value '{value}' at {path} in segment {segment_position} is not in its code list")]
+    InvalidCode {
+        path: String,
+        segment_position: usize,
+        value: String,
+    },
 }
 
 /// Represents EDIFACT interchange control parameters
@@ -40,13 +65,80 @@ pub struct EdifactInterchange {
 pub struct UnbSegment {
     pub syntax_identifier: String,
     pub syntax_version: String,
-    pub sender_identification: String,
-    pub recipient_identification: String,
+    pub sender_identification: PartyIdentification,
+    pub recipient_identification: PartyIdentification,
     pub preparation_time: String,
     pub control_reference: String,
     pub application_reference: String,
 }
 
+/// Interchange sender or recipient (UNB composites S002/S003)
+///
+/// Deserializes from the former flat string form as well, which carries the
+/// identification only.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PartyIdentification {
+    pub identification: String,
+    /// Partner identification code qualifier (0007), e.g. `14` for GLN
+    pub qualifier: Option<String>,
+    /// Reverse routing address (0008)
+    pub routing_address: Option<String>,
+}
+
+impl PartyIdentification {
+    fn from_element(element: &EdifactElement) -> Self {
+        let component = |i: usize| element.components.get(i).filter(|c| !c.is_empty()).cloned();
+        Self {
+            identification: component(0).unwrap_or_default(),
+            qualifier: component(1),
+            routing_address: component(2),
+        }
+    }
+
+    /// Composite element with trailing empty components omitted
+    pub fn to_element(&self) -> EdifactElement {
+        let mut components = vec![
+            self.identification.clone(),
+            self.qualifier.clone().unwrap_or_default(),
+            self.routing_address.clone().unwrap_or_default(),
+        ];
+        while components.len() > 1 && components.last().is_some_and(|c| c.is_empty()) {
+            components.pop();
+        }
+        EdifactElement { components }
+    }
+}
+
+impl From<&str> for PartyIdentification {
+    fn from(identification: &str) -> Self {
+        Self { identification: identification.to_string(), ..Self::default() }
+    }
+}
+
+impl<'de> Deserialize<'de> for PartyIdentification {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Flat(String),
+            Structured {
+                identification: String,
+                #[serde(default)]
+                qualifier: Option<String>,
+                #[serde(default)]
+                routing_address: Option<String>,
+            },
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Flat(identification) => Self { identification, ..Self::default() },
+            Repr::Structured { identification, qualifier, routing_address } => {
+                Self { identification, qualifier, routing_address }
+            }
+        })
+    }
+}
+
 /// UNZ segment structure (Interchange Trailer)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnzSegment {
@@ -92,22 +184,112 @@ pub struct EdifactElement {
     pub components: Vec<String>,
 }
 
+impl EdifactSegment {
+    /// Build a segment from simple (single-component) element values
+    pub fn simple(tag: &str, values: &[&str]) -> Self {
+        Self {
+            tag: tag.to_string(),
+            elements: values.iter().map(|v| EdifactElement::simple(v)).collect(),
+        }
+    }
+}
+
+impl EdifactElement {
+    /// Build a single-component element
+    pub fn simple(value: &str) -> Self {
+        Self { components: vec![value.to_string()] }
+    }
+
+    /// Build a composite element from its components
+    pub fn composite(components: &[&str]) -> Self {
+        Self { components: components.iter().map(|c| c.to_string()).collect() }
+    }
+
+    /// Interpret a component as a numeric value
+    ///
+    /// Accepts an optional leading sign and the interchange's decimal
+    /// separator; release characters are honoured so a released sign or
+    /// separator is read as the literal character.
+    pub fn as_decimal(&self, component: usize, delimiters: &EdiDelimiters) -> Result<Decimal, EdiError> {
+        let raw = self.components.get(component).ok_or_else(|| {
+            EdiError::ValidationError(format!("Numeric component {} not present", component))
+        })?;
+        let malformed = || EdiError::ValidationError(format!("Malformed numeric value '{}'", raw));
+
+        let mut normalized = String::with_capacity(raw.len());
+        let mut seen_digit = false;
+        let mut seen_separator = false;
+        let mut chars = raw.chars();
+
+        while let Some(mut c) = chars.next() {
+            if c == delimiters.escape_character {
+                c = chars.next().ok_or_else(malformed)?;
+            }
+            match c {
+                '+' | '-' if normalized.is_empty() => normalized.push(c),
+                '0'..='9' => {
+                    seen_digit = true;
+                    normalized.push(c);
+                }
+                c if c == delimiters.decimal_separator && !seen_separator => {
+                    seen_separator = true;
+                    normalized.push('.');
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        if !seen_digit {
+            return Err(malformed());
+        }
+        normalized.parse::<Decimal>().map_err(|_| malformed())
+    }
+}
+
+/// Service segments valid in every message type
+const SERVICE_SEGMENT_TAGS: &[&str] = &["UNA", "UNB", "UNG", "UNH", "UNT", "UNE", "UNZ"];
+
+/// Segment tag outside the known set for its message type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnknownSegmentTag {
+    pub tag: String,
+    pub position: usize,
+    pub message_type: Option<String>,
+}
+
 /// Main parser implementation
 pub struct EdiParser<'a> {
+    input: &'a str,
     chars: Peekable<Chars<'a>>,
     position: usize,
     delimiters: EdiDelimiters,
     config: ParserConfig,
+    /// Message identifier from the most recent UNH
+    message_type: Option<String>,
+    unknown_tags: Vec<UnknownSegmentTag>,
 }
 
 /// EDIFACT delimiter set from service string advice
-#[derive(Debug, Clone)]
-struct EdiDelimiters {
-    component_separator: char,
-    data_separator: char,
-    decimal_separator: char,
-    escape_character: char,
-    segment_terminator: char,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdiDelimiters {
+    pub component_separator: char,
+    pub data_separator: char,
+    pub decimal_separator: char,
+    pub escape_character: char,
+    pub segment_terminator: char,
+}
+
+impl Default for EdiDelimiters {
+    /// ISO 9735 default service characters
+    fn default() -> Self {
+        Self {
+            component_separator: ':',
+            data_separator: '+',
+            decimal_separator: '.',
+            escape_character: '?',
+            segment_terminator: '\'',
+        }
+    }
 }
 
 /// Parser configuration parameters
@@ -117,6 +299,16 @@ pub struct ParserConfig {
     pub strict_mode: bool,
     pub max_segment_length: usize,
     pub allowed_versions: Vec<String>,
+    /// Segment tags permitted per message identifier (e.g. `ORDERS`)
+    pub known_tags: HashMap<String, HashSet<String>>,
+    /// Largest UNZ message count or UNT segment count accepted
+    pub max_control_count: u32,
+    /// Messages parsed per interchange before giving up
+    pub max_messages: usize,
+    /// Segments between UNH and UNT before giving up
+    pub max_segments_per_message: usize,
+    /// Components in a single data element before giving up
+    pub max_components_per_element: usize,
 }
 
 impl Default for ParserConfig {
@@ -126,25 +318,60 @@ impl Default for ParserConfig {
             strict_mode: false,
             max_segment_length: 4096,
             allowed_versions: vec!["D".into(), "01B".into(), "02B".into()],
+            known_tags: HashMap::new(),
+            // Both control counts are n..6 data elements
+            max_control_count: 999_999,
+            max_messages: 10_000,
+            max_segments_per_message: 10_000,
+            max_components_per_element: 64,
         }
     }
 }
 
+impl ParserConfig {
+    /// Whether `tag` is a service segment, a security segment inside an
+    /// AUTACK, or declared for `message_type`
+    pub fn is_known_tag(&self, message_type: Option<&str>, tag: &str) -> bool {
+        SERVICE_SEGMENT_TAGS.contains(&tag)
+            || (message_type == Some(AUTACK) && autack::SECURITY_SEGMENT_TAGS.contains(&tag))
+            || message_type
+                .and_then(|m| self.known_tags.get(m))
+                .is_some_and(|tags| tags.contains(tag))
+    }
+}
+
 impl<'a> EdiParser<'a> {
     /// Create new parser instance with custom configuration
     pub fn new(input: &'a str, config: ParserConfig) -> Result<Self, EdiError> {
         let mut parser = Self {
+            input,
             chars: input.chars().peekable(),
             position: 0,
             delimiters: EdiDelimiters::default(),
             config,
+            message_type: None,
+            unknown_tags: Vec::new(),
         };
         
         parser.parse_service_string_advice()?;
         Ok(parser)
     }
 
+    /// Unknown segment tags accepted so far in lenient mode
+    pub fn unknown_tags(&self) -> &[UnknownSegmentTag] {
+        &self.unknown_tags
+    }
+
+    /// Delimiters in effect for this interchange
+    pub fn delimiters(&self) -> &EdiDelimiters {
+        &self.delimiters
+    }
+
     /// Main parsing entry point
+    ///
+    /// Parsing is iterative and every repetition is capped by `ParserConfig`,
+    /// so hostile input ends in `EdiError::ResourceLimit` rather than
+    /// unbounded memory or CPU use.
     #[instrument(name = "EDIFACT parsing", skip(self))]
     pub fn parse_interchange(&mut self) -> Result<EdifactInterchange, EdiError> {
         let _span = info_span!("parse_interchange").entered();
@@ -154,13 +381,19 @@ impl<'a> EdiParser<'a> {
 
         let mut messages = Vec::new();
         while self.peek_segment_tag()? == "UNH" {
+            if messages.len() == self.config.max_messages {
+                return Err(EdiError::ResourceLimit(format!(
+                    "more than {} messages in interchange",
+                    self.config.max_messages
+                )));
+            }
             messages.push(self.parse_message()?);
         }
 
         let unz = self.parse_unz()?;
 
         if self.config.validate_structure {
-            self.validate_interchange(&unb, &unz, messages.len())?;
+            self.validate_interchange(&unb, &unz, &messages)?;
         }
 
         Ok(EdifactInterchange { unb, messages, unz })
@@ -176,42 +409,155 @@ impl<'a> EdiParser<'a> {
             }));
         }
 
+        // S001 syntax:version, S002 sender, S003 recipient, S004 date:time,
+        // 0020 control reference, S005 password, 0026 application reference
+        let elements = self.parse_elements()?;
+        let component = |element: usize, component: usize| {
+            elements.get(element)
+                .and_then(|e| e.components.get(component))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let party = |element: usize| {
+            elements.get(element)
+                .map(PartyIdentification::from_element)
+                .filter(|p| !p.identification.is_empty())
+                .ok_or(EdiError::MandatoryElementMissing)
+        };
+
         Ok(UnbSegment {
-            syntax_identifier: self.parse_element()?,
-            syntax_version: self.parse_element()?,
-            sender_identification: self.parse_element()?,
-            recipient_identification: self.parse_element()?,
-            preparation_time: self.parse_element()?,
-            control_reference: self.parse_element()?,
-            application_reference: self.parse_element()?,
+            syntax_identifier: component(0, 0),
+            syntax_version: component(0, 1),
+            sender_identification: party(1)?,
+            recipient_identification: party(2)?,
+            preparation_time: elements.get(3)
+                .map(|e| e.components.join(&self.delimiters.component_separator.to_string()))
+                .unwrap_or_default(),
+            control_reference: component(4, 0),
+            application_reference: component(6, 0),
         })
     }
 
     /// Core segment parsing logic
     fn parse_segment(&mut self) -> Result<EdifactSegment, EdiError> {
+        let position = self.position;
         let tag = self.parse_segment_tag()?;
+        self.check_segment_tag(&tag, position)?;
+        let elements = self.parse_elements()?;
+
+        if tag == "UNH" {
+            self.message_type = elements.get(1)
+                .and_then(|e| e.components.first())
+                .cloned();
+        }
+
+        Ok(EdifactSegment { tag, elements })
+    }
+
+    /// UNH, body segments and UNT of one message
+    fn parse_message(&mut self) -> Result<EdifactMessage, EdiError> {
+        let header = self.parse_segment()?;
+        let component = |segment: &EdifactSegment, element: usize, component: usize| {
+            segment.elements.get(element)
+                .and_then(|e| e.components.get(component))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let unh = UnhSegment {
+            message_reference_number: component(&header, 0, 0),
+            message_identifier: component(&header, 1, 0),
+            message_version: component(&header, 1, 1),
+            message_release: component(&header, 1, 2),
+            controlling_agency: component(&header, 1, 3),
+        };
+
+        let mut segments = Vec::new();
+        while self.peek_segment_tag()? != "UNT" {
+            if segments.len() == self.config.max_segments_per_message {
+                return Err(EdiError::ResourceLimit(format!(
+                    "more than {} segments in message {}",
+                    self.config.max_segments_per_message, unh.message_reference_number
+                )));
+            }
+            segments.push(self.parse_segment()?);
+        }
+
+        let position = self.position;
+        let trailer = self.parse_segment()?;
+        let unt = UntSegment {
+            segment_count: component(&trailer, 0, 0).parse().map_err(|_| EdiError::SyntaxError {
+                position,
+                details: "UNT segment count is not a number".into(),
+            })?,
+            message_reference_number: component(&trailer, 1, 0),
+        };
+        Ok(EdifactMessage { unh, segments, unt })
+    }
+
+    /// Elements up to and including the segment terminator
+    fn parse_elements(&mut self) -> Result<Vec<EdifactElement>, EdiError> {
         let mut elements = Vec::new();
+        let segment_start = self.position;
 
         while self.peek() != Some(self.delimiters.segment_terminator) {
-            elements.push(self.parse_element()?);
+            elements.push(self.parse_element(segment_start)?);
             if self.peek() == Some(self.delimiters.data_separator) {
                 self.consume_char()?;
             }
         }
         self.consume_segment_terminator()?;
+        Ok(elements)
+    }
 
-        Ok(EdifactSegment { tag, elements })
+    /// Reject (strict) or record (lenient) a tag unknown to the message type
+    fn check_segment_tag(&mut self, tag: &str, position: usize) -> Result<(), EdiError> {
+        if self.config.is_known_tag(self.message_type.as_deref(), tag) {
+            return Ok(());
+        }
+
+        if self.config.strict_mode {
+            return Err(EdiError::SyntaxError {
+                position,
+                details: format!(
+                    "Unknown segment tag {} for message type {}",
+                    tag,
+                    self.message_type.as_deref().unwrap_or("<none>")
+                ),
+            });
+        }
+
+        self.unknown_tags.push(UnknownSegmentTag {
+            tag: tag.to_string(),
+            position,
+            message_type: self.message_type.clone(),
+        });
+        Ok(())
     }
 
     /// Element parsing with component separation
-    fn parse_element(&mut self) -> Result<EdifactElement, EdiError> {
+    ///
+    /// `segment_start` is where the enclosing segment began, for enforcing
+    /// `max_segment_length` as the element is read.
+    fn parse_element(&mut self, segment_start: usize) -> Result<EdifactElement, EdiError> {
         let mut components = Vec::new();
         let mut current = String::new();
         let mut in_escape = false;
 
         loop {
+            if self.position - segment_start > self.config.max_segment_length {
+                return Err(EdiError::ResourceLimit(format!(
+                    "segment at position {} longer than {} characters",
+                    segment_start, self.config.max_segment_length
+                )));
+            }
             match self.chars.peek() {
                 Some(&c) if c == self.delimiters.component_separator && !in_escape => {
+                    if components.len() + 1 >= self.config.max_components_per_element {
+                        return Err(EdiError::ResourceLimit(format!(
+                            "element at position {} has more than {} components",
+                            self.position, self.config.max_components_per_element
+                        )));
+                    }
                     components.push(current);
                     current = String::new();
                     self.chars.next();
@@ -238,29 +584,27 @@ impl<'a> EdiParser<'a> {
         Ok(EdifactElement { components })
     }
 
-    /// Service string advice parsing (UNB+...)
+    /// Service string advice parsing (UNA:+.? ')
+    ///
+    /// The advice is optional; without one the interchange uses the default
+    /// delimiters.
     fn parse_service_string_advice(&mut self) -> Result<(), EdiError> {
-        let re = Regex::new(r"^UNOA\d").unwrap();
-        let mut advice = String::new();
-        
-        for _ in 0..5 {
-            advice.push(*self.chars.peek().ok_or(EdiError::InvalidServiceStringAdvice)?);
-            self.chars.next();
-            self.position += 1;
+        if !self.input.starts_with("UNA") {
+            return Ok(());
         }
-
-        if !re.is_match(&advice) {
+        let advice: Vec<char> = self.input.chars().take(9).collect();
+        let [_, _, _, component, data, decimal, escape, _, terminator] = advice[..] else {
             return Err(EdiError::InvalidServiceStringAdvice);
-        }
-
+        };
         self.delimiters = EdiDelimiters {
-            component_separator: advice.chars().nth(3).unwrap(),
-            data_separator: advice.chars().nth(4).unwrap(),
-            decimal_separator: '.',
-            escape_character: '?',
-            segment_terminator: advice.chars().nth(5).unwrap(),
+            component_separator: component,
+            data_separator: data,
+            decimal_separator: decimal,
+            escape_character: escape,
+            segment_terminator: terminator,
         };
-
+        self.chars.by_ref().take(advice.len()).for_each(drop);
+        self.position += advice.len();
         Ok(())
     }
 
@@ -269,21 +613,57 @@ impl<'a> EdiParser<'a> {
         &self,
         unb: &UnbSegment,
         unz: &UnzSegment,
-        message_count: usize,
+        messages: &[EdifactMessage],
     ) -> Result<(), EdiError> {
+        self.validate_trailer(unb, unz, messages.len() as u64)?;
+        messages.iter().try_for_each(|message| self.validate_segment_count(message))
+    }
+
+    /// UNB/UNZ pairing and the UNZ count against `message_count` received
+    fn validate_trailer(&self, unb: &UnbSegment, unz: &UnzSegment, message_count: u64) -> Result<(), EdiError> {
         if unb.control_reference != unz.interchange_control_reference {
             return Err(EdiError::ValidationError(
                 "Control reference mismatch between UNB and UNZ".into(),
             ));
         }
 
-        if unz.interchange_control_count as usize != message_count {
+        let max = self.config.max_control_count;
+        if unz.interchange_control_count > max {
+            return Err(EdiError::ValidationError(format!(
+                "UNZ message count {} exceeds maximum {}",
+                unz.interchange_control_count, max
+            )));
+        }
+
+        // Compare in u64 space so no count can wrap or truncate
+        if u64::from(unz.interchange_control_count) != message_count {
             return Err(EdiError::ValidationError(format!(
                 "Message count mismatch: UNZ reports {}, actual {}",
                 unz.interchange_control_count, message_count
             )));
         }
+        Ok(())
+    }
 
+    /// UNT segment count of one message against the segments received
+    fn validate_segment_count(&self, message: &EdifactMessage) -> Result<(), EdiError> {
+        let max = self.config.max_control_count;
+        let reported = message.unt.segment_count;
+        if reported > max {
+            return Err(EdiError::ValidationError(format!(
+                "UNT segment count {} in message {} exceeds maximum {}",
+                reported, message.unh.message_reference_number, max
+            )));
+        }
+
+        // UNH and UNT are included in the count
+        let actual = message.segments.len() as u64 + 2;
+        if u64::from(reported) != actual {
+            return Err(EdiError::ValidationError(format!(
+                "Segment count mismatch in message {}: UNT reports {}, actual {}",
+                message.unh.message_reference_number, reported, actual
+            )));
+        }
         Ok(())
     }
 
@@ -293,6 +673,7 @@ impl<'a> EdiParser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::{self, raw_interchange, raw_order};
     use tracing_test::traced_test;
 
     const SAMPLE_EDIFACT: &str = "UNB+UNOA:1+SenderID+RecipientID+230516:1345+123456++1234'UNH+1+ORDERS:D:01B:UN'...";
@@ -308,7 +689,363 @@ mod tests {
         let mut parser = EdiParser::new(SAMPLE_EDIFACT, config).unwrap();
         let interchange = parser.parse_interchange().unwrap();
         
-        assert_eq!(interchange.unb.sender_identification, "SenderID");
+        assert_eq!(interchange.unb.sender_identification.identification, "SenderID");
         assert_eq!(interchange.messages.len(), 1);
     }
+
+    #[test]
+    fn test_service_string_advice_sets_delimiters() {
+        let parser = EdiParser::new("UNA*|,! ~UNB|UNOA*1~", ParserConfig::default()).unwrap();
+        assert_eq!(parser.delimiters().component_separator, '*');
+        assert_eq!(parser.delimiters().data_separator, '|');
+        assert_eq!(parser.delimiters().decimal_separator, ',');
+        assert_eq!(parser.delimiters().escape_character, '!');
+        assert_eq!(parser.delimiters().segment_terminator, '~');
+
+        assert_eq!(EdiParser::new("", ParserConfig::default()).unwrap().delimiters(), &EdiDelimiters::default());
+        assert!(matches!(EdiParser::new("UNA:+", ParserConfig::default()), Err(EdiError::InvalidServiceStringAdvice)));
+    }
+
+    fn segment_parser(input: &str, strict_mode: bool) -> EdiParser<'_> {
+        let mut known_tags = HashMap::new();
+        known_tags.insert(
+            "ORDERS".to_string(),
+            ["BGM", "DTM", "NAD", "LIN"].iter().map(|t| t.to_string()).collect(),
+        );
+        EdiParser {
+            input,
+            chars: input.chars().peekable(),
+            position: 0,
+            delimiters: EdiDelimiters::default(),
+            config: ParserConfig { strict_mode, known_tags, ..Default::default() },
+            message_type: Some("ORDERS".into()),
+            unknown_tags: Vec::new(),
+        }
+    }
+
+    fn limited_parser(input: &str, limit: impl FnOnce(&mut ParserConfig)) -> EdiParser<'_> {
+        let mut parser = segment_parser(input, false);
+        parser.config.allowed_versions.push("1".into());
+        limit(&mut parser.config);
+        parser
+    }
+
+    fn assert_limit(result: Result<impl std::fmt::Debug, EdiError>, expected: &str) {
+        match result {
+            Err(EdiError::ResourceLimit(reason)) => assert!(reason.contains(expected), "{}", reason),
+            other => panic!("expected resource limit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_message_limit() {
+        let input = raw_interchange(&(1..=3).map(raw_order).collect::<String>(), 3);
+        assert_limit(
+            limited_parser(&input, |c| c.max_messages = 2).parse_interchange(),
+            "more than 2 messages",
+        );
+        assert_eq!(limited_parser(&input, |c| c.max_messages = 3).parse_interchange().unwrap().messages.len(), 3);
+    }
+
+    #[test]
+    fn test_segments_per_message_limit() {
+        let input = "UNH+1+ORDERS:D:01B:UN'BGM+220'DTM+137:20230516:102'NAD+BY+5412345000013'UNT+5+1'";
+        assert_limit(
+            limited_parser(input, |c| c.max_segments_per_message = 2).parse_message(),
+            "more than 2 segments in message 1",
+        );
+        assert_eq!(limited_parser(input, |c| c.max_segments_per_message = 3).parse_message().unwrap().segments.len(), 3);
+    }
+
+    #[test]
+    fn test_components_per_element_limit() {
+        assert_limit(
+            limited_parser("DTM+137:20230516:102:extra'", |c| c.max_components_per_element = 3).parse_segment(),
+            "more than 3 components",
+        );
+        let segment = limited_parser("DTM+137:20230516:102'", |c| c.max_components_per_element = 3)
+            .parse_segment()
+            .unwrap();
+        assert_eq!(segment.elements[0].components.len(), 3);
+    }
+
+    #[test]
+    fn test_segment_length_limit() {
+        let input = format!("FTX+AAI+++{}'", "x".repeat(10_000));
+        assert_limit(
+            limited_parser(&input, |c| c.max_segment_length = 512).parse_segment(),
+            "longer than 512 characters",
+        );
+    }
+
+    #[test]
+    fn test_truncated_and_garbled_input_never_panics() {
+        let valid = "UNB+UNOA:1+SenderID+RecipientID+230516:1345+123456'\
+            UNH+1+ORDERS:D:01B:UN'BGM+220+PO-1001+9'DTM+137:20230516:102'UNT+4+1'UNZ+1+123456'";
+        let garbled: String = valid.chars().rev().collect();
+        for input in [valid, garbled.as_str()] {
+            for end in (0..=input.len()).filter(|i| input.is_char_boundary(*i)) {
+                let _ = EdiParser::new(&input[..end], ParserConfig::default())
+                    .and_then(|mut parser| parser.parse_interchange());
+                let _ = limited_parser(&input[..end], |_| {}).parse_interchange();
+            }
+        }
+    }
+
+    #[test]
+    fn test_streamed_unz_count_mismatch_is_terminal_error() {
+        let input = |count: u32| raw_interchange(&(raw_order(1) + &raw_order(2)), count);
+
+        let correct = input(2);
+        let mut parser = limited_parser(&correct, |_| {});
+        let mut stream = parser.stream_messages().unwrap();
+        assert_eq!(stream.by_ref().map(Result::unwrap).count(), 2);
+        assert_eq!(stream.unz().unwrap().interchange_control_count, 2);
+
+        let wrong = input(3);
+        let mut parser = limited_parser(&wrong, |_| {});
+        let items: Vec<_> = parser.stream_messages().unwrap().collect();
+        assert_eq!(items.len(), 3);
+        assert!(items[..2].iter().all(Result::is_ok));
+        assert_eq!(
+            items[2].as_ref().unwrap_err(),
+            &EdiError::ValidationError("Message count mismatch: UNZ reports 3, actual 2".into())
+        );
+    }
+
+    #[test]
+    fn test_resumed_stream_matches_uninterrupted_parse() {
+        let input = raw_interchange(&(1..=4).map(raw_order).collect::<String>(), 4);
+        let as_json = |messages: &[EdifactMessage]| serde_json::to_value(messages).unwrap();
+
+        let mut parser = limited_parser(&input, |_| {});
+        let expected: Vec<_> = parser.stream_messages().unwrap().map(Result::unwrap).collect();
+
+        let mut parser = limited_parser(&input, |_| {});
+        let mut first = parser.stream_messages().unwrap();
+        let mut combined: Vec<_> = first.by_ref().take(2).map(Result::unwrap).collect();
+        let checkpoint = first.checkpoint().unwrap();
+        assert_eq!(checkpoint.message_count, 2);
+        assert!(input[..checkpoint.byte_offset].ends_with("UNT+3+2'"));
+
+        // Persisted, then picked up by a fresh parser after a crash; the UNZ
+        // count only checks out if the running count was restored
+        let checkpoint: ParserCheckpoint =
+            serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+        let mut parser = limited_parser(&input, |_| {});
+        let mut resumed = parser.resume(&checkpoint).unwrap();
+        combined.extend(resumed.by_ref().map(Result::unwrap));
+        assert!(resumed.checkpoint().is_none());
+        assert_eq!(as_json(&combined), as_json(&expected));
+
+        let other = input.replace("123456", "654321");
+        let mut parser = limited_parser(&other, |_| {});
+        assert!(matches!(parser.resume(&checkpoint).map(drop), Err(EdiError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_resume_refuses_checkpoint_off_a_message_boundary() {
+        let input = &raw_interchange(&(raw_order(1) + &raw_order(2).replace("PO-2", "PO?'2")), 2);
+        let mut parser = limited_parser(input, |_| {});
+        let mut stream = parser.stream_messages().unwrap();
+        stream.next().unwrap().unwrap();
+        let checkpoint = serde_json::to_value(stream.checkpoint().unwrap()).unwrap();
+        let forged = |byte_offset: usize, position: usize| {
+            let mut forged = checkpoint.clone();
+            forged["byte_offset"] = byte_offset.into();
+            forged["position"] = position.into();
+            serde_json::from_value::<ParserCheckpoint>(forged).unwrap()
+        };
+        let refused = |checkpoint: ParserCheckpoint| {
+            let mut parser = limited_parser(input, |_| {});
+            matches!(parser.resume(&checkpoint).map(drop), Err(EdiError::SyntaxError { .. }))
+        };
+
+        let offset = checkpoint["byte_offset"].as_u64().unwrap() as usize;
+        assert!(!refused(forged(offset, offset)));
+        // Right offset, but a position that does not match it
+        assert!(refused(forged(offset, offset + 1)));
+        // A real terminator in the middle of a message
+        let mid = input.find("PO-1'").unwrap() + 5;
+        assert!(refused(forged(mid, mid)));
+        // Just past a released terminator inside a data element
+        let escaped = input.find("?'").unwrap() + 2;
+        assert!(refused(forged(escaped, escaped)));
+    }
+
+    fn control_fixture(segment_count: u32, control_count: u32) -> (UnbSegment, UnzSegment, Vec<EdifactMessage>) {
+        let mut message = fixtures::message("1", "ORDERS", vec![
+            EdifactSegment::simple("BGM", &["220", "PO1"]),
+            EdifactSegment::simple("DTM", &["137"]),
+        ]);
+        message.unt.segment_count = segment_count;
+        let EdifactInterchange { unb, mut unz, messages } = fixtures::interchange(vec![message]);
+        unz.interchange_control_count = control_count;
+        (unb, unz, messages)
+    }
+
+    #[test]
+    fn test_segment_count_off_by_one_rejected() {
+        let parser = segment_parser("", false);
+
+        let (unb, unz, messages) = control_fixture(4, 1);
+        assert!(parser.validate_interchange(&unb, &unz, &messages).is_ok());
+
+        let (unb, unz, messages) = control_fixture(3, 1);
+        assert_eq!(
+            parser.validate_interchange(&unb, &unz, &messages),
+            Err(EdiError::ValidationError(
+                "Segment count mismatch in message 1: UNT reports 3, actual 4".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_absurd_interchange_count_rejected() {
+        let parser = segment_parser("", false);
+        let (unb, unz, messages) = control_fixture(4, u32::MAX);
+        assert_eq!(
+            parser.validate_interchange(&unb, &unz, &messages),
+            Err(EdiError::ValidationError(format!(
+                "UNZ message count {} exceeds maximum 999999", u32::MAX
+            )))
+        );
+    }
+
+    #[test]
+    fn test_unb_party_qualifiers_parsed() {
+        let mut parser = segment_parser(
+            "UNB+UNOC:3+5412345000013:14:ROUTE1+4012345500004:14+230516:1345+REF1++APP'",
+            false,
+        );
+        let unb = parser.parse_unb().unwrap();
+
+        assert_eq!((unb.syntax_identifier.as_str(), unb.syntax_version.as_str()), ("UNOC", "3"));
+        assert_eq!(unb.sender_identification, PartyIdentification {
+            identification: "5412345000013".into(),
+            qualifier: Some("14".into()),
+            routing_address: Some("ROUTE1".into()),
+        });
+        assert_eq!(unb.recipient_identification, PartyIdentification {
+            identification: "4012345500004".into(),
+            qualifier: Some("14".into()),
+            routing_address: None,
+        });
+        assert_eq!(unb.preparation_time, "230516:1345");
+        assert_eq!(unb.control_reference, "REF1");
+        assert_eq!(unb.application_reference, "APP");
+        assert_eq!(unb.sender_identification.to_element().components, ["5412345000013", "14", "ROUTE1"]);
+        assert_eq!(unb.recipient_identification.to_element().components, ["4012345500004", "14"]);
+    }
+
+    #[test]
+    fn test_party_identification_accepts_legacy_string() {
+        let legacy: PartyIdentification = serde_json::from_str(r#""SenderID""#).unwrap();
+        assert_eq!(legacy, PartyIdentification::from("SenderID"));
+
+        let structured = PartyIdentification { qualifier: Some("ZZZ".into()), ..legacy };
+        let json = serde_json::to_string(&structured).unwrap();
+        assert_eq!(serde_json::from_str::<PartyIdentification>(&json).unwrap(), structured);
+    }
+
+    #[test]
+    fn test_known_tag_accepted_in_both_modes() {
+        for strict in [true, false] {
+            let mut parser = segment_parser("BGM+220+PO1'", strict);
+            assert_eq!(parser.parse_segment().unwrap().tag, "BGM");
+            assert!(parser.unknown_tags().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_unknown_tag_rejected_only_in_strict_mode() {
+        let mut strict = segment_parser("XYZ+1'", true);
+        match strict.parse_segment() {
+            Err(EdiError::SyntaxError { position, details }) => {
+                assert_eq!(position, 0);
+                assert!(details.contains("XYZ"));
+            }
+            other => panic!("expected syntax error, got {:?}", other),
+        }
+
+        let mut lenient = segment_parser("BGM+220'XYZ+1'LIN+1'", false);
+        for _ in 0..3 {
+            lenient.parse_segment().unwrap();
+        }
+        assert_eq!(lenient.unknown_tags(), &[UnknownSegmentTag {
+            tag: "XYZ".into(),
+            position: 8,
+            message_type: Some("ORDERS".into()),
+        }]);
+    }
+
+    #[test]
+    fn test_as_decimal_separator_conventions() {
+        let point = EdiDelimiters::default();
+        let comma = EdiDelimiters { decimal_separator: ',', ..EdiDelimiters::default() };
+
+        let element = EdifactElement::composite(&["203", "1234.50", "-0.75"]);
+        assert_eq!(element.as_decimal(1, &point).unwrap(), Decimal::new(123450, 2));
+        assert_eq!(element.as_decimal(2, &point).unwrap(), Decimal::new(-75, 2));
+        assert!(element.as_decimal(1, &comma).is_err());
+
+        let element = EdifactElement::composite(&["203", "1234,50", "+12"]);
+        assert_eq!(element.as_decimal(1, &comma).unwrap(), Decimal::new(123450, 2));
+        assert_eq!(element.as_decimal(2, &comma).unwrap(), Decimal::new(12, 0));
+        assert!(element.as_decimal(1, &point).is_err());
+    }
+
+    #[test]
+    fn test_as_decimal_rejects_malformed() {
+        let delimiters = EdiDelimiters::default();
+        // Released sign is still a sign; a released separator is still the separator
+        let released = EdifactElement::composite(&["?-5?.5", "??1"]);
+        assert_eq!(released.as_decimal(0, &delimiters).unwrap(), Decimal::new(-55, 1));
+        assert!(released.as_decimal(1, &delimiters).is_err());
+
+        for bad in ["", "-", "1.2.3", "12-", "1e5", "12?"] {
+            let element = EdifactElement::simple(bad);
+            assert!(
+                matches!(element.as_decimal(0, &delimiters), Err(EdiError::ValidationError(_))),
+                "accepted {:?}", bad
+            );
+        }
+        assert!(EdifactElement::simple("1").as_decimal(3, &delimiters).is_err());
+    }
+
+    fn message(identifier: &str, segments: Vec<EdifactSegment>) -> EdifactMessage {
+        fixtures::message("1", identifier, segments)
+    }
+
+    #[test]
+    fn test_invalid_currency_code_flagged_with_position() {
+        let registry: CodeListRegistry = serde_json::from_value(serde_json::json!({
+            "lists": [
+                {"path": "CUX/0/1", "codes": ["EUR", "USD", "GBP"]},
+                {"message_type": "INVOIC", "path": "MEA/2/0", "codes": ["KGM", "MTR"]},
+            ]
+        })).unwrap();
+        let parser = segment_parser("", false);
+
+        let orders = message("ORDERS", vec![
+            EdifactSegment::simple("BGM", &["220", "PO-1"]),
+            EdifactSegment { tag: "CUX".into(), elements: vec![EdifactElement::composite(&["2", "EUR", "9"])] },
+            EdifactSegment { tag: "CUX".into(), elements: vec![EdifactElement::composite(&["3", "XYZ", "4"])] },
+            // The unit list applies to INVOIC only
+            EdifactSegment::simple("MEA", &["AAE", "WT", "LBS"]),
+        ]);
+        assert_eq!(parser.validate_codes(&orders, &registry), vec![EdiError::InvalidCode {
+            path: "CUX/0/1".into(),
+            segment_position: 4,
+            value: "XYZ".into(),
+        }]);
+
+        let invoice = message("INVOIC", vec![
+            EdifactSegment::simple("MEA", &["AAE", "WT", "LBS"]),
+            EdifactSegment { tag: "CUX".into(), elements: vec![EdifactElement::composite(&["2", ""])] },
+        ]);
+        let violations = parser.validate_codes(&invoice, &registry);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].to_string().contains("MEA/2/0"), "{}", violations[0]);
+    }
 }
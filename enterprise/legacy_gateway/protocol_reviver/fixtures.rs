@@ -0,0 +1,64 @@
+// fixtures.rs - Interchanges Shared by the Parser Tests
+use super::{EdifactInterchange, EdifactMessage, EdifactSegment, UnbSegment, UnhSegment, UntSegment, UnzSegment};
+
+/// Control reference of every fixture interchange
+pub const CONTROL_REFERENCE: &str = "123456";
+
+/// UNB from SenderID to RecipientID, syntax UNOA version 1
+pub fn unb() -> UnbSegment {
+    UnbSegment {
+        syntax_identifier: "UNOA".into(),
+        syntax_version: "1".into(),
+        sender_identification: "SenderID".into(),
+        recipient_identification: "RecipientID".into(),
+        preparation_time: "230516:1345".into(),
+        control_reference: CONTROL_REFERENCE.into(),
+        application_reference: String::new(),
+    }
+}
+
+/// D.01B message `reference` of type `identifier`, with a UNT counting
+/// `segments` plus the UNH and UNT themselves
+pub fn message(reference: &str, identifier: &str, segments: Vec<EdifactSegment>) -> EdifactMessage {
+    EdifactMessage {
+        unh: UnhSegment {
+            message_reference_number: reference.into(),
+            message_identifier: identifier.into(),
+            message_version: "D".into(),
+            message_release: "01B".into(),
+            controlling_agency: "UN".into(),
+        },
+        unt: UntSegment { segment_count: segments.len() as u32 + 2, message_reference_number: reference.into() },
+        segments,
+    }
+}
+
+/// ORDERS message `n` carrying only purchase order PO-`n`
+pub fn order(n: u32) -> EdifactMessage {
+    message(&n.to_string(), "ORDERS", vec![EdifactSegment::simple("BGM", &["220", &format!("PO-{}", n)])])
+}
+
+/// Interchange around `messages`, with a UNZ that counts them
+pub fn interchange(messages: Vec<EdifactMessage>) -> EdifactInterchange {
+    EdifactInterchange {
+        unb: unb(),
+        unz: UnzSegment {
+            interchange_control_count: messages.len() as u32,
+            interchange_control_reference: CONTROL_REFERENCE.into(),
+        },
+        messages,
+    }
+}
+
+/// `order(n)` as it appears on the wire
+pub fn raw_order(n: u32) -> String {
+    format!("UNH+{n}+ORDERS:D:01B:UN'BGM+220+PO-{n}'UNT+3+{n}'")
+}
+
+/// Wire interchange around the raw `messages`, with a UNZ reporting `count`
+pub fn raw_interchange(messages: &str, count: u32) -> String {
+    format!(
+        "UNB+UNOA:1+SenderID+RecipientID+230516:1345+{reference}'{messages}UNZ+{count}+{reference}'",
+        reference = CONTROL_REFERENCE
+    )
+}
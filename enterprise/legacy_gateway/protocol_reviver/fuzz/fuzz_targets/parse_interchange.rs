@@ -0,0 +1,25 @@
+// parse_interchange.rs - cargo-fuzz target for the EDIFACT parser
+//
+// Run with `cargo fuzz run parse_interchange`. Any input may be rejected,
+// but none may panic, overflow the stack or grow memory past the
+// `ParserConfig` limits.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use protocol_reviver::{EdiParser, ParserConfig};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else { return };
+
+    // Small limits so the fuzzer reaches every limit check quickly
+    let config = ParserConfig {
+        max_segment_length: 256,
+        max_messages: 8,
+        max_segments_per_message: 32,
+        max_components_per_element: 8,
+        ..Default::default()
+    };
+    if let Ok(mut parser) = EdiParser::new(input, config) {
+        let _ = parser.parse_interchange();
+    }
+});
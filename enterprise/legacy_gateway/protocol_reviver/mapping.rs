@@ -0,0 +1,292 @@
+// mapping.rs - Declarative EDIFACT to JSON Transformation
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{EdiError, EdifactInterchange, EdifactMessage, EdifactSegment};
+
+/// Location of a component within a message
+///
+/// Written `TAG[QUALIFIER]/element/component`, e.g. `BGM/1/0` or
+/// `NAD[BY]/1/0`. The qualifier, when present, must equal the first
+/// component of the segment's first element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SourcePath {
+    pub tag: String,
+    pub qualifier: Option<String>,
+    pub element: usize,
+    pub component: usize,
+}
+
+impl SourcePath {
+    pub(crate) fn matches(&self, segment: &EdifactSegment) -> bool {
+        segment.tag == self.tag
+            && self.qualifier.as_ref().map_or(true, |q| {
+                segment.elements.first()
+                    .and_then(|e| e.components.first())
+                    .is_some_and(|c| c == q)
+            })
+    }
+
+    /// First non-empty value at this path among `segments`
+    fn resolve<'a>(&self, segments: &'a [EdifactSegment]) -> Option<&'a str> {
+        segments.iter()
+            .filter(|s| self.matches(s))
+            .find_map(|s| {
+                s.elements.get(self.element)
+                    .and_then(|e| e.components.get(self.component))
+                    .filter(|c| !c.is_empty())
+            })
+            .map(String::as_str)
+    }
+}
+
+impl fmt::Display for SourcePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tag)?;
+        if let Some(qualifier) = &self.qualifier {
+            write!(f, "[{}]", qualifier)?;
+        }
+        write!(f, "/{}/{}", self.element, self.component)
+    }
+}
+
+impl FromStr for SourcePath {
+    type Err = EdiError;
+
+    fn from_str(s: &str) -> Result<Self, EdiError> {
+        let invalid = || EdiError::ValidationError(format!("Invalid source path '{}'", s));
+        let mut parts = s.split('/');
+        let head = parts.next().filter(|h| !h.is_empty()).ok_or_else(invalid)?;
+        let (tag, qualifier) = match head.split_once('[') {
+            Some((tag, rest)) => (tag, Some(rest.strip_suffix(']').ok_or_else(invalid)?.to_string())),
+            None => (head, None),
+        };
+        let mut index = || parts.next().ok_or_else(invalid)?.parse::<usize>().map_err(|_| invalid());
+        let (element, component) = (index()?, index()?);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self { tag: tag.to_string(), qualifier, element, component })
+    }
+}
+
+impl TryFrom<String> for SourcePath {
+    type Error = EdiError;
+
+    fn try_from(s: String) -> Result<Self, EdiError> {
+        s.parse()
+    }
+}
+
+impl From<SourcePath> for String {
+    fn from(path: SourcePath) -> Self {
+        path.to_string()
+    }
+}
+
+/// Copy one source component to a JSON pointer in the target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldMapping {
+    pub source: SourcePath,
+    /// RFC 6901 pointer, e.g. `/order/number`
+    pub target: String,
+    #[serde(default)]
+    pub mandatory: bool,
+}
+
+/// Map each occurrence of a repeated segment group to an array element
+///
+/// An occurrence starts at a `trigger` segment and runs until the next one,
+/// so `LIN` followed by its `QTY` and `PRI` segments forms one line item.
+/// Field sources and targets are relative to the occurrence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMapping {
+    pub trigger: String,
+    /// Pointer to the array in the target document
+    pub target: String,
+    pub fields: Vec<FieldMapping>,
+}
+
+/// Declarative mapping from one message type to a JSON document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MappingSpec {
+    /// Message identifier this spec applies to, e.g. `ORDERS`
+    pub message_type: String,
+    #[serde(default)]
+    pub fields: Vec<FieldMapping>,
+    #[serde(default)]
+    pub groups: Vec<GroupMapping>,
+}
+
+/// Build one target document per message of the spec's type
+///
+/// Returns a JSON array in interchange order; other message types are skipped.
+pub fn transform(interchange: &EdifactInterchange, spec: &MappingSpec) -> Result<Value, EdiError> {
+    interchange.messages.iter()
+        .filter(|m| m.unh.message_identifier == spec.message_type)
+        .map(|m| transform_message(m, spec))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array)
+}
+
+/// Build the target document for a single message
+pub fn transform_message(message: &EdifactMessage, spec: &MappingSpec) -> Result<Value, EdiError> {
+    let reference = &message.unh.message_reference_number;
+    let mut document = Value::Object(Map::new());
+    map_fields(&mut document, &message.segments, &spec.fields, reference)?;
+
+    for group in &spec.groups {
+        let mut items = Vec::new();
+        for occurrence in occurrences(&message.segments, &group.trigger) {
+            let mut item = Value::Object(Map::new());
+            map_fields(&mut item, occurrence, &group.fields, reference)?;
+            items.push(item);
+        }
+        set_pointer(&mut document, &group.target, Value::Array(items))?;
+    }
+    Ok(document)
+}
+
+fn map_fields(
+    target: &mut Value,
+    segments: &[EdifactSegment],
+    fields: &[FieldMapping],
+    reference: &str,
+) -> Result<(), EdiError> {
+    for field in fields {
+        match field.source.resolve(segments) {
+            Some(value) => set_pointer(target, &field.target, Value::String(value.to_string()))?,
+            None if field.mandatory => {
+                return Err(EdiError::ValidationError(format!(
+                    "Mandatory source path {} missing in message {}",
+                    field.source, reference
+                )));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Slices of `segments` each starting at a `trigger` segment
+fn occurrences<'a>(segments: &'a [EdifactSegment], trigger: &'a str) -> impl Iterator<Item = &'a [EdifactSegment]> {
+    let starts: Vec<usize> = segments.iter()
+        .enumerate()
+        .filter(|(_, s)| s.tag == trigger)
+        .map(|(i, _)| i)
+        .collect();
+    let ends: Vec<usize> = starts.iter().skip(1).copied().chain([segments.len()]).collect();
+    starts.into_iter().zip(ends).map(move |(start, end)| &segments[start..end])
+}
+
+/// Set `pointer` in `document`, creating intermediate objects
+fn set_pointer(document: &mut Value, pointer: &str, value: Value) -> Result<(), EdiError> {
+    let invalid = |reason: &str| EdiError::ValidationError(format!("Invalid target pointer '{}': {}", pointer, reason));
+    let tokens: Vec<String> = pointer.strip_prefix('/')
+        .ok_or_else(|| invalid("must start with '/'"))?
+        .split('/')
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect();
+
+    let (last, parents) = tokens.split_last().ok_or_else(|| invalid("empty"))?;
+    let mut current = document;
+    for token in parents {
+        current = current.as_object_mut()
+            .ok_or_else(|| invalid("parent is not an object"))?
+            .entry(token.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    current.as_object_mut()
+        .ok_or_else(|| invalid("parent is not an object"))?
+        .insert(last.clone(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, EdifactElement};
+
+    fn orders() -> EdifactInterchange {
+        let segments = vec![
+            EdifactSegment::simple("BGM", &["220", "PO-1001", "9"]),
+            EdifactSegment {
+                tag: "DTM".into(),
+                elements: vec![EdifactElement::composite(&["137", "20230516", "102"])],
+            },
+            EdifactSegment::simple("NAD", &["BY", "5412345000013"]),
+            EdifactSegment::simple("NAD", &["SU", "4012345500004"]),
+            EdifactSegment::simple("LIN", &["1", "", "4000862141404"]),
+            EdifactSegment { tag: "QTY".into(), elements: vec![EdifactElement::composite(&["21", "48"])] },
+            EdifactSegment::simple("LIN", &["2", "", "4000862141411"]),
+            EdifactSegment { tag: "QTY".into(), elements: vec![EdifactElement::composite(&["21", "12"])] },
+        ];
+        fixtures::interchange(vec![fixtures::message("1", "ORDERS", segments)])
+    }
+
+    fn field(source: &str, target: &str, mandatory: bool) -> FieldMapping {
+        FieldMapping { source: source.parse().unwrap(), target: target.into(), mandatory }
+    }
+
+    fn spec() -> MappingSpec {
+        MappingSpec {
+            message_type: "ORDERS".into(),
+            fields: vec![
+                field("BGM/1/0", "/order/number", true),
+                field("DTM[137]/0/1", "/order/date", true),
+                field("NAD[BY]/1/0", "/buyer/gln", true),
+                field("NAD[SU]/1/0", "/supplier/gln", false),
+                field("FTX/3/0", "/notes", false),
+            ],
+            groups: vec![GroupMapping {
+                trigger: "LIN".into(),
+                target: "/order/lines".into(),
+                fields: vec![
+                    field("LIN/0/0", "/line", true),
+                    field("LIN/2/0", "/gtin", true),
+                    field("QTY[21]/0/1", "/quantity", true),
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_orders_maps_to_json_order() {
+        let documents = transform(&orders(), &spec()).unwrap();
+        assert_eq!(documents, serde_json::json!([{
+            "order": {
+                "number": "PO-1001",
+                "date": "20230516",
+                "lines": [
+                    {"line": "1", "gtin": "4000862141404", "quantity": "48"},
+                    {"line": "2", "gtin": "4000862141411", "quantity": "12"},
+                ],
+            },
+            "buyer": {"gln": "5412345000013"},
+            "supplier": {"gln": "4012345500004"},
+        }]));
+    }
+
+    #[test]
+    fn test_missing_mandatory_path_names_it() {
+        let mut spec = spec();
+        spec.fields.push(field("NAD[DP]/1/0", "/delivery/gln", true));
+        assert_eq!(
+            transform(&orders(), &spec),
+            Err(EdiError::ValidationError("Mandatory source path NAD[DP]/1/0 missing in message 1".into()))
+        );
+    }
+
+    #[test]
+    fn test_source_path_round_trips() {
+        for path in ["BGM/1/0", "NAD[BY]/1/0"] {
+            assert_eq!(path.parse::<SourcePath>().unwrap().to_string(), path);
+        }
+        for bad in ["", "BGM", "BGM/x/0", "NAD[BY/1/0", "BGM/1/0/2"] {
+            assert!(bad.parse::<SourcePath>().is_err(), "accepted {:?}", bad);
+        }
+    }
+}
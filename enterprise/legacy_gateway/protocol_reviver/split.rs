@@ -0,0 +1,111 @@
+// split.rs - Splitting Oversized EDIFACT Interchanges
+use ring::digest;
+
+use super::{EdiError, EdifactInterchange, UnbSegment, UnzSegment};
+
+/// Longest interchange control reference (data element 0020, an..14)
+pub(crate) const MAX_CONTROL_REFERENCE_LEN: usize = 14;
+/// Hex digits of the original reference's digest kept when it is shortened
+pub(crate) const DIGEST_LEN: usize = 6;
+
+impl EdifactInterchange {
+    /// Partition the messages into interchanges of at most
+    /// `max_messages_per_interchange`, for receivers capping interchange size
+    ///
+    /// Messages keep their order and their own UNH/UNT. Each part copies
+    /// the UNB parties, syntax and references, and gets its own control
+    /// reference (see `part_control_reference`) with a UNZ carrying it and
+    /// the part's message count. An interchange that already fits is
+    /// returned unchanged, original control reference included. A limit of
+    /// zero is a `ValidationError`.
+    pub fn split(&self, max_messages_per_interchange: usize) -> Result<Vec<EdifactInterchange>, EdiError> {
+        if max_messages_per_interchange == 0 {
+            return Err(EdiError::ValidationError("Interchanges must hold at least one message".into()));
+        }
+        if self.messages.len() <= max_messages_per_interchange {
+            return Ok(vec![self.clone()]);
+        }
+
+        Ok(self.messages
+            .chunks(max_messages_per_interchange)
+            .enumerate()
+            .map(|(index, messages)| {
+                let control_reference = part_control_reference(&self.unb.control_reference, index + 1);
+                EdifactInterchange {
+                    unb: UnbSegment { control_reference: control_reference.clone(), ..self.unb.clone() },
+                    messages: messages.to_vec(),
+                    unz: UnzSegment {
+                        interchange_control_count: messages.len() as u32,
+                        interchange_control_reference: control_reference,
+                    },
+                }
+            })
+            .collect())
+    }
+}
+
+/// Control reference of the `part`th interchange split from `original`:
+/// `original-part`, kept within an..14 so the part number always survives
+///
+/// A reference too long for that keeps its leading characters followed by
+/// a digest of the whole reference, so originals sharing a prefix still get
+/// distinct parts.
+fn part_control_reference(original: &str, part: usize) -> String {
+    let suffix = format!("-{}", part);
+    let keep = MAX_CONTROL_REFERENCE_LEN.saturating_sub(suffix.len());
+    if original.chars().count() <= keep {
+        return format!("{}{}", original, suffix);
+    }
+    let digest_len = DIGEST_LEN.min(keep);
+    let prefix: String = original.chars().take(keep - digest_len).collect();
+    let digest = hex::encode_upper(digest::digest(&digest::SHA256, original.as_bytes()));
+    format!("{}{}{}", prefix, &digest[..digest_len], suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, EdiParser, ParserConfig};
+
+    #[test]
+    fn test_ten_messages_split_into_valid_interchanges_of_three() {
+        let interchange = fixtures::interchange((1..=10).map(fixtures::order).collect());
+        let parser = EdiParser::new("", ParserConfig::default()).unwrap();
+
+        let parts = interchange.split(3).unwrap();
+        assert_eq!(parts.len(), 4);
+        let counts: Vec<_> = parts.iter().map(|part| part.unz.interchange_control_count).collect();
+        assert_eq!(counts, [3, 3, 3, 1]);
+        for (index, part) in parts.iter().enumerate() {
+            assert!(parser.validate_interchange(&part.unb, &part.unz, &part.messages).is_ok());
+            assert_eq!(part.unb.control_reference, format!("123456-{}", index + 1));
+            assert_eq!(part.unb.sender_identification, interchange.unb.sender_identification);
+        }
+
+        // Messages keep their order across the parts
+        let references: Vec<_> = parts.iter()
+            .flat_map(|part| &part.messages)
+            .map(|message| message.unh.message_reference_number.as_str())
+            .collect();
+        assert_eq!(references, ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+
+        // An interchange that fits is left as it is
+        let whole = interchange.split(10).unwrap();
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].unb.control_reference, "123456");
+
+        assert!(matches!(interchange.split(0), Err(EdiError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_part_control_reference_stays_within_an14() {
+        assert_eq!(part_control_reference("REF", 2), "REF-2");
+        assert_eq!(part_control_reference("ABCDEFGHIJKL", 1), "ABCDEFGHIJKL-1");
+        let long = part_control_reference("ABCDEFGHIJKLMN", 12);
+        assert!(long.starts_with("ABCDE") && long.ends_with("-12"), "{}", long);
+        assert_eq!(long.len(), MAX_CONTROL_REFERENCE_LEN);
+
+        // Shortened references sharing a prefix stay apart
+        assert_ne!(part_control_reference("ABCDEFGHIJKLMN", 1), part_control_reference("ABCDEFGHIJKLMZ", 1));
+    }
+}
@@ -0,0 +1,199 @@
+// stream.rs - Message-at-a-Time Interchange Parsing
+use std::cell::Cell;
+
+use serde::{Deserialize, Serialize};
+
+use super::{EdiDelimiters, EdiError, EdiParser, EdifactMessage, UnbSegment, UnknownSegmentTag, UnzSegment};
+
+/// Messages of one interchange, parsed as they are read
+///
+/// Keeps a running message count so that, once the UNZ trailer arrives,
+/// its control count and reference are checked against what was actually
+/// streamed. A disagreement is yielded as a final `EdiError::ValidationError`
+/// after the last message. The stream ends after the first error.
+pub struct MessageStream<'p, 'a> {
+    parser: &'p mut EdiParser<'a>,
+    unb: UnbSegment,
+    unz: Option<UnzSegment>,
+    message_count: u64,
+    finished: bool,
+    /// Characters and bytes of input already matched up, so each
+    /// checkpoint only scans the input read since the last
+    scanned: Cell<(usize, usize)>,
+}
+
+/// Where a stream stood between two messages
+///
+/// Serializable so a batch job can persist it and, after a crash, resume
+/// parsing the same input from here instead of from the start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParserCheckpoint {
+    /// Input consumed, ending just past a segment terminator
+    pub byte_offset: usize,
+    /// Messages streamed before the checkpoint
+    pub message_count: u64,
+    /// Characters consumed, which parser positions count in
+    position: usize,
+    control_reference: String,
+    delimiters: EdiDelimiters,
+    unknown_tags: Vec<UnknownSegmentTag>,
+}
+
+impl<'a> EdiParser<'a> {
+    /// Parse the UNB header and stream the messages that follow it
+    pub fn stream_messages(&mut self) -> Result<MessageStream<'_, 'a>, EdiError> {
+        let unb = self.parse_unb()?;
+        self.validate_version(&unb.syntax_version)?;
+        Ok(MessageStream::new(self, unb, 0))
+    }
+
+    /// Stream the messages after `checkpoint`, taken over this same input
+    ///
+    /// The header is parsed again and its delimiters and control reference
+    /// must match the checkpoint's before parsing jumps to its offset.
+    pub fn resume(&mut self, checkpoint: &ParserCheckpoint) -> Result<MessageStream<'_, 'a>, EdiError> {
+        let unb = self.parse_unb()?;
+        self.validate_version(&unb.syntax_version)?;
+        if self.delimiters != checkpoint.delimiters {
+            return Err(EdiError::ValidationError("Checkpoint delimiters differ from the interchange's".into()));
+        }
+        if unb.control_reference != checkpoint.control_reference {
+            return Err(EdiError::ValidationError(format!(
+                "Checkpoint is for interchange {}, not {}",
+                checkpoint.control_reference, unb.control_reference
+            )));
+        }
+
+        // The offset must close the header or a UNT, on an unescaped
+        // terminator, and sit exactly `position` characters in
+        let offset = checkpoint.byte_offset;
+        let header_end = self.input.char_indices().nth(self.position).map_or(self.input.len(), |(index, _)| index);
+        let trailer = format!("UNT{}", self.delimiters.data_separator);
+        let at_boundary = self.input.get(..offset).is_some_and(|consumed| {
+            last_segment(consumed, &self.delimiters)
+                .is_some_and(|segment| offset == header_end || segment.starts_with(&trailer))
+                && offset >= header_end
+                && consumed.chars().count() == checkpoint.position
+        });
+        if !at_boundary {
+            return Err(EdiError::SyntaxError {
+                position: checkpoint.position,
+                details: "Checkpoint offset is not a message boundary in this input".into(),
+            });
+        }
+
+        self.chars = self.input[offset..].chars().peekable();
+        self.position = checkpoint.position;
+        self.message_type = None;
+        self.unknown_tags = checkpoint.unknown_tags.clone();
+        let stream = MessageStream::new(self, unb, checkpoint.message_count);
+        stream.scanned.set((checkpoint.position, offset));
+        Ok(stream)
+    }
+}
+
+/// Segment `consumed` ends with, if it ends on an unescaped terminator
+fn last_segment<'s>(consumed: &'s str, delimiters: &EdiDelimiters) -> Option<&'s str> {
+    let body = consumed.strip_suffix(delimiters.segment_terminator)?;
+    let escaped = |end: usize| {
+        body[..end].chars().rev().take_while(|&c| c == delimiters.escape_character).count() % 2 == 1
+    };
+    if escaped(body.len()) {
+        return None;
+    }
+    let start = body.rmatch_indices(delimiters.segment_terminator)
+        .map(|(index, _)| index)
+        .find(|&index| !escaped(index))
+        .map_or(0, |index| index + delimiters.segment_terminator.len_utf8());
+    Some(body[start..].trim_start())
+}
+
+impl<'p, 'a> MessageStream<'p, 'a> {
+    fn new(parser: &'p mut EdiParser<'a>, unb: UnbSegment, message_count: u64) -> Self {
+        Self { parser, unb, unz: None, message_count, finished: false, scanned: Cell::new((0, 0)) }
+    }
+
+    /// Checkpoint to resume from, while the stream sits between messages
+    ///
+    /// The stream only ever stops after a whole message or the header, so
+    /// any point it can be asked at is a safe boundary. Once the trailer has
+    /// been read or an error yielded there is nothing left to resume.
+    pub fn checkpoint(&self) -> Option<ParserCheckpoint> {
+        if self.finished {
+            return None;
+        }
+        let parser = &*self.parser;
+        let (chars, bytes) = self.scanned.get();
+        let byte_offset = bytes + parser.input[bytes..]
+            .char_indices()
+            .nth(parser.position - chars)
+            .map_or(parser.input.len() - bytes, |(index, _)| index);
+        self.scanned.set((parser.position, byte_offset));
+
+        Some(ParserCheckpoint {
+            byte_offset,
+            message_count: self.message_count,
+            position: parser.position,
+            control_reference: self.unb.control_reference.clone(),
+            delimiters: parser.delimiters.clone(),
+            unknown_tags: parser.unknown_tags.clone(),
+        })
+    }
+
+    pub fn unb(&self) -> &UnbSegment {
+        &self.unb
+    }
+
+    /// Trailer, once the stream has reached it
+    pub fn unz(&self) -> Option<&UnzSegment> {
+        self.unz.as_ref()
+    }
+
+    /// Messages yielded so far
+    pub fn message_count(&self) -> u64 {
+        self.message_count
+    }
+
+    fn next_message(&mut self) -> Result<Option<EdifactMessage>, EdiError> {
+        let parser = &mut *self.parser;
+        if parser.peek_segment_tag()? == "UNH" {
+            if self.message_count == parser.config.max_messages as u64 {
+                return Err(EdiError::ResourceLimit(format!(
+                    "more than {} messages in interchange",
+                    parser.config.max_messages
+                )));
+            }
+            let message = parser.parse_message()?;
+            if parser.config.validate_structure {
+                parser.validate_segment_count(&message)?;
+            }
+            self.message_count += 1;
+            return Ok(Some(message));
+        }
+
+        self.finished = true;
+        let unz = parser.parse_unz()?;
+        let checked = if parser.config.validate_structure {
+            parser.validate_trailer(&self.unb, &unz, self.message_count)
+        } else {
+            Ok(())
+        };
+        self.unz = Some(unz);
+        checked.map(|()| None)
+    }
+}
+
+impl Iterator for MessageStream<'_, '_> {
+    type Item = Result<EdifactMessage, EdiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let next = self.next_message();
+        if next.is_err() {
+            self.finished = true;
+        }
+        next.transpose()
+    }
+}
@@ -0,0 +1,287 @@
+// x12.rs - ANSI X12 Interchange Model and 997 Functional Acknowledgment
+use serde::{Serialize, Deserialize};
+
+use super::{EdifactSegment, SyntaxErrorCode, ValidationOutcome};
+
+/// ISA interchange control header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsaSegment {
+    pub sender_qualifier: String,
+    pub sender_id: String,
+    pub receiver_qualifier: String,
+    pub receiver_id: String,
+    pub date: String,
+    pub time: String,
+    pub version: String,
+    pub control_number: String,
+    pub usage_indicator: String,
+}
+
+/// GS/GE functional group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct X12FunctionalGroup {
+    pub functional_id: String,
+    pub sender_code: String,
+    pub receiver_code: String,
+    pub control_number: String,
+    pub version: String,
+    pub transactions: Vec<X12Transaction>,
+}
+
+/// ST/SE transaction set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct X12Transaction {
+    pub set_id: String,
+    pub control_number: String,
+    pub segments: Vec<EdifactSegment>,
+}
+
+/// Complete X12 interchange (ISA..IEA)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct X12Interchange {
+    pub isa: IsaSegment,
+    pub groups: Vec<X12FunctionalGroup>,
+}
+
+impl SyntaxErrorCode {
+    /// Closest AK304 segment syntax error code
+    pub fn x12_code(&self) -> &'static str {
+        match self {
+            Self::Missing => "3",
+            Self::NotSupportedInPosition => "2",
+            Self::TooManyRepetitions => "5",
+            Self::InvalidValue | Self::ValueNotSupported | Self::TooManyConstituents => "8",
+            Self::Unspecified => "8",
+        }
+    }
+
+    /// Closest AK403 data element syntax error code in the 004010 code list
+    pub fn x12_element_code(&self) -> &'static str {
+        match self {
+            Self::Missing => "1",
+            Self::TooManyConstituents | Self::NotSupportedInPosition | Self::TooManyRepetitions => "3",
+            Self::InvalidValue | Self::ValueNotSupported | Self::Unspecified => "7",
+        }
+    }
+}
+
+/// AK304 for a segment whose errors are in its data elements, reported in AK4
+const SEGMENT_HAS_ELEMENT_ERRORS: &str = "8";
+
+impl X12Interchange {
+    /// Generate a 997 acknowledging every functional group in this interchange
+    ///
+    /// Rejections are matched to transaction sets by their group's GS06 and
+    /// their own ST02 control number, since ST02 repeats across groups.
+    pub fn generate_997(&self, validation: &ValidationOutcome) -> X12Interchange {
+        let groups = self.groups.iter()
+            .enumerate()
+            .map(|(index, group)| {
+                let control_number = format!("{:04}", index + 1);
+                X12FunctionalGroup {
+                    functional_id: "FA".into(),
+                    sender_code: group.receiver_code.clone(),
+                    receiver_code: group.sender_code.clone(),
+                    control_number: control_number.clone(),
+                    version: group.version.clone(),
+                    transactions: vec![ack_transaction(group, &control_number, validation)],
+                }
+            })
+            .collect();
+
+        X12Interchange {
+            isa: IsaSegment {
+                sender_qualifier: self.isa.receiver_qualifier.clone(),
+                sender_id: self.isa.receiver_id.clone(),
+                receiver_qualifier: self.isa.sender_qualifier.clone(),
+                receiver_id: self.isa.sender_id.clone(),
+                date: self.isa.date.clone(),
+                time: self.isa.time.clone(),
+                version: self.isa.version.clone(),
+                control_number: ack_control_number(&self.isa.control_number),
+                usage_indicator: self.isa.usage_indicator.clone(),
+            },
+            groups,
+        }
+    }
+}
+
+fn ack_transaction(
+    group: &X12FunctionalGroup,
+    control_number: &str,
+    validation: &ValidationOutcome,
+) -> X12Transaction {
+    let mut segments = vec![
+        EdifactSegment::simple("AK1", &[&group.functional_id, &group.control_number]),
+    ];
+    let mut accepted = 0;
+
+    for txn in &group.transactions {
+        segments.push(EdifactSegment::simple("AK2", &[&txn.set_id, &txn.control_number]));
+
+        let mut rejected = false;
+        for rejection in validation.rejections_for_transaction(&group.control_number, &txn.control_number) {
+            rejected = true;
+            let segment_code = match rejection.element_position {
+                Some(_) => SEGMENT_HAS_ELEMENT_ERRORS,
+                None => rejection.error_code.x12_code(),
+            };
+            segments.push(EdifactSegment::simple("AK3", &[
+                &rejection.segment_tag,
+                &rejection.segment_position.to_string(),
+                "",
+                segment_code,
+            ]));
+            if let Some(element) = rejection.element_position {
+                segments.push(EdifactSegment::simple("AK4", &[
+                    &element.to_string(),
+                    "",
+                    rejection.error_code.x12_element_code(),
+                ]));
+            }
+        }
+
+        if rejected {
+            segments.push(EdifactSegment::simple("AK5", &["R", "5"]));
+        } else {
+            accepted += 1;
+            segments.push(EdifactSegment::simple("AK5", &["A"]));
+        }
+    }
+
+    let total = group.transactions.len();
+    let group_code = match accepted {
+        n if n == total => "A",
+        0 => "R",
+        _ => "P",
+    };
+    segments.push(EdifactSegment::simple("AK9", &[
+        group_code,
+        &total.to_string(),
+        &total.to_string(),
+        &accepted.to_string(),
+    ]));
+
+    X12Transaction {
+        set_id: "997".into(),
+        control_number: control_number.to_string(),
+        segments,
+    }
+}
+
+/// ISA13 is a fixed-width nine digit control number
+fn ack_control_number(original: &str) -> String {
+    let next = original.parse::<u64>().unwrap_or(0).wrapping_add(1) % 1_000_000_000;
+    format!("{:09}", next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SegmentRejection;
+
+    fn purchase_order() -> X12Interchange {
+        X12Interchange {
+            isa: IsaSegment {
+                sender_qualifier: "ZZ".into(),
+                sender_id: "SENDER".into(),
+                receiver_qualifier: "ZZ".into(),
+                receiver_id: "RECEIVER".into(),
+                date: "230516".into(),
+                time: "1345".into(),
+                version: "00401".into(),
+                control_number: "000000905".into(),
+                usage_indicator: "P".into(),
+            },
+            groups: vec![X12FunctionalGroup {
+                functional_id: "PO".into(),
+                sender_code: "SENDER".into(),
+                receiver_code: "RECEIVER".into(),
+                control_number: "17".into(),
+                version: "004010".into(),
+                transactions: vec![X12Transaction {
+                    set_id: "850".into(),
+                    control_number: "0001".into(),
+                    segments: vec![EdifactSegment::simple("BEG", &["00", "SA", "PO-1"])],
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_997_references_group_and_transaction() {
+        let interchange = purchase_order();
+        let mut validation = ValidationOutcome::accepted();
+        validation.reject(SegmentRejection {
+            message_reference: "0001".into(),
+            segment_position: 2,
+            segment_tag: "BEG".into(),
+            error_code: SyntaxErrorCode::Missing,
+            element_position: None,
+            group_control_number: Some("17".into()),
+        });
+
+        let ack = interchange.generate_997(&validation);
+        assert_eq!(ack.isa.sender_id, "RECEIVER");
+        assert_eq!(ack.isa.control_number, "000000906");
+
+        let txn = &ack.groups[0].transactions[0];
+        assert_eq!(txn.set_id, "997");
+        assert_eq!(txn.segments[0].elements[1].components[0], "17");
+
+        let ak3 = txn.segments.iter().find(|s| s.tag == "AK3").unwrap();
+        assert_eq!(ak3.elements[1].components[0], "2");
+        let ak9 = txn.segments.iter().find(|s| s.tag == "AK9").unwrap();
+        assert_eq!(ak9.elements[0].components[0], "R");
+    }
+
+    #[test]
+    fn test_997_reports_element_errors_in_ak4() {
+        let mut validation = ValidationOutcome::accepted();
+        validation.reject(SegmentRejection {
+            message_reference: "0001".into(),
+            segment_position: 2,
+            segment_tag: "BEG".into(),
+            error_code: SyntaxErrorCode::InvalidValue,
+            element_position: Some(2),
+            group_control_number: Some("17".into()),
+        });
+
+        let ack = purchase_order().generate_997(&validation);
+        let txn = &ack.groups[0].transactions[0];
+        let ak3 = txn.segments.iter().find(|s| s.tag == "AK3").unwrap();
+        assert_eq!(ak3.elements[3].components[0], SEGMENT_HAS_ELEMENT_ERRORS);
+        let ak4 = txn.segments.iter().find(|s| s.tag == "AK4").unwrap();
+        assert_eq!(ak4.elements[0].components[0], "2");
+        // AK403 is mandatory
+        assert_eq!(ak4.elements[2].components[0], "7");
+    }
+
+    #[test]
+    fn test_rejection_applies_only_to_its_own_group() {
+        let mut interchange = purchase_order();
+        let mut second = interchange.groups[0].clone();
+        second.control_number = "18".into();
+        interchange.groups.push(second);
+
+        // Both groups hold an ST02 of 0001; only the second's is rejected
+        let mut validation = ValidationOutcome::accepted();
+        validation.reject(SegmentRejection {
+            message_reference: "0001".into(),
+            segment_position: 2,
+            segment_tag: "BEG".into(),
+            error_code: SyntaxErrorCode::Missing,
+            element_position: None,
+            group_control_number: Some("18".into()),
+        });
+
+        let ack = interchange.generate_997(&validation);
+        let ak5 = |group: usize| {
+            let txn = &ack.groups[group].transactions[0];
+            txn.segments.iter().find(|s| s.tag == "AK5").unwrap().elements[0].components[0].clone()
+        };
+        assert_eq!(ak5(0), "A");
+        assert_eq!(ak5(1), "R");
+        assert!(ack.groups[0].transactions[0].segments.iter().all(|s| s.tag != "AK3"));
+    }
+}
@@ -0,0 +1,224 @@
+// admin.rs - Reputation Admin gRPC Service
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+use futures::Stream;
+pub use nuzon_core::admin::AdminAuth;
+use tokio::sync::{broadcast::error::RecvError, watch};
+use tonic::{Request, Response, Status};
+use tracing::warn;
+
+use super::{ConvergenceReport, ReputationEngine, TrustUpdateEvent};
+
+pub mod pb {
+    tonic::include_proto!("nuzon.reputation.admin.v1");
+}
+
+use pb::{
+    reputation_admin_server::{ReputationAdmin, ReputationAdminServer},
+    trust_update_event::Event,
+    RecomputeTrustRequest, UpdateStarted, WatchTrustUpdatesRequest,
+};
+
+/// Outcome shared by every caller joined to one recompute
+type RunResult = Option<Result<ConvergenceReport, String>>;
+
+/// Lets operators trigger trust recomputation and follow its progress
+///
+/// Concurrent `RecomputeTrust` calls are coalesced: while a run is in
+/// flight, later callers wait for it and receive its report instead of
+/// starting another.
+pub struct ReputationAdminService {
+    engine: Arc<ReputationEngine>,
+    in_flight: Arc<Mutex<Option<watch::Receiver<RunResult>>>>,
+    auth: AdminAuth,
+}
+
+impl ReputationAdminService {
+    pub fn new(engine: Arc<ReputationEngine>, auth: AdminAuth) -> Self {
+        Self { engine, in_flight: Arc::new(Mutex::new(None)), auth }
+    }
+
+    pub fn into_server(self) -> ReputationAdminServer<Self> {
+        ReputationAdminServer::new(self)
+    }
+
+    /// Join the in-flight run, or start one that outlives its callers
+    async fn recompute(&self) -> Result<ConvergenceReport, String> {
+        let mut run = {
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            match in_flight.as_ref() {
+                Some(run) => run.clone(),
+                None => {
+                    let (done, run) = watch::channel(None);
+                    *in_flight = Some(run.clone());
+                    let (engine, clear) = (self.engine.clone(), ClearInFlight(self.in_flight.clone()));
+                    tokio::spawn(async move {
+                        let result = engine.update_trust().await.map_err(|e| e.to_string());
+                        // Callers arriving from here on start a fresh run
+                        drop(clear);
+                        let _ = done.send(Some(result));
+                    });
+                    run
+                }
+            }
+        };
+        let result = run.wait_for(Option::is_some)
+            .await
+            .map_err(|_| "trust recompute task ended without a result".to_string())?
+            .clone();
+        result.expect("waited for a result")
+    }
+}
+
+/// Clears the in-flight run once its task ends, even by panicking, so a
+/// failed run never holds up the next recompute
+struct ClearInFlight(Arc<Mutex<Option<watch::Receiver<RunResult>>>>);
+
+impl Drop for ClearInFlight {
+    fn drop(&mut self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+}
+
+impl From<ConvergenceReport> for pb::ConvergenceReport {
+    fn from(report: ConvergenceReport) -> Self {
+        Self {
+            iterations: report.iterations as u32,
+            final_delta: if report.final_delta.is_finite() { report.final_delta } else { 0.0 },
+            nodes: report.nodes as u32,
+            converged: report.converged,
+            incremental: report.incremental,
+            seconds: report.duration.as_secs_f64(),
+        }
+    }
+}
+
+impl From<TrustUpdateEvent> for pb::TrustUpdateEvent {
+    fn from(event: TrustUpdateEvent) -> Self {
+        let event = match event {
+            TrustUpdateEvent::Started { incremental } => Event::Started(UpdateStarted { incremental }),
+            TrustUpdateEvent::Completed(report) => Event::Completed(report.into()),
+            TrustUpdateEvent::Failed(reason) => Event::Failed(reason),
+        };
+        Self { event: Some(event) }
+    }
+}
+
+/// Refuse `request` unless it carries a token `auth` accepts
+fn authorize<T>(auth: &AdminAuth, request: &Request<T>) -> Result<(), Status> {
+    let authorization = request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+    auth.check(authorization).map_err(|e| Status::unauthenticated(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl ReputationAdmin for ReputationAdminService {
+    type WatchTrustUpdatesStream =
+        Pin<Box<dyn Stream<Item = Result<pb::TrustUpdateEvent, Status>> + Send + 'static>>;
+
+    async fn recompute_trust(
+        &self,
+        request: Request<RecomputeTrustRequest>,
+    ) -> Result<Response<pb::ConvergenceReport>, Status> {
+        authorize(&self.auth, &request)?;
+        self.recompute()
+            .await
+            .map(|report| Response::new(report.into()))
+            .map_err(Status::unavailable)
+    }
+
+    async fn watch_trust_updates(
+        &self,
+        request: Request<WatchTrustUpdatesRequest>,
+    ) -> Result<Response<Self::WatchTrustUpdatesStream>, Status> {
+        authorize(&self.auth, &request)?;
+        let events = futures::stream::unfold(self.engine.subscribe_updates(), |mut events| async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => return Some((Ok(event.into()), events)),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(missed, "Trust update watcher fell behind, events skipped");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const TOKEN: &str = "operator-token";
+
+    fn authorized<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", format!("Bearer {}", TOKEN).parse().unwrap());
+        request
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_recomputes_share_one_run() {
+        let engine = Arc::new(ReputationEngine::new("host=localhost user=postgres", 0.85).await.unwrap());
+        engine.initialize_trust().await.unwrap();
+        let admin = Arc::new(ReputationAdminService::new(engine.clone(), AdminAuth::default().allow_token(TOKEN)));
+        let mut events = engine.subscribe_updates();
+
+        // Hold the graph so the first run cannot finish before the second joins
+        let hold = engine.nodes.write().await;
+        let calls: Vec<_> = (0..2).map(|_| {
+            let admin = admin.clone();
+            tokio::spawn(async move {
+                admin.recompute_trust(authorized(RecomputeTrustRequest {})).await
+            })
+        }).collect();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(hold);
+
+        let mut reports = Vec::new();
+        for call in calls {
+            reports.push(call.await.unwrap().unwrap().into_inner());
+        }
+        assert_eq!(reports[0], reports[1]);
+
+        let mut started = 0;
+        while let Ok(event) = events.try_recv() {
+            started += matches!(event, TrustUpdateEvent::Started { .. }) as usize;
+        }
+        assert_eq!(started, 1);
+    }
+
+    #[tokio::test]
+    async fn test_calls_without_an_accepted_token_are_refused() {
+        let engine = Arc::new(ReputationEngine::new("host=localhost user=postgres", 0.85).await.unwrap());
+        let admin = ReputationAdminService::new(engine.clone(), AdminAuth::default().allow_token(TOKEN));
+        let mut events = engine.subscribe_updates();
+
+        let anonymous = admin.recompute_trust(Request::new(RecomputeTrustRequest {})).await;
+        assert_eq!(anonymous.unwrap_err().code(), tonic::Code::Unauthenticated);
+        let mut forged = Request::new(WatchTrustUpdatesRequest {});
+        forged.metadata_mut().insert("authorization", "Bearer guessed".parse().unwrap());
+        assert_eq!(admin.watch_trust_updates(forged).await.err().unwrap().code(), tonic::Code::Unauthenticated);
+        // The refused recompute never started
+        assert!(events.try_recv().is_err());
+
+        let closed = ReputationAdminService::new(engine, AdminAuth::default());
+        assert!(closed.recompute_trust(authorized(RecomputeTrustRequest {})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_panicked_run_clears_the_in_flight_slot() {
+        let slot = Arc::new(Mutex::new(Some(watch::channel(None).1)));
+        let clear = ClearInFlight(slot.clone());
+        let run = tokio::spawn(async move {
+            let _clear = clear;
+            panic!("trust update panicked");
+        });
+        assert!(run.await.unwrap_err().is_panic());
+        assert!(slot.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,142 @@
+// connection.rs - Supervised Postgres Connection with Failover
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{Notify, RwLock, RwLockMappedWriteGuard, RwLockReadGuard, RwLockWriteGuard},
+    time::Instant,
+};
+use tokio_postgres::{tls::NoTlsStream, Client, Connection, NoTls, Socket};
+use tracing::{info, warn};
+
+use super::ReputationError;
+
+type PgConnection = Connection<Socket, NoTlsStream>;
+
+/// Database endpoints and reconnection policy
+#[derive(Debug, Clone)]
+pub struct DbConfig {
+    pub primary: String,
+    /// Tried in order after the primary when it cannot be reached
+    pub replicas: Vec<String>,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// How long a query waits for a reconnect before reporting `Unavailable`
+    pub acquire_timeout: Duration,
+}
+
+impl DbConfig {
+    pub fn new(primary: &str) -> Self {
+        Self {
+            primary: primary.to_string(),
+            replicas: Vec::new(),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            acquire_timeout: Duration::from_secs(2),
+        }
+    }
+
+    pub fn with_replicas(mut self, replicas: Vec<String>) -> Self {
+        self.replicas = replicas;
+        self
+    }
+
+    fn uris(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.primary.as_str()).chain(self.replicas.iter().map(String::as_str))
+    }
+}
+
+/// Owns the Postgres client and replaces it whenever the connection dies
+///
+/// The client lives behind a lock that is emptied while reconnecting, so
+/// callers either see the new connection or wait up to `acquire_timeout`
+/// and get `ReputationError::Unavailable`.
+#[derive(Debug)]
+pub struct DbSupervisor {
+    config: DbConfig,
+    client: RwLock<Option<Client>>,
+    connected: Notify,
+}
+
+impl DbSupervisor {
+    /// Connect to the first reachable endpoint and start supervising it
+    pub async fn connect(config: DbConfig) -> Result<Arc<Self>, ReputationError> {
+        let mut last_error = None;
+        for uri in config.uris() {
+            match tokio_postgres::connect(uri, NoTls).await {
+                Ok((client, connection)) => {
+                    let supervisor = Arc::new(Self {
+                        config: config.clone(),
+                        client: RwLock::new(Some(client)),
+                        connected: Notify::new(),
+                    });
+                    tokio::spawn(supervisor.clone().supervise(connection));
+                    return Ok(supervisor);
+                }
+                Err(e) => {
+                    warn!(error = %e, "Reputation database endpoint unreachable");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.map(ReputationError::from).unwrap_or(ReputationError::Unavailable))
+    }
+
+    /// Shared access to the live client
+    pub async fn client(&self) -> Result<RwLockReadGuard<'_, Client>, ReputationError> {
+        let deadline = Instant::now() + self.config.acquire_timeout;
+        loop {
+            let notified = self.connected.notified();
+            let slot = self.client.read().await;
+            if let Ok(client) = RwLockReadGuard::try_map(slot, |s| s.as_ref().filter(|c| !c.is_closed())) {
+                return Ok(client);
+            }
+            tokio::time::timeout_at(deadline, notified).await
+                .map_err(|_| ReputationError::Unavailable)?;
+        }
+    }
+
+    /// Exclusive access to the live client, for transactions
+    pub async fn client_mut(&self) -> Result<RwLockMappedWriteGuard<'_, Client>, ReputationError> {
+        let deadline = Instant::now() + self.config.acquire_timeout;
+        loop {
+            let notified = self.connected.notified();
+            let slot = self.client.write().await;
+            if let Ok(client) = RwLockWriteGuard::try_map(slot, |s| s.as_mut().filter(|c| !c.is_closed())) {
+                return Ok(client);
+            }
+            tokio::time::timeout_at(deadline, notified).await
+                .map_err(|_| ReputationError::Unavailable)?;
+        }
+    }
+
+    /// Drive the connection; when it ends, reconnect and keep going
+    async fn supervise(self: Arc<Self>, mut connection: PgConnection) {
+        loop {
+            if let Err(e) = connection.await {
+                warn!(error = %e, "Reputation database connection lost");
+            }
+            *self.client.write().await = None;
+            connection = self.reconnect().await;
+        }
+    }
+
+    /// Cycle through primary and replicas with exponential backoff
+    async fn reconnect(&self) -> PgConnection {
+        let mut backoff = self.config.initial_backoff;
+        loop {
+            for uri in self.config.uris() {
+                match tokio_postgres::connect(uri, NoTls).await {
+                    Ok((client, connection)) => {
+                        info!("Reputation database connection restored");
+                        *self.client.write().await = Some(client);
+                        self.connected.notify_waiters();
+                        return connection;
+                    }
+                    Err(e) => warn!(error = %e, "Reconnect attempt failed"),
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+    }
+}
@@ -5,23 +5,50 @@
 #![feature(map_first_last)]
 
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::Arc,
-    time::SystemTime
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime}
 };
 
+use arc_swap::ArcSwap;
 use ed25519_dalek::{PublicKey, Signature, Signer, Verifier};
+use nuzon_core::{
+    clock::{Clock, SkewError, SkewPolicy, SystemClock},
+    coordination::{key_id, RevocationList},
+};
+use prometheus::{Encoder, Gauge, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use tokio_postgres::{Client, NoTls};
+use tokio::sync::broadcast;
+
+mod admin;
+mod connection;
+mod graph;
+mod keys;
+
+pub use admin::{AdminAuth, ReputationAdminService};
+pub use connection::{DbConfig, DbSupervisor};
+pub use keys::{interaction_message, rotation_message, KeyEntry, KeyHistory};
 
 const CONVERGENCE_THRESHOLD: f64 = 1e-9;
 const MAX_ITERATIONS: usize = 100;
+/// Incremental updates between forced full recomputes, bounding drift
+const FULL_RECOMPUTE_INTERVAL: usize = 16;
+/// Idle time after which a node's global trust has halved
+const DEFAULT_DECAY_HALF_LIFE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Update events buffered per subscriber before the slowest starts missing some
+const UPDATE_EVENT_CAPACITY: usize = 64;
+/// How long after a rotation interactions signed by the retired key are
+/// still accepted
+const DEFAULT_RETIRED_KEY_GRACE: Duration = Duration::from_secs(60);
+/// Brings a database created by an earlier release up to the current
+/// schema; every statement is idempotent
+const SCHEMA_MIGRATIONS: &str = "ALTER TABLE nodes ADD COLUMN IF NOT EXISTS key_history BYTEA;";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub id: String,
-    pub public_key: PublicKey,
+    pub keys: KeyHistory,
     local_trust: BTreeMap<String, f64>,
     global_trust: f64,
     last_updated: SystemTime,
@@ -30,103 +57,430 @@ pub struct Node {
 #[derive(Debug)]
 pub struct ReputationEngine {
     nodes: Arc<tokio::sync::RwLock<HashMap<String, Node>>>,
-    db_client: Client,
+    db: Arc<DbSupervisor>,
     alpha: f64,
+    metrics: ReputationMetrics,
+    clock: Arc<dyn Clock>,
+    skew: SkewPolicy,
+    revocations: Option<Arc<RevocationList>>,
+    decay_half_life: Duration,
+    retired_key_grace: Duration,
+    incremental: Mutex<IncrementalState>,
+    snapshot: ArcSwap<TrustSnapshot>,
+    updates: broadcast::Sender<TrustUpdateEvent>,
+}
+
+/// Outcome of one completed trust update
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceReport {
+    pub iterations: usize,
+    /// Largest per-node change in the final iteration
+    pub final_delta: f64,
+    pub nodes: usize,
+    /// False when the update stopped at `MAX_ITERATIONS`
+    pub converged: bool,
+    pub incremental: bool,
+    pub duration: Duration,
+}
+
+/// Trust update progress, as delivered to `subscribe_updates`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrustUpdateEvent {
+    Started { incremental: bool },
+    Completed(ConvergenceReport),
+    /// Scores were computed but could not be persisted
+    Failed(String),
+}
+
+/// Global scores as left by one completed update, readable without the
+/// `nodes` lock
+#[derive(Debug, Clone, Default)]
+pub struct TrustSnapshot {
+    /// Incremented each time a new snapshot is published
+    pub generation: u64,
+    /// Global trust and last-update time per node
+    pub scores: HashMap<String, (f64, SystemTime)>,
+}
+
+impl TrustSnapshot {
+    /// The `n` most trusted nodes, highest first
+    pub fn top_n(&self, n: usize) -> Vec<(String, f64)> {
+        let mut ranked: Vec<_> = self.scores.iter()
+            .map(|(id, (trust, _))| (id.clone(), *trust))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(n);
+        ranked
+    }
+}
+
+/// Bookkeeping carried between trust updates for incremental mode
+#[derive(Debug, Default)]
+struct IncrementalState {
+    /// Nodes whose local trust changed since the last update
+    dirty: HashSet<String>,
+    /// Unnormalized scores from the last update, reused for clean nodes
+    raw: HashMap<String, f64>,
+    /// Stored global trust each node's raw score was computed against
+    bias: HashMap<String, f64>,
+    /// Incremental updates since the last full recompute; `None` forces one
+    runs_since_full: Option<usize>,
+}
+
+/// Outcome of a power iteration
+#[derive(Debug)]
+struct Convergence {
+    trust: HashMap<String, f64>,
+    raw: HashMap<String, f64>,
+    iterations: usize,
+    delta: f64,
+}
+
+/// Convergence and ingestion metrics for the reputation engine
+#[derive(Debug, Clone)]
+pub struct ReputationMetrics {
+    registry: Registry,
+    iterations: IntGauge,
+    final_delta: Gauge,
+    interactions: IntCounterVec,
+    nodes: IntGauge,
+    nonconvergence: IntCounter,
+    graph_import: IntCounterVec,
+}
+
+impl ReputationMetrics {
+    fn register() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let metrics = Self {
+            iterations: IntGauge::new(
+                "reputation_iterations",
+                "Power iterations performed by the last trust update"
+            )?,
+            final_delta: Gauge::new(
+                "reputation_final_delta",
+                "Largest per-node trust change in the last iteration"
+            )?,
+            interactions: IntCounterVec::new(
+                Opts::new("reputation_interactions_total", "Signed interactions received"),
+                &["outcome"]
+            )?,
+            nodes: IntGauge::new(
+                "reputation_nodes",
+                "Nodes participating in trust computation"
+            )?,
+            nonconvergence: IntCounter::new(
+                "reputation_nonconvergence_total",
+                "Trust updates that hit MAX_ITERATIONS without converging"
+            )?,
+            graph_import: IntCounterVec::new(
+                Opts::new("reputation_graph_import_total", "Trust graph entries processed by bulk import"),
+                &["outcome"]
+            )?,
+            registry,
+        };
+
+        metrics.registry.register(Box::new(metrics.iterations.clone()))?;
+        metrics.registry.register(Box::new(metrics.final_delta.clone()))?;
+        metrics.registry.register(Box::new(metrics.interactions.clone()))?;
+        metrics.registry.register(Box::new(metrics.nodes.clone()))?;
+        metrics.registry.register(Box::new(metrics.nonconvergence.clone()))?;
+        metrics.registry.register(Box::new(metrics.graph_import.clone()))?;
+        Ok(metrics)
+    }
+
+    /// Registry to mount on the metrics exporter
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Render all reputation metrics in the Prometheus text format
+    pub fn encode_text(&self) -> Result<String, ReputationError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(ReputationError::MetricsError)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
 }
 
 impl ReputationEngine {
     pub async fn new(db_uri: &str, alpha: f64) -> Result<Self, ReputationError> {
-        let (client, connection) = tokio_postgres::connect(db_uri, NoTls).await?;
-        tokio::spawn(async move { connection.await });
-        
+        Self::with_db_config(DbConfig::new(db_uri), alpha).await
+    }
+
+    /// Engine backed by a supervised connection with optional replica failover
+    pub async fn with_db_config(config: DbConfig, alpha: f64) -> Result<Self, ReputationError> {
+        let db = DbSupervisor::connect(config).await?;
+        db.client().await?.batch_execute(SCHEMA_MIGRATIONS).await?;
+
         Ok(Self {
             nodes: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
-            db_client: client,
+            db,
             alpha,
+            metrics: ReputationMetrics::register().map_err(ReputationError::MetricsError)?,
+            clock: Arc::new(SystemClock),
+            skew: SkewPolicy::default(),
+            revocations: None,
+            decay_half_life: DEFAULT_DECAY_HALF_LIFE,
+            retired_key_grace: DEFAULT_RETIRED_KEY_GRACE,
+            incremental: Mutex::new(IncrementalState::default()),
+            snapshot: ArcSwap::from_pointee(TrustSnapshot::default()),
+            updates: broadcast::channel(UPDATE_EVENT_CAPACITY).0,
         })
     }
 
+    /// Replace the wall clock used to timestamp and decay trust
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Refuse interactions timestamped further from the local clock than
+    /// `skew` allows
+    pub fn with_skew_policy(mut self, skew: SkewPolicy) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Refuse interactions signed with a revoked key
+    pub fn with_revocations(mut self, revocations: Arc<RevocationList>) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    pub fn with_decay_half_life(mut self, half_life: Duration) -> Self {
+        self.decay_half_life = half_life;
+        self
+    }
+
+    /// Accept interactions signed by a key retired up to `grace` ago,
+    /// instead of the default minute
+    pub fn with_retired_key_grace(mut self, grace: Duration) -> Self {
+        self.retired_key_grace = grace;
+        self
+    }
+
+    /// Metrics handle for exporter wiring
+    pub fn metrics(&self) -> &ReputationMetrics {
+        &self.metrics
+    }
+
+    /// Node's current global trust and when it was last computed
+    ///
+    /// Served from the published snapshot, so it never waits on an update.
+    pub async fn global_trust(&self, node_id: &str) -> Option<(f64, SystemTime)> {
+        self.snapshot.load().scores.get(node_id).copied()
+    }
+
+    /// The `n` most trusted nodes, highest first, from one snapshot
+    pub fn top_n(&self, n: usize) -> Vec<(String, f64)> {
+        self.snapshot.load().top_n(n)
+    }
+
+    /// Events for every trust update started after subscribing
+    pub fn subscribe_updates(&self) -> broadcast::Receiver<TrustUpdateEvent> {
+        self.updates.subscribe()
+    }
+
+    /// Scores exactly as left by the last completed update
+    pub fn trust_snapshot(&self) -> Arc<TrustSnapshot> {
+        self.snapshot.load_full()
+    }
+
+    /// Publish the scores in `nodes`; called with the write lock still held
+    /// so the snapshot reflects exactly one update
+    fn publish_snapshot(&self, nodes: &HashMap<String, Node>) {
+        let scores = nodes.iter()
+            .map(|(id, node)| (id.clone(), (node.global_trust, node.last_updated)))
+            .collect();
+        let generation = self.snapshot.load().generation + 1;
+        self.snapshot.store(Arc::new(TrustSnapshot { generation, scores }));
+    }
+
     pub async fn initialize_trust(&self) -> Result<(), ReputationError> {
         let mut nodes = self.nodes.write().await;
-        let rows = self.db_client.query("SELECT id, public_key, trust_data FROM nodes", &[]).await?;
+        let rows = self.db.client().await?
+            .query("SELECT id, public_key, trust_data, key_history FROM nodes", &[])
+            .await?;
         
         for row in rows {
             let id: String = row.get(0);
             let public_key: Vec<u8> = row.get(1);
             let trust_data: Vec<u8> = row.get(2);
-            
+            let key_history: Option<Vec<u8>> = row.get(3);
+
+            // Rows written before key rotation carry only the one key
+            let keys = match key_history {
+                Some(history) => bincode::deserialize(&history)?,
+                None => KeyHistory::legacy(PublicKey::from_bytes(&public_key)?),
+            };
             nodes.insert(id.clone(), Node {
                 id,
-                public_key: PublicKey::from_bytes(&public_key)?,
+                keys,
                 local_trust: bincode::deserialize(&trust_data)?,
                 global_trust: 1.0,
-                last_updated: SystemTime::now(),
+                last_updated: self.clock.now(),
             });
         }
+        self.publish_snapshot(&nodes);
+
+        // Membership may have changed, so the next update starts from scratch
+        self.incremental.lock().unwrap_or_else(|e| e.into_inner()).runs_since_full = None;
         Ok(())
     }
 
-    pub async fn update_trust(&self) -> Result<(), ReputationError> {
+    pub async fn update_trust(&self) -> Result<ConvergenceReport, ReputationError> {
+        let started = Instant::now();
+        let _ = self.updates.send(TrustUpdateEvent::Started { incremental: false });
         let nodes = self.nodes.read().await;
-        let mut prev_global = HashMap::new();
-        let mut current_global = nodes.iter()
+        let stored: HashMap<_, _> = nodes.iter()
             .map(|(id, node)| (id.clone(), node.global_trust))
-            .collect::<HashMap<_, _>>();
+            .collect();
+        let result = converge(&nodes, self.alpha, stored.clone(), None);
 
-        for _ in 0..MAX_ITERATIONS {
-            prev_global = current_global.clone();
-            current_global = self.compute_global_trust(¤t_global).await?;
-            
-            let delta = prev_global.iter()
-                .map(|(k, v)| (current_global[k] - v).abs())
-                .fold(0.0, f64::max);
-                
-            if delta < CONVERGENCE_THRESHOLD {
-                break;
+        // Cleared under the read lock so no interaction slips between runs
+        let mut state = self.incremental.lock().unwrap_or_else(|e| e.into_inner());
+        state.dirty.clear();
+        state.raw = result.raw.clone();
+        state.bias = stored;
+        state.runs_since_full = Some(0);
+        drop(state);
+        drop(nodes);
+
+        self.commit_trust(result, false, started).await
+    }
+
+    /// Re-converge only around nodes whose local trust, or stored global
+    /// trust, changed since their score was last computed, seeded from the
+    /// previous global scores
+    ///
+    /// Falls back to `update_trust` before the first full run, after
+    /// `initialize_trust`, and every `FULL_RECOMPUTE_INTERVAL` updates to
+    /// correct accumulated drift.
+    pub async fn update_trust_incremental(&self) -> Result<ConvergenceReport, ReputationError> {
+        let started = Instant::now();
+        let nodes = self.nodes.read().await;
+        let warm = {
+            let mut state = self.incremental.lock().unwrap_or_else(|e| e.into_inner());
+            match state.runs_since_full {
+                Some(runs) if runs + 1 < FULL_RECOMPUTE_INTERVAL && state.raw.len() == nodes.len() => {
+                    state.runs_since_full = Some(runs + 1);
+                    let mut changed = std::mem::take(&mut state.dirty);
+                    for (id, node) in nodes.iter() {
+                        let unmoved = state.bias.get(id)
+                            .is_some_and(|bias| (node.global_trust - bias).abs() < CONVERGENCE_THRESHOLD);
+                        if !unmoved {
+                            changed.insert(id.clone());
+                        }
+                    }
+                    for id in &changed {
+                        if let Some(node) = nodes.get(id) {
+                            state.bias.insert(id.clone(), node.global_trust);
+                        }
+                    }
+                    Some((state.raw.clone(), changed))
+                }
+                _ => None,
             }
+        };
+        let Some((raw, changed)) = warm else {
+            drop(nodes);
+            return self.update_trust().await;
+        };
+        let _ = self.updates.send(TrustUpdateEvent::Started { incremental: true });
+
+        let seed = normalize_trust(&nodes.iter()
+            .map(|(id, node)| (id.clone(), node.global_trust))
+            .collect());
+        let result = converge(&nodes, self.alpha, seed, Some((raw, &changed)));
+        self.incremental.lock().unwrap_or_else(|e| e.into_inner()).raw = result.raw.clone();
+        drop(nodes);
+
+        self.commit_trust(result, true, started).await
+    }
+
+    /// Publish convergence metrics, store the new scores and persist them
+    async fn commit_trust(
+        &self,
+        result: Convergence,
+        incremental: bool,
+        started: Instant,
+    ) -> Result<ConvergenceReport, ReputationError> {
+        let delta = result.delta;
+        let report = ConvergenceReport {
+            iterations: result.iterations,
+            final_delta: delta,
+            nodes: result.trust.len(),
+            converged: delta < CONVERGENCE_THRESHOLD,
+            incremental,
+            duration: Duration::ZERO,
+        };
+        self.metrics.iterations.set(result.iterations as i64);
+        self.metrics.final_delta.set(if delta.is_finite() { delta } else { 0.0 });
+        self.metrics.nodes.set(result.trust.len() as i64);
+        if delta >= CONVERGENCE_THRESHOLD {
+            self.metrics.nonconvergence.inc();
         }
 
+        let now = self.clock.now();
         let mut nodes = self.nodes.write().await;
-        for (id, trust) in current_global {
+        for (id, trust) in result.trust {
             if let Some(node) = nodes.get_mut(&id) {
                 node.global_trust = trust;
+                node.last_updated = now;
             }
         }
+        self.publish_snapshot(&nodes);
+        drop(nodes);
 
-        self.persist_trust().await
+        if let Err(e) = self.persist_trust().await {
+            let _ = self.updates.send(TrustUpdateEvent::Failed(e.to_string()));
+            return Err(e);
+        }
+        let report = ConvergenceReport { duration: started.elapsed(), ..report };
+        let _ = self.updates.send(TrustUpdateEvent::Completed(report.clone()));
+        Ok(report)
     }
 
-    async fn compute_global_trust(&self, prev_trust: &HashMap<String, f64>) -> Result<HashMap<String, f64>, ReputationError> {
-        let nodes = self.nodes.read().await;
-        let new_trust: HashMap<String, f64> = nodes.par_iter()
-            .map(|(node_id, node)| {
-                let weighted_sum = node.local_trust.iter()
-                    .map(|(neighbor_id, local)| {
-                        let global = prev_trust.get(neighbor_id).copied().unwrap_or(0.0);
-                        local * global
-                    })
-                    .sum::<f64>();
-                    
-                (node_id.clone(), self.alpha * weighted_sum + (1.0 - self.alpha) * node.global_trust)
-            })
-            .collect();
-
-        Ok(normalize_trust(&new_trust))
+    /// Halve each node's global trust per `decay_half_life` elapsed since it
+    /// was last updated
+    pub async fn decay_trust(&self) {
+        let now = self.clock.now();
+        let half_life = self.decay_half_life.as_secs_f64();
+        let mut nodes = self.nodes.write().await;
+        for node in nodes.values_mut() {
+            let elapsed = now.duration_since(node.last_updated).unwrap_or_default();
+            node.global_trust *= 0.5f64.powf(elapsed.as_secs_f64() / half_life);
+            node.last_updated = now;
+        }
+        self.publish_snapshot(&nodes);
     }
 
     async fn persist_trust(&self) -> Result<(), ReputationError> {
         let nodes = self.nodes.read().await;
-        let transaction = self.db_client.transaction().await?;
+        self.persist_nodes(&nodes, false).await
+    }
+
+    /// Upsert every node; `replace` first drops rows for nodes no longer held
+    async fn persist_nodes(&self, nodes: &HashMap<String, Node>, replace: bool) -> Result<(), ReputationError> {
+        let mut client = self.db.client_mut().await?;
+        let transaction = client.transaction().await?;
+        if replace {
+            transaction.execute("DELETE FROM nodes", &[]).await?;
+        }
 
         for (id, node) in nodes.iter() {
             let trust_data = bincode::serialize(&node.local_trust)?;
+            let key_history = bincode::serialize(&node.keys)?;
             transaction.execute(
-                "INSERT INTO nodes (id, public_key, trust_data, global_trust) 
-                 VALUES (\$1, \$2, \$3, \$4)
+                "INSERT INTO nodes (id, public_key, trust_data, global_trust, key_history) 
+                 VALUES (\$1, \$2, \$3, \$4, \$5)
                  ON CONFLICT (id) DO UPDATE SET 
+                     public_key = EXCLUDED.public_key,
                      trust_data = EXCLUDED.trust_data,
-                     global_trust = EXCLUDED.global_trust",
-                &[&id, &node.public_key.to_bytes().to_vec(), &trust_data, &node.global_trust]
+                     global_trust = EXCLUDED.global_trust,
+                     key_history = EXCLUDED.key_history",
+                &[&id, &node.keys.current().to_bytes().to_vec(), &trust_data, &node.global_trust, &key_history]
             ).await?;
         }
 
@@ -134,21 +488,57 @@ impl ReputationEngine {
         Ok(())
     }
 
+    /// Record `source_id`'s rating of `target_id`
+    ///
+    /// `signature` is over `interaction_message` and is checked against the
+    /// key the source held at `timestamp`, so interactions signed before a
+    /// rotation stay valid. `timestamp` must also be within the engine's
+    /// `SkewPolicy` of the local clock, so old signed ratings cannot be
+    /// replayed indefinitely. A key on the engine's revocation list is
+    /// refused with `KeyRevoked`, whenever the interaction was signed. The
+    /// timestamp is the signer's own claim, so a key retired by rotation is
+    /// refused with `KeyRetired` once the retired key grace period has
+    /// passed, and cannot go on signing interactions backdated into its
+    /// period.
     pub async fn add_interaction(
         &self,
         source_id: &str,
         target_id: &str,
         score: f64,
+        timestamp: SystemTime,
         signature: &Signature
     ) -> Result<(), ReputationError> {
         let mut nodes = self.nodes.write().await;
-        let source = nodes.get(source_id)
-            .ok_or(ReputationError::NodeNotFound)?;
+        let Some(source) = nodes.get(source_id) else {
+            self.metrics.interactions.with_label_values(&["unknown_node"]).inc();
+            return Err(ReputationError::NodeNotFound);
+        };
+
+        if let Err(e) = self.skew.check(timestamp, self.clock.now()) {
+            self.metrics.interactions.with_label_values(&["clock_skew"]).inc();
+            return Err(e.into());
+        }
 
-        source.public_key.verify(
-            format!("{}{}{}", source_id, target_id, score).as_bytes(),
-            signature
-        )?;
+        let entry = source.keys.entry_at(timestamp);
+        let now = self.clock.now();
+        if entry.and_then(|entry| entry.valid_to).is_some_and(|retired| retired + self.retired_key_grace < now) {
+            self.metrics.interactions.with_label_values(&["retired_key"]).inc();
+            return Err(ReputationError::KeyRetired);
+        }
+        let key = entry.map(|entry| &entry.key);
+        if key.is_some_and(|key| self.is_revoked(key)) {
+            self.metrics.interactions.with_label_values(&["revoked_key"]).inc();
+            return Err(ReputationError::KeyRevoked);
+        }
+
+        let verified = match key {
+            Some(key) => key.verify(&interaction_message(source_id, target_id, score, timestamp), signature),
+            None => Err(ed25519_dalek::SignatureError::new()),
+        };
+        if let Err(e) = verified {
+            self.metrics.interactions.with_label_values(&["invalid_signature"]).inc();
+            return Err(e.into());
+        }
 
         let entry = nodes.get_mut(source_id)
             .ok_or(ReputationError::NodeNotFound)?
@@ -157,8 +547,106 @@ impl ReputationEngine {
             .or_insert(0.0);
             
         *entry = (*entry + score).max(0.0).min(1.0);
+        self.incremental.lock().unwrap_or_else(|e| e.into_inner()).dirty.insert(source_id.to_string());
+        self.metrics.interactions.with_label_values(&["accepted"]).inc();
         Ok(())
     }
+
+    fn is_revoked(&self, key: &PublicKey) -> bool {
+        self.revocations.as_ref().is_some_and(|revocations| revocations.is_revoked(&key_id(key.as_bytes())))
+    }
+}
+
+impl From<tokio_postgres::Error> for ReputationError {
+    /// A closed connection is an outage, not a query failure
+    fn from(e: tokio_postgres::Error) -> Self {
+        if e.is_closed() {
+            ReputationError::Unavailable
+        } else {
+            ReputationError::DbError(e)
+        }
+    }
+}
+
+/// Power iteration towards the trust fixed point
+///
+/// Each node scores `alpha * Σ local * trust(neighbor) + (1 - alpha) * stored`,
+/// where `stored` is its global trust before this update.
+/// A cold run sweeps every node each iteration. A warm run starts from the
+/// previous unnormalized scores, recomputes only the changed nodes, and then
+/// only the dependents of nodes that moved by at least the threshold.
+fn converge(
+    nodes: &HashMap<String, Node>,
+    alpha: f64,
+    seed: HashMap<String, f64>,
+    warm: Option<(HashMap<String, f64>, &HashSet<String>)>,
+) -> Convergence {
+    let (mut raw, mut active, dependents) = match warm {
+        Some((raw, changed)) => (raw, Some(changed.clone()), Some(dependents(nodes))),
+        None => (HashMap::with_capacity(nodes.len()), None, None),
+    };
+    let mut current = seed;
+    let mut iterations = 0;
+    let mut delta = f64::INFINITY;
+
+    while iterations < MAX_ITERATIONS {
+        raw.extend(raw_trust(nodes, alpha, &current, active.as_ref()));
+        let next = normalize_trust(&raw);
+        iterations += 1;
+
+        delta = next.iter()
+            .map(|(k, v)| (v - current[k]).abs())
+            .fold(0.0, f64::max);
+
+        if let Some(dependents) = &dependents {
+            active = Some(next.iter()
+                .filter(|(k, v)| (*v - current[*k]).abs() >= CONVERGENCE_THRESHOLD)
+                .filter_map(|(k, _)| dependents.get(k))
+                .flatten()
+                .cloned()
+                .collect());
+        }
+        current = next;
+
+        if delta < CONVERGENCE_THRESHOLD {
+            break;
+        }
+    }
+
+    Convergence { trust: current, raw, iterations, delta }
+}
+
+/// Unnormalized scores for `active` nodes, or every node when `None`
+fn raw_trust(
+    nodes: &HashMap<String, Node>,
+    alpha: f64,
+    trust: &HashMap<String, f64>,
+    active: Option<&HashSet<String>>,
+) -> Vec<(String, f64)> {
+    let score = |node: &Node| {
+        let weighted_sum = node.local_trust.iter()
+            .map(|(neighbor_id, local)| local * trust.get(neighbor_id).copied().unwrap_or(0.0))
+            .sum::<f64>();
+        alpha * weighted_sum + (1.0 - alpha) * node.global_trust
+    };
+
+    match active {
+        None => nodes.par_iter().map(|(id, node)| (id.clone(), score(node))).collect(),
+        Some(active) => active.iter()
+            .filter_map(|id| nodes.get(id).map(|node| (id.clone(), score(node))))
+            .collect(),
+    }
+}
+
+/// For each node, the nodes whose score reads it
+fn dependents(nodes: &HashMap<String, Node>) -> HashMap<String, Vec<String>> {
+    let mut reverse: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, node) in nodes {
+        for neighbor_id in node.local_trust.keys() {
+            reverse.entry(neighbor_id.clone()).or_default().push(id.clone());
+        }
+    }
+    reverse
 }
 
 fn normalize_trust(trust_scores: &HashMap<String, f64>) -> HashMap<String, f64> {
@@ -177,13 +665,25 @@ fn normalize_trust(trust_scores: &HashMap<String, f64>) -> HashMap<String, f64>
 #[derive(Debug, thiserror::Error)]
 pub enum ReputationError {
     #[error("Database connection failed")]
-    DbError(#[from] tokio_postgres::Error),
+    DbError(#[source] tokio_postgres::Error),
+    #[error("Reputation database unavailable")]
+    Unavailable,
     #[error("Serialization failed")]
     SerializationError(#[from] bincode::Error),
     #[error("Invalid cryptographic operation")]
     CryptoError(#[from] ed25519_dalek::SignatureError),
     #[error("Node not found in registry")]
     NodeNotFound,
+    #[error("Metrics registration failed")]
+    MetricsError(#[source] prometheus::Error),
+    #[error("Unsupported trust graph format version {0}")]
+    UnsupportedFormat(u32),
+    #[error("Interaction timestamp out of tolerance: {0}")]
+    ClockSkew(#[from] SkewError),
+    #[error("Signing key has been revoked")]
+    KeyRevoked,
+    #[error("Signing key was retired by a rotation")]
+    KeyRetired,
 }
 
 #[cfg(test)]
@@ -223,9 +723,236 @@ mod tests {
             "malicious1",
             "malicious2",
             1.0,
+            SystemTime::now(),
             &keypair.sign(b"fake")
         ).await;
         
         assert!(matches!(result, Err(ReputationError::NodeNotFound)));
     }
+
+    #[tokio::test]
+    async fn test_unknown_node_interactions_are_counted() {
+        let keypair = Keypair::generate(&mut rand::rngs::OsRng);
+        let engine = test_setup().await;
+
+        let result = engine.add_interaction("unknown-a", "unknown-b", 1.0, SystemTime::now(), &keypair.sign(b"fake")).await;
+        assert!(matches!(result, Err(ReputationError::NodeNotFound)));
+        assert_eq!(engine.metrics.interactions.with_label_values(&["unknown_node"]).get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_exports_convergence_metrics() {
+        let engine = test_setup().await;
+        engine.update_trust().await.unwrap();
+
+        let node_count = engine.nodes.read().await.len() as i64;
+        let iterations = engine.metrics.iterations.get();
+        assert!(iterations >= 1 && iterations <= MAX_ITERATIONS as i64);
+        assert_eq!(engine.metrics.nodes.get(), node_count);
+
+        let text = engine.metrics.encode_text().unwrap();
+        assert!(text.contains("reputation_iterations"));
+        assert!(text.contains("reputation_nodes"));
+    }
+
+    #[tokio::test]
+    async fn test_trust_decays_with_clock() {
+        let clock = Arc::new(nuzon_core::clock::MockClock::default());
+        let half_life = Duration::from_secs(3600);
+        let engine = ReputationEngine::new("host=localhost user=postgres", 0.85)
+            .await
+            .unwrap()
+            .with_clock(clock.clone())
+            .with_decay_half_life(half_life);
+
+        let keypair = Keypair::generate(&mut rand::rngs::OsRng);
+        engine.nodes.write().await.insert("idle".into(), Node {
+            id: "idle".into(),
+            keys: KeyHistory::legacy(keypair.public),
+            local_trust: BTreeMap::new(),
+            global_trust: 0.8,
+            last_updated: clock.now(),
+        });
+
+        engine.decay_trust().await;
+        assert_eq!(engine.nodes.read().await["idle"].global_trust, 0.8);
+
+        clock.advance(half_life);
+        engine.decay_trust().await;
+        assert!((engine.nodes.read().await["idle"].global_trust - 0.4).abs() < 1e-12);
+
+        clock.advance(half_life * 2);
+        engine.decay_trust().await;
+        assert!((engine.nodes.read().await["idle"].global_trust - 0.1).abs() < 1e-12);
+    }
+
+    fn graph(edges: &[(&str, &str, f64)]) -> HashMap<String, Node> {
+        let mut nodes = HashMap::new();
+        for (source, target, score) in edges {
+            for id in [source, target] {
+                nodes.entry(id.to_string()).or_insert_with(|| Node {
+                    id: id.to_string(),
+                    keys: KeyHistory::legacy(Keypair::generate(&mut rand::rngs::OsRng).public),
+                    local_trust: BTreeMap::new(),
+                    global_trust: 1.0,
+                    last_updated: SystemTime::UNIX_EPOCH,
+                });
+            }
+            nodes.get_mut(*source).unwrap().local_trust.insert(target.to_string(), *score);
+        }
+        nodes
+    }
+
+    fn uniform(nodes: &HashMap<String, Node>) -> HashMap<String, f64> {
+        nodes.keys().map(|id| (id.clone(), 1.0 / nodes.len() as f64)).collect()
+    }
+
+    #[test]
+    fn test_incremental_matches_full_recompute() {
+        // Low alpha contracts quickly, so both runs stop well inside the
+        // threshold of the shared fixed point
+        let alpha = 0.2;
+        let mut nodes = graph(&[
+            ("a", "b", 0.9), ("b", "c", 0.7), ("c", "a", 0.4), ("c", "d", 0.8),
+            ("d", "e", 0.6), ("e", "f", 0.5), ("f", "d", 0.3), ("a", "f", 0.2),
+        ]);
+        let before = converge(&nodes, alpha, uniform(&nodes), None);
+
+        nodes.get_mut("e").unwrap().local_trust.insert("a".into(), 1.0);
+        let changed: HashSet<String> = ["e".to_string()].into();
+
+        let full = converge(&nodes, alpha, uniform(&nodes), None);
+        let incremental = converge(&nodes, alpha, before.trust.clone(), Some((before.raw, &changed)));
+
+        assert!(full.delta < CONVERGENCE_THRESHOLD);
+        assert!(incremental.delta < CONVERGENCE_THRESHOLD);
+        for (id, trust) in &full.trust {
+            assert!(
+                (incremental.trust[id] - trust).abs() < CONVERGENCE_THRESHOLD,
+                "{}: incremental {} vs full {}", id, incremental.trust[id], trust
+            );
+        }
+        assert!(incremental.iterations <= full.iterations);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reads_never_see_partial_update() {
+        let clock = Arc::new(nuzon_core::clock::MockClock::default());
+        let engine = Arc::new(
+            ReputationEngine::new("host=localhost user=postgres", 0.85)
+                .await
+                .unwrap()
+                .with_clock(clock.clone()),
+        );
+        *engine.nodes.write().await = graph(&[
+            ("snap-a", "snap-b", 0.9), ("snap-b", "snap-c", 0.7), ("snap-c", "snap-a", 0.4),
+            ("snap-c", "snap-d", 0.8), ("snap-d", "snap-a", 0.6),
+        ]);
+        engine.update_trust().await.unwrap();
+
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let readers: Vec<_> = (0..8).map(|_| {
+            let (engine, done) = (engine.clone(), done.clone());
+            tokio::spawn(async move {
+                let mut last_generation = 0;
+                while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                    let snapshot = engine.trust_snapshot();
+                    assert!(snapshot.generation >= last_generation);
+                    last_generation = snapshot.generation;
+
+                    // One update stamps every node with the same time and
+                    // leaves normalized scores
+                    let stamps: HashSet<_> = snapshot.scores.values().map(|(_, at)| *at).collect();
+                    assert_eq!(stamps.len(), 1, "mixed generations: {:?}", snapshot.scores);
+                    let total: f64 = snapshot.scores.values().map(|(trust, _)| trust).sum();
+                    assert!((total - 1.0).abs() < 1e-9, "partial scores sum to {}", total);
+                    assert!(engine.global_trust("snap-a").await.is_some());
+                    tokio::task::yield_now().await;
+                }
+            })
+        }).collect();
+
+        for _ in 0..20 {
+            clock.advance(Duration::from_secs(1));
+            engine.update_trust().await.unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        for reader in readers {
+            reader.await.unwrap();
+        }
+        assert_eq!(engine.top_n(1).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_interactions_outside_skew_tolerance_rejected() {
+        let clock = Arc::new(nuzon_core::clock::MockClock::default());
+        let skew = SkewPolicy { max_future: Duration::from_secs(5), max_past: Duration::from_secs(30) };
+        let engine = ReputationEngine::new("host=localhost user=postgres", 0.85)
+            .await
+            .unwrap()
+            .with_clock(clock.clone())
+            .with_skew_policy(skew);
+        let key = Keypair::generate(&mut rand::rngs::OsRng);
+        let mut nodes = graph(&[("skew-a", "skew-b", 0.5)]);
+        nodes.get_mut("skew-a").unwrap().keys = KeyHistory::legacy(key.public);
+        *engine.nodes.write().await = nodes;
+
+        let now = clock.now();
+        let ms = Duration::from_millis(1);
+        for (at, accepted) in [
+            (now + skew.max_future, true),
+            (now - skew.max_past, true),
+            (now + skew.max_future + ms, false),
+            (now - skew.max_past - ms, false),
+        ] {
+            let signature = key.sign(&interaction_message("skew-a", "skew-b", 0.1, at));
+            match engine.add_interaction("skew-a", "skew-b", 0.1, at, &signature).await {
+                Ok(()) => assert!(accepted, "accepted interaction at {:?}", at),
+                Err(ReputationError::ClockSkew(_)) => assert!(!accepted, "refused interaction at {:?}", at),
+                Err(e) => panic!("unexpected {:?}", e),
+            }
+        }
+        assert_eq!(engine.metrics.interactions.with_label_values(&["clock_skew"]).get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_interactions_signed_with_revoked_key_rejected() {
+        use nuzon_core::coordination::ReplicatedStateMachine;
+        let revocations = Arc::new(RevocationList::attach(Arc::new(ReplicatedStateMachine::new())).await);
+        let engine = ReputationEngine::new("host=localhost user=postgres", 0.85)
+            .await
+            .unwrap()
+            .with_revocations(revocations.clone());
+        let key = Keypair::generate(&mut rand::rngs::OsRng);
+        let mut nodes = graph(&[("revoked-a", "revoked-b", 0.5)]);
+        nodes.get_mut("revoked-a").unwrap().keys = KeyHistory::legacy(key.public);
+        *engine.nodes.write().await = nodes;
+
+        let interact = |score: f64| {
+            let at = SystemTime::now();
+            let signature = key.sign(&interaction_message("revoked-a", "revoked-b", score, at));
+            let engine = &engine;
+            async move { engine.add_interaction("revoked-a", "revoked-b", score, at, &signature).await }
+        };
+        interact(0.1).await.unwrap();
+
+        revocations.revoke(&key_id(key.public.as_bytes()), "key compromised", 0).await.unwrap();
+        assert!(matches!(interact(0.1).await, Err(ReputationError::KeyRevoked)));
+        assert_eq!(engine.metrics.interactions.with_label_values(&["revoked_key"]).get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recovers_from_dropped_connection() {
+        let engine = test_setup().await;
+
+        // Kill the backend serving this session out from under the client
+        {
+            let client = engine.db.client().await.unwrap();
+            let _ = client.execute("SELECT pg_terminate_backend(pg_backend_pid())", &[]).await;
+        }
+
+        // The next query waits for the supervisor to reconnect and succeeds
+        engine.initialize_trust().await.unwrap();
+        assert!(!engine.db.client().await.unwrap().is_closed());
+    }
 }
@@ -0,0 +1,238 @@
+// graph.rs - Bulk Trust Graph Import and Export
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::SystemTime,
+};
+
+use ed25519_dalek::PublicKey;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{KeyEntry, KeyHistory, Node, ReputationEngine, ReputationError};
+
+/// Bumped whenever `GraphExport` changes shape
+const GRAPH_FORMAT_VERSION: u32 = 2;
+
+/// Serialized form of a whole trust graph
+#[derive(Debug, Serialize, Deserialize)]
+struct GraphExport {
+    version: u32,
+    nodes: Vec<NodeRecord>,
+}
+
+/// One node as exported
+///
+/// Records are validated one at a time after the export decodes, so a
+/// record with an invalid key, id or score can be skipped without rejecting
+/// the rest of the graph. Bytes that do not decode reject the whole export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NodeRecord {
+    id: String,
+    keys: Vec<KeyRecord>,
+    local_trust: BTreeMap<String, f64>,
+    global_trust: f64,
+    last_updated: SystemTime,
+}
+
+/// One entry of a node's key history as exported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyRecord {
+    public_key: Vec<u8>,
+    valid_from: SystemTime,
+    valid_to: Option<SystemTime>,
+}
+
+impl From<&Node> for NodeRecord {
+    fn from(node: &Node) -> Self {
+        Self {
+            id: node.id.clone(),
+            keys: node.keys.entries().iter().map(|entry| KeyRecord {
+                public_key: entry.key.to_bytes().to_vec(),
+                valid_from: entry.valid_from,
+                valid_to: entry.valid_to,
+            }).collect(),
+            local_trust: node.local_trust.clone(),
+            global_trust: node.global_trust,
+            last_updated: node.last_updated,
+        }
+    }
+}
+
+impl NodeRecord {
+    /// Node this record describes, or why it cannot be imported
+    fn into_node(self) -> Result<Node, String> {
+        if self.id.is_empty() {
+            return Err("empty node id".into());
+        }
+        let entries = self.keys.into_iter().map(|record| {
+            let key = PublicKey::from_bytes(&record.public_key)
+                .map_err(|e| format!("invalid public key: {}", e))?;
+            Ok(KeyEntry { key, valid_from: record.valid_from, valid_to: record.valid_to })
+        }).collect::<Result<_, String>>()?;
+        let keys = KeyHistory::from_entries(entries)?;
+        if !self.global_trust.is_finite() || self.global_trust < 0.0 {
+            return Err(format!("invalid global trust {}", self.global_trust));
+        }
+        if let Some((target, score)) = self.local_trust.iter()
+            .find(|(_, score)| !(0.0..=1.0).contains(*score))
+        {
+            return Err(format!("local trust {} towards {} outside [0, 1]", score, target));
+        }
+        Ok(Node {
+            id: self.id,
+            keys,
+            local_trust: self.local_trust,
+            global_trust: self.global_trust,
+            last_updated: self.last_updated,
+        })
+    }
+}
+
+impl ReputationEngine {
+    /// Serialize every node with its key history, local-trust edges and
+    /// global score
+    pub async fn export_graph(&self) -> Result<Vec<u8>, ReputationError> {
+        let nodes = self.nodes.read().await;
+        let mut records: Vec<NodeRecord> = nodes.values().map(NodeRecord::from).collect();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(bincode::serialize(&GraphExport { version: GRAPH_FORMAT_VERSION, nodes: records })?)
+    }
+
+    /// Load a graph produced by `export_graph`, returning the nodes imported
+    ///
+    /// With `merge`, imported nodes are added to the current graph: a known
+    /// node gains the imported edges and takes the imported score if that
+    /// is newer, but a node whose current key differs from the one already
+    /// registered is refused. Without it, the imported graph replaces the
+    /// current one, in memory and in the database.
+    ///
+    /// The import is all-or-nothing: the resulting graph is written to the
+    /// database in one transaction before it replaces the in-memory graph,
+    /// so a failed write leaves both as they were. Entries with an invalid
+    /// public key, id or scores are skipped and counted under
+    /// `reputation_graph_import_total{outcome="skipped"}`.
+    pub async fn import_graph(&self, bytes: &[u8], merge: bool) -> Result<usize, ReputationError> {
+        let export: GraphExport = bincode::deserialize(bytes)?;
+        if export.version != GRAPH_FORMAT_VERSION {
+            return Err(ReputationError::UnsupportedFormat(export.version));
+        }
+
+        // Held until the swap, so no update lands on the graph being replaced
+        let mut nodes = self.nodes.write().await;
+        let mut graph = if merge { nodes.clone() } else { HashMap::new() };
+        let (mut imported, mut skipped) = (0, 0);
+        for record in export.nodes {
+            let id = record.id.clone();
+            let node = match record.into_node() {
+                Ok(node) => node,
+                Err(reason) => {
+                    warn!(node = %id, %reason, "Skipping malformed trust graph entry");
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            match graph.get_mut(&node.id) {
+                Some(existing) if existing.keys.current() != node.keys.current() => {
+                    warn!(node = %id, "Skipping trust graph entry with a conflicting public key");
+                    skipped += 1;
+                    continue;
+                }
+                Some(existing) => {
+                    existing.local_trust.extend(node.local_trust);
+                    if node.last_updated > existing.last_updated {
+                        existing.global_trust = node.global_trust;
+                        existing.last_updated = node.last_updated;
+                    }
+                }
+                None => {
+                    graph.insert(node.id.clone(), node);
+                }
+            }
+            imported += 1;
+        }
+
+        self.persist_nodes(&graph, !merge).await?;
+        *nodes = graph;
+        self.metrics.nodes.set(nodes.len() as i64);
+        self.publish_snapshot(&nodes);
+        drop(nodes);
+
+        // The graph changed wholesale, so the next update starts from scratch
+        self.incremental.lock().unwrap_or_else(|e| e.into_inner()).runs_since_full = None;
+        self.metrics.graph_import.with_label_values(&["imported"]).inc_by(imported as u64);
+        self.metrics.graph_import.with_label_values(&["skipped"]).inc_by(skipped);
+        info!(imported, skipped, merge, "Trust graph imported");
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Keypair;
+
+    fn node(id: &str, edges: &[(&str, f64)], global_trust: f64) -> Node {
+        Node {
+            id: id.into(),
+            keys: KeyHistory::legacy(Keypair::generate(&mut rand::rngs::OsRng).public),
+            local_trust: edges.iter().map(|(target, score)| (target.to_string(), *score)).collect(),
+            global_trust,
+            last_updated: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    async fn engine() -> ReputationEngine {
+        ReputationEngine::new("host=localhost user=postgres", 0.85).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_graph_round_trips_into_fresh_engine() {
+        let source = engine().await;
+        {
+            let mut nodes = source.nodes.write().await;
+            for node in [
+                node("graph-a", &[("graph-b", 0.9), ("graph-c", 0.1)], 0.5),
+                node("graph-b", &[("graph-c", 0.7)], 0.3),
+                node("graph-c", &[], 0.2),
+            ] {
+                nodes.insert(node.id.clone(), node);
+            }
+        }
+        let bytes = source.export_graph().await.unwrap();
+
+        let target = engine().await;
+        assert_eq!(target.import_graph(&bytes, true).await.unwrap(), 3);
+
+        let (expected, imported) = (source.nodes.read().await, target.nodes.read().await);
+        assert_eq!(imported.len(), expected.len());
+        for (id, node) in expected.iter() {
+            let copy = &imported[id];
+            assert_eq!(copy.keys, node.keys);
+            assert_eq!(copy.local_trust, node.local_trust);
+            assert_eq!(copy.global_trust, node.global_trust);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_entries_are_skipped_and_counted() {
+        let good = NodeRecord::from(&node("graph-good", &[("graph-bad-key", 0.4)], 0.6));
+        let mut bad_key = NodeRecord { id: "graph-bad-key".into(), ..good.clone() };
+        bad_key.keys[0].public_key = vec![0xff; 7];
+        let bad_score = NodeRecord {
+            id: "graph-bad-score".into(),
+            local_trust: [("graph-good".to_string(), 4.0)].into(),
+            ..good.clone()
+        };
+        let bytes = bincode::serialize(&GraphExport {
+            version: GRAPH_FORMAT_VERSION,
+            nodes: vec![good, bad_key, bad_score],
+        }).unwrap();
+
+        let engine = engine().await;
+        let skipped_before = engine.metrics.graph_import.with_label_values(&["skipped"]).get();
+        assert_eq!(engine.import_graph(&bytes, true).await.unwrap(), 1);
+        assert_eq!(engine.metrics.graph_import.with_label_values(&["skipped"]).get(), skipped_before + 2);
+        assert!(engine.nodes.read().await.contains_key("graph-good"));
+    }
+}
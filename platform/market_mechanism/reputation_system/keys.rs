@@ -0,0 +1,238 @@
+// keys.rs - Node Signing Keys Across Rotations
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{ReputationEngine, ReputationError};
+
+/// One signing key and the period it was in use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyEntry {
+    pub key: PublicKey,
+    pub valid_from: SystemTime,
+    /// `None` for the current key
+    pub valid_to: Option<SystemTime>,
+}
+
+impl KeyEntry {
+    fn covers(&self, at: SystemTime) -> bool {
+        self.valid_from <= at && self.valid_to.map_or(true, |to| at < to)
+    }
+}
+
+/// Every key a node has signed with, oldest first
+///
+/// Periods are contiguous: each key is valid until the next takes over,
+/// and only the last, current key is open-ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyHistory(Vec<KeyEntry>);
+
+impl KeyHistory {
+    /// History holding only `key`, in use since `valid_from`
+    pub fn new(key: PublicKey, valid_from: SystemTime) -> Self {
+        Self(vec![KeyEntry { key, valid_from, valid_to: None }])
+    }
+
+    /// History for a key that predates rotation and so covers all time
+    pub fn legacy(key: PublicKey) -> Self {
+        Self::new(key, UNIX_EPOCH)
+    }
+
+    /// Rebuild a history, or say why `entries` is not one
+    pub fn from_entries(entries: Vec<KeyEntry>) -> Result<Self, String> {
+        let Some(current) = entries.last() else {
+            return Err("no signing keys".into());
+        };
+        if current.valid_to.is_some() {
+            return Err("current key has an expiry".into());
+        }
+        for pair in entries.windows(2) {
+            if pair[0].valid_to != Some(pair[1].valid_from) || pair[1].valid_from < pair[0].valid_from {
+                return Err("key validity periods are not contiguous".into());
+            }
+        }
+        Ok(Self(entries))
+    }
+
+    pub fn current(&self) -> &PublicKey {
+        &self.0.last().expect("a key history is never empty").key
+    }
+
+    /// Key the node signed with at `at`, if it had one then
+    pub fn valid_at(&self, at: SystemTime) -> Option<&PublicKey> {
+        self.entry_at(at).map(|entry| &entry.key)
+    }
+
+    /// Entry of the key the node signed with at `at`
+    pub fn entry_at(&self, at: SystemTime) -> Option<&KeyEntry> {
+        self.0.iter().rev().find(|entry| entry.covers(at))
+    }
+
+    pub fn entries(&self) -> &[KeyEntry] {
+        &self.0
+    }
+
+    /// Retire the current key at `at` in favour of `key`
+    fn rotate(&mut self, key: PublicKey, at: SystemTime) {
+        let current = self.0.last_mut().expect("a key history is never empty");
+        // A clock behind the last rotation must not leave a gap or overlap
+        let at = at.max(current.valid_from);
+        current.valid_to = Some(at);
+        self.0.push(KeyEntry { key, valid_from: at, valid_to: None });
+    }
+}
+
+/// Bytes a node signs to report an interaction at `timestamp`
+///
+/// A domain tag, then each node id prefixed with its length, the score's
+/// exact bits and the timestamp in milliseconds, so no two interactions
+/// share an encoding and the bytes cannot be passed off as another message.
+pub fn interaction_message(source_id: &str, target_id: &str, score: f64, timestamp: SystemTime) -> Vec<u8> {
+    let millis = timestamp.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis());
+    let mut message = b"nuzon-interaction:v1".to_vec();
+    for id in [source_id, target_id] {
+        message.extend_from_slice(&(id.len() as u64).to_be_bytes());
+        message.extend_from_slice(id.as_bytes());
+    }
+    message.extend_from_slice(&score.to_bits().to_be_bytes());
+    message.extend_from_slice(&millis.to_be_bytes());
+    message
+}
+
+/// Bytes a node's current key signs to endorse `new_key` as its successor
+pub fn rotation_message(node_id: &str, new_key: &PublicKey) -> Vec<u8> {
+    [b"nuzon-key-rotation:".as_slice(), node_id.as_bytes(), b":", new_key.as_bytes()].concat()
+}
+
+impl ReputationEngine {
+    /// Make `new_key` the node's signing key from now on
+    ///
+    /// `signed_by_old` must be the current key's signature over
+    /// `rotation_message`. The old key stays valid for interactions
+    /// timestamped before the rotation, but only those that arrive within
+    /// the engine's retired key grace period of it.
+    pub async fn rotate_key(
+        &self,
+        node_id: &str,
+        new_key: PublicKey,
+        signed_by_old: &Signature,
+    ) -> Result<(), ReputationError> {
+        let mut nodes = self.nodes.write().await;
+        let node = nodes.get_mut(node_id).ok_or(ReputationError::NodeNotFound)?;
+        if let Err(e) = node.keys.current().verify(&rotation_message(node_id, &new_key), signed_by_old) {
+            warn!(node = node_id, "Rejected key rotation not endorsed by the current key");
+            return Err(e.into());
+        }
+        node.keys.rotate(new_key, self.clock.now());
+        info!(node = node_id, keys = node.keys.entries().len(), "Node signing key rotated");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use ed25519_dalek::{Keypair, Signer};
+    use super::super::{Node, DEFAULT_RETIRED_KEY_GRACE};
+
+    fn keypair() -> Keypair {
+        Keypair::generate(&mut rand::rngs::OsRng)
+    }
+
+    async fn engine_with(id: &str, key: &Keypair) -> (ReputationEngine, std::sync::Arc<nuzon_core::clock::MockClock>) {
+        let clock = std::sync::Arc::new(nuzon_core::clock::MockClock::default());
+        let engine = ReputationEngine::new("host=localhost user=postgres", 0.85)
+            .await
+            .unwrap()
+            .with_clock(clock.clone());
+        let now = clock.now();
+        let mut nodes = engine.nodes.write().await;
+        for id in [id, "keys-peer"] {
+            nodes.insert(id.into(), Node {
+                id: id.into(),
+                keys: KeyHistory::new(key.public, now),
+                local_trust: BTreeMap::new(),
+                global_trust: 1.0,
+                last_updated: now,
+            });
+        }
+        drop(nodes);
+        (engine, clock)
+    }
+
+    #[tokio::test]
+    async fn test_old_key_still_verifies_earlier_interactions() {
+        let (old, new) = (keypair(), keypair());
+        let (engine, clock) = engine_with("keys-rotated", &old).await;
+        let before = clock.now();
+
+        clock.advance(Duration::from_secs(60));
+        let endorsement = old.sign(&rotation_message("keys-rotated", &new.public));
+        engine.rotate_key("keys-rotated", new.public, &endorsement).await.unwrap();
+        clock.advance(Duration::from_secs(10));
+        let after = clock.now();
+
+        let sign = |key: &Keypair, at| key.sign(&interaction_message("keys-rotated", "keys-peer", 0.5, at));
+        engine.add_interaction("keys-rotated", "keys-peer", 0.5, before, &sign(&old, before)).await.unwrap();
+        engine.add_interaction("keys-rotated", "keys-peer", 0.5, after, &sign(&new, after)).await.unwrap();
+
+        // Each key is only good for its own period
+        assert!(engine.add_interaction("keys-rotated", "keys-peer", 0.5, after, &sign(&old, after)).await.is_err());
+        assert!(engine.add_interaction("keys-rotated", "keys-peer", 0.5, before, &sign(&new, before)).await.is_err());
+
+        // Once the grace period has passed, the retired key cannot sign
+        // backdated interactions that are still within clock skew
+        clock.advance(DEFAULT_RETIRED_KEY_GRACE);
+        let backdated = before + Duration::from_secs(30);
+        let result = engine.add_interaction("keys-rotated", "keys-peer", 0.5, backdated, &sign(&old, backdated)).await;
+        assert!(matches!(result, Err(ReputationError::KeyRetired)));
+    }
+
+    #[test]
+    fn test_interaction_fields_cannot_shift_between_each_other() {
+        let at = UNIX_EPOCH + Duration::from_secs(1);
+        assert_ne!(interaction_message("ab", "c", 0.5, at), interaction_message("a", "bc", 0.5, at));
+        assert_ne!(interaction_message("a", "b1", 0.5, at), interaction_message("a", "b", 10.5, at));
+        assert!(interaction_message("a", "b", 0.5, at).starts_with(b"nuzon-interaction:v1"));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_not_endorsed_by_current_key_is_rejected() {
+        let (owner, attacker) = (keypair(), keypair());
+        let (engine, _) = engine_with("keys-target", &owner).await;
+
+        let forged = attacker.sign(&rotation_message("keys-target", &attacker.public));
+        let result = engine.rotate_key("keys-target", attacker.public, &forged).await;
+        assert!(matches!(result, Err(ReputationError::CryptoError(_))));
+
+        // Endorsing a different successor does not authorize this one
+        let other = owner.sign(&rotation_message("keys-target", &keypair().public));
+        assert!(engine.rotate_key("keys-target", attacker.public, &other).await.is_err());
+        assert_eq!(engine.nodes.read().await["keys-target"].keys.current(), &owner.public);
+    }
+
+    #[test]
+    fn test_histories_must_be_contiguous() {
+        let (a, b) = (keypair().public, keypair().public);
+        let t0 = UNIX_EPOCH + Duration::from_secs(10);
+        let t1 = t0 + Duration::from_secs(10);
+
+        let mut history = KeyHistory::new(a, t0);
+        history.rotate(b, t1);
+        assert_eq!(KeyHistory::from_entries(history.entries().to_vec()), Ok(history.clone()));
+        assert_eq!(history.valid_at(t0), Some(&a));
+        assert_eq!(history.valid_at(t1), Some(&b));
+        assert_eq!(history.valid_at(UNIX_EPOCH), None);
+
+        let gap = vec![
+            KeyEntry { key: a, valid_from: t0, valid_to: Some(t0) },
+            KeyEntry { key: b, valid_from: t1, valid_to: None },
+        ];
+        assert!(KeyHistory::from_entries(gap).is_err());
+        assert!(KeyHistory::from_entries(Vec::new()).is_err());
+    }
+}
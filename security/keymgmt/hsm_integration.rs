@@ -4,30 +4,151 @@
 #![feature(async_fn_in_trait)]
 
 use std::{
+    cell::Cell,
+    collections::HashMap,
     path::Path,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
     time::{Duration, Instant},
 };
 use pkcs11::{
     types::{
         CInitializeArgs, CK_OBJECT_HANDLE, CK_SESSION_HANDLE, 
         Mechanism, MechanismType, Ulong,
+        CKR_DEVICE_ERROR, CKR_DEVICE_MEMORY, CKR_DEVICE_REMOVED, CKR_SESSION_CLOSED,
+        CKR_SESSION_HANDLE_INVALID, CKR_SIGNATURE_INVALID, CKR_SIGNATURE_LEN_RANGE, CKR_TOKEN_NOT_PRESENT,
     },
     Ctx,
 };
 use thiserror::Error;
-use tracing::{debug, error, info, instrument, warn};
-use prometheus::{register_int_counter_vec, IntCounterVec};
+use tracing::{debug, error, field::Empty, info, instrument, warn, Span};
+use prometheus::{register_histogram, register_int_counter_vec, Histogram, IntCounterVec};
+use sha2::{Digest, Sha256};
+use nuzon_core::telemetry::{self, RateLimitedLog};
+
+/// How long a slot that returned a device error is skipped
+const SLOT_RECOVERY_BACKOFF: Duration = Duration::from_secs(30);
+/// Interval over which identical operation failures share one log line
+const ERROR_LOG_WINDOW: Duration = Duration::from_secs(10);
+/// DER-encoded OID of the NIST P-256 curve (prime256v1)
+const P256_EC_PARAMS: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+/// Length of an uncompressed SEC1 P-256 point
+const P256_POINT_LEN: usize = 65;
 
 #[derive(Debug, Clone)]
 pub struct HsmConfig {
     lib_path: String,
-    pin: String,
-    slot: Ulong,
+    /// `(slot, pin)` per partition; partitions must hold replicas of the
+    /// same keys, which keygen copies to each one
+    slots: Vec<(Ulong, String)>,
+    /// Label every key this client manages is stored under
     key_label: String,
+    /// SHA-256 of the credential each permitted tenant authenticates with
+    tenant_credentials: HashMap<String, [u8; 32]>,
+    /// Label of the AES key, trusted on every partition, that generated
+    /// keys are wrapped under to copy them between slots
+    replication_key: Option<String>,
     operation_timeout: Duration,
 }
 
+impl HsmConfig {
+    /// Client for the PKCS#11 module at `lib_path`, logging in to each
+    /// `(slot, pin)` and managing keys under `key_label`; no tenant may use
+    /// keys until allowed
+    pub fn new(lib_path: impl Into<String>, key_label: impl Into<String>, slots: Vec<(Ulong, String)>) -> Self {
+        Self {
+            lib_path: lib_path.into(),
+            slots,
+            key_label: key_label.into(),
+            tenant_credentials: HashMap::new(),
+            replication_key: None,
+            operation_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Copy generated keys between slots by wrapping them under the AES key
+    /// stored as `label` on every partition
+    ///
+    /// Required to generate keys when more than one slot is configured. The
+    /// key must carry `CKA_TRUSTED`, which only the security officer can
+    /// set, since generated private keys may only be wrapped by trusted keys.
+    pub fn with_replication_key(mut self, label: impl Into<String>) -> Self {
+        self.replication_key = Some(label.into());
+        self
+    }
+
+    /// Permit a tenant, authenticating with `credential`, to use keys under
+    /// its namespace
+    pub fn allow_tenant(mut self, tenant_id: impl Into<String>, credential: impl AsRef<[u8]>) -> Self {
+        self.tenant_credentials.insert(tenant_id.into(), Sha256::digest(credential.as_ref()).into());
+        self
+    }
+}
+
+/// Source of client ids, so a principal is only honoured by its issuer
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Tenant a caller proved it acts for, issued by `HsmClient::authenticate`
+///
+/// Key operations take a principal rather than a tenant id, so access to a
+/// tenant's keys goes to whoever holds its credential, not to whoever names
+/// it. A principal is refused by every client but the one that issued it.
+#[derive(Debug, Clone)]
+pub struct TenantPrincipal {
+    tenant_id: String,
+    client_id: u64,
+}
+
+impl TenantPrincipal {
+    /// Tenant the principal acts for
+    pub fn tenant_id(&self) -> &str {
+        &self.tenant_id
+    }
+}
+
+/// Tenant-scoped key label namespace
+///
+/// Every key is stored under `{key_label}:tenant:{id}:{label}`, and a
+/// principal this client did not issue is refused before any PKCS#11 call
+/// is made.
+#[derive(Debug, Clone)]
+struct TenantScope {
+    client_id: u64,
+    key_label: String,
+    credentials: HashMap<String, [u8; 32]>,
+}
+
+impl TenantScope {
+    fn new(client_id: u64, key_label: String, credentials: HashMap<String, [u8; 32]>) -> Self {
+        Self { client_id, key_label, credentials }
+    }
+
+    /// Principal for `tenant_id` if `credential` is the one it was allowed with
+    fn authenticate(&self, tenant_id: &str, credential: &[u8]) -> Result<TenantPrincipal, HsmError> {
+        // A separator in the id would let one tenant alias another's namespace
+        if tenant_id.is_empty() || tenant_id.contains(':') {
+            return Err(HsmError::AuthError);
+        }
+        let presented: [u8; 32] = Sha256::digest(credential).into();
+        let expected = self.credentials.get(tenant_id).ok_or(HsmError::AuthError)?;
+        let difference = expected.iter().zip(&presented).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if difference != 0 {
+            return Err(HsmError::AuthError);
+        }
+        Ok(TenantPrincipal { tenant_id: tenant_id.to_string(), client_id: self.client_id })
+    }
+
+    /// Namespaced label for one of the principal's keys
+    fn scoped_label(&self, principal: &TenantPrincipal, label: &str) -> Result<String, HsmError> {
+        if principal.client_id != self.client_id || !self.credentials.contains_key(&principal.tenant_id) {
+            return Err(HsmError::AuthError);
+        }
+        Ok(format!("{}:tenant:{}:{}", self.key_label, principal.tenant_id, label))
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum HsmError {
     #[error("HSM initialization failed: {0}")]
@@ -42,13 +163,81 @@ pub enum HsmError {
     Timeout,
     #[error("Invalid configuration: {0}")]
     ConfigError(String),
+    #[error("HSM device error: {0}")]
+    DeviceError(String),
+    #[error("No healthy HSM slot available")]
+    NoHealthySlot,
+    #[error("Batch failed at message {index}: {source}")]
+    BatchFailed {
+        index: usize,
+        #[source]
+        source: Box<HsmError>,
+    },
+}
+
+/// Map a PKCS#11 failure, separating device faults that warrant failover
+fn pkcs11_error(e: pkcs11::errors::Error) -> HsmError {
+    match e {
+        pkcs11::errors::Error::Pkcs11(rv) if matches!(
+            rv,
+            CKR_DEVICE_ERROR | CKR_DEVICE_MEMORY | CKR_DEVICE_REMOVED
+                | CKR_TOKEN_NOT_PRESENT | CKR_SESSION_HANDLE_INVALID | CKR_SESSION_CLOSED
+        ) => HsmError::DeviceError(e.to_string()),
+        e => HsmError::CryptoError(e.to_string()),
+    }
+}
+
+/// One partition's logged-in session and health
+struct SlotSession {
+    slot: Ulong,
+    pin: String,
+    /// Held for the duration of an operation; PKCS#11 sessions are not
+    /// safe for concurrent use
+    session: Mutex<CK_SESSION_HANDLE>,
+    /// Set on a device error; the slot is skipped until the instant passes
+    unhealthy_until: Mutex<Option<Instant>>,
+    operations: AtomicU64,
+}
+
+impl SlotSession {
+    fn open(ctx: &Ctx, slot: Ulong, pin: &str) -> Result<CK_SESSION_HANDLE, HsmError> {
+        let session = ctx.open_session(slot, pkcs11::types::SessionType::Rw)
+            .map_err(|e| HsmError::InitializationFailed(e.to_string()))?;
+        ctx.login(session, pkcs11::types::UserType::User, pin)
+            .map_err(|_| HsmError::AuthError)?;
+        Ok(session)
+    }
+
+    fn failed_at(&self) -> Option<Instant> {
+        *self.unhealthy_until.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_available(&self, now: Instant) -> bool {
+        self.failed_at().map_or(true, |until| now >= until)
+    }
+
+    fn set_health(&self, healthy: bool) {
+        *self.unhealthy_until.lock().unwrap_or_else(|e| e.into_inner()) =
+            (!healthy).then(|| Instant::now() + SLOT_RECOVERY_BACKOFF);
+    }
 }
 
 pub struct HsmClient {
     ctx: Arc<Ctx>,
-    session: CK_SESSION_HANDLE,
+    slots: Vec<SlotSession>,
+    next_slot: AtomicUsize,
     config: HsmConfig,
+    tenants: TenantScope,
     metrics: HsmMetrics,
+    error_log: Arc<RateLimitedLog>,
+    /// Reports what `error_log` held back once a flood stops
+    error_log_flush: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for HsmClient {
+    fn drop(&mut self) {
+        self.error_log_flush.abort();
+    }
 }
 
 #[derive(Clone)]
@@ -56,6 +245,7 @@ struct HsmMetrics {
     operations: IntCounterVec,
     errors: IntCounterVec,
     latency: prometheus::HistogramVec,
+    batch_size: Histogram,
 }
 
 impl HsmClient {
@@ -68,36 +258,292 @@ impl HsmClient {
                 .map_err(|e| HsmError::InitializationFailed(e.to_string()))?
         );
         
-        let session = ctx.open_session(config.slot, pkcs11::types::SessionType::Rw)
-            .map_err(|e| HsmError::InitializationFailed(e.to_string()))?;
-        
-        ctx.login(session, pkcs11::types::UserType::User, &config.pin)
-            .map_err(|_| HsmError::AuthError)?;
+        if config.slots.is_empty() {
+            return Err(HsmError::ConfigError("no HSM slots configured".into()));
+        }
+
+        // Start with whichever partitions are reachable; the rest are retried
+        let mut slots = Vec::with_capacity(config.slots.len());
+        let mut last_error = None;
+        for (slot, pin) in &config.slots {
+            match SlotSession::open(&ctx, *slot, pin) {
+                Ok(session) => slots.push(SlotSession {
+                    slot: *slot,
+                    pin: pin.clone(),
+                    session: Mutex::new(session),
+                    unhealthy_until: Mutex::new(None),
+                    operations: AtomicU64::new(0),
+                }),
+                Err(e) => {
+                    warn!(slot, error = %e, "HSM slot unavailable at startup");
+                    last_error = Some(e);
+                }
+            }
+        }
+        if slots.is_empty() {
+            return Err(last_error.unwrap_or(HsmError::NoHealthySlot));
+        }
 
         let metrics = HsmMetrics::register();
+        let tenants = TenantScope::new(
+            NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            config.key_label.clone(),
+            config.tenant_credentials.clone(),
+        );
+        let error_log = Arc::new(RateLimitedLog::new(ERROR_LOG_WINDOW));
+        let error_log_flush = error_log.spawn_flush(|event, occurrences| {
+            warn!(event, occurrences, "HSM events repeated since their last log line");
+        });
         
-        Ok(Self { ctx, session, config, metrics })
+        Ok(Self {
+            ctx,
+            slots,
+            next_slot: AtomicUsize::new(0),
+            config,
+            tenants,
+            metrics,
+            error_log,
+            error_log_flush,
+        })
     }
 
-    #[instrument(skip(self))]
-    pub async fn generate_key_pair(&self) -> Result<(CK_OBJECT_HANDLE, CK_OBJECT_HANDLE), HsmError> {
+    /// Run an operation on the next available slot, failing over on device
+    /// errors and missing keys
+    ///
+    /// Slots are taken round-robin. A slot that returns a device error is
+    /// skipped for `SLOT_RECOVERY_BACKOFF`, after which it gets a fresh session
+    /// on its next turn. Unavailable slots are tried only when every
+    /// available one has failed. A slot that lacks the key, because it was
+    /// down when the key was generated, is passed over without being marked
+    /// unhealthy.
+    fn with_slot<T>(
+        &self,
+        operation: &str,
+        f: impl Fn(CK_SESSION_HANDLE) -> Result<T, HsmError>,
+    ) -> Result<T, HsmError> {
+        self.with_slot_indexed(operation, |_, session| f(session))
+    }
+
+    /// `with_slot`, also telling the operation which slot it runs on
+    fn with_slot_indexed<T>(
+        &self,
+        operation: &str,
+        f: impl Fn(usize, CK_SESSION_HANDLE) -> Result<T, HsmError>,
+    ) -> Result<T, HsmError> {
+        let start = self.next_slot.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let (available, unavailable): (Vec<_>, Vec<_>) = (0..self.slots.len())
+            .map(|i| (start + i) % self.slots.len())
+            .partition(|&index| self.slots[index].is_available(now));
+
+        let mut last_error = HsmError::NoHealthySlot;
+        for index in available.into_iter().chain(unavailable) {
+            let slot = &self.slots[index];
+            let session = match self.checkout(slot) {
+                Ok(session) => session,
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+
+            match f(index, *session) {
+                Ok(value) => {
+                    slot.set_health(true);
+                    slot.operations.fetch_add(1, Ordering::Relaxed);
+                    return Ok(value);
+                }
+                Err(HsmError::DeviceError(reason)) => {
+                    warn!(slot = slot.slot, operation, %reason, "HSM slot failed, failing over");
+                    slot.set_health(false);
+                    last_error = HsmError::DeviceError(reason);
+                }
+                Err(HsmError::KeyNotFound(label)) => {
+                    slot.set_health(true);
+                    debug!(slot = slot.slot, operation, %label, "Key missing on HSM slot, trying the next");
+                    last_error = HsmError::KeyNotFound(label);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Generate a key pair on one slot and copy it to every other slot
+    ///
+    /// The private key is wrapped under the replication key on the slot it
+    /// was generated on and unwrapped on the others, and the public key is
+    /// recreated there from its `public_values`, so every partition holds
+    /// the same pair. A slot that cannot take the copy is logged and left
+    /// without the key; operations fail over past it.
+    fn generate_replicated(
+        &self,
+        mechanism: &Mechanism,
+        key_type: pkcs11::types::KeyType,
+        pub_template: &[pkcs11::types::Attribute],
+        priv_template: &[pkcs11::types::Attribute],
+        public_values: &[pkcs11::types::AttributeType],
+    ) -> Result<(CK_OBJECT_HANDLE, CK_OBJECT_HANDLE), HsmError> {
+        let replication_key = self.replication_key()?;
+        let (source, pair, replica) = self.with_slot_indexed("keygen", |index, session| {
+            let (public, private) = self.ctx.generate_key_pair(session, mechanism, pub_template, priv_template)
+                .map_err(pkcs11_error)?;
+            let Some(wrapping_label) = replication_key else {
+                return Ok((index, (public, private), None));
+            };
+            let exported = self.find_key(session, pkcs11::types::ObjectClass::SECRET_KEY, wrapping_label)
+                .and_then(|wrapping| {
+                    self.ctx.wrap_key(session, &Mechanism::AesKeyWrapPad, wrapping, private).map_err(pkcs11_error)
+                })
+                .and_then(|wrapped| {
+                    let values = self.ctx.get_attributes(session, public, public_values).map_err(pkcs11_error)?;
+                    Ok((wrapped, values))
+                });
+            match exported {
+                Ok(replica) => Ok((index, (public, private), Some(replica))),
+                Err(e) => {
+                    // A retry on another slot must not leave this pair behind
+                    let _ = self.ctx.destroy_object(session, public);
+                    let _ = self.ctx.destroy_object(session, private);
+                    Err(e)
+                }
+            }
+        })?;
+
+        let (Some(wrapping_label), Some((wrapped, values))) = (replication_key, replica) else {
+            return Ok(pair);
+        };
+        let mut public_template = pub_template.to_vec();
+        public_template.push(pkcs11::types::Attribute::Class(pkcs11::types::ObjectClass::PUBLIC_KEY));
+        public_template.push(pkcs11::types::Attribute::KeyType(key_type));
+        public_template.extend(values);
+        let mut private_template = priv_template.to_vec();
+        private_template.push(pkcs11::types::Attribute::Class(pkcs11::types::ObjectClass::PRIVATE_KEY));
+        private_template.push(pkcs11::types::Attribute::KeyType(key_type));
+
+        for slot in self.slots.iter().enumerate().filter(|(index, _)| *index != source).map(|(_, slot)| slot) {
+            let copied = self.checkout(slot).and_then(|session| {
+                let unwrapping = self.find_key(*session, pkcs11::types::ObjectClass::SECRET_KEY, wrapping_label)?;
+                let private = self.ctx
+                    .unwrap_key(*session, &Mechanism::AesKeyWrapPad, unwrapping, &wrapped, &private_template)
+                    .map_err(pkcs11_error)?;
+                if let Err(e) = self.ctx.create_object(*session, &public_template) {
+                    let _ = self.ctx.destroy_object(*session, private);
+                    return Err(pkcs11_error(e));
+                }
+                Ok(())
+            });
+            if let Err(e) = copied {
+                warn!(slot = slot.slot, error = %e, "Generated key not copied to HSM slot");
+                if matches!(e, HsmError::DeviceError(_)) {
+                    slot.set_health(false);
+                }
+            }
+        }
+        Ok(pair)
+    }
+
+    /// Label of the key generated keys are wrapped under, if keys have to be
+    /// copied to other slots
+    fn replication_key(&self) -> Result<Option<&str>, HsmError> {
+        match self.config.replication_key.as_deref() {
+            _ if self.config.slots.len() <= 1 => Ok(None),
+            Some(label) => Ok(Some(label)),
+            None => Err(HsmError::ConfigError(
+                "generating keys across several slots needs a replication key".into(),
+            )),
+        }
+    }
+
+    /// Lock a slot's session, replacing it first if the slot has failed
+    fn checkout<'a>(&self, slot: &'a SlotSession) -> Result<std::sync::MutexGuard<'a, CK_SESSION_HANDLE>, HsmError> {
+        let mut session = slot.session.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.failed_at().is_some() {
+            match SlotSession::open(&self.ctx, slot.slot, &slot.pin) {
+                Ok(fresh) => {
+                    let _ = self.ctx.close_session(*session);
+                    *session = fresh;
+                }
+                Err(e) => {
+                    slot.set_health(false);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    /// Prove a caller acts for `tenant_id`, returning the principal its key
+    /// operations are made as
+    pub fn authenticate(&self, tenant_id: &str, credential: &[u8]) -> Result<TenantPrincipal, HsmError> {
+        self.tenants.authenticate(tenant_id, credential)
+            .map_err(|e| self.denied("authenticate", tenant_id, e))
+    }
+
+    /// Resolve one of a principal's key labels, counting refused requests
+    fn authorize(&self, operation: &str, principal: &TenantPrincipal, label: &str) -> Result<String, HsmError> {
+        self.tenants.scoped_label(principal, label)
+            .map_err(|e| self.denied(operation, &principal.tenant_id, e))
+    }
+
+    fn denied(&self, operation: &str, tenant_id: &str, e: HsmError) -> HsmError {
+        self.metrics.errors.with_label_values(&[operation]).inc();
+        telemetry::record_error(&Span::current(), &e);
+        self.error_log.log(&format!("{}:denied:{}", operation, tenant_id), |occurrences| {
+            warn!(tenant_id, operation, occurrences, "HSM key access denied for tenant");
+        });
+        e
+    }
+
+    /// Count a failed operation and mark its span failed, logging it at most
+    /// once per `ERROR_LOG_WINDOW` with the number of failures the line
+    /// stands for
+    fn record_error(&self, operation: &str, e: &HsmError) {
+        self.metrics.errors.with_label_values(&[operation]).inc();
+        telemetry::record_error(&Span::current(), e);
+        self.error_log.log(operation, |occurrences| {
+            error!(operation, occurrences, error = ?e, "HSM operation failed");
+        });
+    }
+
+    #[instrument(skip(self), fields(otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn generate_key_pair(
+        &self,
+        principal: &TenantPrincipal,
+        label: &str,
+    ) -> Result<(CK_OBJECT_HANDLE, CK_OBJECT_HANDLE), HsmError> {
         let start = Instant::now();
+        let scoped = self.authorize("keygen", principal, label)?;
         let mechanism = Mechanism::RsaPkcsKeyPairGen;
+        let replicated = matches!(self.replication_key(), Ok(Some(_)));
         
         let pub_template = vec![
             pkcs11::types::Attribute::Token(true),
             pkcs11::types::Attribute::Verify(true),
-            pkcs11::types::Attribute::Label(self.config.key_label.as_bytes().to_vec()),
+            pkcs11::types::Attribute::Label(scoped.as_bytes().to_vec()),
         ];
 
-        let priv_template = vec![
+        let mut priv_template = vec![
             pkcs11::types::Attribute::Token(true),
             pkcs11::types::Attribute::Sign(true),
             pkcs11::types::Attribute::Sensitive(true),
-            pkcs11::types::Attribute::Label(self.config.key_label.as_bytes().to_vec()),
+            pkcs11::types::Attribute::Label(scoped.as_bytes().to_vec()),
         ];
+        if replicated {
+            // Only ever leaves the HSM wrapped, to be copied to another slot
+            priv_template.push(pkcs11::types::Attribute::Extractable(true));
+            priv_template.push(pkcs11::types::Attribute::WrapWithTrusted(true));
+        }
 
-        match self.ctx.generate_key_pair(self.session, &mechanism, &pub_template, &priv_template) {
+        let generated = self.generate_replicated(
+            &mechanism,
+            pkcs11::types::KeyType::RSA,
+            &pub_template,
+            &priv_template,
+            &[pkcs11::types::AttributeType::Modulus, pkcs11::types::AttributeType::PublicExponent],
+        );
+
+        match generated {
             Ok((public, private)) => {
                 self.metrics.operations.with_label_values(&["keygen"]).inc();
                 self.metrics.latency.with_label_values(&["keygen"])
@@ -105,23 +551,137 @@ impl HsmClient {
                 Ok((public, private))
             }
             Err(e) => {
-                self.metrics.errors.with_label_values(&["keygen"]).inc();
-                error!("Key generation failed: {:?}", e);
-                Err(HsmError::CryptoError(e.to_string()))
+                self.record_error("keygen", &e);
+                Err(e)
             }
         }
     }
 
-    #[instrument(skip(self, data))]
-    pub async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, HsmError> {
+    /// Generate an ECDSA P-256 key pair for a tenant
+    ///
+    /// The private key never leaves the HSM in the clear. With a single slot
+    /// it is not extractable at all; with several it may only be wrapped
+    /// under the trusted replication key, to be copied to the other slots.
+    #[instrument(skip(self), fields(otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn generate_ec_key_pair(
+        &self,
+        principal: &TenantPrincipal,
+        label: &str,
+    ) -> Result<(CK_OBJECT_HANDLE, CK_OBJECT_HANDLE), HsmError> {
         let start = Instant::now();
-        let key = self.find_key()?;
+        let scoped = self.authorize("keygen", principal, label)?;
+        let mechanism = Mechanism::EccKeyPairGen;
+        let replicated = matches!(self.replication_key(), Ok(Some(_)));
+
+        let pub_template = vec![
+            pkcs11::types::Attribute::Token(true),
+            pkcs11::types::Attribute::Verify(true),
+            pkcs11::types::Attribute::EcParams(P256_EC_PARAMS.to_vec()),
+            pkcs11::types::Attribute::Label(scoped.as_bytes().to_vec()),
+        ];
+
+        let priv_template = vec![
+            pkcs11::types::Attribute::Token(true),
+            pkcs11::types::Attribute::Sign(true),
+            pkcs11::types::Attribute::Sensitive(true),
+            pkcs11::types::Attribute::Extractable(replicated),
+            pkcs11::types::Attribute::WrapWithTrusted(true),
+            pkcs11::types::Attribute::Label(scoped.as_bytes().to_vec()),
+        ];
+
+        let generated = self.generate_replicated(
+            &mechanism,
+            pkcs11::types::KeyType::EC,
+            &pub_template,
+            &priv_template,
+            &[pkcs11::types::AttributeType::EcPoint],
+        );
+
+        match generated {
+            Ok(pair) => {
+                self.metrics.operations.with_label_values(&["keygen"]).inc();
+                self.metrics.latency.with_label_values(&["keygen"])
+                    .observe(start.elapsed().as_secs_f64());
+                Ok(pair)
+            }
+            Err(e) => {
+                self.record_error("keygen", &e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Uncompressed SEC1 point of a tenant's P-256 public key
+    #[instrument(skip(self), fields(otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn ec_public_key(&self, principal: &TenantPrincipal, label: &str) -> Result<Vec<u8>, HsmError> {
+        let scoped = self.authorize("public_key", principal, label)?;
+
+        let point = self.with_slot("public_key", |session| {
+            let key = self.find_key(session, pkcs11::types::ObjectClass::PUBLIC_KEY, &scoped)?;
+            let attributes = self.ctx
+                .get_attributes(session, key, &[pkcs11::types::AttributeType::EcPoint])
+                .map_err(pkcs11_error)?;
+            attributes.into_iter()
+                .find_map(|attribute| match attribute {
+                    pkcs11::types::Attribute::EcPoint(point) => Some(point),
+                    _ => None,
+                })
+                .ok_or_else(|| HsmError::CryptoError(format!("{} is not an EC key", scoped)))
+        }).map_err(|e| {
+            self.record_error("public_key", &e);
+            e
+        })?;
+
+        // CKA_EC_POINT is the point wrapped in a DER OCTET STRING
+        nuzon_core::enterprise_result_span!(match point.as_slice() {
+            [0x04, len, rest @ ..] if *len as usize == P256_POINT_LEN && rest.len() == P256_POINT_LEN => {
+                Ok(rest.to_vec())
+            }
+            raw if raw.len() == P256_POINT_LEN => Ok(raw.to_vec()),
+            _ => Err(HsmError::CryptoError(format!("{} is not a P-256 key", scoped))),
+        })
+    }
+
+    /// ECDSA P-256 signature over SHA-256 of `data`, as raw `r || s`
+    #[instrument(skip(self, data), fields(size = data.len(), otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn sign_ecdsa(&self, principal: &TenantPrincipal, label: &str, data: &[u8]) -> Result<Vec<u8>, HsmError> {
+        let start = Instant::now();
+        let scoped = self.authorize("sign", principal, label)?;
+        let mechanism = Mechanism::EcdsaSha256;
+
+        let signed = self.with_slot("sign", |session| {
+            let key = self.find_key(session, pkcs11::types::ObjectClass::PRIVATE_KEY, &scoped)?;
+            self.ctx.sign_init(session, &mechanism, key).map_err(pkcs11_error)?;
+            self.ctx.sign(session, data).map_err(pkcs11_error)
+        });
+
+        match signed {
+            Ok(signature) => {
+                self.metrics.operations.with_label_values(&["sign"]).inc();
+                self.metrics.latency.with_label_values(&["sign"])
+                    .observe(start.elapsed().as_secs_f64());
+                Ok(signature)
+            }
+            Err(e) => {
+                self.record_error("sign", &e);
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self, data), fields(size = data.len(), otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn sign(&self, principal: &TenantPrincipal, label: &str, data: &[u8]) -> Result<Vec<u8>, HsmError> {
+        let start = Instant::now();
+        let scoped = self.authorize("sign", principal, label)?;
         let mechanism = Mechanism::RsaPkcs;
-        
-        self.ctx.sign_init(self.session, &mechanism, key)
-            .map_err(|e| HsmError::CryptoError(e.to_string()))?;
 
-        match self.ctx.sign(self.session, data) {
+        let signed = self.with_slot("sign", |session| {
+            let key = self.find_key(session, pkcs11::types::ObjectClass::PRIVATE_KEY, &scoped)?;
+            self.ctx.sign_init(session, &mechanism, key).map_err(pkcs11_error)?;
+            self.ctx.sign(session, data).map_err(pkcs11_error)
+        });
+
+        match signed {
             Ok(signature) => {
                 self.metrics.operations.with_label_values(&["sign"]).inc();
                 self.metrics.latency.with_label_values(&["sign"])
@@ -129,33 +689,136 @@ impl HsmClient {
                 Ok(signature)
             }
             Err(e) => {
-                self.metrics.errors.with_label_values(&["sign"]).inc();
-                error!("Signing failed: {:?}", e);
-                Err(HsmError::CryptoError(e.to_string()))
+                self.record_error("sign", &e);
+                Err(e)
             }
         }
     }
 
+    /// Sign several messages on one session, returning signatures in order
+    ///
+    /// The key is looked up once and every message is signed on the same
+    /// checked-out session, saving the per-call slot selection and object
+    /// search. PKCS#11 2.40 ends the signing operation with each `C_Sign`,
+    /// so `sign_init` is repeated per message; it is a session-local call.
+    /// Stops at the first failing message, reported as `BatchFailed`.
+    #[instrument(skip(self, messages), fields(batch = messages.len(), otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn sign_batch(
+        &self,
+        principal: &TenantPrincipal,
+        label: &str,
+        messages: &[Vec<u8>],
+    ) -> Result<Vec<Vec<u8>>, HsmError> {
+        let start = Instant::now();
+        let scoped = self.authorize("sign_batch", principal, label)?;
+        let mechanism = Mechanism::RsaPkcs;
+        let failed_index = Cell::new(None);
+
+        let signed = self.with_slot("sign_batch", |session| {
+            // A failover retries the whole batch on the next slot
+            failed_index.set(None);
+            let key = self.find_key(session, pkcs11::types::ObjectClass::PRIVATE_KEY, &scoped)?;
+            let mut signatures = Vec::with_capacity(messages.len());
+            for (index, message) in messages.iter().enumerate() {
+                failed_index.set(Some(index));
+                self.ctx.sign_init(session, &mechanism, key).map_err(pkcs11_error)?;
+                signatures.push(self.ctx.sign(session, message).map_err(pkcs11_error)?);
+            }
+            Ok(signatures)
+        });
+
+        match signed {
+            Ok(signatures) => {
+                self.metrics.operations.with_label_values(&["sign_batch"]).inc();
+                self.metrics.batch_size.observe(messages.len() as f64);
+                self.metrics.latency.with_label_values(&["sign_batch"])
+                    .observe(start.elapsed().as_secs_f64());
+                Ok(signatures)
+            }
+            Err(e) => {
+                self.record_error("sign_batch", &e);
+                Err(match failed_index.get() {
+                    Some(index) => HsmError::BatchFailed { index, source: Box::new(e) },
+                    None => e,
+                })
+            }
+        }
+    }
+
+    /// Verify a signature with a tenant's public key
+    ///
+    /// A signature of the wrong length is `Ok(false)` like any other bad
+    /// signature, so callers cannot tell a malformed signature from a forged
+    /// one; errors are reserved for the key and the device.
+    #[instrument(skip(self, data, signature), fields(size = data.len(), otel.status_code = Empty, otel.status_message = Empty))]
+    pub async fn verify(
+        &self,
+        principal: &TenantPrincipal,
+        label: &str,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, HsmError> {
+        let start = Instant::now();
+        let scoped = self.authorize("verify", principal, label)?;
+        let mechanism = Mechanism::RsaPkcs;
+
+        let verified = self.with_slot("verify", |session| {
+            let key = self.find_key(session, pkcs11::types::ObjectClass::PUBLIC_KEY, &scoped)?;
+            self.ctx.verify_init(session, &mechanism, key).map_err(pkcs11_error)?;
+            match self.ctx.verify(session, data, signature) {
+                Ok(()) => Ok(true),
+                Err(pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_INVALID | CKR_SIGNATURE_LEN_RANGE)) => Ok(false),
+                Err(e) => Err(pkcs11_error(e)),
+            }
+        });
+
+        let result = match verified {
+            Ok(valid) => Ok(valid),
+            Err(e) => {
+                self.record_error("verify", &e);
+                return Err(e);
+            }
+        };
+
+        self.metrics.operations.with_label_values(&["verify"]).inc();
+        self.metrics.latency.with_label_values(&["verify"])
+            .observe(start.elapsed().as_secs_f64());
+        result
+    }
+
     #[instrument(skip(self))]
-    fn find_key(&self) -> Result<CK_OBJECT_HANDLE, HsmError> {
+    fn find_key(
+        &self,
+        session: CK_SESSION_HANDLE,
+        class: pkcs11::types::ObjectClass,
+        label: &str,
+    ) -> Result<CK_OBJECT_HANDLE, HsmError> {
         let template = vec![
-            pkcs11::types::Attribute::Class(pkcs11::types::ObjectClass::PRIVATE_KEY),
-            pkcs11::types::Attribute::Label(self.config.key_label.as_bytes().to_vec()),
+            pkcs11::types::Attribute::Class(class),
+            pkcs11::types::Attribute::Label(label.as_bytes().to_vec()),
         ];
 
-        match self.ctx.find_objects(self.session, &template, 1) {
+        match self.ctx.find_objects(session, &template, 1) {
             Ok(mut objects) => objects.pop()
-                .ok_or_else(|| HsmError::KeyNotFound(self.config.key_label.clone())),
+                .ok_or_else(|| HsmError::KeyNotFound(label.to_string())),
             Err(e) => {
-                error!("Key search failed: {:?}", e);
-                Err(HsmError::CryptoError(e.to_string()))
+                self.error_log.log("find_key", |occurrences| {
+                    error!(occurrences, error = ?e, "Key search failed");
+                });
+                Err(pkcs11_error(e))
             }
         }
     }
 }
 
 impl HsmMetrics {
+    /// Process-wide metrics, registered once and shared by every client
     fn register() -> Self {
+        static METRICS: OnceLock<HsmMetrics> = OnceLock::new();
+        METRICS.get_or_init(Self::build).clone()
+    }
+
+    fn build() -> Self {
         Self {
             operations: register_int_counter_vec!(
                 "hsm_operations_total",
@@ -173,6 +836,11 @@ impl HsmMetrics {
                 &["operation"],
                 vec![0.01, 0.05, 0.1, 0.5, 1.0, 5.0]
             ).unwrap(),
+            batch_size: register_histogram!(
+                "hsm_sign_batch_size",
+                "Messages per HSM batch sign",
+                vec![1.0, 10.0, 50.0, 100.0, 500.0, 1000.0]
+            ).unwrap(),
         }
     }
 }
@@ -182,20 +850,210 @@ mod tests {
     use super::*;
     use tokio::runtime::Runtime;
 
+    fn test_config() -> HsmConfig {
+        HsmConfig::new("/usr/lib/softhsm/libsofthsm2.so", "test-key", vec![(0, "1234".to_string())])
+            .allow_tenant("tenant-a", b"secret-a")
+            .allow_tenant("tenant-b", b"secret-b")
+    }
+
     #[test]
     fn test_hsm_initialization() {
-        let config = HsmConfig {
-            lib_path: "/usr/lib/softhsm/libsofthsm2.so".to_string(),
-            pin: "1234".to_string(),
-            slot: 0,
-            key_label: "test-key".to_string(),
-            operation_timeout: Duration::from_secs(5),
-        };
-
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            let client = HsmClient::new(config).await;
+            let client = HsmClient::new(test_config()).await;
             assert!(client.is_ok());
         });
     }
+
+    #[test]
+    fn test_signing_spreads_and_fails_over_across_slots() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config = HsmConfig {
+                slots: vec![(0, "1234".to_string()), (1, "1234".to_string())],
+                ..test_config()
+            };
+            let client = HsmClient::new(config).await.unwrap();
+            assert_eq!(client.slots.len(), 2);
+            let tenant_a = client.authenticate("tenant-a", b"secret-a").unwrap();
+
+            // SoftHSM tokens do not replicate, so provision the key on each
+            for slot in &client.slots {
+                let session = *slot.session.lock().unwrap();
+                let label = client.authorize("keygen", &tenant_a, "signing").unwrap();
+                let label = pkcs11::types::Attribute::Label(label.into_bytes());
+                client.ctx.generate_key_pair(
+                    session,
+                    &Mechanism::RsaPkcsKeyPairGen,
+                    &[pkcs11::types::Attribute::Token(true), pkcs11::types::Attribute::Verify(true), label.clone()],
+                    &[pkcs11::types::Attribute::Token(true), pkcs11::types::Attribute::Sign(true), label],
+                ).unwrap();
+            }
+
+            let served = |i: usize| client.slots[i].operations.load(Ordering::Relaxed);
+            for _ in 0..10 {
+                client.sign(&tenant_a, "signing", b"payload").await.unwrap();
+            }
+            assert_eq!((served(0), served(1)), (5, 5));
+
+            // Kill slot 1's session out-of-band so its next operation fails
+            client.ctx.close_session(*client.slots[1].session.lock().unwrap()).unwrap();
+            for _ in 0..6 {
+                client.sign(&tenant_a, "signing", b"payload").await.unwrap();
+            }
+            assert_eq!((served(0), served(1)), (11, 5));
+            assert!(!client.slots[1].is_available(Instant::now()));
+        });
+    }
+
+    #[test]
+    fn test_generated_keys_reach_every_slot() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let two_slots = vec![(0, "1234".to_string()), (1, "1234".to_string())];
+            // Only the security officer can mark the wrapping key trusted
+            let provisioning = Ctx::new_and_initialize(
+                Path::new("/usr/lib/softhsm/libsofthsm2.so"),
+                CInitializeArgs::OsThreads,
+            ).unwrap();
+            for (slot, _) in &two_slots {
+                let session = provisioning.open_session(*slot, pkcs11::types::SessionType::Rw).unwrap();
+                provisioning.login(session, pkcs11::types::UserType::SO, "5678").unwrap();
+                provisioning.create_object(session, &[
+                    pkcs11::types::Attribute::Class(pkcs11::types::ObjectClass::SECRET_KEY),
+                    pkcs11::types::Attribute::KeyType(pkcs11::types::KeyType::AES),
+                    pkcs11::types::Attribute::Token(true),
+                    pkcs11::types::Attribute::Trusted(true),
+                    pkcs11::types::Attribute::Wrap(true),
+                    pkcs11::types::Attribute::Unwrap(true),
+                    pkcs11::types::Attribute::Value(vec![7; 32]),
+                    pkcs11::types::Attribute::Label(b"test-key:replication".to_vec()),
+                ]).unwrap();
+                provisioning.close_session(session).unwrap();
+            }
+            drop(provisioning);
+
+            let config = HsmConfig { slots: two_slots.clone(), ..test_config() }
+                .with_replication_key("test-key:replication");
+            let client = HsmClient::new(config).await.unwrap();
+            let tenant_a = client.authenticate("tenant-a", b"secret-a").unwrap();
+            client.generate_key_pair(&tenant_a, "replicated").await.unwrap();
+
+            // Consecutive signs land on different slots, and each verifies on both
+            let signatures = [
+                client.sign(&tenant_a, "replicated", b"payload").await.unwrap(),
+                client.sign(&tenant_a, "replicated", b"payload").await.unwrap(),
+            ];
+            for signature in &signatures {
+                for _ in 0..2 {
+                    assert!(client.verify(&tenant_a, "replicated", b"payload", signature).await.unwrap());
+                }
+            }
+
+            // A key only slot 1 holds is found from whichever slot is tried first
+            let session = *client.slots[1].session.lock().unwrap();
+            let label = pkcs11::types::Attribute::Label(
+                client.authorize("keygen", &tenant_a, "partial").unwrap().into_bytes(),
+            );
+            client.ctx.generate_key_pair(
+                session,
+                &Mechanism::RsaPkcsKeyPairGen,
+                &[pkcs11::types::Attribute::Token(true), pkcs11::types::Attribute::Verify(true), label.clone()],
+                &[pkcs11::types::Attribute::Token(true), pkcs11::types::Attribute::Sign(true), label],
+            ).unwrap();
+            for _ in 0..4 {
+                client.sign(&tenant_a, "partial", b"payload").await.unwrap();
+            }
+            assert!(client.slots.iter().all(|slot| slot.is_available(Instant::now())));
+
+            // Keys generated without a way to copy them would differ per slot
+            let unreplicated = HsmClient::new(HsmConfig { slots: two_slots, ..test_config() }).await.unwrap();
+            let principal = unreplicated.authenticate("tenant-a", b"secret-a").unwrap();
+            assert!(matches!(
+                unreplicated.generate_key_pair(&principal, "unreplicated").await,
+                Err(HsmError::ConfigError(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_sign_batch_signatures_verify_individually() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let client = HsmClient::new(test_config()).await.unwrap();
+            let tenant_a = client.authenticate("tenant-a", b"secret-a").unwrap();
+            client.generate_key_pair(&tenant_a, "batch").await.unwrap();
+
+            let messages: Vec<Vec<u8>> = (0..50).map(|i| format!("leaf-{}", i).into_bytes()).collect();
+            let batches_before = client.metrics.batch_size.get_sample_count();
+            let signatures = client.sign_batch(&tenant_a, "batch", &messages).await.unwrap();
+            assert_eq!(signatures.len(), messages.len());
+            assert_eq!(client.metrics.batch_size.get_sample_count(), batches_before + 1);
+
+            for (message, signature) in messages.iter().zip(&signatures) {
+                assert!(client.verify(&tenant_a, "batch", message, signature).await.unwrap());
+            }
+            // Signatures stay paired with their own message
+            assert!(!client.verify(&tenant_a, "batch", &messages[0], &signatures[1]).await.unwrap());
+            // A truncated signature reads as invalid, not as an error
+            assert!(!client.verify(&tenant_a, "batch", &messages[0], &signatures[0][..8]).await.unwrap());
+
+            assert!(matches!(
+                client.sign_batch(&tenant_a, "missing", &messages).await,
+                Err(HsmError::KeyNotFound(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_tenant_scope_labels() {
+        let config = test_config();
+        let scope = TenantScope::new(7, config.key_label.clone(), config.tenant_credentials.clone());
+        let tenant_a = scope.authenticate("tenant-a", b"secret-a").unwrap();
+        assert_eq!(scope.scoped_label(&tenant_a, "signing").unwrap(), "test-key:tenant:tenant-a:signing");
+
+        assert!(matches!(scope.authenticate("tenant-a", b"secret-b"), Err(HsmError::AuthError)));
+        assert!(matches!(scope.authenticate("tenant-c", b"secret-a"), Err(HsmError::AuthError)));
+        assert!(matches!(scope.authenticate("tenant-a:x", b"secret-a"), Err(HsmError::AuthError)));
+        assert!(matches!(scope.authenticate("", b""), Err(HsmError::AuthError)));
+
+        // Another client's principal is not honoured
+        let other = TenantScope::new(8, config.key_label, config.tenant_credentials);
+        assert!(matches!(other.scoped_label(&tenant_a, "signing"), Err(HsmError::AuthError)));
+    }
+
+    #[test]
+    fn test_cross_tenant_key_access_denied() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let client = HsmClient::new(test_config()).await.unwrap();
+            let tenant_a = client.authenticate("tenant-a", b"secret-a").unwrap();
+            let tenant_b = client.authenticate("tenant-b", b"secret-b").unwrap();
+            client.generate_key_pair(&tenant_a, "signing").await.unwrap();
+
+            let signature = client.sign(&tenant_a, "signing", b"payload").await.unwrap();
+            assert!(client.verify(&tenant_a, "signing", b"payload", &signature).await.unwrap());
+
+            // Tenant B reaches only its own namespace, where no such key exists
+            assert!(matches!(
+                client.sign(&tenant_b, "signing", b"payload").await,
+                Err(HsmError::KeyNotFound(_))
+            ));
+
+            // Naming tenant A without its credential gets nothing
+            let denied_before = client.metrics.errors.with_label_values(&["authenticate"]).get();
+            assert!(matches!(client.authenticate("tenant-a", b"secret-b"), Err(HsmError::AuthError)));
+            assert_eq!(client.metrics.errors.with_label_values(&["authenticate"]).get(), denied_before + 1);
+
+            // Nor does a principal issued by a different client
+            let other = HsmClient::new(test_config()).await.unwrap();
+            let foreign = other.authenticate("tenant-a", b"secret-a").unwrap();
+            let denied_before = client.metrics.errors.with_label_values(&["sign"]).get();
+            assert!(matches!(
+                client.sign(&foreign, "signing", b"payload").await,
+                Err(HsmError::AuthError)
+            ));
+            assert_eq!(client.metrics.errors.with_label_values(&["sign"]).get(), denied_before + 1);
+        });
+    }
 }
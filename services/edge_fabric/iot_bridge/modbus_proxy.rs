@@ -14,6 +14,10 @@ use futures::StreamExt;
 use serde::Deserialize;
 use log::{info, error, warn};
 
+mod register_map;
+
+pub use register_map::{DataType, PointError, PointSpec, RegisterMap, WordOrder};
+
 const MAX_CONNECTIONS: u32 = 1024;
 const DEFAULT_TIMEOUT: u64 = 5000; // milliseconds
 
@@ -24,6 +28,9 @@ struct ModbusProxyConfig {
     tls_key_path: String,
     scada_endpoints: Vec<ScadaEndpoint>,
     access_policies: Vec<AccessPolicy>,
+    /// Named engineering points decoded from holding registers
+    #[serde(default)]
+    register_map: RegisterMap,
     #[serde(default = "default_timeout")]
     request_timeout: u64,
 }
@@ -61,7 +68,9 @@ struct ModbusProxy {
 impl ModbusProxy {
     pub async fn run(config: ModbusProxyConfig) -> Result<()> {
         let security = ScadaSecurity::new(&config).await?;
-        let scada_ctx = Arc::new(ScadaContext::new(config.scada_endpoints));
+        let scada_ctx = Arc::new(
+            ScadaContext::new(config.scada_endpoints).with_register_map(config.register_map)
+        );
         let proxy = Self { security, scada_ctx, runtime: RuntimeManager::new() };
 
         let listener = TcpListener::bind(&config.listen_addr).await?;
@@ -116,6 +125,24 @@ impl ModbusProxy {
     }
 }
 
+impl ScadaContext {
+    /// Read a mapped point and return its scaled engineering value
+    pub async fn read_point(&self, name: &str) -> Result<f64, PointError> {
+        let point = self.register_map.point(name)?;
+        let registers = self
+            .read_holding_registers(point.unit_id, point.address, point.data_type.register_count())
+            .await
+            .map_err(|e| PointError::Read(e.to_string()))?;
+        point.decode(&registers)
+    }
+
+    /// Install the point definitions used by `read_point`
+    pub fn with_register_map(mut self, register_map: RegisterMap) -> Self {
+        self.register_map = register_map;
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +157,7 @@ mod tests {
             tls_key_path: "certs/server-key.pem".into(),
             scada_endpoints: vec![],
             access_policies: vec![],
+            register_map: RegisterMap::default(),
             request_timeout: 1000,
         };
         
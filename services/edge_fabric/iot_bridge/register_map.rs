@@ -0,0 +1,163 @@
+// register_map.rs - Typed Modbus Point Definitions
+use std::collections::HashMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Errors resolving or decoding a mapped point
+#[derive(Debug, Error)]
+pub enum PointError {
+    #[error("Unknown point: {0}")]
+    UnknownPoint(String),
+    #[error("Expected {expected} registers, got {actual}")]
+    RegisterCount { expected: usize, actual: usize },
+    #[error("Register read failed: {0}")]
+    Read(String),
+}
+
+/// Engineering data type carried by one or two holding registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    U16,
+    I16,
+    U32,
+    F32,
+}
+
+impl DataType {
+    /// Number of 16-bit registers the value spans
+    pub fn register_count(&self) -> u16 {
+        match self {
+            Self::U16 | Self::I16 => 1,
+            Self::U32 | Self::F32 => 2,
+        }
+    }
+}
+
+/// Byte layout of a value across registers, named by the order of bytes A..D
+/// of the big-endian encoding as they appear on the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordOrder {
+    /// ABCD
+    #[default]
+    BigEndian,
+    /// DCBA
+    LittleEndian,
+    /// CDAB: big-endian words, low word first
+    WordSwap,
+    /// BADC: little-endian bytes within big-endian word order
+    ByteSwap,
+}
+
+impl WordOrder {
+    /// Reorder wire bytes into big-endian order
+    fn normalize<const N: usize>(&self, mut bytes: [u8; N]) -> [u8; N] {
+        match self {
+            Self::BigEndian => {}
+            Self::LittleEndian => bytes.reverse(),
+            Self::WordSwap => {
+                if N == 4 {
+                    bytes.swap(0, 2);
+                    bytes.swap(1, 3);
+                }
+            }
+            Self::ByteSwap => bytes.chunks_exact_mut(2).for_each(|w| w.swap(0, 1)),
+        }
+        bytes
+    }
+}
+
+/// A named point mapped onto holding registers
+#[derive(Debug, Clone, Deserialize)]
+pub struct PointSpec {
+    pub unit_id: u8,
+    pub address: u16,
+    pub data_type: DataType,
+    #[serde(default)]
+    pub word_order: WordOrder,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl PointSpec {
+    /// Decode raw registers and apply `value * scale + offset`
+    pub fn decode(&self, registers: &[u16]) -> Result<f64, PointError> {
+        let expected = self.data_type.register_count() as usize;
+        if registers.len() != expected {
+            return Err(PointError::RegisterCount { expected, actual: registers.len() });
+        }
+
+        let raw = match self.data_type {
+            DataType::U16 => u16::from_be_bytes(self.word_order.normalize(registers[0].to_be_bytes())) as f64,
+            DataType::I16 => i16::from_be_bytes(self.word_order.normalize(registers[0].to_be_bytes())) as f64,
+            DataType::U32 => u32::from_be_bytes(self.word_order.normalize(wire_bytes(registers))) as f64,
+            DataType::F32 => f32::from_be_bytes(self.word_order.normalize(wire_bytes(registers))) as f64,
+        };
+        Ok(raw * self.scale + self.offset)
+    }
+}
+
+fn wire_bytes(registers: &[u16]) -> [u8; 4] {
+    let [a, b] = registers[0].to_be_bytes();
+    let [c, d] = registers[1].to_be_bytes();
+    [a, b, c, d]
+}
+
+/// Named points exposed by the proxy
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct RegisterMap {
+    points: HashMap<String, PointSpec>,
+}
+
+impl RegisterMap {
+    pub fn point(&self, name: &str) -> Result<&PointSpec, PointError> {
+        self.points.get(name).ok_or_else(|| PointError::UnknownPoint(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(data_type: DataType, word_order: WordOrder) -> PointSpec {
+        PointSpec { unit_id: 1, address: 0, data_type, word_order, scale: 1.0, offset: 0.0 }
+    }
+
+    #[test]
+    fn test_float32_under_each_word_order() {
+        let value = 123.456f32;
+        let [a, b, c, d] = value.to_be_bytes();
+        let word = |hi: u8, lo: u8| u16::from_be_bytes([hi, lo]);
+
+        let layouts = [
+            (WordOrder::BigEndian, [word(a, b), word(c, d)]),
+            (WordOrder::LittleEndian, [word(d, c), word(b, a)]),
+            (WordOrder::WordSwap, [word(c, d), word(a, b)]),
+            (WordOrder::ByteSwap, [word(b, a), word(d, c)]),
+        ];
+        for (order, registers) in layouts {
+            let decoded = spec(DataType::F32, order).decode(&registers).unwrap();
+            assert_eq!(decoded as f32, value, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn test_scaled_integers() {
+        let temperature = PointSpec { scale: 0.1, offset: -40.0, ..spec(DataType::I16, WordOrder::BigEndian) };
+        assert!((temperature.decode(&[655]).unwrap() - 25.5).abs() < 1e-9);
+        assert_eq!(spec(DataType::I16, WordOrder::BigEndian).decode(&[0xFFFE]).unwrap(), -2.0);
+        assert_eq!(spec(DataType::U32, WordOrder::WordSwap).decode(&[0x0001, 0x0002]).unwrap(), 0x0002_0001 as f64);
+        assert!(matches!(
+            spec(DataType::F32, WordOrder::BigEndian).decode(&[1]),
+            Err(PointError::RegisterCount { expected: 2, actual: 1 })
+        ));
+    }
+}